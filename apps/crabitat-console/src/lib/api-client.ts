@@ -1,6 +1,10 @@
 import type {
   Repo,
   CreateRepoRequest,
+  FleetSummary,
+  RiskRules,
+  IssueCompletion,
+  ForgeConfig,
   Issue,
   WorkflowSummary,
   WorkflowDetail,
@@ -11,26 +15,50 @@ import type {
   SystemStatus,
   Mission,
   Task,
+  Run,
   CreateMissionRequest,
+  ReplayMissionRequest,
   StateHistoryEntry,
+  StatusDelta,
+  ConsoleEvent,
+  Benchmark,
+  BenchmarkReport,
+  CreateBenchmarkRequest,
 } from "./types";
 
 const API_BASE = "http://localhost:3001";
 
+/** localStorage key holding the console's API key, once the control-plane requires one */
+export const API_KEY_STORAGE = "crabitat.apiKey";
+
+function apiKey(): string | null {
+  if (typeof localStorage === "undefined") return null;
+  return localStorage.getItem(API_KEY_STORAGE);
+}
+
+/** `fetch` with the stored API key sent as a bearer token */
+async function apiFetch(url: string, init: RequestInit = {}): Promise<Response> {
+  const key = apiKey();
+  if (!key) return fetch(url, init);
+  const headers = new Headers(init.headers);
+  headers.set("Authorization", `Bearer ${key}`);
+  return fetch(url, { ...init, headers });
+}
+
 export async function listRepos(): Promise<Repo[]> {
-  const res = await fetch(`${API_BASE}/v1/repos`);
+  const res = await apiFetch(`${API_BASE}/v1/repos`);
   if (!res.ok) throw new Error(`Failed to list repos: ${res.status}`);
   return res.json();
 }
 
 export async function getRepo(repoId: string): Promise<Repo> {
-  const res = await fetch(`${API_BASE}/v1/repos/${repoId}`);
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}`);
   if (!res.ok) throw new Error(`Failed to get repo: ${res.status}`);
   return res.json();
 }
 
 export async function createRepo(body: CreateRepoRequest): Promise<Repo> {
-  const res = await fetch(`${API_BASE}/v1/repos`, {
+  const res = await apiFetch(`${API_BASE}/v1/repos`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
     body: JSON.stringify(body),
@@ -42,6 +70,88 @@ export async function createRepo(body: CreateRepoRequest): Promise<Repo> {
   return res.json();
 }
 
+export async function setRepoSlackWebhook(
+  repoId: string,
+  webhookUrl: string | null,
+): Promise<void> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/slack`, {
+    method: "PUT",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ webhook_url: webhookUrl }),
+  });
+  if (!res.ok) throw new Error(`Failed to update Slack webhook: ${res.status}`);
+}
+
+export async function setRepoSandbox(repoId: string, sandbox: boolean): Promise<void> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/sandbox`, {
+    method: "PUT",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ sandbox }),
+  });
+  if (!res.ok) throw new Error(`Failed to update sandbox flag: ${res.status}`);
+}
+
+export async function fetchRepoRiskRules(repoId: string): Promise<RiskRules> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/risk-rules`);
+  if (!res.ok) throw new Error(`Failed to fetch risk rules: ${res.status}`);
+  return res.json();
+}
+
+export async function setRepoRiskRules(
+  repoId: string,
+  rules: RiskRules,
+): Promise<void> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/risk-rules`, {
+    method: "PUT",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(rules),
+  });
+  if (!res.ok) throw new Error(`Failed to update risk rules: ${res.status}`);
+}
+
+export async function fetchRepoIssueCompletion(
+  repoId: string,
+): Promise<IssueCompletion> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/issue-completion`);
+  if (!res.ok)
+    throw new Error(`Failed to fetch issue completion: ${res.status}`);
+  return res.json();
+}
+
+export async function setRepoIssueCompletion(
+  repoId: string,
+  completion: IssueCompletion,
+): Promise<void> {
+  const res = await apiFetch(
+    `${API_BASE}/v1/repos/${repoId}/issue-completion`,
+    {
+      method: "PUT",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify(completion),
+    },
+  );
+  if (!res.ok)
+    throw new Error(`Failed to update issue completion: ${res.status}`);
+}
+
+export async function fetchRepoForge(repoId: string): Promise<ForgeConfig> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/forge`);
+  if (!res.ok) throw new Error(`Failed to fetch forge: ${res.status}`);
+  return res.json();
+}
+
+export async function setRepoForge(
+  repoId: string,
+  forge: ForgeConfig,
+): Promise<void> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/forge`, {
+    method: "PUT",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(forge),
+  });
+  if (!res.ok) throw new Error(`Failed to update forge: ${res.status}`);
+}
+
 export interface GhRepoResult {
   nameWithOwner: string;
 }
@@ -49,7 +159,7 @@ export interface GhRepoResult {
 export async function searchGithubRepos(
   query: string,
 ): Promise<GhRepoResult[]> {
-  const res = await fetch(
+  const res = await apiFetch(
     `${API_BASE}/v1/github/repos?q=${encodeURIComponent(query)}`,
   );
   if (!res.ok) throw new Error(`Failed to search repos: ${res.status}`);
@@ -57,23 +167,55 @@ export async function searchGithubRepos(
 }
 
 export async function deleteRepo(repoId: string): Promise<void> {
-  const res = await fetch(`${API_BASE}/v1/repos/${repoId}`, {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}`, {
     method: "DELETE",
   });
   if (!res.ok) throw new Error(`Failed to delete repo: ${res.status}`);
 }
 
-export async function listIssues(repoId: string): Promise<Issue[]> {
-  const res = await fetch(`${API_BASE}/v1/repos/${repoId}/issues`);
+export interface IssueFilter {
+  state?: "open" | "closed" | "all";
+  labels?: string[];
+  /** Cursor from the previous page's `nextPage` */
+  page?: number;
+  perPage?: number;
+}
+
+export async function listIssuePage(
+  repoId: string,
+  filter: IssueFilter = {},
+): Promise<{ issues: Issue[]; nextPage: number | null }> {
+  const params = new URLSearchParams();
+  if (filter.state) params.set("state", filter.state);
+  if (filter.labels?.length) params.set("labels", filter.labels.join(","));
+  if (filter.page !== undefined) params.set("page", String(filter.page));
+  if (filter.perPage !== undefined) params.set("per_page", String(filter.perPage));
+  const query = params.size > 0 ? `?${params}` : "";
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/issues${query}`);
   if (!res.ok) {
     const err = await res.json();
     throw new Error(err.error || `Failed to list issues: ${res.status}`);
   }
+  const next = res.headers.get("X-Next-Page");
+  return { issues: await res.json(), nextPage: next ? Number(next) : null };
+}
+
+/** One issue, open or closed, fetched from the repo's forge into the cache */
+export async function getIssue(repoId: string, number: number): Promise<Issue> {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/issues/${number}`);
+  if (!res.ok) {
+    const err = await res.json();
+    throw new Error(err.error || `Failed to get issue: ${res.status}`);
+  }
   return res.json();
 }
 
+export async function listIssues(repoId: string): Promise<Issue[]> {
+  return (await listIssuePage(repoId)).issues;
+}
+
 export async function refreshIssues(repoId: string): Promise<Issue[]> {
-  const res = await fetch(`${API_BASE}/v1/repos/${repoId}/issues/refresh`, {
+  const res = await apiFetch(`${API_BASE}/v1/repos/${repoId}/issues/refresh`, {
     method: "POST",
   });
   if (!res.ok) {
@@ -84,13 +226,13 @@ export async function refreshIssues(repoId: string): Promise<Issue[]> {
 }
 
 export async function listAllWorkflows(): Promise<WorkflowSummary[]> {
-  const res = await fetch(`${API_BASE}/v1/workflows`);
+  const res = await apiFetch(`${API_BASE}/v1/workflows`);
   if (!res.ok) throw new Error(`Failed to list workflows: ${res.status}`);
   return res.json();
 }
 
 export async function getWorkflow(name: string): Promise<WorkflowDetail> {
-  const res = await fetch(`${API_BASE}/v1/workflows/${name}`);
+  const res = await apiFetch(`${API_BASE}/v1/workflows/${name}`);
   if (!res.ok) throw new Error(`Failed to get workflow: ${res.status}`);
   return res.json();
 }
@@ -99,7 +241,7 @@ export async function createFlavor(
   workflowName: string,
   body: CreateFlavorRequest,
 ): Promise<WorkflowFlavor> {
-  const res = await fetch(
+  const res = await apiFetch(
     `${API_BASE}/v1/workflows/${workflowName}/flavors`,
     {
       method: "POST",
@@ -118,7 +260,7 @@ export async function deleteFlavor(
   workflowName: string,
   flavorId: string,
 ): Promise<void> {
-  const res = await fetch(
+  const res = await apiFetch(
     `${API_BASE}/v1/workflows/${workflowName}/flavors/${flavorId}`,
     { method: "DELETE" },
   );
@@ -130,7 +272,7 @@ export async function updateFlavor(
   flavorId: string,
   body: CreateFlavorRequest,
 ): Promise<void> {
-  const res = await fetch(
+  const res = await apiFetch(
     `${API_BASE}/v1/workflows/${workflowName}/flavors/${flavorId}`,
     {
       method: "PATCH",
@@ -142,7 +284,7 @@ export async function updateFlavor(
 }
 
 export async function getPromptsContent(paths: string[]): Promise<string> {
-  const res = await fetch(`${API_BASE}/v1/prompts/content`, {
+  const res = await apiFetch(`${API_BASE}/v1/prompts/content`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
     body: JSON.stringify({ paths }),
@@ -153,13 +295,13 @@ export async function getPromptsContent(paths: string[]): Promise<string> {
 }
 
 export async function listSettings(): Promise<Setting[]> {
-  const res = await fetch(`${API_BASE}/v1/settings`);
+  const res = await apiFetch(`${API_BASE}/v1/settings`);
   if (!res.ok) throw new Error(`Failed to list settings: ${res.status}`);
   return res.json();
 }
 
 export async function updateSetting(key: string, value: string): Promise<Setting> {
-  const res = await fetch(`${API_BASE}/v1/settings/${key}`, {
+  const res = await apiFetch(`${API_BASE}/v1/settings/${key}`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
     body: JSON.stringify({ value }),
@@ -169,31 +311,31 @@ export async function updateSetting(key: string, value: string): Promise<Setting
 }
 
 export async function getSystemStatus(): Promise<SystemStatus> {
-  const res = await fetch(`${API_BASE}/v1/system/status`);
+  const res = await apiFetch(`${API_BASE}/v1/system/status`);
   if (!res.ok) throw new Error(`Failed to get system status: ${res.status}`);
   return res.json();
 }
 
 export async function listEnvironmentPaths(): Promise<EnvironmentPath[]> {
-  const res = await fetch(`${API_BASE}/v1/system/env-paths`);
+  const res = await apiFetch(`${API_BASE}/v1/system/env-paths`);
   if (!res.ok) throw new Error(`Failed to list environment paths: ${res.status}`);
   return res.json();
 }
 
 export async function listPromptFiles(): Promise<string[]> {
-  const res = await fetch(`${API_BASE}/v1/prompts/files`);
+  const res = await apiFetch(`${API_BASE}/v1/prompts/files`);
   if (!res.ok) throw new Error(`Failed to list prompt files: ${res.status}`);
   return res.json();
 }
 
 export async function listDirs(query: string): Promise<string[]> {
-  const res = await fetch(`${API_BASE}/v1/system/dirs?q=${encodeURIComponent(query)}`);
+  const res = await apiFetch(`${API_BASE}/v1/system/dirs?q=${encodeURIComponent(query)}`);
   if (!res.ok) throw new Error(`Failed to list directories: ${res.status}`);
   return res.json();
 }
 
 export async function createMission(body: CreateMissionRequest): Promise<Mission> {
-  const res = await fetch(`${API_BASE}/v1/missions`, {
+  const res = await apiFetch(`${API_BASE}/v1/missions`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
     body: JSON.stringify(body),
@@ -205,20 +347,126 @@ export async function createMission(body: CreateMissionRequest): Promise<Mission
   return res.json();
 }
 
-export async function listMissions(): Promise<Mission[]> {
-  const res = await fetch(`${API_BASE}/v1/missions`);
+export async function replayMission(missionId: string, body: ReplayMissionRequest = {}): Promise<Mission> {
+  const res = await apiFetch(`${API_BASE}/v1/missions/${missionId}/replay`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(body),
+  });
+  if (!res.ok) {
+    const err = await res.json();
+    throw new Error(err.error || `Failed to replay mission: ${res.status}`);
+  }
+  return res.json();
+}
+
+export interface MissionFilter {
+  status?: string[];
+  repoId?: string;
+  q?: string;
+  archived?: boolean;
+}
+
+export async function listMissionPage(
+  filter: MissionFilter = {},
+  page: { cursor?: string; limit?: number } = {},
+): Promise<{ missions: Mission[]; nextCursor: string | null }> {
+  const params = new URLSearchParams();
+  if (filter.status?.length) params.set("status", filter.status.join(","));
+  if (filter.repoId) params.set("repo_id", filter.repoId);
+  if (filter.q) params.set("q", filter.q);
+  if (filter.archived) params.set("archived", "true");
+  if (page.cursor) params.set("cursor", page.cursor);
+  if (page.limit !== undefined) params.set("limit", String(page.limit));
+  const query = params.size > 0 ? `?${params}` : "";
+  const res = await apiFetch(`${API_BASE}/v1/missions${query}`);
   if (!res.ok) throw new Error(`Failed to list missions: ${res.status}`);
+  return { missions: await res.json(), nextCursor: res.headers.get("X-Next-Cursor") };
+}
+
+export async function listMissions(filter: MissionFilter = {}): Promise<Mission[]> {
+  return (await listMissionPage(filter)).missions;
+}
+
+/** Every matching mission, newest first, fetched a page at a time */
+export async function* iterateMissions(
+  filter: MissionFilter = {},
+  limit = 100,
+): AsyncGenerator<Mission> {
+  let cursor: string | undefined;
+  do {
+    const page = await listMissionPage(filter, { cursor, limit });
+    yield* page.missions;
+    cursor = page.nextCursor ?? undefined;
+  } while (cursor);
+}
+
+export async function createBenchmark(body: CreateBenchmarkRequest): Promise<Benchmark> {
+  const res = await apiFetch(`${API_BASE}/v1/benchmarks`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(body),
+  });
+  if (!res.ok) {
+    const err = await res.json();
+    throw new Error(err.error || `Failed to create benchmark: ${res.status}`);
+  }
+  return res.json();
+}
+
+export async function listBenchmarks(): Promise<Benchmark[]> {
+  const res = await apiFetch(`${API_BASE}/v1/benchmarks`);
+  if (!res.ok) throw new Error(`Failed to list benchmarks: ${res.status}`);
+  return res.json();
+}
+
+export async function fetchBenchmarkReport(benchmarkId: string): Promise<BenchmarkReport> {
+  const res = await apiFetch(`${API_BASE}/v1/benchmarks/${benchmarkId}/report`);
+  if (!res.ok) throw new Error(`Failed to fetch benchmark report: ${res.status}`);
+  return res.json();
+}
+
+export async function fetchFleetSummary(): Promise<FleetSummary> {
+  const res = await apiFetch(`${API_BASE}/v1/missions/summary`);
+  if (!res.ok) throw new Error(`Failed to fetch fleet summary: ${res.status}`);
   return res.json();
 }
 
 export async function getMission(missionId: string): Promise<{ mission: Mission; tasks: Task[]; state_history: StateHistoryEntry[] }> {
-  const res = await fetch(`${API_BASE}/v1/missions/${missionId}`);
+  const res = await apiFetch(`${API_BASE}/v1/missions/${missionId}`);
   if (!res.ok) throw new Error(`Failed to get mission: ${res.status}`);
   return res.json();
 }
 
+export async function getRun(runId: string): Promise<Run> {
+  const res = await apiFetch(`${API_BASE}/v1/runs/${runId}`);
+  if (!res.ok) throw new Error(`Failed to get run: ${res.status}`);
+  return res.json();
+}
+
+export async function listEvents(afterSeq?: number): Promise<ConsoleEvent[]> {
+  const query = afterSeq === undefined ? "" : `?after_seq=${afterSeq}`;
+  const res = await apiFetch(`${API_BASE}/v1/events${query}`);
+  if (!res.ok) throw new Error(`Failed to list events: ${res.status}`);
+  return res.json();
+}
+
+export async function fetchStatusDelta(
+  sinceSeq?: number,
+  filter: { repoId?: string; kinds?: ("missions" | "tasks" | "runs")[] } = {},
+): Promise<StatusDelta> {
+  const params = new URLSearchParams();
+  if (sinceSeq !== undefined) params.set("since_seq", String(sinceSeq));
+  if (filter.repoId) params.set("repo_id", filter.repoId);
+  if (filter.kinds) params.set("kinds", filter.kinds.join(","));
+  const query = params.size > 0 ? `?${params}` : "";
+  const res = await apiFetch(`${API_BASE}/v1/status${query}`);
+  if (!res.ok) throw new Error(`Failed to fetch status: ${res.status}`);
+  return res.json();
+}
+
 export async function retryTask(taskId: string, context?: string): Promise<void> {
-  const res = await fetch(`${API_BASE}/v1/tasks/${taskId}/retry`, {
+  const res = await apiFetch(`${API_BASE}/v1/tasks/${taskId}/retry`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
     body: JSON.stringify(context ? { context } : {}),
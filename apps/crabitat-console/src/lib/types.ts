@@ -5,6 +5,34 @@ export interface Repo {
   local_path: string | null;
   repo_url: string | null;
   created_at: string;
+  slack_notifications: boolean;
+  forge: "github" | "gitlab" | "gitea" | "jira";
+  sandbox: boolean;
+}
+
+export interface RiskRules {
+  paths: string[];
+  keywords: string[];
+}
+
+export interface ForgeConfig {
+  kind: "github" | "gitlab" | "gitea" | "jira";
+  base_url?: string;
+  jira?: JiraConfig;
+}
+
+/** A repo's issue queue in Jira; issue `PLAT-42` is the repo's issue 42 */
+export interface JiraConfig {
+  project: string;
+  /** Filter on the project's unresolved issues, without ORDER BY */
+  jql?: string;
+  /** Transition name or target status applied once the mission completes */
+  done_transition?: string;
+}
+
+export interface IssueCompletion {
+  close: boolean;
+  label?: string;
 }
 
 export interface CreateRepoRequest {
@@ -22,6 +50,7 @@ export interface Issue {
   labels: string[];
   state: string;
   fetched_at: string;
+  stale?: boolean;
 }
 
 export interface WorkflowInfo {
@@ -36,6 +65,11 @@ export interface WorkflowStepFile {
   depends_on?: string[];
   on_fail?: string;
   max_retries?: number;
+  for_each?: string;
+  context_max_bytes?: number;
+  context_max_tokens?: number;
+  context_strategy?: "tail" | "head" | "summarize";
+  report_criteria?: boolean;
 }
 
 export interface WorkflowFlavor {
@@ -50,7 +84,16 @@ export interface WorkflowDetail {
   description: string;
   version?: string;
   steps: WorkflowStepFile[];
+  previews: StepPreview[];
   flavors: WorkflowFlavor[];
+  stored: boolean;
+  source?: string;
+}
+
+export interface StepPreview {
+  step_id: string;
+  step_order: number;
+  prompt?: string;
 }
 
 export interface WorkflowSummary {
@@ -58,6 +101,8 @@ export interface WorkflowSummary {
   description: string;
   step_count: number;
   flavor_count: number;
+  stored: boolean;
+  source?: string;
 }
 
 export interface CreateFlavorRequest {
@@ -82,6 +127,14 @@ export interface SystemStatus {
   gh_auth_status: boolean;
   gh_version: string | null;
   gh_user: string | null;
+  github?: GithubHealth;
+}
+
+export interface GithubHealth {
+  status: "ok" | "degraded" | "down" | "rate_limited";
+  consecutive_failures: number;
+  last_error?: string;
+  retry_after_secs?: number;
 }
 
 export interface Mission {
@@ -96,6 +149,64 @@ export interface Mission {
   branch: string | null;
   created_at: string;
   updated_at?: string;
+  rollup: MissionRollup;
+  prompt?: string;
+  exclusive: boolean;
+  acceptance_criteria: AcceptanceCriterion[];
+  workflow_version?: string;
+  replay_of_mission_id?: string;
+  high_risk: boolean;
+  risk_reasons?: string[];
+  pr_url?: string;
+}
+
+export interface RepoMissionCounts {
+  repo_id: string;
+  repo_owner: string;
+  repo_name: string;
+  missions_by_status: Record<string, number>;
+}
+
+export interface FleetSummary {
+  repos: number;
+  busy_repos: number;
+  missions_by_status: Record<string, number>;
+  tasks_by_status: Record<string, number>;
+  high_risk_missions: number;
+  completed_last_24h: number;
+  failed_last_24h: number;
+  active_crabs: number;
+  by_repo: RepoMissionCounts[];
+}
+
+export interface ReplayMissionRequest {
+  workflow_name?: string;
+  flavor_id?: string;
+  workflow_version?: string;
+}
+
+export interface AcceptanceCriterion {
+  index: number;
+  text: string;
+  done: boolean;
+  result?: "pass" | "fail";
+  note?: string;
+  task_id?: string;
+}
+
+export interface CriterionResult {
+  index: number;
+  passed: boolean;
+  note?: string;
+}
+
+export interface MissionRollup {
+  total_tokens: number;
+  total_duration_ms: number;
+  attempts: number;
+  first_started_at?: string;
+  last_finished_at?: string;
+  wall_clock_ms?: number;
 }
 
 export interface StateHistoryEntry {
@@ -105,16 +216,62 @@ export interface StateHistoryEntry {
   exited_at: string | null;
 }
 
+export interface TaskTiming {
+  task_id: string;
+  step_id: string;
+  step_order: number;
+  status: string;
+  blocked_ms: number;
+  queue_wait_ms: number;
+  setup_ms: number;
+  model_ms: number;
+  approval_wait_ms: number;
+  runs: number;
+  started_at?: string;
+  finished_at?: string;
+  on_critical_path: boolean;
+}
+
+export interface MissionTimings {
+  mission_id: string;
+  wall_ms: number;
+  tasks: TaskTiming[];
+  critical_path: string[];
+  critical_path_ms: number;
+}
+
 export interface Run {
   run_id: string;
   task_id: string;
   status: string;
   logs: string | null;
   summary: string | null;
+  summary_hash?: string;
   duration_ms: number | null;
   tokens_used: number | null;
   started_at: string;
   finished_at: string | null;
+  burrow_path?: string;
+  criteria?: CriterionResult[];
+}
+
+export interface RunLogChunk {
+  seq: number;
+  stream: "stdout" | "stderr";
+  content: string;
+  created_at?: string;
+}
+
+export interface Artifact {
+  artifact_id: string;
+  run_id: string;
+  task_id: string;
+  name: string;
+  kind?: string;
+  content_type: string;
+  size: number;
+  sha256: string;
+  created_at: string;
 }
 
 export interface Task {
@@ -128,9 +285,110 @@ export interface Task {
   runs?: Run[];
 }
 
+export interface ConsoleEvent {
+  seq: number;
+  kind:
+    | "mission_created"
+    | "mission_updated"
+    | "mission_deleted"
+    | "task_created"
+    | "task_updated"
+    | "run_created"
+    | "run_updated";
+  record_id: string;
+  mission_id?: string;
+  status?: string;
+  created_at: string;
+}
+
+export interface StatusDelta {
+  seq: number;
+  as_of_ms: number;
+  missions: Mission[];
+  tasks: Task[];
+  runs: Run[];
+  deleted_mission_ids: string[];
+}
+
 export interface CreateMissionRequest {
   repo_id: string;
   issue_number: number;
   workflow_name: string;
   flavor_id?: string;
+  exclusive?: boolean;
+}
+
+export interface UpdateMissionRequest {
+  workflow_name?: string;
+  flavor_id?: string;
+  prompt?: string;
+}
+
+export type ApiKeyScope = "admin" | "console:read" | "console:write" | "crab:write";
+
+export interface ApiKey {
+  key_id: string;
+  name: string;
+  prefix: string;
+  scopes: ApiKeyScope[];
+  created_at: string;
+  last_used_at?: string;
+  revoked_at?: string;
+}
+
+/** Returned once, on creation; `token` can't be fetched again */
+export interface CreatedApiKey extends ApiKey {
+  token: string;
+}
+
+export interface BenchmarkVariant {
+  workflow_name: string;
+  workflow_version?: string;
+  flavor_id?: string;
+}
+
+export interface CreateBenchmarkRequest {
+  repo_id: string;
+  name?: string;
+  issue_numbers: number[];
+  baseline: BenchmarkVariant;
+  candidate: BenchmarkVariant;
+}
+
+export interface Benchmark {
+  benchmark_id: string;
+  repo_id: string;
+  name: string;
+  baseline: BenchmarkVariant;
+  candidate: BenchmarkVariant;
+  issue_numbers: number[];
+  created_at: string;
+}
+
+export interface BenchmarkOutcome {
+  issue_number: number;
+  variant: "baseline" | "candidate";
+  mission_id: string;
+  status: string;
+  score: number | null;
+  total_tokens: number;
+  duration_ms: number | null;
+}
+
+export interface VariantStats {
+  missions: number;
+  completed: number;
+  failed: number;
+  success_rate: number | null;
+  avg_score: number | null;
+  avg_tokens: number | null;
+  avg_duration_ms: number | null;
+}
+
+export interface BenchmarkReport {
+  benchmark: Benchmark;
+  finished: boolean;
+  baseline: VariantStats;
+  candidate: VariantStats;
+  outcomes: BenchmarkOutcome[];
 }
@@ -0,0 +1,62 @@
+use crate::models::missions::AcceptanceCriterion;
+
+/// Acceptance criteria from the GitHub-style task list (`- [ ] ...`) in an
+/// issue body, numbered from 1 in order of appearance. Items inside fenced
+/// code blocks are examples, not criteria.
+pub fn parse_checklist(body: &str) -> Vec<AcceptanceCriterion> {
+    let mut criteria = Vec::new();
+    let mut in_fence = false;
+    for line in body.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let Some(item) = line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .or_else(|| line.strip_prefix("+ "))
+        else {
+            continue;
+        };
+        let (done, text) = match item.get(..3) {
+            Some("[ ]") => (false, &item[3..]),
+            Some("[x]" | "[X]") => (true, &item[3..]),
+            _ => continue,
+        };
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        criteria.push(AcceptanceCriterion {
+            index: criteria.len() as i64 + 1,
+            text: text.to_string(),
+            done,
+            ..Default::default()
+        });
+    }
+    criteria
+}
+
+/// The `# Acceptance Criteria` prompt section; `report` adds the line format
+/// steps use to give a verdict on each criterion
+pub fn prompt_section(criteria: &[AcceptanceCriterion], report: bool) -> Option<String> {
+    if criteria.is_empty() {
+        return None;
+    }
+    let mut section = String::from("# Acceptance Criteria\n");
+    for c in criteria {
+        let mark = if c.done { "x" } else { " " };
+        section.push_str(&format!("{}. [{}] {}\n", c.index, mark, c.text));
+    }
+    if report {
+        section.push_str(
+            "\nCheck every criterion and end your output with one line per criterion, \
+             `CRITERION <n>: PASS` or `CRITERION <n>: FAIL - <reason>`.\n",
+        );
+    }
+    Some(section.trim_end().to_string())
+}
@@ -0,0 +1,279 @@
+//! API-key authentication for every `/v1` route.
+//!
+//! Auth switches on once the first key exists (`crabitat-control-plane keys
+//! create` or `POST /v1/admin/keys`). From then on each request needs
+//! `Authorization: Bearer <token>` from a live key holding the route's scope;
+//! see [`SCOPES`](crate::models::api_keys::SCOPES). Browsers can't set
+//! headers on `EventSource`/WebSocket connections, so `GET` requests may pass
+//! the token as `?access_token=` instead.
+//!
+//! Separately, a crab that registered (`POST /v1/crabs/register`) must send
+//! its crab token as `X-Crab-Token` on every request naming its worker id, so
+//! nobody else can claim or report work as that crab. Crab requests about a
+//! task that name no worker act for the task's current holder and need its
+//! token just the same; so do requests streaming logs or artifacts for a run,
+//! which always act for the holder of the run's task.
+
+use std::collections::HashMap;
+
+use axum::RequestExt;
+use axum::body::{Body, to_bytes};
+use axum::extract::{MatchedPath, Path, Request, State};
+use axum::http::{Method, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::AppState;
+use crate::db::api_keys as db;
+use crate::db::crabs as crabs_db;
+use crate::db::run_logs as run_logs_db;
+use crate::db::tasks as tasks_db;
+use crate::error::{ErrorCode, api_error};
+
+/// Routes that check their own credentials
+const EXEMPT_ROUTES: &[&str] = &["/v1/github/webhook"];
+
+/// The crab protocol: what `crab:write` keys exist for
+const CRAB_ROUTES: &[(&str, &str)] = &[
+    ("GET", "/v1/tasks/next"),
+    ("POST", "/v1/tasks/claim"),
+    ("POST", "/v1/tasks/{task_id}/heartbeat"),
+    ("POST", "/v1/tasks/{task_id}/status"),
+    ("POST", "/v1/tasks/{task_id}/reject"),
+    ("POST", "/v1/tasks/{task_id}/runs"),
+    ("POST", "/v1/runs/{run_id}/logs"),
+    ("POST", "/v1/runs/{run_id}/artifacts"),
+    ("POST", "/v1/missions/{mission_id}/burrow"),
+    ("DELETE", "/v1/missions/{mission_id}/burrow"),
+    ("POST", "/v1/crabs/register"),
+];
+
+/// Crab routes that name the worker in the JSON body rather than the query
+const BODY_WORKER_ROUTES: &[&str] = &[
+    "/v1/tasks/{task_id}/status",
+    "/v1/tasks/{task_id}/reject",
+    "/v1/tasks/{task_id}/runs",
+    "/v1/missions/{mission_id}/burrow",
+];
+
+/// Crab routes about a run. Logs may arrive before the run is recorded, so
+/// their body names the task.
+const RUN_ROUTES: &[&str] = &["/v1/runs/{run_id}/logs", "/v1/runs/{run_id}/artifacts"];
+
+/// Header a registered crab sends its crab token in
+pub const CRAB_TOKEN_HEADER: &str = "x-crab-token";
+
+/// Bodies read to find the worker id; axum's default `Json` limit
+const MAX_CRAB_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// `POST` routes that only read, taking their query as a body
+const READ_ONLY_POSTS: &[&str] = &["/v1/prompts/content", "/v1/workflows/validate"];
+
+/// The scope a request to `route` needs
+pub fn required_scope(method: &Method, route: &str) -> &'static str {
+    if route.starts_with("/v1/admin") {
+        "admin"
+    } else if is_crab_route(method, route) {
+        "crab:write"
+    } else if matches!(*method, Method::GET | Method::HEAD)
+        || (*method == Method::POST && READ_ONLY_POSTS.contains(&route))
+    {
+        "console:read"
+    } else {
+        "console:write"
+    }
+}
+
+/// Whether `route` is part of the crab protocol
+pub fn is_crab_route(method: &Method, route: &str) -> bool {
+    CRAB_ROUTES
+        .iter()
+        .any(|(m, r)| *m == method.as_str() && *r == route)
+}
+
+/// The bearer token from the `Authorization` header, or `access_token` on a `GET`
+fn bearer_token(req: &Request) -> Option<String> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        return value
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")
+            .map(|t| t.trim().to_string());
+    }
+    if req.method() != Method::GET {
+        return None;
+    }
+    query_param(req, "access_token")
+}
+
+/// `name` from the query string, percent-decoded the way axum's `Query` reads it
+fn query_param(req: &Request, name: &str) -> Option<String> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(req.uri().query()?).ok()?;
+    pairs
+        .into_iter()
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
+fn matched_route(req: &Request) -> String {
+    req.extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string())
+}
+
+/// Middleware: reject requests without a key holding the route's scope.
+/// The authenticated key is left in the request extensions.
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let route = matched_route(&req);
+    if EXEMPT_ROUTES.contains(&route.as_str()) {
+        return next.run(req).await;
+    }
+
+    let token = bearer_token(&req);
+    // Checking for keys only reads; recording a key's use needs the writer
+    let active = db::any_active(&state.read());
+    let key = {
+        match active {
+            Ok(false) => None,
+            Ok(true) => match token
+                .as_deref()
+                .map(|t| db::authenticate(&state.db.lock().unwrap(), t))
+            {
+                Some(Ok(Some(key))) => Some(key),
+                Some(Ok(None)) => {
+                    return api_error(ErrorCode::Unauthorized, "invalid or revoked API key")
+                        .into_response();
+                }
+                None => {
+                    return api_error(ErrorCode::Unauthorized, "missing API key").into_response();
+                }
+                Some(Err(e)) => return api_error(ErrorCode::Internal, e).into_response(),
+            },
+            Err(e) => return api_error(ErrorCode::Internal, e).into_response(),
+        }
+    };
+
+    if let Some(key) = key {
+        let scope = required_scope(req.method(), &route);
+        if !key.allows(scope) {
+            return api_error(
+                ErrorCode::Forbidden,
+                format!("API key {} lacks the {} scope", key.prefix, scope),
+            )
+            .into_response();
+        }
+        req.extensions_mut().insert(key);
+    }
+    next.run(req).await
+}
+
+/// Path parameter `name`, percent-decoded the way the handler's `Path` reads it
+async fn path_param(req: &mut Request, name: &str) -> Option<String> {
+    let Path(mut params) = req
+        .extract_parts::<Path<HashMap<String, String>>>()
+        .await
+        .ok()?;
+    params.remove(name)
+}
+
+/// The task a crab route is about: the one under `/v1/tasks/{task_id}/`, or
+/// for a run route the recorded run's, else the one its streamed logs or the
+/// body name
+async fn crab_route_task(
+    state: &AppState,
+    req: &mut Request,
+    route: &str,
+    body_task_id: Option<String>,
+) -> Result<Option<String>, String> {
+    if !is_crab_route(req.method(), route) {
+        return Ok(None);
+    }
+    if route.starts_with("/v1/tasks/{task_id}/") {
+        return Ok(path_param(req, "task_id").await);
+    }
+    if !RUN_ROUTES.contains(&route) {
+        return Ok(None);
+    }
+    let Some(run_id) = path_param(req, "run_id").await else {
+        return Ok(None);
+    };
+    let conn = state.read();
+    if let Some(run) = tasks_db::get_run(&conn, &run_id)? {
+        return Ok(Some(run.task_id));
+    }
+    Ok(run_logs_db::task_for_run(&conn, &run_id)?.or(body_task_id))
+}
+
+/// Middleware: a request naming a registered worker id, in the `worker_id`
+/// query parameter or (on crab routes) the JSON body, must carry that
+/// worker's crab token. A crab route about a task that names no worker, and
+/// any run route, is taken to come from the task's holder. Worker ids that
+/// never registered pass through.
+pub async fn require_crab_token(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = matched_route(&req);
+    let run_route = RUN_ROUTES.contains(&route.as_str());
+    let reads_body =
+        *req.method() != Method::GET && (run_route || BODY_WORKER_ROUTES.contains(&route.as_str()));
+    let (mut req, body) = if reads_body {
+        let (parts, body) = req.into_parts();
+        let Ok(bytes) = to_bytes(body, MAX_CRAB_BODY_BYTES).await else {
+            return api_error(ErrorCode::InvalidRequest, "request body too large").into_response();
+        };
+        let body = serde_json::from_slice::<serde_json::Value>(&bytes).ok();
+        (Request::from_parts(parts, Body::from(bytes)), body)
+    } else {
+        (req, None)
+    };
+    let body_field = |name: &str| {
+        body.as_ref()
+            .and_then(|body| body.get(name)?.as_str().map(String::from))
+    };
+
+    // Run routes act for the task's holder whatever worker they name
+    let worker_id = match run_route {
+        true => None,
+        false => query_param(&req, "worker_id").or_else(|| body_field("worker_id")),
+    };
+    let worker_id = match worker_id {
+        Some(worker_id) => worker_id,
+        None => {
+            let task_id = crab_route_task(&state, &mut req, &route, body_field("task_id")).await;
+            let holder = task_id.and_then(|task_id| match task_id {
+                Some(task_id) => tasks_db::holder(&state.read(), &task_id),
+                None => Ok(None),
+            });
+            match holder {
+                Ok(Some(holder)) => holder,
+                Ok(None) => return next.run(req).await,
+                Err(e) => return api_error(ErrorCode::Internal, e).into_response(),
+            }
+        }
+    };
+    let token = req
+        .headers()
+        .get(CRAB_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .or_else(|| query_param(&req, "crab_token"));
+    let checked = {
+        let conn = state.db.lock().unwrap();
+        crabs_db::check_token(&conn, &worker_id, token.as_deref())
+    };
+    match checked {
+        Ok(None) | Ok(Some(true)) => next.run(req).await,
+        Ok(Some(false)) => api_error(
+            ErrorCode::Unauthorized,
+            format!("crab token missing or not issued to worker {}", worker_id),
+        )
+        .into_response(),
+        Err(e) => api_error(ErrorCode::Internal, e).into_response(),
+    }
+}
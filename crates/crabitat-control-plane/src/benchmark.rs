@@ -0,0 +1,235 @@
+//! Workflow benchmarks.
+//!
+//! A benchmark re-runs a set of a repo's past issues through two workflow
+//! variants, a baseline and a candidate, as ordinary missions that any crab
+//! picks up. Each mission works on a branch of its own, and none of them
+//! claim, comment on or close the GitHub issue. Their steps still push and
+//! open pull requests, so benchmarks only run against a repo marked as a
+//! sandbox copy (`PUT /v1/repos/{repo_id}/sandbox`). The report sets the variants side by side:
+//! success rate, review score, tokens and duration. Past issues are usually
+//! closed, so they are cached one by one with
+//! `GET /v1/repos/{repo_id}/issues/{number}` first.
+
+use std::fmt;
+
+use rusqlite::Connection;
+
+use crate::db::benchmarks as db;
+use crate::db::issues as issues_db;
+use crate::db::repos as repos_db;
+use crate::db::settings as settings_db;
+use crate::mission_service::{CreateMissionError, create_mission_on_branch};
+use crate::models::benchmarks::{
+    BASELINE, Benchmark, BenchmarkOutcome, BenchmarkReport, BenchmarkVariant, CANDIDATE,
+    CreateBenchmarkRequest, MAX_BENCHMARK_ISSUES, VariantStats,
+};
+use crate::models::missions::CreateMissionRequest;
+use crate::workflow_registry::WorkflowRegistry;
+
+#[derive(Debug)]
+pub enum BenchmarkError {
+    Invalid(String),
+    /// The repo isn't marked as a sandbox, and the missions would open real PRs
+    NotSandbox,
+    /// A variant's workflow isn't at the version it was pinned to:
+    /// (workflow, expected, found)
+    VersionMismatch(String, String, Option<String>),
+    Create(CreateMissionError),
+}
+
+impl fmt::Display for BenchmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(e) => write!(f, "{}", e),
+            Self::VersionMismatch(workflow, expected, found) => write!(
+                f,
+                "workflow {} is at version {}, not {}",
+                workflow,
+                found.as_deref().unwrap_or("(none)"),
+                expected
+            ),
+            Self::NotSandbox => write!(
+                f,
+                "benchmarks open pull requests, so they only run against a repo marked as a sandbox with PUT /v1/repos/{{repo_id}}/sandbox"
+            ),
+            Self::Create(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Check the request, then create one mission per issue and variant
+pub fn start(
+    conn: &mut Connection,
+    req: &CreateBenchmarkRequest,
+) -> Result<Benchmark, BenchmarkError> {
+    let internal = |e: String| BenchmarkError::Create(CreateMissionError::Internal(e));
+
+    let repo = repos_db::get_by_id(conn, &req.repo_id)
+        .map_err(internal)?
+        .filter(|repo| repo.deleted_at.is_none())
+        .ok_or(BenchmarkError::Create(CreateMissionError::RepoNotFound))?;
+    if !repo.sandbox {
+        return Err(BenchmarkError::NotSandbox);
+    }
+
+    let mut issue_numbers: Vec<i64> = Vec::new();
+    for &number in &req.issue_numbers {
+        if !issue_numbers.contains(&number) {
+            issue_numbers.push(number);
+        }
+    }
+    if issue_numbers.is_empty() || issue_numbers.len() > MAX_BENCHMARK_ISSUES {
+        return Err(BenchmarkError::Invalid(format!(
+            "issue_numbers must name between 1 and {} issues",
+            MAX_BENCHMARK_ISSUES
+        )));
+    }
+    if req.baseline == req.candidate {
+        return Err(BenchmarkError::Invalid(
+            "baseline and candidate are the same variant".to_string(),
+        ));
+    }
+    let mut uncached = Vec::new();
+    for &number in &issue_numbers {
+        if issues_db::get_cached_issue(conn, &req.repo_id, number)
+            .map_err(internal)?
+            .is_none()
+        {
+            uncached.push(format!("#{}", number));
+        }
+    }
+    if !uncached.is_empty() {
+        return Err(BenchmarkError::Invalid(format!(
+            "issues not in the repo's cache: {}; fetch each with GET /v1/repos/{{repo_id}}/issues/{{number}}",
+            uncached.join(", ")
+        )));
+    }
+
+    let prompts_root = settings_db::get(conn, "prompts_root")
+        .map_err(|e| internal(e.to_string()))?
+        .ok_or(BenchmarkError::Create(
+            CreateMissionError::PromptsRootNotSet,
+        ))?;
+    let registry = WorkflowRegistry::open(conn, prompts_root).map_err(internal)?;
+    for variant in [&req.baseline, &req.candidate] {
+        let wf = registry
+            .get_workflow(&variant.workflow_name)
+            .ok_or(BenchmarkError::Create(CreateMissionError::WorkflowNotFound))?;
+        if let Some(expected) = &variant.workflow_version
+            && wf.workflow.version.as_ref() != Some(expected)
+        {
+            return Err(BenchmarkError::VersionMismatch(
+                variant.workflow_name.clone(),
+                expected.clone(),
+                wf.workflow.version.clone(),
+            ));
+        }
+    }
+
+    let name = match req.name.as_deref().map(str::trim) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => format!(
+            "{} vs {}",
+            variant_label(&req.baseline),
+            variant_label(&req.candidate)
+        ),
+    };
+    let benchmark = db::insert(
+        conn,
+        &req.repo_id,
+        &name,
+        &req.baseline,
+        &req.candidate,
+        &issue_numbers,
+    )
+    .map_err(internal)?;
+
+    let short_id = &benchmark.benchmark_id[..8];
+    for &number in &issue_numbers {
+        for (variant_name, variant) in [(BASELINE, &req.baseline), (CANDIDATE, &req.candidate)] {
+            let mission = create_mission_on_branch(
+                conn,
+                &CreateMissionRequest {
+                    repo_id: req.repo_id.clone(),
+                    issue_number: number,
+                    workflow_name: variant.workflow_name.clone(),
+                    flavor_id: variant.flavor_id.clone(),
+                    priority: Some(0),
+                    context_from_mission_id: None,
+                    exclusive: false,
+                },
+                &format!("benchmark/{}/{}/issue-{}", short_id, variant_name, number),
+            )
+            .map_err(BenchmarkError::Create)?;
+            db::tag_mission(
+                conn,
+                &mission.mission_id,
+                &benchmark.benchmark_id,
+                variant_name,
+            )
+            .map_err(internal)?;
+        }
+    }
+    Ok(benchmark)
+}
+
+fn variant_label(variant: &BenchmarkVariant) -> String {
+    let mut label = variant.workflow_name.clone();
+    if let Some(version) = &variant.workflow_version {
+        label.push_str(&format!("@{}", version));
+    }
+    if let Some(flavor) = &variant.flavor_id {
+        label.push_str(&format!(" ({})", flavor));
+    }
+    label
+}
+
+/// The benchmark's outcomes so far, summed up per variant
+pub fn report(conn: &Connection, benchmark_id: &str) -> Result<Option<BenchmarkReport>, String> {
+    let Some(benchmark) = db::get(conn, benchmark_id)? else {
+        return Ok(None);
+    };
+    let outcomes = db::outcomes(conn, benchmark_id)?;
+    Ok(Some(BenchmarkReport {
+        finished: outcomes.iter().all(is_finished),
+        baseline: stats(&outcomes, BASELINE),
+        candidate: stats(&outcomes, CANDIDATE),
+        benchmark,
+        outcomes,
+    }))
+}
+
+fn is_finished(outcome: &BenchmarkOutcome) -> bool {
+    matches!(
+        outcome.status.as_str(),
+        "completed" | "failed" | "cancelled"
+    )
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Numbers for one variant. Tokens and duration only count finished
+/// missions, so those still running don't drag the averages down.
+pub fn stats(outcomes: &[BenchmarkOutcome], variant: &str) -> VariantStats {
+    let mine: Vec<&BenchmarkOutcome> = outcomes.iter().filter(|o| o.variant == variant).collect();
+    let finished: Vec<&BenchmarkOutcome> =
+        mine.iter().copied().filter(|o| is_finished(o)).collect();
+    let completed = finished.iter().filter(|o| o.status == "completed").count() as i64;
+    VariantStats {
+        missions: mine.len() as i64,
+        completed,
+        failed: finished.len() as i64 - completed,
+        success_rate: (!finished.is_empty()).then(|| completed as f64 / finished.len() as f64),
+        avg_score: average(mine.iter().filter_map(|o| o.score)),
+        avg_tokens: average(finished.iter().map(|o| o.total_tokens as f64)),
+        avg_duration_ms: average(
+            finished
+                .iter()
+                .filter_map(|o| o.duration_ms)
+                .map(|d| d as f64),
+        ),
+    }
+}
@@ -0,0 +1,132 @@
+//! Fault injection, for hardening retries, the watchdog and GitHub fallbacks
+//! against reproducible failures.
+//!
+//! Nothing is injected until an admin sets rules with `POST /v1/admin/chaos`.
+//! Rules live in memory only, so a restart clears them; set a `seed` to
+//! replay the same sequence of faults. Meant for simulation and integration
+//! environments.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::auth;
+use crate::error::{ErrorCode, api_error};
+use crate::models::chaos::{ChaosRules, ChaosStatus};
+
+/// Faults that are rolled for, each with its own chance in [`ChaosRules`]
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    DropCrabRequest,
+    FailGithub,
+    KillAssignment,
+}
+
+struct Chaos {
+    status: ChaosStatus,
+    /// splitmix64 state
+    rng: u64,
+}
+
+static CHAOS: Mutex<Chaos> = Mutex::new(Chaos {
+    status: ChaosStatus {
+        rules: ChaosRules::NONE,
+        dropped_crab_requests: 0,
+        delayed_writes: 0,
+        failed_github_calls: 0,
+        killed_assignments: 0,
+    },
+    rng: 0,
+});
+
+impl Chaos {
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Replace the rules and reset the counters
+pub fn set(rules: ChaosRules) -> ChaosStatus {
+    let mut chaos = CHAOS.lock().unwrap();
+    chaos.rng = rules
+        .seed
+        .unwrap_or_else(|| uuid::Uuid::new_v4().as_u64_pair().0);
+    chaos.status = ChaosStatus {
+        rules,
+        ..Default::default()
+    };
+    chaos.status.clone()
+}
+
+pub fn status() -> ChaosStatus {
+    CHAOS.lock().unwrap().status.clone()
+}
+
+/// Whether to inject `fault` now; counted when it is
+pub fn roll(fault: Fault) -> bool {
+    let mut chaos = CHAOS.lock().unwrap();
+    let rules = &chaos.status.rules;
+    let chance = match fault {
+        Fault::DropCrabRequest => rules.drop_crab_requests,
+        Fault::FailGithub => rules.fail_github,
+        Fault::KillAssignment => rules.kill_assignments,
+    };
+    if chance <= 0.0 || chaos.next_f64() >= chance {
+        return false;
+    }
+    let status = &mut chaos.status;
+    match fault {
+        Fault::DropCrabRequest => status.dropped_crab_requests += 1,
+        Fault::FailGithub => status.failed_github_calls += 1,
+        Fault::KillAssignment => status.killed_assignments += 1,
+    }
+    true
+}
+
+/// The configured write delay, counted as one delayed write
+fn write_delay() -> Option<Duration> {
+    let mut chaos = CHAOS.lock().unwrap();
+    let ms = chaos.status.rules.write_delay_ms;
+    if ms == 0 {
+        return None;
+    }
+    chaos.status.delayed_writes += 1;
+    Some(Duration::from_millis(ms))
+}
+
+/// Middleware: drop crab requests and stall writes per the rules. The chaos
+/// route itself is spared so faults can always be switched off.
+pub async fn inject(req: Request, next: Next) -> Response {
+    let Some(route) = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+    else {
+        return next.run(req).await;
+    };
+    if route == "/v1/admin/chaos" {
+        return next.run(req).await;
+    }
+
+    if auth::is_crab_route(req.method(), &route) && roll(Fault::DropCrabRequest) {
+        tracing::warn!("chaos: dropped {} {}", req.method(), route);
+        return api_error(ErrorCode::Unavailable, "request dropped by fault injection")
+            .into_response();
+    }
+    if !matches!(*req.method(), Method::GET | Method::HEAD)
+        && let Some(delay) = write_delay()
+    {
+        tokio::time::sleep(delay).await;
+    }
+    next.run(req).await
+}
@@ -0,0 +1,133 @@
+//! Consistency checks.
+//!
+//! Crashes, manual edits and old bugs leave records that contradict each
+//! other. A nightly job (and `POST /v1/admin/consistency`) looks for them:
+//! missions whose status no longer matches their tasks, missions with no
+//! tasks, running tasks held by crabs the control plane never saw, and tasks
+//! or runs whose parent is gone. The first two are derived state and are
+//! repaired on the spot. The rest are reported in the stored report and to
+//! the Slack webhook of the mission's repo, when it has one.
+
+use std::collections::BTreeMap;
+
+use rusqlite::Connection;
+
+use crate::db::consistency as db;
+use crate::db::missions as missions_db;
+use crate::db::repos as repos_db;
+use crate::mission_service::repair_taskless_missions;
+use crate::models::consistency::{ConsistencyReport, Finding};
+use crate::notify::Notification;
+
+/// Look for anomalies, fix the safe ones and store the report
+pub fn check(conn: &Connection) -> Result<ConsistencyReport, String> {
+    let mut findings = Vec::new();
+
+    for (mission_id, status) in db::list_status_drift(conn)? {
+        missions_db::recalculate_mission_status(conn, &mission_id)?;
+        let now = missions_db::get_mission(conn, &mission_id)?.map(|m| m.status);
+        let fixed = now.as_deref().is_some_and(|s| s != status);
+        findings.push(Finding {
+            kind: "mission_status_drift".to_string(),
+            record_id: mission_id.clone(),
+            mission_id: Some(mission_id),
+            detail: format!(
+                "{} with no task left to finish; now {}",
+                status,
+                now.as_deref().unwrap_or("gone")
+            ),
+            fixed,
+        });
+    }
+
+    let taskless = missions_db::list_taskless(conn)?;
+    if !taskless.is_empty() {
+        let repaired = repair_taskless_missions(conn);
+        for mission_id in taskless {
+            let (fixed, detail) = match &repaired {
+                Ok(repaired) if repaired.contains(&mission_id) => {
+                    (true, "had no tasks; expanded from its workflow".to_string())
+                }
+                Ok(_) => (false, "has no tasks and its workflow is gone".to_string()),
+                Err(e) => (false, format!("has no tasks; expanding failed: {}", e)),
+            };
+            findings.push(Finding {
+                kind: "taskless_mission".to_string(),
+                record_id: mission_id.clone(),
+                mission_id: Some(mission_id),
+                detail,
+                fixed,
+            });
+        }
+    }
+
+    for (task_id, mission_id, worker_id) in db::list_unknown_crab_tasks(conn)? {
+        findings.push(Finding {
+            kind: "task_of_unknown_crab".to_string(),
+            record_id: task_id,
+            mission_id: Some(mission_id),
+            detail: format!("running on {}, which never registered or polled", worker_id),
+            fixed: false,
+        });
+    }
+    for (task_id, mission_id) in db::list_orphaned_tasks(conn)? {
+        findings.push(Finding {
+            kind: "orphaned_task".to_string(),
+            record_id: task_id,
+            mission_id: None,
+            detail: format!("mission {} does not exist", mission_id),
+            fixed: false,
+        });
+    }
+    for (run_id, task_id) in db::list_orphaned_runs(conn)? {
+        findings.push(Finding {
+            kind: "orphaned_run".to_string(),
+            record_id: run_id,
+            mission_id: None,
+            detail: format!("task {} does not exist", task_id),
+            fixed: false,
+        });
+    }
+
+    db::insert_report(conn, &findings)
+}
+
+/// Slack messages about `report`'s open findings, one per repo with a
+/// webhook. Findings without a mission only show in the report.
+pub fn notifications(
+    conn: &Connection,
+    report: &ConsistencyReport,
+) -> Result<Vec<Notification>, String> {
+    let mut by_webhook: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for finding in report.open() {
+        let Some(mission_id) = &finding.mission_id else {
+            continue;
+        };
+        let Some(mission) = missions_db::get_mission(conn, mission_id)? else {
+            continue;
+        };
+        let Some(webhook_url) = repos_db::slack_webhook(conn, &mission.repo_id)? else {
+            continue;
+        };
+        by_webhook.entry(webhook_url).or_default().push(format!(
+            "• `{}` {} ({}/{}#{}): {}",
+            finding.kind,
+            finding.record_id,
+            mission.repo_owner,
+            mission.repo_name,
+            mission.issue_number,
+            finding.detail
+        ));
+    }
+    Ok(by_webhook
+        .into_iter()
+        .map(|(webhook_url, lines)| Notification {
+            webhook_url,
+            text: format!(
+                ":mag: Consistency check found {} problem(s) needing a look:\n{}",
+                lines.len(),
+                lines.join("\n")
+            ),
+        })
+        .collect())
+}
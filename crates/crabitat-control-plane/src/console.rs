@@ -0,0 +1,93 @@
+//! Serving the console from the control plane.
+//!
+//! With the `console_url` setting (`serve --console-url`), requests outside
+//! `/v1` are redirected to a console hosted elsewhere. Built with the
+//! `embed-console` feature, the console's built assets are compiled into the
+//! binary and served at `/` instead, so there is nothing else to deploy.
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect, Response};
+
+use crate::AppState;
+use crate::db::settings as settings_db;
+
+/// Settings key for the external console requests are redirected to
+pub const CONSOLE_URL_SETTING: &str = "console_url";
+
+#[cfg(feature = "embed-console")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "../../apps/crabitat-console/dist/client"]
+#[allow_missing = true]
+struct Assets;
+
+/// Fallback for every path no API route matched
+pub async fn fallback(State(state): State<AppState>, req: Request) -> Response {
+    let uri = req.uri();
+    if uri.path().starts_with("/v1/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let console_url = settings_db::get(&state.read(), CONSOLE_URL_SETTING)
+        .ok()
+        .flatten()
+        .filter(|url| !url.trim().is_empty());
+    if let Some(base) = console_url {
+        let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        return Redirect::temporary(&format!("{}{}", base.trim_end_matches('/'), path))
+            .into_response();
+    }
+    embedded(&req)
+}
+
+/// The embedded asset at the request path, or the directory's `index.html`.
+/// Fingerprinted bundles under `_astro/` are cached for good; everything
+/// else is revalidated against its ETag.
+#[cfg(feature = "embed-console")]
+fn embedded(req: &Request) -> Response {
+    use axum::http::header;
+
+    let path = req.uri().path().trim_matches('/');
+    let index = if path.is_empty() {
+        "index.html".to_string()
+    } else {
+        format!("{path}/index.html")
+    };
+    let Some((name, file)) = [path.to_string(), index]
+        .into_iter()
+        .find_map(|name| Assets::get(&name).map(|file| (name, file)))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let etag = format!(
+        "\"{}\"",
+        file.metadata
+            .sha256_hash()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()
+    );
+    let cache_control = if name.starts_with("_astro/") {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let fresh = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|v| v.as_bytes() == etag.as_bytes());
+    let headers = [
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, cache_control.to_string()),
+        (header::CONTENT_TYPE, file.metadata.mimetype().to_string()),
+    ];
+    if fresh {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    (headers, file.data).into_response()
+}
+
+#[cfg(not(feature = "embed-console"))]
+fn embedded(_req: &Request) -> Response {
+    StatusCode::NOT_FOUND.into_response()
+}
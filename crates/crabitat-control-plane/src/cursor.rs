@@ -0,0 +1,44 @@
+//! Opaque keyset cursors for paged list endpoints.
+//!
+//! A cursor names the last row of a page by `(created_at, id)`. The next
+//! page starts strictly after it in `created_at DESC, id DESC` order, so rows
+//! inserted or deleted while a client pages don't shift or repeat entries the
+//! way an offset would. Clients only pass cursors back; the encoding is
+//! URL-safe base64 and not part of the API.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+/// Response header carrying the `cursor` of the next page
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// Position after the last row of a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: String,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn new(created_at: &str, id: &str) -> Self {
+        Self {
+            created_at: created_at.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}\n{}", self.created_at, self.id))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid cursor {:?}", cursor);
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (created_at, id) = text.split_once('\n').ok_or_else(invalid)?;
+        if created_at.is_empty() || id.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self::new(created_at, id))
+    }
+}
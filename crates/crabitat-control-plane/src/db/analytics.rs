@@ -0,0 +1,197 @@
+use rusqlite::{Connection, params};
+
+use crate::models::analytics::{ActivityBucket, CrabDailyStats, ScoreTrend};
+
+/// Aggregate scored (non-debug) runs per group per day. `group_by` must be `worker` or `workflow`.
+pub fn score_trends(conn: &Connection, group_by: &str) -> Result<Vec<ScoreTrend>, String> {
+    let group_expr = match group_by {
+        "worker" => "COALESCE(r.worker_id, 'unknown')",
+        "workflow" => "m.workflow_name",
+        other => return Err(format!("unsupported group_by: {}", other)),
+    };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {group_expr} AS grp, substr(COALESCE(r.finished_at, r.started_at), 1, 10) AS day,
+                    COUNT(*), AVG(r.score), MIN(r.score), MAX(r.score)
+             FROM runs r
+             JOIN tasks t ON r.task_id = t.task_id
+             JOIN missions m ON t.mission_id = m.mission_id
+             WHERE r.score IS NOT NULL AND r.debug = 0
+             GROUP BY grp, day
+             ORDER BY grp ASC, day ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ScoreTrend {
+                group: row.get(0)?,
+                day: row.get(1)?,
+                runs: row.get(2)?,
+                avg_score: row.get(3)?,
+                min_score: row.get(4)?,
+                max_score: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut trends = Vec::new();
+    for trend in rows {
+        trends.push(trend.map_err(|e| e.to_string())?);
+    }
+    Ok(trends)
+}
+
+/// Recompute `crab_daily_stats` for one UTC day (`YYYY-MM-DD`) from its non-debug runs.
+/// Idempotent; returns the number of crab rows written.
+pub fn aggregate_crab_day(conn: &Connection, day: &str) -> Result<usize, String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO crab_daily_stats
+            (worker_id, day, runs, tasks_completed, tasks_failed, busy_ms, tokens_used, updated_at)
+         SELECT worker_id, ?1, COUNT(*),
+                SUM(status = 'completed'), SUM(status = 'failed'),
+                COALESCE(SUM(duration_ms), 0), COALESCE(SUM(tokens_used), 0),
+                strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         FROM runs
+         WHERE worker_id IS NOT NULL AND debug = 0
+           AND substr(COALESCE(finished_at, started_at), 1, 10) = ?1
+         GROUP BY worker_id",
+        [day],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Daily crab stats from `since_day` onwards, per crab, oldest day first
+pub fn crab_stats(conn: &Connection, since_day: &str) -> Result<Vec<CrabDailyStats>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT worker_id, day, runs, tasks_completed, tasks_failed, busy_ms, tokens_used
+             FROM crab_daily_stats
+             WHERE day >= ?1
+             ORDER BY worker_id ASC, day ASC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![since_day], |row| {
+            let busy_ms: i64 = row.get(5)?;
+            Ok(CrabDailyStats {
+                worker_id: row.get(0)?,
+                day: row.get(1)?,
+                runs: row.get(2)?,
+                tasks_completed: row.get(3)?,
+                tasks_failed: row.get(4)?,
+                busy_ms,
+                busy_pct: (busy_ms as f64 / 86_400_000.0 * 100.0).min(100.0),
+                tokens_used: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut stats = Vec::new();
+    for s in rows {
+        stats.push(s.map_err(|e| e.to_string())?);
+    }
+    Ok(stats)
+}
+
+/// Unix seconds of an ISO-8601 timestamp (or `now`); `None` if SQLite can't parse it
+pub fn unix_secs(conn: &Connection, timestamp: &str) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT CAST(strftime('%s', ?1) AS INTEGER)",
+        [timestamp],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// ISO-8601 UTC form of Unix seconds
+pub fn iso_timestamp(conn: &Connection, secs: i64) -> Result<String, String> {
+    conn.query_row(
+        "SELECT strftime('%Y-%m-%dT%H:%M:%SZ', ?1, 'unixepoch')",
+        [secs],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Busy minutes and run starts of `repo_id`'s non-debug runs in `step`-second
+/// buckets from `from` to `to` (Unix seconds; `from` on a bucket boundary).
+/// Runs still going count as busy until now.
+pub fn repo_activity(
+    conn: &Connection,
+    repo_id: &str,
+    from: i64,
+    to: i64,
+    step: i64,
+) -> Result<Vec<ActivityBucket>, String> {
+    let mut buckets = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "WITH RECURSIVE b(start) AS (
+                     SELECT ?1 UNION ALL SELECT start + ?3 FROM b WHERE start + ?3 < ?2)
+                 SELECT strftime('%Y-%m-%dT%H:%M:%SZ', start, 'unixepoch') FROM b",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![from, to, step], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        for start in rows {
+            buckets.push(ActivityBucket {
+                start: start.map_err(|e| e.to_string())?,
+                busy_minutes: 0,
+                runs_started: 0,
+            });
+        }
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT CAST(strftime('%s', r.started_at) AS INTEGER),
+                    CAST(strftime('%s', COALESCE(r.finished_at, 'now')) AS INTEGER)
+             FROM runs r
+             JOIN tasks t ON r.task_id = t.task_id
+             JOIN missions m ON t.mission_id = m.mission_id
+             WHERE m.repo_id = ?1 AND r.debug = 0
+               AND r.started_at < strftime('%Y-%m-%dT%H:%M:%SZ', ?3, 'unixepoch')
+               AND COALESCE(r.finished_at, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+                   >= strftime('%Y-%m-%dT%H:%M:%SZ', ?2, 'unixepoch')",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![repo_id, from, to], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+
+    // Minutes since `from` with a run going, each counted once
+    let mut busy = std::collections::BTreeSet::new();
+    for interval in rows {
+        let (started, finished) = interval.map_err(|e| e.to_string())?;
+        if (from..to).contains(&started) {
+            buckets[((started - from) / step) as usize].runs_started += 1;
+        }
+        let first = (started.max(from) - from) / 60;
+        let last = (finished.min(to) - from + 59) / 60;
+        busy.extend(first..last.max(first + 1));
+    }
+    let minutes_per_bucket = step / 60;
+    for minute in busy {
+        if let Some(bucket) = buckets.get_mut((minute / minutes_per_bucket) as usize) {
+            bucket.busy_minutes += 1;
+        }
+    }
+    Ok(buckets)
+}
+
+/// UTC date `days_ago` days before today, as `YYYY-MM-DD`
+pub fn day_offset(conn: &Connection, days_ago: i64) -> Result<String, String> {
+    conn.query_row(
+        "SELECT date('now', ?1)",
+        [format!("-{} days", days_ago)],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
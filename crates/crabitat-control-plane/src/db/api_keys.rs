@@ -0,0 +1,128 @@
+use rusqlite::{Connection, Row, params};
+use sha2::{Digest, Sha256};
+
+use crate::models::api_keys::ApiKey;
+
+/// Tokens look like `crab_<64 hex chars>`
+const TOKEN_PREFIX: &str = "crab_";
+
+/// Characters of the token kept in the clear, prefix included
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+const API_KEY_COLUMNS: &str = "key_id, name, prefix, scopes, created_at, last_used_at, revoked_at";
+
+fn row_to_api_key(row: &Row) -> rusqlite::Result<ApiKey> {
+    let scopes: String = row.get(3)?;
+    Ok(ApiKey {
+        key_id: row.get(0)?,
+        name: row.get(1)?,
+        prefix: row.get(2)?,
+        scopes: serde_json::from_str(&scopes).unwrap_or_default(),
+        created_at: row.get(4)?,
+        last_used_at: row.get(5)?,
+        revoked_at: row.get(6)?,
+    })
+}
+
+pub(crate) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Create a key and return it with its token, which is only stored hashed
+pub fn insert(
+    conn: &Connection,
+    name: &str,
+    scopes: &[String],
+) -> Result<(ApiKey, String), String> {
+    let key_id = uuid::Uuid::new_v4().to_string();
+    let token = format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let scopes_json = serde_json::to_string(scopes).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO api_keys (key_id, name, prefix, key_hash, scopes) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            key_id,
+            name,
+            &token[..DISPLAY_PREFIX_LEN],
+            hash_token(&token),
+            scopes_json
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let key = get(conn, &key_id)?.ok_or("api key vanished after insert")?;
+    Ok((key, token))
+}
+
+pub fn get(conn: &Connection, key_id: &str) -> Result<Option<ApiKey>, String> {
+    match conn.query_row(
+        &format!("SELECT {API_KEY_COLUMNS} FROM api_keys WHERE key_id = ?1"),
+        [key_id],
+        row_to_api_key,
+    ) {
+        Ok(key) => Ok(Some(key)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Every key, revoked ones included, newest first
+pub fn list(conn: &Connection) -> Result<Vec<ApiKey>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {API_KEY_COLUMNS} FROM api_keys ORDER BY created_at DESC, rowid DESC"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], row_to_api_key)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Revoke a key; returns false if it doesn't exist or was already revoked
+pub fn revoke(conn: &Connection, key_id: &str) -> Result<bool, String> {
+    conn.execute(
+        "UPDATE api_keys SET revoked_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         WHERE key_id = ?1 AND revoked_at IS NULL",
+        [key_id],
+    )
+    .map(|n| n > 0)
+    .map_err(|e| e.to_string())
+}
+
+/// Whether any key is live; until one is, the API stays open
+pub fn any_active(conn: &Connection) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM api_keys WHERE revoked_at IS NULL)",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// The live key holding `token`, noting that it was used
+pub fn authenticate(conn: &Connection, token: &str) -> Result<Option<ApiKey>, String> {
+    let key = match conn.query_row(
+        &format!(
+            "SELECT {API_KEY_COLUMNS} FROM api_keys WHERE key_hash = ?1 AND revoked_at IS NULL"
+        ),
+        [hash_token(token)],
+        row_to_api_key,
+    ) {
+        Ok(key) => key,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    conn.execute(
+        "UPDATE api_keys SET last_used_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE key_id = ?1",
+        [&key.key_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(Some(key))
+}
@@ -0,0 +1,131 @@
+use rusqlite::{Connection, Row, params};
+use sha2::{Digest, Sha256};
+
+use crate::models::artifacts::Artifact;
+
+/// Column list shared by every artifact query; tables must be aliased as `a`
+/// and `r` (the run).
+const ARTIFACT_COLUMNS: &str = "a.artifact_id, a.run_id, r.task_id, a.name, a.kind, a.content_type, a.size, a.sha256, a.created_at";
+
+fn row_to_artifact(row: &Row) -> rusqlite::Result<Artifact> {
+    Ok(Artifact {
+        artifact_id: row.get(0)?,
+        run_id: row.get(1)?,
+        task_id: row.get(2)?,
+        name: row.get(3)?,
+        kind: row.get(4)?,
+        content_type: row.get(5)?,
+        size: row.get(6)?,
+        sha256: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn query_artifacts<P: rusqlite::Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> Result<Vec<Artifact>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    stmt.query_map(params, row_to_artifact)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+pub fn insert(
+    conn: &Connection,
+    run_id: &str,
+    name: &str,
+    kind: Option<&str>,
+    content_type: &str,
+    content: &[u8],
+) -> Result<Artifact, String> {
+    let artifact_id = uuid::Uuid::new_v4().to_string();
+    let sha256: String = Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    conn.execute(
+        "INSERT INTO artifacts (artifact_id, run_id, name, kind, content_type, size, sha256, content)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            artifact_id,
+            run_id,
+            name,
+            kind,
+            content_type,
+            content.len() as i64,
+            sha256,
+            content
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    get(conn, &artifact_id)?.ok_or_else(|| "artifact vanished after insert".to_string())
+}
+
+pub fn get(conn: &Connection, artifact_id: &str) -> Result<Option<Artifact>, String> {
+    Ok(query_artifacts(
+        conn,
+        &format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts a JOIN runs r ON a.run_id = r.run_id
+             WHERE a.artifact_id = ?1"
+        ),
+        [artifact_id],
+    )?
+    .pop())
+}
+
+/// The artifact's metadata and raw content
+pub fn get_content(
+    conn: &Connection,
+    artifact_id: &str,
+) -> Result<Option<(Artifact, Vec<u8>)>, String> {
+    let Some(artifact) = get(conn, artifact_id)? else {
+        return Ok(None);
+    };
+    let content = conn
+        .query_row(
+            "SELECT content FROM artifacts WHERE artifact_id = ?1",
+            [artifact_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(Some((artifact, content)))
+}
+
+pub fn list_for_run(conn: &Connection, run_id: &str) -> Result<Vec<Artifact>, String> {
+    query_artifacts(
+        conn,
+        &format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts a JOIN runs r ON a.run_id = r.run_id
+             WHERE a.run_id = ?1
+             ORDER BY a.created_at ASC"
+        ),
+        [run_id],
+    )
+}
+
+/// Every artifact of every run in the mission, oldest first
+pub fn list_for_mission(conn: &Connection, mission_id: &str) -> Result<Vec<Artifact>, String> {
+    query_artifacts(
+        conn,
+        &format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts a
+             JOIN runs r ON a.run_id = r.run_id
+             JOIN tasks t ON r.task_id = t.task_id
+             WHERE t.mission_id = ?1
+             ORDER BY a.created_at ASC"
+        ),
+        [mission_id],
+    )
+}
+
+pub fn run_exists(conn: &Connection, run_id: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM runs WHERE run_id = ?1)",
+        [run_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,119 @@
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use crate::models::benchmarks::{Benchmark, BenchmarkOutcome, BenchmarkVariant};
+
+const BENCHMARK_COLUMNS: &str =
+    "benchmark_id, repo_id, name, baseline, candidate, issue_numbers, created_at";
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn row_to_benchmark(row: &Row) -> rusqlite::Result<Benchmark> {
+    Ok(Benchmark {
+        benchmark_id: row.get(0)?,
+        repo_id: row.get(1)?,
+        name: row.get(2)?,
+        baseline: json_column(row, 3)?,
+        candidate: json_column(row, 4)?,
+        issue_numbers: json_column(row, 5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+pub fn insert(
+    conn: &Connection,
+    repo_id: &str,
+    name: &str,
+    baseline: &BenchmarkVariant,
+    candidate: &BenchmarkVariant,
+    issue_numbers: &[i64],
+) -> Result<Benchmark, String> {
+    let benchmark_id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO benchmarks (benchmark_id, repo_id, name, baseline, candidate, issue_numbers)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            benchmark_id,
+            repo_id,
+            name,
+            serde_json::to_string(baseline).map_err(|e| e.to_string())?,
+            serde_json::to_string(candidate).map_err(|e| e.to_string())?,
+            serde_json::to_string(issue_numbers).map_err(|e| e.to_string())?,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    get(conn, &benchmark_id)?.ok_or_else(|| "benchmark vanished after insert".to_string())
+}
+
+pub fn get(conn: &Connection, benchmark_id: &str) -> Result<Option<Benchmark>, String> {
+    conn.query_row(
+        &format!("SELECT {BENCHMARK_COLUMNS} FROM benchmarks WHERE benchmark_id = ?1"),
+        [benchmark_id],
+        row_to_benchmark,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Every benchmark, newest first
+pub fn list(conn: &Connection) -> Result<Vec<Benchmark>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {BENCHMARK_COLUMNS} FROM benchmarks ORDER BY created_at DESC, rowid DESC"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], row_to_benchmark)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Mark a mission as one side of a benchmark
+pub fn tag_mission(
+    conn: &Connection,
+    mission_id: &str,
+    benchmark_id: &str,
+    variant: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET benchmark_id = ?1, benchmark_variant = ?2 WHERE mission_id = ?3",
+        params![benchmark_id, variant, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The benchmark's missions by issue, then variant. Scores average the
+/// non-debug runs that were scored.
+pub fn outcomes(conn: &Connection, benchmark_id: &str) -> Result<Vec<BenchmarkOutcome>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.issue_number, m.benchmark_variant, m.mission_id, m.status,
+                    (SELECT AVG(r.score) FROM runs r JOIN tasks t ON r.task_id = t.task_id
+                     WHERE t.mission_id = m.mission_id AND r.score IS NOT NULL AND r.debug = 0),
+                    m.total_tokens,
+                    MAX(0, CAST(ROUND((julianday(m.last_finished_at) - julianday(m.first_started_at)) * 86400000) AS INTEGER))
+             FROM missions m
+             WHERE m.benchmark_id = ?1
+             ORDER BY m.issue_number ASC, m.benchmark_variant ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([benchmark_id], |row| {
+        Ok(BenchmarkOutcome {
+            issue_number: row.get(0)?,
+            variant: row.get(1)?,
+            mission_id: row.get(2)?,
+            status: row.get(3)?,
+            score: row.get(4)?,
+            total_tokens: row.get(5)?,
+            duration_ms: row.get(6)?,
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,90 @@
+use rusqlite::{Connection, params};
+use sha2::{Digest, Sha256};
+
+use crate::models::blobs::Blob;
+
+/// Hex-encoded SHA-256 of the content
+pub fn hash_content(content: &str) -> String {
+    let digest = Sha256::digest(content.as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Split a prompt into chunks at top-level `# ` headings.
+/// Concatenating the chunks yields the original text byte-for-byte.
+pub fn split_chunks(content: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("# ") && offset > start {
+            chunks.push(&content[start..offset]);
+            start = offset;
+        }
+        offset += line.len();
+    }
+    if offset > start {
+        chunks.push(&content[start..offset]);
+    }
+
+    chunks
+}
+
+/// Cut `body` to about `max_bytes`, keeping its head or its tail, and mark
+/// where text was dropped with `[... N bytes truncated ...]`
+pub fn truncate(body: &str, max_bytes: usize, keep_head: bool) -> String {
+    if body.len() <= max_bytes {
+        return body.to_string();
+    }
+    if keep_head {
+        let end = body.floor_char_boundary(max_bytes);
+        format!(
+            "{}\n[... {} bytes truncated ...]",
+            &body[..end],
+            body.len() - end
+        )
+    } else {
+        let start = body.ceil_char_boundary(body.len() - max_bytes);
+        format!("[... {} bytes truncated ...]\n{}", start, &body[start..])
+    }
+}
+
+/// Store a single blob, returning its hash. Existing blobs are left untouched.
+pub fn put(conn: &Connection, content: &str) -> Result<String, String> {
+    let hash = hash_content(content);
+    conn.execute(
+        "INSERT OR IGNORE INTO blobs (hash, content, size) VALUES (?1, ?2, ?3)",
+        params![hash, content, content.len() as i64],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(hash)
+}
+
+/// Chunk the content and store every chunk, returning the ordered chunk hashes
+pub fn put_chunks(conn: &Connection, content: &str) -> Result<Vec<String>, String> {
+    split_chunks(content)
+        .into_iter()
+        .map(|chunk| put(conn, chunk))
+        .collect()
+}
+
+pub fn get(conn: &Connection, hash: &str) -> Result<Option<Blob>, String> {
+    let result = conn.query_row(
+        "SELECT hash, content, size, created_at FROM blobs WHERE hash = ?1",
+        [hash],
+        |row| {
+            Ok(Blob {
+                hash: row.get(0)?,
+                content: row.get(1)?,
+                size: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        },
+    );
+
+    match result {
+        Ok(blob) => Ok(Some(blob)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,143 @@
+use rusqlite::{Connection, Row, params};
+
+use crate::models::burrows::{BurrowLease, BurrowPoolStats};
+
+const LEASE_COLUMNS: &str =
+    "l.mission_id, l.worker_id, l.path, l.acquired_at, l.renewed_at, l.released_at";
+
+fn row_to_lease(row: &Row) -> rusqlite::Result<BurrowLease> {
+    Ok(BurrowLease {
+        mission_id: row.get(0)?,
+        worker_id: row.get(1)?,
+        path: row.get(2)?,
+        acquired_at: row.get(3)?,
+        renewed_at: row.get(4)?,
+        released_at: row.get(5)?,
+    })
+}
+
+fn query_leases<P: rusqlite::Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> Result<Vec<BurrowLease>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params, row_to_lease)
+        .map_err(|e| e.to_string())?;
+
+    let mut leases = Vec::new();
+    for lease in rows {
+        leases.push(lease.map_err(|e| e.to_string())?);
+    }
+    Ok(leases)
+}
+
+/// Take or renew a worker's lease on a mission burrow
+pub fn acquire(
+    conn: &Connection,
+    mission_id: &str,
+    worker_id: &str,
+    path: &str,
+) -> Result<BurrowLease, String> {
+    conn.execute(
+        "INSERT INTO burrow_leases (mission_id, worker_id, path) VALUES (?1, ?2, ?3)
+         ON CONFLICT(mission_id, worker_id) DO UPDATE SET
+            path = excluded.path,
+            renewed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+            released_at = NULL",
+        params![mission_id, worker_id, path],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get_active(conn, mission_id)?.ok_or_else(|| "lease not found after acquire".to_string())
+}
+
+/// The most recently renewed unreleased lease on a mission
+pub fn get_active(conn: &Connection, mission_id: &str) -> Result<Option<BurrowLease>, String> {
+    Ok(query_leases(
+        conn,
+        &format!(
+            "SELECT {LEASE_COLUMNS} FROM burrow_leases l
+             WHERE l.mission_id = ?1 AND l.released_at IS NULL
+             ORDER BY l.renewed_at DESC LIMIT 1"
+        ),
+        [mission_id],
+    )?
+    .pop())
+}
+
+pub fn release(conn: &Connection, mission_id: &str, worker_id: &str) -> Result<bool, String> {
+    let affected = conn
+        .execute(
+            "UPDATE burrow_leases SET released_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+             WHERE mission_id = ?1 AND worker_id = ?2 AND released_at IS NULL",
+            params![mission_id, worker_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// Unreleased leases a worker should clean up: the mission has finished or was
+/// archived, or another worker has since taken over the mission's burrow.
+pub fn list_stale(conn: &Connection, worker_id: &str) -> Result<Vec<BurrowLease>, String> {
+    query_leases(
+        conn,
+        &format!(
+            "SELECT {LEASE_COLUMNS} FROM burrow_leases l
+             JOIN missions m ON l.mission_id = m.mission_id
+             WHERE l.worker_id = ?1 AND l.released_at IS NULL
+               AND (m.status IN ('completed', 'failed') OR m.archived_at IS NOT NULL
+                    OR EXISTS (SELECT 1 FROM burrow_leases o
+                               WHERE o.mission_id = l.mission_id AND o.worker_id != l.worker_id
+                                 AND o.released_at IS NULL AND o.renewed_at > l.renewed_at))
+             ORDER BY l.renewed_at ASC"
+        ),
+        [worker_id],
+    )
+}
+
+/// Store the warm-pool counters a crab reported, replacing its previous report
+pub fn record_pool_stats(
+    conn: &Connection,
+    worker_id: &str,
+    ready: i64,
+    hits: i64,
+    misses: i64,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO burrow_pools (worker_id, ready, hits, misses) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(worker_id) DO UPDATE SET
+            ready = excluded.ready, hits = excluded.hits, misses = excluded.misses,
+            reported_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+        params![worker_id, ready, hits, misses],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn list_pool_stats(conn: &Connection) -> Result<Vec<BurrowPoolStats>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT worker_id, ready, hits, misses, reported_at
+             FROM burrow_pools ORDER BY reported_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(BurrowPoolStats {
+                worker_id: row.get(0)?,
+                ready: row.get(1)?,
+                hits: row.get(2)?,
+                misses: row.get(3)?,
+                reported_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut pools = Vec::new();
+    for pool in rows {
+        pools.push(pool.map_err(|e| e.to_string())?);
+    }
+    Ok(pools)
+}
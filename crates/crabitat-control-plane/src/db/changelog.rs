@@ -0,0 +1,69 @@
+use rusqlite::{Connection, params};
+
+use crate::github::pr_number;
+use crate::models::changelog::{Changelog, ChangelogEntry};
+
+/// Completed missions of a repo, oldest first, with the final step's run
+/// summary and the mission's pull request.
+pub fn build(conn: &Connection, repo_id: &str, since: Option<&str>) -> Result<Changelog, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id, m.issue_number, COALESCE(i.title, ''), m.workflow_name,
+                    COALESCE(m.updated_at, m.created_at) AS completed_at,
+                    (SELECT r.summary FROM runs r
+                       JOIN tasks t ON r.task_id = t.task_id
+                      WHERE t.mission_id = m.mission_id AND r.debug = 0 AND r.summary IS NOT NULL
+                      ORDER BY t.step_order DESC, r.started_at DESC LIMIT 1),
+                    m.pr_url
+             FROM missions m
+             LEFT JOIN github_issues_cache i
+                    ON i.repo_id = m.repo_id AND i.number = m.issue_number
+             WHERE m.repo_id = ?1 AND m.status = 'completed'
+               AND (?2 IS NULL OR COALESCE(m.updated_at, m.created_at) > ?2)
+             ORDER BY completed_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![repo_id, since], |row| {
+            let pr_url: Option<String> = row.get(6)?;
+            Ok(ChangelogEntry {
+                mission_id: row.get(0)?,
+                issue_number: row.get(1)?,
+                issue_title: row.get(2)?,
+                workflow_name: row.get(3)?,
+                completed_at: row.get(4)?,
+                summary: row.get(5)?,
+                pr_number: pr_url.as_deref().and_then(pr_number),
+                pr_url,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    for entry in rows {
+        entries.push(entry.map_err(|e| e.to_string())?);
+    }
+
+    Ok(Changelog {
+        repo_id: repo_id.to_string(),
+        since: since.map(String::from),
+        entries,
+    })
+}
+
+/// Completion time of the most recent completed mission of `workflow_name`,
+/// i.e. the previous release when drafting release notes.
+pub fn last_completed_at(
+    conn: &Connection,
+    repo_id: &str,
+    workflow_name: &str,
+) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT MAX(COALESCE(updated_at, created_at)) FROM missions
+         WHERE repo_id = ?1 AND workflow_name = ?2 AND status = 'completed'",
+        params![repo_id, workflow_name],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,116 @@
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::models::consistency::{ConsistencyReport, Finding};
+
+/// Reports kept; older ones are deleted as new ones are stored
+const REPORTS_KEPT: i64 = 30;
+
+/// Task statuses a mission still has work in
+const INCOMPLETE_TASK: &str = "t.status NOT IN ('completed', 'failed', 'skipped', 'cancelled')";
+
+fn query_rows<T>(
+    conn: &Connection,
+    sql: &str,
+    map: impl FnMut(&rusqlite::Row) -> rusqlite::Result<T>,
+) -> Result<Vec<T>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    stmt.query_map([], map)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Pending or running missions with tasks, none of them still to finish, and
+/// their status
+pub fn list_status_drift(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    query_rows(
+        conn,
+        &format!(
+            "SELECT m.mission_id, m.status FROM missions m
+             WHERE m.status IN ('pending', 'running') AND m.archived_at IS NULL
+               AND EXISTS (SELECT 1 FROM tasks t WHERE t.mission_id = m.mission_id)
+               AND NOT EXISTS (SELECT 1 FROM tasks t WHERE t.mission_id = m.mission_id AND {INCOMPLETE_TASK})
+             ORDER BY m.created_at"
+        ),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Running tasks held by a crab that never registered or polled: task,
+/// mission and worker ids
+pub fn list_unknown_crab_tasks(conn: &Connection) -> Result<Vec<(String, String, String)>, String> {
+    query_rows(
+        conn,
+        "SELECT t.task_id, t.mission_id, t.heartbeat_worker_id FROM tasks t
+         WHERE t.status = 'running' AND t.heartbeat_worker_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM crab_executors c WHERE c.worker_id = t.heartbeat_worker_id)
+           AND NOT EXISTS (SELECT 1 FROM crab_registrations c WHERE c.worker_id = t.heartbeat_worker_id)
+         ORDER BY t.created_at",
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+}
+
+/// Tasks whose mission is gone, with the mission id they point at
+pub fn list_orphaned_tasks(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    query_rows(
+        conn,
+        "SELECT t.task_id, t.mission_id FROM tasks t
+         WHERE NOT EXISTS (SELECT 1 FROM missions m WHERE m.mission_id = t.mission_id)
+         ORDER BY t.created_at",
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Runs whose task is gone, with the task id they point at
+pub fn list_orphaned_runs(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    query_rows(
+        conn,
+        "SELECT r.run_id, r.task_id FROM runs r
+         WHERE NOT EXISTS (SELECT 1 FROM tasks t WHERE t.task_id = r.task_id)
+         ORDER BY r.started_at",
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+fn row_to_report(row: &rusqlite::Row) -> rusqlite::Result<ConsistencyReport> {
+    Ok(ConsistencyReport {
+        report_id: row.get(0)?,
+        checked_at: row.get(1)?,
+        findings: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+    })
+}
+
+/// Store a report, dropping the oldest past `REPORTS_KEPT`
+pub fn insert_report(conn: &Connection, findings: &[Finding]) -> Result<ConsistencyReport, String> {
+    let json = serde_json::to_string(findings).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO consistency_reports (findings) VALUES (?1)",
+        params![json],
+    )
+    .map_err(|e| e.to_string())?;
+    let report = conn
+        .query_row(
+            "SELECT report_id, checked_at, findings FROM consistency_reports WHERE report_id = ?1",
+            params![conn.last_insert_rowid()],
+            row_to_report,
+        )
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM consistency_reports WHERE report_id <= ?1",
+        params![report.report_id - REPORTS_KEPT],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// The most recent report, if a check ever ran
+pub fn latest_report(conn: &Connection) -> Result<Option<ConsistencyReport>, String> {
+    conn.query_row(
+        "SELECT report_id, checked_at, findings FROM consistency_reports
+         ORDER BY report_id DESC LIMIT 1",
+        [],
+        row_to_report,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,132 @@
+use rusqlite::{Connection, params};
+
+use crate::db::api_keys::hash_token;
+use crate::db::settings as settings_db;
+use crate::db::tasks as tasks_db;
+use crate::models::crabs::{CrabExecutors, CrabRegistration, Executor};
+
+/// Crabs that polled within this many seconds count as available
+pub const ACTIVE_CRAB_SECS: i64 = 3600;
+
+/// Settings key for how long a running task may go without output before
+/// its crab counts as stuck
+pub const STUCK_TASK_SECS_SETTING: &str = "stuck_task_secs";
+const DEFAULT_STUCK_TASK_SECS: i64 = 900;
+
+pub fn stuck_task_secs(conn: &Connection) -> i64 {
+    settings_db::get(conn, STUCK_TASK_SECS_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_STUCK_TASK_SECS)
+}
+
+/// Record the executors a crab reported when polling, replacing its previous inventory
+pub fn record_executors(
+    conn: &Connection,
+    worker_id: &str,
+    executors: &[Executor],
+) -> Result<(), String> {
+    let executors_json = serde_json::to_string(executors).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO crab_executors (worker_id, executors) VALUES (?1, ?2)
+         ON CONFLICT(worker_id) DO UPDATE SET
+            executors = excluded.executors,
+            seen_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')",
+        params![worker_id, executors_json],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Record the role a crab polled with; `None` clears it
+pub fn record_role(conn: &Connection, worker_id: &str, role: Option<&str>) -> Result<(), String> {
+    conn.execute(
+        "UPDATE crab_executors SET role = ?2 WHERE worker_id = ?1",
+        params![worker_id, role],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Inventories of crabs that polled within the last `since_secs`, most recent first
+pub fn list_executors(conn: &Connection, since_secs: i64) -> Result<Vec<CrabExecutors>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT worker_id, executors, seen_at, role FROM crab_executors
+             WHERE seen_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?1)
+             ORDER BY seen_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([format!("-{} seconds", since_secs)], |row| {
+            let executors_json: String = row.get(1)?;
+            Ok(CrabExecutors {
+                worker_id: row.get(0)?,
+                executors: serde_json::from_str(&executors_json).unwrap_or_default(),
+                seen_at: row.get(2)?,
+                role: row.get(3)?,
+                state: "idle".to_string(),
+                task_id: None,
+                last_output_at: None,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut crabs = Vec::new();
+    for crab in rows {
+        crabs.push(crab.map_err(|e| e.to_string())?);
+    }
+
+    let stuck_secs = stuck_task_secs(conn);
+    for running in tasks_db::list_running_output(conn)? {
+        let Some(crab) = crabs
+            .iter_mut()
+            .find(|c| Some(&c.worker_id) == running.worker_id.as_ref())
+        else {
+            continue;
+        };
+        crab.state = if running.silent_secs >= stuck_secs {
+            "stuck"
+        } else {
+            "busy"
+        }
+        .to_string();
+        crab.task_id = Some(running.task.task_id);
+        crab.last_output_at = Some(running.last_output_at);
+    }
+    Ok(crabs)
+}
+
+/// Give a new crab its worker id and the token it must present with it
+pub fn register(conn: &Connection) -> Result<CrabRegistration, String> {
+    let worker_id = uuid::Uuid::new_v4().to_string();
+    let token = format!(
+        "crabt_{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    conn.execute(
+        "INSERT INTO crab_registrations (worker_id, token_hash) VALUES (?1, ?2)",
+        params![worker_id, hash_token(&token)],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(CrabRegistration { worker_id, token })
+}
+
+/// Whether `token` belongs to `worker_id`; `None` when the worker never registered
+pub fn check_token(
+    conn: &Connection,
+    worker_id: &str,
+    token: Option<&str>,
+) -> Result<Option<bool>, String> {
+    match conn.query_row(
+        "SELECT token_hash FROM crab_registrations WHERE worker_id = ?1",
+        [worker_id],
+        |row| row.get::<_, String>(0),
+    ) {
+        Ok(hash) => Ok(Some(token.is_some_and(|t| hash_token(t) == hash))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,65 @@
+use rusqlite::{Connection, Row, params};
+
+use crate::models::events::ConsoleEvent;
+
+const EVENT_COLUMNS: &str = "seq, kind, record_id, mission_id, status, created_at";
+
+fn row_to_event(row: &Row) -> rusqlite::Result<ConsoleEvent> {
+    Ok(ConsoleEvent {
+        seq: row.get(0)?,
+        kind: row.get(1)?,
+        record_id: row.get(2)?,
+        mission_id: row.get(3)?,
+        status: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+/// Up to `limit` events after `after_seq`, oldest first
+pub fn list_after(
+    conn: &Connection,
+    after_seq: i64,
+    limit: i64,
+) -> Result<Vec<ConsoleEvent>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {EVENT_COLUMNS} FROM events WHERE seq > ?1 ORDER BY seq LIMIT ?2"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map(params![after_seq, limit], row_to_event)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// The newest event's seq; 0 before any event
+pub fn latest_seq(conn: &Connection) -> Result<i64, String> {
+    conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM events", [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Whether events after `after_seq` were pruned, so replaying from it would
+/// silently skip some
+pub fn gap_after(conn: &Connection, after_seq: i64) -> Result<bool, String> {
+    // With every event pruned, the next seq to be issued stands in for the oldest
+    conn.query_row(
+        "SELECT COALESCE(
+             (SELECT MIN(seq) FROM events),
+             (SELECT seq + 1 FROM sqlite_sequence WHERE name = 'events'),
+             1) > ?1 + 1",
+        [after_seq],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Drop events older than `days`; returns how many went
+pub fn prune(conn: &Connection, days: i64) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM events WHERE created_at < strftime('%Y-%m-%dT%H:%M:%fZ', 'now', ?1)",
+        [format!("-{days} days")],
+    )
+    .map_err(|e| e.to_string())
+}
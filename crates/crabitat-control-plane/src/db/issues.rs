@@ -1,4 +1,4 @@
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, Row, params};
 
 use crate::models::Issue;
 
@@ -28,35 +28,59 @@ pub fn upsert_issues(conn: &Connection, repo_id: &str, issues: &[Issue]) -> Resu
 }
 
 pub fn list_by_repo(conn: &Connection, repo_id: &str) -> Result<Vec<Issue>, String> {
+    list_filtered(conn, repo_id, None, &[], None, None)
+}
+
+/// Cached issues of `repo_id`, newest first: those in `state` (`OPEN` or
+/// `CLOSED`) carrying every one of `labels`, numbered below `before`, at
+/// most `limit` of them
+pub fn list_filtered(
+    conn: &Connection,
+    repo_id: &str,
+    state: Option<&str>,
+    labels: &[String],
+    before: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<Issue>, String> {
+    let labels: Vec<String> = labels.iter().map(|l| l.to_lowercase()).collect();
+    let labels_json = serde_json::to_string(&labels).map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
             "SELECT repo_id, number, title, body, labels, state, fetched_at
              FROM github_issues_cache
              WHERE repo_id = ?1
-             ORDER BY number DESC",
+               AND (?2 IS NULL OR UPPER(state) = ?2)
+               AND (?3 IS NULL OR number < ?3)
+               AND NOT EXISTS (
+                   SELECT 1 FROM json_each(?4) wanted
+                   WHERE wanted.value NOT IN (
+                       SELECT LOWER(has.value) FROM json_each(github_issues_cache.labels) has))
+             ORDER BY number DESC
+             LIMIT ?5",
         )
         .map_err(|e| e.to_string())?;
 
-    let issues = stmt
-        .query_map(params![repo_id], |row| {
-            let labels_str: String = row.get(4)?;
-            let labels: Vec<String> = serde_json::from_str(&labels_str).unwrap_or_default();
-
-            Ok(Issue {
-                repo_id: row.get(0)?,
-                number: row.get(1)?,
-                title: row.get(2)?,
-                body: row.get(3)?,
-                labels,
-                state: row.get(5)?,
-                fetched_at: row.get(6)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+    stmt.query_map(
+        params![repo_id, state, before, labels_json, limit.unwrap_or(-1)],
+        row_to_issue,
+    )
+    .map_err(|e| e.to_string())?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| e.to_string())
+}
 
-    Ok(issues)
+fn row_to_issue(row: &Row) -> rusqlite::Result<Issue> {
+    let labels_str: String = row.get(4)?;
+    Ok(Issue {
+        repo_id: row.get(0)?,
+        number: row.get(1)?,
+        title: row.get(2)?,
+        body: row.get(3)?,
+        labels: serde_json::from_str(&labels_str).unwrap_or_default(),
+        state: row.get(5)?,
+        fetched_at: row.get(6)?,
+        stale: false,
+    })
 }
 
 pub fn get_cached_issue(
@@ -72,19 +96,7 @@ pub fn get_cached_issue(
         )
         .map_err(|e| e.to_string())?;
 
-    let result = stmt.query_row(params![repo_id, issue_number], |row| {
-        let labels_str: String = row.get(4)?;
-        let labels: Vec<String> = serde_json::from_str(&labels_str).unwrap_or_default();
-        Ok(Issue {
-            repo_id: row.get(0)?,
-            number: row.get(1)?,
-            title: row.get(2)?,
-            body: row.get(3)?,
-            labels,
-            state: row.get(5)?,
-            fetched_at: row.get(6)?,
-        })
-    });
+    let result = stmt.query_row(params![repo_id, issue_number], row_to_issue);
 
     match result {
         Ok(issue) => Ok(Some(issue)),
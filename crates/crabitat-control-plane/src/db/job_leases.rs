@@ -0,0 +1,54 @@
+use rusqlite::{Connection, Row, params};
+
+use crate::models::system::JobLease;
+
+const JOB_LEASE_COLUMNS: &str = "job, holder, acquired_at, renewed_at, expires_at";
+
+fn row_to_lease(row: &Row) -> rusqlite::Result<JobLease> {
+    Ok(JobLease {
+        job: row.get(0)?,
+        holder: row.get(1)?,
+        acquired_at: row.get(2)?,
+        renewed_at: row.get(3)?,
+        expires_at: row.get(4)?,
+        held_here: false,
+    })
+}
+
+/// Take or renew the lease on `job` for `ttl_secs`. Returns false while
+/// another holder's lease is still live.
+pub fn try_acquire(
+    conn: &Connection,
+    job: &str,
+    holder: &str,
+    ttl_secs: u64,
+) -> Result<bool, String> {
+    conn.execute(
+        "INSERT INTO job_leases (job, holder, expires_at)
+         VALUES (?1, ?2, strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '+' || ?3 || ' seconds'))
+         ON CONFLICT(job) DO UPDATE SET
+            holder = excluded.holder,
+            acquired_at = CASE WHEN job_leases.holder = excluded.holder
+                THEN job_leases.acquired_at ELSE excluded.acquired_at END,
+            renewed_at = excluded.renewed_at,
+            expires_at = excluded.expires_at
+         WHERE job_leases.holder = excluded.holder
+            OR job_leases.expires_at <= excluded.renewed_at",
+        params![job, holder, ttl_secs as i64],
+    )
+    .map(|n| n > 0)
+    .map_err(|e| e.to_string())
+}
+
+/// Every job's lease, live or lapsed
+pub fn list(conn: &Connection) -> Result<Vec<JobLease>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {JOB_LEASE_COLUMNS} FROM job_leases ORDER BY job"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], row_to_lease)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
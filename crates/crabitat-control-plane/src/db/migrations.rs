@@ -0,0 +1,874 @@
+//! Schema migrations, planned before they are applied.
+//!
+//! `db status` shows the plan without touching the database; `serve` applies
+//! it on startup (or refuses to start with `--no-migrate` while anything is
+//! pending) and `db migrate` applies it on its own, for controlled rollouts.
+//!
+//! Every database is first brought to the baseline (`SCHEMA`, `ADD_COLUMNS`,
+//! backfills and rebuilds). Schema changes after that are numbered files in
+//! `migrations/`, each with an `up` and a `down` script, listed in
+//! [`MIGRATIONS`] and recorded in `schema_migrations` once applied;
+//! `db rollback` runs the `down` scripts.
+
+use std::io::Write;
+
+use rusqlite::{Connection, params};
+use serde::Serialize;
+
+use super::missions;
+
+/// Tables and indexes of a fresh database; existing ones are left alone
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS repos (
+        repo_id    TEXT PRIMARY KEY,
+        owner      TEXT NOT NULL,
+        name       TEXT NOT NULL,
+        local_path TEXT,
+        repo_url   TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at TEXT,
+        deleted_at TEXT
+    );
+
+    CREATE UNIQUE INDEX IF NOT EXISTS repos_owner_name_uniq
+        ON repos(owner, name) WHERE deleted_at IS NULL;
+
+    CREATE TABLE IF NOT EXISTS github_issues_cache (
+        repo_id    TEXT NOT NULL REFERENCES repos(repo_id),
+        number     INTEGER NOT NULL,
+        title      TEXT NOT NULL,
+        body       TEXT,
+        labels     TEXT NOT NULL DEFAULT '[]',
+        state      TEXT NOT NULL DEFAULT 'open',
+        fetched_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        PRIMARY KEY (repo_id, number)
+    );
+
+    CREATE TABLE IF NOT EXISTS workflow_flavors (
+        flavor_id     TEXT PRIMARY KEY,
+        workflow_name TEXT NOT NULL,
+        name          TEXT NOT NULL,
+        prompt_paths  TEXT NOT NULL DEFAULT '[]',
+        created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at    TEXT,
+        deleted_at    TEXT
+    );
+
+    CREATE UNIQUE INDEX IF NOT EXISTS workflow_flavors_name_uniq
+        ON workflow_flavors(workflow_name, name) WHERE deleted_at IS NULL;
+
+    CREATE TABLE IF NOT EXISTS settings (
+        key        TEXT PRIMARY KEY,
+        value      TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS environment_paths (
+        environment   TEXT NOT NULL,
+        resource_type TEXT NOT NULL,
+        resource_name TEXT NOT NULL,
+        path          TEXT NOT NULL,
+        created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at    TEXT,
+        PRIMARY KEY (environment, resource_type, resource_name)
+    );
+
+    -- Execution Layer (FR-4)
+
+    CREATE TABLE IF NOT EXISTS missions (
+        mission_id    TEXT PRIMARY KEY,
+        repo_id       TEXT NOT NULL REFERENCES repos(repo_id),
+        issue_number  INTEGER NOT NULL,
+        workflow_name TEXT NOT NULL,
+        flavor_id     TEXT REFERENCES workflow_flavors(flavor_id),
+        status        TEXT NOT NULL DEFAULT 'pending',
+        branch        TEXT,
+        created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at    TEXT,
+        repo_owner    TEXT,
+        repo_name     TEXT,
+        last_worker_id TEXT,
+        priority      INTEGER NOT NULL DEFAULT 0,
+        FOREIGN KEY (repo_id, issue_number) REFERENCES github_issues_cache(repo_id, number)
+    );
+
+    CREATE TABLE IF NOT EXISTS tasks (
+        task_id          TEXT PRIMARY KEY,
+        mission_id       TEXT NOT NULL REFERENCES missions(mission_id),
+        step_id          TEXT NOT NULL,
+        step_order       INTEGER NOT NULL,
+        assembled_prompt TEXT NOT NULL,
+        status           TEXT NOT NULL DEFAULT 'queued',
+        retry_count      INTEGER DEFAULT 0,
+        max_retries      INTEGER DEFAULT 3,
+        created_at       TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at       TEXT,
+        prompt_chunks    TEXT NOT NULL DEFAULT '[]',
+        step_config      TEXT NOT NULL DEFAULT '{}'
+    );
+
+    CREATE TABLE IF NOT EXISTS runs (
+        run_id      TEXT PRIMARY KEY,
+        task_id     TEXT NOT NULL REFERENCES tasks(task_id),
+        status      TEXT NOT NULL,
+        logs        TEXT,
+        summary     TEXT,
+        duration_ms INTEGER,
+        tokens_used INTEGER,
+        started_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        finished_at TEXT,
+        score       INTEGER,
+        worker_id   TEXT,
+        debug       INTEGER NOT NULL DEFAULT 0,
+        prompt_override    TEXT,
+        assigned_worker_id TEXT,
+        burrow_mode TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS mission_state_history (
+        id         INTEGER PRIMARY KEY,
+        mission_id TEXT NOT NULL REFERENCES missions(mission_id),
+        state      TEXT NOT NULL,
+        entered_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        exited_at  TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS task_transitions (
+        id          INTEGER PRIMARY KEY,
+        task_id     TEXT NOT NULL REFERENCES tasks(task_id),
+        from_status TEXT NOT NULL,
+        to_status   TEXT NOT NULL,
+        actor       TEXT NOT NULL,
+        reason      TEXT,
+        created_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS task_messages (
+        message_id   TEXT PRIMARY KEY,
+        task_id      TEXT NOT NULL REFERENCES tasks(task_id),
+        body         TEXT NOT NULL,
+        sender       TEXT,
+        worker_id    TEXT,
+        created_at   TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        delivered_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS task_notes (
+        note_id    TEXT PRIMARY KEY,
+        task_id    TEXT NOT NULL REFERENCES tasks(task_id),
+        body       TEXT NOT NULL,
+        author     TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS task_rejections (
+        task_id    TEXT NOT NULL REFERENCES tasks(task_id),
+        worker_id  TEXT NOT NULL,
+        reason     TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        PRIMARY KEY (task_id, worker_id)
+    );
+
+    CREATE TABLE IF NOT EXISTS artifacts (
+        artifact_id  TEXT PRIMARY KEY,
+        run_id       TEXT NOT NULL REFERENCES runs(run_id),
+        name         TEXT NOT NULL,
+        kind         TEXT,
+        content_type TEXT NOT NULL,
+        size         INTEGER NOT NULL,
+        sha256       TEXT NOT NULL,
+        content      BLOB NOT NULL,
+        created_at   TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS run_logs (
+        run_id     TEXT NOT NULL,
+        task_id    TEXT NOT NULL REFERENCES tasks(task_id),
+        seq        INTEGER NOT NULL,
+        stream     TEXT NOT NULL,
+        content    TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        PRIMARY KEY (run_id, seq)
+    );
+
+    CREATE TABLE IF NOT EXISTS scheduler_decisions (
+        id             INTEGER PRIMARY KEY,
+        tick_id        TEXT NOT NULL,
+        worker_id      TEXT,
+        task_id        TEXT NOT NULL,
+        mission_id     TEXT NOT NULL,
+        decision       TEXT NOT NULL,
+        reason         TEXT,
+        detail         TEXT,
+        eligible_crabs TEXT NOT NULL DEFAULT '[]',
+        created_at     TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS api_keys (
+        key_id       TEXT PRIMARY KEY,
+        name         TEXT NOT NULL,
+        prefix       TEXT NOT NULL,
+        key_hash     TEXT NOT NULL UNIQUE,
+        scopes       TEXT NOT NULL DEFAULT '[]',
+        created_at   TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        last_used_at TEXT,
+        revoked_at   TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS blobs (
+        hash       TEXT PRIMARY KEY,
+        content    TEXT NOT NULL,
+        size       INTEGER NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS burrow_leases (
+        mission_id  TEXT NOT NULL REFERENCES missions(mission_id),
+        worker_id   TEXT NOT NULL,
+        path        TEXT NOT NULL,
+        acquired_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        renewed_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        released_at TEXT,
+        PRIMARY KEY (mission_id, worker_id)
+    );
+
+    CREATE TABLE IF NOT EXISTS burrow_pools (
+        worker_id   TEXT PRIMARY KEY,
+        ready       INTEGER NOT NULL,
+        hits        INTEGER NOT NULL,
+        misses      INTEGER NOT NULL,
+        reported_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS triggers (
+        trigger_id    TEXT PRIMARY KEY,
+        repo_id       TEXT NOT NULL REFERENCES repos(repo_id),
+        event_type    TEXT NOT NULL,
+        matcher       TEXT NOT NULL DEFAULT '{}',
+        workflow_name TEXT NOT NULL,
+        flavor_id     TEXT,
+        priority      INTEGER NOT NULL DEFAULT 0,
+        created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS workflow_packs (
+        name         TEXT PRIMARY KEY,
+        git_url      TEXT NOT NULL,
+        git_ref      TEXT,
+        commit_sha   TEXT NOT NULL,
+        workflows    TEXT NOT NULL DEFAULT '[]',
+        installed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at   TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS stored_workflows (
+        name       TEXT PRIMARY KEY,
+        content    TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        updated_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS stored_prompts (
+        path          TEXT PRIMARY KEY,
+        workflow_name TEXT NOT NULL REFERENCES stored_workflows(name),
+        content       TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS crab_executors (
+        worker_id TEXT PRIMARY KEY,
+        executors TEXT NOT NULL DEFAULT '[]',
+        seen_at   TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS crab_registrations (
+        worker_id     TEXT PRIMARY KEY,
+        token_hash    TEXT NOT NULL,
+        registered_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version    INTEGER PRIMARY KEY,
+        name       TEXT NOT NULL,
+        applied_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+    );
+
+    CREATE TABLE IF NOT EXISTS crab_daily_stats (
+        worker_id       TEXT NOT NULL,
+        day             TEXT NOT NULL,
+        runs            INTEGER NOT NULL DEFAULT 0,
+        tasks_completed INTEGER NOT NULL DEFAULT 0,
+        tasks_failed    INTEGER NOT NULL DEFAULT 0,
+        busy_ms         INTEGER NOT NULL DEFAULT 0,
+        tokens_used     INTEGER NOT NULL DEFAULT 0,
+        updated_at      TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        PRIMARY KEY (worker_id, day)
+    );";
+
+/// Columns added after their table first shipped (ALTER TABLE cannot use non-constant DEFAULT)
+const ADD_COLUMNS: &[&str] = &[
+    "ALTER TABLE repos ADD COLUMN deleted_at TEXT",
+    "ALTER TABLE repos ADD COLUMN updated_at TEXT",
+    "ALTER TABLE workflow_flavors ADD COLUMN deleted_at TEXT",
+    "ALTER TABLE workflow_flavors ADD COLUMN created_at TEXT",
+    "ALTER TABLE workflow_flavors ADD COLUMN updated_at TEXT",
+    "ALTER TABLE settings ADD COLUMN created_at TEXT",
+    "ALTER TABLE settings ADD COLUMN updated_at TEXT",
+    "ALTER TABLE environment_paths ADD COLUMN created_at TEXT",
+    "ALTER TABLE environment_paths ADD COLUMN updated_at TEXT",
+    "ALTER TABLE missions ADD COLUMN updated_at TEXT",
+    "ALTER TABLE missions ADD COLUMN last_worker_id TEXT",
+    "ALTER TABLE missions ADD COLUMN priority INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE missions ADD COLUMN archived_at TEXT",
+    "ALTER TABLE missions ADD COLUMN max_concurrent_tasks INTEGER",
+    "ALTER TABLE missions ADD COLUMN context_from_mission_id TEXT",
+    "ALTER TABLE missions ADD COLUMN total_tokens INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE missions ADD COLUMN total_duration_ms INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE missions ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE missions ADD COLUMN first_started_at TEXT",
+    "ALTER TABLE missions ADD COLUMN last_finished_at TEXT",
+    "ALTER TABLE missions ADD COLUMN github_claimed INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE missions ADD COLUMN prompt TEXT",
+    "ALTER TABLE missions ADD COLUMN exclusive INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE missions ADD COLUMN acceptance_criteria TEXT NOT NULL DEFAULT '[]'",
+    "ALTER TABLE tasks ADD COLUMN updated_at TEXT",
+    "ALTER TABLE tasks ADD COLUMN prompt_chunks TEXT NOT NULL DEFAULT '[]'",
+    "ALTER TABLE tasks ADD COLUMN step_config TEXT NOT NULL DEFAULT '{}'",
+    "ALTER TABLE tasks ADD COLUMN heartbeat_at TEXT",
+    "ALTER TABLE tasks ADD COLUMN heartbeat_worker_id TEXT",
+    "ALTER TABLE runs ADD COLUMN score INTEGER",
+    "ALTER TABLE runs ADD COLUMN worker_id TEXT",
+    "ALTER TABLE runs ADD COLUMN debug INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE runs ADD COLUMN prompt_override TEXT",
+    "ALTER TABLE runs ADD COLUMN assigned_worker_id TEXT",
+    "ALTER TABLE runs ADD COLUMN burrow_mode TEXT",
+    "ALTER TABLE runs ADD COLUMN next_workflow TEXT",
+    "ALTER TABLE runs ADD COLUMN work_log_hash TEXT",
+    "ALTER TABLE runs ADD COLUMN redactions INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE runs ADD COLUMN failure_kind TEXT",
+    "ALTER TABLE runs ADD COLUMN criteria TEXT",
+    "ALTER TABLE runs ADD COLUMN burrow_path TEXT",
+];
+
+/// A table first created with a UNIQUE constraint that has since become a
+/// partial unique index. SQLite has no DROP CONSTRAINT, so the table is
+/// copied into a new one and the old one dropped.
+struct Rebuild {
+    table: &'static str,
+    /// Columns the old UNIQUE constraint covered
+    unique_columns: &'static [&'static str],
+    create_sql: &'static str,
+    columns: &'static str,
+    index_name: &'static str,
+}
+
+const REBUILDS: &[Rebuild] = &[
+    Rebuild {
+        table: "repos",
+        unique_columns: &["owner", "name"],
+        create_sql: "CREATE TABLE repos_new (
+            repo_id    TEXT PRIMARY KEY,
+            owner      TEXT NOT NULL,
+            name       TEXT NOT NULL,
+            local_path TEXT,
+            repo_url   TEXT,
+            created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+            updated_at TEXT,
+            deleted_at TEXT
+        )",
+        columns: "repo_id, owner, name, local_path, repo_url, created_at, updated_at, deleted_at",
+        index_name: "repos_owner_name_uniq",
+    },
+    Rebuild {
+        table: "workflow_flavors",
+        unique_columns: &["workflow_name", "name"],
+        create_sql: "CREATE TABLE workflow_flavors_new (
+            flavor_id     TEXT PRIMARY KEY,
+            workflow_name TEXT NOT NULL,
+            name          TEXT NOT NULL,
+            prompt_paths  TEXT NOT NULL DEFAULT '[]',
+            created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+            updated_at    TEXT,
+            deleted_at    TEXT
+        )",
+        columns: "flavor_id, workflow_name, name, prompt_paths, created_at, updated_at, deleted_at",
+        index_name: "workflow_flavors_name_uniq",
+    },
+];
+
+/// A numbered schema change applied after the baseline
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    /// Undoes `up`
+    pub down: &'static str,
+}
+
+/// Embed `migrations/{name}.up.sql` and `migrations/{name}.down.sql`
+macro_rules! migration {
+    ($version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("migrations/", $name, ".up.sql")),
+            down: include_str!(concat!("migrations/", $name, ".down.sql")),
+        }
+    };
+}
+
+/// Versioned migrations, oldest first; append new ones with the next version
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_hot_path_indexes"),
+    migration!(2, "0002_run_summary_hash"),
+    migration!(3, "0003_job_leases"),
+    migration!(4, "0004_status_changes"),
+    migration!(5, "0005_mission_replays"),
+    migration!(6, "0006_events"),
+    migration!(7, "0007_activity_indexes"),
+    migration!(8, "0008_slack_webhooks"),
+    migration!(9, "0009_crab_roles"),
+    migration!(10, "0010_mission_risk"),
+    migration!(11, "0011_run_network"),
+    migration!(12, "0012_issue_status"),
+    migration!(13, "0013_issue_completion"),
+    migration!(14, "0014_consistency_reports"),
+    migration!(15, "0015_repo_forge"),
+    migration!(16, "0016_benchmarks"),
+    migration!(17, "0017_pr_review_comments"),
+    migration!(18, "0018_repo_jira"),
+    migration!(19, "0019_mission_context_artifacts"),
+    migration!(20, "0020_run_log_redactions"),
+    migration!(21, "0021_repo_sandbox"),
+];
+
+/// An `UPDATE {table} SET {set} WHERE {filter}` filling in rows that predate a column
+struct Backfill {
+    table: &'static str,
+    set: String,
+    filter: &'static str,
+}
+
+fn backfills() -> Vec<Backfill> {
+    let created_at = "created_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')";
+    vec![
+        Backfill {
+            table: "workflow_flavors",
+            set: created_at.to_string(),
+            filter: "created_at IS NULL",
+        },
+        Backfill {
+            table: "settings",
+            set: created_at.to_string(),
+            filter: "created_at IS NULL",
+        },
+        Backfill {
+            table: "environment_paths",
+            set: created_at.to_string(),
+            filter: "created_at IS NULL",
+        },
+        // Run rollups for missions whose runs predate them
+        Backfill {
+            table: "missions",
+            set: missions::ROLLUP_SET.to_string(),
+            filter: "attempts = 0 AND EXISTS (
+                SELECT 1 FROM runs r JOIN tasks t ON r.task_id = t.task_id
+                WHERE t.mission_id = missions.mission_id AND r.debug = 0)",
+        },
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationKind {
+    CreateTable,
+    CreateIndex,
+    AddColumn,
+    Backfill,
+    RebuildTable,
+    /// A versioned migration's `up` script
+    Migrate,
+    /// A versioned migration's `down` script
+    Revert,
+}
+
+impl MigrationKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::CreateTable => "create table",
+            Self::CreateIndex => "create index",
+            Self::AddColumn => "add column",
+            Self::Backfill => "backfill",
+            Self::RebuildTable => "rebuild table",
+            Self::Migrate => "migrate",
+            Self::Revert => "revert",
+        }
+    }
+}
+
+/// One pending step of the schema migration
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedMigration {
+    pub kind: MigrationKind,
+    pub table: String,
+    /// Column, index or statement the step is about
+    pub detail: String,
+    /// Rows the step reads or writes; a rough guide to how long it holds the write lock
+    pub rows: i64,
+    /// Drops data structures (a table rebuild); needs `--allow-destructive`
+    pub destructive: bool,
+    /// The versioned migration, for `migrate` and `revert` steps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+}
+
+impl PlannedMigration {
+    fn versioned(kind: MigrationKind, migration: &Migration) -> Self {
+        Self {
+            kind,
+            table: "schema_migrations".to_string(),
+            detail: format!("{:04} {}", migration.version, migration.name),
+            rows: 0,
+            destructive: false,
+            version: Some(migration.version),
+        }
+    }
+}
+
+/// Versions of the applied versioned migrations, oldest first
+pub fn applied_versions(conn: &Connection) -> Result<Vec<i64>, String> {
+    if !table_exists(conn, "schema_migrations")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn
+        .prepare("SELECT version FROM schema_migrations ORDER BY version")
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [table],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn index_exists(conn: &Connection, index: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = ?1)",
+        [index],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Rows in `table`, zero when it doesn't exist yet
+fn row_count(conn: &Connection, table: &str) -> Result<i64, String> {
+    if !table_exists(conn, table)? {
+        return Ok(0);
+    }
+    conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table), [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// The identifier following `keyword` in `sql`, e.g. the table after `ALTER TABLE`
+fn name_after<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = &sql[sql.find(keyword)? + keyword.len()..];
+    rest.split(|c: char| c.is_whitespace() || c == '(')
+        .find(|word| !word.is_empty())
+}
+
+/// Tables (`CREATE TABLE`) and indexes (`CREATE ... INDEX`, with their table) in `SCHEMA`
+fn schema_objects() -> (Vec<&'static str>, Vec<(&'static str, &'static str)>) {
+    let mut tables = Vec::new();
+    let mut indexes = Vec::new();
+    for statement in SCHEMA.split(';') {
+        if let Some(table) = name_after(statement, "CREATE TABLE IF NOT EXISTS") {
+            tables.push(table);
+        } else if let (Some(index), Some(table)) = (
+            name_after(statement, "INDEX IF NOT EXISTS"),
+            name_after(statement, " ON "),
+        ) {
+            indexes.push((index, table));
+        }
+    }
+    (tables, indexes)
+}
+
+fn needs_rebuild(conn: &Connection, rebuild: &Rebuild) -> Result<bool, String> {
+    let sql: String = match conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [rebuild.table],
+        |row| row.get(0),
+    ) {
+        Ok(sql) => sql,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(sql.contains("UNIQUE") && rebuild.unique_columns.iter().all(|c| sql.contains(c)))
+}
+
+/// What `apply` would do to this database, without changing it
+pub fn plan(conn: &Connection) -> Result<Vec<PlannedMigration>, String> {
+    let mut steps = Vec::new();
+    let (tables, indexes) = schema_objects();
+
+    for table in tables {
+        if !table_exists(conn, table)? {
+            steps.push(PlannedMigration {
+                kind: MigrationKind::CreateTable,
+                table: table.to_string(),
+                detail: String::new(),
+                rows: 0,
+                destructive: false,
+                version: None,
+            });
+        }
+    }
+    for sql in ADD_COLUMNS {
+        let (Some(table), Some(column)) = (
+            name_after(sql, "ALTER TABLE"),
+            name_after(sql, "ADD COLUMN"),
+        ) else {
+            continue;
+        };
+        if table_exists(conn, table)? && column_exists(conn, table, column)? {
+            continue;
+        }
+        steps.push(PlannedMigration {
+            kind: MigrationKind::AddColumn,
+            table: table.to_string(),
+            detail: column.to_string(),
+            rows: row_count(conn, table)?,
+            destructive: false,
+            version: None,
+        });
+    }
+    for (index, table) in indexes {
+        if !index_exists(conn, index)? {
+            steps.push(PlannedMigration {
+                kind: MigrationKind::CreateIndex,
+                table: table.to_string(),
+                detail: index.to_string(),
+                rows: row_count(conn, table)?,
+                destructive: false,
+                version: None,
+            });
+        }
+    }
+    for backfill in backfills() {
+        if !table_exists(conn, backfill.table)? {
+            continue;
+        }
+        // The filter may name a column that is still to be added; then every row qualifies
+        let rows = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {} WHERE {}",
+                    backfill.table, backfill.filter
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .or_else(|_| row_count(conn, backfill.table))?;
+        if rows > 0 {
+            steps.push(PlannedMigration {
+                kind: MigrationKind::Backfill,
+                table: backfill.table.to_string(),
+                detail: backfill
+                    .filter
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                rows,
+                destructive: false,
+                version: None,
+            });
+        }
+    }
+    for rebuild in REBUILDS {
+        if needs_rebuild(conn, rebuild)? {
+            steps.push(PlannedMigration {
+                kind: MigrationKind::RebuildTable,
+                table: rebuild.table.to_string(),
+                detail: format!("drop UNIQUE ({})", rebuild.unique_columns.join(", ")),
+                rows: row_count(conn, rebuild.table)?,
+                destructive: true,
+                version: None,
+            });
+        }
+    }
+    let applied = applied_versions(conn)?;
+    for migration in MIGRATIONS {
+        if !applied.contains(&migration.version) {
+            steps.push(PlannedMigration::versioned(
+                MigrationKind::Migrate,
+                migration,
+            ));
+        }
+    }
+    Ok(steps)
+}
+
+/// Bring the schema up to date and return the steps that were pending.
+///
+/// Refuses, before changing anything, when a destructive step is pending and
+/// `allow_destructive` isn't set.
+pub fn apply(conn: &Connection, allow_destructive: bool) -> Result<Vec<PlannedMigration>, String> {
+    let steps = plan(conn)?;
+    let destructive: Vec<String> = steps
+        .iter()
+        .filter(|s| s.destructive)
+        .map(|s| format!("{} {} ({} rows)", s.kind.label(), s.table, s.rows))
+        .collect();
+    if !destructive.is_empty() && !allow_destructive {
+        return Err(format!(
+            "refusing destructive migrations without --allow-destructive: {}",
+            destructive.join(", ")
+        ));
+    }
+
+    conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+
+    for stmt in ADD_COLUMNS {
+        match conn.execute(stmt, []) {
+            Ok(_) => {}
+            Err(e) if e.to_string().contains("duplicate column") => {}
+            Err(e) => return Err(format!("{}: {}", stmt, e)),
+        }
+    }
+
+    for backfill in backfills() {
+        conn.execute(
+            &format!(
+                "UPDATE {} SET {} WHERE {}",
+                backfill.table, backfill.set, backfill.filter
+            ),
+            [],
+        )
+        .map_err(|e| format!("backfilling {}: {}", backfill.table, e))?;
+    }
+
+    for rebuild in REBUILDS {
+        if !needs_rebuild(conn, rebuild)? {
+            continue;
+        }
+        let Rebuild {
+            table,
+            unique_columns,
+            create_sql,
+            columns,
+            index_name,
+        } = rebuild;
+        let index_cols = unique_columns.join(", ");
+        conn.execute_batch(&format!(
+            "PRAGMA foreign_keys = OFF;
+             BEGIN TRANSACTION;
+             {create_sql};
+             INSERT INTO {table}_new ({columns}) SELECT {columns} FROM {table};
+             DROP TABLE {table};
+             ALTER TABLE {table}_new RENAME TO {table};
+             CREATE UNIQUE INDEX IF NOT EXISTS {index_name} ON {table}({index_cols}) WHERE deleted_at IS NULL;
+             COMMIT;
+             PRAGMA foreign_key_check;
+             PRAGMA foreign_keys = ON;"
+        ))
+        .map_err(|e| format!("rebuilding {}: {}", table, e))?;
+    }
+
+    let applied = applied_versions(conn)?;
+    for migration in MIGRATIONS {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration.up)
+            .map_err(|e| format!("migration {}: {}", migration.name, e))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)",
+            params![migration.version, migration.name],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    Ok(steps)
+}
+
+/// Run the `down` scripts of the applied versioned migrations newer than
+/// `version`, newest first, and return what was reverted. The baseline
+/// can't be reverted.
+pub fn revert(conn: &Connection, version: i64) -> Result<Vec<PlannedMigration>, String> {
+    let applied = applied_versions(conn)?;
+    let mut reverted = Vec::new();
+    for migration in MIGRATIONS.iter().rev() {
+        if migration.version <= version || !applied.contains(&migration.version) {
+            continue;
+        }
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration.down)
+            .map_err(|e| format!("reverting {}: {}", migration.name, e))?;
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = ?1",
+            [migration.version],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        reverted.push(PlannedMigration::versioned(
+            MigrationKind::Revert,
+            migration,
+        ));
+    }
+    Ok(reverted)
+}
+
+/// The plan as a table for `db status`
+pub fn write_plan(steps: &[PlannedMigration], out: &mut impl Write) -> std::io::Result<()> {
+    if steps.is_empty() {
+        return writeln!(out, "schema is up to date");
+    }
+    writeln!(out, "{:<14} {:<22} {:>10}  DETAIL", "STEP", "TABLE", "ROWS")?;
+    for step in steps {
+        let flag = if step.destructive {
+            " [destructive]"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "{:<14} {:<22} {:>10}  {}{}",
+            step.kind.label(),
+            step.table,
+            step.rows,
+            step.detail,
+            flag
+        )?;
+    }
+    let destructive = steps.iter().filter(|s| s.destructive).count();
+    writeln!(
+        out,
+        "{} pending step(s), {} destructive{}",
+        steps.len(),
+        destructive,
+        if destructive > 0 {
+            "; apply with `db migrate --allow-destructive`"
+        } else {
+            ""
+        }
+    )
+}
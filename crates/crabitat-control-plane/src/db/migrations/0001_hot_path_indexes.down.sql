@@ -0,0 +1,4 @@
+DROP INDEX tasks_mission_id;
+DROP INDEX tasks_status;
+DROP INDEX runs_task_id;
+DROP INDEX task_transitions_task_id;
@@ -0,0 +1,5 @@
+-- Lookups every poll, mission view and watchdog pass makes
+CREATE INDEX tasks_mission_id ON tasks(mission_id);
+CREATE INDEX tasks_status ON tasks(status);
+CREATE INDEX runs_task_id ON runs(task_id);
+CREATE INDEX task_transitions_task_id ON task_transitions(task_id);
@@ -0,0 +1,2 @@
+-- Full run summaries too long to keep inline live in blobs
+ALTER TABLE runs ADD COLUMN summary_hash TEXT;
@@ -0,0 +1,10 @@
+DROP TRIGGER missions_changed_insert;
+DROP TRIGGER missions_changed_update;
+DROP TRIGGER missions_changed_delete;
+DROP TRIGGER tasks_changed_insert;
+DROP TRIGGER tasks_changed_update;
+DROP TRIGGER tasks_changed_delete;
+DROP TRIGGER runs_changed_insert;
+DROP TRIGGER runs_changed_update;
+DROP TRIGGER runs_changed_delete;
+DROP TABLE status_changes;
@@ -0,0 +1,56 @@
+-- Latest change per mission, task and run, so the console can ask for what
+-- changed since the last sequence number it saw instead of reloading everything.
+-- A deleted mission keeps its row (deleted = 1) so clients can drop it.
+CREATE TABLE status_changes (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    record_id TEXT NOT NULL,
+    changed_ms INTEGER NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (kind, record_id)
+);
+
+INSERT INTO status_changes (kind, record_id, changed_ms)
+    SELECT 'mission', mission_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER) FROM missions;
+INSERT INTO status_changes (kind, record_id, changed_ms)
+    SELECT 'task', task_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER) FROM tasks;
+INSERT INTO status_changes (kind, record_id, changed_ms)
+    SELECT 'run', run_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER) FROM runs;
+
+CREATE TRIGGER missions_changed_insert AFTER INSERT ON missions BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms)
+    VALUES ('mission', NEW.mission_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+CREATE TRIGGER missions_changed_update AFTER UPDATE ON missions BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms)
+    VALUES ('mission', NEW.mission_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+CREATE TRIGGER missions_changed_delete AFTER DELETE ON missions BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms, deleted)
+    VALUES ('mission', OLD.mission_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER), 1);
+END;
+
+CREATE TRIGGER tasks_changed_insert AFTER INSERT ON tasks BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms)
+    VALUES ('task', NEW.task_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+CREATE TRIGGER tasks_changed_update AFTER UPDATE ON tasks BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms)
+    VALUES ('task', NEW.task_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+-- Tasks and runs only go away with their mission, whose deletion clients see
+CREATE TRIGGER tasks_changed_delete AFTER DELETE ON tasks BEGIN
+    DELETE FROM status_changes WHERE kind = 'task' AND record_id = OLD.task_id;
+END;
+
+CREATE TRIGGER runs_changed_insert AFTER INSERT ON runs BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms)
+    VALUES ('run', NEW.run_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+CREATE TRIGGER runs_changed_update AFTER UPDATE ON runs BEGIN
+    INSERT OR REPLACE INTO status_changes (kind, record_id, changed_ms)
+    VALUES ('run', NEW.run_id, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+CREATE TRIGGER runs_changed_delete AFTER DELETE ON runs BEGIN
+    DELETE FROM status_changes WHERE kind = 'run' AND record_id = OLD.run_id;
+END;
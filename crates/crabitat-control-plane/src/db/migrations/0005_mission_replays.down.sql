@@ -0,0 +1,3 @@
+DROP INDEX missions_replay_of_mission_id;
+ALTER TABLE missions DROP COLUMN replay_of_mission_id;
+ALTER TABLE missions DROP COLUMN workflow_version;
@@ -0,0 +1,5 @@
+-- Version of the workflow a mission was expanded from, and the mission a
+-- replay re-ran, so the two can be compared
+ALTER TABLE missions ADD COLUMN workflow_version TEXT;
+ALTER TABLE missions ADD COLUMN replay_of_mission_id TEXT;
+CREATE INDEX missions_replay_of_mission_id ON missions(replay_of_mission_id);
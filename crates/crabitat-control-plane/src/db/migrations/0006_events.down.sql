@@ -0,0 +1,9 @@
+DROP TRIGGER missions_event_insert;
+DROP TRIGGER missions_event_update;
+DROP TRIGGER missions_event_delete;
+DROP TRIGGER tasks_event_insert;
+DROP TRIGGER tasks_event_update;
+DROP TRIGGER runs_event_insert;
+DROP TRIGGER runs_event_update;
+DROP INDEX events_created_at;
+DROP TABLE events;
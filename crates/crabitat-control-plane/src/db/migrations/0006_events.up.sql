@@ -0,0 +1,47 @@
+-- Append-only log of mission, task and run changes; clients that lost their
+-- connection replay it from the last seq they saw
+CREATE TABLE events (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    record_id TEXT NOT NULL,
+    mission_id TEXT,
+    status TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+CREATE INDEX events_created_at ON events(created_at);
+
+CREATE TRIGGER missions_event_insert AFTER INSERT ON missions BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('mission_created', NEW.mission_id, NEW.mission_id, NEW.status);
+END;
+CREATE TRIGGER missions_event_update AFTER UPDATE ON missions
+WHEN OLD.status IS NOT NEW.status OR OLD.archived_at IS NOT NEW.archived_at BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('mission_updated', NEW.mission_id, NEW.mission_id, NEW.status);
+END;
+CREATE TRIGGER missions_event_delete AFTER DELETE ON missions BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('mission_deleted', OLD.mission_id, OLD.mission_id, OLD.status);
+END;
+
+CREATE TRIGGER tasks_event_insert AFTER INSERT ON tasks BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('task_created', NEW.task_id, NEW.mission_id, NEW.status);
+END;
+CREATE TRIGGER tasks_event_update AFTER UPDATE ON tasks
+WHEN OLD.status IS NOT NEW.status BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('task_updated', NEW.task_id, NEW.mission_id, NEW.status);
+END;
+
+CREATE TRIGGER runs_event_insert AFTER INSERT ON runs BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('run_created', NEW.run_id,
+            (SELECT mission_id FROM tasks WHERE task_id = NEW.task_id), NEW.status);
+END;
+CREATE TRIGGER runs_event_update AFTER UPDATE ON runs
+WHEN OLD.status IS NOT NEW.status OR OLD.finished_at IS NOT NEW.finished_at BEGIN
+    INSERT INTO events (kind, record_id, mission_id, status)
+    VALUES ('run_updated', NEW.run_id,
+            (SELECT mission_id FROM tasks WHERE task_id = NEW.task_id), NEW.status);
+END;
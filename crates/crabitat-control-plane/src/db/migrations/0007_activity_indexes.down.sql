@@ -0,0 +1,2 @@
+DROP INDEX runs_started_at;
+DROP INDEX missions_repo_id;
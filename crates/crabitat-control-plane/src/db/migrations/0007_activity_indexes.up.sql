@@ -0,0 +1,3 @@
+-- Repo activity buckets scan a repo's runs by start time
+CREATE INDEX runs_started_at ON runs(started_at);
+CREATE INDEX missions_repo_id ON missions(repo_id);
@@ -0,0 +1 @@
+ALTER TABLE repos DROP COLUMN slack_webhook_url;
@@ -0,0 +1,2 @@
+-- Slack incoming webhook each repo's mission notifications are posted to
+ALTER TABLE repos ADD COLUMN slack_webhook_url TEXT;
@@ -0,0 +1 @@
+ALTER TABLE crab_executors DROP COLUMN role;
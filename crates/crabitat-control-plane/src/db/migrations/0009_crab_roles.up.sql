@@ -0,0 +1,2 @@
+-- Role each crab last polled with, so guides can tell which roles are unstaffed
+ALTER TABLE crab_executors ADD COLUMN role TEXT;
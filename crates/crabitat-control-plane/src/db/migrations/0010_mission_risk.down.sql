@@ -0,0 +1,2 @@
+ALTER TABLE missions DROP COLUMN risk_reasons;
+ALTER TABLE repos DROP COLUMN risk_rules;
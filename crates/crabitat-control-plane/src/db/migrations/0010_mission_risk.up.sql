@@ -0,0 +1,4 @@
+-- Per-repo risk rules, JSON {"paths": [globs], "keywords": [...]}
+ALTER TABLE repos ADD COLUMN risk_rules TEXT;
+-- Why a mission was flagged high-risk, JSON array; NULL while it isn't
+ALTER TABLE missions ADD COLUMN risk_reasons TEXT;
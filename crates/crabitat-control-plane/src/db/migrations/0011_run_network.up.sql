@@ -0,0 +1,2 @@
+-- Network policy the crab ran the agent under: none, restricted or full
+ALTER TABLE runs ADD COLUMN network TEXT;
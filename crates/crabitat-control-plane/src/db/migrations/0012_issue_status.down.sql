@@ -0,0 +1,3 @@
+ALTER TABLE missions DROP COLUMN issue_comment_state;
+ALTER TABLE missions DROP COLUMN issue_comment_id;
+ALTER TABLE missions DROP COLUMN pr_url;
@@ -0,0 +1,5 @@
+-- Pull request a step of the mission opened, when its output linked one
+ALTER TABLE missions ADD COLUMN pr_url TEXT;
+-- The status comment on the mission's issue and the state it last showed
+ALTER TABLE missions ADD COLUMN issue_comment_id INTEGER;
+ALTER TABLE missions ADD COLUMN issue_comment_state TEXT;
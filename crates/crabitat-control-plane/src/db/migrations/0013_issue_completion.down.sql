@@ -0,0 +1,2 @@
+ALTER TABLE missions DROP COLUMN issue_done;
+ALTER TABLE repos DROP COLUMN issue_completion;
@@ -0,0 +1,6 @@
+-- What to do to the issue once a mission completes, JSON {"close": bool, "label": "..."}
+ALTER TABLE repos ADD COLUMN issue_completion TEXT;
+-- Set once the completed mission's issue was dealt with; missions that
+-- completed before this migration count as dealt with
+ALTER TABLE missions ADD COLUMN issue_done INTEGER NOT NULL DEFAULT 0;
+UPDATE missions SET issue_done = 1 WHERE status = 'completed';
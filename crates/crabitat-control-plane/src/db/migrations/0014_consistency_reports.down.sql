@@ -0,0 +1 @@
+DROP TABLE consistency_reports;
@@ -0,0 +1,6 @@
+-- Results of the consistency checker: anomalies found and whether each was fixed
+CREATE TABLE consistency_reports (
+    report_id  INTEGER PRIMARY KEY AUTOINCREMENT,
+    checked_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    findings   TEXT NOT NULL DEFAULT '[]'
+);
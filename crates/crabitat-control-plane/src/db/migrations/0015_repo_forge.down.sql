@@ -0,0 +1,2 @@
+ALTER TABLE repos DROP COLUMN forge_url;
+ALTER TABLE repos DROP COLUMN forge;
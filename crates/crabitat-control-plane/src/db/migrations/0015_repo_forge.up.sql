@@ -0,0 +1,3 @@
+-- Where the repo's issues live: github or gitlab, and the GitLab instance
+ALTER TABLE repos ADD COLUMN forge TEXT NOT NULL DEFAULT 'github';
+ALTER TABLE repos ADD COLUMN forge_url TEXT;
@@ -0,0 +1,4 @@
+DROP INDEX idx_missions_benchmark;
+ALTER TABLE missions DROP COLUMN benchmark_variant;
+ALTER TABLE missions DROP COLUMN benchmark_id;
+DROP TABLE benchmarks;
@@ -0,0 +1,14 @@
+-- Benchmarks: past issues re-run through two workflow variants side by side
+CREATE TABLE benchmarks (
+    benchmark_id  TEXT PRIMARY KEY,
+    repo_id       TEXT NOT NULL REFERENCES repos(repo_id),
+    name          TEXT NOT NULL,
+    baseline      TEXT NOT NULL,
+    candidate     TEXT NOT NULL,
+    issue_numbers TEXT NOT NULL DEFAULT '[]',
+    created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+ALTER TABLE missions ADD COLUMN benchmark_id TEXT;
+ALTER TABLE missions ADD COLUMN benchmark_variant TEXT;
+CREATE INDEX idx_missions_benchmark ON missions(benchmark_id);
@@ -0,0 +1 @@
+DROP TABLE pr_review_comments;
@@ -0,0 +1,16 @@
+-- Change-request feedback from reviewers on a mission's pull request, kept
+-- until a fix step is requeued with it
+CREATE TABLE pr_review_comments (
+    mission_id   TEXT NOT NULL REFERENCES missions(mission_id),
+    kind         TEXT NOT NULL,
+    comment_id   INTEGER NOT NULL,
+    author       TEXT NOT NULL,
+    body         TEXT NOT NULL,
+    path         TEXT,
+    line         INTEGER,
+    url          TEXT NOT NULL DEFAULT '',
+    submitted_at TEXT,
+    taken_at     TEXT,
+    created_at   TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    PRIMARY KEY (mission_id, kind, comment_id)
+);
@@ -0,0 +1,3 @@
+-- Jira issue queue of a repo whose forge is jira: project, JQL filter and
+-- completion transition, as JSON
+ALTER TABLE repos ADD COLUMN forge_jira TEXT;
@@ -0,0 +1,2 @@
+-- Artifacts of context_from_mission_id carried into the mission, by name or kind
+ALTER TABLE missions ADD COLUMN context_artifacts TEXT NOT NULL DEFAULT '[]';
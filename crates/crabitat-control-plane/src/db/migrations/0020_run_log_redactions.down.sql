@@ -0,0 +1 @@
+ALTER TABLE run_logs DROP COLUMN redactions;
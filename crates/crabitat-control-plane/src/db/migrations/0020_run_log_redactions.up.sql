@@ -0,0 +1,2 @@
+-- Secrets scrubbed from each streamed chunk, added to the run once it is recorded
+ALTER TABLE run_logs ADD COLUMN redactions INTEGER NOT NULL DEFAULT 0;
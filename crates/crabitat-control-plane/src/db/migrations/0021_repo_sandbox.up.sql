@@ -0,0 +1,2 @@
+-- Disposable copies of a repo that benchmark missions may open pull requests against
+ALTER TABLE repos ADD COLUMN sandbox INTEGER NOT NULL DEFAULT 0;
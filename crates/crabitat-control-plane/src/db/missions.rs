@@ -1,5 +1,72 @@
-use crate::models::missions::{CreateMissionRequest, Mission, StateHistoryEntry};
-use rusqlite::{Connection, params};
+use crate::cursor::Cursor;
+use crate::db::crabs;
+use crate::models::missions::{
+    AcceptanceCriterion, CreateMissionRequest, FleetSummary, IssueClaimChange, IssueCommentChange,
+    IssueDoneChange, JiraDoneChange, Mission, MissionDeletionReport, MissionListQuery,
+    MissionRollup, PrReviewComment, PrReviewWatch, RepoMissionCounts, StateHistoryEntry,
+};
+use crate::models::tasks::CriterionResult;
+use rusqlite::{Connection, Row, params};
+
+/// Column list shared by mission queries; expects `missions m JOIN repos r`.
+const MISSION_COLUMNS: &str = "m.mission_id, m.repo_id, r.owner, r.name, m.issue_number, m.workflow_name, m.flavor_id, m.status, m.created_at, m.updated_at, m.branch, m.last_worker_id, m.priority, m.archived_at, m.max_concurrent_tasks, m.context_from_mission_id, m.total_tokens, m.total_duration_ms, m.attempts, m.first_started_at, m.last_finished_at, MAX(0, CAST(ROUND((julianday(m.last_finished_at) - julianday(m.first_started_at)) * 86400000) AS INTEGER)), m.prompt, m.exclusive, m.acceptance_criteria, m.workflow_version, m.replay_of_mission_id, m.risk_reasons, m.pr_url, m.context_artifacts";
+
+/// `SET` clause recomputing a mission's rollup from its runs; the statement
+/// must update `missions` without an alias. Run finish times only have
+/// second precision, hence the clamp on the wall-clock column above.
+pub(crate) const ROLLUP_SET: &str = "
+    total_tokens = (SELECT COALESCE(SUM(r.tokens_used), 0) FROM runs r JOIN tasks t ON r.task_id = t.task_id
+        WHERE t.mission_id = missions.mission_id AND r.debug = 0),
+    total_duration_ms = (SELECT COALESCE(SUM(r.duration_ms), 0) FROM runs r JOIN tasks t ON r.task_id = t.task_id
+        WHERE t.mission_id = missions.mission_id AND r.debug = 0),
+    attempts = (SELECT COUNT(*) FROM runs r JOIN tasks t ON r.task_id = t.task_id
+        WHERE t.mission_id = missions.mission_id AND r.debug = 0),
+    first_started_at = (SELECT MIN(tt.created_at) FROM task_transitions tt JOIN tasks t ON tt.task_id = t.task_id
+        WHERE t.mission_id = missions.mission_id AND tt.to_status = 'running'),
+    last_finished_at = (SELECT MAX(r.finished_at) FROM runs r JOIN tasks t ON r.task_id = t.task_id
+        WHERE t.mission_id = missions.mission_id AND r.debug = 0)";
+
+fn row_to_mission(row: &Row) -> rusqlite::Result<Mission> {
+    let risk_reasons: Option<String> = row.get(27)?;
+    Ok(Mission {
+        mission_id: row.get(0)?,
+        repo_id: row.get(1)?,
+        repo_owner: row.get(2)?,
+        repo_name: row.get(3)?,
+        issue_number: row.get(4)?,
+        workflow_name: row.get(5)?,
+        flavor_id: row.get(6)?,
+        status: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        branch: row.get(10)?,
+        last_worker_id: row.get(11)?,
+        priority: row.get(12)?,
+        archived_at: row.get(13)?,
+        max_concurrent_tasks: row.get(14)?,
+        context_from_mission_id: row.get(15)?,
+        blocking_reason: None,
+        rollup: MissionRollup {
+            total_tokens: row.get(16)?,
+            total_duration_ms: row.get(17)?,
+            attempts: row.get(18)?,
+            first_started_at: row.get(19)?,
+            last_finished_at: row.get(20)?,
+            wall_clock_ms: row.get(21)?,
+        },
+        prompt: row.get(22)?,
+        exclusive: row.get(23)?,
+        acceptance_criteria: serde_json::from_str(&row.get::<_, String>(24)?).unwrap_or_default(),
+        workflow_version: row.get(25)?,
+        replay_of_mission_id: row.get(26)?,
+        high_risk: risk_reasons.is_some(),
+        risk_reasons: risk_reasons
+            .and_then(|r| serde_json::from_str(&r).ok())
+            .unwrap_or_default(),
+        pr_url: row.get(28)?,
+        context_artifacts: serde_json::from_str(&row.get::<_, String>(29)?).unwrap_or_default(),
+    })
+}
 
 pub fn insert_mission(
     conn: &Connection,
@@ -7,6 +74,7 @@ pub fn insert_mission(
     branch: &str,
 ) -> Result<Mission, String> {
     let mission_id = uuid::Uuid::new_v4().to_string();
+    let priority = req.priority.unwrap_or(0);
 
     // Fetch repo owner/name for hydration
     let (owner, name): (String, String) = conn
@@ -18,15 +86,18 @@ pub fn insert_mission(
         .map_err(|e| e.to_string())?;
 
     conn.execute(
-        "INSERT INTO missions (mission_id, repo_id, issue_number, workflow_name, flavor_id, branch) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        "INSERT INTO missions (mission_id, repo_id, issue_number, workflow_name, flavor_id, branch, priority, context_from_mission_id, exclusive)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         params![
             mission_id,
             req.repo_id,
             req.issue_number,
             req.workflow_name,
             req.flavor_id,
-            branch
+            branch,
+            priority,
+            req.context_from_mission_id,
+            req.exclusive
         ],
     )
     .map_err(|e| e.to_string())?;
@@ -44,33 +115,185 @@ pub fn insert_mission(
         updated_at: None,
         branch: branch.to_string(),
         last_worker_id: None,
+        priority,
+        archived_at: None,
+        max_concurrent_tasks: None,
+        context_from_mission_id: req.context_from_mission_id.clone(),
+        blocking_reason: None,
+        rollup: MissionRollup::default(),
+        prompt: None,
+        exclusive: req.exclusive,
+        acceptance_criteria: Vec::new(),
+        workflow_version: None,
+        replay_of_mission_id: None,
+        high_risk: false,
+        risk_reasons: Vec::new(),
+        pr_url: None,
+        context_artifacts: Vec::new(),
     })
 }
 
+/// Record the version of the workflow the mission's tasks were expanded from
+pub fn set_workflow_version(
+    conn: &Connection,
+    mission_id: &str,
+    version: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET workflow_version = ?1 WHERE mission_id = ?2",
+        params![version, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Mark `mission_id` as a replay of `original_id`
+pub fn set_replay_of(conn: &Connection, mission_id: &str, original_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET replay_of_mission_id = ?1 WHERE mission_id = ?2",
+        params![original_id, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Replays of a mission, oldest first, archived ones included
+pub fn list_replays(conn: &Connection, mission_id: &str) -> Result<Vec<Mission>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MISSION_COLUMNS}
+         FROM missions m
+         JOIN repos r ON m.repo_id = r.repo_id
+         WHERE m.replay_of_mission_id = ?1
+         ORDER BY m.created_at ASC, m.rowid ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([mission_id], row_to_mission)
+        .map_err(|e| e.to_string())?;
+
+    let mut missions = Vec::new();
+    for m in rows {
+        missions.push(m.map_err(|e| e.to_string())?);
+    }
+    Ok(missions)
+}
+
+pub fn set_acceptance_criteria(
+    conn: &Connection,
+    mission_id: &str,
+    criteria: &[AcceptanceCriterion],
+) -> Result<(), String> {
+    let json = serde_json::to_string(criteria).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE missions SET acceptance_criteria = ?1 WHERE mission_id = ?2",
+        params![json, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The mission's acceptance criteria; empty for an unknown mission
+pub fn get_acceptance_criteria(
+    conn: &Connection,
+    mission_id: &str,
+) -> Result<Vec<AcceptanceCriterion>, String> {
+    match conn.query_row(
+        "SELECT acceptance_criteria FROM missions WHERE mission_id = ?1",
+        [mission_id],
+        |row| row.get::<_, String>(0),
+    ) {
+        Ok(json) => Ok(serde_json::from_str(&json).unwrap_or_default()),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Record a run's verdicts on the acceptance criteria of its task's mission;
+/// later verdicts replace earlier ones and unknown indexes are ignored
+pub fn record_criteria_results(
+    conn: &Connection,
+    task_id: &str,
+    results: &[CriterionResult],
+) -> Result<(), String> {
+    let mission_id: String = conn
+        .query_row(
+            "SELECT mission_id FROM tasks WHERE task_id = ?1",
+            [task_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut criteria = get_acceptance_criteria(conn, &mission_id)?;
+    for result in results {
+        if let Some(c) = criteria.iter_mut().find(|c| c.index == result.index) {
+            c.result = Some(if result.passed { "pass" } else { "fail" }.to_string());
+            c.note = result.note.clone();
+            c.task_id = Some(task_id.to_string());
+        }
+    }
+    set_acceptance_criteria(conn, &mission_id, &criteria)
+}
+
+/// Flag `mission_id` high-risk, adding `reasons` to any it already has
+pub fn flag_high_risk(
+    conn: &Connection,
+    mission_id: &str,
+    reasons: &[String],
+) -> Result<(), String> {
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT risk_reasons FROM missions WHERE mission_id = ?1",
+            [mission_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut all: Vec<String> = existing
+        .and_then(|r| serde_json::from_str(&r).ok())
+        .unwrap_or_default();
+    for reason in reasons {
+        if !all.contains(reason) {
+            all.push(reason.clone());
+        }
+    }
+    let json = serde_json::to_string(&all).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE missions SET risk_reasons = ?1 WHERE mission_id = ?2",
+        params![json, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Point a not-yet-started mission at another workflow, flavor or prompt
+pub fn update_pending_mission(
+    conn: &Connection,
+    mission_id: &str,
+    workflow_name: &str,
+    flavor_id: Option<&str>,
+    prompt: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET workflow_name = ?1, flavor_id = ?2, prompt = ?3,
+                updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         WHERE mission_id = ?4",
+        params![workflow_name, flavor_id, prompt, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub fn get_mission(conn: &Connection, mission_id: &str) -> Result<Option<Mission>, String> {
-    let mut stmt = conn.prepare(
-        "SELECT m.mission_id, m.repo_id, r.owner, r.name, m.issue_number, m.workflow_name, m.flavor_id, m.status, m.created_at, m.updated_at, m.branch, m.last_worker_id
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MISSION_COLUMNS}
          FROM missions m
          JOIN repos r ON m.repo_id = r.repo_id
          WHERE m.mission_id = ?1"
-    ).map_err(|e| e.to_string())?;
+        ))
+        .map_err(|e| e.to_string())?;
 
-    let mission = stmt.query_row([mission_id], |row| {
-        Ok(Mission {
-            mission_id: row.get(0)?,
-            repo_id: row.get(1)?,
-            repo_owner: row.get(2)?,
-            repo_name: row.get(3)?,
-            issue_number: row.get(4)?,
-            workflow_name: row.get(5)?,
-            flavor_id: row.get(6)?,
-            status: row.get(7)?,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
-            branch: row.get(10)?,
-            last_worker_id: row.get(11)?,
-        })
-    });
+    let mission = stmt.query_row([mission_id], row_to_mission);
 
     match mission {
         Ok(m) => Ok(Some(m)),
@@ -80,30 +303,18 @@ pub fn get_mission(conn: &Connection, mission_id: &str) -> Result<Option<Mission
 }
 
 pub fn list_all(conn: &Connection) -> Result<Vec<Mission>, String> {
-    let mut stmt = conn.prepare(
-        "SELECT m.mission_id, m.repo_id, r.owner, r.name, m.issue_number, m.workflow_name, m.flavor_id, m.status, m.created_at, m.updated_at, m.branch, m.last_worker_id
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MISSION_COLUMNS}
          FROM missions m
          JOIN repos r ON m.repo_id = r.repo_id
+         WHERE m.archived_at IS NULL
          ORDER BY m.created_at DESC"
-    ).map_err(|e| e.to_string())?;
+        ))
+        .map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map([], |row| {
-            Ok(Mission {
-                mission_id: row.get(0)?,
-                repo_id: row.get(1)?,
-                repo_owner: row.get(2)?,
-                repo_name: row.get(3)?,
-                issue_number: row.get(4)?,
-                workflow_name: row.get(5)?,
-                flavor_id: row.get(6)?,
-                status: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-                branch: row.get(10)?,
-                last_worker_id: row.get(11)?,
-            })
-        })
+        .query_map([], row_to_mission)
         .map_err(|e| e.to_string())?;
 
     let mut missions = Vec::new();
@@ -113,32 +324,209 @@ pub fn list_all(conn: &Connection) -> Result<Vec<Mission>, String> {
     Ok(missions)
 }
 
+/// Missions across every repo matching `query`, newest first
+/// Missions matching `query`, newest first. With `after`, only those past
+/// that cursor; with `limit`, at most that many.
+pub fn list_filtered(
+    conn: &Connection,
+    query: &MissionListQuery,
+    after: Option<&Cursor>,
+    limit: Option<i64>,
+) -> Result<Vec<Mission>, String> {
+    let statuses: Vec<&str> = query
+        .status
+        .as_deref()
+        .map(|s| {
+            s.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let statuses_json = serde_json::to_string(&statuses).map_err(|e| e.to_string())?;
+    let pattern = query
+        .q
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(|q| format!("%{}%", q.to_lowercase()));
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MISSION_COLUMNS}
+         FROM missions m
+         JOIN repos r ON m.repo_id = r.repo_id
+         LEFT JOIN github_issues_cache i ON i.repo_id = m.repo_id AND i.number = m.issue_number
+         WHERE r.deleted_at IS NULL
+           AND (?1 OR m.archived_at IS NULL)
+           AND (?2 IS NULL OR m.repo_id = ?2)
+           AND (?3 = '[]' OR m.status IN (SELECT value FROM json_each(?3)))
+           AND (?4 IS NULL
+                OR LOWER(r.owner || '/' || r.name || '#' || m.issue_number) LIKE ?4
+                OR LOWER(COALESCE(i.title, '')) LIKE ?4
+                OR LOWER(m.branch) LIKE ?4
+                OR LOWER(m.workflow_name) LIKE ?4)
+           AND (?5 IS NULL OR (m.created_at, m.mission_id) < (?5, ?6))
+         ORDER BY m.created_at DESC, m.mission_id DESC
+         LIMIT ?7"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map(
+        params![
+            query.archived,
+            query.repo_id,
+            statuses_json,
+            pattern,
+            after.map(|c| &c.created_at),
+            after.map(|c| &c.id),
+            limit.unwrap_or(-1)
+        ],
+        row_to_mission,
+    )
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+/// Mission, task and crab counts across the fleet
+pub fn fleet_summary(conn: &Connection) -> Result<FleetSummary, String> {
+    let mut summary = FleetSummary {
+        repos: conn
+            .query_row(
+                "SELECT COUNT(*) FROM repos WHERE deleted_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?,
+        active_crabs: crabs::list_executors(conn, crabs::ACTIVE_CRAB_SECS)?.len() as i64,
+        ..Default::default()
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.repo_id, r.owner, r.name, m.status, COUNT(*),
+                    SUM(m.risk_reasons IS NOT NULL),
+                    SUM(m.last_finished_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-1 day'))
+             FROM missions m
+             JOIN repos r ON m.repo_id = r.repo_id
+             WHERE r.deleted_at IS NULL AND m.archived_at IS NULL
+             GROUP BY r.repo_id, m.status
+             ORDER BY r.owner, r.name",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                (
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ),
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, Option<i64>>(6)?.unwrap_or(0),
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let ((repo_id, repo_owner, repo_name), status, count, high_risk, recent) =
+            row.map_err(|e| e.to_string())?;
+        *summary
+            .missions_by_status
+            .entry(status.clone())
+            .or_default() += count;
+        summary.high_risk_missions += high_risk;
+        match status.as_str() {
+            "completed" => summary.completed_last_24h += recent,
+            "failed" => summary.failed_last_24h += recent,
+            _ => {}
+        }
+        if summary.by_repo.last().is_none_or(|r| r.repo_id != repo_id) {
+            summary.by_repo.push(RepoMissionCounts {
+                repo_id,
+                repo_owner,
+                repo_name,
+                ..Default::default()
+            });
+        }
+        if let Some(repo) = summary.by_repo.last_mut() {
+            repo.missions_by_status.insert(status, count);
+        }
+    }
+    let live = |r: &RepoMissionCounts, status: &str| {
+        r.missions_by_status.get(status).copied().unwrap_or(0)
+    };
+    summary.busy_repos = summary
+        .by_repo
+        .iter()
+        .filter(|r| live(r, "pending") + live(r, "running") > 0)
+        .count() as i64;
+    summary
+        .by_repo
+        .sort_by_key(|r| std::cmp::Reverse((live(r, "running"), live(r, "pending"))));
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.status, COUNT(*)
+             FROM tasks t
+             JOIN missions m ON t.mission_id = m.mission_id
+             JOIN repos r ON m.repo_id = r.repo_id
+             WHERE r.deleted_at IS NULL AND m.archived_at IS NULL
+             GROUP BY t.status",
+        )
+        .map_err(|e| e.to_string())?;
+    summary.tasks_by_status = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
 pub fn list_by_repo(conn: &Connection, repo_id: &str) -> Result<Vec<Mission>, String> {
-    let mut stmt = conn.prepare(
-        "SELECT m.mission_id, m.repo_id, r.owner, r.name, m.issue_number, m.workflow_name, m.flavor_id, m.status, m.created_at, m.updated_at, m.branch, m.last_worker_id
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MISSION_COLUMNS}
          FROM missions m
          JOIN repos r ON m.repo_id = r.repo_id
-         WHERE m.repo_id = ?1
+         WHERE m.repo_id = ?1 AND m.archived_at IS NULL
          ORDER BY m.created_at DESC"
-    ).map_err(|e| e.to_string())?;
+        ))
+        .map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map([repo_id], |row| {
-            Ok(Mission {
-                mission_id: row.get(0)?,
-                repo_id: row.get(1)?,
-                repo_owner: row.get(2)?,
-                repo_name: row.get(3)?,
-                issue_number: row.get(4)?,
-                workflow_name: row.get(5)?,
-                flavor_id: row.get(6)?,
-                status: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-                branch: row.get(10)?,
-                last_worker_id: row.get(11)?,
-            })
-        })
+        .query_map([repo_id], row_to_mission)
+        .map_err(|e| e.to_string())?;
+
+    let mut missions = Vec::new();
+    for m in rows {
+        missions.push(m.map_err(|e| e.to_string())?);
+    }
+    Ok(missions)
+}
+
+/// Missions changed after `since_seq` and `since_ms` (see `status_changes`),
+/// archived ones included so clients notice the archiving; only `repo_id`'s if given
+pub fn list_changed_since(
+    conn: &Connection,
+    since_seq: i64,
+    since_ms: i64,
+    repo_id: Option<&str>,
+) -> Result<Vec<Mission>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MISSION_COLUMNS}
+         FROM status_changes c
+         JOIN missions m ON c.kind = 'mission' AND c.record_id = m.mission_id
+         JOIN repos r ON m.repo_id = r.repo_id
+         WHERE c.seq > ?1 AND c.changed_ms > ?2 AND (?3 IS NULL OR m.repo_id = ?3)
+         ORDER BY c.seq"
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![since_seq, since_ms, repo_id], row_to_mission)
         .map_err(|e| e.to_string())?;
 
     let mut missions = Vec::new();
@@ -148,6 +536,399 @@ pub fn list_by_repo(conn: &Connection, repo_id: &str) -> Result<Vec<Mission>, St
     Ok(missions)
 }
 
+/// Workflows of `repo_id`'s pending and running missions, with how many use
+/// each, busiest first
+pub fn live_workflows(conn: &Connection, repo_id: &str) -> Result<Vec<(String, i64)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT workflow_name, COUNT(*) FROM missions
+             WHERE repo_id = ?1 AND status IN ('pending', 'running') AND archived_at IS NULL
+             GROUP BY workflow_name
+             ORDER BY COUNT(*) DESC, workflow_name",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([repo_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Live missions without a single task, which nothing would ever schedule
+pub fn list_taskless(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id FROM missions m
+             WHERE m.status IN ('pending', 'running') AND m.archived_at IS NULL
+               AND NOT EXISTS (SELECT 1 FROM tasks t WHERE t.mission_id = m.mission_id)
+             ORDER BY m.created_at",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Recompute the run rollup of the mission that owns `task_id`
+pub fn refresh_rollup_for_task(conn: &Connection, task_id: &str) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "UPDATE missions SET {ROLLUP_SET}
+             WHERE mission_id = (SELECT mission_id FROM tasks WHERE task_id = ?1)"
+        ),
+        [task_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// GitHub repos' missions whose claim is out of date: running ones not yet claimed,
+/// and finished, cancelled or archived ones still claimed. Benchmark missions
+/// never touch the issue.
+pub fn list_issue_claim_changes(conn: &Connection) -> Result<Vec<IssueClaimChange>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id, r.owner, r.name, m.issue_number, m.github_claimed = 0
+             FROM missions m JOIN repos r ON m.repo_id = r.repo_id
+             WHERE r.forge = 'github' AND m.benchmark_id IS NULL
+               AND ((m.github_claimed = 0 AND m.status = 'running' AND m.archived_at IS NULL)
+                OR (m.github_claimed = 1
+                    AND (m.status IN ('completed', 'failed', 'cancelled') OR m.archived_at IS NOT NULL)))
+             ORDER BY m.updated_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(IssueClaimChange {
+                mission_id: row.get(0)?,
+                repo_owner: row.get(1)?,
+                repo_name: row.get(2)?,
+                issue_number: row.get(3)?,
+                claim: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut changes = Vec::new();
+    for change in rows {
+        changes.push(change.map_err(|e| e.to_string())?);
+    }
+    Ok(changes)
+}
+
+pub fn set_github_claimed(
+    conn: &Connection,
+    mission_id: &str,
+    claimed: bool,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET github_claimed = ?1 WHERE mission_id = ?2",
+        params![claimed, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Record the pull request a mission opened; the first one recorded stays
+pub fn set_pr_url(conn: &Connection, mission_id: &str, pr_url: &str) -> Result<bool, String> {
+    let updated = conn
+        .execute(
+            "UPDATE missions SET pr_url = ?1 WHERE mission_id = ?2 AND pr_url IS NULL",
+            params![pr_url, mission_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(updated > 0)
+}
+
+/// Running missions of GitHub repos that opened a pull request
+pub fn list_pr_review_watches(conn: &Connection) -> Result<Vec<PrReviewWatch>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id, r.owner, r.name, m.pr_url
+             FROM missions m JOIN repos r ON m.repo_id = r.repo_id
+             WHERE m.status = 'running' AND m.pr_url IS NOT NULL
+               AND m.archived_at IS NULL AND r.forge = 'github'
+             ORDER BY m.updated_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| {
+        Ok(PrReviewWatch {
+            mission_id: row.get(0)?,
+            repo_owner: row.get(1)?,
+            repo_name: row.get(2)?,
+            pr_url: row.get(3)?,
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+/// Store review feedback on a mission's pull request; comments seen before
+/// are left as they are. Returns how many were new.
+pub fn insert_pr_review_comments(
+    conn: &Connection,
+    mission_id: &str,
+    comments: &[PrReviewComment],
+) -> Result<usize, String> {
+    let mut added = 0;
+    for c in comments {
+        added += conn
+            .execute(
+                "INSERT OR IGNORE INTO pr_review_comments
+                     (mission_id, kind, comment_id, author, body, path, line, url, submitted_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    mission_id,
+                    c.kind,
+                    c.comment_id,
+                    c.author,
+                    c.body,
+                    c.path,
+                    c.line,
+                    c.url,
+                    c.submitted_at
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(added)
+}
+
+/// Whether a mission has review feedback no fix step has been given yet
+pub fn has_untaken_pr_review_comments(conn: &Connection, mission_id: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pr_review_comments WHERE mission_id = ?1 AND taken_at IS NULL)",
+        [mission_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Review feedback no fix step has been given yet, oldest first, marked as
+/// taken so the next fix doesn't get it again
+pub fn take_pr_review_comments(
+    conn: &Connection,
+    mission_id: &str,
+) -> Result<Vec<PrReviewComment>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT kind, comment_id, author, body, path, line, url, submitted_at
+             FROM pr_review_comments WHERE mission_id = ?1 AND taken_at IS NULL
+             ORDER BY submitted_at ASC, kind DESC, path ASC, line ASC, comment_id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let comments = stmt
+        .query_map([mission_id], |row| {
+            Ok(PrReviewComment {
+                kind: row.get(0)?,
+                comment_id: row.get(1)?,
+                author: row.get(2)?,
+                body: row.get(3)?,
+                path: row.get(4)?,
+                line: row.get(5)?,
+                url: row.get(6)?,
+                submitted_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE pr_review_comments SET taken_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         WHERE mission_id = ?1 AND taken_at IS NULL",
+        [mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(comments)
+}
+
+/// The state a mission's issue comment shows, or NULL while it has none
+const ISSUE_COMMENT_STATE: &str = "CASE
+        WHEN m.status IN ('completed', 'failed', 'cancelled') THEN m.status
+        WHEN m.status = 'running' AND m.pr_url IS NOT NULL THEN 'pr_opened'
+        WHEN m.status = 'running' THEN 'running'
+    END";
+
+/// GitHub repos' missions whose issue comment doesn't show their current state. A mission
+/// that finished without a comment only gets one if it finished within the
+/// last day, so turning comments on doesn't post on every old issue.
+pub fn list_issue_comment_changes(conn: &Connection) -> Result<Vec<IssueCommentChange>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT m.mission_id, r.owner, r.name, m.issue_number, {ISSUE_COMMENT_STATE}, m.issue_comment_id
+             FROM missions m JOIN repos r ON m.repo_id = r.repo_id
+             WHERE m.archived_at IS NULL AND r.forge = 'github' AND m.benchmark_id IS NULL
+               AND {ISSUE_COMMENT_STATE} IS NOT NULL
+               AND {ISSUE_COMMENT_STATE} IS NOT m.issue_comment_state
+               AND (m.status = 'running' OR m.issue_comment_id IS NOT NULL
+                    OR m.updated_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-1 day'))
+             ORDER BY m.updated_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| {
+        Ok(IssueCommentChange {
+            mission_id: row.get(0)?,
+            repo_owner: row.get(1)?,
+            repo_name: row.get(2)?,
+            issue_number: row.get(3)?,
+            state: row.get(4)?,
+            comment_id: row.get(5)?,
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+/// Remember the status comment posted for a mission and the state it shows
+pub fn set_issue_comment(
+    conn: &Connection,
+    mission_id: &str,
+    comment_id: i64,
+    state: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET issue_comment_id = ?1, issue_comment_state = ?2 WHERE mission_id = ?3",
+        params![comment_id, state, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// GitHub repos' completed missions whose issue wasn't dealt with yet, with their repo's
+/// issue completion setting
+pub fn list_issue_done_changes(conn: &Connection) -> Result<Vec<IssueDoneChange>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id, r.owner, r.name, m.issue_number, r.issue_completion
+             FROM missions m JOIN repos r ON m.repo_id = r.repo_id
+             WHERE m.status = 'completed' AND m.issue_done = 0 AND m.archived_at IS NULL
+               AND r.forge = 'github' AND m.benchmark_id IS NULL
+             ORDER BY m.updated_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| {
+        let completion: Option<String> = row.get(4)?;
+        Ok(IssueDoneChange {
+            mission_id: row.get(0)?,
+            repo_owner: row.get(1)?,
+            repo_name: row.get(2)?,
+            issue_number: row.get(3)?,
+            completion: completion.and_then(|c| serde_json::from_str(&c).ok()),
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+/// Jira repos' completed missions whose ticket wasn't dealt with yet
+pub fn list_jira_done_changes(conn: &Connection) -> Result<Vec<JiraDoneChange>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id, m.repo_id, m.issue_number
+             FROM missions m JOIN repos r ON m.repo_id = r.repo_id
+             WHERE m.status = 'completed' AND m.issue_done = 0 AND m.archived_at IS NULL
+               AND r.forge = 'jira' AND r.deleted_at IS NULL AND m.benchmark_id IS NULL
+             ORDER BY m.updated_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| {
+        Ok(JiraDoneChange {
+            mission_id: row.get(0)?,
+            repo_id: row.get(1)?,
+            issue_number: row.get(2)?,
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+pub fn set_issue_done(conn: &Connection, mission_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET issue_done = 1 WHERE mission_id = ?1",
+        [mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Missions still in progress on a workflow
+pub fn count_active_for_workflow(conn: &Connection, workflow_name: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM missions
+         WHERE workflow_name = ?1 AND status IN ('pending', 'running')",
+        [workflow_name],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Count the rows a mission deletion would remove
+pub fn deletion_report(
+    conn: &Connection,
+    mission_id: &str,
+) -> Result<MissionDeletionReport, String> {
+    conn.query_row(
+        "SELECT
+            (SELECT COUNT(*) FROM tasks WHERE mission_id = ?1),
+            (SELECT COUNT(*) FROM runs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)),
+            (SELECT COUNT(*) FROM artifacts WHERE run_id IN (SELECT run_id FROM runs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1))),
+            (SELECT COUNT(*) FROM run_logs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)),
+            (SELECT COUNT(*) FROM mission_state_history WHERE mission_id = ?1),
+            (SELECT COUNT(*) FROM burrow_leases WHERE mission_id = ?1),
+            (SELECT COUNT(*) FROM tasks WHERE mission_id = ?1 AND status = 'running')",
+        [mission_id],
+        |row| {
+            Ok(MissionDeletionReport {
+                mission_id: mission_id.to_string(),
+                tasks: row.get(0)?,
+                runs: row.get(1)?,
+                artifacts: row.get(2)?,
+                run_logs: row.get(3)?,
+                state_history: row.get(4)?,
+                burrow_leases: row.get(5)?,
+                running_tasks: row.get(6)?,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Delete a mission and every row that references it, in one transaction
+pub fn delete_mission(conn: &mut Connection, mission_id: &str) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for sql in [
+        "DELETE FROM artifacts WHERE run_id IN (SELECT run_id FROM runs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1))",
+        "DELETE FROM run_logs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM runs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_transitions WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_messages WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_rejections WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_notes WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM tasks WHERE mission_id = ?1",
+        "DELETE FROM mission_state_history WHERE mission_id = ?1",
+        "DELETE FROM burrow_leases WHERE mission_id = ?1",
+        "DELETE FROM pr_review_comments WHERE mission_id = ?1",
+        "DELETE FROM missions WHERE mission_id = ?1",
+    ] {
+        tx.execute(sql, [mission_id]).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+pub fn archive_mission(conn: &Connection, mission_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET archived_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         WHERE mission_id = ?1 AND archived_at IS NULL",
+        [mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub fn recalculate_mission_status(conn: &Connection, mission_id: &str) -> Result<(), String> {
     // Get current mission status before recalculating
     let current_status: String = conn
@@ -173,11 +954,16 @@ pub fn recalculate_mission_status(conn: &Connection, mission_id: &str) -> Result
         return Ok(());
     }
 
-    let new_status = if statuses.iter().any(|s| s == "failed") {
+    let new_status = if statuses.iter().any(|s| s == "skipped") {
+        "cancelled"
+    } else if statuses.iter().any(|s| s == "failed") {
         "failed"
     } else if statuses.iter().all(|s| s == "completed") {
         "completed"
-    } else if statuses.iter().any(|s| s == "running") {
+    } else if statuses
+        .iter()
+        .any(|s| s == "running" || s == "awaiting_checks")
+    {
         "running"
     } else {
         "pending"
@@ -198,6 +984,49 @@ pub fn recalculate_mission_status(conn: &Connection, mission_id: &str) -> Result
     Ok(())
 }
 
+pub fn set_max_concurrent_tasks(
+    conn: &Connection,
+    mission_id: &str,
+    max_concurrent_tasks: Option<i64>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET max_concurrent_tasks = ?1 WHERE mission_id = ?2",
+        params![max_concurrent_tasks, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Record which of `context_from_mission_id`'s artifacts the mission carries
+pub fn set_context_artifacts(
+    conn: &Connection,
+    mission_id: &str,
+    artifacts: &[String],
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET context_artifacts = ?1 WHERE mission_id = ?2",
+        params![
+            serde_json::to_string(artifacts).map_err(|e| e.to_string())?,
+            mission_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn update_workflow_name(
+    conn: &Connection,
+    mission_id: &str,
+    workflow_name: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE missions SET workflow_name = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE mission_id = ?2",
+        params![workflow_name, mission_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub fn insert_state_history_entry(
     conn: &Connection,
     mission_id: &str,
@@ -1,226 +1,129 @@
+pub mod analytics;
+pub mod api_keys;
+pub mod artifacts;
+pub mod benchmarks;
+pub mod blobs;
+pub mod burrows;
+pub mod changelog;
+pub mod consistency;
+pub mod crabs;
+pub mod events;
 pub mod issues;
+pub mod job_leases;
+pub mod migrations;
 pub mod missions;
+pub mod queue;
 pub mod repos;
+pub mod run_logs;
+pub mod scheduler;
 pub mod settings;
+pub mod status_changes;
 pub mod tasks;
+pub mod timings;
+pub mod triggers;
+pub mod workflow_packs;
 pub mod workflows;
 
-use rusqlite::{Connection, params};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::trace::{TraceEvent, TraceEventCodes};
+use rusqlite::{Connection, OpenFlags};
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+use std::sync::{MutexGuard, OnceLock};
+use std::time::Duration;
+
+use crate::metrics::SLOW_QUERIES;
+
+/// Statements slower than this are logged (override with `SLOW_QUERY_MS`)
+const DEFAULT_SLOW_QUERY_MS: u64 = 100;
+
+static SLOW_QUERY_MS: OnceLock<u64> = OnceLock::new();
+
+/// SQLite profile hook: log statements slower than the threshold
+fn log_slow_query(event: TraceEvent<'_>) {
+    let TraceEvent::Profile(stmt, elapsed) = event else {
+        return;
+    };
+    let threshold = *SLOW_QUERY_MS.get_or_init(|| {
+        std::env::var("SLOW_QUERY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SLOW_QUERY_MS)
+    });
+    if elapsed.as_millis() as u64 >= threshold {
+        SLOW_QUERIES.fetch_add(1, Ordering::Relaxed);
+        // The statement's leading text identifies it without dumping the whole query
+        let statement = stmt.sql().split_whitespace().collect::<Vec<_>>().join(" ");
+        let name: String = statement.chars().take(120).collect();
+        tracing::warn!("slow query ({} ms): {}", elapsed.as_millis(), name);
+    }
+}
 
-pub fn init(path: &str) -> Connection {
+/// Open the database without migrating it
+pub fn open(path: &str) -> Connection {
     let conn = Connection::open(path).expect("failed to open database");
+    conn.trace_v2(TraceEventCodes::SQLITE_TRACE_PROFILE, Some(log_slow_query));
     conn.pragma_update(None, "journal_mode", "WAL").unwrap();
     conn.pragma_update(None, "foreign_keys", "ON").unwrap();
-    migrate(&conn);
     conn
 }
 
-pub fn migrate(conn: &Connection) {
-    conn.execute_batch(
-        "CREATE TABLE IF NOT EXISTS repos (
-            repo_id    TEXT PRIMARY KEY,
-            owner      TEXT NOT NULL,
-            name       TEXT NOT NULL,
-            local_path TEXT,
-            repo_url   TEXT,
-            created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            updated_at TEXT,
-            deleted_at TEXT
-        );
-
-        CREATE UNIQUE INDEX IF NOT EXISTS repos_owner_name_uniq
-            ON repos(owner, name) WHERE deleted_at IS NULL;
-
-        CREATE TABLE IF NOT EXISTS github_issues_cache (
-            repo_id    TEXT NOT NULL REFERENCES repos(repo_id),
-            number     INTEGER NOT NULL,
-            title      TEXT NOT NULL,
-            body       TEXT,
-            labels     TEXT NOT NULL DEFAULT '[]',
-            state      TEXT NOT NULL DEFAULT 'open',
-            fetched_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            PRIMARY KEY (repo_id, number)
-        );
-
-        CREATE TABLE IF NOT EXISTS workflow_flavors (
-            flavor_id     TEXT PRIMARY KEY,
-            workflow_name TEXT NOT NULL,
-            name          TEXT NOT NULL,
-            prompt_paths  TEXT NOT NULL DEFAULT '[]',
-            created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            updated_at    TEXT,
-            deleted_at    TEXT
-        );
-
-        CREATE UNIQUE INDEX IF NOT EXISTS workflow_flavors_name_uniq
-            ON workflow_flavors(workflow_name, name) WHERE deleted_at IS NULL;
-
-        CREATE TABLE IF NOT EXISTS settings (
-            key        TEXT PRIMARY KEY,
-            value      TEXT NOT NULL,
-            created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            updated_at TEXT
-        );
-
-        CREATE TABLE IF NOT EXISTS environment_paths (
-            environment   TEXT NOT NULL,
-            resource_type TEXT NOT NULL,
-            resource_name TEXT NOT NULL,
-            path          TEXT NOT NULL,
-            created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            updated_at    TEXT,
-            PRIMARY KEY (environment, resource_type, resource_name)
-        );
-
-        -- Execution Layer (FR-4)
-
-        CREATE TABLE IF NOT EXISTS missions (
-            mission_id    TEXT PRIMARY KEY,
-            repo_id       TEXT NOT NULL REFERENCES repos(repo_id),
-            issue_number  INTEGER NOT NULL,
-            workflow_name TEXT NOT NULL,
-            flavor_id     TEXT REFERENCES workflow_flavors(flavor_id),
-            status        TEXT NOT NULL DEFAULT 'pending',
-            branch        TEXT,
-            created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            updated_at    TEXT,
-            repo_owner    TEXT,
-            repo_name     TEXT,
-            last_worker_id TEXT,
-            FOREIGN KEY (repo_id, issue_number) REFERENCES github_issues_cache(repo_id, number)
-        );
-
-        CREATE TABLE IF NOT EXISTS tasks (
-            task_id          TEXT PRIMARY KEY,
-            mission_id       TEXT NOT NULL REFERENCES missions(mission_id),
-            step_id          TEXT NOT NULL,
-            step_order       INTEGER NOT NULL,
-            assembled_prompt TEXT NOT NULL,
-            status           TEXT NOT NULL DEFAULT 'queued',
-            retry_count      INTEGER DEFAULT 0,
-            max_retries      INTEGER DEFAULT 3,
-            created_at       TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            updated_at       TEXT
-        );
+/// Read-only connections next to the single writer. In WAL mode they see the
+/// last committed state while a write transaction is open.
+pub type ReadPool = r2d2::Pool<SqliteConnectionManager>;
+
+/// Read connections kept open (override with `DB_READ_CONNECTIONS`)
+const DEFAULT_READ_CONNECTIONS: u32 = 4;
+
+/// Open a pool of read-only connections to a database `open` already set up
+pub fn open_read_pool(path: &str) -> Result<ReadPool, String> {
+    let size = std::env::var("DB_READ_CONNECTIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_READ_CONNECTIONS);
+    let manager = SqliteConnectionManager::file(path)
+        .with_flags(
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_init(|conn| {
+            conn.trace_v2(TraceEventCodes::SQLITE_TRACE_PROFILE, Some(log_slow_query));
+            conn.busy_timeout(Duration::from_secs(5))
+        });
+    r2d2::Pool::builder()
+        .max_size(size)
+        .build(manager)
+        .map_err(|e| e.to_string())
+}
 
-        CREATE TABLE IF NOT EXISTS runs (
-            run_id      TEXT PRIMARY KEY,
-            task_id     TEXT NOT NULL REFERENCES tasks(task_id),
-            status      TEXT NOT NULL,
-            logs        TEXT,
-            summary     TEXT,
-            duration_ms INTEGER,
-            tokens_used INTEGER,
-            started_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-            finished_at TEXT
-        );
+/// A connection for queries that don't write
+pub enum ReadConn<'a> {
+    Pooled(r2d2::PooledConnection<SqliteConnectionManager>),
+    /// The writer, when there's no pool or it's exhausted
+    Shared(MutexGuard<'a, Connection>),
+}
 
-        CREATE TABLE IF NOT EXISTS mission_state_history (
-            id         INTEGER PRIMARY KEY,
-            mission_id TEXT NOT NULL REFERENCES missions(mission_id),
-            state      TEXT NOT NULL,
-            entered_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
-            exited_at  TEXT
-        );",
-    )
-    .expect("failed to run migrations");
+impl Deref for ReadConn<'_> {
+    type Target = Connection;
 
-    // Add columns for existing databases (ALTER TABLE cannot use non-constant DEFAULT)
-    for stmt in &[
-        "ALTER TABLE repos ADD COLUMN deleted_at TEXT",
-        "ALTER TABLE repos ADD COLUMN updated_at TEXT",
-        "ALTER TABLE workflow_flavors ADD COLUMN deleted_at TEXT",
-        "ALTER TABLE workflow_flavors ADD COLUMN created_at TEXT",
-        "ALTER TABLE workflow_flavors ADD COLUMN updated_at TEXT",
-        "ALTER TABLE settings ADD COLUMN created_at TEXT",
-        "ALTER TABLE settings ADD COLUMN updated_at TEXT",
-        "ALTER TABLE environment_paths ADD COLUMN created_at TEXT",
-        "ALTER TABLE environment_paths ADD COLUMN updated_at TEXT",
-        "ALTER TABLE missions ADD COLUMN updated_at TEXT",
-        "ALTER TABLE missions ADD COLUMN last_worker_id TEXT",
-        "ALTER TABLE tasks ADD COLUMN updated_at TEXT",
-    ] {
-        match conn.execute(stmt, []) {
-            Ok(_) => {}
-            Err(e) if e.to_string().contains("duplicate column") => {}
-            Err(e) => panic!("migration failed: {e}"),
+    fn deref(&self) -> &Connection {
+        match self {
+            Self::Pooled(conn) => conn,
+            Self::Shared(conn) => conn,
         }
     }
+}
 
-    // Backfill created_at for rows added before the column existed
-    for stmt in &[
-        "UPDATE workflow_flavors SET created_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE created_at IS NULL",
-        "UPDATE settings SET created_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE created_at IS NULL",
-        "UPDATE environment_paths SET created_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE created_at IS NULL",
-    ] {
-        conn.execute(stmt, [])
-            .expect("failed to backfill created_at");
-    }
-
-    // Migration: Remove UNIQUE constraints from repos and workflow_flavors by rebuilding tables
-    // This is necessary because SQLite doesn't support DROP CONSTRAINT.
-    for table in &["repos", "workflow_flavors"] {
-        let sql: String = conn
-            .query_row(
-                "SELECT sql FROM sqlite_master WHERE type='table' AND name=?1",
-                params![table],
-                |row| row.get(0),
-            )
-            .unwrap_or_default();
-
-        let needs_rebuild = if *table == "repos" {
-            sql.contains("UNIQUE") && sql.contains("owner") && sql.contains("name")
-        } else {
-            sql.contains("UNIQUE") && sql.contains("workflow_name") && sql.contains("name")
-        };
-
-        if needs_rebuild {
-            let (create_sql, columns, index_name, index_cols) = if *table == "repos" {
-                (
-                    "CREATE TABLE repos_new (
-                        repo_id    TEXT PRIMARY KEY,
-                        owner      TEXT NOT NULL,
-                        name       TEXT NOT NULL,
-                        local_path TEXT,
-                        repo_url   TEXT,
-                        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-                        updated_at TEXT,
-                        deleted_at TEXT
-                    )",
-                    "repo_id, owner, name, local_path, repo_url, created_at, updated_at, deleted_at",
-                    "repos_owner_name_uniq",
-                    "owner, name",
-                )
-            } else {
-                (
-                    "CREATE TABLE workflow_flavors_new (
-                        flavor_id     TEXT PRIMARY KEY,
-                        workflow_name TEXT NOT NULL,
-                        name          TEXT NOT NULL,
-                        prompt_paths  TEXT NOT NULL DEFAULT '[]',
-                        created_at    TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-                        updated_at    TEXT,
-                        deleted_at    TEXT
-                    )",
-                    "flavor_id, workflow_name, name, prompt_paths, created_at, updated_at, deleted_at",
-                    "workflow_flavors_name_uniq",
-                    "workflow_name, name",
-                )
-            };
+pub fn init(path: &str) -> Connection {
+    let conn = open(path);
+    migrate(&conn);
+    conn
+}
 
-            conn.execute_batch(&format!(
-                "PRAGMA foreign_keys = OFF;
-                 BEGIN TRANSACTION;
-                 {create_sql};
-                 INSERT INTO {table}_new ({columns}) SELECT {columns} FROM {table};
-                 DROP TABLE {table};
-                 ALTER TABLE {table}_new RENAME TO {table};
-                 CREATE UNIQUE INDEX IF NOT EXISTS {index_name} ON {table}({index_cols}) WHERE deleted_at IS NULL;
-                 COMMIT;
-                 PRAGMA foreign_key_check;
-                 PRAGMA foreign_keys = ON;"
-            ))
-            .expect("failed to rebuild table");
-        }
-    }
+/// Bring the schema up to date, destructive steps included; for fresh
+/// databases and tests. Deployments go through `migrations::apply`.
+pub fn migrate(conn: &Connection) {
+    migrations::apply(conn, true).expect("failed to run migrations");
 }
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use rusqlite::Connection;
+
+use crate::db::analytics::iso_timestamp;
+use crate::db::crabs;
+use crate::db::tasks::{self, REPO_ADMITS_MISSION, UNDER_CONCURRENCY_CAP};
+use crate::models::missions::{Mission, QueueDiagnostic};
+use crate::models::tasks::StepConfig;
+
+/// Explain, for every pending mission, what it is waiting on.
+///
+/// Queue positions follow the order `get_next_queued_task` hands out work in
+/// (priority, then age); worker stickiness is ignored since it differs per crab.
+/// Tasks needing an executor no recently polling crab has, or rejected by
+/// every crab that has it, hold no position; neither do tasks of missions
+/// waiting for room in their repo.
+///
+/// Missions holding a position also get start and finish estimates from
+/// history; see [`EtaModel`].
+pub fn diagnostics(conn: &Connection) -> Result<Vec<QueueDiagnostic>, String> {
+    let inventories = crabs::list_executors(conn, crabs::ACTIVE_CRAB_SECS)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT t.mission_id, t.step_config, t.task_id
+             FROM tasks t
+             JOIN missions m ON t.mission_id = m.mission_id
+             JOIN repos r ON m.repo_id = r.repo_id
+             WHERE t.status = 'queued'
+               AND r.deleted_at IS NULL
+               AND m.archived_at IS NULL
+               AND {UNDER_CONCURRENCY_CAP}
+               AND {REPO_ADMITS_MISSION}
+             ORDER BY m.priority DESC, t.created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let queued: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT DISTINCT m.mission_id, m.exclusive
+             FROM tasks t
+             JOIN missions m ON t.mission_id = m.mission_id
+             WHERE t.status = 'queued' AND m.archived_at IS NULL
+               AND NOT {REPO_ADMITS_MISSION}"
+        ))
+        .map_err(|e| e.to_string())?;
+    let waiting_for_repo: HashMap<String, bool> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut queue: Vec<&str> = Vec::new();
+    let mut unmet: HashMap<&str, String> = HashMap::new();
+    let mut rejected: HashMap<&str, String> = HashMap::new();
+    for (mission_id, config_json, task_id) in &queued {
+        let config: StepConfig = serde_json::from_str(config_json).unwrap_or_default();
+        // With no executors at all, the requirement itself is the reason
+        let blocked = config.executor_unmet(&[]).filter(|_| {
+            inventories
+                .iter()
+                .all(|crab| config.executor_unmet(&crab.executors).is_some())
+        });
+        match blocked {
+            Some(why) => {
+                unmet.entry(mission_id.as_str()).or_insert(why);
+            }
+            None => {
+                let rejections = tasks::active_rejections(conn, task_id)?;
+                if tasks::rejected_by_all_capable(&rejections, &config, &inventories) {
+                    rejected
+                        .entry(mission_id.as_str())
+                        .or_insert_with(|| rejections[0].reason.clone());
+                } else {
+                    queue.push(mission_id.as_str());
+                }
+            }
+        }
+    }
+
+    let mut first_position: HashMap<&str, usize> = HashMap::new();
+    for (pos, mission_id) in queue.iter().enumerate() {
+        first_position.entry(mission_id).or_insert(pos);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.mission_id, r.owner, r.name, m.priority, r.deleted_at IS NOT NULL,
+                    COALESCE(SUM(t.status = 'queued'), 0),
+                    COALESCE(SUM(t.status = 'blocked'), 0),
+                    COUNT(t.task_id),
+                    COALESCE(SUM(t.status = 'awaiting_approval'), 0),
+                    m.repo_id
+             FROM missions m
+             JOIN repos r ON m.repo_id = r.repo_id
+             LEFT JOIN tasks t ON t.mission_id = m.mission_id
+             WHERE m.status = 'pending' AND m.archived_at IS NULL
+             GROUP BY m.mission_id
+             ORDER BY m.priority DESC, m.created_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, bool>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, i64>(6)?,
+                row.get::<_, i64>(7)?,
+                row.get::<_, i64>(8)?,
+                row.get::<_, String>(9)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let eta = EtaModel::load(conn, inventories.len())?;
+    let mut out = Vec::new();
+    for row in rows {
+        let (
+            mission_id,
+            repo_owner,
+            repo_name,
+            priority,
+            repo_deleted,
+            queued,
+            blocked,
+            total,
+            awaiting,
+            repo_id,
+        ) = row.map_err(|e| e.to_string())?;
+        let queue_position = first_position.get(mission_id.as_str()).copied();
+
+        let (reason, detail) = if repo_deleted {
+            (
+                "repo_deleted",
+                "the repo was deleted, so its tasks are never handed out".to_string(),
+            )
+        } else if total == 0 {
+            ("no_tasks", "the mission has no tasks".to_string())
+        } else if let Some(0) = queue_position {
+            (
+                "next_in_queue",
+                "first in the queue; waiting for a crab to poll for work".to_string(),
+            )
+        } else if let Some(pos) = queue_position {
+            (
+                "queued_behind",
+                format!(
+                    "queued behind {} task(s) from higher-priority or older missions",
+                    pos
+                ),
+            )
+        } else if let Some(exclusive) = waiting_for_repo.get(&mission_id) {
+            (
+                "repo_busy",
+                if *exclusive {
+                    "exclusive; waiting for the repo's active missions to finish".to_string()
+                } else {
+                    format!(
+                        "waiting for room in {}/{}: an exclusive mission is active or the repo is at max_active_missions_per_repo",
+                        repo_owner, repo_name
+                    )
+                },
+            )
+        } else if let Some(why) = unmet.get(mission_id.as_str()) {
+            (
+                "no_capable_crab",
+                format!("{}; no crab that polled in the last hour reports it", why),
+            )
+        } else if let Some(why) = rejected.get(mission_id.as_str()) {
+            (
+                "rejected_by_all_crabs",
+                format!(
+                    "every crab able to run a queued task rejected it; latest: {}",
+                    why
+                ),
+            )
+        } else if awaiting > 0 {
+            (
+                "awaiting_approval",
+                format!(
+                    "{} task(s) waiting for a human to approve or reject",
+                    awaiting
+                ),
+            )
+        } else {
+            (
+                "stalled",
+                format!(
+                    "{} blocked task(s) with nothing queued or running to unblock them",
+                    blocked
+                ),
+            )
+        };
+
+        let (estimated_start_at, estimated_finish_at) = match queue_position {
+            Some(pos) => eta.estimate(conn, &repo_id, pos)?,
+            None => (None, None),
+        };
+
+        out.push(QueueDiagnostic {
+            mission_id,
+            repo_owner,
+            repo_name,
+            priority,
+            reason: reason.to_string(),
+            detail,
+            queued_tasks: queued,
+            blocked_tasks: blocked,
+            queue_position: queue_position.map(|p| p as i64),
+            estimated_start_at,
+            estimated_finish_at,
+        });
+    }
+    Ok(out)
+}
+
+/// How long queued work has taken lately, for queue ETAs.
+///
+/// A mission at queue position `p` is expected to start once the `p` tasks
+/// ahead of it and the tasks already running have been worked through, the
+/// active crabs taking one task each at a time and every task taking the
+/// average non-debug run duration of the last 30 days. It is expected to
+/// finish after the average wall-clock time of its repo's completed
+/// missions, or of all completed missions when the repo has none. Nothing is
+/// stored, so estimates follow completions as they happen.
+struct EtaModel {
+    now: i64,
+    crabs: usize,
+    running: usize,
+    avg_task_ms: Option<f64>,
+    avg_mission_ms: HashMap<String, f64>,
+    overall_mission_ms: Option<f64>,
+}
+
+impl EtaModel {
+    fn load(conn: &Connection, active_crabs: usize) -> Result<Self, String> {
+        let (now, running, avg_task_ms) = conn
+            .query_row(
+                "SELECT CAST(strftime('%s', 'now') AS INTEGER),
+                        (SELECT COUNT(*) FROM tasks WHERE status = 'running'),
+                        (SELECT AVG(duration_ms) FROM runs
+                         WHERE debug = 0 AND finished_at IS NOT NULL AND duration_ms > 0
+                           AND started_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-30 days'))",
+                [],
+                |row| Ok((row.get(0)?, row.get::<_, i64>(1)?, row.get(2)?)),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT repo_id,
+                        AVG((julianday(last_finished_at) - julianday(first_started_at)) * 86400000),
+                        COUNT(*)
+                 FROM missions
+                 WHERE status = 'completed'
+                   AND first_started_at IS NOT NULL AND last_finished_at IS NOT NULL
+                 GROUP BY repo_id",
+            )
+            .map_err(|e| e.to_string())?;
+        let per_repo: Vec<(String, f64, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        let completed: i64 = per_repo.iter().map(|(_, _, n)| n).sum();
+        let overall_mission_ms = (completed > 0).then(|| {
+            per_repo
+                .iter()
+                .map(|(_, avg, n)| avg * *n as f64)
+                .sum::<f64>()
+                / completed as f64
+        });
+
+        Ok(Self {
+            now,
+            crabs: active_crabs.max(1),
+            running: running as usize,
+            avg_task_ms,
+            avg_mission_ms: per_repo
+                .into_iter()
+                .map(|(repo_id, avg, _)| (repo_id, avg))
+                .collect(),
+            overall_mission_ms,
+        })
+    }
+
+    /// Estimated start and finish of a mission at `position`, when there is
+    /// enough history to say
+    fn estimate(
+        &self,
+        conn: &Connection,
+        repo_id: &str,
+        position: usize,
+    ) -> Result<(Option<String>, Option<String>), String> {
+        let Some(avg_task_ms) = self.avg_task_ms else {
+            return Ok((None, None));
+        };
+        let rounds = (position + self.running) / self.crabs;
+        let start = self.now + (rounds as f64 * avg_task_ms / 1000.0).round() as i64;
+        let finish = self
+            .avg_mission_ms
+            .get(repo_id)
+            .copied()
+            .or(self.overall_mission_ms)
+            .map(|ms| start + (ms / 1000.0).round() as i64);
+        Ok((
+            Some(iso_timestamp(conn, start)?),
+            finish.map(|f| iso_timestamp(conn, f)).transpose()?,
+        ))
+    }
+}
+
+/// Fill in `blocking_reason` on the pending missions in `missions`
+pub fn annotate(conn: &Connection, missions: &mut [Mission]) -> Result<(), String> {
+    if !missions.iter().any(|m| m.status == "pending") {
+        return Ok(());
+    }
+    let mut details: HashMap<String, String> = diagnostics(conn)?
+        .into_iter()
+        .map(|d| (d.mission_id, d.detail))
+        .collect();
+    for mission in missions.iter_mut().filter(|m| m.status == "pending") {
+        mission.blocking_reason = details.remove(&mission.mission_id);
+    }
+    Ok(())
+}
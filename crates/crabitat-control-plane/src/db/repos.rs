@@ -1,6 +1,7 @@
 use rusqlite::{Connection, params};
 
 use crate::models::Repo;
+use crate::models::repos::{ForgeConfig, IssueCompletion, RiskRules};
 
 pub fn insert(
     conn: &Connection,
@@ -21,7 +22,7 @@ pub fn insert(
 
 pub fn list(conn: &Connection) -> Result<Vec<Repo>, String> {
     let mut stmt = conn
-        .prepare("SELECT repo_id, owner, name, local_path, created_at, repo_url, updated_at, deleted_at FROM repos WHERE deleted_at IS NULL ORDER BY created_at DESC")
+        .prepare("SELECT repo_id, owner, name, local_path, created_at, repo_url, updated_at, deleted_at, slack_webhook_url IS NOT NULL, forge, sandbox FROM repos WHERE deleted_at IS NULL ORDER BY created_at DESC")
         .map_err(|e| e.to_string())?;
 
     let repos = stmt
@@ -35,6 +36,9 @@ pub fn list(conn: &Connection) -> Result<Vec<Repo>, String> {
                 repo_url: row.get(5)?,
                 updated_at: row.get(6)?,
                 deleted_at: row.get(7)?,
+                slack_notifications: row.get(8)?,
+                forge: row.get(9)?,
+                sandbox: row.get(10)?,
             })
         })
         .map_err(|e| e.to_string())?
@@ -47,7 +51,7 @@ pub fn list(conn: &Connection) -> Result<Vec<Repo>, String> {
 pub fn get_by_id(conn: &Connection, repo_id: &str) -> Result<Option<Repo>, String> {
     let mut stmt = conn
         .prepare(
-            "SELECT repo_id, owner, name, local_path, created_at, repo_url, updated_at, deleted_at FROM repos WHERE repo_id = ?1",
+            "SELECT repo_id, owner, name, local_path, created_at, repo_url, updated_at, deleted_at, slack_webhook_url IS NOT NULL, forge, sandbox FROM repos WHERE repo_id = ?1",
         )
         .map_err(|e| e.to_string())?;
 
@@ -62,6 +66,9 @@ pub fn get_by_id(conn: &Connection, repo_id: &str) -> Result<Option<Repo>, Strin
                 repo_url: row.get(5)?,
                 updated_at: row.get(6)?,
                 deleted_at: row.get(7)?,
+                slack_notifications: row.get(8)?,
+                forge: row.get(9)?,
+                sandbox: row.get(10)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -72,6 +79,28 @@ pub fn get_by_id(conn: &Connection, repo_id: &str) -> Result<Option<Repo>, Strin
     }
 }
 
+/// Look up an active (non-deleted) repo by its GitHub owner/name
+pub fn get_by_owner_name(
+    conn: &Connection,
+    owner: &str,
+    name: &str,
+) -> Result<Option<Repo>, String> {
+    let repo_id: Option<String> = match conn.query_row(
+        "SELECT repo_id FROM repos WHERE owner = ?1 AND name = ?2 AND deleted_at IS NULL",
+        params![owner, name],
+        |row| row.get(0),
+    ) {
+        Ok(id) => Some(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    match repo_id {
+        Some(id) => get_by_id(conn, &id),
+        None => Ok(None),
+    }
+}
+
 pub fn delete(conn: &Connection, repo_id: &str) -> Result<bool, String> {
     let affected = conn
         .execute(
@@ -96,3 +125,167 @@ pub fn update(
         .map_err(|e| e.to_string())? ;
     Ok(affected > 0)
 }
+
+/// Set or clear the Slack webhook `repo_id`'s notifications go to
+pub fn set_slack_webhook(
+    conn: &Connection,
+    repo_id: &str,
+    webhook_url: Option<&str>,
+) -> Result<bool, String> {
+    let affected = conn
+        .execute(
+            "UPDATE repos SET slack_webhook_url = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE repo_id = ?2 AND deleted_at IS NULL",
+            params![webhook_url, repo_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// Mark `repo_id` as a sandbox copy, or unmark it
+pub fn set_sandbox(conn: &Connection, repo_id: &str, sandbox: bool) -> Result<bool, String> {
+    let affected = conn
+        .execute(
+            "UPDATE repos SET sandbox = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE repo_id = ?2 AND deleted_at IS NULL",
+            params![sandbox, repo_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// Replace `repo_id`'s risk rules
+pub fn set_risk_rules(conn: &Connection, repo_id: &str, rules: &RiskRules) -> Result<bool, String> {
+    let json = serde_json::to_string(rules).map_err(|e| e.to_string())?;
+    let affected = conn
+        .execute(
+            "UPDATE repos SET risk_rules = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE repo_id = ?2 AND deleted_at IS NULL",
+            params![json, repo_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// The risk rules of `repo_id`, empty when none are set; `None` if there is no such repo
+pub fn risk_rules(conn: &Connection, repo_id: &str) -> Result<Option<RiskRules>, String> {
+    match conn.query_row(
+        "SELECT risk_rules FROM repos WHERE repo_id = ?1 AND deleted_at IS NULL",
+        params![repo_id],
+        |row| row.get::<_, Option<String>>(0),
+    ) {
+        Ok(json) => Ok(Some(
+            json.and_then(|j| serde_json::from_str(&j).ok())
+                .unwrap_or_default(),
+        )),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Set what happens to `repo_id`'s issues once their missions complete;
+/// `None` leaves them alone
+pub fn set_issue_completion(
+    conn: &Connection,
+    repo_id: &str,
+    completion: Option<&IssueCompletion>,
+) -> Result<bool, String> {
+    let json = completion
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let affected = conn
+        .execute(
+            "UPDATE repos SET issue_completion = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE repo_id = ?2 AND deleted_at IS NULL",
+            params![json, repo_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// The issue completion setting of `repo_id`, default when unset; `None` if
+/// there is no such repo
+pub fn issue_completion(
+    conn: &Connection,
+    repo_id: &str,
+) -> Result<Option<IssueCompletion>, String> {
+    match conn.query_row(
+        "SELECT issue_completion FROM repos WHERE repo_id = ?1 AND deleted_at IS NULL",
+        params![repo_id],
+        |row| row.get::<_, Option<String>>(0),
+    ) {
+        Ok(json) => Ok(Some(
+            json.and_then(|j| serde_json::from_str(&j).ok())
+                .unwrap_or_default(),
+        )),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Set where `repo_id`'s issues live
+pub fn set_forge(conn: &Connection, repo_id: &str, forge: &ForgeConfig) -> Result<bool, String> {
+    let affected = conn
+        .execute(
+            "UPDATE repos SET forge = ?1, forge_url = ?2, forge_jira = ?3, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE repo_id = ?4 AND deleted_at IS NULL",
+            params![
+                forge.kind,
+                forge.base_url,
+                forge
+                    .jira
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()
+                    .map_err(|e| e.to_string())?,
+                repo_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+fn row_to_forge(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<ForgeConfig> {
+    let jira: Option<String> = row.get(idx + 2)?;
+    Ok(ForgeConfig {
+        kind: row.get(idx)?,
+        base_url: row.get(idx + 1)?,
+        jira: jira.and_then(|j| serde_json::from_str(&j).ok()),
+    })
+}
+
+/// Where `repo_id`'s issues live; `None` if there is no such repo
+pub fn forge(conn: &Connection, repo_id: &str) -> Result<Option<ForgeConfig>, String> {
+    match conn.query_row(
+        "SELECT forge, forge_url, forge_jira FROM repos WHERE repo_id = ?1 AND deleted_at IS NULL",
+        params![repo_id],
+        |row| row_to_forge(row, 0),
+    ) {
+        Ok(forge) => Ok(Some(forge)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Ids and forge settings of the repos whose issues live on forge `kind`
+pub fn list_by_forge(conn: &Connection, kind: &str) -> Result<Vec<(String, ForgeConfig)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT repo_id, forge, forge_url, forge_jira FROM repos
+             WHERE forge = ?1 AND deleted_at IS NULL ORDER BY created_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([kind], |row| Ok((row.get(0)?, row_to_forge(row, 1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// The Slack webhook of `repo_id`, if one is set and the repo isn't deleted
+pub fn slack_webhook(conn: &Connection, repo_id: &str) -> Result<Option<String>, String> {
+    match conn.query_row(
+        "SELECT slack_webhook_url FROM repos WHERE repo_id = ?1 AND deleted_at IS NULL",
+        params![repo_id],
+        |row| row.get(0),
+    ) {
+        Ok(url) => Ok(url),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,141 @@
+use rusqlite::{Connection, params};
+
+use crate::models::run_logs::RunLogChunk;
+
+/// Store a batch of chunks; chunks already stored (a resent batch) are skipped.
+/// Returns the number of new chunks.
+pub fn append(
+    conn: &Connection,
+    run_id: &str,
+    task_id: &str,
+    chunks: &[RunLogChunk],
+) -> Result<usize, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut added = 0;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR IGNORE INTO run_logs (run_id, task_id, seq, stream, content, redactions)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .map_err(|e| e.to_string())?;
+        for chunk in chunks {
+            added += stmt
+                .execute(params![
+                    run_id,
+                    task_id,
+                    chunk.seq,
+                    chunk.stream,
+                    chunk.content,
+                    chunk.redactions
+                ])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(added)
+}
+
+/// Chunks of a run with `seq` greater than `after`, in order
+pub fn list(conn: &Connection, run_id: &str, after: i64) -> Result<Vec<RunLogChunk>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT seq, stream, content, created_at FROM run_logs
+             WHERE run_id = ?1 AND seq > ?2
+             ORDER BY seq ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map(params![run_id, after], |row| {
+        Ok(RunLogChunk {
+            seq: row.get(0)?,
+            stream: row.get(1)?,
+            content: row.get(2)?,
+            created_at: row.get(3)?,
+            redactions: 0,
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+/// Whether the chunk with `seq` is already stored
+pub fn stored(conn: &Connection, run_id: &str, seq: i64) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM run_logs WHERE run_id = ?1 AND seq = ?2)",
+        params![run_id, seq],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// The stored chunk preceding `seq` on the same stream, if any
+pub fn previous(
+    conn: &Connection,
+    run_id: &str,
+    stream: &str,
+    seq: i64,
+) -> Result<Option<RunLogChunk>, String> {
+    match conn.query_row(
+        "SELECT seq, stream, content, created_at, redactions FROM run_logs
+         WHERE run_id = ?1 AND stream = ?2 AND seq < ?3
+         ORDER BY seq DESC LIMIT 1",
+        params![run_id, stream, seq],
+        |row| {
+            Ok(RunLogChunk {
+                seq: row.get(0)?,
+                stream: row.get(1)?,
+                content: row.get(2)?,
+                created_at: row.get(3)?,
+                redactions: row.get(4)?,
+            })
+        },
+    ) {
+        Ok(chunk) => Ok(Some(chunk)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Replace a stored chunk's content
+pub fn set_content(conn: &Connection, run_id: &str, seq: i64, content: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE run_logs SET content = ?3 WHERE run_id = ?1 AND seq = ?2",
+        params![run_id, seq, content],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// Secrets scrubbed from the run's streamed logs so far
+pub fn redactions(conn: &Connection, run_id: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(redactions), 0) FROM run_logs WHERE run_id = ?1",
+        [run_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// The task the run's logs were shipped for, if any were
+pub fn task_for_run(conn: &Connection, run_id: &str) -> Result<Option<String>, String> {
+    match conn.query_row(
+        "SELECT task_id FROM run_logs WHERE run_id = ?1 LIMIT 1",
+        [run_id],
+        |row| row.get(0),
+    ) {
+        Ok(task_id) => Ok(Some(task_id)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether the run has been recorded as finished; its logs won't grow any more
+pub fn run_finished(conn: &Connection, run_id: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM runs WHERE run_id = ?1 AND finished_at IS NOT NULL)",
+        [run_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,168 @@
+use rusqlite::{Connection, Row, params};
+
+use crate::db::crabs;
+use crate::db::settings;
+use crate::db::tasks::{self, REJECTION_EXCLUDE_SECS, REPO_ADMITS_MISSION, UNDER_CONCURRENCY_CAP};
+use crate::models::crabs::Executor;
+use crate::models::scheduler::SchedulerDecision;
+use crate::models::tasks::StepConfig;
+
+/// Decisions kept when the `scheduler_decisions_cap` setting is unset
+pub const DEFAULT_DECISIONS_CAP: i64 = 5000;
+
+const DECISION_COLUMNS: &str = "id, tick_id, worker_id, task_id, mission_id, decision, reason, detail, eligible_crabs, created_at";
+
+fn row_to_decision(row: &Row) -> rusqlite::Result<SchedulerDecision> {
+    let eligible_json: String = row.get(8)?;
+    Ok(SchedulerDecision {
+        id: row.get(0)?,
+        tick_id: row.get(1)?,
+        worker_id: row.get(2)?,
+        task_id: row.get(3)?,
+        mission_id: row.get(4)?,
+        decision: row.get(5)?,
+        reason: row.get(6)?,
+        detail: row.get(7)?,
+        eligible_crabs: serde_json::from_str(&eligible_json).unwrap_or_default(),
+        created_at: row.get(9)?,
+    })
+}
+
+/// Record why each queued task was or wasn't handed to `worker_id` on this
+/// poll, then trim the table to its cap. Must run before the chosen task is
+/// claimed, so the caps and filters are seen as the scheduler saw them.
+pub fn record_tick(
+    conn: &Connection,
+    worker_id: Option<&str>,
+    executors: &[Executor],
+    chosen: Option<&str>,
+) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT t.task_id, t.mission_id, t.step_config,
+                    m.archived_at IS NOT NULL, r.deleted_at IS NOT NULL,
+                    {UNDER_CONCURRENCY_CAP}, {REPO_ADMITS_MISSION},
+                    EXISTS (SELECT 1 FROM task_rejections rej
+                        WHERE rej.task_id = t.task_id AND rej.worker_id = ?1
+                          AND rej.created_at > strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-{REJECTION_EXCLUDE_SECS} seconds'))
+             FROM tasks t
+             JOIN missions m ON t.mission_id = m.mission_id
+             JOIN repos r ON m.repo_id = r.repo_id
+             WHERE t.status = 'queued'
+             ORDER BY m.priority DESC, t.created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    #[allow(clippy::type_complexity)]
+    let queued: Vec<(String, String, String, bool, bool, bool, bool, bool)> = stmt
+        .query_map(params![worker_id], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    if queued.is_empty() {
+        return Ok(());
+    }
+
+    let crabs = crabs::list_executors(conn, crabs::ACTIVE_CRAB_SECS)?;
+    let tick_id = uuid::Uuid::new_v4().to_string();
+    for (
+        task_id,
+        mission_id,
+        config_json,
+        archived,
+        repo_deleted,
+        under_cap,
+        repo_admits,
+        rejected,
+    ) in queued
+    {
+        let config: StepConfig = serde_json::from_str(&config_json).unwrap_or_default();
+        let rejections = tasks::active_rejections(conn, &task_id)?;
+        let eligible: Vec<&str> = crabs
+            .iter()
+            .filter(|c| config.executor_unmet(&c.executors).is_none())
+            .filter(|c| !rejections.iter().any(|r| r.worker_id == c.worker_id))
+            .map(|c| c.worker_id.as_str())
+            .collect();
+
+        let (decision, reason, detail) = if chosen == Some(task_id.as_str()) {
+            ("chosen", None, None)
+        } else if repo_deleted {
+            ("skipped", Some("repo_deleted"), None)
+        } else if archived {
+            ("skipped", Some("mission_archived"), None)
+        } else if !under_cap {
+            ("skipped", Some("mission_at_concurrency_cap"), None)
+        } else if !repo_admits {
+            ("skipped", Some("repo_busy"), None)
+        } else if rejected {
+            ("skipped", Some("rejected_by_worker"), None)
+        } else if let Some(why) = config.executor_unmet(executors) {
+            ("skipped", Some("executor_unmet"), Some(why))
+        } else {
+            (
+                "skipped",
+                Some("outranked"),
+                chosen.map(|c| format!("task {} ranked higher", c)),
+            )
+        };
+        conn.execute(
+            "INSERT INTO scheduler_decisions
+                (tick_id, worker_id, task_id, mission_id, decision, reason, detail, eligible_crabs)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                tick_id,
+                worker_id,
+                task_id,
+                mission_id,
+                decision,
+                reason,
+                detail,
+                serde_json::to_string(&eligible).map_err(|e| e.to_string())?
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let cap = settings::get(conn, "scheduler_decisions_cap")
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_DECISIONS_CAP);
+    conn.execute(
+        "DELETE FROM scheduler_decisions WHERE id <= (SELECT MAX(id) FROM scheduler_decisions) - ?1",
+        [cap],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Most recent decisions first, optionally for one task or one crab
+pub fn list_decisions(
+    conn: &Connection,
+    task_id: Option<&str>,
+    worker_id: Option<&str>,
+    limit: i64,
+) -> Result<Vec<SchedulerDecision>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {DECISION_COLUMNS} FROM scheduler_decisions
+             WHERE (?1 IS NULL OR task_id = ?1) AND (?2 IS NULL OR worker_id = ?2)
+             ORDER BY id DESC
+             LIMIT ?3"
+        ))
+        .map_err(|e| e.to_string())?;
+    stmt.query_map(params![task_id, worker_id, limit], row_to_decision)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
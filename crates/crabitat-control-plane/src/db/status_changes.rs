@@ -0,0 +1,30 @@
+use rusqlite::{Connection, params};
+
+/// The newest change sequence number; 0 before anything changed
+pub fn latest_seq(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(seq), 0) FROM status_changes",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Missions deleted after `since_seq` and `since_ms`
+pub fn deleted_missions_since(
+    conn: &Connection,
+    since_seq: i64,
+    since_ms: i64,
+) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT record_id FROM status_changes
+             WHERE kind = 'mission' AND deleted = 1 AND seq > ?1 AND changed_ms > ?2
+             ORDER BY seq",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map(params![since_seq, since_ms], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
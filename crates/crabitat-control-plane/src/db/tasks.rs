@@ -1,5 +1,102 @@
-use crate::models::tasks::{CreateRunRequest, GitInfo, Run, Task, TaskWithGit};
-use rusqlite::{Connection, params};
+use crate::db::blobs;
+use crate::db::missions;
+use crate::db::scheduler;
+use crate::models::crabs::{CrabExecutors, Executor};
+use crate::models::tasks::{
+    ChecksWait, CreateRunRequest, GitInfo, NewTask, Run, RunningOutput, StepConfig, Task,
+    TaskMessage, TaskNote, TaskRejection, TaskTransition, TaskWithGit, can_transition,
+};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::fmt;
+
+/// Filter keeping missions (aliased `m`) below their running-task cap: the
+/// workflow's `max_concurrent_tasks`, else the `max_concurrent_tasks` setting;
+/// unset or 0 means no cap
+pub(crate) const UNDER_CONCURRENCY_CAP: &str = "(SELECT COUNT(*) FROM tasks busy
+      WHERE busy.mission_id = m.mission_id AND busy.status = 'running')
+    < COALESCE(
+        NULLIF(m.max_concurrent_tasks, 0),
+        (SELECT NULLIF(CAST(value AS INTEGER), 0) FROM settings WHERE key = 'max_concurrent_tasks'),
+        9223372036854775807)";
+
+/// Filter keeping missions (aliased `m`) their repo has room for. A mission
+/// is active once any of its tasks left blocked/queued and until it finishes;
+/// an active mission always keeps going. Another may start while the repo has
+/// fewer active missions than the `max_active_missions_per_repo` setting
+/// (unset or 0 means no cap), and only if neither it nor any active one is
+/// exclusive. Branch-per-mission keeps non-exclusive missions from colliding.
+pub(crate) const REPO_ADMITS_MISSION: &str = "(EXISTS (SELECT 1 FROM tasks st
+      WHERE st.mission_id = m.mission_id AND st.status NOT IN ('blocked', 'queued'))
+    OR (NOT EXISTS (SELECT 1 FROM missions act
+          WHERE act.repo_id = m.repo_id AND act.mission_id != m.mission_id
+            AND act.status IN ('pending', 'running') AND act.archived_at IS NULL
+            AND (act.exclusive = 1 OR m.exclusive = 1)
+            AND EXISTS (SELECT 1 FROM tasks st
+                WHERE st.mission_id = act.mission_id AND st.status NOT IN ('blocked', 'queued')))
+      AND (SELECT COUNT(*) FROM missions act
+          WHERE act.repo_id = m.repo_id
+            AND act.status IN ('pending', 'running') AND act.archived_at IS NULL
+            AND EXISTS (SELECT 1 FROM tasks st
+                WHERE st.mission_id = act.mission_id AND st.status NOT IN ('blocked', 'queued')))
+        < COALESCE(
+            (SELECT NULLIF(CAST(value AS INTEGER), 0) FROM settings WHERE key = 'max_active_missions_per_repo'),
+            9223372036854775807)))";
+
+/// How long a crab that rejected a task is kept from being offered it again
+pub const REJECTION_EXCLUDE_SECS: i64 = 1800;
+
+/// Column list shared by every task query; tables must be aliased as `t`.
+const TASK_COLUMNS: &str = "t.task_id, t.mission_id, t.step_id, t.step_order, t.assembled_prompt, t.status, t.retry_count, t.max_retries, t.created_at, t.updated_at, t.prompt_chunks, t.step_config";
+const TASK_COLUMN_COUNT: usize = 12;
+
+fn row_to_task(row: &Row) -> rusqlite::Result<Task> {
+    let chunks_json: String = row.get(10)?;
+    let config_json: String = row.get(11)?;
+    Ok(Task {
+        task_id: row.get(0)?,
+        mission_id: row.get(1)?,
+        step_id: row.get(2)?,
+        step_order: row.get(3)?,
+        assembled_prompt: row.get(4)?,
+        status: row.get(5)?,
+        retry_count: row.get(6)?,
+        max_retries: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        prompt_chunks: serde_json::from_str(&chunks_json).unwrap_or_default(),
+        step_config: serde_json::from_str(&config_json).unwrap_or_default(),
+    })
+}
+
+fn query_tasks<P: rusqlite::Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> Result<Vec<Task>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params, row_to_task)
+        .map_err(|e| e.to_string())?;
+
+    let mut tasks = Vec::new();
+    for task in rows {
+        tasks.push(task.map_err(|e| e.to_string())?);
+    }
+    Ok(tasks)
+}
+
+fn query_task<P: rusqlite::Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> Result<Option<Task>, String> {
+    match conn.query_row(sql, params, row_to_task) {
+        Ok(task) => Ok(Some(task)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
 pub fn insert_task(
     conn: &Connection,
@@ -10,113 +107,163 @@ pub fn insert_task(
     max_retries: i64,
     status: &str,
 ) -> Result<Task, String> {
-    let task_id = uuid::Uuid::new_v4().to_string();
-
-    conn.execute(
-        "INSERT INTO tasks (task_id, mission_id, step_id, step_order, assembled_prompt, max_retries, status)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        params![
-            task_id,
+    insert_new_task(
+        conn,
+        &NewTask {
             mission_id,
             step_id,
             step_order,
             assembled_prompt,
             max_retries,
-            status
+            status,
+            step_config: Default::default(),
+        },
+    )
+}
+
+pub fn insert_new_task(conn: &Connection, new: &NewTask) -> Result<Task, String> {
+    let task_id = uuid::Uuid::new_v4().to_string();
+    let prompt_chunks = blobs::put_chunks(conn, new.assembled_prompt)?;
+    let chunks_json = serde_json::to_string(&prompt_chunks).map_err(|e| e.to_string())?;
+    let config_json = serde_json::to_string(&new.step_config).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO tasks (task_id, mission_id, step_id, step_order, assembled_prompt, max_retries, status, prompt_chunks, step_config)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            task_id,
+            new.mission_id,
+            new.step_id,
+            new.step_order,
+            new.assembled_prompt,
+            new.max_retries,
+            new.status,
+            chunks_json,
+            config_json
         ],
     )
     .map_err(|e| e.to_string())?;
 
     Ok(Task {
         task_id,
-        mission_id: mission_id.to_string(),
-        step_id: step_id.to_string(),
-        step_order,
-        assembled_prompt: assembled_prompt.to_string(),
-        status: status.to_string(),
+        mission_id: new.mission_id.to_string(),
+        step_id: new.step_id.to_string(),
+        step_order: new.step_order,
+        assembled_prompt: new.assembled_prompt.to_string(),
+        status: new.status.to_string(),
         retry_count: 0,
-        max_retries,
+        max_retries: new.max_retries,
         created_at: "".to_string(),
         updated_at: None,
+        prompt_chunks,
+        step_config: new.step_config.clone(),
     })
 }
 
 pub fn list_tasks_for_mission(conn: &Connection, mission_id: &str) -> Result<Vec<Task>, String> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT task_id, mission_id, step_id, step_order, assembled_prompt, status, retry_count, max_retries, created_at, updated_at
-         FROM tasks WHERE mission_id = ?1 ORDER BY step_order ASC",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let rows = stmt
-        .query_map([mission_id], |row| {
-            Ok(Task {
-                task_id: row.get(0)?,
-                mission_id: row.get(1)?,
-                step_id: row.get(2)?,
-                step_order: row.get(3)?,
-                assembled_prompt: row.get(4)?,
-                status: row.get(5)?,
-                retry_count: row.get(6)?,
-                max_retries: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+    query_tasks(
+        conn,
+        &format!(
+            "SELECT {TASK_COLUMNS} FROM tasks t WHERE t.mission_id = ?1 ORDER BY t.step_order ASC"
+        ),
+        [mission_id],
+    )
+}
 
-    let mut tasks = Vec::new();
-    for task in rows {
-        tasks.push(task.map_err(|e| e.to_string())?);
-    }
-    Ok(tasks)
+/// Tasks changed after `since_seq` and `since_ms` (see `status_changes`),
+/// only those of `repo_id`'s missions if given
+pub fn list_tasks_changed_since(
+    conn: &Connection,
+    since_seq: i64,
+    since_ms: i64,
+    repo_id: Option<&str>,
+) -> Result<Vec<Task>, String> {
+    query_tasks(
+        conn,
+        &format!(
+            "SELECT {TASK_COLUMNS} FROM status_changes c
+             JOIN tasks t ON c.kind = 'task' AND c.record_id = t.task_id
+             WHERE c.seq > ?1 AND c.changed_ms > ?2
+               AND (?3 IS NULL OR t.mission_id IN (SELECT mission_id FROM missions WHERE repo_id = ?3))
+             ORDER BY c.seq"
+        ),
+        params![since_seq, since_ms, repo_id],
+    )
 }
 
+/// Next queued task for a crab that reported no executors
 pub fn get_next_queued_task(
     conn: &Connection,
     worker_id: Option<&str>,
 ) -> Result<Option<TaskWithGit>, String> {
-    // Get oldest queued task along with Git info, prioritizing sticky worker if provided
-    let mut stmt = conn.prepare(
-        "SELECT t.task_id, t.mission_id, t.step_id, t.step_order, t.assembled_prompt, t.status, t.retry_count, t.max_retries, t.created_at, t.updated_at,
-                r.repo_url, m.branch, r.local_path
+    next_queued_task_for(conn, worker_id, &[], true)
+}
+
+/// Next queued task a crab with `executors` can run; tasks whose step needs
+/// an executor the crab lacks are passed over. With `record`, the scheduler's
+/// reasoning is kept and the mission sticks to `worker_id`; without it
+/// nothing is written, so a read-only connection will do.
+pub fn next_queued_task_for(
+    conn: &Connection,
+    worker_id: Option<&str>,
+    executors: &[Executor],
+    record: bool,
+) -> Result<Option<TaskWithGit>, String> {
+    // Get oldest queued task along with Git info: highest mission priority first,
+    // then the sticky worker if provided
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TASK_COLUMNS}, r.repo_url, m.branch, r.local_path
          FROM tasks t
          JOIN missions m ON t.mission_id = m.mission_id
          JOIN repos r ON m.repo_id = r.repo_id
          WHERE t.status = 'queued'
            AND r.deleted_at IS NULL
-         ORDER BY (CASE WHEN ?1 IS NOT NULL AND m.last_worker_id = ?1 THEN 1 ELSE 0 END) DESC, t.created_at ASC
-         LIMIT 1"
-    ).map_err(|e| e.to_string())?;
+           AND m.archived_at IS NULL
+           AND {UNDER_CONCURRENCY_CAP}
+           AND {REPO_ADMITS_MISSION}
+           AND NOT EXISTS (SELECT 1 FROM task_rejections rej
+                WHERE rej.task_id = t.task_id AND rej.worker_id = ?1
+                  AND rej.created_at > strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-{REJECTION_EXCLUDE_SECS} seconds'))
+         ORDER BY m.priority DESC,
+                  (CASE WHEN ?1 IS NOT NULL AND m.last_worker_id = ?1 THEN 1 ELSE 0 END) DESC,
+                  t.created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
 
-    let result = stmt.query_row(params![worker_id], |row| {
-        Ok(TaskWithGit {
-            task: Task {
-                task_id: row.get(0)?,
-                mission_id: row.get(1)?,
-                step_id: row.get(2)?,
-                step_order: row.get(3)?,
-                assembled_prompt: row.get(4)?,
-                status: row.get(5)?,
-                retry_count: row.get(6)?,
-                max_retries: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            },
-            git: GitInfo {
-                repo_url: row.get(10)?,
-                branch: row.get(11)?,
-                local_path: row.get(12)?,
-            },
+    let mut rows = stmt
+        .query_map(params![worker_id], |row| {
+            Ok(TaskWithGit {
+                task: row_to_task(row)?,
+                git: GitInfo {
+                    repo_url: row.get(TASK_COLUMN_COUNT)?,
+                    branch: row.get(TASK_COLUMN_COUNT + 1)?,
+                    local_path: row.get(TASK_COLUMN_COUNT + 2)?,
+                },
+            })
         })
-    });
+        .map_err(|e| e.to_string())?;
+    let result = loop {
+        match rows.next() {
+            Some(Ok(next)) if next.task.step_config.executor_unmet(executors).is_some() => {}
+            Some(next) => break next.map(Some),
+            None => break Ok(None),
+        }
+    };
+
+    // Keep the reasoning around for later; failing to is no reason to stall the queue
+    if record && let Ok(chosen) = &result {
+        let chosen_id = chosen.as_ref().map(|c| c.task.task_id.as_str());
+        if let Err(e) = scheduler::record_tick(conn, worker_id, executors, chosen_id) {
+            tracing::warn!("failed to record scheduler decisions: {}", e);
+        }
+    }
 
     match result {
-        Ok(res) => {
+        Ok(Some(res)) => {
             // Stickiness is last-writer-wins: the most recent worker to pick up
             // a task from this mission gets affinity for subsequent tasks.
-            if let Some(wid) = worker_id {
+            if record && let Some(wid) = worker_id {
                 conn.execute(
                     "UPDATE missions SET last_worker_id = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE mission_id = ?2",
                     params![wid, res.task.mission_id],
@@ -125,11 +272,540 @@ pub fn get_next_queued_task(
             }
             Ok(Some(res))
         }
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Ok(None) => Ok(None),
         Err(e) => Err(e.to_string()),
     }
 }
 
+/// Pick the next queued task for `worker_id` and move it to `running` in one
+/// transaction, so two crabs polling at once can never get the same task.
+pub fn claim_next_task(conn: &Connection, worker_id: &str) -> Result<Option<TaskWithGit>, String> {
+    claim_next_task_for(conn, worker_id, &[])
+}
+
+/// [`claim_next_task`] for a crab that reported its executors
+pub fn claim_next_task_for(
+    conn: &Connection,
+    worker_id: &str,
+    executors: &[Executor],
+) -> Result<Option<TaskWithGit>, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let Some(mut next) = next_queued_task_for(&tx, Some(worker_id), executors, true)? else {
+        return Ok(None);
+    };
+    transition_task(
+        &tx,
+        &next.task.task_id,
+        "running",
+        worker_id,
+        Some("claimed"),
+    )?;
+    record_heartbeat(&tx, &next.task.task_id, worker_id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    next.task.status = "running".to_string();
+    Ok(Some(next))
+}
+
+/// Note that `worker_id` is still working on the task. Returns false for unknown tasks.
+pub fn record_heartbeat(conn: &Connection, task_id: &str, worker_id: &str) -> Result<bool, String> {
+    let n = conn
+        .execute(
+            "UPDATE tasks SET heartbeat_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+                    heartbeat_worker_id = ?1
+             WHERE task_id = ?2",
+            params![worker_id, task_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(n > 0)
+}
+
+/// The worker last heard from on a task; `None` if it never ran or doesn't exist
+pub fn holder(conn: &Connection, task_id: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT heartbeat_worker_id FROM tasks WHERE task_id = ?1",
+        [task_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+    .map_err(|e| e.to_string())
+}
+
+/// Running tasks whose crab hasn't been heard from in `stale_secs`, with the
+/// last worker seen on each
+pub fn list_stale_running(
+    conn: &Connection,
+    stale_secs: i64,
+) -> Result<Vec<(Task, Option<String>)>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TASK_COLUMNS}, t.heartbeat_worker_id FROM tasks t
+             WHERE t.status = 'running'
+               AND COALESCE(t.heartbeat_at, t.updated_at, t.created_at)
+                   < strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?1)
+             ORDER BY t.created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([format!("-{} seconds", stale_secs)], |row| {
+            Ok((row_to_task(row)?, row.get(TASK_COLUMN_COUNT)?))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut stale = Vec::new();
+    for row in rows {
+        stale.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(stale)
+}
+
+/// Tasks waiting on CI checks, with their mission's pull request
+pub fn list_awaiting_checks(conn: &Connection) -> Result<Vec<ChecksWait>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.task_id, r.owner, r.forge, m.pr_url FROM tasks t
+             JOIN missions m ON t.mission_id = m.mission_id
+             JOIN repos r ON m.repo_id = r.repo_id
+             WHERE t.status = 'awaiting_checks'
+             ORDER BY t.updated_at ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    stmt.query_map([], |row| {
+        Ok(ChecksWait {
+            task_id: row.get(0)?,
+            repo_owner: row.get(1)?,
+            forge: row.get(2)?,
+            pr_url: row.get(3)?,
+        })
+    })
+    .map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())
+}
+
+/// Running tasks with their crab and when they last showed progress.
+/// Heartbeats don't count; they only show the crab is alive.
+pub fn list_running_output(conn: &Connection) -> Result<Vec<RunningOutput>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TASK_COLUMNS}, t.heartbeat_worker_id, p.last_output_at,
+                    CAST((julianday('now') - julianday(p.last_output_at)) * 86400 AS INTEGER)
+             FROM tasks t
+             JOIN (
+                SELECT c.task_id, COALESCE(
+                    (SELECT MAX(l.created_at) FROM run_logs l
+                     WHERE l.task_id = c.task_id AND julianday(l.created_at) >= julianday(c.claimed_at)),
+                    c.claimed_at) AS last_output_at
+                FROM (
+                    SELECT t2.task_id, COALESCE(
+                        (SELECT MAX(tr.created_at) FROM task_transitions tr
+                         WHERE tr.task_id = t2.task_id AND tr.to_status = 'running'),
+                        t2.updated_at, t2.created_at) AS claimed_at
+                    FROM tasks t2 WHERE t2.status = 'running'
+                ) c
+             ) p ON p.task_id = t.task_id
+             ORDER BY t.created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(RunningOutput {
+                task: row_to_task(row)?,
+                worker_id: row.get(TASK_COLUMN_COUNT)?,
+                last_output_at: row.get(TASK_COLUMN_COUNT + 1)?,
+                silent_secs: row.get(TASK_COLUMN_COUNT + 2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut running = Vec::new();
+    for row in rows {
+        running.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(running)
+}
+
+/// Running tasks whose step timeout plus `grace_secs` has passed since they
+/// were last claimed, with the last worker seen on each
+pub fn list_timed_out_running(
+    conn: &Connection,
+    grace_secs: i64,
+) -> Result<Vec<(Task, Option<String>)>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TASK_COLUMNS}, t.heartbeat_worker_id FROM tasks t
+             WHERE t.status = 'running'
+               AND json_extract(t.step_config, '$.timeout_secs') IS NOT NULL
+               AND (julianday('now') - julianday(COALESCE(
+                       (SELECT MAX(tr.created_at) FROM task_transitions tr
+                        WHERE tr.task_id = t.task_id AND tr.to_status = 'running'),
+                       t.updated_at, t.created_at))) * 86400
+                   > json_extract(t.step_config, '$.timeout_secs') + ?1
+             ORDER BY t.created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([grace_secs], |row| {
+            Ok((row_to_task(row)?, row.get(TASK_COLUMN_COUNT)?))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut timed_out = Vec::new();
+    for row in rows {
+        timed_out.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(timed_out)
+}
+
+#[derive(Debug)]
+pub enum TransitionError {
+    NotFound,
+    Invalid { from: String, to: String },
+    Db(String),
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "task not found"),
+            Self::Invalid { from, to } => {
+                write!(f, "invalid task status transition: {} -> {}", from, to)
+            }
+            Self::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<TransitionError> for String {
+    fn from(e: TransitionError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Move a task to `to` if the state machine allows it, recording who did it and why.
+///
+/// Every status change outside of test fixtures goes through here; moving to
+/// the current status is accepted without recording anything.
+pub fn transition_task(
+    conn: &Connection,
+    task_id: &str,
+    to: &str,
+    actor: &str,
+    reason: Option<&str>,
+) -> Result<(), TransitionError> {
+    let from: String = match conn.query_row(
+        "SELECT status FROM tasks WHERE task_id = ?1",
+        [task_id],
+        |row| row.get(0),
+    ) {
+        Ok(status) => status,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Err(TransitionError::NotFound),
+        Err(e) => return Err(TransitionError::Db(e.to_string())),
+    };
+
+    if from == to {
+        return Ok(());
+    }
+    if !can_transition(&from, to) {
+        return Err(TransitionError::Invalid {
+            from,
+            to: to.to_string(),
+        });
+    }
+
+    // Compare-and-set so a concurrent writer can't slip in between the read and the update
+    let updated = conn
+        .execute(
+            "UPDATE tasks SET status = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+             WHERE task_id = ?2 AND status = ?3",
+            params![to, task_id, from],
+        )
+        .map_err(|e| TransitionError::Db(e.to_string()))?;
+    if updated == 0 {
+        return Err(TransitionError::Invalid {
+            from,
+            to: to.to_string(),
+        });
+    }
+
+    conn.execute(
+        "INSERT INTO task_transitions (task_id, from_status, to_status, actor, reason)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![task_id, from, to, actor, reason],
+    )
+    .map_err(|e| TransitionError::Db(e.to_string()))?;
+    Ok(())
+}
+
+/// Seconds since the task last moved to `status`; `None` if it never did
+pub fn secs_in_status(
+    conn: &Connection,
+    task_id: &str,
+    status: &str,
+) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT CAST(strftime('%s', 'now') AS INTEGER) - CAST(strftime('%s', created_at) AS INTEGER)
+         FROM task_transitions WHERE task_id = ?1 AND to_status = ?2
+         ORDER BY id DESC LIMIT 1",
+        params![task_id, status],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+pub fn list_transitions(conn: &Connection, task_id: &str) -> Result<Vec<TaskTransition>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT task_id, from_status, to_status, actor, reason, created_at
+             FROM task_transitions WHERE task_id = ?1 ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([task_id], |row| {
+            Ok(TaskTransition {
+                task_id: row.get(0)?,
+                from_status: row.get(1)?,
+                to_status: row.get(2)?,
+                actor: row.get(3)?,
+                reason: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut transitions = Vec::new();
+    for t in rows {
+        transitions.push(t.map_err(|e| e.to_string())?);
+    }
+    Ok(transitions)
+}
+
+const MESSAGE_COLUMNS: &str =
+    "message_id, task_id, body, sender, worker_id, created_at, delivered_at";
+
+fn row_to_message(row: &Row) -> rusqlite::Result<TaskMessage> {
+    Ok(TaskMessage {
+        message_id: row.get(0)?,
+        task_id: row.get(1)?,
+        body: row.get(2)?,
+        sender: row.get(3)?,
+        worker_id: row.get(4)?,
+        created_at: row.get(5)?,
+        delivered_at: row.get(6)?,
+    })
+}
+
+/// Queue a message for the crab running `task_id`; it picks it up with its next heartbeat
+pub fn insert_message(
+    conn: &Connection,
+    task_id: &str,
+    body: &str,
+    sender: Option<&str>,
+) -> Result<TaskMessage, String> {
+    let message_id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO task_messages (message_id, task_id, body, sender, worker_id)
+         SELECT ?1, task_id, ?2, ?3, heartbeat_worker_id FROM tasks WHERE task_id = ?4",
+        params![message_id, body, sender, task_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!("SELECT {MESSAGE_COLUMNS} FROM task_messages WHERE message_id = ?1"),
+        [&message_id],
+        row_to_message,
+    )
+    .map_err(|e| e.to_string())
+}
+
+pub fn list_messages(conn: &Connection, task_id: &str) -> Result<Vec<TaskMessage>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MESSAGE_COLUMNS} FROM task_messages WHERE task_id = ?1 ORDER BY created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([task_id], row_to_message)
+        .map_err(|e| e.to_string())?;
+
+    let mut messages = Vec::new();
+    for m in rows {
+        messages.push(m.map_err(|e| e.to_string())?);
+    }
+    Ok(messages)
+}
+
+const REJECTION_COLUMNS: &str = "task_id, worker_id, reason, created_at";
+
+fn row_to_rejection(row: &Row) -> rusqlite::Result<TaskRejection> {
+    Ok(TaskRejection {
+        task_id: row.get(0)?,
+        worker_id: row.get(1)?,
+        reason: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+/// Hand a claimed task back to the queue because `worker_id` can't serve it.
+/// The crab is passed over for the task for [`REJECTION_EXCLUDE_SECS`].
+pub fn reject_task(
+    conn: &Connection,
+    task_id: &str,
+    worker_id: &str,
+    reason: &str,
+) -> Result<TaskRejection, TransitionError> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| TransitionError::Db(e.to_string()))?;
+    transition_task(
+        &tx,
+        task_id,
+        "queued",
+        worker_id,
+        Some(&format!("rejected: {}", reason)),
+    )?;
+    tx.execute(
+        "INSERT INTO task_rejections (task_id, worker_id, reason) VALUES (?1, ?2, ?3)
+         ON CONFLICT(task_id, worker_id) DO UPDATE SET
+            reason = excluded.reason,
+            created_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')",
+        params![task_id, worker_id, reason],
+    )
+    .map_err(|e| TransitionError::Db(e.to_string()))?;
+    tx.commit()
+        .map_err(|e| TransitionError::Db(e.to_string()))?;
+
+    conn.query_row(
+        &format!(
+            "SELECT {REJECTION_COLUMNS} FROM task_rejections WHERE task_id = ?1 AND worker_id = ?2"
+        ),
+        params![task_id, worker_id],
+        row_to_rejection,
+    )
+    .map_err(|e| TransitionError::Db(e.to_string()))
+}
+
+/// Every rejection recorded for a task, newest first
+pub fn list_rejections(conn: &Connection, task_id: &str) -> Result<Vec<TaskRejection>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {REJECTION_COLUMNS} FROM task_rejections
+             WHERE task_id = ?1 ORDER BY created_at DESC, worker_id ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([task_id], row_to_rejection)
+        .map_err(|e| e.to_string())?;
+
+    let mut rejections = Vec::new();
+    for r in rows {
+        rejections.push(r.map_err(|e| e.to_string())?);
+    }
+    Ok(rejections)
+}
+
+/// Rejections still keeping their crab away from the task, newest first
+pub fn active_rejections(conn: &Connection, task_id: &str) -> Result<Vec<TaskRejection>, String> {
+    let cutoff: String = conn
+        .query_row(
+            "SELECT strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?1)",
+            [format!("-{} seconds", REJECTION_EXCLUDE_SECS)],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(list_rejections(conn, task_id)?
+        .into_iter()
+        .filter(|r| r.created_at > cutoff)
+        .collect())
+}
+
+/// True when at least one of `crabs` could run the task and every one that
+/// could has rejected it, so the task will sit in the queue until a new crab
+/// turns up or the rejections expire
+pub fn rejected_by_all_capable(
+    rejections: &[TaskRejection],
+    config: &StepConfig,
+    crabs: &[CrabExecutors],
+) -> bool {
+    let mut capable = crabs
+        .iter()
+        .filter(|crab| config.executor_unmet(&crab.executors).is_none())
+        .peekable();
+    capable.peek().is_some()
+        && capable.all(|crab| rejections.iter().any(|r| r.worker_id == crab.worker_id))
+}
+
+const NOTE_COLUMNS: &str = "note_id, task_id, body, author, created_at";
+
+fn row_to_note(row: &Row) -> rusqlite::Result<TaskNote> {
+    Ok(TaskNote {
+        note_id: row.get(0)?,
+        task_id: row.get(1)?,
+        body: row.get(2)?,
+        author: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+/// Attach an operator note to a task; it becomes part of the task's context
+/// whenever its prompt is assembled
+pub fn insert_note(
+    conn: &Connection,
+    task_id: &str,
+    body: &str,
+    author: Option<&str>,
+) -> Result<TaskNote, String> {
+    let note_id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO task_notes (note_id, task_id, body, author) VALUES (?1, ?2, ?3, ?4)",
+        params![note_id, task_id, body, author],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!("SELECT {NOTE_COLUMNS} FROM task_notes WHERE note_id = ?1"),
+        [&note_id],
+        row_to_note,
+    )
+    .map_err(|e| e.to_string())
+}
+
+pub fn list_notes(conn: &Connection, task_id: &str) -> Result<Vec<TaskNote>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM task_notes WHERE task_id = ?1 ORDER BY created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([task_id], row_to_note)
+        .map_err(|e| e.to_string())?;
+
+    let mut notes = Vec::new();
+    for n in rows {
+        notes.push(n.map_err(|e| e.to_string())?);
+    }
+    Ok(notes)
+}
+
+/// Undelivered messages for a task, marked delivered as they are handed out
+pub fn take_pending_messages(conn: &Connection, task_id: &str) -> Result<Vec<TaskMessage>, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let pending: Vec<TaskMessage> = list_messages(&tx, task_id)?
+        .into_iter()
+        .filter(|m| m.delivered_at.is_none())
+        .collect();
+    tx.execute(
+        "UPDATE task_messages SET delivered_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+         WHERE task_id = ?1 AND delivered_at IS NULL",
+        [task_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(pending)
+}
+
+/// Raw status write without state machine checks or history, for fixtures and
+/// seeding. Runtime code must use [`transition_task`].
 pub fn update_task_status(conn: &Connection, task_id: &str, status: &str) -> Result<(), String> {
     conn.execute(
         "UPDATE tasks SET status = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE task_id = ?2",
@@ -139,6 +815,16 @@ pub fn update_task_status(conn: &Connection, task_id: &str, status: &str) -> Res
     Ok(())
 }
 
+pub fn bump_retry_count(conn: &Connection, task_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE tasks SET retry_count = retry_count + 1 WHERE task_id = ?1",
+        params![task_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Raw requeue with a retry bump; runtime code transitions to `queued` and calls [`bump_retry_count`]
 pub fn increment_task_retry(conn: &Connection, task_id: &str) -> Result<(), String> {
     conn.execute(
         "UPDATE tasks SET status = 'queued', retry_count = retry_count + 1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE task_id = ?1",
@@ -148,59 +834,174 @@ pub fn increment_task_retry(conn: &Connection, task_id: &str) -> Result<(), Stri
     Ok(())
 }
 
+const RUN_COLUMNS: &str = "run_id, task_id, status, logs, summary, duration_ms, tokens_used, started_at, finished_at, score, worker_id, debug, prompt_override, assigned_worker_id, burrow_mode, next_workflow, work_log_hash, redactions, failure_kind, criteria, burrow_path, summary_hash, network";
+
+fn row_to_run(row: &Row) -> rusqlite::Result<Run> {
+    Ok(Run {
+        run_id: row.get(0)?,
+        task_id: row.get(1)?,
+        status: row.get(2)?,
+        logs: row.get(3)?,
+        summary: row.get(4)?,
+        duration_ms: row.get(5)?,
+        tokens_used: row.get(6)?,
+        started_at: row.get(7)?,
+        finished_at: row.get(8)?,
+        score: row.get(9)?,
+        worker_id: row.get(10)?,
+        debug: row.get(11)?,
+        prompt_override: row.get(12)?,
+        assigned_worker_id: row.get(13)?,
+        burrow_mode: row.get(14)?,
+        next_workflow: row.get(15)?,
+        work_log_hash: row.get(16)?,
+        redactions: row.get(17)?,
+        failure_kind: row.get(18)?,
+        criteria: row
+            .get::<_, Option<String>>(19)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        burrow_path: row.get(20)?,
+        summary_hash: row.get(21)?,
+        network: row.get(22)?,
+    })
+}
+
+/// Summaries up to this size are stored inline as they are
+pub const SUMMARY_EXCERPT_BYTES: usize = 2 * 1024;
+
+/// Longer summaries are cut to this before they are stored
+pub const MAX_SUMMARY_BYTES: usize = 256 * 1024;
+
+/// Split a summary into what `runs.summary` keeps and, when it is too long
+/// for that, the blob hash of the full text
+fn put_summary(
+    conn: &Connection,
+    summary: Option<&str>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let Some(summary) = summary else {
+        return Ok((None, None));
+    };
+    if summary.len() <= SUMMARY_EXCERPT_BYTES {
+        return Ok((Some(summary.to_string()), None));
+    }
+    let full = blobs::truncate(summary, MAX_SUMMARY_BYTES, true);
+    let hash = blobs::put(conn, &full)?;
+    Ok((
+        Some(blobs::truncate(summary, SUMMARY_EXCERPT_BYTES, true)),
+        Some(hash),
+    ))
+}
+
+/// Replace a run's summary excerpt with the full summary
+pub fn expand_summary(conn: &Connection, run: &mut Run) -> Result<(), String> {
+    if let Some(hash) = &run.summary_hash
+        && let Some(blob) = blobs::get(conn, hash)?
+    {
+        run.summary = Some(blob.content);
+    }
+    Ok(())
+}
+
+/// Store the crab's working log, if any, returning its blob hash
+fn put_work_log(conn: &Connection, req: &CreateRunRequest) -> Result<Option<String>, String> {
+    req.work_log
+        .as_deref()
+        .map(|log| blobs::put(conn, log))
+        .transpose()
+}
+
 pub fn insert_run(conn: &Connection, task_id: &str, req: &CreateRunRequest) -> Result<Run, String> {
-    let run_id = uuid::Uuid::new_v4().to_string();
+    let run_id = req
+        .run_id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let work_log_hash = put_work_log(conn, req)?;
+    let (summary, summary_hash) = put_summary(conn, req.summary.as_deref())?;
+    let criteria = if req.criteria.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&req.criteria).map_err(|e| e.to_string())?)
+    };
 
     conn.execute(
-        "INSERT INTO runs (run_id, task_id, status, logs, summary, duration_ms, tokens_used, finished_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
+        "INSERT INTO runs (run_id, task_id, status, logs, summary, duration_ms, tokens_used, score, worker_id, burrow_mode, next_workflow, work_log_hash, redactions, failure_kind, criteria, burrow_path, summary_hash, network, finished_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
         params![
             run_id,
             task_id,
             req.status,
             req.logs,
-            req.summary,
+            summary,
             req.duration_ms,
-            req.tokens_used
+            req.tokens_used,
+            req.score,
+            req.worker_id,
+            req.burrow_mode,
+            req.next_workflow,
+            work_log_hash,
+            req.redactions,
+            req.failure_kind,
+            criteria,
+            req.burrow_path,
+            summary_hash,
+            req.network
         ],
     )
     .map_err(|e| e.to_string())?;
+    missions::refresh_rollup_for_task(conn, task_id)?;
+    if !req.criteria.is_empty() {
+        missions::record_criteria_results(conn, task_id, &req.criteria)?;
+    }
 
     Ok(Run {
         run_id,
         task_id: task_id.to_string(),
         status: req.status.clone(),
         logs: req.logs.clone(),
-        summary: req.summary.clone(),
+        summary,
+        summary_hash,
         duration_ms: req.duration_ms,
         tokens_used: req.tokens_used,
         started_at: "".into(),
         finished_at: Some("".into()),
+        score: req.score,
+        worker_id: req.worker_id.clone(),
+        debug: false,
+        prompt_override: None,
+        assigned_worker_id: None,
+        burrow_mode: req.burrow_mode.clone(),
+        burrow_path: req.burrow_path.clone(),
+        next_workflow: req.next_workflow.clone(),
+        work_log_hash,
+        redactions: req.redactions,
+        failure_kind: req.failure_kind.clone(),
+        criteria: req.criteria.clone(),
+        network: req.network.clone(),
     })
 }
 
+pub fn get_run(conn: &Connection, run_id: &str) -> Result<Option<Run>, String> {
+    match conn.query_row(
+        &format!("SELECT {RUN_COLUMNS} FROM runs WHERE run_id = ?1"),
+        [run_id],
+        row_to_run,
+    ) {
+        Ok(run) => Ok(Some(run)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 pub fn list_runs_for_task(conn: &Connection, task_id: &str) -> Result<Vec<Run>, String> {
     let mut stmt = conn
-        .prepare(
-            "SELECT run_id, task_id, status, logs, summary, duration_ms, tokens_used, started_at, finished_at 
-         FROM runs WHERE task_id = ?1 ORDER BY started_at DESC",
-        )
+        .prepare(&format!(
+            "SELECT {RUN_COLUMNS} FROM runs WHERE task_id = ?1 ORDER BY started_at DESC"
+        ))
         .map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map([task_id], |row| {
-            Ok(Run {
-                run_id: row.get(0)?,
-                task_id: row.get(1)?,
-                status: row.get(2)?,
-                logs: row.get(3)?,
-                summary: row.get(4)?,
-                duration_ms: row.get(5)?,
-                tokens_used: row.get(6)?,
-                started_at: row.get(7)?,
-                finished_at: row.get(8)?,
-            })
-        })
+        .query_map([task_id], row_to_run)
         .map_err(|e| e.to_string())?;
 
     let mut runs = Vec::new();
@@ -210,65 +1011,216 @@ pub fn list_runs_for_task(conn: &Connection, task_id: &str) -> Result<Vec<Run>,
     Ok(runs)
 }
 
-pub fn get_task(conn: &Connection, task_id: &str) -> Result<Option<Task>, String> {
+/// Runs changed after `since_seq` and `since_ms` (see `status_changes`),
+/// only those of `repo_id`'s missions if given
+pub fn list_runs_changed_since(
+    conn: &Connection,
+    since_seq: i64,
+    since_ms: i64,
+    repo_id: Option<&str>,
+) -> Result<Vec<Run>, String> {
+    let columns = RUN_COLUMNS
+        .split(", ")
+        .map(|c| format!("r.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {columns} FROM status_changes c
+             JOIN runs r ON c.kind = 'run' AND c.record_id = r.run_id
+             WHERE c.seq > ?1 AND c.changed_ms > ?2
+               AND (?3 IS NULL OR r.task_id IN (
+                   SELECT t.task_id FROM tasks t JOIN missions m ON t.mission_id = m.mission_id
+                   WHERE m.repo_id = ?3))
+             ORDER BY c.seq"
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![since_seq, since_ms, repo_id], row_to_run)
+        .map_err(|e| e.to_string())?;
+
+    let mut runs = Vec::new();
+    for run in rows {
+        runs.push(run.map_err(|e| e.to_string())?);
+    }
+    Ok(runs)
+}
+
+/// The task's newest non-debug run, with its full summary
+pub fn latest_run_for_task(conn: &Connection, task_id: &str) -> Result<Option<Run>, String> {
+    let Some(mut run) = list_runs_for_task(conn, task_id)?
+        .into_iter()
+        .find(|r| !r.debug)
+    else {
+        return Ok(None);
+    };
+    expand_summary(conn, &mut run)?;
+    Ok(Some(run))
+}
+
+/// Fail every pending debug run of a mission's tasks, returning how many there were
+pub fn cancel_pending_debug_runs(conn: &Connection, mission_id: &str) -> Result<i64, String> {
+    let n = conn
+        .execute(
+            "UPDATE runs SET status = 'failed', summary = 'cancelled',
+                    finished_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+             WHERE debug = 1 AND status = 'pending'
+               AND task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+            [mission_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(n as i64)
+}
+
+/// Queue a debug re-execution of a task with the given prompt
+pub fn insert_debug_run(
+    conn: &Connection,
+    task_id: &str,
+    prompt: &str,
+    assigned_worker_id: Option<&str>,
+) -> Result<Run, String> {
+    let run_id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO runs (run_id, task_id, status, debug, prompt_override, assigned_worker_id)
+         VALUES (?1, ?2, 'pending', 1, ?3, ?4)",
+        params![run_id, task_id, prompt, assigned_worker_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get_run(conn, &run_id)?.ok_or_else(|| "run not found after insert".to_string())
+}
+
+/// The oldest pending debug run this worker may execute, left pending.
+/// Runs assigned to a specific crab are only handed to that crab.
+pub fn next_debug_run(conn: &Connection, worker_id: Option<&str>) -> Result<Option<Run>, String> {
+    match conn.query_row(
+        &format!(
+            "SELECT {RUN_COLUMNS} FROM runs
+             WHERE debug = 1 AND status = 'pending'
+               AND (assigned_worker_id IS NULL OR assigned_worker_id = ?1)
+             ORDER BY started_at ASC LIMIT 1"
+        ),
+        params![worker_id],
+        row_to_run,
+    ) {
+        Ok(run) => Ok(Some(run)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Claim the debug run `next_debug_run` would return and mark it running
+pub fn claim_debug_run(conn: &Connection, worker_id: Option<&str>) -> Result<Option<Run>, String> {
+    let Some(run) = next_debug_run(conn, worker_id)? else {
+        return Ok(None);
+    };
+
+    conn.execute(
+        "UPDATE runs SET status = 'running', worker_id = ?1 WHERE run_id = ?2",
+        params![worker_id, run.run_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get_run(conn, &run.run_id)
+}
+
+/// Record the outcome of a claimed debug run of `task_id`. `None` when there
+/// is no such run still in progress.
+pub fn finish_debug_run(
+    conn: &Connection,
+    task_id: &str,
+    run_id: &str,
+    req: &CreateRunRequest,
+) -> Result<Option<Run>, String> {
+    let work_log_hash = put_work_log(conn, req)?;
+    let (summary, summary_hash) = put_summary(conn, req.summary.as_deref())?;
+    let updated = conn
+        .execute(
+            "UPDATE runs SET status = ?1, logs = ?2, summary = ?3, duration_ms = ?4, tokens_used = ?5,
+                    score = ?6, worker_id = COALESCE(?7, worker_id), burrow_mode = ?8,
+                    work_log_hash = ?9, redactions = ?10, failure_kind = ?11, burrow_path = ?12,
+                    summary_hash = ?14, network = ?15, finished_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+             WHERE run_id = ?13 AND task_id = ?16 AND debug = 1 AND finished_at IS NULL",
+            params![
+                req.status,
+                req.logs,
+                summary,
+                req.duration_ms,
+                req.tokens_used,
+                req.score,
+                req.worker_id,
+                req.burrow_mode,
+                work_log_hash,
+                req.redactions,
+                req.failure_kind,
+                req.burrow_path,
+                run_id,
+                summary_hash,
+                req.network,
+                task_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if updated == 0 {
+        return Ok(None);
+    }
+    get_run(conn, run_id)
+}
+
+/// A single task joined with its mission's git info
+pub fn get_task_with_git(conn: &Connection, task_id: &str) -> Result<Option<TaskWithGit>, String> {
     let result = conn.query_row(
-        "SELECT task_id, mission_id, step_id, step_order, assembled_prompt, status, retry_count, max_retries, created_at, updated_at
-         FROM tasks WHERE task_id = ?1",
+        &format!(
+            "SELECT {TASK_COLUMNS}, r.repo_url, m.branch, r.local_path
+             FROM tasks t
+             JOIN missions m ON t.mission_id = m.mission_id
+             JOIN repos r ON m.repo_id = r.repo_id
+             WHERE t.task_id = ?1"
+        ),
         [task_id],
         |row| {
-            Ok(Task {
-                task_id: row.get(0)?,
-                mission_id: row.get(1)?,
-                step_id: row.get(2)?,
-                step_order: row.get(3)?,
-                assembled_prompt: row.get(4)?,
-                status: row.get(5)?,
-                retry_count: row.get(6)?,
-                max_retries: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
+            Ok(TaskWithGit {
+                task: row_to_task(row)?,
+                git: GitInfo {
+                    repo_url: row.get(TASK_COLUMN_COUNT)?,
+                    branch: row.get(TASK_COLUMN_COUNT + 1)?,
+                    local_path: row.get(TASK_COLUMN_COUNT + 2)?,
+                },
             })
         },
     );
 
     match result {
-        Ok(task) => Ok(Some(task)),
+        Ok(res) => Ok(Some(res)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
         Err(e) => Err(e.to_string()),
     }
 }
 
+pub fn get_task(conn: &Connection, task_id: &str) -> Result<Option<Task>, String> {
+    query_task(
+        conn,
+        &format!("SELECT {TASK_COLUMNS} FROM tasks t WHERE t.task_id = ?1"),
+        [task_id],
+    )
+}
+
 pub fn get_next_task_in_mission(
     conn: &Connection,
     mission_id: &str,
     after_step_order: i64,
 ) -> Result<Option<Task>, String> {
-    let result = conn.query_row(
-        "SELECT task_id, mission_id, step_id, step_order, assembled_prompt, status, retry_count, max_retries, created_at, updated_at
-         FROM tasks WHERE mission_id = ?1 AND step_order > ?2
-         ORDER BY step_order ASC LIMIT 1",
+    query_task(
+        conn,
+        &format!(
+            "SELECT {TASK_COLUMNS} FROM tasks t WHERE t.mission_id = ?1 AND t.step_order > ?2
+             ORDER BY t.step_order ASC LIMIT 1"
+        ),
         params![mission_id, after_step_order],
-        |row| {
-            Ok(Task {
-                task_id: row.get(0)?,
-                mission_id: row.get(1)?,
-                step_id: row.get(2)?,
-                step_order: row.get(3)?,
-                assembled_prompt: row.get(4)?,
-                status: row.get(5)?,
-                retry_count: row.get(6)?,
-                max_retries: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        },
-    );
-
-    match result {
-        Ok(task) => Ok(Some(task)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
-    }
+    )
 }
 
 pub fn count_incomplete_at_order(
@@ -289,36 +1241,15 @@ pub fn get_completed_tasks_at_order(
     mission_id: &str,
     step_order: i64,
 ) -> Result<Vec<Task>, String> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT task_id, mission_id, step_id, step_order, assembled_prompt, status, retry_count, max_retries, created_at, updated_at
-             FROM tasks WHERE mission_id = ?1 AND step_order = ?2 AND status = 'completed'
-             ORDER BY created_at ASC",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let rows = stmt
-        .query_map(params![mission_id, step_order], |row| {
-            Ok(Task {
-                task_id: row.get(0)?,
-                mission_id: row.get(1)?,
-                step_id: row.get(2)?,
-                step_order: row.get(3)?,
-                assembled_prompt: row.get(4)?,
-                status: row.get(5)?,
-                retry_count: row.get(6)?,
-                max_retries: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    let mut tasks = Vec::new();
-    for task in rows {
-        tasks.push(task.map_err(|e| e.to_string())?);
-    }
-    Ok(tasks)
+    query_tasks(
+        conn,
+        &format!(
+            "SELECT {TASK_COLUMNS} FROM tasks t
+             WHERE t.mission_id = ?1 AND t.step_order = ?2 AND t.status = 'completed'
+             ORDER BY t.created_at ASC"
+        ),
+        params![mission_id, step_order],
+    )
 }
 
 pub fn get_blocked_tasks_at_order(
@@ -326,36 +1257,15 @@ pub fn get_blocked_tasks_at_order(
     mission_id: &str,
     step_order: i64,
 ) -> Result<Vec<Task>, String> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT task_id, mission_id, step_id, step_order, assembled_prompt, status, retry_count, max_retries, created_at, updated_at
-             FROM tasks WHERE mission_id = ?1 AND step_order = ?2 AND status = 'blocked'
-             ORDER BY created_at ASC",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let rows = stmt
-        .query_map(params![mission_id, step_order], |row| {
-            Ok(Task {
-                task_id: row.get(0)?,
-                mission_id: row.get(1)?,
-                step_id: row.get(2)?,
-                step_order: row.get(3)?,
-                assembled_prompt: row.get(4)?,
-                status: row.get(5)?,
-                retry_count: row.get(6)?,
-                max_retries: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    let mut tasks = Vec::new();
-    for task in rows {
-        tasks.push(task.map_err(|e| e.to_string())?);
-    }
-    Ok(tasks)
+    query_tasks(
+        conn,
+        &format!(
+            "SELECT {TASK_COLUMNS} FROM tasks t
+             WHERE t.mission_id = ?1 AND t.step_order = ?2 AND t.status = 'blocked'
+             ORDER BY t.created_at ASC"
+        ),
+        params![mission_id, step_order],
+    )
 }
 
 pub fn update_task_assembled_prompt(
@@ -363,10 +1273,97 @@ pub fn update_task_assembled_prompt(
     task_id: &str,
     assembled_prompt: &str,
 ) -> Result<(), String> {
+    let prompt_chunks = blobs::put_chunks(conn, assembled_prompt)?;
+    let chunks_json = serde_json::to_string(&prompt_chunks).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "UPDATE tasks SET assembled_prompt = ?1, prompt_chunks = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE task_id = ?3",
+        params![assembled_prompt, chunks_json, task_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn update_task_step_config(
+    conn: &Connection,
+    task_id: &str,
+    step_config: &StepConfig,
+) -> Result<(), String> {
+    let config_json = serde_json::to_string(step_config).map_err(|e| e.to_string())?;
     conn.execute(
-        "UPDATE tasks SET assembled_prompt = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE task_id = ?2",
-        params![assembled_prompt, task_id],
+        "UPDATE tasks SET step_config = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE task_id = ?2",
+        params![config_json, task_id],
     )
     .map_err(|e| e.to_string())?;
     Ok(())
 }
+
+/// Remove every task of a mission along with its runs, artifacts, logs, history,
+/// messages, rejections and notes, so the mission can be expanded afresh
+pub fn delete_tasks_for_mission(conn: &Connection, mission_id: &str) -> Result<(), String> {
+    for sql in [
+        "DELETE FROM artifacts WHERE run_id IN (SELECT run_id FROM runs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1))",
+        "DELETE FROM run_logs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM runs WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_transitions WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_messages WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_rejections WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM task_notes WHERE task_id IN (SELECT task_id FROM tasks WHERE mission_id = ?1)",
+        "DELETE FROM tasks WHERE mission_id = ?1",
+    ] {
+        conn.execute(sql, [mission_id]).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Remove the not-yet-started tasks after `step_order`, along with their runs.
+/// Returns the number of tasks removed.
+pub fn delete_blocked_tasks_after(
+    conn: &Connection,
+    mission_id: &str,
+    step_order: i64,
+) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM artifacts WHERE run_id IN (SELECT run_id FROM runs WHERE task_id IN (
+            SELECT task_id FROM tasks
+            WHERE mission_id = ?1 AND step_order > ?2 AND status = 'blocked'))",
+        params![mission_id, step_order],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "DELETE FROM run_logs WHERE task_id IN (
+            SELECT task_id FROM tasks
+            WHERE mission_id = ?1 AND step_order > ?2 AND status = 'blocked')",
+        params![mission_id, step_order],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "DELETE FROM runs WHERE task_id IN (
+            SELECT task_id FROM tasks
+            WHERE mission_id = ?1 AND step_order > ?2 AND status = 'blocked')",
+        params![mission_id, step_order],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "DELETE FROM tasks WHERE mission_id = ?1 AND step_order > ?2 AND status = 'blocked'",
+        params![mission_id, step_order],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Move every task after `step_order` one tier later, freeing the tier
+/// right after it for an inserted step. Returns the number of tasks moved.
+pub fn shift_tasks_after(
+    conn: &Connection,
+    mission_id: &str,
+    step_order: i64,
+) -> Result<usize, String> {
+    conn.execute(
+        "UPDATE tasks SET step_order = step_order + 1 WHERE mission_id = ?1 AND step_order > ?2",
+        params![mission_id, step_order],
+    )
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use rusqlite::{Connection, params};
+
+use crate::db::tasks;
+use crate::models::missions::{MissionTimings, TaskTiming};
+
+/// Unix milliseconds of a stored timestamp column
+fn unix_ms(column: &str) -> String {
+    format!("CAST(ROUND((julianday({column}) - 2440587.5) * 86400000) AS INTEGER)")
+}
+
+/// Statuses a task doesn't leave on its own
+const FINISHED: &[&str] = &["completed", "failed", "skipped"];
+
+struct Transition {
+    task_id: String,
+    from_status: String,
+    to_status: String,
+    at_ms: i64,
+    at: String,
+}
+
+/// Per-task phase breakdown and critical path of a mission
+pub fn mission_timings(conn: &Connection, mission_id: &str) -> Result<MissionTimings, String> {
+    let now_ms: i64 = conn
+        .query_row(&format!("SELECT {}", unix_ms("'now'")), [], |row| {
+            row.get(0)
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT tr.task_id, tr.from_status, tr.to_status, {}, tr.created_at
+             FROM task_transitions tr JOIN tasks t ON t.task_id = tr.task_id
+             WHERE t.mission_id = ?1 ORDER BY tr.id",
+            unix_ms("tr.created_at")
+        ))
+        .map_err(|e| e.to_string())?;
+    let transitions = stmt
+        .query_map([mission_id], |row| {
+            Ok(Transition {
+                task_id: row.get(0)?,
+                from_status: row.get(1)?,
+                to_status: row.get(2)?,
+                at_ms: row.get(3)?,
+                at: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT t.task_id, {},
+                    COALESCE(SUM(r.duration_ms), 0), COUNT(r.run_id)
+             FROM tasks t LEFT JOIN runs r ON r.task_id = t.task_id AND r.debug = 0
+             WHERE t.mission_id = ?1 GROUP BY t.task_id",
+            unix_ms("t.created_at")
+        ))
+        .map_err(|e| e.to_string())?;
+    let task_stats: HashMap<String, (i64, i64, i64)> = stmt
+        .query_map(params![mission_id], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut first_ms = i64::MAX;
+    let mut last_ms = i64::MIN;
+    let mut timings = Vec::new();
+    for task in tasks::list_tasks_for_mission(conn, mission_id)? {
+        let (created_ms, model_ms, runs) = task_stats
+            .get(&task.task_id)
+            .copied()
+            .unwrap_or((now_ms, 0, 0));
+        let history: Vec<&Transition> = transitions
+            .iter()
+            .filter(|t| t.task_id == task.task_id)
+            .collect();
+
+        // Walk the status history as (status, from, to) segments
+        let mut status = history
+            .first()
+            .map_or(task.status.as_str(), |t| t.from_status.as_str());
+        let mut since_ms = created_ms;
+        let mut spent: HashMap<&str, i64> = HashMap::new();
+        let mut started_at = None;
+        let mut finished_at = None;
+        for t in &history {
+            *spent.entry(status).or_default() += t.at_ms - since_ms;
+            if t.to_status == "running" && started_at.is_none() {
+                started_at = Some(t.at.clone());
+            }
+            status = &t.to_status;
+            since_ms = t.at_ms;
+            finished_at = FINISHED.contains(&status).then(|| t.at.clone());
+        }
+        let end_ms = if FINISHED.contains(&status) {
+            since_ms
+        } else {
+            *spent.entry(status).or_default() += now_ms - since_ms;
+            now_ms
+        };
+        first_ms = first_ms.min(created_ms);
+        last_ms = last_ms.max(end_ms);
+
+        let running_ms = spent.get("running").copied().unwrap_or(0);
+        timings.push(TaskTiming {
+            task_id: task.task_id,
+            step_id: task.step_id,
+            step_order: task.step_order,
+            status: task.status.clone(),
+            blocked_ms: spent.get("blocked").copied().unwrap_or(0),
+            queue_wait_ms: spent.get("queued").copied().unwrap_or(0),
+            setup_ms: (running_ms - model_ms).max(0),
+            model_ms,
+            approval_wait_ms: spent.get("awaiting_approval").copied().unwrap_or(0),
+            runs,
+            started_at,
+            finished_at,
+            on_critical_path: false,
+        });
+    }
+
+    let (critical_path, critical_path_ms) = critical_path(&mut timings);
+    Ok(MissionTimings {
+        mission_id: mission_id.to_string(),
+        wall_ms: if timings.is_empty() {
+            0
+        } else {
+            last_ms - first_ms
+        },
+        tasks: timings,
+        critical_path,
+        critical_path_ms,
+    })
+}
+
+/// Longest chain of active time through the tiers (every task depends on
+/// every task of the tier before it). Marks the tasks on it.
+fn critical_path(timings: &mut [TaskTiming]) -> (Vec<String>, i64) {
+    let mut tiers: Vec<i64> = timings.iter().map(|t| t.step_order).collect();
+    tiers.sort_unstable();
+    tiers.dedup();
+
+    // Per tier, the heaviest chain ending there: (total, task indices)
+    let mut best: (i64, Vec<usize>) = (0, Vec::new());
+    for order in tiers {
+        let heaviest = timings
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.step_order == order)
+            .max_by_key(|(_, t)| t.active_ms());
+        if let Some((idx, task)) = heaviest {
+            best.0 += task.active_ms();
+            best.1.push(idx);
+        }
+    }
+
+    for &idx in &best.1 {
+        timings[idx].on_critical_path = true;
+    }
+    let path = best
+        .1
+        .iter()
+        .map(|&idx| timings[idx].task_id.clone())
+        .collect();
+    (path, best.0)
+}
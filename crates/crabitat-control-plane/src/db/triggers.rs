@@ -0,0 +1,92 @@
+use rusqlite::{Connection, Row, params};
+
+use crate::models::triggers::{CreateTriggerRequest, Trigger, TriggerEvent};
+
+const TRIGGER_COLUMNS: &str =
+    "trigger_id, repo_id, event_type, matcher, workflow_name, flavor_id, priority, created_at";
+
+fn row_to_trigger(row: &Row) -> rusqlite::Result<Trigger> {
+    let matcher_json: String = row.get(3)?;
+    Ok(Trigger {
+        trigger_id: row.get(0)?,
+        repo_id: row.get(1)?,
+        event_type: row.get(2)?,
+        matcher: serde_json::from_str(&matcher_json).unwrap_or_default(),
+        workflow_name: row.get(4)?,
+        flavor_id: row.get(5)?,
+        priority: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+pub fn insert(conn: &Connection, req: &CreateTriggerRequest) -> Result<Trigger, String> {
+    let trigger_id = uuid::Uuid::new_v4().to_string();
+    let matcher_json = serde_json::to_string(&req.matcher).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO triggers (trigger_id, repo_id, event_type, matcher, workflow_name, flavor_id, priority)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            trigger_id,
+            req.repo_id,
+            req.event_type,
+            matcher_json,
+            req.workflow_name,
+            req.flavor_id,
+            req.priority
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get(conn, &trigger_id)?.ok_or_else(|| "trigger not found after insert".to_string())
+}
+
+pub fn get(conn: &Connection, trigger_id: &str) -> Result<Option<Trigger>, String> {
+    let result = conn.query_row(
+        &format!("SELECT {TRIGGER_COLUMNS} FROM triggers WHERE trigger_id = ?1"),
+        [trigger_id],
+        row_to_trigger,
+    );
+
+    match result {
+        Ok(trigger) => Ok(Some(trigger)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// List triggers, optionally for a single repo, highest priority first
+pub fn list(conn: &Connection, repo_id: Option<&str>) -> Result<Vec<Trigger>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TRIGGER_COLUMNS} FROM triggers
+             WHERE ?1 IS NULL OR repo_id = ?1
+             ORDER BY priority DESC, created_at ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![repo_id], row_to_trigger)
+        .map_err(|e| e.to_string())?;
+
+    let mut triggers = Vec::new();
+    for trigger in rows {
+        triggers.push(trigger.map_err(|e| e.to_string())?);
+    }
+    Ok(triggers)
+}
+
+pub fn delete(conn: &Connection, trigger_id: &str) -> Result<bool, String> {
+    let affected = conn
+        .execute("DELETE FROM triggers WHERE trigger_id = ?1", [trigger_id])
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// All triggers matching the event, highest priority first
+pub fn evaluate(conn: &Connection, event: &TriggerEvent) -> Result<Vec<Trigger>, String> {
+    Ok(list(conn, Some(&event.repo_id))?
+        .into_iter()
+        .filter(|t| t.matches(event))
+        .collect())
+}
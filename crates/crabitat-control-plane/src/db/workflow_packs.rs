@@ -0,0 +1,79 @@
+use rusqlite::{Connection, Row, params};
+
+use crate::models::workflows::WorkflowPack;
+
+const PACK_COLUMNS: &str =
+    "name, git_url, git_ref, commit_sha, workflows, installed_at, updated_at";
+
+fn row_to_pack(row: &Row) -> rusqlite::Result<WorkflowPack> {
+    let workflows_json: String = row.get(4)?;
+    Ok(WorkflowPack {
+        name: row.get(0)?,
+        git_url: row.get(1)?,
+        git_ref: row.get(2)?,
+        commit_sha: row.get(3)?,
+        workflows: serde_json::from_str(&workflows_json).unwrap_or_default(),
+        installed_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+/// Record an installed pack, replacing the source and commit of an existing one
+pub fn upsert(
+    conn: &Connection,
+    name: &str,
+    git_url: &str,
+    git_ref: Option<&str>,
+    commit_sha: &str,
+    workflows: &[String],
+) -> Result<WorkflowPack, String> {
+    let workflows_json = serde_json::to_string(workflows).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO workflow_packs (name, git_url, git_ref, commit_sha, workflows)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(name) DO UPDATE SET
+            git_url = excluded.git_url,
+            git_ref = excluded.git_ref,
+            commit_sha = excluded.commit_sha,
+            workflows = excluded.workflows,
+            updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')",
+        params![name, git_url, git_ref, commit_sha, workflows_json],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get(conn, name)?.ok_or_else(|| "pack not found after upsert".to_string())
+}
+
+pub fn get(conn: &Connection, name: &str) -> Result<Option<WorkflowPack>, String> {
+    match conn.query_row(
+        &format!("SELECT {PACK_COLUMNS} FROM workflow_packs WHERE name = ?1"),
+        [name],
+        row_to_pack,
+    ) {
+        Ok(pack) => Ok(Some(pack)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<WorkflowPack>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {PACK_COLUMNS} FROM workflow_packs ORDER BY name ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_pack).map_err(|e| e.to_string())?;
+
+    let mut packs = Vec::new();
+    for pack in rows {
+        packs.push(pack.map_err(|e| e.to_string())?);
+    }
+    Ok(packs)
+}
+
+pub fn delete(conn: &Connection, name: &str) -> Result<bool, String> {
+    let affected = conn
+        .execute("DELETE FROM workflow_packs WHERE name = ?1", [name])
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
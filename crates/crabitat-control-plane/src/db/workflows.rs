@@ -1,5 +1,6 @@
-use crate::models::workflows::WorkflowFlavor;
-use rusqlite::{Connection, params};
+use crate::models::workflows::{StoredWorkflow, WorkflowFlavor};
+use rusqlite::{Connection, Row, params};
+use std::collections::BTreeMap;
 
 pub fn list_flavors_for_workflow(
     conn: &Connection,
@@ -117,3 +118,131 @@ pub fn update_flavor(
 
     Ok(())
 }
+
+const STORED_COLUMNS: &str = "name, content, created_at, updated_at";
+
+fn row_to_stored(row: &Row) -> rusqlite::Result<StoredWorkflow> {
+    Ok(StoredWorkflow {
+        name: row.get(0)?,
+        content: row.get(1)?,
+        prompts: BTreeMap::new(),
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+fn load_prompts(conn: &Connection, wf: &mut StoredWorkflow) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT path, content FROM stored_prompts WHERE workflow_name = ?1")
+        .map_err(|e| e.to_string())?;
+    wf.prompts = stmt
+        .query_map(params![wf.name], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Workflows created through the API, with their prompt files
+pub fn list_stored(conn: &Connection) -> Result<Vec<StoredWorkflow>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {STORED_COLUMNS} FROM stored_workflows ORDER BY name ASC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut workflows = stmt
+        .query_map([], row_to_stored)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    for wf in &mut workflows {
+        load_prompts(conn, wf)?;
+    }
+    Ok(workflows)
+}
+
+pub fn get_stored(conn: &Connection, name: &str) -> Result<Option<StoredWorkflow>, String> {
+    match conn.query_row(
+        &format!("SELECT {STORED_COLUMNS} FROM stored_workflows WHERE name = ?1"),
+        params![name],
+        row_to_stored,
+    ) {
+        Ok(mut wf) => {
+            load_prompts(conn, &mut wf)?;
+            Ok(Some(wf))
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Stored workflow that owns a prompt path, if any
+pub fn prompt_owner(conn: &Connection, path: &str) -> Result<Option<String>, String> {
+    match conn.query_row(
+        "SELECT workflow_name FROM stored_prompts WHERE path = ?1",
+        params![path],
+        |row| row.get(0),
+    ) {
+        Ok(name) => Ok(Some(name)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Create or replace a stored workflow; its prompt files are replaced as a set
+pub fn save_stored(
+    conn: &Connection,
+    name: &str,
+    content: &str,
+    prompts: &BTreeMap<String, String>,
+) -> Result<StoredWorkflow, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO stored_workflows (name, content) VALUES (?1, ?2)
+         ON CONFLICT(name) DO UPDATE SET
+            content = excluded.content,
+            updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')",
+        params![name, content],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM stored_prompts WHERE workflow_name = ?1",
+        params![name],
+    )
+    .map_err(|e| e.to_string())?;
+    for (path, body) in prompts {
+        tx.execute(
+            "INSERT INTO stored_prompts (path, workflow_name, content) VALUES (?1, ?2, ?3)",
+            params![path, name, body],
+        )
+        .map_err(|e| {
+            if e.to_string().contains("UNIQUE constraint failed") {
+                format!("prompt '{}' belongs to another workflow", path)
+            } else {
+                e.to_string()
+            }
+        })?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    get_stored(conn, name)?.ok_or_else(|| "workflow not found after save".to_string())
+}
+
+/// Delete a stored workflow and its prompt files
+pub fn delete_stored(conn: &Connection, name: &str) -> Result<bool, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM stored_prompts WHERE workflow_name = ?1",
+        params![name],
+    )
+    .map_err(|e| e.to_string())?;
+    let affected = tx
+        .execute(
+            "DELETE FROM stored_workflows WHERE name = ?1",
+            params![name],
+        )
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
@@ -0,0 +1,112 @@
+use std::fmt::Display;
+
+use axum::Json;
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Machine-readable error codes carried by every API error body.
+///
+/// Clients should branch on the code rather than the message; the HTTP status
+/// is derived from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 400: the request body or query is malformed or out of range
+    InvalidRequest,
+    /// 401: a signature or credential check failed
+    Unauthorized,
+    /// 403: the API key is valid but lacks the scope the route needs
+    Forbidden,
+    /// 404: the addressed resource doesn't exist
+    NotFound,
+    /// 404: the repo doesn't exist or was deleted
+    RepoNotFound,
+    /// 404: the mission doesn't exist
+    MissionNotFound,
+    /// 404: the task doesn't exist
+    TaskNotFound,
+    /// 404: no workflow with that name is installed
+    WorkflowNotFound,
+    /// 404: nothing is queued for this crab right now; poll again later
+    NoQueuedTasks,
+    /// 409: the resource's current status doesn't allow the operation
+    InvalidState,
+    /// 422: a workflow or workflow pack failed validation
+    InvalidWorkflow,
+    /// 424: the `prompts_root` setting hasn't been configured
+    PromptsRootNotSet,
+    /// 500: the control plane failed, usually in the database
+    Internal,
+    /// 502: GitHub or a git remote failed
+    Upstream,
+    /// 503: the request was dropped before it was handled; send it again
+    Unavailable,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::InvalidRequest => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound
+            | Self::RepoNotFound
+            | Self::MissionNotFound
+            | Self::TaskNotFound
+            | Self::WorkflowNotFound
+            | Self::NoQueuedTasks => StatusCode::NOT_FOUND,
+            Self::InvalidState => StatusCode::CONFLICT,
+            Self::InvalidWorkflow => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::PromptsRootNotSet => StatusCode::FAILED_DEPENDENCY,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Upstream => StatusCode::BAD_GATEWAY,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Whether the same request may succeed if simply sent again later
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            Self::NoQueuedTasks | Self::Internal | Self::Upstream | Self::Unavailable
+        )
+    }
+}
+
+/// JSON body of every error response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+    pub retryable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// Same as `message`; kept for clients that predate error codes
+    pub error: String,
+}
+
+/// Error response in the `(status, body)` shape handlers return
+pub fn api_error(code: ErrorCode, message: impl Display) -> (StatusCode, Json<Value>) {
+    build(code, message.to_string(), None)
+}
+
+/// Like [`api_error`], with structured context for the client
+pub fn api_error_with_details(
+    code: ErrorCode,
+    message: impl Display,
+    details: Value,
+) -> (StatusCode, Json<Value>) {
+    build(code, message.to_string(), Some(details))
+}
+
+fn build(code: ErrorCode, message: String, details: Option<Value>) -> (StatusCode, Json<Value>) {
+    let body = ApiErrorBody {
+        code,
+        retryable: code.retryable(),
+        details,
+        error: message.clone(),
+        message,
+    };
+    (code.status(), Json(json!(body)))
+}
@@ -0,0 +1,745 @@
+//! Where a repo's issues live.
+//!
+//! Repos are on GitHub unless configured otherwise with
+//! `PUT /v1/repos/{repo_id}/forge`. GitLab repos, on gitlab.com or a
+//! self-hosted instance, are read through the GitLab REST API, authenticated
+//! with `GITLAB_TOKEN` when it is set. The repo's owner is the GitLab
+//! namespace, groups and subgroups included. Gitea and Forgejo share an API;
+//! their repos name the instance's `base_url` and are read with `GITEA_TOKEN`
+//! when it is set. Jira repos take their issues from one project of a Jira
+//! site, narrowed by a JQL filter, with `JIRA_EMAIL` and `JIRA_API_TOKEN` as
+//! basic auth; the code itself stays on GitHub.
+//!
+//! Each credential belongs to the one instance the operator names for it in
+//! `GITLAB_URL` (gitlab.com by default), `GITEA_URL` or `JIRA_URL`. Anyone who
+//! may edit a repo may set its `base_url`, so while a credential is set, repos
+//! on any other instance are refused rather than sent it. Claiming issues, status
+//! comments and closing issues go through `gh` and stay GitHub-only; the jobs
+//! doing them skip other forges. Jira tickets are moved along a transition
+//! instead once their mission completes.
+
+use std::future::Future;
+
+use rusqlite::Connection;
+use serde::Deserialize;
+
+use crate::db::repos as repos_db;
+use crate::github;
+use crate::models::Issue;
+use crate::models::repos::{ForgeConfig, JiraConfig};
+
+const GITLAB_URL: &str = "https://gitlab.com";
+
+/// What the issue queue needs from a forge
+pub trait ForgeProvider {
+    /// Open issues of `owner/name`, most recently updated first
+    fn fetch_issues(
+        &self,
+        owner: &str,
+        name: &str,
+    ) -> impl Future<Output = Result<Vec<Issue>, String>> + Send;
+
+    /// Issue `number` of `owner/name`, open or closed; `None` if there is
+    /// no such issue
+    fn fetch_issue(
+        &self,
+        owner: &str,
+        name: &str,
+        number: i64,
+    ) -> impl Future<Output = Result<Option<Issue>, String>> + Send;
+
+    /// Whether pull request (merge request, on GitLab) `number` was merged
+    fn is_merged(
+        &self,
+        owner: &str,
+        name: &str,
+        number: i64,
+    ) -> impl Future<Output = Result<bool, String>> + Send;
+}
+
+/// GitHub, through `gh`
+pub struct GitHub;
+
+impl ForgeProvider for GitHub {
+    async fn fetch_issues(&self, owner: &str, name: &str) -> Result<Vec<Issue>, String> {
+        github::fetch_issues(owner, name).await
+    }
+
+    async fn fetch_issue(
+        &self,
+        owner: &str,
+        name: &str,
+        number: i64,
+    ) -> Result<Option<Issue>, String> {
+        github::fetch_issue(owner, name, number).await
+    }
+
+    async fn is_merged(&self, owner: &str, name: &str, number: i64) -> Result<bool, String> {
+        github::pull_request_merged(owner, name, number).await
+    }
+}
+
+/// A GitLab instance's REST API
+pub struct GitLab {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct GlIssue {
+    iid: i64,
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    state: String,
+}
+
+impl GlIssue {
+    fn into_issue(self) -> Issue {
+        Issue {
+            repo_id: String::new(), // filled by caller
+            number: self.iid,
+            title: self.title,
+            body: self.description,
+            labels: self.labels,
+            // GitLab's `opened` as the `OPEN` GitHub reports
+            state: match self.state.as_str() {
+                "opened" => "OPEN".to_string(),
+                other => other.to_uppercase(),
+            },
+            fetched_at: String::new(), // filled by DB
+            stale: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GlMergeRequest {
+    state: String,
+}
+
+impl GitLab {
+    /// `token` is sent as `PRIVATE-TOKEN`; public projects need none
+    pub fn new(base_url: &str, token: Option<String>) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .user_agent("crabitat-control-plane")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            http,
+        })
+    }
+
+    /// API URL of `owner/name`, with the project path encoded as one segment
+    fn project_url(&self, owner: &str, name: &str) -> String {
+        format!(
+            "{}/api/v4/projects/{}",
+            self.base_url,
+            format!("{owner}/{name}").replace('/', "%2F")
+        )
+    }
+
+    async fn get(&self, url: &str, query: &[(&str, String)]) -> Result<reqwest::Response, String> {
+        let mut request = self.http.get(url).query(query);
+        if let Some(token) = &self.token {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| format!("GitLab request failed: {e}"))?;
+        check_status("GitLab", resp)
+    }
+}
+
+impl ForgeProvider for GitLab {
+    async fn fetch_issues(&self, owner: &str, name: &str) -> Result<Vec<Issue>, String> {
+        let url = format!("{}/issues", self.project_url(owner, name));
+        let mut issues = Vec::new();
+        for page in 1..=github::MAX_ISSUE_PAGES {
+            let resp = self
+                .get(
+                    &url,
+                    &[
+                        ("state", "opened".to_string()),
+                        ("order_by", "updated_at".to_string()),
+                        ("sort", "desc".to_string()),
+                        ("per_page", "100".to_string()),
+                        ("page", page.to_string()),
+                    ],
+                )
+                .await?;
+            let has_next = resp
+                .headers()
+                .get("x-next-page")
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| !v.trim().is_empty());
+            let gl_issues: Vec<GlIssue> = resp
+                .json()
+                .await
+                .map_err(|e| format!("failed to parse GitLab response: {e}"))?;
+            issues.extend(gl_issues.into_iter().map(GlIssue::into_issue));
+            if !has_next {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+
+    async fn fetch_issue(
+        &self,
+        owner: &str,
+        name: &str,
+        number: i64,
+    ) -> Result<Option<Issue>, String> {
+        let url = format!("{}/issues/{}", self.project_url(owner, name), number);
+        match self.get(&url, &[]).await {
+            Ok(resp) => resp
+                .json::<GlIssue>()
+                .await
+                .map(|issue| Some(issue.into_issue()))
+                .map_err(|e| format!("failed to parse GitLab response: {e}")),
+            Err(e) if e.ends_with(NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn is_merged(&self, owner: &str, name: &str, number: i64) -> Result<bool, String> {
+        let url = format!(
+            "{}/merge_requests/{}",
+            self.project_url(owner, name),
+            number
+        );
+        let mr: GlMergeRequest = self
+            .get(&url, &[])
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("failed to parse GitLab response: {e}"))?;
+        Ok(mr.state == "merged")
+    }
+}
+
+/// A Gitea or Forgejo instance's REST API
+pub struct Gitea {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct GtIssue {
+    number: i64,
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    labels: Vec<GtLabel>,
+    state: String,
+    #[serde(default)]
+    updated_at: String,
+    /// Set on pull requests, which share the issue numbering
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct GtLabel {
+    name: String,
+}
+
+impl GtIssue {
+    fn into_issue(self) -> Issue {
+        Issue {
+            repo_id: String::new(), // filled by caller
+            number: self.number,
+            title: self.title,
+            // Gitea sends an empty body rather than none
+            body: self.body.filter(|body| !body.is_empty()),
+            labels: self.labels.into_iter().map(|l| l.name).collect(),
+            state: self.state.to_uppercase(),
+            fetched_at: String::new(), // filled by DB
+            stale: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GtPullRequest {
+    merged: bool,
+}
+
+/// Issues Gitea returns per page at most, unless the instance raised it
+const GITEA_PAGE_SIZE: usize = 50;
+
+impl Gitea {
+    /// `token` is sent as `Authorization: token …`; public repos need none
+    pub fn new(base_url: &str, token: Option<String>) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .user_agent("crabitat-control-plane")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            http,
+        })
+    }
+
+    fn repo_url(&self, owner: &str, name: &str) -> String {
+        format!("{}/api/v1/repos/{}/{}", self.base_url, owner, name)
+    }
+
+    async fn get(&self, url: &str, query: &[(&str, String)]) -> Result<reqwest::Response, String> {
+        let mut request = self.http.get(url).query(query);
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", format!("token {token}"));
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| format!("Gitea request failed: {e}"))?;
+        check_status("Gitea", resp)
+    }
+}
+
+impl ForgeProvider for Gitea {
+    async fn fetch_issues(&self, owner: &str, name: &str) -> Result<Vec<Issue>, String> {
+        let url = format!("{}/issues", self.repo_url(owner, name));
+        let mut gt_issues: Vec<GtIssue> = Vec::new();
+        for page in 1..=github::MAX_ISSUE_PAGES {
+            let batch: Vec<GtIssue> = self
+                .get(
+                    &url,
+                    &[
+                        ("state", "open".to_string()),
+                        ("type", "issues".to_string()),
+                        ("limit", GITEA_PAGE_SIZE.to_string()),
+                        ("page", page.to_string()),
+                    ],
+                )
+                .await?
+                .json()
+                .await
+                .map_err(|e| format!("failed to parse Gitea response: {e}"))?;
+            let last = batch.len() < GITEA_PAGE_SIZE;
+            gt_issues.extend(batch);
+            if last {
+                break;
+            }
+        }
+        // Gitea lists newest first; the queue wants the latest activity first
+        gt_issues.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(gt_issues
+            .into_iter()
+            .filter(|issue| issue.pull_request.is_none())
+            .map(GtIssue::into_issue)
+            .collect())
+    }
+
+    async fn fetch_issue(
+        &self,
+        owner: &str,
+        name: &str,
+        number: i64,
+    ) -> Result<Option<Issue>, String> {
+        let url = format!("{}/issues/{}", self.repo_url(owner, name), number);
+        match self.get(&url, &[]).await {
+            Ok(resp) => {
+                let issue: GtIssue = resp
+                    .json()
+                    .await
+                    .map_err(|e| format!("failed to parse Gitea response: {e}"))?;
+                Ok(issue.pull_request.is_none().then(|| issue.into_issue()))
+            }
+            Err(e) if e.ends_with(NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn is_merged(&self, owner: &str, name: &str, number: i64) -> Result<bool, String> {
+        let url = format!("{}/pulls/{}", self.repo_url(owner, name), number);
+        let pr: GtPullRequest = self
+            .get(&url, &[])
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("failed to parse Gitea response: {e}"))?;
+        Ok(pr.merged)
+    }
+}
+
+/// A Jira site's REST API, for one project's issues
+pub struct Jira {
+    base_url: String,
+    config: JiraConfig,
+    /// Account email and API token
+    auth: Option<(String, String)>,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct JiraSearch {
+    issues: Vec<JiraIssue>,
+    #[serde(default, rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JiraIssue {
+    key: String,
+    fields: JiraFields,
+}
+
+#[derive(Deserialize)]
+struct JiraFields {
+    summary: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    status: JiraStatus,
+}
+
+#[derive(Deserialize)]
+struct JiraStatus {
+    #[serde(rename = "statusCategory")]
+    category: JiraStatusCategory,
+}
+
+#[derive(Deserialize)]
+struct JiraStatusCategory {
+    /// `new`, `indeterminate` or `done`
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct JiraTransitions {
+    transitions: Vec<JiraTransition>,
+}
+
+#[derive(Deserialize)]
+struct JiraTransition {
+    id: String,
+    name: String,
+    to: JiraTransitionTarget,
+}
+
+#[derive(Deserialize)]
+struct JiraTransitionTarget {
+    name: String,
+}
+
+/// Fields asked of Jira for an issue
+const JIRA_FIELDS: &str = "summary,description,labels,status";
+
+/// Issues Jira returns per page at most
+const JIRA_PAGE_SIZE: usize = 100;
+
+impl Jira {
+    /// `auth` is the account's email and API token, sent as basic auth;
+    /// projects open to anonymous users need none
+    pub fn new(
+        base_url: &str,
+        config: JiraConfig,
+        auth: Option<(String, String)>,
+    ) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .user_agent("crabitat-control-plane")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            config,
+            auth,
+            http,
+        })
+    }
+
+    /// Ticket key of the project's issue `number`, e.g. `PLAT-42`
+    fn key(&self, number: i64) -> String {
+        format!("{}-{}", self.config.project, number)
+    }
+
+    /// The repo's issue number for a ticket of the project
+    fn number(&self, key: &str) -> Option<i64> {
+        key.strip_prefix(&self.config.project)?
+            .strip_prefix('-')?
+            .parse()
+            .ok()
+    }
+
+    /// The unresolved issues to queue, most recently updated first
+    fn jql(&self) -> String {
+        let mut jql = format!(
+            "project = \"{}\" AND statusCategory != Done",
+            self.config.project
+        );
+        if let Some(filter) = &self.config.jql {
+            jql.push_str(&format!(" AND ({filter})"));
+        }
+        jql.push_str(" ORDER BY updated DESC");
+        jql
+    }
+
+    fn to_issue(&self, issue: JiraIssue) -> Option<Issue> {
+        Some(Issue {
+            repo_id: String::new(), // filled by caller
+            number: self.number(&issue.key)?,
+            title: issue.fields.summary,
+            body: issue.fields.description.filter(|body| !body.is_empty()),
+            labels: issue.fields.labels,
+            state: if issue.fields.status.category.key == "done" {
+                "CLOSED".to_string()
+            } else {
+                "OPEN".to_string()
+            },
+            fetched_at: String::new(), // filled by DB
+            stale: false,
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        let request = self.http.request(method, url);
+        match &self.auth {
+            Some((email, token)) => request.basic_auth(email, Some(token)),
+            None => request,
+        }
+    }
+
+    async fn get(&self, url: &str, query: &[(&str, String)]) -> Result<reqwest::Response, String> {
+        let resp = self
+            .request(reqwest::Method::GET, url)
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| format!("Jira request failed: {e}"))?;
+        check_status("Jira", resp)
+    }
+
+    /// Move issue `number` along `done_transition`, matched by the
+    /// transition's name or the status it leads to. Returns whether it
+    /// moved; a ticket the transition isn't open to, such as one already
+    /// done, stays as it is.
+    pub async fn complete_issue(&self, number: i64) -> Result<bool, String> {
+        let Some(wanted) = &self.config.done_transition else {
+            return Ok(false);
+        };
+        let url = format!(
+            "{}/rest/api/2/issue/{}/transitions",
+            self.base_url,
+            self.key(number)
+        );
+        let available: JiraTransitions = self
+            .get(&url, &[])
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("failed to parse Jira response: {e}"))?;
+        let Some(transition) = available.transitions.into_iter().find(|t| {
+            t.name.eq_ignore_ascii_case(wanted) || t.to.name.eq_ignore_ascii_case(wanted)
+        }) else {
+            return Ok(false);
+        };
+        let resp = self
+            .request(reqwest::Method::POST, &url)
+            .json(&serde_json::json!({"transition": {"id": transition.id}}))
+            .send()
+            .await
+            .map_err(|e| format!("Jira request failed: {e}"))?;
+        check_status("Jira", resp)?;
+        Ok(true)
+    }
+}
+
+// A Jira project isn't tied to the git repo, so `owner` and `name` go unused
+impl ForgeProvider for Jira {
+    async fn fetch_issues(&self, _owner: &str, _name: &str) -> Result<Vec<Issue>, String> {
+        let url = format!("{}/rest/api/2/search/jql", self.base_url);
+        let mut issues = Vec::new();
+        let mut page_token: Option<String> = None;
+        for _ in 1..=github::MAX_ISSUE_PAGES {
+            let mut query = vec![
+                ("jql", self.jql()),
+                ("fields", JIRA_FIELDS.to_string()),
+                ("maxResults", JIRA_PAGE_SIZE.to_string()),
+            ];
+            if let Some(token) = page_token.take() {
+                query.push(("nextPageToken", token));
+            }
+            let page: JiraSearch = self
+                .get(&url, &query)
+                .await?
+                .json()
+                .await
+                .map_err(|e| format!("failed to parse Jira response: {e}"))?;
+            // Tickets moved in from another project keep their old key
+            issues.extend(page.issues.into_iter().filter_map(|i| self.to_issue(i)));
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        Ok(issues)
+    }
+
+    async fn fetch_issue(
+        &self,
+        _owner: &str,
+        _name: &str,
+        number: i64,
+    ) -> Result<Option<Issue>, String> {
+        let url = format!("{}/rest/api/2/issue/{}", self.base_url, self.key(number));
+        match self.get(&url, &[("fields", JIRA_FIELDS.to_string())]).await {
+            Ok(resp) => {
+                let issue: JiraIssue = resp
+                    .json()
+                    .await
+                    .map_err(|e| format!("failed to parse Jira response: {e}"))?;
+                Ok(self.to_issue(issue))
+            }
+            Err(e) if e.ends_with(NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn is_merged(&self, _owner: &str, _name: &str, _number: i64) -> Result<bool, String> {
+        Err("Jira has no pull requests".to_string())
+    }
+}
+
+/// How [`check_status`] words a 404, so callers can tell "no such thing" apart
+const NOT_FOUND: &str = "HTTP 404 Not Found";
+
+/// `resp` if it succeeded, otherwise an error naming the forge and status
+fn check_status(forge: &str, resp: reqwest::Response) -> Result<reqwest::Response, String> {
+    if !resp.status().is_success() {
+        return Err(format!("{forge} request failed: HTTP {}", resp.status()));
+    }
+    Ok(resp)
+}
+
+/// The forge a repo is configured for
+pub enum Forge {
+    GitHub(GitHub),
+    GitLab(GitLab),
+    Gitea(Gitea),
+    Jira(Jira),
+}
+
+/// `credential`, named `name`, for requests to `base_url`. It is only ever
+/// sent to `bound_url`, the instance the operator configured it for; while
+/// it is set, any other instance is an error.
+pub fn bind_credential<T>(
+    name: &str,
+    credential: Option<T>,
+    bound_url: Option<&str>,
+    base_url: &str,
+) -> Result<Option<T>, String> {
+    let Some(credential) = credential else {
+        return Ok(None);
+    };
+    match bound_url.map(|url| url.trim().trim_end_matches('/')) {
+        Some(url) if url == base_url.trim_end_matches('/') => Ok(Some(credential)),
+        Some(url) => Err(format!("{name} is only sent to {url}, not to {base_url}")),
+        None => Err(format!(
+            "{name} is set but not the URL of the instance it is for"
+        )),
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+impl Forge {
+    pub fn from_config(config: &ForgeConfig) -> Result<Self, String> {
+        match config.kind.as_str() {
+            "github" => Ok(Self::GitHub(GitHub)),
+            "gitlab" => {
+                let bound_url = env("GITLAB_URL").unwrap_or_else(|| GITLAB_URL.to_string());
+                let base_url = config.base_url.as_deref().unwrap_or(&bound_url);
+                let token = bind_credential(
+                    "GITLAB_TOKEN",
+                    env("GITLAB_TOKEN"),
+                    Some(&bound_url),
+                    base_url,
+                )?;
+                GitLab::new(base_url, token).map(Self::GitLab)
+            }
+            "gitea" => {
+                let base_url = config
+                    .base_url
+                    .as_deref()
+                    .ok_or("gitea forge has no base_url")?;
+                let token = bind_credential(
+                    "GITEA_TOKEN",
+                    env("GITEA_TOKEN"),
+                    env("GITEA_URL").as_deref(),
+                    base_url,
+                )?;
+                Gitea::new(base_url, token).map(Self::Gitea)
+            }
+            "jira" => {
+                let base_url = config
+                    .base_url
+                    .as_deref()
+                    .ok_or("jira forge has no base_url")?;
+                let jira = config.jira.clone().ok_or("jira forge has no project")?;
+                let auth = bind_credential(
+                    "JIRA_API_TOKEN",
+                    env("JIRA_EMAIL").zip(env("JIRA_API_TOKEN")),
+                    env("JIRA_URL").as_deref(),
+                    base_url,
+                )?;
+                Jira::new(base_url, jira, auth).map(Self::Jira)
+            }
+            other => Err(format!("unknown forge {other:?}")),
+        }
+    }
+
+    /// The forge of `repo_id`; `None` if there is no such repo
+    pub fn for_repo(conn: &Connection, repo_id: &str) -> Result<Option<Self>, String> {
+        repos_db::forge(conn, repo_id)?
+            .map(|config| Self::from_config(&config))
+            .transpose()
+    }
+}
+
+impl ForgeProvider for Forge {
+    async fn fetch_issues(&self, owner: &str, name: &str) -> Result<Vec<Issue>, String> {
+        match self {
+            Self::GitHub(forge) => forge.fetch_issues(owner, name).await,
+            Self::GitLab(forge) => forge.fetch_issues(owner, name).await,
+            Self::Gitea(forge) => forge.fetch_issues(owner, name).await,
+            Self::Jira(forge) => forge.fetch_issues(owner, name).await,
+        }
+    }
+
+    async fn fetch_issue(
+        &self,
+        owner: &str,
+        name: &str,
+        number: i64,
+    ) -> Result<Option<Issue>, String> {
+        match self {
+            Self::GitHub(forge) => forge.fetch_issue(owner, name, number).await,
+            Self::GitLab(forge) => forge.fetch_issue(owner, name, number).await,
+            Self::Gitea(forge) => forge.fetch_issue(owner, name, number).await,
+            Self::Jira(forge) => forge.fetch_issue(owner, name, number).await,
+        }
+    }
+
+    async fn is_merged(&self, owner: &str, name: &str, number: i64) -> Result<bool, String> {
+        match self {
+            Self::GitHub(forge) => forge.is_merged(owner, name, number).await,
+            Self::GitLab(forge) => forge.is_merged(owner, name, number).await,
+            Self::Gitea(forge) => forge.is_merged(owner, name, number).await,
+            Self::Jira(forge) => forge.is_merged(owner, name, number).await,
+        }
+    }
+}
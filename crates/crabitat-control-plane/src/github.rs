@@ -1,7 +1,281 @@
+use std::collections::HashMap;
+use std::process::Output;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 
+use crate::chaos::{self, Fault};
+use crate::github_app;
 use crate::models::Issue;
-use crate::models::system::SystemStatus;
+use crate::models::missions::PrReviewComment;
+use crate::models::system::{GithubHealth, SystemStatus};
+use crate::models::tasks::CheckRun;
+
+/// Consecutive outage-like failures that open the circuit
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an open circuit fails calls fast before letting one through
+pub const OPEN_DURATION: Duration = Duration::from_secs(60);
+
+/// Longest a rate limit keeps the circuit open; GitHub's quotas reset hourly
+pub const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(3600);
+
+/// Trips after repeated GitHub failures so callers fail fast (and fall back
+/// to cached data) instead of each waiting on `gh` to time out. Once
+/// `OPEN_DURATION` has passed one call is let through; its outcome closes
+/// the circuit or opens it again.
+///
+/// Rate limiting opens the circuit at once: until the reset GitHub reported,
+/// or, for secondary limits that name none, for `OPEN_DURATION` doubling
+/// with each limit hit in a row.
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    last_error: Option<String>,
+    rate_limit_hits: u32,
+}
+
+impl CircuitBreaker {
+    pub const fn new() -> Self {
+        Self {
+            consecutive_failures: 0,
+            open_until: None,
+            last_error: None,
+            rate_limit_hits: 0,
+        }
+    }
+
+    /// `Err` with the time left while the circuit is open
+    pub fn allow(&self, now: Instant) -> Result<(), Duration> {
+        match self.open_until {
+            Some(until) if until > now => Err(until - now),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn record_failure(&mut self, now: Instant, error: &str) {
+        self.consecutive_failures += 1;
+        self.last_error = Some(error.to_string());
+        if self.consecutive_failures >= FAILURE_THRESHOLD {
+            self.open_until = Some(now + OPEN_DURATION);
+        }
+    }
+
+    /// GitHub refused the call for rate limiting; `reset` is how long until
+    /// it said the quota comes back, when it said
+    pub fn record_rate_limit(&mut self, now: Instant, reset: Option<Duration>, error: &str) {
+        self.rate_limit_hits += 1;
+        self.last_error = Some(error.to_string());
+        let wait = reset
+            .unwrap_or_else(|| OPEN_DURATION * 2u32.pow((self.rate_limit_hits - 1).min(5)))
+            .min(MAX_RATE_LIMIT_WAIT);
+        self.open_until = Some(now + wait);
+    }
+
+    pub fn health(&self, now: Instant) -> GithubHealth {
+        let retry_after = self.allow(now).err();
+        GithubHealth {
+            status: match (retry_after, self.consecutive_failures) {
+                (Some(_), _) if self.rate_limit_hits > 0 => "rate_limited",
+                (Some(_), _) => "down",
+                (None, 0) => "ok",
+                (None, _) => "degraded",
+            }
+            .to_string(),
+            consecutive_failures: self.consecutive_failures,
+            last_error: self.last_error.clone(),
+            retry_after_secs: retry_after.map(|d| d.as_secs().max(1)),
+        }
+    }
+}
+
+static BREAKER: Mutex<CircuitBreaker> = Mutex::new(CircuitBreaker::new());
+
+/// Whether GitHub calls are currently failing fast
+pub fn circuit_open() -> bool {
+    BREAKER.lock().unwrap().allow(Instant::now()).is_err()
+}
+
+pub fn health() -> GithubHealth {
+    BREAKER.lock().unwrap().health(Instant::now())
+}
+
+/// Whether `gh` stderr points at GitHub or the network being unavailable,
+/// as opposed to a problem with the request itself
+pub fn is_outage(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        "http 5",
+        "error connecting",
+        "connection refused",
+        "connection reset",
+        "no such host",
+        "timeout",
+        "timed out",
+        "rate limit",
+        "service unavailable",
+        "bad gateway",
+    ]
+    .iter()
+    .any(|needle| stderr.contains(needle))
+}
+
+/// Whether `gh` stderr says GitHub refused the call for rate limiting
+pub fn is_rate_limited(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    stderr.contains("rate limit") || stderr.contains("abuse detection")
+}
+
+/// GitHub's rate-limit headers, as `gh api -i` prints them ahead of the body
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Calls left in the current window
+    pub remaining: Option<u64>,
+    /// When the window resets, in Unix seconds
+    pub reset_at: Option<u64>,
+    /// Seconds to wait, sent with secondary rate limits
+    pub retry_after: Option<u64>,
+}
+
+impl RateLimit {
+    /// Read the headers of `gh api -i` output; output without a status line
+    /// (plain `gh` commands) has none
+    pub fn parse(output: &str) -> Self {
+        let (status, headers, _) = split_response(output);
+        if status.is_none() {
+            return Self::default();
+        }
+        let number = |name: &str| header_value(headers, name).and_then(|v| v.parse().ok());
+        Self {
+            remaining: number("x-ratelimit-remaining"),
+            reset_at: number("x-ratelimit-reset"),
+            retry_after: number("retry-after"),
+        }
+    }
+
+    /// How long to hold off calling GitHub, when the headers say
+    pub fn wait(&self, now_unix: u64) -> Option<Duration> {
+        if let Some(secs) = self.retry_after {
+            return Some(Duration::from_secs(secs.max(1)));
+        }
+        match (self.remaining, self.reset_at) {
+            (Some(0), Some(reset)) => {
+                Some(Duration::from_secs(reset.saturating_sub(now_unix).max(1)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Status code, header block and body of `gh api -i` output
+fn split_response(output: &str) -> (Option<u16>, &str, &str) {
+    let Some(status_line) = output.lines().next().filter(|l| l.starts_with("HTTP/")) else {
+        return (None, "", output);
+    };
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok());
+    let (headers, body) = output
+        .split_once("\r\n\r\n")
+        .or_else(|| output.split_once("\n\n"))
+        .unwrap_or((output, ""));
+    (status, headers, body)
+}
+
+fn header_value<'a>(headers: &'a str, name: &str) -> Option<&'a str> {
+    headers.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Run `gh` for a repo of `owner` through the circuit breaker. Request
+/// errors GitHub answered (not found, validation) don't count against it.
+async fn run_gh(owner: &str, args: &[&str]) -> Result<Output, String> {
+    let output = gh_output(owner, args).await?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(format!(
+            "gh failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Run `gh` through the circuit breaker and return its output whatever the
+/// exit status; `Err` only when it could not run or the circuit is open
+async fn gh_output(owner: &str, args: &[&str]) -> Result<Output, String> {
+    if chaos::roll(Fault::FailGithub) {
+        let error = "gh failed: HTTP 503 (injected by chaos)".to_string();
+        BREAKER
+            .lock()
+            .unwrap()
+            .record_failure(Instant::now(), &error);
+        return Err(error);
+    }
+    if let Err(wait) = BREAKER.lock().unwrap().allow(Instant::now()) {
+        return Err(format!(
+            "GitHub unavailable; not retrying for {}s",
+            wait.as_secs().max(1)
+        ));
+    }
+
+    let mut command = tokio::process::Command::new("gh");
+    command.args(args);
+    if let Some(app) = github_app::configured() {
+        command.env("GH_TOKEN", app.token(owner).await?);
+    }
+    let result = command
+        .output()
+        .await
+        .map_err(|e| format!("failed to run gh: {e}"));
+    let mut breaker = BREAKER.lock().unwrap();
+    match result {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let limit = RateLimit::parse(&stdout);
+            let not_modified = split_response(&stdout).0 == Some(304);
+            if (!output.status.success() && !not_modified && is_rate_limited(&stderr))
+                || limit.remaining == Some(0)
+            {
+                // An exhausted quota is waited out before the next call fails
+                let error = if output.status.success() {
+                    "GitHub rate limit used up".to_string()
+                } else {
+                    format!("gh failed: {stderr}")
+                };
+                breaker.record_rate_limit(Instant::now(), limit.wait(now_unix()), &error);
+            } else if !output.status.success() && !not_modified && is_outage(&stderr) {
+                breaker.record_failure(Instant::now(), &format!("gh failed: {stderr}"));
+            } else {
+                breaker.record_success();
+            }
+            Ok(output)
+        }
+        Err(error) => {
+            breaker.record_failure(Instant::now(), &error);
+            Err(error)
+        }
+    }
+}
 
 #[derive(Deserialize)]
 struct GhIssue {
@@ -10,8 +284,23 @@ struct GhIssue {
     body: Option<String>,
     labels: Vec<GhLabel>,
     state: String,
+    /// Set on pull requests, which the REST issues endpoint lists too
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
 }
 
+/// Pages of 100 open issues read per fetch; past that only the most
+/// recently updated are listed
+pub const MAX_ISSUE_PAGES: usize = 30;
+
+/// An issue list and the ETag GitHub sent with it
+type TaggedIssues = (String, Vec<Issue>);
+
+/// Last open-issue list per repo. Revalidating it costs no rate limit when
+/// GitHub answers 304.
+static ISSUE_LISTS: LazyLock<Mutex<HashMap<String, TaggedIssues>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
 #[derive(Deserialize)]
 struct GhLabel {
     name: String,
@@ -25,6 +314,9 @@ pub async fn check_status() -> SystemStatus {
         gh_user: None,
         gh_cli: false,
         gh_auth: false,
+        crabs: Vec::new(),
+        github: health(),
+        github_app_id: github_app::configured().map(|app| app.app_id()),
     };
 
     // Check installation and version
@@ -46,6 +338,13 @@ pub async fn check_status() -> SystemStatus {
         return status;
     }
 
+    // An app's tokens stand in for a gh login
+    if status.github_app_id.is_some() {
+        status.gh_auth_status = true;
+        status.gh_auth = true;
+        return status;
+    }
+
     // Check auth status
     let auth_output = tokio::process::Command::new("gh")
         .args(["auth", "status"])
@@ -75,46 +374,434 @@ pub async fn check_status() -> SystemStatus {
     status
 }
 
-pub async fn fetch_issues(owner: &str, name: &str) -> Result<Vec<Issue>, String> {
+/// Mark an issue as being worked on by assigning `assignee` and/or adding
+/// `label`, or undo both when `claimed` is false. The label must already
+/// exist in the repo.
+pub async fn set_issue_claim(
+    owner: &str,
+    name: &str,
+    number: i64,
+    assignee: Option<&str>,
+    label: Option<&str>,
+    claimed: bool,
+) -> Result<(), String> {
     let repo_slug = format!("{owner}/{name}");
-    let output = tokio::process::Command::new("gh")
-        .args([
-            "issue",
-            "list",
-            "--repo",
-            &repo_slug,
+    let number = number.to_string();
+    let mut args = vec!["issue", "edit", &number, "--repo", &repo_slug];
+    if let Some(assignee) = assignee {
+        args.push(if claimed {
+            "--add-assignee"
+        } else {
+            "--remove-assignee"
+        });
+        args.push(assignee);
+    }
+    if let Some(label) = label {
+        args.push(if claimed {
+            "--add-label"
+        } else {
+            "--remove-label"
+        });
+        args.push(label);
+    }
+
+    run_gh(owner, &args).await?;
+    Ok(())
+}
+
+/// Wrap up an issue whose mission completed: add `label` and/or close it as
+/// completed. The label must already exist in the repo.
+pub async fn complete_issue(
+    owner: &str,
+    name: &str,
+    number: i64,
+    close: bool,
+    label: Option<&str>,
+) -> Result<(), String> {
+    let repo_slug = format!("{owner}/{name}");
+    let number = number.to_string();
+    if let Some(label) = label {
+        run_gh(
+            owner,
+            &[
+                "issue",
+                "edit",
+                &number,
+                "--repo",
+                &repo_slug,
+                "--add-label",
+                label,
+            ],
+        )
+        .await?;
+    }
+    if close {
+        run_gh(
+            owner,
+            &[
+                "issue",
+                "close",
+                &number,
+                "--repo",
+                &repo_slug,
+                "--reason",
+                "completed",
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Whether pull request `number` of `owner/name` was merged
+pub async fn pull_request_merged(owner: &str, name: &str, number: i64) -> Result<bool, String> {
+    let path = format!("repos/{owner}/{name}/pulls/{number}");
+    let output = run_gh(owner, &["api", &path, "--jq", ".merged"]).await?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+/// The required checks on pull request `pr_url`. `gh` exits non-zero while
+/// checks are pending or failing but still prints them, so only a run
+/// without output is an error. A branch without required checks has none.
+pub async fn pull_request_checks(owner: &str, pr_url: &str) -> Result<Vec<CheckRun>, String> {
+    let output = gh_output(
+        owner,
+        &[
+            "pr",
+            "checks",
+            pr_url,
+            "--required",
             "--json",
-            "number,title,body,labels,state",
-            "--limit",
-            "100",
-        ])
-        .output()
-        .await
-        .map_err(|e| format!("failed to run gh: {e}"))?;
+            "name,bucket,link,description",
+        ],
+    )
+    .await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        return serde_json::from_str(&stdout).map_err(|e| format!("bad gh output: {}", e));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("no required checks reported") || output.status.success() {
+        return Ok(Vec::new());
+    }
+    Err(format!("gh failed: {}", stderr))
+}
+
+#[derive(Deserialize)]
+struct GhUser {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GhReview {
+    id: i64,
+    user: Option<GhUser>,
+    author_association: String,
+    #[serde(default)]
+    body: Option<String>,
+    state: String,
+    html_url: String,
+    submitted_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GhReviewComment {
+    id: i64,
+    pull_request_review_id: Option<i64>,
+    user: Option<GhUser>,
+    author_association: String,
+    body: String,
+    path: String,
+    line: Option<i64>,
+    original_line: Option<i64>,
+    html_url: String,
+    created_at: String,
+}
+
+/// Reviewers whose feedback reaches the agent: people with a say in the
+/// repo. On a public repo anyone else could steer it through a review.
+const TRUSTED_ASSOCIATIONS: &[&str] = &["OWNER", "MEMBER", "COLLABORATOR"];
+
+fn login(user: Option<GhUser>) -> String {
+    user.map_or_else(|| "ghost".to_string(), |u| u.login)
+}
+
+/// Number of the pull request at `pr_url`, its last path segment
+pub fn pr_number(pr_url: &str) -> Option<i64> {
+    pr_url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Feedback from the reviews requesting changes on pull request `pr_url` of
+/// `owner/name`: each review's body and its inline comments, from owners,
+/// members and collaborators only. Only the first 100 reviews and comments
+/// are read.
+pub async fn pull_request_review_comments(
+    owner: &str,
+    name: &str,
+    pr_url: &str,
+) -> Result<Vec<PrReviewComment>, String> {
+    let number = pr_number(pr_url).ok_or_else(|| format!("not a pull request URL: {pr_url}"))?;
+    let base = format!("repos/{owner}/{name}/pulls/{number}");
+
+    let output = run_gh(owner, &["api", &format!("{base}/reviews?per_page=100")]).await?;
+    let reviews: Vec<GhReview> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("failed to parse gh output: {e}"))?;
+    let reviews: Vec<GhReview> = reviews
+        .into_iter()
+        .filter(|r| {
+            r.state == "CHANGES_REQUESTED"
+                && TRUSTED_ASSOCIATIONS.contains(&r.author_association.as_str())
+        })
+        .collect();
+    if reviews.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let output = run_gh(owner, &["api", &format!("{base}/comments?per_page=100")]).await?;
+    let inline: Vec<GhReviewComment> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("failed to parse gh output: {e}"))?;
+
+    let review_ids: Vec<i64> = reviews.iter().map(|r| r.id).collect();
+    let mut comments = Vec::new();
+    for review in reviews {
+        let body = review.body.unwrap_or_default();
+        if body.trim().is_empty() {
+            continue;
+        }
+        comments.push(PrReviewComment {
+            kind: "review".to_string(),
+            comment_id: review.id,
+            author: login(review.user),
+            body,
+            path: None,
+            line: None,
+            url: review.html_url,
+            submitted_at: review.submitted_at,
+        });
+    }
+    for comment in inline {
+        if !comment
+            .pull_request_review_id
+            .is_some_and(|id| review_ids.contains(&id))
+            || !TRUSTED_ASSOCIATIONS.contains(&comment.author_association.as_str())
+        {
+            continue;
+        }
+        comments.push(PrReviewComment {
+            kind: "inline".to_string(),
+            comment_id: comment.id,
+            author: login(comment.user),
+            body: comment.body,
+            path: Some(comment.path),
+            line: comment.line.or(comment.original_line),
+            url: comment.html_url,
+            submitted_at: Some(comment.created_at),
+        });
+    }
+    Ok(comments)
+}
+
+#[derive(Deserialize)]
+struct GhComment {
+    id: i64,
+}
+
+/// Post `body` as a comment on issue `number` of `owner/name`, or edit
+/// comment `comment_id` instead when given. A comment someone deleted is
+/// posted again. Returns the comment's id.
+pub async fn upsert_issue_comment(
+    owner: &str,
+    name: &str,
+    number: i64,
+    comment_id: Option<i64>,
+    body: &str,
+) -> Result<i64, String> {
+    let repo_slug = format!("{owner}/{name}");
+    let field = format!("body={body}");
+    if let Some(id) = comment_id {
+        let path = format!("repos/{repo_slug}/issues/comments/{id}");
+        let output = gh_output(owner, &["api", "-X", "PATCH", &path, "-f", &field]).await?;
+        if output.status.success() {
+            return Ok(id);
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.contains("HTTP 404") {
+            return Err(format!("gh failed: {stderr}"));
+        }
+    }
+    let path = format!("repos/{repo_slug}/issues/{number}/comments");
+    let output = run_gh(owner, &["api", &path, "-f", &field]).await?;
+    let comment: GhComment = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("failed to parse gh output: {e}"))?;
+    Ok(comment.id)
+}
 
+/// Issue `number` of `owner/name`, open or closed; `None` if there is no
+/// such issue or it is a pull request
+pub async fn fetch_issue(owner: &str, name: &str, number: i64) -> Result<Option<Issue>, String> {
+    let path = format!("repos/{owner}/{name}/issues/{number}");
+    let output = gh_output(owner, &["api", &path]).await?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("HTTP 404") {
+            return Ok(None);
+        }
         return Err(format!("gh failed: {stderr}"));
     }
+    let mut issue: GhIssue = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("failed to parse gh output: {e}"))?;
+    if issue.pull_request.is_some() {
+        return Ok(None);
+    }
+    issue.state = issue.state.to_uppercase();
+    Ok(Some(issue.into_issue()))
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let gh_issues: Vec<GhIssue> =
-        serde_json::from_str(&stdout).map_err(|e| format!("failed to parse gh output: {e}"))?;
+/// Open issues of `owner/name`, most recently updated first, following
+/// GitHub's pages up to `MAX_ISSUE_PAGES`. Sorted that way, any change to an
+/// open issue changes the first page, so that page alone is revalidated with
+/// `If-None-Match` against the last list fetched.
+pub async fn fetch_issues(owner: &str, name: &str) -> Result<Vec<Issue>, String> {
+    let repo_slug = format!("{owner}/{name}");
+    let if_none_match = ISSUE_LISTS
+        .lock()
+        .unwrap()
+        .get(&repo_slug)
+        .map(|(etag, _)| format!("If-None-Match: {etag}"));
+    let mut issues = Vec::new();
+    let mut etag = None;
+    for page in 1..=MAX_ISSUE_PAGES {
+        let path = format!(
+            "repos/{repo_slug}/issues?state=open&sort=updated&direction=desc&per_page=100&page={page}"
+        );
+        let mut args = vec!["api", "-i", path.as_str()];
+        if page == 1
+            && let Some(header) = &if_none_match
+        {
+            args.extend(["-H", header.as_str()]);
+        }
+        let output = gh_output(owner, &args).await?;
 
-    let issues = gh_issues
-        .into_iter()
-        .map(|gi| Issue {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (status, headers, body) = split_response(&stdout);
+        if page == 1 {
+            if status == Some(304)
+                && let Some((_, issues)) = ISSUE_LISTS.lock().unwrap().get(&repo_slug)
+            {
+                return Ok(issues.clone());
+            }
+            etag = header_value(headers, "etag").map(String::from);
+        }
+        if !output.status.success() {
+            return Err(format!(
+                "gh failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let gh_issues: Vec<GhIssue> =
+            serde_json::from_str(body).map_err(|e| format!("failed to parse gh output: {e}"))?;
+        issues.extend(
+            gh_issues
+                .into_iter()
+                .filter(|issue| issue.pull_request.is_none())
+                .map(|mut issue| {
+                    // Match the upper-case states `gh issue list` reports
+                    issue.state = issue.state.to_uppercase();
+                    issue.into_issue()
+                }),
+        );
+        if !has_next_page(headers) {
+            break;
+        }
+        if page == MAX_ISSUE_PAGES {
+            tracing::warn!(
+                "{} has more than {} pages of open issues; listing the most recently updated",
+                repo_slug,
+                MAX_ISSUE_PAGES
+            );
+        }
+    }
+    if let Some(etag) = etag {
+        ISSUE_LISTS
+            .lock()
+            .unwrap()
+            .insert(repo_slug, (etag, issues.clone()));
+    }
+    Ok(issues)
+}
+
+/// Whether a response's `Link` header points at a next page
+pub fn has_next_page(headers: &str) -> bool {
+    header_value(headers, "link").is_some_and(|link| {
+        link.split(',')
+            .any(|part| part.trim_end().ends_with(r#"rel="next""#))
+    })
+}
+
+impl GhIssue {
+    fn into_issue(self) -> Issue {
+        Issue {
             repo_id: String::new(), // filled by caller
-            number: gi.number,
-            title: gi.title,
-            body: gi.body,
-            labels: gi.labels.into_iter().map(|l| l.name).collect(),
-            state: gi.state,
+            number: self.number,
+            title: self.title,
+            body: self.body,
+            labels: self.labels.into_iter().map(|l| l.name).collect(),
+            state: self.state,
             fetched_at: String::new(), // filled by DB
-        })
-        .collect();
+            stale: false,
+        }
+    }
+}
 
-    Ok(issues)
+/// Subset of a GitHub `issues` webhook payload
+#[derive(Deserialize)]
+pub struct WebhookPayload {
+    pub action: Option<String>,
+    issue: Option<GhIssue>,
+    pub repository: WebhookRepository,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookRepository {
+    pub name: String,
+    pub owner: WebhookOwner,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookOwner {
+    pub login: String,
+}
+
+impl WebhookPayload {
+    pub fn take_issue(&mut self) -> Option<Issue> {
+        self.issue.take().map(GhIssue::into_issue)
+    }
+}
+
+/// Check an `X-Hub-Signature-256` header (`sha256=<hex>`) against the payload
+pub fn verify_signature(secret: &str, payload: &[u8], signature: &str) -> bool {
+    let Some(hex) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let Ok(expected) = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("zz"), 16))
+        .collect::<Result<Vec<u8>, _>>()
+    else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(payload);
+    mac.verify_slice(&expected).is_ok()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -130,8 +817,9 @@ pub async fn search_repos(query: &str) -> Result<Vec<GhRepo>, String> {
         None => (query, ""),
     };
 
-    let output = tokio::process::Command::new("gh")
-        .args([
+    let output = run_gh(
+        owner,
+        &[
             "repo",
             "list",
             owner,
@@ -139,15 +827,9 @@ pub async fn search_repos(query: &str) -> Result<Vec<GhRepo>, String> {
             "nameWithOwner",
             "--limit",
             "50",
-        ])
-        .output()
-        .await
-        .map_err(|e| format!("failed to run gh: {e}"))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("gh failed: {stderr}"));
-    }
+        ],
+    )
+    .await?;
 
     let stdout = String::from_utf8_lossy(&output.stdout);
     let repos: Vec<GhRepo> =
@@ -0,0 +1,217 @@
+//! Authenticating to GitHub as a GitHub App.
+//!
+//! With `GITHUB_APP_ID` and `GITHUB_APP_PRIVATE_KEY_PATH` set, every `gh`
+//! call runs with `GH_TOKEN` set to an installation token of the app instead
+//! of whoever is logged in to `gh`. The installation is
+//! `GITHUB_APP_INSTALLATION_ID`, or else the one on the repo owner's account.
+//! Tokens are cached per installation and exchanged again shortly before
+//! they expire. `GITHUB_API_URL` points at GitHub Enterprise.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use ring::rand::SystemRandom;
+use ring::signature::{RSA_PKCS1_SHA256, RsaKeyPair};
+use serde::Deserialize;
+
+/// Installation tokens live an hour; exchange them again before that
+const TOKEN_REUSE: Duration = Duration::from_secs(50 * 60);
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// A GitHub App's credentials and its cached installation tokens
+pub struct AppAuth {
+    app_id: u64,
+    key: RsaKeyPair,
+    installation_id: Option<u64>,
+    api_url: String,
+    http: reqwest::Client,
+    /// Installation per repo owner, once looked up
+    installations: Mutex<HashMap<String, u64>>,
+    tokens: Mutex<HashMap<u64, (String, Instant)>>,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    token: String,
+}
+
+impl AppAuth {
+    /// `private_key_pem` is the PKCS#1 (`BEGIN RSA PRIVATE KEY`) or PKCS#8
+    /// (`BEGIN PRIVATE KEY`) key GitHub generated for the app
+    pub fn new(
+        app_id: u64,
+        private_key_pem: &str,
+        installation_id: Option<u64>,
+        api_url: &str,
+    ) -> Result<Self, String> {
+        let der: String = private_key_pem
+            .lines()
+            .filter(|l| !l.starts_with("-----"))
+            .collect();
+        let der = STANDARD
+            .decode(der.trim())
+            .map_err(|e| format!("private key is not PEM: {e}"))?;
+        let key = if private_key_pem.contains("BEGIN RSA PRIVATE KEY") {
+            RsaKeyPair::from_der(&der)
+        } else {
+            RsaKeyPair::from_pkcs8(&der)
+        }
+        .map_err(|e| format!("private key rejected: {e}"))?;
+        let http = reqwest::Client::builder()
+            .user_agent("crabitat-control-plane")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            app_id,
+            key,
+            installation_id,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            http,
+            installations: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// From the environment; `None` when no app is configured
+    fn from_env() -> Result<Option<Self>, String> {
+        let Ok(app_id) = std::env::var("GITHUB_APP_ID") else {
+            return Ok(None);
+        };
+        let app_id = app_id
+            .trim()
+            .parse()
+            .map_err(|_| format!("GITHUB_APP_ID {app_id:?} is not a number"))?;
+        let key_path = std::env::var("GITHUB_APP_PRIVATE_KEY_PATH")
+            .map_err(|_| "GITHUB_APP_ID is set but GITHUB_APP_PRIVATE_KEY_PATH is not")?;
+        let pem = std::fs::read_to_string(&key_path)
+            .map_err(|e| format!("failed to read {key_path}: {e}"))?;
+        let installation_id = match std::env::var("GITHUB_APP_INSTALLATION_ID") {
+            Ok(id) => Some(
+                id.trim()
+                    .parse()
+                    .map_err(|_| format!("GITHUB_APP_INSTALLATION_ID {id:?} is not a number"))?,
+            ),
+            Err(_) => None,
+        };
+        let api_url = std::env::var("GITHUB_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.into());
+        Self::new(app_id, &pem, installation_id, &api_url).map(Some)
+    }
+
+    pub fn app_id(&self) -> u64 {
+        self.app_id
+    }
+
+    /// A short-lived JWT signed with the app's key, for the app's own endpoints
+    pub fn jwt(&self) -> Result<String, String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+        // Backdated against clock drift; GitHub allows at most ten minutes
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(format!(
+            r#"{{"iat":{},"exp":{},"iss":"{}"}}"#,
+            now - 60,
+            now + 540,
+            self.app_id
+        ));
+        let message = format!("{header}.{claims}");
+        let mut signature = vec![0; self.key.public().modulus_len()];
+        self.key
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|e| format!("failed to sign app JWT: {e}"))?;
+        Ok(format!("{message}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// An installation token good for `owner`'s repos
+    pub async fn token(&self, owner: &str) -> Result<String, String> {
+        let installation_id = self.installation_for(owner).await?;
+        if let Some((token, fetched)) = self.tokens.lock().unwrap().get(&installation_id)
+            && fetched.elapsed() < TOKEN_REUSE
+        {
+            return Ok(token.clone());
+        }
+        let url = format!(
+            "{}/app/installations/{}/access_tokens",
+            self.api_url, installation_id
+        );
+        let token: AccessToken = self.app_request(self.http.post(url)).await?;
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(installation_id, (token.token.clone(), Instant::now()));
+        Ok(token.token)
+    }
+
+    async fn installation_for(&self, owner: &str) -> Result<u64, String> {
+        if let Some(id) = self.installation_id {
+            return Ok(id);
+        }
+        if let Some(id) = self.installations.lock().unwrap().get(owner) {
+            return Ok(*id);
+        }
+        let mut last_error = String::new();
+        for kind in ["orgs", "users"] {
+            let url = format!("{}/{}/{}/installation", self.api_url, kind, owner);
+            match self.app_request::<Installation>(self.http.get(url)).await {
+                Ok(installation) => {
+                    self.installations
+                        .lock()
+                        .unwrap()
+                        .insert(owner.to_string(), installation.id);
+                    return Ok(installation.id);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(format!(
+            "GitHub App {} is not installed for {}: {}",
+            self.app_id, owner, last_error
+        ))
+    }
+
+    async fn app_request<T: for<'de> Deserialize<'de>>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, String> {
+        let resp = request
+            .bearer_auth(self.jwt()?)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await
+            .map_err(|e| format!("GitHub App request failed: {e}"))?;
+        if !resp.status().is_success() {
+            return Err(format!("GitHub App request failed: HTTP {}", resp.status()));
+        }
+        resp.json()
+            .await
+            .map_err(|e| format!("failed to parse GitHub response: {e}"))
+    }
+}
+
+static APP: LazyLock<Option<AppAuth>> = LazyLock::new(|| match AppAuth::from_env() {
+    Ok(app) => app,
+    Err(e) => {
+        tracing::error!("GitHub App authentication disabled: {}", e);
+        None
+    }
+});
+
+/// The configured app, if GitHub calls authenticate as one
+pub fn configured() -> Option<&'static AppAuth> {
+    APP.as_ref()
+}
@@ -0,0 +1,65 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::analytics as db;
+use crate::db::missions as missions_db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::analytics::{CrabDailyStats, CrabStatsQuery, ScoreTrend, ScoreTrendQuery};
+use crate::models::missions::ReplayComparison;
+
+/// GET /v1/analytics/scores?group_by=worker|workflow — daily review score trends
+pub async fn get_score_trends(
+    State(state): State<AppState>,
+    Query(query): Query<ScoreTrendQuery>,
+) -> Result<Json<Vec<ScoreTrend>>, (StatusCode, Json<Value>)> {
+    let group_by = query.group_by.as_deref().unwrap_or("workflow");
+    if group_by != "worker" && group_by != "workflow" {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "group_by must be 'worker' or 'workflow'",
+        ));
+    }
+
+    let conn = state.read();
+    match db::score_trends(&conn, group_by) {
+        Ok(trends) => Ok(Json(trends)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/analytics/crabs?window=N — per-crab daily utilization for the last N days
+pub async fn get_crab_stats(
+    State(state): State<AppState>,
+    Query(query): Query<CrabStatsQuery>,
+) -> Result<Json<Vec<CrabDailyStats>>, (StatusCode, Json<Value>)> {
+    let window = query.window.unwrap_or(7);
+    if !(1..=365).contains(&window) {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "window must be between 1 and 365 days",
+        ));
+    }
+
+    let conn = state.read();
+    match db::day_offset(&conn, window - 1).and_then(|since| db::crab_stats(&conn, &since)) {
+        Ok(stats) => Ok(Json(stats)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/analytics/replays/{mission_id} — a mission next to its replays
+pub async fn get_replay_comparison(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+) -> Result<Json<ReplayComparison>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    let original = missions_db::get_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or(api_error(ErrorCode::MissionNotFound, "mission not found"))?;
+    let replays = missions_db::list_replays(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    Ok(Json(ReplayComparison { original, replays }))
+}
@@ -0,0 +1,67 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::api_keys as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::api_keys::{ApiKey, CreateApiKeyRequest, CreatedApiKey, SCOPES};
+
+/// Check a requested scope list; an empty list would make a useless key
+pub fn validate_scopes(scopes: &[String]) -> Result<(), String> {
+    if scopes.is_empty() {
+        return Err("at least one scope is required".to_string());
+    }
+    match scopes.iter().find(|s| !SCOPES.contains(&s.as_str())) {
+        Some(unknown) => Err(format!(
+            "unknown scope {:?}; expected one of {}",
+            unknown,
+            SCOPES.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// POST /v1/admin/keys — create a key. The token is in this response only.
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Json(body): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), (StatusCode, Json<Value>)> {
+    if body.name.trim().is_empty() {
+        return Err(api_error(ErrorCode::InvalidRequest, "name is required"));
+    }
+    validate_scopes(&body.scopes).map_err(|e| api_error(ErrorCode::InvalidRequest, e))?;
+
+    let conn = state.db.lock().unwrap();
+    match db::insert(&conn, body.name.trim(), &body.scopes) {
+        Ok((key, token)) => Ok((StatusCode::CREATED, Json(CreatedApiKey { key, token }))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/admin/keys — every key, without tokens
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ApiKey>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    db::list(&conn)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// DELETE /v1/admin/keys/{key_id} — revoke a key; it stops working at once
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match db::revoke(&conn, &key_id) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(
+            ErrorCode::NotFound,
+            "api key not found or already revoked",
+        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
@@ -0,0 +1,129 @@
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::artifacts as db;
+use crate::db::missions as missions_db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::artifacts::{Artifact, CreateArtifactRequest, MAX_ARTIFACT_BYTES};
+use crate::redaction::Scanner;
+
+/// POST /v1/runs/{run_id}/artifacts — attach a base64-encoded file to a run.
+/// Text content is scrubbed of secrets like run logs are.
+pub async fn create_artifact(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    Json(body): Json<CreateArtifactRequest>,
+) -> Result<(StatusCode, Json<Artifact>), (StatusCode, Json<Value>)> {
+    if body.name.trim().is_empty() {
+        return Err(api_error(ErrorCode::InvalidRequest, "name is required"));
+    }
+    let mut content = STANDARD
+        .decode(body.content_base64.trim())
+        .map_err(|e| api_error(ErrorCode::InvalidRequest, format!("invalid base64: {}", e)))?;
+    if content.len() > MAX_ARTIFACT_BYTES {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "artifact is {} bytes; the limit is {}",
+                content.len(),
+                MAX_ARTIFACT_BYTES
+            ),
+        ));
+    }
+
+    let conn = state.db.lock().unwrap();
+    if !db::run_exists(&conn, &run_id).map_err(|e| api_error(ErrorCode::Internal, e))? {
+        return Err(api_error(ErrorCode::NotFound, "run not found"));
+    }
+
+    let is_text = std::str::from_utf8(&content).is_ok();
+    if let Ok(text) = std::str::from_utf8(&content) {
+        let (redacted, count) = Scanner::from_settings(&conn).redact(text);
+        if count > 0 {
+            tracing::warn!(
+                "redacted {} secret(s) from artifact {} of run {}",
+                count,
+                body.name,
+                run_id
+            );
+            content = redacted.into_bytes();
+        }
+    }
+    let content_type = body.content_type.unwrap_or_else(|| {
+        if is_text {
+            "text/plain; charset=utf-8".to_string()
+        } else {
+            "application/octet-stream".to_string()
+        }
+    });
+
+    match db::insert(
+        &conn,
+        &run_id,
+        body.name.trim(),
+        body.kind.as_deref(),
+        &content_type,
+        &content,
+    ) {
+        Ok(artifact) => Ok((StatusCode::CREATED, Json(artifact))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/runs/{run_id}/artifacts — list a run's artifacts, without content
+pub async fn list_run_artifacts(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Json<Vec<Artifact>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    if !db::run_exists(&conn, &run_id).map_err(|e| api_error(ErrorCode::Internal, e))? {
+        return Err(api_error(ErrorCode::NotFound, "run not found"));
+    }
+    db::list_for_run(&conn, &run_id)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// GET /v1/runs/{run_id}/artifacts/{artifact_id} — the artifact's raw content
+pub async fn get_artifact_content(
+    State(state): State<AppState>,
+    Path((run_id, artifact_id)): Path<(String, String)>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    let (artifact, content) = db::get_content(&conn, &artifact_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .filter(|(a, _)| a.run_id == run_id)
+        .ok_or_else(|| api_error(ErrorCode::NotFound, "artifact not found"))?;
+
+    let disposition = format!(
+        "inline; filename=\"{}\"",
+        artifact.name.replace(['"', '\\', '\r', '\n'], "_")
+    );
+    Response::builder()
+        .header(header::CONTENT_TYPE, artifact.content_type)
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .header(header::ETAG, format!("\"{}\"", artifact.sha256))
+        .body(Body::from(content))
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// GET /v1/missions/{mission_id}/artifacts — every artifact the mission's runs produced
+pub async fn list_mission_artifacts(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+) -> Result<Json<Vec<Artifact>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    missions_db::get_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or_else(|| api_error(ErrorCode::MissionNotFound, "mission not found"))?;
+    db::list_for_mission(&conn, &mission_id)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
@@ -0,0 +1,57 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::benchmark::{self, BenchmarkError};
+use crate::db::benchmarks as db;
+use crate::error::{ErrorCode, api_error};
+use crate::handlers::missions::create_error_code;
+use crate::models::benchmarks::{Benchmark, BenchmarkReport, CreateBenchmarkRequest};
+
+/// POST /v1/benchmarks — re-run past issues through a baseline and a
+/// candidate workflow, one mission per issue and variant, on a sandbox repo
+pub async fn create_benchmark(
+    State(state): State<AppState>,
+    Json(req): Json<CreateBenchmarkRequest>,
+) -> Result<(StatusCode, Json<Benchmark>), (StatusCode, Json<Value>)> {
+    let mut conn = state.db.lock().unwrap();
+    match benchmark::start(&mut conn, &req) {
+        Ok(benchmark) => Ok((StatusCode::CREATED, Json(benchmark))),
+        Err(e) => {
+            let code = match &e {
+                BenchmarkError::Invalid(_) => ErrorCode::InvalidRequest,
+                BenchmarkError::VersionMismatch(..) | BenchmarkError::NotSandbox => {
+                    ErrorCode::InvalidState
+                }
+                BenchmarkError::Create(e) => create_error_code(e),
+            };
+            Err(api_error(code, e))
+        }
+    }
+}
+
+/// GET /v1/benchmarks — every benchmark, newest first
+pub async fn list_benchmarks(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Benchmark>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    db::list(&conn)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// GET /v1/benchmarks/{benchmark_id}/report — the variants side by side,
+/// with every mission's outcome
+pub async fn get_benchmark_report(
+    State(state): State<AppState>,
+    Path(benchmark_id): Path<String>,
+) -> Result<Json<BenchmarkReport>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match benchmark::report(&conn, &benchmark_id) {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err(api_error(ErrorCode::NotFound, "benchmark not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
@@ -0,0 +1,22 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::blobs as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::blobs::Blob;
+
+/// GET /v1/blobs/{hash} — fetch a content-addressed prompt chunk
+pub async fn get_blob(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Result<Json<Blob>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::get(&conn, &hash) {
+        Ok(Some(blob)) => Ok(Json(blob)),
+        Ok(None) => Err(api_error(ErrorCode::NotFound, "blob not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
@@ -0,0 +1,72 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::burrows as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::burrows::{AcquireBurrowRequest, BurrowLease, BurrowPoolStats, BurrowQuery};
+
+/// POST /v1/missions/{mission_id}/burrow — acquire or renew the mission burrow lease
+pub async fn acquire_burrow(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+    Json(req): Json<AcquireBurrowRequest>,
+) -> Result<Json<BurrowLease>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match db::acquire(&conn, &mission_id, &req.worker_id, &req.path) {
+        Ok(lease) => Ok(Json(lease)),
+        Err(e) => Err(api_error(ErrorCode::InvalidRequest, e)),
+    }
+}
+
+/// GET /v1/missions/{mission_id}/burrow — current lease holder
+pub async fn get_burrow(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+) -> Result<Json<BurrowLease>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::get_active(&conn, &mission_id) {
+        Ok(Some(lease)) => Ok(Json(lease)),
+        Ok(None) => Err(api_error(ErrorCode::NotFound, "no active burrow lease")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// DELETE /v1/missions/{mission_id}/burrow?worker_id= — release after cleanup
+pub async fn release_burrow(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+    Query(query): Query<BurrowQuery>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match db::release(&conn, &mission_id, &query.worker_id) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::NotFound, "no active burrow lease")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/burrows/stale?worker_id= — leases the crab should tear down
+pub async fn list_stale_burrows(
+    State(state): State<AppState>,
+    Query(query): Query<BurrowQuery>,
+) -> Result<Json<Vec<BurrowLease>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list_stale(&conn, &query.worker_id) {
+        Ok(leases) => Ok(Json(leases)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/burrows/pools — warm-pool counters each crab last reported
+pub async fn list_pools(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BurrowPoolStats>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list_pool_stats(&conn) {
+        Ok(pools) => Ok(Json(pools)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
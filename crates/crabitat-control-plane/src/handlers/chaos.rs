@@ -0,0 +1,26 @@
+use axum::Json;
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::chaos;
+use crate::error::{ErrorCode, api_error};
+use crate::models::chaos::{ChaosRules, ChaosStatus};
+
+/// GET /v1/admin/chaos — the fault injection rules and what they've injected
+pub async fn get_chaos() -> Json<ChaosStatus> {
+    Json(chaos::status())
+}
+
+/// POST /v1/admin/chaos — replace the fault injection rules; `{}` turns
+/// every fault off
+pub async fn set_chaos(
+    Json(rules): Json<ChaosRules>,
+) -> Result<Json<ChaosStatus>, (StatusCode, Json<Value>)> {
+    rules
+        .validate()
+        .map_err(|e| api_error(ErrorCode::InvalidRequest, e))?;
+    if rules != ChaosRules::default() {
+        tracing::warn!("chaos: fault injection rules set: {:?}", rules);
+    }
+    Ok(Json(chaos::set(rules)))
+}
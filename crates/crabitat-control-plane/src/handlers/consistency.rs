@@ -0,0 +1,35 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::consistency;
+use crate::db::consistency as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::consistency::ConsistencyReport;
+
+/// GET /v1/admin/consistency — the latest consistency check's report
+pub async fn get_consistency(
+    State(state): State<AppState>,
+) -> Result<Json<ConsistencyReport>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::latest_report(&conn) {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err(api_error(
+            ErrorCode::NotFound,
+            "no consistency check has run yet",
+        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// POST /v1/admin/consistency — check now, fixing what is safe to fix
+pub async fn run_consistency(
+    State(state): State<AppState>,
+) -> Result<Json<ConsistencyReport>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    consistency::check(&conn)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
@@ -0,0 +1,153 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::crabs as db;
+use crate::db::missions as missions_db;
+use crate::db::repos as repos_db;
+use crate::db::settings as settings_db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::crabs::{
+    CrabGuide, CrabRegistration, GuideQuery, GuideStep, GuideWorkflow, RoleStaffing,
+};
+use crate::workflow_registry::WorkflowRegistry;
+
+/// POST /v1/crabs/register — issue a worker id and its token. The token is
+/// only in this response; requests naming the worker id must present it.
+pub async fn register_crab(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<CrabRegistration>), (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match db::register(&conn) {
+        Ok(registration) => Ok((StatusCode::CREATED, Json(registration))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/repos/{repo_id}/guide?role= — how to start a crab on the repo:
+/// the URL to poll, which roles lack crabs, and what the live workflows'
+/// steps will ask of it
+pub async fn get_repo_guide(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Query(query): Query<GuideQuery>,
+    headers: HeaderMap,
+) -> Result<Json<CrabGuide>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    let repo = match repos_db::get_by_id(&conn, &repo_id) {
+        Ok(Some(repo)) if repo.deleted_at.is_none() => repo,
+        Ok(_) => return Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+    let registry = match settings_db::get(&conn, "prompts_root") {
+        Ok(Some(root)) => Some(
+            WorkflowRegistry::open(&conn, root).map_err(|e| api_error(ErrorCode::Internal, e))?,
+        ),
+        Ok(None) => None,
+        Err(e) => return Err(api_error(ErrorCode::Internal, e.to_string())),
+    };
+
+    let role_names = registry
+        .as_ref()
+        .map(|r| r.list_roles())
+        .unwrap_or_default();
+    let role_stack_files = match (&query.role, &registry) {
+        (Some(role), Some(registry)) => match registry.read_stack(role) {
+            Ok(Some(stack)) => stack.files,
+            Ok(None) => {
+                return Err(api_error(
+                    ErrorCode::InvalidRequest,
+                    format!("unknown role {:?}; roles: {}", role, role_names.join(", ")),
+                ));
+            }
+            Err(e) => return Err(api_error(ErrorCode::InvalidRequest, e)),
+        },
+        (Some(_), None) => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                "no roles: prompts_root not configured in settings",
+            ));
+        }
+        (None, _) => Vec::new(),
+    };
+
+    let crabs = db::list_executors(&conn, db::ACTIVE_CRAB_SECS)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let roles: Vec<RoleStaffing> = role_names
+        .into_iter()
+        .map(|role| RoleStaffing {
+            active_crabs: crabs
+                .iter()
+                .filter(|c| c.role.as_deref() == Some(role.as_str()))
+                .count(),
+            role,
+        })
+        .collect();
+    let unfilled_roles = roles
+        .iter()
+        .filter(|r| r.active_crabs == 0)
+        .map(|r| r.role.clone())
+        .collect();
+
+    let workflows = missions_db::live_workflows(&conn, &repo_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .into_iter()
+        .map(|(name, live_missions)| {
+            let steps = registry
+                .as_ref()
+                .and_then(|r| r.get_workflow(&name))
+                .map(|wf| {
+                    wf.steps
+                        .into_iter()
+                        .map(|step| GuideStep {
+                            executor: step.executor.map(|executor| {
+                                match step.executor_min_version {
+                                    Some(min) => format!("{}>={}", executor, min),
+                                    None => executor,
+                                }
+                            }),
+                            read_only: step.read_only.unwrap_or(false),
+                            step_id: step.id,
+                            prompt_file: step.prompt_file,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            GuideWorkflow {
+                name,
+                live_missions,
+                steps,
+            }
+        })
+        .collect();
+
+    let control_plane_url = control_plane_url(&headers);
+    let mut command = format!("crabitat-crab --api-url {}", control_plane_url);
+    if let Some(role) = &query.role {
+        command.push_str(&format!(" --role {}", role));
+    }
+    Ok(Json(CrabGuide {
+        repo_id: repo.repo_id,
+        repo_owner: repo.owner,
+        repo_name: repo.name,
+        control_plane_url,
+        role: query.role,
+        role_stack_files,
+        roles,
+        unfilled_roles,
+        workflows,
+        command,
+    }))
+}
+
+/// The address the request reached us at, as a client would dial it
+fn control_plane_url(headers: &HeaderMap) -> String {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let scheme = header("x-forwarded-proto").unwrap_or("http");
+    let host = header("x-forwarded-host")
+        .or_else(|| header("host"))
+        .unwrap_or("localhost:3001");
+    format!("{}://{}", scheme, host)
+}
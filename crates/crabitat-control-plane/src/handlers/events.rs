@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures_util::stream;
+use serde_json::{Value, json};
+
+use crate::AppState;
+use crate::db::events as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::events::{ConsoleEvent, EventsQuery, MAX_EVENTS_PER_PAGE};
+
+/// How often a follower checks for new events
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// GET /v1/events?after_seq=&follow= — mission, task and run changes after `after_seq`.
+///
+/// With `follow=true` the response is a server-sent event stream: one event
+/// per change, named by its kind and with its `seq` as id, so a reconnecting
+/// `EventSource` resumes via `Last-Event-ID`. If events after the cursor were
+/// already pruned, the list is refused and the stream starts with `reset`;
+/// either way the client reloads from `GET /v1/status` and goes on from there.
+pub async fn list_events(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    let after = last_event_id.or(query.after_seq);
+
+    let conn = state.read();
+    let gap = match after {
+        Some(after) => {
+            db::gap_after(&conn, after).map_err(|e| api_error(ErrorCode::Internal, e))?
+        }
+        None => false,
+    };
+    if !query.follow {
+        if gap {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                "events after after_seq were pruned; reload from /v1/status",
+            ));
+        }
+        return db::list_after(&conn, after.unwrap_or(0), MAX_EVENTS_PER_PAGE)
+            .map(|events| Json(events).into_response())
+            .map_err(|e| api_error(ErrorCode::Internal, e));
+    }
+
+    let latest = db::latest_seq(&conn).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    drop(conn);
+    let follow = Follow {
+        state,
+        after: if gap { latest } else { after.unwrap_or(latest) },
+        reset: gap,
+        pending: VecDeque::new(),
+        polled: false,
+    };
+    let events = stream::unfold(follow, |mut f| async move {
+        f.next_event()
+            .await
+            .map(|event| (Ok::<_, Infallible>(event), f))
+    });
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// Cursor of one `follow=true` stream
+struct Follow {
+    state: AppState,
+    after: i64,
+    /// Send `reset` before anything else
+    reset: bool,
+    pending: VecDeque<ConsoleEvent>,
+    polled: bool,
+}
+
+impl Follow {
+    /// The next event to send, or `None` if reading events failed
+    async fn next_event(&mut self) -> Option<Event> {
+        if std::mem::take(&mut self.reset) {
+            return Some(
+                Event::default()
+                    .event("reset")
+                    .id(self.after.to_string())
+                    .data(json!({ "seq": self.after }).to_string()),
+            );
+        }
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                self.after = event.seq;
+                let data = serde_json::to_string(&event).unwrap_or_default();
+                return Some(
+                    Event::default()
+                        .event(event.kind)
+                        .id(event.seq.to_string())
+                        .data(data),
+                );
+            }
+            if self.polled {
+                tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+            }
+            self.polled = true;
+
+            let polled = db::list_after(&self.state.read(), self.after, MAX_EVENTS_PER_PAGE);
+            match polled {
+                Ok(events) => self.pending.extend(events),
+                Err(e) => {
+                    tracing::warn!("following events failed: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,19 @@
 use axum::Json;
-use axum::extract::Query;
-use axum::http::StatusCode;
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
 use serde::Deserialize;
 use serde_json::{Value, json};
 
+use crate::AppState;
+use crate::db::issues as issues_db;
+use crate::db::repos as repos_db;
+use crate::db::settings as settings_db;
+use crate::db::triggers as triggers_db;
+use crate::error::{ErrorCode, api_error};
 use crate::github;
+use crate::mission_service;
+use crate::models::triggers::TriggerEvent;
 
 #[derive(Deserialize)]
 pub struct SearchQuery {
@@ -16,6 +25,109 @@ pub async fn search_repos(
 ) -> Result<Json<Vec<github::GhRepo>>, (StatusCode, Json<Value>)> {
     match github::search_repos(&params.q).await {
         Ok(repos) => Ok(Json(repos)),
-        Err(e) => Err((StatusCode::BAD_GATEWAY, Json(json!({"error": e})))),
+        Err(e) => Err(api_error(ErrorCode::Upstream, e)),
     }
 }
+
+/// POST /v1/github/webhook — evaluate triggers for `issues` events.
+///
+/// When `github_webhook_secret` is set, the `X-Hub-Signature-256` header must match.
+/// Issue events refresh the issue cache and start a mission for the best matching
+/// trigger unless one already exists for that issue and workflow.
+pub async fn receive_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let event = headers
+        .get("x-github-event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut conn = state.db.lock().unwrap();
+
+    if let Ok(Some(secret)) = settings_db::get(&conn, "github_webhook_secret") {
+        let signature = headers
+            .get("x-hub-signature-256")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if !github::verify_signature(&secret, &body, signature) {
+            return Err(api_error(
+                ErrorCode::Unauthorized,
+                "invalid webhook signature",
+            ));
+        }
+    }
+
+    if event == "ping" {
+        return Ok(Json(json!({"event": event})));
+    }
+
+    let mut payload: github::WebhookPayload = serde_json::from_slice(&body)
+        .map_err(|e| api_error(ErrorCode::InvalidRequest, format!("invalid payload: {e}")))?;
+
+    let repo = repos_db::get_by_owner_name(
+        &conn,
+        &payload.repository.owner.login,
+        &payload.repository.name,
+    )
+    .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let Some(repo) = repo else {
+        return Ok(Json(json!({"event": event, "ignored": "unknown repo"})));
+    };
+
+    if event != "issues" {
+        return Ok(Json(
+            json!({"event": event, "ignored": "unsupported event"}),
+        ));
+    }
+    let Some(mut issue) = payload.take_issue() else {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "issues event without issue",
+        ));
+    };
+    issue.repo_id = repo.repo_id.clone();
+    issues_db::upsert_issues(&conn, &repo.repo_id, std::slice::from_ref(&issue))
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+
+    if !matches!(
+        payload.action.as_deref(),
+        Some("opened" | "reopened" | "edited" | "labeled")
+    ) {
+        return Ok(Json(json!({"event": event, "action": payload.action})));
+    }
+
+    let number = issue.number;
+    let trigger_event = TriggerEvent {
+        repo_id: repo.repo_id.clone(),
+        event_type: "issue".to_string(),
+        title: issue.title,
+        body: issue.body,
+        labels: issue.labels,
+    };
+
+    let matched = triggers_db::evaluate(&conn, &trigger_event)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+
+    let mut mission = None;
+    if let Some(best) = matched.first() {
+        match mission_service::start_for_trigger(&mut conn, &repo.repo_id, number, best) {
+            Ok(m) => mission = m,
+            Err(e) => {
+                tracing::error!(
+                    "trigger {} failed to create mission: {}",
+                    best.trigger_id,
+                    e
+                )
+            }
+        }
+    }
+
+    Ok(Json(json!({
+        "event": event,
+        "matched": matched,
+        "mission": mission,
+    })))
+}
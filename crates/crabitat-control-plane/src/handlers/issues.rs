@@ -1,42 +1,158 @@
 use axum::Json;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use serde_json::{Value, json};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
 
 use crate::AppState;
 use crate::db::{issues as issues_db, repos};
-use crate::github;
+use crate::error::{ErrorCode, api_error};
+use crate::forge::{Forge, ForgeProvider};
 use crate::models::Issue;
+use crate::models::issues::{
+    DEFAULT_ISSUES_PER_PAGE, IssuesQuery, MAX_ISSUES_PER_PAGE, NEXT_PAGE_HEADER,
+};
 
-/// GET /v1/repos/{repo_id}/issues — return cached issues, or fetch if cache is empty
+/// GET /v1/repos/{repo_id}/issues?state=&labels=&page=&per_page= — cached
+/// issues, newest first, fetched first if the cache is empty. Paged when
+/// `page` or `per_page` is set: while more remain, the `X-Next-Page` header
+/// holds the `page` to ask for next.
 pub async fn list_repo_issues(
     State(state): State<AppState>,
     Path(repo_id): Path<String>,
-) -> Result<Json<Vec<Issue>>, (StatusCode, Json<Value>)> {
-    let (owner, name) = lookup_repo(&state, &repo_id)?;
+    Query(query): Query<IssuesQuery>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let issue_state = match query.state.as_deref().map(str::trim) {
+        None | Some("all") => None,
+        Some("open") => Some("OPEN"),
+        Some("closed") => Some("CLOSED"),
+        Some(other) => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                format!("state must be open, closed or all, not {:?}", other),
+            ));
+        }
+    };
+    let labels: Vec<String> = query
+        .labels
+        .as_deref()
+        .map(|l| {
+            l.split(',')
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let per_page = match (query.page, query.per_page) {
+        (None, None) => None,
+        (_, Some(n)) if !(1..=MAX_ISSUES_PER_PAGE).contains(&n) => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                format!("per_page must be between 1 and {}", MAX_ISSUES_PER_PAGE),
+            ));
+        }
+        (_, n) => Some(n.unwrap_or(DEFAULT_ISSUES_PER_PAGE)),
+    };
 
-    // Return cached if available
-    {
+    let (owner, name) = lookup_repo(&state, &repo_id)?;
+    let cached = {
         let conn = state.db.lock().unwrap();
-        if issues_db::has_cached(&conn, &repo_id).unwrap_or(false) {
-            return match issues_db::list_by_repo(&conn, &repo_id) {
-                Ok(issues) => Ok(Json(issues)),
-                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
-            };
+        issues_db::has_cached(&conn, &repo_id).unwrap_or(false)
+    };
+    let stale = !cached && refresh(&state, &repo_id, &owner, &name).await?;
+
+    let conn = state.db.lock().unwrap();
+    // One extra issue tells whether another page follows
+    let mut issues = issues_db::list_filtered(
+        &conn,
+        &repo_id,
+        issue_state,
+        &labels,
+        query.page,
+        per_page.map(|n| n + 1),
+    )
+    .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let next_page = match per_page {
+        Some(n) if issues.len() as i64 > n => {
+            issues.truncate(n as usize);
+            issues.last().map(|i| i.number)
         }
+        _ => None,
+    };
+    for issue in &mut issues {
+        issue.stale = stale;
     }
 
-    // No cache — fetch from GitHub
-    fetch_and_cache(&state, &repo_id, &owner, &name).await
+    let mut response = Json(issues).into_response();
+    if let Some(next) = next_page {
+        response
+            .headers_mut()
+            .insert(NEXT_PAGE_HEADER, HeaderValue::from(next));
+    }
+    Ok(response)
 }
 
-/// POST /v1/repos/{repo_id}/issues/refresh — force re-fetch from GitHub
+/// POST /v1/repos/{repo_id}/issues/refresh — force re-fetch from GitHub.
+/// If GitHub can't be reached, the cached issues are returned marked `stale`.
 pub async fn refresh_repo_issues(
     State(state): State<AppState>,
     Path(repo_id): Path<String>,
 ) -> Result<Json<Vec<Issue>>, (StatusCode, Json<Value>)> {
     let (owner, name) = lookup_repo(&state, &repo_id)?;
-    fetch_and_cache(&state, &repo_id, &owner, &name).await
+    let stale = refresh(&state, &repo_id, &owner, &name).await?;
+
+    let conn = state.db.lock().unwrap();
+    let mut issues =
+        issues_db::list_by_repo(&conn, &repo_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    for issue in &mut issues {
+        issue.stale = stale;
+    }
+    Ok(Json(issues))
+}
+
+/// GET /v1/repos/{repo_id}/issues/{number} — one issue, open or closed,
+/// fetched from the repo's forge into the cache. Closed issues never arrive
+/// with the open list, so this is how a past issue gets cached. If the forge
+/// can't be reached, the cached copy is returned marked `stale`.
+pub async fn get_repo_issue(
+    State(state): State<AppState>,
+    Path((repo_id, number)): Path<(String, i64)>,
+) -> Result<Json<Issue>, (StatusCode, Json<Value>)> {
+    let (owner, name) = lookup_repo(&state, &repo_id)?;
+    let forge = {
+        let conn = state.db.lock().unwrap();
+        Forge::for_repo(&conn, &repo_id)
+    };
+    let forge = match forge {
+        Ok(Some(forge)) => forge,
+        Ok(None) => return Err(api_error(ErrorCode::RepoNotFound, "repo not found")),
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+    let fetched = forge.fetch_issue(&owner, &name, number).await;
+
+    let conn = state.db.lock().unwrap();
+    match fetched {
+        Ok(Some(issue)) => issues_db::upsert_issues(&conn, &repo_id, &[issue])
+            .map_err(|e| api_error(ErrorCode::Internal, e))?,
+        Ok(None) => return Err(api_error(ErrorCode::NotFound, "issue not found")),
+        Err(e) => {
+            let cached = issues_db::get_cached_issue(&conn, &repo_id, number)
+                .map_err(|e| api_error(ErrorCode::Internal, e))?;
+            let Some(issue) = cached else {
+                return Err(api_error(ErrorCode::Upstream, e));
+            };
+            tracing::warn!("serving cached issue {}/{}#{}: {}", owner, name, number, e);
+            return Ok(Json(Issue {
+                stale: true,
+                ..issue
+            }));
+        }
+    }
+    issues_db::get_cached_issue(&conn, &repo_id, number)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .map(Json)
+        .ok_or_else(|| api_error(ErrorCode::Internal, "issue vanished from the cache"))
 }
 
 pub fn lookup_repo(
@@ -45,41 +161,49 @@ pub fn lookup_repo(
 ) -> Result<(String, String), (StatusCode, Json<Value>)> {
     let conn = state.db.lock().unwrap();
     match repos::get_by_id(&conn, repo_id) {
-        Ok(Some(repo)) if repo.deleted_at.is_some() => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "repo not found"})),
-        )),
+        Ok(Some(repo)) if repo.deleted_at.is_some() => {
+            Err(api_error(ErrorCode::RepoNotFound, "repo not found"))
+        }
         Ok(Some(repo)) => Ok((repo.owner, repo.name)),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "repo not found"})),
-        )),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Ok(None) => Err(api_error(ErrorCode::RepoNotFound, "repo not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
-async fn fetch_and_cache(
+/// Fetch the repo's open issues from its forge into the cache. Returns
+/// whether the forge couldn't be reached and the cache is serving as the
+/// last known good list.
+async fn refresh(
     state: &AppState,
     repo_id: &str,
     owner: &str,
     name: &str,
-) -> Result<Json<Vec<Issue>>, (StatusCode, Json<Value>)> {
-    let issues = github::fetch_issues(owner, name)
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, Json(json!({"error": e}))))?;
-
-    let conn = state.db.lock().unwrap();
+) -> Result<bool, (StatusCode, Json<Value>)> {
+    let forge = {
+        let conn = state.db.lock().unwrap();
+        Forge::for_repo(&conn, repo_id)
+    };
+    let forge = match forge {
+        Ok(Some(forge)) => forge,
+        Ok(None) => return Err(api_error(ErrorCode::RepoNotFound, "repo not found")),
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+    let issues = match forge.fetch_issues(owner, name).await {
+        Ok(issues) => issues,
+        Err(e) => {
+            let conn = state.db.lock().unwrap();
+            if issues_db::has_cached(&conn, repo_id).unwrap_or(false) {
+                tracing::warn!("serving cached issues for {}/{}: {}", owner, name, e);
+                return Ok(true);
+            }
+            return Err(api_error(ErrorCode::Upstream, e));
+        }
+    };
 
-    // We DO NOT clear the cache anymore, because missions refer to issues.
+    // We DO NOT clear the cache, because missions refer to issues.
     // Instead we upsert the ones we found.
+    let conn = state.db.lock().unwrap();
     issues_db::upsert_issues(&conn, repo_id, &issues)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
-
-    // Mark missing issues as closed (optional, but good for accuracy)
-    // For now, we just return the updated list.
-
-    match issues_db::list_by_repo(&conn, repo_id) {
-        Ok(issues) => Ok(Json(issues)),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
-    }
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    Ok(false)
 }
@@ -1,183 +1,247 @@
-use axum::Json;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use serde_json::{Value, json};
-
-use std::collections::{HashMap, VecDeque};
-
 use crate::AppState;
+use crate::cursor::{Cursor, NEXT_CURSOR_HEADER};
 use crate::db::missions as db;
-use crate::db::repos as repos_db;
-use crate::db::settings as settings_db;
+use crate::db::queue as queue_db;
 use crate::db::tasks as tasks_db;
-use crate::mission_service::{AssemblePromptRequest, MissionService};
-use crate::models::missions::{CreateMissionRequest, Mission};
-use crate::models::workflows::WorkflowStepFile;
-use crate::workflow_registry::WorkflowRegistry;
+use crate::db::timings as timings_db;
+use crate::error::{ErrorCode, api_error, api_error_with_details};
+use crate::mission_service::{self, CreateMissionError, EditMissionError, ReplayMissionError};
+use crate::models::missions::{
+    CreateMissionBody, DEFAULT_MISSIONS_PER_PAGE, DeleteMissionQuery, FleetSummary, GraphQuery,
+    MAX_MISSIONS_PER_PAGE, Mission, MissionCancellation, MissionGraph, MissionListQuery,
+    MissionTimings, QueueDiagnostic, ReplayMissionRequest, UpdateMissionRequest,
+};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde_json::{Value, json};
 
+/// GET /v1/missions?status=&repo_id=&q=&archived=&cursor=&limit= — missions
+/// across every repo, newest first, each carrying its repo's owner and name.
+/// Paged when `cursor` or `limit` is set: while more remain, the
+/// `X-Next-Cursor` header holds the `cursor` to ask for next.
 pub async fn list_missions(
     State(state): State<AppState>,
-) -> Result<Json<Vec<Mission>>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
-    match db::list_all(&conn) {
-        Ok(missions) => Ok(Json(missions)),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+    Query(query): Query<MissionListQuery>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let after = query
+        .cursor
+        .as_deref()
+        .map(Cursor::decode)
+        .transpose()
+        .map_err(|e| api_error(ErrorCode::InvalidRequest, e))?;
+    let limit = match (&after, query.limit) {
+        (None, None) => None,
+        (_, Some(n)) if !(1..=MAX_MISSIONS_PER_PAGE).contains(&n) => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                format!("limit must be between 1 and {}", MAX_MISSIONS_PER_PAGE),
+            ));
+        }
+        (_, n) => Some(n.unwrap_or(DEFAULT_MISSIONS_PER_PAGE)),
+    };
+
+    let conn = state.read();
+    // One extra mission tells whether another page follows
+    let mut missions = db::list_filtered(&conn, &query, after.as_ref(), limit.map(|n| n + 1))
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let next_cursor = match limit {
+        Some(n) if missions.len() as i64 > n => {
+            missions.truncate(n as usize);
+            missions
+                .last()
+                .map(|m| Cursor::new(&m.created_at, &m.mission_id).encode())
+        }
+        _ => None,
+    };
+    queue_db::annotate(&conn, &mut missions).map_err(|e| api_error(ErrorCode::Internal, e))?;
+
+    let mut response = Json(missions).into_response();
+    if let Some(next) = next_cursor.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert(NEXT_CURSOR_HEADER, next);
     }
+    Ok(response)
+}
+
+/// GET /v1/missions/summary — fleet-wide mission, task and crab counts
+pub async fn fleet_summary(
+    State(state): State<AppState>,
+) -> Result<Json<FleetSummary>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    db::fleet_summary(&conn)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
 }
 
 pub async fn list_repo_missions(
     State(state): State<AppState>,
     Path(repo_id): Path<String>,
 ) -> Result<Json<Vec<Mission>>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
-    match db::list_by_repo(&conn, &repo_id) {
-        Ok(missions) => Ok(Json(missions)),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+    let conn = state.read();
+    let mut missions =
+        db::list_by_repo(&conn, &repo_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    queue_db::annotate(&conn, &mut missions).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    Ok(Json(missions))
+}
+
+/// GET /v1/missions/{mission_id}/graph?format=mermaid|dot|json — task
+/// dependency graph coloured by status
+pub async fn get_mission_graph(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+    Query(query): Query<GraphQuery>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    if db::get_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .is_none()
+    {
+        return Err(api_error(ErrorCode::MissionNotFound, "mission not found"));
+    }
+    let tasks = tasks_db::list_tasks_for_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let graph = MissionGraph::from_tasks(&mission_id, &tasks);
+
+    match query.format.as_deref() {
+        None | Some("mermaid") => Ok((
+            [(header::CONTENT_TYPE, "text/vnd.mermaid; charset=utf-8")],
+            graph.to_mermaid(),
+        )
+            .into_response()),
+        Some("dot") => Ok((
+            [(header::CONTENT_TYPE, "text/vnd.graphviz; charset=utf-8")],
+            graph.to_dot(),
+        )
+            .into_response()),
+        Some("json") => Ok(Json(graph).into_response()),
+        Some(other) => Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!("unsupported format: {}", other),
+        )),
+    }
+}
+
+/// GET /v1/missions/{mission_id}/timings — where each task's time went
+/// (blocked, queue wait, setup, model, approval wait) and the critical path
+pub async fn get_mission_timings(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+) -> Result<Json<MissionTimings>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    if db::get_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .is_none()
+    {
+        return Err(api_error(ErrorCode::MissionNotFound, "mission not found"));
+    }
+    timings_db::mission_timings(&conn, &mission_id)
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// Per pending mission, what it is waiting on
+pub async fn queue_diagnostics(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<QueueDiagnostic>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match queue_db::diagnostics(&conn) {
+        Ok(diagnostics) => Ok(Json(diagnostics)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
 pub async fn create_mission(
     State(state): State<AppState>,
-    Json(req): Json<CreateMissionRequest>,
+    Json(body): Json<CreateMissionBody>,
 ) -> Result<(StatusCode, Json<Mission>), (StatusCode, Json<Value>)> {
     let mut conn = state.db.lock().unwrap();
 
-    // Guard: reject missions for soft-deleted repos
-    match repos_db::get_by_id(&conn, &req.repo_id) {
-        Ok(Some(repo)) if repo.deleted_at.is_some() => {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({"error": "repo not found"})),
-            ));
-        }
-        Ok(None) => {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({"error": "repo not found"})),
-            ));
-        }
-        Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))));
-        }
-        Ok(Some(_)) => {}
+    match mission_service::create_mission_with_artifacts(
+        &mut conn,
+        &body.mission,
+        &body.context_artifacts,
+    ) {
+        Ok(mission) => Ok((StatusCode::CREATED, Json(mission))),
+        Err(e) => Err(api_error(create_error_code(&e), e)),
     }
+}
 
-    // 1. Define Intent (Deterministic Branch)
-    let branch = format!("mission/issue-{}", req.issue_number);
-
-    // 2. Initialize Service
-    let service = MissionService::new(&conn)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
-
-    let prompts_root = settings_db::get(&conn, "prompts_root")
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": e.to_string()})),
-            )
-        })?
-        .ok_or((
-            StatusCode::FAILED_DEPENDENCY,
-            Json(json!({"error": "prompts_root not set"})),
-        ))?;
-
-    let registry = WorkflowRegistry::new(prompts_root);
-    let wf = registry.get_workflow(&req.workflow_name).ok_or((
-        StatusCode::NOT_FOUND,
-        Json(json!({"error": "workflow not found"})),
-    ))?;
-
-    // 3. Start Transaction
-    let tx = conn.transaction().map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )
-    })?;
-
-    // 4. Create Mission Record
-    let mission = db::insert_mission(&tx, &req, &branch)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
-
-    // Seed initial state history entry
-    db::insert_state_history_entry(&tx, &mission.mission_id, "pending")
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
-
-    // 5. Expand Workflow into Tasks (DAG-aware ordering)
-    let step_orders = compute_step_orders(&wf.steps)
-        .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({"error": e}))))?;
-
-    for (step_idx, order) in &step_orders {
-        let step = &wf.steps[*step_idx];
-        let prompt = service
-            .assemble_prompt(
-                &tx,
-                AssemblePromptRequest {
-                    workflow_name: &req.workflow_name,
-                    step_id: &step.id,
-                    flavor_id: req.flavor_id.as_deref(),
-                    repo_id: &req.repo_id,
-                    issue_number: req.issue_number,
-                    context: None, // Initial mission creation has no prior context
-                },
-            )
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
-
-        let max_retries = step.max_retries.unwrap_or(3) as i64;
-        let status = if *order == 0 { "queued" } else { "blocked" };
-
-        tasks_db::insert_task(
-            &tx,
-            &mission.mission_id,
-            &step.id,
-            *order as i64,
-            &prompt,
-            max_retries,
-            status,
-        )
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+pub(crate) fn create_error_code(e: &CreateMissionError) -> ErrorCode {
+    match e {
+        CreateMissionError::RepoNotFound => ErrorCode::RepoNotFound,
+        CreateMissionError::WorkflowNotFound => ErrorCode::WorkflowNotFound,
+        CreateMissionError::SourceMissionNotFound => ErrorCode::MissionNotFound,
+        CreateMissionError::SourceMissionOtherRepo => ErrorCode::InvalidRequest,
+        CreateMissionError::PromptsRootNotSet => ErrorCode::PromptsRootNotSet,
+        CreateMissionError::InvalidWorkflow(_) => ErrorCode::InvalidRequest,
+        CreateMissionError::Internal(_) => ErrorCode::Internal,
     }
+}
 
-    // 6. Commit
-    tx.commit().map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )
-    })?;
+fn edit_error_code(e: &EditMissionError) -> ErrorCode {
+    match e {
+        EditMissionError::MissionNotFound => ErrorCode::MissionNotFound,
+        EditMissionError::AlreadyStarted(_) => ErrorCode::InvalidState,
+        EditMissionError::WorkflowNotFound => ErrorCode::WorkflowNotFound,
+        EditMissionError::PromptsRootNotSet => ErrorCode::PromptsRootNotSet,
+        EditMissionError::InvalidWorkflow(_) => ErrorCode::InvalidRequest,
+        EditMissionError::Internal(_) => ErrorCode::Internal,
+    }
+}
 
-    Ok((StatusCode::CREATED, Json(mission)))
+/// POST /v1/missions/{mission_id}/replay — run the mission's issue again on
+/// the current workflow, as a new mission linked back to this one
+pub async fn replay_mission(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+    body: Option<Json<ReplayMissionRequest>>,
+) -> Result<(StatusCode, Json<Mission>), (StatusCode, Json<Value>)> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let mut conn = state.db.lock().unwrap();
+
+    match mission_service::replay_mission(&mut conn, &mission_id, &req) {
+        Ok(mission) => Ok((StatusCode::CREATED, Json(mission))),
+        Err(e) => {
+            let code = match &e {
+                ReplayMissionError::MissionNotFound => ErrorCode::MissionNotFound,
+                ReplayMissionError::VersionMismatch(..) => ErrorCode::InvalidState,
+                ReplayMissionError::Create(e) => create_error_code(e),
+                ReplayMissionError::Edit(e) => edit_error_code(e),
+            };
+            Err(api_error(code, e))
+        }
+    }
 }
 
 pub async fn get_mission(
     State(state): State<AppState>,
     Path(mission_id): Path<String>,
 ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
 
-    let mission = db::get_mission(&conn, &mission_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "mission not found"})),
-        ))?;
+    let mut mission = db::get_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or(api_error(ErrorCode::MissionNotFound, "mission not found"))?;
+    queue_db::annotate(&conn, std::slice::from_mut(&mut mission))
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
 
     let mut tasks = tasks_db::list_tasks_for_mission(&conn, &mission_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
 
     // Hydrate tasks with their runs
     let mut tasks_with_runs = Vec::new();
     for task in tasks.drain(..) {
         let runs = tasks_db::list_runs_for_task(&conn, &task.task_id)
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+            .map_err(|e| api_error(ErrorCode::Internal, e))?;
 
         let mut task_val = json!(task);
         task_val["runs"] = json!(runs);
         tasks_with_runs.push(task_val);
     }
 
-    let state_history = db::get_state_history(&conn, &mission_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+    let state_history =
+        db::get_state_history(&conn, &mission_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
 
     Ok(Json(json!({
         "mission": mission,
@@ -186,72 +250,101 @@ pub async fn get_mission(
     })))
 }
 
-/// Topological sort using Kahn's algorithm.
-/// Returns a vec of (step_index, depth) pairs where depth is the DAG level.
-pub fn topological_sort_steps(steps: &[WorkflowStepFile]) -> Result<Vec<(usize, usize)>, String> {
-    let id_to_idx: HashMap<&str, usize> = steps
-        .iter()
-        .enumerate()
-        .map(|(i, s)| (s.id.as_str(), i))
-        .collect();
-
-    let n = steps.len();
-    let mut in_degree = vec![0usize; n];
-    let mut children: Vec<Vec<usize>> = vec![vec![]; n];
-
-    for (i, step) in steps.iter().enumerate() {
-        if let Some(deps) = &step.depends_on {
-            for dep_id in deps {
-                let &parent = id_to_idx.get(dep_id.as_str()).ok_or_else(|| {
-                    format!("step '{}' depends on unknown step '{}'", step.id, dep_id)
-                })?;
-                children[parent].push(i);
-                in_degree[i] += 1;
-            }
-        }
-    }
-
-    let mut queue: VecDeque<usize> = VecDeque::new();
-    let mut depth = vec![0usize; n];
+/// PATCH /v1/missions/{id} — edit a mission that is still pending and
+/// untouched by crabs; its tasks are expanded again from the new workflow,
+/// flavor or prompt
+pub async fn update_mission(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+    Json(req): Json<UpdateMissionRequest>,
+) -> Result<Json<Mission>, (StatusCode, Json<Value>)> {
+    let mut conn = state.db.lock().unwrap();
 
-    for (i, &deg) in in_degree.iter().enumerate() {
-        if deg == 0 {
-            queue.push_back(i);
-        }
+    match mission_service::edit_pending_mission(&mut conn, &mission_id, &req) {
+        Ok(mission) => Ok(Json(mission)),
+        Err(e) => Err(api_error(edit_error_code(&e), e)),
     }
+}
 
-    let mut visited = 0usize;
-    let mut result: Vec<(usize, usize)> = Vec::with_capacity(n);
+/// POST /v1/missions/{id}/cancel — skip every unfinished task and mark the
+/// mission cancelled; crabs running its tasks kill their agents
+pub async fn cancel_mission(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+) -> Result<Json<MissionCancellation>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
 
-    while let Some(idx) = queue.pop_front() {
-        visited += 1;
-        result.push((idx, depth[idx]));
+    let mission = db::get_mission(&conn, &mission_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or(api_error(ErrorCode::MissionNotFound, "mission not found"))?;
+    if matches!(
+        mission.status.as_str(),
+        "completed" | "failed" | "cancelled"
+    ) {
+        return Err(api_error(
+            ErrorCode::InvalidState,
+            format!("mission is already {}", mission.status),
+        ));
+    }
 
-        for &child in &children[idx] {
-            in_degree[child] -= 1;
-            depth[child] = depth[child].max(depth[idx] + 1);
-            if in_degree[child] == 0 {
-                queue.push_back(child);
-            }
-        }
+    match mission_service::cancel_mission(&conn, &mission_id, "api") {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
+}
 
-    if visited != n {
-        return Err("cycle detected in workflow step dependencies".to_string());
+/// DELETE /v1/missions/{id}?confirm=true&mode=delete|archive
+///
+/// Without `confirm` this is a dry run that only reports the dependent rows.
+/// Missions with running tasks cannot be removed until those tasks finish.
+pub async fn delete_mission(
+    State(state): State<AppState>,
+    Path(mission_id): Path<String>,
+    Query(query): Query<DeleteMissionQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let mode = query.mode.as_deref().unwrap_or("delete");
+    if mode != "delete" && mode != "archive" {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "mode must be 'delete' or 'archive'",
+        ));
     }
 
-    Ok(result)
-}
+    let mut conn = state.db.lock().unwrap();
+
+    match db::get_mission(&conn, &mission_id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err(api_error(ErrorCode::MissionNotFound, "mission not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
 
-/// Compute step_order values for workflow steps.
-/// If no step has `depends_on`, falls back to sequential enumerate (backward compat).
-/// Otherwise uses topological sort to assign DAG depth as step_order.
-pub fn compute_step_orders(steps: &[WorkflowStepFile]) -> Result<Vec<(usize, usize)>, String> {
-    let has_deps = steps.iter().any(|s| s.depends_on.is_some());
+    let report =
+        db::deletion_report(&conn, &mission_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
 
-    if !has_deps {
-        return Ok(steps.iter().enumerate().map(|(i, _)| (i, i)).collect());
+    if !query.confirm {
+        return Ok(Json(
+            json!({"mode": mode, "applied": false, "report": report}),
+        ));
+    }
+    if report.running_tasks > 0 {
+        return Err(api_error_with_details(
+            ErrorCode::InvalidState,
+            "mission has running tasks",
+            json!({"report": report}),
+        ));
     }
 
-    topological_sort_steps(steps)
+    let result = if mode == "archive" {
+        db::archive_mission(&conn, &mission_id)
+    } else {
+        db::delete_mission(&mut conn, &mission_id)
+    };
+    match result {
+        Ok(()) => Ok(Json(
+            json!({"mode": mode, "applied": true, "report": report}),
+        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
 }
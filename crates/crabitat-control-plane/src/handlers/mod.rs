@@ -1,8 +1,21 @@
+pub mod analytics;
+pub mod api_keys;
+pub mod artifacts;
+pub mod benchmarks;
+pub mod blobs;
+pub mod burrows;
+pub mod chaos;
+pub mod consistency;
+pub mod crabs;
+pub mod events;
 pub mod github;
 pub mod issues;
 pub mod missions;
 pub mod repos;
+pub mod run_logs;
+pub mod scheduler;
 pub mod settings;
 pub mod system;
 pub mod tasks;
+pub mod triggers;
 pub mod workflows;
@@ -1,11 +1,24 @@
 use axum::Json;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use serde_json::{Value, json};
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
 
 use crate::AppState;
+use crate::db::analytics;
+use crate::db::changelog;
 use crate::db::repos;
+use crate::error::{ErrorCode, api_error};
+use crate::forge::Forge;
+use crate::models::analytics::{
+    ACTIVITY_GRANULARITIES, ActivityQuery, MAX_ACTIVITY_RANGE_SECS, RepoActivity,
+};
+use crate::models::changelog::ChangelogQuery;
+use crate::models::repos::{
+    FORGES, ForgeConfig, IssueCompletion, RiskRules, SandboxRequest, SlackSettingsRequest,
+};
 use crate::models::{CreateRepoRequest, Repo, UpdateRepoRequest};
+use crate::risk;
 
 pub async fn create_repo(
     State(state): State<AppState>,
@@ -20,17 +33,17 @@ pub async fn create_repo(
         body.repo_url.as_deref(),
     ) {
         Ok(repo) => Ok((StatusCode::CREATED, Json(repo))),
-        Err(e) => Err((StatusCode::CONFLICT, Json(json!({"error": e})))),
+        Err(e) => Err(api_error(ErrorCode::InvalidState, e)),
     }
 }
 
 pub async fn list_repos(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<Repo>>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     match repos::list(&conn) {
         Ok(repos) => Ok(Json(repos)),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -38,14 +51,14 @@ pub async fn get_repo(
     State(state): State<AppState>,
     Path(repo_id): Path<String>,
 ) -> Result<Json<Repo>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     match repos::get_by_id(&conn, &repo_id) {
         Ok(Some(repo)) if repo.deleted_at.is_some() => {
-            Err((StatusCode::NOT_FOUND, Json(json!({"error": "not found"}))))
+            Err(api_error(ErrorCode::RepoNotFound, "not found"))
         }
         Ok(Some(repo)) => Ok(Json(repo)),
-        Ok(None) => Err((StatusCode::NOT_FOUND, Json(json!({"error": "not found"})))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Ok(None) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -56,8 +69,8 @@ pub async fn delete_repo(
     let conn = state.db.lock().unwrap();
     match repos::delete(&conn, &repo_id) {
         Ok(true) => Ok(StatusCode::NO_CONTENT),
-        Ok(false) => Err((StatusCode::NOT_FOUND, Json(json!({"error": "not found"})))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -74,7 +87,324 @@ pub async fn update_repo(
         body.repo_url.as_deref(),
     ) {
         Ok(true) => Ok(StatusCode::NO_CONTENT),
-        Ok(false) => Err((StatusCode::NOT_FOUND, Json(json!({"error": "not found"})))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// PUT /v1/repos/{repo_id}/slack — set or clear the repo's Slack webhook
+pub async fn update_slack_settings(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Json(body): Json<SlackSettingsRequest>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let webhook_url = body
+        .webhook_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|url| !url.is_empty());
+    if webhook_url.is_some_and(|url| !url.starts_with("https://")) {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "webhook_url must be an https:// URL",
+        ));
+    }
+    let conn = state.db.lock().unwrap();
+    match repos::set_slack_webhook(&conn, &repo_id, webhook_url) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
+
+/// PUT /v1/repos/{repo_id}/sandbox — mark the repo as a disposable copy that
+/// benchmarks may open pull requests against
+pub async fn update_sandbox(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Json(body): Json<SandboxRequest>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match repos::set_sandbox(&conn, &repo_id, body.sandbox) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/repos/{repo_id}/risk-rules — what makes the repo's missions high-risk
+pub async fn get_risk_rules(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+) -> Result<Json<RiskRules>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match repos::risk_rules(&conn, &repo_id) {
+        Ok(Some(rules)) => Ok(Json(rules)),
+        Ok(None) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// PUT /v1/repos/{repo_id}/risk-rules — replace the repo's risk rules
+pub async fn update_risk_rules(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Json(body): Json<RiskRules>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    risk::validate(&body).map_err(|e| api_error(ErrorCode::InvalidRequest, e))?;
+    let conn = state.db.lock().unwrap();
+    match repos::set_risk_rules(&conn, &repo_id, &body) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/repos/{repo_id}/issue-completion — what happens to issues whose missions complete
+pub async fn get_issue_completion(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+) -> Result<Json<IssueCompletion>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match repos::issue_completion(&conn, &repo_id) {
+        Ok(Some(completion)) => Ok(Json(completion)),
+        Ok(None) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// PUT /v1/repos/{repo_id}/issue-completion — close and/or label issues once their missions complete
+pub async fn update_issue_completion(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Json(mut body): Json<IssueCompletion>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    body.label = body.label.map(|l| l.trim().to_string());
+    if body.label.as_deref() == Some("") {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "label must not be empty; leave it out to add none",
+        ));
+    }
+    let completion = (body != IssueCompletion::default()).then_some(&body);
+    let conn = state.db.lock().unwrap();
+    match repos::set_issue_completion(&conn, &repo_id, completion) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/repos/{repo_id}/forge — where the repo's issues live
+pub async fn get_forge(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+) -> Result<Json<ForgeConfig>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match repos::forge(&conn, &repo_id) {
+        Ok(Some(forge)) => Ok(Json(forge)),
+        Ok(None) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// PUT /v1/repos/{repo_id}/forge — move the repo's issue queue to GitHub,
+/// a GitLab instance, a Gitea/Forgejo instance or a Jira project
+pub async fn update_forge(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Json(mut body): Json<ForgeConfig>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    if !FORGES.contains(&body.kind.as_str()) {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!("kind must be one of {}", FORGES.join(", ")),
+        ));
+    }
+    body.base_url = body
+        .base_url
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty());
+    match &body.base_url {
+        Some(_) if body.kind == "github" => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                "base_url is only for gitlab, gitea and jira",
+            ));
+        }
+        None if body.kind == "gitea" || body.kind == "jira" => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                format!("{} needs the instance's base_url", body.kind),
+            ));
+        }
+        Some(url) if !url.starts_with("https://") && !url.starts_with("http://") => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                "base_url must be an http(s):// URL",
+            ));
+        }
+        _ => {}
+    }
+    match &mut body.jira {
+        Some(_) if body.kind != "jira" => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                "jira settings are only for the jira forge",
+            ));
+        }
+        None if body.kind == "jira" => {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                "jira needs the project to take issues from",
+            ));
+        }
+        Some(jira) => {
+            jira.project = jira.project.trim().to_string();
+            if jira.project.is_empty()
+                || !jira
+                    .project
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(api_error(
+                    ErrorCode::InvalidRequest,
+                    "jira.project must be a project key, e.g. PLAT",
+                ));
+            }
+            jira.jql = jira
+                .jql
+                .take()
+                .map(|jql| jql.trim().to_string())
+                .filter(|jql| !jql.is_empty());
+            if jira
+                .jql
+                .as_deref()
+                .is_some_and(|jql| jql.to_lowercase().contains("order by"))
+            {
+                return Err(api_error(
+                    ErrorCode::InvalidRequest,
+                    "jira.jql is a filter; issues are always ordered by last update",
+                ));
+            }
+            jira.done_transition = jira
+                .done_transition
+                .take()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty());
+        }
+        None => {}
+    }
+    // Refuses instances the forge's credentials aren't meant for
+    if let Err(e) = Forge::from_config(&body) {
+        return Err(api_error(ErrorCode::InvalidRequest, e));
+    }
+    let conn = state.db.lock().unwrap();
+    match repos::set_forge(&conn, &repo_id, &body) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/repos/{repo_id}/changelog?since=&format=json|markdown — completed missions as release notes
+pub async fn get_changelog(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Query(query): Query<ChangelogQuery>,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match repos::get_by_id(&conn, &repo_id) {
+        Ok(Some(repo)) if repo.deleted_at.is_none() => {}
+        Ok(_) => return Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
+
+    let log = changelog::build(&conn, &repo_id, query.since.as_deref())
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+
+    match query.format.as_deref() {
+        Some("markdown") => Ok((
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            log.to_markdown(),
+        )
+            .into_response()),
+        None | Some("json") => Ok(Json(log).into_response()),
+        Some(other) => Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!("unsupported format: {}", other),
+        )),
+    }
+}
+
+/// GET /v1/repos/{repo_id}/activity?granularity=hour|day&from=&to= — busy
+/// minutes per hour or day, for spotting quiet maintenance windows
+pub async fn get_activity(
+    State(state): State<AppState>,
+    Path(repo_id): Path<String>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<Json<RepoActivity>, (StatusCode, Json<Value>)> {
+    let granularity = query.granularity.as_deref().unwrap_or("hour");
+    let Some(&(_, step)) = ACTIVITY_GRANULARITIES
+        .iter()
+        .find(|(name, _)| *name == granularity)
+    else {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "granularity must be 'hour' or 'day'",
+        ));
+    };
+
+    let conn = state.read();
+    match repos::get_by_id(&conn, &repo_id) {
+        Ok(Some(repo)) if repo.deleted_at.is_none() => {}
+        Ok(_) => return Err(api_error(ErrorCode::RepoNotFound, "not found")),
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
+
+    let parse = |name: &str, value: &str| {
+        analytics::unix_secs(&conn, value)
+            .map_err(|e| api_error(ErrorCode::Internal, e))?
+            .ok_or_else(|| {
+                api_error(
+                    ErrorCode::InvalidRequest,
+                    format!("{name} is not an ISO-8601 timestamp"),
+                )
+            })
+    };
+    let to = parse("to", query.to.as_deref().unwrap_or("now"))?;
+    let from = match query.from.as_deref() {
+        Some(from) => parse("from", from)?,
+        None => to - 7 * 86_400,
+    };
+    // Whole buckets: the range starts on a UTC hour or day boundary
+    let from = from - from.rem_euclid(step);
+    if from >= to {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "from must be before to",
+        ));
+    }
+    if to - from > MAX_ACTIVITY_RANGE_SECS {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "range is longer than {} days",
+                MAX_ACTIVITY_RANGE_SECS / 86_400
+            ),
+        ));
+    }
+
+    let activity = analytics::repo_activity(&conn, &repo_id, from, to, step).and_then(|buckets| {
+        Ok(RepoActivity {
+            repo_id: repo_id.clone(),
+            granularity: granularity.to_string(),
+            from: analytics::iso_timestamp(&conn, from)?,
+            to: analytics::iso_timestamp(&conn, to)?,
+            buckets,
+        })
+    });
+    activity
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures_util::stream;
+use serde_json::{Value, json};
+
+use crate::AppState;
+use crate::db::run_logs as db;
+use crate::db::tasks as tasks_db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::run_logs::{AppendRunLogsRequest, RUN_LOG_STREAMS, RunLogChunk, RunLogsQuery};
+use crate::redaction::Scanner;
+
+/// How often a follower checks for new chunks
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A follower gives up on a run that neither grows nor finishes for this long
+/// (its crab most likely died)
+const FOLLOW_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// POST /v1/runs/{run_id}/logs — append stdout/stderr chunks while the agent runs.
+///
+/// Crabs pick the run id up front and pass it with the final run report, so
+/// chunks can arrive before the run itself is recorded.
+pub async fn append_run_logs(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    Json(mut body): Json<AppendRunLogsRequest>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    if let Some(chunk) = body
+        .chunks
+        .iter()
+        .find(|c| !RUN_LOG_STREAMS.contains(&c.stream.as_str()))
+    {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "unknown stream {:?}; expected one of {}",
+                chunk.stream,
+                RUN_LOG_STREAMS.join(", ")
+            ),
+        ));
+    }
+
+    let conn = state.db.lock().unwrap();
+    tasks_db::get_task(&conn, &body.task_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or_else(|| api_error(ErrorCode::TaskNotFound, "task not found"))?;
+
+    let run = tasks_db::get_run(&conn, &run_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let owner = match &run {
+        Some(run) => Some(run.task_id.clone()),
+        None => db::task_for_run(&conn, &run_id).map_err(|e| api_error(ErrorCode::Internal, e))?,
+    };
+    if owner.is_some_and(|task_id| task_id != body.task_id) {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "run belongs to another task",
+        ));
+    }
+    if run.is_some_and(|run| run.finished_at.is_some()) {
+        return Err(api_error(ErrorCode::InvalidState, "run already finished"));
+    }
+
+    // Scrub secrets the agent may have echoed before anything is stored. A
+    // secret can be split across chunks, so each one is scanned along with the
+    // tail of the previous chunk on its stream.
+    let scanner = Scanner::from_settings(&conn);
+    body.chunks.sort_by_key(|chunk| chunk.seq);
+    let mut fresh = Vec::with_capacity(body.chunks.len());
+    for chunk in &body.chunks {
+        let stored =
+            db::stored(&conn, &run_id, chunk.seq).map_err(|e| api_error(ErrorCode::Internal, e))?;
+        fresh.push(!stored);
+    }
+    let mut redactions = 0;
+    for i in (0..body.chunks.len()).filter(|&i| fresh[i]) {
+        let (seq, stream) = (body.chunks[i].seq, body.chunks[i].stream.clone());
+        let in_batch = (0..i)
+            .rev()
+            .find(|&j| fresh[j] && body.chunks[j].stream == stream);
+        let stored = db::previous(&conn, &run_id, &stream, seq)
+            .map_err(|e| api_error(ErrorCode::Internal, e))?
+            .filter(|prev| in_batch.is_none_or(|j| prev.seq > body.chunks[j].seq));
+        let previous = match (&stored, in_batch) {
+            (Some(prev), _) => prev.content.clone(),
+            (None, Some(j)) => body.chunks[j].content.clone(),
+            (None, None) => String::new(),
+        };
+        let (keep, redacted, count) =
+            scanner.redact_continuation(&previous, &body.chunks[i].content);
+        if count == 0 {
+            continue;
+        }
+        if keep < previous.len() {
+            match (stored, in_batch) {
+                (Some(prev), _) => db::set_content(&conn, &run_id, prev.seq, &previous[..keep])
+                    .map_err(|e| api_error(ErrorCode::Internal, e))?,
+                (None, Some(j)) => body.chunks[j].content.truncate(keep),
+                (None, None) => {}
+            }
+        }
+        body.chunks[i].content = redacted;
+        body.chunks[i].redactions = count as i64;
+        redactions += count;
+    }
+    if redactions > 0 {
+        tracing::warn!(
+            "redacted {} secret(s) from streamed logs of run {}",
+            redactions,
+            run_id
+        );
+    }
+
+    match db::append(&conn, &run_id, &body.task_id, &body.chunks) {
+        Ok(appended) => Ok(Json(json!({ "appended": appended }))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/runs/{run_id}/logs — the run's full stdout/stderr as ordered chunks.
+///
+/// With `follow=true` the response is a server-sent event stream: a `chunk`
+/// event per chunk (its id is the chunk's `seq`, so a reconnecting
+/// `EventSource` resumes via `Last-Event-ID`), then `end` once the run finishes.
+pub async fn get_run_logs(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    Query(query): Query<RunLogsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<Value>)> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    let after = last_event_id.or(query.after).unwrap_or(-1);
+
+    let chunks = {
+        let conn = state.read();
+        let known = tasks_db::get_run(&conn, &run_id)
+            .map_err(|e| api_error(ErrorCode::Internal, e))?
+            .is_some()
+            || db::task_for_run(&conn, &run_id)
+                .map_err(|e| api_error(ErrorCode::Internal, e))?
+                .is_some();
+        if !known {
+            return Err(api_error(ErrorCode::NotFound, "run not found"));
+        }
+        if query.follow {
+            None
+        } else {
+            Some(db::list(&conn, &run_id, after).map_err(|e| api_error(ErrorCode::Internal, e))?)
+        }
+    };
+    if let Some(chunks) = chunks {
+        return Ok(Json(chunks).into_response());
+    }
+
+    let follow = Follow {
+        state,
+        run_id,
+        after,
+        pending: VecDeque::new(),
+        last_activity: Instant::now(),
+        finished: false,
+        ended: false,
+        polled: false,
+    };
+    let events = stream::unfold(follow, |mut f| async move {
+        f.next_event()
+            .await
+            .map(|event| (Ok::<_, Infallible>(event), f))
+    });
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// Cursor of one `follow=true` stream
+struct Follow {
+    state: AppState,
+    run_id: String,
+    after: i64,
+    pending: VecDeque<RunLogChunk>,
+    last_activity: Instant,
+    finished: bool,
+    ended: bool,
+    polled: bool,
+}
+
+impl Follow {
+    /// The next event to send, or `None` once the stream is over
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(chunk) = self.pending.pop_front() {
+                self.after = chunk.seq;
+                let data = serde_json::to_string(&chunk).unwrap_or_default();
+                return Some(
+                    Event::default()
+                        .event("chunk")
+                        .id(chunk.seq.to_string())
+                        .data(data),
+                );
+            }
+            if self.finished {
+                return (!std::mem::replace(&mut self.ended, true))
+                    .then(|| Event::default().event("end").data(""));
+            }
+            if self.last_activity.elapsed() > FOLLOW_IDLE_TIMEOUT {
+                tracing::debug!("stopped following idle run {}", self.run_id);
+                return None;
+            }
+            if self.polled {
+                tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+            }
+            self.polled = true;
+
+            // Check for the finish first: every chunk written before it is then listed
+            let polled = {
+                let conn = self.state.read();
+                db::run_finished(&conn, &self.run_id)
+                    .and_then(|finished| Ok((finished, db::list(&conn, &self.run_id, self.after)?)))
+            };
+            match polled {
+                Ok((finished, chunks)) => {
+                    if !chunks.is_empty() {
+                        self.last_activity = Instant::now();
+                    }
+                    self.pending.extend(chunks);
+                    self.finished = finished;
+                }
+                Err(e) => {
+                    tracing::warn!("following logs of run {} failed: {}", self.run_id, e);
+                    return None;
+                }
+            }
+        }
+    }
+}
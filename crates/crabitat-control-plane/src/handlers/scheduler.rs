@@ -0,0 +1,42 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::scheduler as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::scheduler::SchedulerDecision;
+
+#[derive(Deserialize)]
+pub struct DecisionsQuery {
+    pub task_id: Option<String>,
+    pub worker_id: Option<String>,
+    /// Defaults to 100
+    pub limit: Option<i64>,
+}
+
+/// GET /v1/admin/scheduler/decisions — why recent polls handed out or passed
+/// over each queued task, newest first
+pub async fn list_decisions(
+    State(state): State<AppState>,
+    Query(query): Query<DecisionsQuery>,
+) -> Result<Json<Vec<SchedulerDecision>>, (StatusCode, Json<Value>)> {
+    let limit = query.limit.unwrap_or(100);
+    if limit < 1 {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "limit must be positive",
+        ));
+    }
+    let conn = state.read();
+    db::list_decisions(
+        &conn,
+        query.task_id.as_deref(),
+        query.worker_id.as_deref(),
+        limit,
+    )
+    .map(Json)
+    .map_err(|e| api_error(ErrorCode::Internal, e))
+}
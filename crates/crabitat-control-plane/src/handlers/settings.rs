@@ -1,22 +1,20 @@
 use axum::Json;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use serde_json::{Value, json};
+use serde_json::Value;
 
 use crate::AppState;
 use crate::db::settings as db;
+use crate::error::{ErrorCode, api_error};
 use crate::models::settings::{Setting, UpdateSettingRequest};
 
 pub async fn list_settings(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<Setting>>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     match db::list_all(&conn) {
         Ok(settings) => Ok(Json(settings)),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -24,17 +22,11 @@ pub async fn get_setting(
     State(state): State<AppState>,
     Path(key): Path<String>,
 ) -> Result<Json<Setting>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     match db::get_full(&conn, &key) {
         Ok(Some(setting)) => Ok(Json(setting)),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "setting not found"})),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )),
+        Ok(None) => Err(api_error(ErrorCode::NotFound, "setting not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -47,18 +39,12 @@ pub async fn update_setting(
     match db::set(&conn, &key, &body.value) {
         Ok(_) => match db::get_full(&conn, &key) {
             Ok(Some(setting)) => Ok(Json(setting)),
-            Ok(None) => Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "setting not found after upsert"})),
-            )),
-            Err(e) => Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": e.to_string()})),
+            Ok(None) => Err(api_error(
+                ErrorCode::Internal,
+                "setting not found after upsert",
             )),
+            Err(e) => Err(api_error(ErrorCode::Internal, e)),
         },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
@@ -1,19 +1,119 @@
 use crate::AppState;
+use crate::db::analytics as analytics_db;
+use crate::db::crabs as crabs_db;
+use crate::db::job_leases as leases_db;
+use crate::db::missions as missions_db;
 use crate::db::settings as settings_db;
+use crate::db::status_changes as changes_db;
+use crate::db::tasks as tasks_db;
+use crate::error::{ErrorCode, api_error};
 use crate::github;
-use crate::models::system::SystemStatus;
+use crate::jobs;
+use crate::models::crabs::CrabExecutors;
+use crate::models::system::{JobLease, STATUS_KINDS, StatusDelta, StatusDeltaQuery, SystemStatus};
 use axum::Json;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
 use serde::Deserialize;
 use serde_json::{Value, json};
 use std::fs;
 
-pub async fn get_status() -> Json<SystemStatus> {
-    let status = github::check_status().await;
+pub async fn get_status(State(state): State<AppState>) -> Json<SystemStatus> {
+    let mut status = github::check_status().await;
+    let conn = state.read();
+    if let Ok(today) = analytics_db::day_offset(&conn, 0) {
+        status.crabs = analytics_db::crab_stats(&conn, &today).unwrap_or_default();
+    }
     Json(status)
 }
 
+/// GET /v1/system/executors — executor inventory of the crabs polling in the last hour
+pub async fn list_executors(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CrabExecutors>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match crabs_db::list_executors(&conn, crabs_db::ACTIVE_CRAB_SECS) {
+        Ok(crabs) => Ok(Json(crabs)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/status?since_seq=&since_ms=&repo_id=&kinds= — missions, tasks and
+/// runs changed since the client's last look, with the sequence number to ask
+/// from next. A repo page narrows it to its repo and the kinds it shows.
+pub async fn get_status_delta(
+    State(state): State<AppState>,
+    Query(query): Query<StatusDeltaQuery>,
+) -> Result<Json<StatusDelta>, (StatusCode, Json<Value>)> {
+    let since_seq = query.since_seq.unwrap_or(0);
+    let since_ms = query.since_ms.unwrap_or(0);
+    let repo_id = query.repo_id.as_deref();
+    let kinds: Vec<&str> = match query.kinds.as_deref() {
+        Some(kinds) => kinds.split(',').map(str::trim).collect(),
+        None => STATUS_KINDS.to_vec(),
+    };
+    if let Some(unknown) = kinds.iter().find(|k| !STATUS_KINDS.contains(k)) {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "unknown kind {:?}; expected any of {}",
+                unknown,
+                STATUS_KINDS.join(", ")
+            ),
+        ));
+    }
+    let wants = |kind: &str| kinds.contains(&kind);
+    let conn = state.read();
+    let as_of_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    // Read the sequence first: a change landing mid-read is sent again next time
+    // rather than skipped
+    let delta = changes_db::latest_seq(&conn).and_then(|seq| {
+        Ok(StatusDelta {
+            seq,
+            as_of_ms,
+            missions: if wants("missions") {
+                missions_db::list_changed_since(&conn, since_seq, since_ms, repo_id)?
+            } else {
+                Vec::new()
+            },
+            tasks: if wants("tasks") {
+                tasks_db::list_tasks_changed_since(&conn, since_seq, since_ms, repo_id)?
+            } else {
+                Vec::new()
+            },
+            runs: if wants("runs") {
+                tasks_db::list_runs_changed_since(&conn, since_seq, since_ms, repo_id)?
+            } else {
+                Vec::new()
+            },
+            deleted_mission_ids: if wants("missions") {
+                changes_db::deleted_missions_since(&conn, since_seq, since_ms)?
+            } else {
+                Vec::new()
+            },
+        })
+    });
+    delta
+        .map(Json)
+        .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// GET /v1/system/jobs — who runs each background job
+pub async fn list_job_leases(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<JobLease>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    let mut leases = leases_db::list(&conn).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    for lease in &mut leases {
+        lease.held_here = lease.holder == jobs::instance_id();
+    }
+    Ok(Json(leases))
+}
+
 #[derive(Deserialize)]
 pub struct DirQuery {
     pub q: String,
@@ -63,30 +163,21 @@ pub async fn get_environment_path(
     State(state): State<AppState>,
     Path((env, res_type, res_name)): Path<(String, String, String)>,
 ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     match settings_db::get_environment_path(&conn, &env, &res_type, &res_name) {
         Ok(Some(path)) => Ok(Json(json!({ "path": path }))),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "path not found"})),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )),
+        Ok(None) => Err(api_error(ErrorCode::NotFound, "path not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
 pub async fn list_environment_paths(
     State(state): State<AppState>,
 ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     match settings_db::list_all_environment_paths(&conn) {
         Ok(paths) => Ok(Json(json!(paths))),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -103,9 +194,14 @@ pub async fn update_environment_path(
     let conn = state.db.lock().unwrap();
     match settings_db::upsert_environment_path(&conn, &env, &res_type, &res_name, &body.path) {
         Ok(_) => Ok(StatusCode::NO_CONTENT),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
+
+/// GET /v1/metrics — per-route latency and error metrics in Prometheus text format
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
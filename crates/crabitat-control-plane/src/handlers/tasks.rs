@@ -5,34 +5,175 @@ use serde::Deserialize;
 use serde_json::{Value, json};
 
 use crate::AppState;
+use crate::chaos::{self, Fault};
+use crate::db::burrows as burrows_db;
+use crate::db::crabs as crabs_db;
 use crate::db::missions as db_missions;
-use crate::db::tasks as db;
-use crate::mission_service::reassemble_prompt_with_context;
-use crate::models::tasks::{CreateRunRequest, RetryTaskRequest};
+use crate::db::run_logs as run_logs_db;
+use crate::db::settings as settings_db;
+use crate::db::tasks::{self as db, TransitionError};
+use crate::error::{ErrorCode, api_error};
+use crate::issue_status;
+use crate::mission_service::{
+    GateOutcome, advance_completed_task, apply_quality_gate, hold_for_checks, kill_assignment,
+    promote_next_tier, reassemble_prompt_with_context, requeue_failed_task, upstream_context,
+};
+use crate::models::crabs::Executor;
+use crate::models::tasks::{
+    AddContextRequest, ApproveTaskRequest, CreateRunRequest, DebugRunRequest,
+    RejectApprovalRequest, RejectTaskRequest, RetryTaskRequest, Run, SendMessageRequest,
+};
+use crate::redaction::Scanner;
+use crate::risk;
+use crate::workflow_registry::WorkflowRegistry;
 
 #[derive(Deserialize)]
 pub struct TaskQuery {
     pub worker_id: Option<String>,
+    /// When set, omit `assembled_prompt` so the worker rebuilds it from cached `prompt_chunks`
+    #[serde(default)]
+    pub delta: bool,
+    /// Role of the polling crab; the response then carries its prompt stack hash
+    pub role: Option<String>,
+    /// Agent CLIs the crab can run, as `name@version,...`; steps that need an
+    /// executor are only handed to crabs reporting it
+    pub executors: Option<String>,
 }
 
+/// GET /v1/tasks/next — peek at what `POST /v1/tasks/claim` would hand this
+/// crab, without claiming or recording anything
 pub async fn get_next_task(
     State(state): State<AppState>,
     Query(query): Query<TaskQuery>,
 ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    next_task_response(&conn, &query, false)
+}
+
+/// Like `GET /next`, but atomically moves the task to `running` for the calling crab
+pub async fn claim_task(
+    State(state): State<AppState>,
+    Query(query): Query<TaskQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    if query.worker_id.is_none() {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "worker_id is required to claim a task",
+        ));
+    }
     let conn = state.db.lock().unwrap();
-    match db::get_next_queued_task(&conn, query.worker_id.as_deref()) {
-        Ok(Some(task_with_git)) => Ok(Json(json!(task_with_git))),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "no queued tasks"})),
-        )),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+    next_task_response(&conn, &query, true)
+}
+
+fn next_task_response(
+    conn: &rusqlite::Connection,
+    query: &TaskQuery,
+    claim: bool,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    // Pending debug runs go first; they carry their own prompt and bypass the mission flow
+    let debug_run = if claim {
+        db::claim_debug_run(conn, query.worker_id.as_deref())
+    } else {
+        db::next_debug_run(conn, query.worker_id.as_deref())
+    };
+    match debug_run {
+        Ok(Some(run)) => {
+            let Ok(Some(mut task_with_git)) = db::get_task_with_git(conn, &run.task_id) else {
+                return Err(api_error(ErrorCode::Internal, "debug run task not found"));
+            };
+            task_with_git.task.assembled_prompt = run.prompt_override.unwrap_or_default();
+            task_with_git.task.prompt_chunks.clear();
+
+            let mut val = json!(task_with_git);
+            val["debug_run_id"] = json!(run.run_id);
+            return Ok(Json(val));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
+
+    let executors = query
+        .executors
+        .as_deref()
+        .map(Executor::parse_list)
+        .unwrap_or_default();
+    if claim && let (Some(worker_id), Some(_)) = (query.worker_id.as_deref(), &query.executors) {
+        crabs_db::record_executors(conn, worker_id, &executors)
+            .and_then(|_| crabs_db::record_role(conn, worker_id, query.role.as_deref()))
+            .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    }
+
+    let next = match query.worker_id.as_deref() {
+        Some(worker_id) if claim => db::claim_next_task_for(conn, worker_id, &executors),
+        worker_id => db::next_queued_task_for(conn, worker_id, &executors, false),
+    };
+    match next {
+        Ok(Some(task_with_git)) => {
+            if claim {
+                let _ =
+                    db_missions::recalculate_mission_status(conn, &task_with_git.task.mission_id);
+                if chaos::roll(Fault::KillAssignment) {
+                    kill_assignment(conn, &task_with_git.task, query.worker_id.clone())
+                        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+                }
+            }
+            let mut val = json!(task_with_git);
+            if query.delta
+                && let Some(task) = val["task"].as_object_mut()
+            {
+                task.remove("assembled_prompt");
+            }
+            if let Some(role) = query.role.as_deref()
+                && let Ok(Some(root)) = settings_db::get(conn, "prompts_root")
+                && let Ok(Some(stack)) = WorkflowRegistry::open(conn, root)
+                    .and_then(|registry| registry.read_stack(role))
+            {
+                val["stack_hash"] = json!(stack.hash);
+            }
+            Ok(Json(val))
+        }
+        Ok(None) => Err(api_error(ErrorCode::NoQueuedTasks, "no queued tasks")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
 #[derive(Deserialize)]
 pub struct UpdateStatusRequest {
     pub status: String,
+    /// Recorded as the actor in the task's transition history
+    pub worker_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+fn transition_error(e: TransitionError) -> (StatusCode, Json<Value>) {
+    let code = match e {
+        TransitionError::NotFound => ErrorCode::TaskNotFound,
+        TransitionError::Invalid { .. } => ErrorCode::InvalidState,
+        TransitionError::Db(_) => ErrorCode::Internal,
+    };
+    api_error(code, e)
+}
+
+/// Refuse a worker reporting on a running task another worker now holds,
+/// e.g. after the watchdog requeued it and a second crab claimed it
+fn check_holder(
+    conn: &rusqlite::Connection,
+    task_id: &str,
+    worker_id: &str,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let task = db::get_task(conn, task_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or_else(|| api_error(ErrorCode::TaskNotFound, "task not found"))?;
+    if task.status != "running" {
+        return Ok(());
+    }
+    match db::holder(conn, task_id).map_err(|e| api_error(ErrorCode::Internal, e))? {
+        Some(holder) if holder != worker_id => Err(api_error(
+            ErrorCode::InvalidState,
+            format!("task {} is held by worker {}", task_id, holder),
+        )),
+        _ => Ok(()),
+    }
 }
 
 pub async fn update_task_status(
@@ -41,48 +182,47 @@ pub async fn update_task_status(
     Json(body): Json<UpdateStatusRequest>,
 ) -> Result<StatusCode, (StatusCode, Json<Value>)> {
     let conn = state.db.lock().unwrap();
+    if let Some(worker_id) = body.worker_id.as_deref() {
+        check_holder(&conn, &task_id, worker_id)?;
+    }
 
     // 1. Update the task status
-    if let Err(e) = db::update_task_status(&conn, &task_id, &body.status) {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))));
+    let actor = body.worker_id.as_deref().unwrap_or("api");
+    db::transition_task(&conn, &task_id, &body.status, actor, body.reason.as_deref())
+        .map_err(transition_error)?;
+
+    // 2. A failed attempt is requeued while the step has retries left
+    if body.status == "failed"
+        && let Ok(Some(failed_task)) = db::get_task(&conn, &task_id)
+        && let Err(e) = requeue_failed_task(&conn, &failed_task)
+    {
+        tracing::warn!("automatic retry of task {} failed: {}", task_id, e);
     }
 
-    // 2. Fan-in / fan-out: promote next tier when all siblings complete
+    // 3. Quality gate and CI gate, then fan-in / fan-out: promote next tier
+    //    when all siblings complete. A gate that can't be evaluated holds the
+    //    task where it is rather than letting it through.
     if body.status == "completed"
         && let Ok(Some(completed_task)) = db::get_task(&conn, &task_id)
+        && match apply_quality_gate(&conn, &completed_task) {
+            Ok(outcome) => outcome == GateOutcome::Passed,
+            Err(e) => {
+                tracing::error!("quality gate for task {} failed: {}", task_id, e);
+                false
+            }
+        }
     {
-        let mission_id = &completed_task.mission_id;
-        let current_order = completed_task.step_order;
-
-        // Check if all tasks at this order are done
-        let incomplete =
-            db::count_incomplete_at_order(&conn, mission_id, current_order).unwrap_or(1);
-
-        if incomplete == 0 {
-            // Fan-in complete — collect context from ALL completed tasks at this order
-            let combined_context = collect_fan_in_context(&conn, mission_id, current_order);
-
-            // Get ALL blocked tasks at the next order (fan-out)
-            let next_order = current_order + 1;
-            if let Ok(blocked_tasks) = db::get_blocked_tasks_at_order(&conn, mission_id, next_order)
-            {
-                for next_task in &blocked_tasks {
-                    if let Ok(new_prompt) =
-                        reassemble_prompt_with_context(&conn, next_task, &combined_context)
-                    {
-                        let _ = db::update_task_assembled_prompt(
-                            &conn,
-                            &next_task.task_id,
-                            &new_prompt,
-                        );
-                    }
-                    let _ = db::update_task_status(&conn, &next_task.task_id, "queued");
-                }
+        match hold_for_checks(&conn, &completed_task) {
+            Ok(true) => {}
+            Ok(false) => advance_completed_task(&conn, &completed_task),
+            Err(e) => {
+                tracing::warn!("CI gate for task {} failed: {}", task_id, e);
+                advance_completed_task(&conn, &completed_task);
             }
         }
     }
 
-    // 3. Recalculate mission status
+    // 4. Recalculate mission status
     if let Ok(Some(task)) = db::get_task(&conn, &task_id) {
         let _ = db_missions::recalculate_mission_status(&conn, &task.mission_id);
     }
@@ -99,35 +239,53 @@ pub async fn retry_task(
 
     // 1. Fetch task, return 404 if not found
     let task = db::get_task(&conn, &task_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?
-        .ok_or_else(|| {
-            (
-                StatusCode::NOT_FOUND,
-                Json(json!({"error": "task not found"})),
-            )
-        })?;
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or_else(|| api_error(ErrorCode::TaskNotFound, "task not found"))?;
 
     // 2. Validate task is in failed status
     if task.status != "failed" {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(
-                json!({"error": format!("task status is '{}', must be 'failed' to retry", task.status)}),
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "task status is '{}', must be 'failed' to retry",
+                task.status
             ),
         ));
     }
 
-    // 3. If context provided, reassemble prompt with human guidance
-    if let Some(ctx) = body.and_then(|b| b.context.clone()) {
+    // 3. If context or guidance provided, reassemble the prompt with it
+    let (context, guidance) = body
+        .map(|Json(b)| (b.context, b.guidance))
+        .unwrap_or_default();
+    let context = match guidance {
+        None => context,
+        Some(guidance) => {
+            let upstream = context.unwrap_or_else(|| upstream_context(&conn, &task));
+            let guidance = format!("<guidance>\n{}\n</guidance>", guidance);
+            Some(if upstream.is_empty() {
+                guidance
+            } else {
+                format!("{}\n\n{}", upstream, guidance)
+            })
+        }
+    };
+    if let Some(ctx) = context {
         let new_prompt = reassemble_prompt_with_context(&conn, &task, &ctx)
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+            .map_err(|e| api_error(ErrorCode::Internal, e))?;
         db::update_task_assembled_prompt(&conn, &task_id, &new_prompt)
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+            .map_err(|e| api_error(ErrorCode::Internal, e))?;
     }
 
-    // 4. Increment retry (resets status to queued, bumps retry_count)
-    db::increment_task_retry(&conn, &task_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))))?;
+    // 4. Requeue (approval steps go back to their reviewer) and bump retry_count
+    db::transition_task(
+        &conn,
+        &task_id,
+        task.step_config.ready_status(),
+        "retry",
+        None,
+    )
+    .map_err(transition_error)?;
+    db::bump_retry_count(&conn, &task_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
 
     // 5. Recalculate mission status
     let _ = db_missions::recalculate_mission_status(&conn, &task.mission_id);
@@ -135,37 +293,436 @@ pub async fn retry_task(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// A crab hands back a task it claimed but can't serve (missing tool, dirty
+/// disk, wrong repo). The task is requeued and that crab passed over for it
+/// for a while; `rejected_by_all` flags when no capable crab is left to take it.
+pub async fn reject_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    Json(req): Json<RejectTaskRequest>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    if req.reason.trim().is_empty() {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "a rejection needs a reason",
+        ));
+    }
+    let conn = state.db.lock().unwrap();
+    let task = match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) if task.status == "running" => task,
+        Ok(Some(task)) => {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!("task is {}, not running", task.status),
+            ));
+        }
+        Ok(None) => {
+            return Err(api_error(ErrorCode::TaskNotFound, "task not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+    let Some(worker_id) = req.worker_id.as_deref() else {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "worker_id is required to reject a running task",
+        ));
+    };
+    match db::holder(&conn, &task_id) {
+        Ok(Some(holder)) if holder != worker_id => {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!("task is held by {}, not {}", holder, worker_id),
+            ));
+        }
+        Ok(_) => {}
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
+
+    let rejection =
+        db::reject_task(&conn, &task_id, worker_id, &req.reason).map_err(transition_error)?;
+    let _ = db_missions::recalculate_mission_status(&conn, &task.mission_id);
+
+    let crabs = crabs_db::list_executors(&conn, crabs_db::ACTIVE_CRAB_SECS)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let active =
+        db::active_rejections(&conn, &task_id).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    let rejected_by_all = db::rejected_by_all_capable(&active, &task.step_config, &crabs);
+    if rejected_by_all {
+        tracing::warn!(
+            "task {} has been rejected by every capable crab, latest by {}: {}",
+            task_id,
+            worker_id,
+            req.reason
+        );
+    }
+
+    let mut val = json!(rejection);
+    val["rejected_by_all"] = json!(rejected_by_all);
+    Ok(Json(val))
+}
+
+/// Turn an approval step down: the task fails, and with it the mission, and
+/// the task is returned
+pub async fn reject_approval(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    Json(req): Json<RejectApprovalRequest>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    if req.reason.trim().is_empty() {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "a rejection needs a reason",
+        ));
+    }
+    let conn = state.db.lock().unwrap();
+    let task = match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) if task.status == "awaiting_approval" => task,
+        Ok(Some(task)) => {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!("task is {}, not awaiting approval", task.status),
+            ));
+        }
+        Ok(None) => {
+            return Err(api_error(ErrorCode::TaskNotFound, "task not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+
+    let actor = req.reviewer.as_deref().unwrap_or("api");
+    let reason = format!("approval rejected: {}", req.reason);
+    db::transition_task(&conn, &task.task_id, "failed", actor, Some(&reason))
+        .map_err(transition_error)?;
+    let _ = db_missions::recalculate_mission_status(&conn, &task.mission_id);
+
+    match db::get_task(&conn, &task.task_id) {
+        Ok(Some(task)) => Ok(Json(json!(task))),
+        Ok(None) => Err(api_error(ErrorCode::TaskNotFound, "task not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// Let an approval step through: the task completes and its dependents are
+/// promoted as if a crab had finished it
+pub async fn approve_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    body: Option<Json<ApproveTaskRequest>>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let Json(req) = body.unwrap_or_default();
+    let conn = state.db.lock().unwrap();
+    let task = match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) if task.status == "awaiting_approval" => task,
+        Ok(Some(task)) => {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!("task is {}, not awaiting approval", task.status),
+            ));
+        }
+        Ok(None) => {
+            return Err(api_error(ErrorCode::TaskNotFound, "task not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+
+    let actor = req.approver.as_deref().unwrap_or("api");
+    let reason = match req.comment.as_deref() {
+        Some(comment) => format!("approved: {}", comment),
+        None => "approved".to_string(),
+    };
+    db::transition_task(&conn, &task_id, "completed", actor, Some(&reason))
+        .map_err(transition_error)?;
+    if let Err(e) = promote_next_tier(&conn, &task) {
+        tracing::warn!("cascade from task {} failed: {}", task_id, e);
+    }
+    let _ = db_missions::recalculate_mission_status(&conn, &task.mission_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_rejections(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list_rejections(&conn, &task_id) {
+        Ok(rejections) => Ok(Json(json!(rejections))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
 pub async fn create_run(
     State(state): State<AppState>,
     Path(task_id): Path<String>,
-    Json(body): Json<CreateRunRequest>,
+    Json(mut body): Json<CreateRunRequest>,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+
+    // Older crabs don't report a burrow mode; derive it from the step config
+    if body.burrow_mode.is_none()
+        && let Ok(Some(task)) = db::get_task(&conn, &task_id)
+    {
+        let mode = if task.step_config.read_only {
+            "read_only"
+        } else {
+            "worktree"
+        };
+        body.burrow_mode = Some(mode.to_string());
+    }
+
+    // Scrub secrets the agent may have echoed before anything is stored
+    let scanner = Scanner::from_settings(&conn);
+    body.redactions = (scanner.redact_field(&mut body.logs)
+        + scanner.redact_field(&mut body.summary)
+        + scanner.redact_field(&mut body.work_log)) as i64;
+    // Chunks streamed while the agent ran were scrubbed as they arrived
+    if let Some(run_id) = body.debug_run_id.as_ref().or(body.run_id.as_ref()) {
+        body.redactions += run_logs_db::redactions(&conn, run_id)
+            .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    }
+    if body.redactions > 0 {
+        tracing::warn!(
+            "redacted {} secret(s) from run output of task {}",
+            body.redactions,
+            task_id
+        );
+    }
+
+    if let Some(run_id) = &body.debug_run_id {
+        return match db::finish_debug_run(&conn, &task_id, run_id, &body) {
+            Ok(Some(run)) => Ok((StatusCode::OK, Json(json!(run)))),
+            Ok(None) => match db::get_run(&conn, run_id) {
+                Ok(Some(run)) if run.debug && run.task_id == task_id => Err(api_error(
+                    ErrorCode::InvalidState,
+                    "debug run already finished",
+                )),
+                Ok(_) => Err(api_error(ErrorCode::NotFound, "debug run not found")),
+                Err(e) => Err(api_error(ErrorCode::Internal, e)),
+            },
+            Err(e) => Err(api_error(ErrorCode::Internal, e)),
+        };
+    }
+
+    let run =
+        db::insert_run(&conn, &task_id, &body).map_err(|e| api_error(ErrorCode::Internal, e))?;
+
+    // Score the step's diff against the repo's risk rules
+    if body.status == "completed" && body.burrow_mode.as_deref() != Some("read_only") {
+        match risk::record_run(&conn, &task_id, &body) {
+            Ok(reasons) if !reasons.is_empty() => tracing::warn!(
+                "task {} made a high-risk change: {}",
+                task_id,
+                reasons.join("; ")
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("risk scoring of task {} failed: {}", task_id, e),
+        }
+    }
+    match issue_status::record_run(&conn, &task_id, &body) {
+        Ok(Some(pr_url)) => tracing::info!("task {} opened {}", task_id, pr_url),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("pull request lookup for task {} failed: {}", task_id, e),
+    }
+    Ok((StatusCode::CREATED, Json(json!(run))))
+}
+
+/// POST /v1/tasks/{task_id}/debug-run — re-execute a task with an overridden prompt.
+///
+/// The run is queued for crabs (optionally a specific one) without touching the
+/// task's status, so the mission carries on undisturbed.
+pub async fn create_debug_run(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    body: Option<Json<DebugRunRequest>>,
 ) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
     let conn = state.db.lock().unwrap();
-    match db::insert_run(&conn, &task_id, &body) {
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+
+    let task = db::get_task(&conn, &task_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or_else(|| api_error(ErrorCode::TaskNotFound, "task not found"))?;
+
+    let prompt = match (req.prompt, req.context) {
+        (Some(prompt), _) => prompt,
+        (None, Some(ctx)) => reassemble_prompt_with_context(&conn, &task, &ctx)
+            .map_err(|e| api_error(ErrorCode::Internal, e))?,
+        (None, None) => task.assembled_prompt.clone(),
+    };
+
+    match db::insert_debug_run(&conn, &task_id, &prompt, req.worker_id.as_deref()) {
         Ok(run) => Ok((StatusCode::CREATED, Json(json!(run)))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
-/// Collect logs from all completed tasks at a given step_order, wrapped in XML tags.
-fn collect_fan_in_context(
-    conn: &rusqlite::Connection,
-    mission_id: &str,
-    step_order: i64,
-) -> String {
-    let completed =
-        db::get_completed_tasks_at_order(conn, mission_id, step_order).unwrap_or_default();
-
-    let mut parts: Vec<String> = Vec::new();
-    for task in &completed {
-        let logs = db::list_runs_for_task(conn, &task.task_id)
-            .unwrap_or_default()
-            .into_iter()
-            .next()
-            .and_then(|r| r.logs)
-            .unwrap_or_default();
-        parts.push(format!("<step id=\"{}\">\n{}\n</step>", task.step_id, logs));
-    }
-
-    parts.join("\n\n")
+pub async fn get_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) => Ok(Json(json!(task))),
+        Ok(None) => Err(api_error(ErrorCode::TaskNotFound, "task not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// GET /v1/runs/{run_id} — one run, with its full summary even when lists
+/// only carry an excerpt
+pub async fn get_run(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Json<Run>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    let mut run = db::get_run(&conn, &run_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?
+        .ok_or_else(|| api_error(ErrorCode::NotFound, "run not found"))?;
+    db::expand_summary(&conn, &mut run).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    Ok(Json(run))
+}
+
+#[derive(Deserialize)]
+pub struct HeartbeatQuery {
+    pub worker_id: String,
+    /// Warm-pool counters, sent by crabs running with a burrow pool
+    pub pool_ready: Option<i64>,
+    pub pool_hits: Option<i64>,
+    pub pool_misses: Option<i64>,
+}
+
+/// Crabs call this from claim until the agent exits; the response carries
+/// the task's current status so they also learn about cancellation, plus any
+/// messages posted for the agent since the last heartbeat. A crab whose task
+/// another crab has claimed since gets a conflict.
+pub async fn heartbeat(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    Query(query): Query<HeartbeatQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    check_holder(&conn, &task_id, &query.worker_id)?;
+    db::record_heartbeat(&conn, &task_id, &query.worker_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    if let Some(ready) = query.pool_ready {
+        burrows_db::record_pool_stats(
+            &conn,
+            &query.worker_id,
+            ready,
+            query.pool_hits.unwrap_or(0),
+            query.pool_misses.unwrap_or(0),
+        )
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    }
+    let task = match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) => task,
+        Ok(None) => {
+            return Err(api_error(ErrorCode::TaskNotFound, "task not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    };
+    let messages = db::take_pending_messages(&conn, &task_id)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+
+    let mut response = json!(task);
+    response["messages"] = json!(messages);
+    Ok(Json(response))
+}
+
+/// Post a message for the agent on a running task, e.g. "focus on the tests".
+/// The crab receives it with its next heartbeat.
+pub async fn send_message(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    Json(req): Json<SendMessageRequest>,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    if req.body.trim().is_empty() {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "message body is empty",
+        ));
+    }
+    let conn = state.db.lock().unwrap();
+    match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) if task.status == "running" => {}
+        Ok(Some(task)) => {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!("task is {}, not running", task.status),
+            ));
+        }
+        Ok(None) => {
+            return Err(api_error(ErrorCode::TaskNotFound, "task not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
+
+    match db::insert_message(&conn, &task_id, &req.body, req.sender.as_deref()) {
+        Ok(message) => Ok((StatusCode::CREATED, Json(json!(message)))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// Append operator guidance to a task that is still blocked. The note is
+/// merged with the upstream context when the task is promoted, and recorded
+/// with its author.
+pub async fn add_context(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    Json(req): Json<AddContextRequest>,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    if req.body.trim().is_empty() {
+        return Err(api_error(ErrorCode::InvalidRequest, "note body is empty"));
+    }
+    let conn = state.db.lock().unwrap();
+    match db::get_task(&conn, &task_id) {
+        Ok(Some(task)) if task.status == "blocked" => {}
+        Ok(Some(task)) => {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!("task is {}, not blocked", task.status),
+            ));
+        }
+        Ok(None) => {
+            return Err(api_error(ErrorCode::TaskNotFound, "task not found"));
+        }
+        Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+    }
+
+    match db::insert_note(&conn, &task_id, &req.body, req.author.as_deref()) {
+        Ok(note) => Ok((StatusCode::CREATED, Json(json!(note)))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+pub async fn list_context(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list_notes(&conn, &task_id) {
+        Ok(notes) => Ok(Json(json!(notes))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+pub async fn list_messages(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list_messages(&conn, &task_id) {
+        Ok(messages) => Ok(Json(json!(messages))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+pub async fn list_transitions(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list_transitions(&conn, &task_id) {
+        Ok(transitions) => Ok(Json(json!(transitions))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
 }
@@ -0,0 +1,67 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::AppState;
+use crate::db::triggers as db;
+use crate::error::{ErrorCode, api_error};
+use crate::models::triggers::{
+    CreateTriggerRequest, TRIGGER_EVENT_TYPES, Trigger, TriggerEvent, TriggerListQuery,
+};
+
+/// GET /v1/settings/triggers?repo_id=
+pub async fn list_triggers(
+    State(state): State<AppState>,
+    Query(query): Query<TriggerListQuery>,
+) -> Result<Json<Vec<Trigger>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match db::list(&conn, query.repo_id.as_deref()) {
+        Ok(triggers) => Ok(Json(triggers)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// POST /v1/settings/triggers
+pub async fn create_trigger(
+    State(state): State<AppState>,
+    Json(req): Json<CreateTriggerRequest>,
+) -> Result<(StatusCode, Json<Trigger>), (StatusCode, Json<Value>)> {
+    if !TRIGGER_EVENT_TYPES.contains(&req.event_type.as_str()) {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            format!("unsupported event_type: {}", req.event_type),
+        ));
+    }
+
+    let conn = state.db.lock().unwrap();
+    match db::insert(&conn, &req) {
+        Ok(trigger) => Ok((StatusCode::CREATED, Json(trigger))),
+        Err(e) => Err(api_error(ErrorCode::InvalidRequest, e)),
+    }
+}
+
+/// DELETE /v1/settings/triggers/{trigger_id}
+pub async fn delete_trigger(
+    State(state): State<AppState>,
+    Path(trigger_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match db::delete(&conn, &trigger_id) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(ErrorCode::NotFound, "trigger not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// POST /v1/settings/triggers/evaluate — dry-run an event against configured triggers
+pub async fn evaluate_triggers(
+    State(state): State<AppState>,
+    Json(event): Json<TriggerEvent>,
+) -> Result<Json<Vec<Trigger>>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match db::evaluate(&conn, &event) {
+        Ok(triggers) => Ok(Json(triggers)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
@@ -5,31 +5,38 @@ use serde::Deserialize;
 use serde_json::{Value, json};
 
 use crate::AppState;
+use crate::db::missions as missions_db;
 use crate::db::settings as settings_db;
+use crate::db::workflow_packs as packs_db;
 use crate::db::workflows as wf_db;
+use crate::error::{ErrorCode, api_error, api_error_with_details};
 use crate::models::workflows::{
-    CreateFlavorRequest, WorkflowDetail, WorkflowFlavor, WorkflowSummary,
+    CreateFlavorRequest, InstallPackRequest, PromptStack, SaveWorkflowRequest, StepPreview,
+    StoredWorkflow, ValidateWorkflowRequest, WorkflowDetail, WorkflowFile, WorkflowFlavor,
+    WorkflowPack, WorkflowSummary, WorkflowValidation,
 };
-use crate::workflow_registry::WorkflowRegistry;
+use crate::workflow_packs::{self, PackError};
+use crate::workflow_registry::compute_step_orders;
+use crate::workflow_registry::{WorkflowRegistry, parse_workflow, valid_prompt_path};
 
 fn get_registry(
     conn: &rusqlite::Connection,
 ) -> Result<WorkflowRegistry, (StatusCode, Json<Value>)> {
     match settings_db::get(conn, "prompts_root") {
-        Ok(Some(root)) => Ok(WorkflowRegistry::new(root)),
+        Ok(Some(root)) => WorkflowRegistry::open(conn, root).map_err(|e| {
+            tracing::error!("failed to load stored workflows: {}", e);
+            api_error(ErrorCode::Internal, e)
+        }),
         Ok(None) => {
             tracing::warn!("prompts_root not configured in settings");
-            Err((
-                StatusCode::FAILED_DEPENDENCY,
-                Json(json!({"error": "prompts_root not configured in settings"})),
+            Err(api_error(
+                ErrorCode::PromptsRootNotSet,
+                "prompts_root not configured in settings",
             ))
         }
         Err(e) => {
             tracing::error!("failed to get prompts_root from db: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": e.to_string()})),
-            ))
+            Err(api_error(ErrorCode::Internal, e))
         }
     }
 }
@@ -37,7 +44,7 @@ fn get_registry(
 pub async fn list_all_workflows(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<WorkflowSummary>>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     let registry = get_registry(&conn)?;
 
     let workflows = registry.list_workflows();
@@ -57,6 +64,8 @@ pub async fn list_all_workflows(
         };
 
         summaries.push(WorkflowSummary {
+            stored: registry.is_stored(&wf.workflow.name),
+            source: wf.source.map(|p| p.display().to_string()),
             name: wf.workflow.name,
             description: wf.workflow.description,
             step_count: wf.steps.len(),
@@ -71,29 +80,220 @@ pub async fn get_workflow(
     State(state): State<AppState>,
     Path(name): Path<String>,
 ) -> Result<Json<WorkflowDetail>, (StatusCode, Json<Value>)> {
-    let conn = state.db.lock().unwrap();
+    let conn = state.read();
     let registry = get_registry(&conn)?;
 
     let wf = registry.get_workflow(&name).ok_or_else(|| {
         tracing::warn!("workflow not found in registry: {}", name);
-        (
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "workflow not found"})),
-        )
+        api_error(ErrorCode::WorkflowNotFound, "workflow not found")
     })?;
 
-    let flavors = wf_db::list_flavors_for_workflow(&conn, &name).map_err(|e| {
+    workflow_detail(&conn, &registry, wf).map(Json)
+}
+
+fn workflow_detail(
+    conn: &rusqlite::Connection,
+    registry: &WorkflowRegistry,
+    wf: WorkflowFile,
+) -> Result<WorkflowDetail, (StatusCode, Json<Value>)> {
+    let name = wf.workflow.name;
+    let flavors = wf_db::list_flavors_for_workflow(conn, &name).map_err(|e| {
         tracing::error!("failed to list flavors for workflow {}: {}", name, e);
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))
+        api_error(ErrorCode::Internal, e)
     })?;
 
-    Ok(Json(WorkflowDetail {
-        name: wf.workflow.name,
+    let mut orders = vec![0; wf.steps.len()];
+    for (idx, order) in
+        compute_step_orders(&wf.steps).map_err(|e| api_error(ErrorCode::InvalidWorkflow, e))?
+    {
+        orders[idx] = order;
+    }
+    let previews = wf
+        .steps
+        .iter()
+        .zip(orders)
+        .map(|(step, step_order)| StepPreview {
+            step_id: step.id.clone(),
+            step_order,
+            prompt: registry.read_prompt(&step.prompt_file).ok(),
+        })
+        .collect();
+
+    Ok(WorkflowDetail {
+        stored: registry.is_stored(&name),
+        source: wf.source.map(|p| p.display().to_string()),
+        name,
         description: wf.workflow.description,
         version: wf.workflow.version,
         steps: wf.steps,
+        previews,
         flavors,
-    }))
+    })
+}
+
+/// Validate a workflow and its prompts as if already stored, then save it
+fn save_workflow(
+    conn: &rusqlite::Connection,
+    registry: WorkflowRegistry,
+    req: SaveWorkflowRequest,
+) -> Result<WorkflowDetail, (StatusCode, Json<Value>)> {
+    let wf = parse_workflow(&req.content).map_err(|issue| {
+        api_error_with_details(
+            ErrorCode::InvalidWorkflow,
+            &issue.message,
+            json!({ "issues": [issue] }),
+        )
+    })?;
+    let name = wf.workflow.name.clone();
+
+    for step in &wf.steps {
+        if !valid_prompt_path(&step.prompt_file) {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                format!(
+                    "step '{}' has an invalid prompt path: {}",
+                    step.id, step.prompt_file
+                ),
+            ));
+        }
+    }
+    for path in req.prompts.keys() {
+        if !valid_prompt_path(path) {
+            return Err(api_error(
+                ErrorCode::InvalidRequest,
+                format!("invalid prompt path: {}", path),
+            ));
+        }
+        if registry.prompt_file(path).is_some() {
+            return Err(api_error(
+                ErrorCode::InvalidState,
+                format!(
+                    "prompt file already exists in a prompts directory: {}",
+                    path
+                ),
+            ));
+        }
+        match wf_db::prompt_owner(conn, path) {
+            Ok(Some(owner)) if owner != name => {
+                return Err(api_error(
+                    ErrorCode::InvalidState,
+                    format!("prompt '{}' belongs to workflow {}", path, owner),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => return Err(api_error(ErrorCode::Internal, e)),
+        }
+    }
+
+    let candidate = StoredWorkflow {
+        name: name.clone(),
+        content: req.content.clone(),
+        prompts: req.prompts.clone(),
+        created_at: String::new(),
+        updated_at: None,
+    };
+    let mut stored = wf_db::list_stored(conn).map_err(|e| api_error(ErrorCode::Internal, e))?;
+    stored.retain(|s| s.name != name);
+    stored.push(candidate);
+    let registry = registry.with_stored(stored);
+
+    let issues = registry.validate(&wf);
+    if !issues.is_empty() {
+        return Err(api_error_with_details(
+            ErrorCode::InvalidWorkflow,
+            format!("workflow {} is invalid", name),
+            json!({ "issues": issues }),
+        ));
+    }
+
+    wf_db::save_stored(conn, &name, &req.content, &req.prompts)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    workflow_detail(conn, &registry, wf)
+}
+
+/// POST /v1/workflows — create a workflow and its prompt files in SQLite
+pub async fn create_workflow(
+    State(state): State<AppState>,
+    Json(req): Json<SaveWorkflowRequest>,
+) -> Result<(StatusCode, Json<WorkflowDetail>), (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    let registry = get_registry(&conn)?;
+
+    let name = parse_workflow(&req.content)
+        .map(|wf| wf.workflow.name)
+        .unwrap_or_default();
+    if registry.is_stored(&name) || registry.get_workflow(&name).is_some() {
+        return Err(api_error(
+            ErrorCode::InvalidState,
+            format!("workflow already exists: {}", name),
+        ));
+    }
+
+    let detail = save_workflow(&conn, registry, req)?;
+    Ok((StatusCode::CREATED, Json(detail)))
+}
+
+/// PUT /v1/workflows/{name} — replace a stored workflow and its prompt files
+pub async fn update_workflow(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<SaveWorkflowRequest>,
+) -> Result<Json<WorkflowDetail>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    let registry = get_registry(&conn)?;
+    require_stored(&registry, &name)?;
+
+    if let Ok(wf) = parse_workflow(&req.content)
+        && wf.workflow.name != name
+    {
+        return Err(api_error(
+            ErrorCode::InvalidRequest,
+            "workflow name can't be changed",
+        ));
+    }
+
+    save_workflow(&conn, registry, req).map(Json)
+}
+
+/// DELETE /v1/workflows/{name} — remove a stored workflow and its prompt files
+pub async fn delete_workflow(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    let registry = get_registry(&conn)?;
+    require_stored(&registry, &name)?;
+
+    let active = missions_db::count_active_for_workflow(&conn, &name)
+        .map_err(|e| api_error(ErrorCode::Internal, e))?;
+    if active > 0 {
+        return Err(api_error(
+            ErrorCode::InvalidState,
+            format!("{} missions are still running workflow {}", active, name),
+        ));
+    }
+
+    match wf_db::delete_stored(&conn, &name) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// Only workflows created through the API can be edited; files are edited on disk
+fn require_stored(
+    registry: &WorkflowRegistry,
+    name: &str,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    if registry.is_stored(name) {
+        Ok(())
+    } else if registry.get_workflow(name).is_some() {
+        Err(api_error(
+            ErrorCode::InvalidState,
+            "workflow is loaded from prompts_root; edit the file instead",
+        ))
+    } else {
+        Err(api_error(ErrorCode::WorkflowNotFound, "workflow not found"))
+    }
 }
 
 pub async fn create_flavor(
@@ -105,15 +305,12 @@ pub async fn create_flavor(
     // Validate workflow exists
     let registry = get_registry(&conn)?;
     if registry.get_workflow(&workflow_name).is_none() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "workflow not found"})),
-        ));
+        return Err(api_error(ErrorCode::WorkflowNotFound, "workflow not found"));
     }
 
     match wf_db::insert_flavor(&conn, &workflow_name, &body.name, &body.prompt_paths) {
         Ok(flavor) => Ok((StatusCode::CREATED, Json(flavor))),
-        Err(e) => Err((StatusCode::CONFLICT, Json(json!({"error": e})))),
+        Err(e) => Err(api_error(ErrorCode::InvalidState, e)),
     }
 }
 
@@ -124,11 +321,8 @@ pub async fn delete_flavor(
     let conn = state.db.lock().unwrap();
     match wf_db::delete_flavor(&conn, &flavor_id) {
         Ok(true) => Ok(StatusCode::NO_CONTENT),
-        Ok(false) => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "flavor not found"})),
-        )),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Ok(false) => Err(api_error(ErrorCode::NotFound, "flavor not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -140,7 +334,7 @@ pub async fn update_flavor(
     let conn = state.db.lock().unwrap();
     match wf_db::update_flavor(&conn, &flavor_id, &body.name, &body.prompt_paths) {
         Ok(_) => Ok(StatusCode::NO_CONTENT),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
     }
 }
 
@@ -181,3 +375,149 @@ pub async fn list_prompt_files(
     let registry = get_registry(&conn)?;
     Ok(Json(registry.list_prompt_files()))
 }
+
+/// GET /v1/prompts/stacks/{role} — assembled role prompt + skills for crabs
+pub async fn get_prompt_stack(
+    State(state): State<AppState>,
+    Path(role): Path<String>,
+) -> Result<Json<PromptStack>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    let registry = get_registry(&conn)?;
+    match registry.read_stack(&role) {
+        Ok(Some(stack)) => Ok(Json(stack)),
+        Ok(None) => Err(api_error(
+            ErrorCode::NotFound,
+            format!("no prompt stack for role: {}", role),
+        )),
+        Err(e) => Err(api_error(ErrorCode::InvalidRequest, e)),
+    }
+}
+
+fn prompts_root(state: &AppState) -> Result<std::path::PathBuf, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match settings_db::get(&conn, "prompts_root") {
+        Ok(Some(root)) => Ok(root.into()),
+        Ok(None) => Err(api_error(
+            ErrorCode::PromptsRootNotSet,
+            "prompts_root not configured in settings",
+        )),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+fn pack_error(e: PackError) -> (StatusCode, Json<Value>) {
+    let code = match &e {
+        PackError::InvalidName(_) | PackError::InvalidSource(_) => ErrorCode::InvalidRequest,
+        PackError::Git(_) => ErrorCode::Upstream,
+        PackError::Invalid(_) => ErrorCode::InvalidWorkflow,
+        PackError::Io(_) => ErrorCode::Internal,
+    };
+    api_error(code, e)
+}
+
+/// Clone, validate and record a pack; shared by install and update
+async fn install_pack_from(
+    state: &AppState,
+    name: &str,
+    git_url: &str,
+    git_ref: Option<&str>,
+) -> Result<WorkflowPack, (StatusCode, Json<Value>)> {
+    let root = prompts_root(state)?;
+    let installed = workflow_packs::install(&root, name, git_url, git_ref)
+        .await
+        .map_err(pack_error)?;
+
+    let conn = state.db.lock().unwrap();
+    packs_db::upsert(
+        &conn,
+        name,
+        git_url,
+        git_ref,
+        &installed.commit_sha,
+        &installed.workflows,
+    )
+    .map_err(|e| api_error(ErrorCode::Internal, e))
+}
+
+/// POST /v1/workflows/install — install (or reinstall) a workflow pack from git
+pub async fn install_pack(
+    State(state): State<AppState>,
+    Json(body): Json<InstallPackRequest>,
+) -> Result<(StatusCode, Json<WorkflowPack>), (StatusCode, Json<Value>)> {
+    let name =
+        workflow_packs::pack_name(&body.git_url, body.name.as_deref()).map_err(pack_error)?;
+    let pack = install_pack_from(&state, &name, &body.git_url, body.git_ref.as_deref()).await?;
+    Ok((StatusCode::CREATED, Json(pack)))
+}
+
+pub async fn list_packs(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WorkflowPack>>, (StatusCode, Json<Value>)> {
+    let conn = state.read();
+    match packs_db::list(&conn) {
+        Ok(packs) => Ok(Json(packs)),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+fn get_pack(state: &AppState, name: &str) -> Result<WorkflowPack, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    match packs_db::get(&conn, name) {
+        Ok(Some(pack)) => Ok(pack),
+        Ok(None) => Err(api_error(ErrorCode::NotFound, "pack not found")),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// POST /v1/workflows/packs/{name}/update — refetch the pack's source at its recorded ref
+pub async fn update_pack(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<WorkflowPack>, (StatusCode, Json<Value>)> {
+    let pack = get_pack(&state, &name)?;
+    let updated = install_pack_from(&state, &name, &pack.git_url, pack.git_ref.as_deref()).await?;
+    Ok(Json(updated))
+}
+
+/// DELETE /v1/workflows/packs/{name} — remove the pack and all of its workflows
+pub async fn uninstall_pack(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    get_pack(&state, &name)?;
+    let root = prompts_root(&state)?;
+    workflow_packs::uninstall(&root, &name).map_err(pack_error)?;
+
+    let conn = state.db.lock().unwrap();
+    match packs_db::delete(&conn, &name) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => Err(api_error(ErrorCode::Internal, e)),
+    }
+}
+
+/// POST /v1/workflows/validate — check a workflow manifest before saving it,
+/// with prompt files resolved against prompts_root
+pub async fn validate_workflow_manifest(
+    State(state): State<AppState>,
+    Json(req): Json<ValidateWorkflowRequest>,
+) -> Result<Json<WorkflowValidation>, (StatusCode, Json<Value>)> {
+    let conn = state.db.lock().unwrap();
+    let registry = get_registry(&conn)?;
+
+    let validation = match parse_workflow(&req.content) {
+        Ok(wf) => {
+            let issues = registry.validate(&wf);
+            WorkflowValidation {
+                valid: issues.is_empty(),
+                name: Some(wf.workflow.name),
+                issues,
+            }
+        }
+        Err(issue) => WorkflowValidation {
+            valid: false,
+            name: None,
+            issues: vec![issue],
+        },
+    };
+    Ok(Json(validation))
+}
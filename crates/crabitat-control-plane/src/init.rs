@@ -0,0 +1,229 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::db;
+use crate::db::repos as repos_db;
+use crate::db::settings as settings_db;
+use crate::db::workflow_packs as packs_db;
+use crate::github;
+use crate::workflow_packs;
+
+/// `crabitat-control-plane init`: set up a new deployment in one go
+#[derive(Args, Debug, Default)]
+pub struct InitArgs {
+    /// Env file to write with DATABASE_PATH and LISTEN_ADDR; kept if it already exists
+    #[arg(long, default_value = "crabitat.env")]
+    pub config: PathBuf,
+    /// SQLite database to create (defaults to $DATABASE_PATH, then crabitat.db)
+    #[arg(long)]
+    pub db: Option<String>,
+    /// Address the control plane will listen on (defaults to $LISTEN_ADDR, then 127.0.0.1:3001)
+    #[arg(long)]
+    pub listen: Option<String>,
+    /// Directory holding workflows and prompts
+    #[arg(long)]
+    pub prompts_root: Option<PathBuf>,
+    /// First repo to register, as owner/name
+    #[arg(long)]
+    pub repo: Option<String>,
+    /// Local checkout of the repo
+    #[arg(long)]
+    pub local_path: Option<String>,
+    /// Clone URL of the repo, for crabs without a local checkout
+    #[arg(long)]
+    pub repo_url: Option<String>,
+    /// Git URL of a starter workflow pack to install
+    #[arg(long)]
+    pub pack: Option<String>,
+    /// Branch, tag or commit of the pack
+    #[arg(long)]
+    pub pack_ref: Option<String>,
+    /// Never prompt; use flags and defaults only
+    #[arg(long)]
+    pub yes: bool,
+}
+
+/// Ask for a value on the terminal, falling back to `default` on an empty answer
+fn ask(question: &str, default: &str) -> String {
+    print!("{} [{}]: ", question, default);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    let _ = std::io::stdin().lock().read_line(&mut answer);
+    match answer.trim() {
+        "" => default.to_string(),
+        a => a.to_string(),
+    }
+}
+
+/// Ask for an optional value; an empty answer leaves it unset
+fn ask_optional(question: &str) -> Option<String> {
+    Some(ask(question, "")).filter(|a| !a.is_empty())
+}
+
+impl InitArgs {
+    /// Fill in what the flags left out, prompting when attached to a terminal
+    pub fn resolve(mut self) -> Self {
+        let interactive = !self.yes && std::io::stdin().is_terminal();
+
+        let db = self
+            .db
+            .take()
+            .or_else(|| std::env::var("DATABASE_PATH").ok())
+            .unwrap_or_else(|| "crabitat.db".into());
+        let listen = self
+            .listen
+            .take()
+            .or_else(|| std::env::var("LISTEN_ADDR").ok())
+            .unwrap_or_else(|| "127.0.0.1:3001".into());
+        let prompts_root = self
+            .prompts_root
+            .take()
+            .unwrap_or_else(|| PathBuf::from("prompts"));
+
+        if !interactive {
+            self.db = Some(db);
+            self.listen = Some(listen);
+            self.prompts_root = Some(prompts_root);
+            return self;
+        }
+
+        self.db = Some(ask("Database path", &db));
+        self.listen = Some(ask("Listen address", &listen));
+        self.prompts_root = Some(PathBuf::from(ask(
+            "Prompts root",
+            &prompts_root.to_string_lossy(),
+        )));
+        if self.repo.is_none() {
+            self.repo = ask_optional("First repo (owner/name, empty to skip)");
+        }
+        if self.repo.is_some() && self.local_path.is_none() && self.repo_url.is_none() {
+            self.local_path = ask_optional("Local checkout path (empty to use a clone URL)");
+            if self.local_path.is_none() {
+                self.repo_url = ask_optional("Clone URL");
+            }
+        }
+        if self.pack.is_none() {
+            self.pack = ask_optional("Starter workflow pack git URL (empty to skip)");
+        }
+        self
+    }
+}
+
+/// Create the env file, database, settings, first repo and starter pack, reporting
+/// each step to `out`. Safe to re-run: existing pieces are kept.
+pub async fn run(args: &InitArgs, out: &mut impl Write) -> Result<(), String> {
+    let io = |e: std::io::Error| e.to_string();
+    let db_path = args.db.as_deref().unwrap_or("crabitat.db");
+    let listen = args.listen.as_deref().unwrap_or("127.0.0.1:3001");
+    let prompts_root = args
+        .prompts_root
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("prompts"));
+
+    // 1. Env file
+    if args.config.exists() {
+        writeln!(out, "config: keeping existing {}", args.config.display()).map_err(io)?;
+    } else {
+        std::fs::write(
+            &args.config,
+            format!("DATABASE_PATH={}\nLISTEN_ADDR={}\n", db_path, listen),
+        )
+        .map_err(io)?;
+        writeln!(out, "config: wrote {}", args.config.display()).map_err(io)?;
+    }
+
+    // 2. Database and prompts root
+    let conn = db::init(db_path);
+    writeln!(out, "database: {}", db_path).map_err(io)?;
+    std::fs::create_dir_all(prompts_root.join("workflows")).map_err(io)?;
+    let prompts_root = absolute(&prompts_root)?;
+    settings_db::set(&conn, "prompts_root", &prompts_root.to_string_lossy())
+        .map_err(|e| e.to_string())?;
+    writeln!(out, "prompts_root: {}", prompts_root.display()).map_err(io)?;
+
+    // 3. GitHub credentials (issue sync and PRs go through the gh CLI)
+    let gh = github::check_status().await;
+    match (gh.gh_installed, gh.gh_auth_status) {
+        (false, _) => writeln!(out, "github: gh CLI not found; issue sync will not work"),
+        (true, false) => writeln!(out, "github: gh is not logged in; run `gh auth login`"),
+        (true, true) => writeln!(
+            out,
+            "github: logged in as {}",
+            gh.gh_user.as_deref().unwrap_or("unknown")
+        ),
+    }
+    .map_err(io)?;
+
+    // 4. First repo
+    if let Some(repo) = &args.repo {
+        let (owner, name) = repo
+            .split_once('/')
+            .filter(|(o, n)| !o.is_empty() && !n.is_empty())
+            .ok_or_else(|| format!("repo must be owner/name, got {}", repo))?;
+        match repos_db::get_by_owner_name(&conn, owner, name)? {
+            Some(existing) => writeln!(
+                out,
+                "repo: {} already registered ({})",
+                repo, existing.repo_id
+            )
+            .map_err(io)?,
+            None => {
+                let created = repos_db::insert(
+                    &conn,
+                    owner,
+                    name,
+                    args.local_path.as_deref(),
+                    args.repo_url.as_deref(),
+                )?;
+                writeln!(out, "repo: registered {} ({})", repo, created.repo_id).map_err(io)?;
+            }
+        }
+    }
+
+    // 5. Starter workflow pack
+    if let Some(git_url) = &args.pack {
+        let name = workflow_packs::pack_name(git_url, None).map_err(|e| e.to_string())?;
+        let installed =
+            workflow_packs::install(&prompts_root, &name, git_url, args.pack_ref.as_deref())
+                .await
+                .map_err(|e| e.to_string())?;
+        packs_db::upsert(
+            &conn,
+            &name,
+            git_url,
+            args.pack_ref.as_deref(),
+            &installed.commit_sha,
+            &installed.workflows,
+        )?;
+        writeln!(
+            out,
+            "pack: installed {} at {} ({})",
+            name,
+            &installed.commit_sha[..installed.commit_sha.len().min(12)],
+            installed.workflows.join(", ")
+        )
+        .map_err(io)?;
+    }
+
+    // 6. How to start things
+    writeln!(out).map_err(io)?;
+    writeln!(
+        out,
+        "Start the control plane:\n  set -a; . {}; set +a; crabitat-control-plane",
+        args.config.display()
+    )
+    .map_err(io)?;
+    writeln!(
+        out,
+        "Start a crab:\n  crabitat-crab --api-url http://{} --agent claude",
+        listen
+    )
+    .map_err(io)?;
+    Ok(())
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, String> {
+    std::fs::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,122 @@
+//! Mission status on GitHub issues.
+//!
+//! With the `github_status_comments` setting `true`, a background job keeps
+//! one comment on each mission's issue saying how it stands. The comment is
+//! posted when the mission starts and edited when a step opens a pull
+//! request and when the mission completes, fails or is cancelled. A pull
+//! request counts as opened once a completed run's summary or logs link one
+//! in the mission's repo.
+
+use rusqlite::Connection;
+
+use crate::db::missions as missions_db;
+use crate::db::tasks as tasks_db;
+use crate::models::missions::{IssueCommentChange, Mission};
+use crate::models::tasks::CreateRunRequest;
+use crate::notify;
+
+/// Settings key that, when `true`, turns issue status comments on
+pub const STATUS_COMMENTS_SETTING: &str = "github_status_comments";
+
+/// The first pull request URL of `owner/name` in `text`. Matches on any host,
+/// so GitHub Enterprise links count too.
+pub fn find_pr_url(owner: &str, name: &str, text: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets, so they index `text` as well
+    let lower = text.to_ascii_lowercase();
+    let marker = format!("/{}/{}/pull/", owner, name).to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find(&marker) {
+        let at = from + pos;
+        from = at + marker.len();
+        let start = lower[..at]
+            .rfind(|c: char| c.is_whitespace() || "(<[\"'".contains(c))
+            .map_or(0, |i| {
+                i + lower[i..].chars().next().map_or(1, char::len_utf8)
+            });
+        let host = &lower[start..at];
+        let Some(host) = host.strip_prefix("https://") else {
+            continue;
+        };
+        let digits = lower[from..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(lower.len(), |i| from + i);
+        if host.is_empty() || host.contains('/') || digits == from {
+            continue;
+        }
+        return Some(text[start..digits].to_string());
+    }
+    None
+}
+
+/// Look for a pull request in what a completed run of `task_id` reported and
+/// record it on the mission. Returns the URL when one was newly recorded.
+pub fn record_run(
+    conn: &Connection,
+    task_id: &str,
+    req: &CreateRunRequest,
+) -> Result<Option<String>, String> {
+    if req.status != "completed" {
+        return Ok(None);
+    }
+    let task = tasks_db::get_task(conn, task_id)?.ok_or("task not found")?;
+    let mission = missions_db::get_mission(conn, &task.mission_id)?
+        .ok_or_else(|| format!("mission not found: {}", task.mission_id))?;
+    if mission.pr_url.is_some() {
+        return Ok(None);
+    }
+    let found = [req.summary.as_deref(), req.logs.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(|text| find_pr_url(&mission.repo_owner, &mission.repo_name, text));
+    let Some(pr_url) = found else {
+        return Ok(None);
+    };
+    Ok(missions_db::set_pr_url(conn, &mission.mission_id, &pr_url)?.then_some(pr_url))
+}
+
+/// Comments to post or edit, each with its body
+pub fn pending(conn: &Connection) -> Result<Vec<(IssueCommentChange, String)>, String> {
+    let base_url = notify::base_url(conn);
+    let mut out = Vec::new();
+    for change in missions_db::list_issue_comment_changes(conn)? {
+        let Some(mission) = missions_db::get_mission(conn, &change.mission_id)? else {
+            continue;
+        };
+        let body = comment_body(conn, &mission, &change.state, base_url.as_deref())?;
+        out.push((change, body));
+    }
+    Ok(out)
+}
+
+/// The comment text for `mission` in `state`
+fn comment_body(
+    conn: &Connection,
+    mission: &Mission,
+    state: &str,
+    base_url: Option<&str>,
+) -> Result<String, String> {
+    let headline = match state {
+        "running" => ":crab: Mission running",
+        "pr_opened" => ":crab: Pull request opened",
+        "completed" => ":white_check_mark: Mission completed",
+        "failed" => ":x: Mission failed",
+        _ => ":no_entry_sign: Mission cancelled",
+    };
+    let mut body = format!(
+        "### {}\n\nWorkflow `{}` on branch `{}`.\n\n| Step | Status |\n| --- | --- |\n",
+        headline, mission.workflow_name, mission.branch
+    );
+    for task in tasks_db::list_tasks_for_mission(conn, &mission.mission_id)? {
+        body.push_str(&format!("| `{}` | {} |\n", task.step_id, task.status));
+    }
+    if let Some(pr_url) = &mission.pr_url {
+        body.push_str(&format!("\nPull request: {}\n", pr_url));
+    }
+    if let Some(base) = base_url {
+        body.push_str(&format!(
+            "\n[Open in Crabitat]({}/missions/{})\n",
+            base, mission.mission_id
+        ));
+    }
+    Ok(body)
+}
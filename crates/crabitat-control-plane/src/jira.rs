@@ -0,0 +1,127 @@
+//! Jira issue queues.
+//!
+//! Jira doesn't call the GitHub webhook, so repos whose forge is `jira` are
+//! polled instead. Each pass fetches the project's unresolved issues matching
+//! the repo's JQL into the issue cache, and starts a mission for every issue
+//! that one of the repo's `issue` triggers matches, as the webhook would for
+//! a GitHub issue. Tickets of completed missions are then moved along the
+//! repo's `done_transition`.
+
+use crate::AppState;
+use crate::db::issues as issues_db;
+use crate::db::missions as missions_db;
+use crate::db::repos as repos_db;
+use crate::db::triggers as triggers_db;
+use crate::forge::{Forge, ForgeProvider, Jira};
+use crate::mission_service::start_for_trigger;
+use crate::models::repos::ForgeConfig;
+use crate::models::triggers::TriggerEvent;
+
+/// What one pass did
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Issues fetched into the cache
+    pub issues: usize,
+    /// Missions started for them
+    pub missions: Vec<String>,
+    /// Tickets moved along their repo's `done_transition`
+    pub completed: usize,
+}
+
+fn jira_for(config: &ForgeConfig) -> Result<Jira, String> {
+    match Forge::from_config(config)? {
+        Forge::Jira(jira) => Ok(jira),
+        _ => Err(format!("{} is not a jira forge", config.kind)),
+    }
+}
+
+/// Queue every Jira repo's issues and complete the tickets of finished
+/// missions. A repo that fails is logged and skipped until the next pass.
+pub async fn sync(state: &AppState) -> Result<SyncReport, String> {
+    let mut report = SyncReport::default();
+    let repos = {
+        let conn = state.db.lock().unwrap();
+        repos_db::list_by_forge(&conn, "jira")?
+    };
+
+    for (repo_id, config) in &repos {
+        let issues = match jira_for(config) {
+            Ok(jira) => jira.fetch_issues("", "").await,
+            Err(e) => Err(e),
+        };
+        let issues = match issues {
+            Ok(issues) => issues,
+            Err(e) => {
+                tracing::warn!("failed to fetch Jira issues for repo {}: {}", repo_id, e);
+                continue;
+            }
+        };
+
+        let mut conn = state.db.lock().unwrap();
+        issues_db::upsert_issues(&conn, repo_id, &issues)?;
+        report.issues += issues.len();
+        for issue in issues {
+            let number = issue.number;
+            let event = TriggerEvent {
+                repo_id: repo_id.clone(),
+                event_type: "issue".to_string(),
+                title: issue.title,
+                body: issue.body,
+                labels: issue.labels,
+            };
+            let Some(best) = triggers_db::evaluate(&conn, &event)?.into_iter().next() else {
+                continue;
+            };
+            match start_for_trigger(&mut conn, repo_id, number, &best) {
+                Ok(Some(mission)) => report.missions.push(mission.mission_id),
+                Ok(None) => {}
+                Err(e) => tracing::error!(
+                    "trigger {} failed to create mission: {}",
+                    best.trigger_id,
+                    e
+                ),
+            }
+        }
+    }
+
+    let changes = {
+        let conn = state.db.lock().unwrap();
+        missions_db::list_jira_done_changes(&conn)?
+    };
+    for change in changes {
+        let Some((_, config)) = repos.iter().find(|(id, _)| *id == change.repo_id) else {
+            continue;
+        };
+        // Without a transition there is nothing to do, now or later
+        if config
+            .jira
+            .as_ref()
+            .is_some_and(|j| j.done_transition.is_some())
+        {
+            let moved = match jira_for(config) {
+                Ok(jira) => jira.complete_issue(change.issue_number).await,
+                Err(e) => Err(e),
+            };
+            match moved {
+                Ok(true) => report.completed += 1,
+                Ok(false) => tracing::info!(
+                    "Jira ticket {} of repo {} has no such transition; left as it is",
+                    change.issue_number,
+                    change.repo_id
+                ),
+                Err(e) => {
+                    tracing::warn!(
+                        "failed to complete Jira ticket {} of repo {}: {}",
+                        change.issue_number,
+                        change.repo_id,
+                        e
+                    );
+                    continue;
+                }
+            }
+        }
+        let conn = state.db.lock().unwrap();
+        missions_db::set_issue_done(&conn, &change.mission_id)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,607 @@
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::AppState;
+use crate::consistency;
+use crate::db::analytics as analytics_db;
+use crate::db::crabs as crabs_db;
+use crate::db::events as events_db;
+use crate::db::job_leases as leases_db;
+use crate::db::missions as missions_db;
+use crate::db::settings as settings_db;
+use crate::db::tasks as tasks_db;
+use crate::github;
+use crate::issue_status;
+use crate::jira;
+use crate::mission_service::{
+    apply_checks, fail_stuck_tasks, fail_timed_out_tasks, repair_taskless_missions,
+    requeue_stale_tasks,
+};
+use crate::notify;
+
+/// How often the crab utilization rollup refreshes
+const CRAB_STATS_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How often events past their retention are deleted
+const EVENT_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Days of events kept for clients catching up; older cursors get a reset
+const EVENT_RETENTION_DAYS: i64 = 7;
+
+/// How often new events are checked for Slack notifications
+const SLACK_NOTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Most events turned into Slack notifications per pass
+const SLACK_NOTIFY_BATCH: i64 = 200;
+
+/// How often the consistency checker runs
+const CONSISTENCY_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+/// How often the watchdog looks for running tasks whose crab went silent
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Settings key for the heartbeat gap after which a running task is requeued
+pub const STALE_TASK_SECS_SETTING: &str = "stale_task_secs";
+const DEFAULT_STALE_TASK_SECS: i64 = 300;
+
+/// Slack past a step's timeout before the watchdog steps in; covers the crab's
+/// checkout setup and gives it time to kill the agent and report on its own
+const TIMEOUT_GRACE_SECS: i64 = 300;
+
+/// Settings key that, when `true`, has the watchdog take back tasks stuck
+/// past `stuck_task_secs` instead of only reporting them
+pub const CANCEL_STUCK_TASKS_SETTING: &str = "cancel_stuck_tasks";
+
+/// How often mission state is mirrored onto GitHub issues
+const ISSUE_CLAIM_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Settings keys for how a running mission shows on its issue; neither set
+/// leaves GitHub untouched
+pub const CLAIM_ASSIGNEE_SETTING: &str = "github_claim_assignee";
+pub const CLAIM_LABEL_SETTING: &str = "github_claim_label";
+
+/// How often pull request checks are read for tasks waiting on CI
+const CHECKS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often running missions' pull requests are checked for change requests
+const PR_REVIEW_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often Jira repos' issues are queued and finished tickets completed
+const JIRA_SYNC_INTERVAL: Duration = Duration::from_secs(300);
+
+/// `base` stretched by up to a fifth, so control planes started together
+/// don't call GitHub in lockstep
+fn jittered(base: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    base + base.mul_f64(f64::from(nanos % 1000) / 5000.0)
+}
+
+/// Identifies this process in `job_leases`
+static INSTANCE_ID: LazyLock<String> = LazyLock::new(|| uuid::Uuid::new_v4().to_string());
+
+/// The id this control plane holds job leases under
+pub fn instance_id() -> &'static str {
+    &INSTANCE_ID
+}
+
+/// Whether this control plane runs `job` on this tick. Several control planes
+/// may share a database; the lease outlives two missed ticks before another
+/// one takes the job over.
+fn hold_lease(state: &AppState, job: &str, interval: Duration) -> bool {
+    let ttl = interval.as_secs() * 2 + 30;
+    let conn = state.db.lock().unwrap();
+    match leases_db::try_acquire(&conn, job, instance_id(), ttl) {
+        Ok(held) => held,
+        Err(e) => {
+            tracing::error!("failed to take the {} lease: {}", job, e);
+            false
+        }
+    }
+}
+
+/// Spawn the background jobs that run for the life of the server
+pub fn spawn_all(state: AppState) {
+    tracing::info!("background jobs run as instance {}", instance_id());
+    tokio::spawn(crab_stats_job(state.clone()));
+    tokio::spawn(issue_claim_job(state.clone()));
+    tokio::spawn(issue_comment_job(state.clone()));
+    tokio::spawn(issue_done_job(state.clone()));
+    tokio::spawn(checks_job(state.clone()));
+    tokio::spawn(pr_review_job(state.clone()));
+    tokio::spawn(jira_sync_job(state.clone()));
+    tokio::spawn(consistency_job(state.clone()));
+    tokio::spawn(event_prune_job(state.clone()));
+    tokio::spawn(slack_notify_job(state.clone()));
+    tokio::spawn(watchdog_job(state));
+}
+
+/// Assign the bot account and/or add the in-progress label on the issue of
+/// every mission that started running, and take them off again once it ends
+async fn issue_claim_job(state: AppState) {
+    loop {
+        tokio::time::sleep(jittered(ISSUE_CLAIM_INTERVAL)).await;
+        if !hold_lease(&state, "issue_claim", ISSUE_CLAIM_INTERVAL) {
+            continue;
+        }
+        // Nothing would get through; the flags stay put until GitHub is back
+        if github::circuit_open() {
+            tracing::debug!("GitHub circuit open; skipping issue claim sync");
+            continue;
+        }
+        let (assignee, label, changes) = {
+            let conn = state.db.lock().unwrap();
+            let assignee = settings_db::get(&conn, CLAIM_ASSIGNEE_SETTING)
+                .ok()
+                .flatten();
+            let label = settings_db::get(&conn, CLAIM_LABEL_SETTING).ok().flatten();
+            if assignee.is_none() && label.is_none() {
+                continue;
+            }
+            match missions_db::list_issue_claim_changes(&conn) {
+                Ok(changes) => (assignee, label, changes),
+                Err(e) => {
+                    tracing::error!("issue claim sync failed: {}", e);
+                    continue;
+                }
+            }
+        };
+
+        for change in changes {
+            // Failures are retried on the next tick since the flag stays as it was
+            if let Err(e) = github::set_issue_claim(
+                &change.repo_owner,
+                &change.repo_name,
+                change.issue_number,
+                assignee.as_deref(),
+                label.as_deref(),
+                change.claim,
+            )
+            .await
+            {
+                tracing::warn!(
+                    "failed to update claim on {}/{}#{}: {}",
+                    change.repo_owner,
+                    change.repo_name,
+                    change.issue_number,
+                    e
+                );
+                if github::circuit_open() {
+                    break;
+                }
+                continue;
+            }
+            let conn = state.db.lock().unwrap();
+            if let Err(e) = missions_db::set_github_claimed(&conn, &change.mission_id, change.claim)
+            {
+                tracing::error!("issue claim sync failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Requeue running tasks whose crab stopped sending heartbeats, that outlived
+/// their step's timeout, or (when enabled) that are stuck without output, and
+/// expand missions left without tasks. The first pass runs at startup.
+async fn watchdog_job(state: AppState) {
+    let mut interval = tokio::time::interval(WATCHDOG_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !hold_lease(&state, "watchdog", WATCHDOG_INTERVAL) {
+            continue;
+        }
+        let conn = state.db.lock().unwrap();
+        let stale_secs = settings_db::get(&conn, STALE_TASK_SECS_SETTING)
+            .ok()
+            .flatten()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_STALE_TASK_SECS);
+        match requeue_stale_tasks(&conn, stale_secs) {
+            Ok(recovered) if !recovered.is_empty() => {
+                tracing::warn!("watchdog recovered stale tasks: {:?}", recovered)
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("watchdog failed: {}", e),
+        }
+        match fail_timed_out_tasks(&conn, TIMEOUT_GRACE_SECS) {
+            Ok(timed_out) if !timed_out.is_empty() => {
+                tracing::warn!("watchdog took back timed-out tasks: {:?}", timed_out)
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("watchdog failed: {}", e),
+        }
+        match repair_taskless_missions(&conn) {
+            Ok(repaired) if !repaired.is_empty() => {
+                tracing::warn!(
+                    "watchdog expanded missions that had no tasks: {:?}",
+                    repaired
+                )
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("watchdog failed: {}", e),
+        }
+        let cancel_stuck = settings_db::get(&conn, CANCEL_STUCK_TASKS_SETTING)
+            .ok()
+            .flatten()
+            .is_some_and(|v| v == "true");
+        if cancel_stuck {
+            match fail_stuck_tasks(&conn, crabs_db::stuck_task_secs(&conn)) {
+                Ok(stuck) if !stuck.is_empty() => {
+                    tracing::warn!("watchdog took back stuck tasks: {:?}", stuck)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("watchdog failed: {}", e),
+            }
+        }
+    }
+}
+
+/// Delete events older than `EVENT_RETENTION_DAYS`
+async fn event_prune_job(state: AppState) {
+    let mut interval = tokio::time::interval(EVENT_PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !hold_lease(&state, "event_prune", EVENT_PRUNE_INTERVAL) {
+            continue;
+        }
+        let conn = state.db.lock().unwrap();
+        match events_db::prune(&conn, EVENT_RETENTION_DAYS) {
+            Ok(pruned) if pruned > 0 => tracing::debug!("pruned {} old events", pruned),
+            Ok(_) => {}
+            Err(e) => tracing::error!("event pruning failed: {}", e),
+        }
+    }
+}
+
+/// Post or edit the status comment on the issue of every mission whose state
+/// changed since it was last shown; see [`issue_status`]
+async fn issue_comment_job(state: AppState) {
+    loop {
+        tokio::time::sleep(jittered(ISSUE_CLAIM_INTERVAL)).await;
+        if !hold_lease(&state, "issue_comment", ISSUE_CLAIM_INTERVAL) {
+            continue;
+        }
+        if github::circuit_open() {
+            tracing::debug!("GitHub circuit open; skipping issue status comments");
+            continue;
+        }
+        let pending = {
+            let conn = state.db.lock().unwrap();
+            let enabled = settings_db::get(&conn, issue_status::STATUS_COMMENTS_SETTING)
+                .ok()
+                .flatten()
+                .is_some_and(|v| v == "true");
+            if !enabled {
+                continue;
+            }
+            match issue_status::pending(&conn) {
+                Ok(pending) => pending,
+                Err(e) => {
+                    tracing::error!("issue status comments failed: {}", e);
+                    continue;
+                }
+            }
+        };
+
+        for (change, body) in pending {
+            // Failures are retried on the next tick since the stored state stays as it was
+            let comment_id = match github::upsert_issue_comment(
+                &change.repo_owner,
+                &change.repo_name,
+                change.issue_number,
+                change.comment_id,
+                &body,
+            )
+            .await
+            {
+                Ok(id) => id,
+                Err(e) => {
+                    tracing::warn!(
+                        "failed to comment on {}/{}#{}: {}",
+                        change.repo_owner,
+                        change.repo_name,
+                        change.issue_number,
+                        e
+                    );
+                    if github::circuit_open() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let conn = state.db.lock().unwrap();
+            if let Err(e) =
+                missions_db::set_issue_comment(&conn, &change.mission_id, comment_id, &change.state)
+            {
+                tracing::error!("issue status comments failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Close and/or label the issue of every newly completed mission, as its
+/// repo's issue completion setting says
+async fn issue_done_job(state: AppState) {
+    loop {
+        tokio::time::sleep(jittered(ISSUE_CLAIM_INTERVAL)).await;
+        if !hold_lease(&state, "issue_done", ISSUE_CLAIM_INTERVAL) {
+            continue;
+        }
+        if github::circuit_open() {
+            tracing::debug!("GitHub circuit open; skipping completed issues");
+            continue;
+        }
+        let changes = {
+            let conn = state.db.lock().unwrap();
+            match missions_db::list_issue_done_changes(&conn) {
+                Ok(changes) => changes,
+                Err(e) => {
+                    tracing::error!("completed issue sync failed: {}", e);
+                    continue;
+                }
+            }
+        };
+
+        for change in changes {
+            // Without a setting there is nothing to do, now or later
+            if let Some(completion) = &change.completion
+                && let Err(e) = github::complete_issue(
+                    &change.repo_owner,
+                    &change.repo_name,
+                    change.issue_number,
+                    completion.close,
+                    completion.label.as_deref(),
+                )
+                .await
+            {
+                tracing::warn!(
+                    "failed to complete {}/{}#{}: {}",
+                    change.repo_owner,
+                    change.repo_name,
+                    change.issue_number,
+                    e
+                );
+                if github::circuit_open() {
+                    break;
+                }
+                continue;
+            }
+            let conn = state.db.lock().unwrap();
+            if let Err(e) = missions_db::set_issue_done(&conn, &change.mission_id) {
+                tracing::error!("completed issue sync failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Read the required checks on the pull request of every task waiting on CI
+/// and settle those whose checks have finished. A check run that can't be
+/// read is tried again on the next tick.
+async fn checks_job(state: AppState) {
+    loop {
+        tokio::time::sleep(jittered(CHECKS_INTERVAL)).await;
+        if !hold_lease(&state, "ci_checks", CHECKS_INTERVAL) {
+            continue;
+        }
+        if github::circuit_open() {
+            tracing::debug!("GitHub circuit open; skipping CI checks");
+            continue;
+        }
+        let waiting = {
+            let conn = state.db.lock().unwrap();
+            match tasks_db::list_awaiting_checks(&conn) {
+                Ok(waiting) => waiting,
+                Err(e) => {
+                    tracing::error!("CI check sync failed: {}", e);
+                    continue;
+                }
+            }
+        };
+
+        for wait in waiting {
+            let checks = match (wait.forge.as_str(), &wait.pr_url) {
+                ("github", Some(pr_url)) => {
+                    match github::pull_request_checks(&wait.repo_owner, pr_url).await {
+                        Ok(checks) => Ok(checks),
+                        Err(e) => {
+                            tracing::warn!("failed to read checks on {}: {}", pr_url, e);
+                            if github::circuit_open() {
+                                break;
+                            }
+                            continue;
+                        }
+                    }
+                }
+                ("github", None) => Err("no pull request to check"),
+                _ => Err("CI checks are only read from GitHub"),
+            };
+            let conn = state.db.lock().unwrap();
+            let task = match tasks_db::get_task(&conn, &wait.task_id) {
+                // Cancelled or settled by hand while the checks were read
+                Ok(Some(task)) if task.status == "awaiting_checks" => task,
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::error!("CI check sync failed: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = apply_checks(&conn, &task, checks.as_deref().map_err(|e| *e)) {
+                tracing::error!("CI gate for task {} failed: {}", task.task_id, e);
+            }
+        }
+    }
+}
+
+/// Store the feedback of reviews requesting changes on running missions'
+/// pull requests, for the next fix step to work from
+async fn pr_review_job(state: AppState) {
+    loop {
+        tokio::time::sleep(jittered(PR_REVIEW_INTERVAL)).await;
+        if !hold_lease(&state, "pr_reviews", PR_REVIEW_INTERVAL) {
+            continue;
+        }
+        if github::circuit_open() {
+            tracing::debug!("GitHub circuit open; skipping pull request reviews");
+            continue;
+        }
+        let watches = {
+            let conn = state.db.lock().unwrap();
+            match missions_db::list_pr_review_watches(&conn) {
+                Ok(watches) => watches,
+                Err(e) => {
+                    tracing::error!("pull request review sync failed: {}", e);
+                    continue;
+                }
+            }
+        };
+
+        for watch in watches {
+            let comments = match github::pull_request_review_comments(
+                &watch.repo_owner,
+                &watch.repo_name,
+                &watch.pr_url,
+            )
+            .await
+            {
+                Ok(comments) => comments,
+                Err(e) => {
+                    tracing::warn!("failed to read reviews on {}: {}", watch.pr_url, e);
+                    if github::circuit_open() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let conn = state.db.lock().unwrap();
+            match missions_db::insert_pr_review_comments(&conn, &watch.mission_id, &comments) {
+                Ok(0) => {}
+                Ok(added) => tracing::info!(
+                    "{} new review comments on {} for mission {}",
+                    added,
+                    watch.pr_url,
+                    watch.mission_id
+                ),
+                Err(e) => tracing::error!("pull request review sync failed: {}", e),
+            }
+        }
+    }
+}
+
+/// Queue Jira repos' issues as missions and move the tickets of completed
+/// missions along
+async fn jira_sync_job(state: AppState) {
+    loop {
+        tokio::time::sleep(jittered(JIRA_SYNC_INTERVAL)).await;
+        if !hold_lease(&state, "jira_sync", JIRA_SYNC_INTERVAL) {
+            continue;
+        }
+        match jira::sync(&state).await {
+            Ok(report) if report.missions.is_empty() && report.completed == 0 => {}
+            Ok(report) => tracing::info!(
+                "Jira sync started {} missions and completed {} tickets",
+                report.missions.len(),
+                report.completed
+            ),
+            Err(e) => tracing::error!("Jira sync failed: {}", e),
+        }
+    }
+}
+
+/// Post Slack notifications for events logged since the last pass. The
+/// cursor starts at the newest event whenever this instance takes the job
+/// on, so a restart or failover skips what happened meanwhile rather than
+/// flooding channels with stale news.
+async fn slack_notify_job(state: AppState) {
+    let mut interval = tokio::time::interval(SLACK_NOTIFY_INTERVAL);
+    let client = reqwest::Client::new();
+    let mut cursor: Option<i64> = None;
+    loop {
+        interval.tick().await;
+        if !hold_lease(&state, "slack_notify", SLACK_NOTIFY_INTERVAL) {
+            cursor = None;
+            continue;
+        }
+        let result = {
+            let conn = state.read();
+            match cursor {
+                None => events_db::latest_seq(&conn).map(|seq| (seq, Vec::new())),
+                Some(after) => notify::pending(&conn, after, SLACK_NOTIFY_BATCH),
+            }
+        };
+        let (last_seq, notifications) = match result {
+            Ok(pending) => pending,
+            Err(e) => {
+                tracing::error!("slack notifications failed: {}", e);
+                continue;
+            }
+        };
+        cursor = Some(last_seq);
+        for notification in &notifications {
+            if let Err(e) = notify::post(&client, notification).await {
+                tracing::warn!("slack notification failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Check the database for records that contradict each other, fix what is
+/// safe to fix and tell the repos' Slack channels about the rest
+async fn consistency_job(state: AppState) {
+    let mut interval = tokio::time::interval(CONSISTENCY_INTERVAL);
+    let client = reqwest::Client::new();
+    loop {
+        interval.tick().await;
+        if !hold_lease(&state, "consistency", CONSISTENCY_INTERVAL) {
+            continue;
+        }
+        let result = {
+            let conn = state.db.lock().unwrap();
+            consistency::check(&conn).and_then(|report| {
+                let notifications = consistency::notifications(&conn, &report)?;
+                Ok((report, notifications))
+            })
+        };
+        let (report, notifications) = match result {
+            Ok(checked) => checked,
+            Err(e) => {
+                tracing::error!("consistency check failed: {}", e);
+                continue;
+            }
+        };
+        let open = report.open().count();
+        if open > 0 {
+            tracing::warn!(
+                "consistency check {} found {} problem(s) it could not fix",
+                report.report_id,
+                open
+            );
+        }
+        for notification in &notifications {
+            if let Err(e) = notify::post(&client, notification).await {
+                tracing::warn!("consistency notification failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Roll up runs into `crab_daily_stats`. Yesterday is recomputed too so runs
+/// that finished after the last pass before midnight are not lost.
+async fn crab_stats_job(state: AppState) {
+    let mut interval = tokio::time::interval(CRAB_STATS_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !hold_lease(&state, "crab_stats", CRAB_STATS_INTERVAL) {
+            continue;
+        }
+        let conn = state.db.lock().unwrap();
+        for days_ago in [1, 0] {
+            let result = analytics_db::day_offset(&conn, days_ago)
+                .and_then(|day| analytics_db::aggregate_crab_day(&conn, &day));
+            if let Err(e) = result {
+                tracing::error!("crab stats aggregation failed: {}", e);
+            }
+        }
+    }
+}
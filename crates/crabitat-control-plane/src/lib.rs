@@ -1,9 +1,29 @@
+pub mod acceptance;
+pub mod auth;
+pub mod benchmark;
+pub mod chaos;
+pub mod consistency;
+pub mod console;
+pub mod cursor;
 pub mod db;
+pub mod error;
+pub mod forge;
 pub mod github;
+pub mod github_app;
 pub mod handlers;
+pub mod init;
+pub mod issue_status;
+pub mod jira;
+pub mod jobs;
+pub mod metrics;
 pub mod mission_service;
 pub mod models;
+pub mod notify;
+pub mod prompt_template;
+pub mod redaction;
+pub mod risk;
 pub mod routes;
+pub mod workflow_packs;
 pub mod workflow_registry;
 
 use std::sync::{Arc, Mutex};
@@ -12,5 +32,36 @@ use rusqlite::Connection;
 
 #[derive(Clone)]
 pub struct AppState {
+    /// The one connection that writes
     pub db: Arc<Mutex<Connection>>,
+    /// Read-only connections; without them reads share `db`
+    pub reads: Option<db::ReadPool>,
+    pub metrics: Arc<metrics::Metrics>,
+}
+
+impl AppState {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            db: Arc::new(Mutex::new(conn)),
+            reads: None,
+            metrics: Arc::new(metrics::Metrics::default()),
+        }
+    }
+
+    /// Serve reads from a pool over the database file at `path`
+    pub fn with_read_pool(conn: Connection, path: &str) -> Result<Self, String> {
+        Ok(Self {
+            reads: Some(db::open_read_pool(path)?),
+            ..Self::new(conn)
+        })
+    }
+
+    /// A connection for a handler that only reads. Doesn't wait on the writer
+    /// unless every pooled connection is busy.
+    pub fn read(&self) -> db::ReadConn<'_> {
+        if let Some(conn) = self.reads.as_ref().and_then(|pool| pool.try_get()) {
+            return db::ReadConn::Pooled(conn);
+        }
+        db::ReadConn::Shared(self.db.lock().unwrap())
+    }
 }
@@ -1,10 +1,208 @@
-use std::sync::{Arc, Mutex};
-
-use crabitat_control_plane::{AppState, db, routes};
+use clap::{Args, Parser, Subcommand};
+use crabitat_control_plane::console::CONSOLE_URL_SETTING;
+use crabitat_control_plane::db::api_keys as keys_db;
+use crabitat_control_plane::db::migrations;
+use crabitat_control_plane::db::settings as settings_db;
+use crabitat_control_plane::handlers::api_keys::validate_scopes;
+use crabitat_control_plane::init::{self, InitArgs};
+use crabitat_control_plane::workflow_registry::{PROMPTS_PATHS_SETTING, WorkflowRegistry};
+use crabitat_control_plane::{AppState, db, jobs, routes};
+use rusqlite::Connection;
+use std::path::PathBuf;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+#[derive(Parser)]
+#[command(author, version, about = "The Crabitat control plane")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the config, database, first repo and starter workflows
+    Init(Box<InitArgs>),
+    /// Serve the API; the default when no command is given
+    Serve(ServeArgs),
+    /// Inspect or apply schema migrations
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Manage API keys; the API requires one once any exists
+    #[command(subcommand)]
+    Keys(KeysCommand),
+}
+
+#[derive(Args, Default)]
+struct ServeArgs {
+    /// Don't migrate on startup; refuse to start while migrations are pending
+    #[arg(long)]
+    no_migrate: bool,
+    /// Allow migrations that rebuild tables
+    #[arg(long)]
+    allow_destructive: bool,
+    /// Prompts directory; repeat to stack override directories over the
+    /// first, later ones winning. Saved as the prompts_root and prompts_paths
+    /// settings.
+    #[arg(long = "prompts-path")]
+    prompts_paths: Vec<PathBuf>,
+    /// Redirect requests outside /v1 to a console hosted at this URL, in
+    /// place of the embedded one. Saved as the console_url setting; pass an
+    /// empty string to clear it.
+    #[arg(long)]
+    console_url: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Show pending migrations and the rows they touch, without applying them
+    Status,
+    /// Apply pending migrations
+    Migrate {
+        /// Allow migrations that rebuild tables
+        #[arg(long)]
+        allow_destructive: bool,
+    },
+    /// Revert versioned migrations newer than a version, newest first
+    Rollback {
+        /// Version to go back to; 0 reverts every versioned migration
+        #[arg(long)]
+        to: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Create a key and print its token, which is not shown again
+    Create {
+        #[arg(long)]
+        name: String,
+        /// admin, console:read, console:write or crab:write; repeatable
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<String>,
+    },
+    /// List keys
+    List,
+    /// Revoke a key
+    Revoke { key_id: String },
+}
+
+/// Point prompts_root at the first `--prompts-path` and prompts_paths at the
+/// rest; nothing changes when the flag isn't given
+fn save_prompts_paths(conn: &Connection, paths: &[PathBuf]) -> Result<(), String> {
+    let Some((root, overrides)) = paths.split_first() else {
+        return Ok(());
+    };
+    let absolute = |p: &PathBuf| std::path::absolute(p).map_err(|e| e.to_string());
+    let overrides = overrides
+        .iter()
+        .map(absolute)
+        .collect::<Result<Vec<_>, _>>()?;
+    let joined = std::env::join_paths(&overrides).map_err(|e| e.to_string())?;
+    settings_db::set(conn, "prompts_root", &absolute(root)?.to_string_lossy())
+        .map_err(|e| e.to_string())?;
+    settings_db::set(conn, PROMPTS_PATHS_SETTING, &joined.to_string_lossy())
+        .map_err(|e| e.to_string())
+}
+
+fn db_path() -> String {
+    std::env::var("DATABASE_PATH").unwrap_or_else(|_| "crabitat.db".into())
+}
+
+fn run_db_command(command: DbCommand) -> Result<(), String> {
+    let conn = db::open(&db_path());
+    let mut out = std::io::stdout();
+    match command {
+        DbCommand::Status => {
+            let version = migrations::applied_versions(&conn)?
+                .last()
+                .copied()
+                .unwrap_or(0);
+            println!("schema version {}", version);
+            let steps = migrations::plan(&conn)?;
+            migrations::write_plan(&steps, &mut out).map_err(|e| e.to_string())
+        }
+        DbCommand::Migrate { allow_destructive } => {
+            let steps = migrations::apply(&conn, allow_destructive)?;
+            println!("applied {} migration step(s)", steps.len());
+            Ok(())
+        }
+        DbCommand::Rollback { to } => {
+            let reverted = migrations::revert(&conn, to)?;
+            for step in &reverted {
+                println!("reverted {}", step.detail);
+            }
+            println!("reverted {} migration(s)", reverted.len());
+            Ok(())
+        }
+    }
+}
+
+fn run_keys_command(command: KeysCommand) -> Result<(), String> {
+    let conn = db::open(&db_path());
+    migrations::apply(&conn, false)?;
+    match command {
+        KeysCommand::Create { name, scopes } => {
+            validate_scopes(&scopes)?;
+            let (key, token) = keys_db::insert(&conn, &name, &scopes)?;
+            println!("created key {} ({})", key.key_id, key.scopes.join(", "));
+            println!("{}", token);
+        }
+        KeysCommand::List => {
+            for key in keys_db::list(&conn)? {
+                let status = match &key.revoked_at {
+                    Some(at) => format!("revoked {}", at),
+                    None => "active".to_string(),
+                };
+                println!(
+                    "{}  {}...  {}  [{}]  {}",
+                    key.key_id,
+                    key.prefix,
+                    key.name,
+                    key.scopes.join(", "),
+                    status
+                );
+            }
+        }
+        KeysCommand::Revoke { key_id } => {
+            if !keys_db::revoke(&conn, &key_id)? {
+                return Err(format!("no active key {}", key_id));
+            }
+            println!("revoked {}", key_id);
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    let serve = match cli.command {
+        Some(Command::Init(args)) => {
+            let args = args.resolve();
+            if let Err(e) = init::run(&args, &mut std::io::stdout()).await {
+                eprintln!("init failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Db(command)) => {
+            if let Err(e) = run_db_command(command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Keys(command)) => {
+            if let Err(e) = run_keys_command(command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Serve(args)) => args,
+        None => ServeArgs::default(),
+    };
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -13,16 +211,70 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let db_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "crabitat.db".into());
+    let db_path = db_path();
     let addr = std::env::var("LISTEN_ADDR").unwrap_or_else(|_| "127.0.0.1:3001".into());
 
-    let conn = db::init(&db_path);
+    let conn = db::open(&db_path);
+    let migrated = if serve.no_migrate {
+        match migrations::plan(&conn) {
+            Ok(steps) if steps.is_empty() => Ok(()),
+            Ok(steps) => Err(format!(
+                "{} migration step(s) pending; run `db migrate` first",
+                steps.len()
+            )),
+            Err(e) => Err(e),
+        }
+    } else {
+        migrations::apply(&conn, serve.allow_destructive).map(|steps| {
+            if !steps.is_empty() {
+                tracing::info!("applied {} migration step(s)", steps.len());
+            }
+        })
+    };
+    if let Err(e) = migrated {
+        tracing::error!("database {} not ready: {}", db_path, e);
+        std::process::exit(1);
+    }
     tracing::info!("database initialized at {}", db_path);
 
-    let state = AppState {
-        db: Arc::new(Mutex::new(conn)),
+    if let Err(e) = save_prompts_paths(&conn, &serve.prompts_paths) {
+        tracing::error!("failed to save --prompts-path: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(url) = &serve.console_url
+        && let Err(e) = settings_db::set(&conn, CONSOLE_URL_SETTING, url.trim())
+    {
+        tracing::error!("failed to save --console-url: {}", e);
+        std::process::exit(1);
+    }
+
+    // Bad manifests are skipped when missions are created; flag them up front too
+    if let Ok(Some(root)) = settings_db::get(&conn, "prompts_root") {
+        for (path, issues) in WorkflowRegistry::open(&conn, root)
+            .map(|r| r.invalid_workflows())
+            .unwrap_or_default()
+        {
+            for issue in issues {
+                tracing::error!("invalid workflow {:?}: {}", path, issue.message);
+            }
+        }
+    }
+
+    // An in-memory database is private to its connection; it can't be pooled
+    let state = if db_path == ":memory:" {
+        AppState::new(conn)
+    } else {
+        match AppState::with_read_pool(conn, &db_path) {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::error!("failed to open read connections to {}: {}", db_path, e);
+                std::process::exit(1);
+            }
+        }
     };
 
+    jobs::spawn_all(state.clone());
+
     let app = routes::create_router(state);
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+/// Upper bounds (ms) of the latency histogram buckets
+const BUCKETS_MS: [u64; 10] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+/// Statements slower than the slow-query threshold, counted by the DB profiler
+pub static SLOW_QUERIES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct RouteStats {
+    count: u64,
+    client_errors: u64,
+    server_errors: u64,
+    sum_ms: f64,
+    buckets: [u64; BUCKETS_MS.len()],
+}
+
+/// Per-route request latency histograms and error counts
+#[derive(Default)]
+pub struct Metrics {
+    routes: Mutex<BTreeMap<(String, String), RouteStats>>,
+}
+
+impl Metrics {
+    pub fn record(&self, method: &str, route: &str, status: StatusCode, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let mut routes = self.routes.lock().unwrap();
+        let stats = routes
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+
+        stats.count += 1;
+        stats.sum_ms += ms;
+        if status.is_client_error() {
+            stats.client_errors += 1;
+        } else if status.is_server_error() {
+            stats.server_errors += 1;
+        }
+        for (bucket, le) in stats.buckets.iter_mut().zip(BUCKETS_MS) {
+            if ms <= le as f64 {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# TYPE crabitat_http_request_duration_ms histogram\n");
+        for ((method, route), stats) in routes.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, route);
+            for (le, n) in BUCKETS_MS.iter().zip(stats.buckets) {
+                let _ = writeln!(
+                    out,
+                    "crabitat_http_request_duration_ms_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, n
+                );
+            }
+            let _ = writeln!(
+                out,
+                "crabitat_http_request_duration_ms_bucket{{{},le=\"+Inf\"}} {}",
+                labels, stats.count
+            );
+            let _ = writeln!(
+                out,
+                "crabitat_http_request_duration_ms_sum{{{}}} {:.3}",
+                labels, stats.sum_ms
+            );
+            let _ = writeln!(
+                out,
+                "crabitat_http_request_duration_ms_count{{{}}} {}",
+                labels, stats.count
+            );
+        }
+
+        out.push_str("# TYPE crabitat_http_errors_total counter\n");
+        for ((method, route), stats) in routes.iter() {
+            for (class, n) in [("4xx", stats.client_errors), ("5xx", stats.server_errors)] {
+                let _ = writeln!(
+                    out,
+                    "crabitat_http_errors_total{{method=\"{}\",route=\"{}\",class=\"{}\"}} {}",
+                    method, route, class, n
+                );
+            }
+        }
+
+        out.push_str("# TYPE crabitat_db_slow_queries_total counter\n");
+        let _ = writeln!(
+            out,
+            "crabitat_db_slow_queries_total {}",
+            SLOW_QUERIES.load(Ordering::Relaxed)
+        );
+        out
+    }
+}
+
+/// Middleware recording latency and status per matched route
+pub async fn track(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+    state
+        .metrics
+        .record(&method, &route, response.status(), start.elapsed());
+    response
+}
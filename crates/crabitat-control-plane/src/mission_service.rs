@@ -1,22 +1,49 @@
+use crate::acceptance;
+use crate::db::artifacts as artifacts_db;
+use crate::db::blobs;
+use crate::db::changelog as changelog_db;
 use crate::db::issues as issues_db;
 use crate::db::missions as missions_db;
+use crate::db::repos as repos_db;
 use crate::db::settings as settings_db;
+use crate::db::tasks as tasks_db;
+use crate::db::triggers as triggers_db;
 use crate::db::workflows as wf_db;
-use crate::models::tasks::Task;
+use crate::models::missions::{
+    CreateMissionRequest, Mission, MissionCancellation, PrReviewComment, ReplayMissionRequest,
+    UpdateMissionRequest,
+};
+use crate::models::tasks::{CheckRun, CreateRunRequest, NewTask, StepConfig, Task};
+use crate::models::triggers::{Trigger, TriggerEvent};
+use crate::models::workflows::WorkflowFile;
+use crate::prompt_template::{
+    IssueVars, PromptTemplates, PromptVars, RepoVars, StepResult, TEMPLATE_VAR_PREFIX,
+};
+use crate::risk;
 use crate::workflow_registry::WorkflowRegistry;
+use crate::workflow_registry::compute_step_orders;
 use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::fmt;
 
 pub struct MissionService {
     registry: WorkflowRegistry,
+    templates: PromptTemplates,
 }
 
 pub struct AssemblePromptRequest<'a> {
+    pub mission_id: &'a str,
+    pub branch: &'a str,
     pub workflow_name: &'a str,
     pub step_id: &'a str,
     pub flavor_id: Option<&'a str>,
     pub repo_id: &'a str,
     pub issue_number: i64,
     pub context: Option<&'a str>,
+    /// Fills `{{item}}` for a task expanded from a `for_each` step
+    pub item: Option<&'a str>,
+    /// The mission's extra instructions, appended after the issue
+    pub mission_prompt: Option<&'a str>,
 }
 
 impl MissionService {
@@ -25,8 +52,11 @@ impl MissionService {
             .map_err(|e| e.to_string())?
             .ok_or("prompts_root not configured")?;
 
+        let registry = WorkflowRegistry::open(conn, prompts_root)?;
+        let templates = PromptTemplates::load(&registry);
         Ok(Self {
-            registry: WorkflowRegistry::new(prompts_root),
+            registry,
+            templates,
         })
     }
 
@@ -55,8 +85,18 @@ impl MissionService {
 
         let base_layer = self.registry.read_prompt(&step.prompt_file)?;
 
+        // Built-in step types contribute their own context
+        let mut context = req.context.unwrap_or("").to_string();
+        if step.step_type.as_deref() == Some("release-notes") {
+            let since = changelog_db::last_completed_at(conn, req.repo_id, req.workflow_name)?;
+            let changelog = changelog_db::build(conn, req.repo_id, since.as_deref())?;
+            context = format!("{}\n\n{}", changelog.to_markdown(), context)
+                .trim()
+                .to_string();
+        }
+
         // 2. Get Flavor Layer
-        let mut flavor_layer = String::new();
+        let mut flavor_layer = Vec::new();
         if let Some(fid) = req.flavor_id {
             let flavors = wf_db::list_flavors_for_workflow(conn, req.workflow_name)?;
             let flavor = flavors
@@ -65,9 +105,7 @@ impl MissionService {
                 .ok_or_else(|| format!("flavor not found: {}", fid))?;
 
             for path in &flavor.prompt_paths {
-                let content = self.registry.read_prompt(path)?;
-                flavor_layer.push_str(&content);
-                flavor_layer.push_str("\n\n");
+                flavor_layer.push((path.clone(), self.registry.read_prompt(path)?));
             }
         }
 
@@ -82,40 +120,892 @@ impl MissionService {
             issue.title, issue_body
         );
 
-        // 4. Resolve Template Variables
-        // Note: {{worktree_path}} is handled by the Crab worker (late-binding)
-        let mission_content = format!("{}\n\n{}", issue.title, issue_body);
-
-        let mut resolved_base = base_layer.replace("{{mission}}", &mission_content);
-        let mut resolved_flavor = flavor_layer.replace("{{mission}}", &mission_content);
-
-        // Handle {{context}} cleanup
-        let ctx_val = req.context.unwrap_or("");
-        if ctx_val.is_empty() {
-            resolved_base = resolved_base.replace("{{context}}", "");
-            resolved_flavor = resolved_flavor.replace("{{context}}", "");
+        // 4. Render the layers as templates
+        let vars = PromptVars {
+            mission: format!("{}\n\n{}", issue.title, issue_body),
+            context: context.clone(),
+            item: req.item.unwrap_or("").to_string(),
+            worktree_path: "{{worktree_path}}",
+            mission_id: req.mission_id.to_string(),
+            branch: req.branch.to_string(),
+            workflow: req.workflow_name.to_string(),
+            step_id: req.step_id.to_string(),
+            issue: IssueVars {
+                number: issue.number,
+                title: issue.title.clone(),
+                body: issue_body.clone(),
+                labels: issue.labels.clone(),
+            },
+            repo: repos_db::get_by_id(conn, req.repo_id)
+                .map_err(|e| e.to_string())?
+                .map(|r| RepoVars {
+                    owner: r.owner,
+                    name: r.name,
+                })
+                .unwrap_or_default(),
+            vars: template_vars(conn)?,
+            steps: step_results(conn, req.mission_id)?,
+            criteria: missions_db::get_acceptance_criteria(conn, req.mission_id)?,
+        };
+        let mut resolved_base = self.templates.render(&step.prompt_file, &base_layer, &vars);
+        let mut resolved_flavor = String::new();
+        for (path, content) in &flavor_layer {
+            resolved_flavor.push_str(&self.templates.render(path, content, &vars));
+            resolved_flavor.push_str("\n\n");
+        }
 
-            // Clean up the "Context from prior steps" header if it exists
+        // Clean up the "Context from prior steps" header if there is no context
+        if context.is_empty() {
             resolved_base = resolved_base.replace("## Context from prior steps", "");
             resolved_flavor = resolved_flavor.replace("## Context from prior steps", "");
-        } else {
-            resolved_base = resolved_base.replace("{{context}}", ctx_val);
-            resolved_flavor = resolved_flavor.replace("{{context}}", ctx_val);
         }
 
         // 5. Final Assembly
-        let final_prompt = format!(
+        let mut final_prompt = format!(
             "# Instructions\n{}\n\n# Context & Standards\n{}\n\n# Target Issue\n{}",
             resolved_base.trim(),
             resolved_flavor.trim(),
             issue_layer
         );
+        if let Some(section) =
+            acceptance::prompt_section(&vars.criteria, step.report_criteria.unwrap_or(false))
+        {
+            final_prompt.push_str(&format!("\n\n{}", section));
+        }
+        if let Some(extra) = req.mission_prompt.filter(|p| !p.trim().is_empty()) {
+            final_prompt.push_str(&format!("\n\n# Additional Instructions\n{}", extra.trim()));
+        }
 
         Ok(final_prompt)
     }
 }
 
-/// Re-run prompt assembly for a task's workflow step, injecting `context` into `{{context}}`.
+/// `template_var_*` settings, keyed without the prefix
+fn template_vars(conn: &Connection) -> Result<BTreeMap<String, String>, String> {
+    Ok(settings_db::list_all(conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|s| {
+            let name = s.key.strip_prefix(TEMPLATE_VAR_PREFIX)?.to_string();
+            Some((name, s.value))
+        })
+        .collect())
+}
+
+/// Latest run of every completed task in the mission, by step id
+fn step_results(
+    conn: &Connection,
+    mission_id: &str,
+) -> Result<BTreeMap<String, StepResult>, String> {
+    let mut steps = BTreeMap::new();
+    for task in tasks_db::list_tasks_for_mission(conn, mission_id)? {
+        if task.status != "completed" {
+            continue;
+        }
+        let run = tasks_db::latest_run_for_task(conn, &task.task_id)?;
+        steps.insert(
+            task.step_id,
+            StepResult {
+                status: task.status,
+                summary: run
+                    .as_ref()
+                    .and_then(|r| r.summary.clone())
+                    .unwrap_or_default(),
+                output: run.and_then(|r| r.logs).unwrap_or_default(),
+            },
+        );
+    }
+    Ok(steps)
+}
+
+#[derive(Debug)]
+pub enum CreateMissionError {
+    RepoNotFound,
+    PromptsRootNotSet,
+    WorkflowNotFound,
+    SourceMissionNotFound,
+    SourceMissionOtherRepo,
+    InvalidWorkflow(String),
+    Internal(String),
+}
+
+impl fmt::Display for CreateMissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepoNotFound => write!(f, "repo not found"),
+            Self::PromptsRootNotSet => write!(f, "prompts_root not set"),
+            Self::WorkflowNotFound => write!(f, "workflow not found"),
+            Self::SourceMissionNotFound => write!(f, "context_from_mission_id not found"),
+            Self::SourceMissionOtherRepo => {
+                write!(f, "context_from_mission_id belongs to another repo")
+            }
+            Self::InvalidWorkflow(e) | Self::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Create a mission and expand its workflow into tasks in one transaction.
+///
+/// Without an explicit priority, the mission inherits the highest priority of
+/// the issue triggers that route this issue to the same workflow.
+pub fn create_mission(
+    conn: &mut Connection,
+    req: &CreateMissionRequest,
+) -> Result<Mission, CreateMissionError> {
+    // Deterministic branch
+    let branch = format!("mission/issue-{}", req.issue_number);
+    create_mission_on_branch(conn, req, &branch)
+}
+
+/// Start a mission on issue `issue_number` with the workflow of `trigger`,
+/// the best trigger the issue matched, unless the issue already has a mission
+/// on that workflow. Returns the mission started.
+pub fn start_for_trigger(
+    conn: &mut Connection,
+    repo_id: &str,
+    issue_number: i64,
+    trigger: &Trigger,
+) -> Result<Option<Mission>, CreateMissionError> {
+    let existing = missions_db::list_by_repo(conn, repo_id)
+        .map_err(CreateMissionError::Internal)?
+        .into_iter()
+        .any(|m| m.issue_number == issue_number && m.workflow_name == trigger.workflow_name);
+    if existing {
+        return Ok(None);
+    }
+    let req = CreateMissionRequest {
+        repo_id: repo_id.to_string(),
+        issue_number,
+        workflow_name: trigger.workflow_name.clone(),
+        flavor_id: trigger.flavor_id.clone(),
+        priority: Some(trigger.priority),
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    create_mission(conn, &req).map(Some)
+}
+
+/// [`create_mission`] that also seeds the first steps with the artifacts of
+/// `context_from_mission_id` whose name or kind is in `artifacts`
+pub fn create_mission_with_artifacts(
+    conn: &mut Connection,
+    req: &CreateMissionRequest,
+    artifacts: &[String],
+) -> Result<Mission, CreateMissionError> {
+    let branch = format!("mission/issue-{}", req.issue_number);
+    create(conn, req, artifacts, &branch)
+}
+
+/// [`create_mission`] working on `branch` instead of the issue's own, for
+/// missions that mustn't share it with others on the same issue
+pub fn create_mission_on_branch(
+    conn: &mut Connection,
+    req: &CreateMissionRequest,
+    branch: &str,
+) -> Result<Mission, CreateMissionError> {
+    create(conn, req, &[], branch)
+}
+
+fn create(
+    conn: &mut Connection,
+    req: &CreateMissionRequest,
+    artifacts: &[String],
+    branch: &str,
+) -> Result<Mission, CreateMissionError> {
+    use CreateMissionError::Internal;
+
+    // 1. Guard: reject missions for soft-deleted repos
+    let repo = match repos_db::get_by_id(conn, &req.repo_id).map_err(Internal)? {
+        Some(repo) if repo.deleted_at.is_none() => repo,
+        _ => return Err(CreateMissionError::RepoNotFound),
+    };
+
+    // 2. Initialize Service
+    let service = MissionService::new(conn).map_err(Internal)?;
+
+    let prompts_root = settings_db::get(conn, "prompts_root")
+        .map_err(|e| Internal(e.to_string()))?
+        .ok_or(CreateMissionError::PromptsRootNotSet)?;
+
+    let registry = WorkflowRegistry::open(conn, prompts_root).map_err(Internal)?;
+    let wf = registry
+        .get_workflow(&req.workflow_name)
+        .ok_or(CreateMissionError::WorkflowNotFound)?;
+
+    let step_orders =
+        compute_step_orders(&wf.steps).map_err(CreateMissionError::InvalidWorkflow)?;
+    checks_supported(&repo.forge, &wf).map_err(CreateMissionError::InvalidWorkflow)?;
+
+    let priority = match req.priority {
+        Some(p) => Some(p),
+        None => trigger_priority(conn, req).map_err(Internal)?,
+    };
+
+    let source_context = match &req.context_from_mission_id {
+        Some(source_id) => {
+            let source = missions_db::get_mission(conn, source_id)
+                .map_err(Internal)?
+                .ok_or(CreateMissionError::SourceMissionNotFound)?;
+            if source.repo_id != req.repo_id {
+                return Err(CreateMissionError::SourceMissionOtherRepo);
+            }
+            mission_outputs_context(conn, source_id, artifacts).map_err(Internal)?
+        }
+        None => None,
+    };
+
+    // 3. Start Transaction
+    let tx = conn.transaction().map_err(|e| Internal(e.to_string()))?;
+
+    // 4. Create Mission Record
+    let mut mission = missions_db::insert_mission(
+        &tx,
+        &CreateMissionRequest {
+            repo_id: req.repo_id.clone(),
+            issue_number: req.issue_number,
+            workflow_name: req.workflow_name.clone(),
+            flavor_id: req.flavor_id.clone(),
+            priority,
+            context_from_mission_id: req.context_from_mission_id.clone(),
+            exclusive: req.exclusive || wf.workflow.exclusive,
+        },
+        branch,
+    )
+    .map_err(Internal)?;
+    if !artifacts.is_empty() {
+        missions_db::set_context_artifacts(&tx, &mission.mission_id, artifacts)
+            .map_err(Internal)?;
+        mission.context_artifacts = artifacts.to_vec();
+    }
+
+    // Seed initial state history entry
+    missions_db::insert_state_history_entry(&tx, &mission.mission_id, "pending")
+        .map_err(Internal)?;
+
+    // Pin the issue's task list now; later edits to the issue don't move the goalposts
+    if let Some(issue) = issues_db::get_cached_issue(&tx, &req.repo_id, req.issue_number)
+        .map_err(|e| Internal(e.to_string()))?
+    {
+        mission.acceptance_criteria =
+            acceptance::parse_checklist(issue.body.as_deref().unwrap_or_default());
+        missions_db::set_acceptance_criteria(
+            &tx,
+            &mission.mission_id,
+            &mission.acceptance_criteria,
+        )
+        .map_err(Internal)?;
+    }
+
+    // 5. Expand Workflow into Tasks (DAG-aware ordering)
+    expand_workflow(
+        &tx,
+        &service,
+        &mission,
+        &wf,
+        &step_orders,
+        0,
+        source_context.as_deref(),
+    )
+    .map_err(Internal)?;
+    mission.max_concurrent_tasks = wf.workflow.max_concurrent_tasks;
+    missions_db::set_workflow_version(&tx, &mission.mission_id, wf.workflow.version.as_deref())
+        .map_err(Internal)?;
+    mission.workflow_version = wf.workflow.version.clone();
+
+    // 6. Commit
+    tx.commit().map_err(|e| Internal(e.to_string()))?;
+
+    Ok(mission)
+}
+
+#[derive(Debug)]
+pub enum EditMissionError {
+    MissionNotFound,
+    /// Some task has already been picked up; carries the mission status
+    AlreadyStarted(String),
+    PromptsRootNotSet,
+    WorkflowNotFound,
+    InvalidWorkflow(String),
+    Internal(String),
+}
+
+impl fmt::Display for EditMissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissionNotFound => write!(f, "mission not found"),
+            Self::AlreadyStarted(status) => {
+                write!(
+                    f,
+                    "mission is {} and has started; it can no longer be edited",
+                    status
+                )
+            }
+            Self::PromptsRootNotSet => write!(f, "prompts_root not set"),
+            Self::WorkflowNotFound => write!(f, "workflow not found"),
+            Self::InvalidWorkflow(e) | Self::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// CI checks are only read from GitHub, so a repo on another forge can't run
+/// a workflow with a step that requires them
+fn checks_supported(forge: &str, wf: &WorkflowFile) -> Result<(), String> {
+    if forge == "github" {
+        return Ok(());
+    }
+    match wf.steps.iter().find(|s| s.require_checks == Some(true)) {
+        Some(step) => Err(format!(
+            "step '{}' requires CI checks, which are only read from GitHub; this repo is on {}",
+            step.id, forge
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Change the workflow, flavor or prompt of a mission no crab has touched yet.
+///
+/// Missions are expanded when created, so the untouched tasks are thrown
+/// away and the workflow is expanded again with the new settings, all in
+/// one transaction.
+pub fn edit_pending_mission(
+    conn: &mut Connection,
+    mission_id: &str,
+    req: &UpdateMissionRequest,
+) -> Result<Mission, EditMissionError> {
+    use EditMissionError::Internal;
+
+    let mission = missions_db::get_mission(conn, mission_id)
+        .map_err(Internal)?
+        .ok_or(EditMissionError::MissionNotFound)?;
+    let untouched = tasks_db::list_tasks_for_mission(conn, mission_id)
+        .map_err(Internal)?
+        .iter()
+        .all(|t| {
+            matches!(
+                t.status.as_str(),
+                "blocked" | "queued" | "awaiting_approval"
+            )
+        });
+    if mission.status != "pending"
+        || mission.archived_at.is_some()
+        || mission.rollup.first_started_at.is_some()
+        || !untouched
+    {
+        return Err(EditMissionError::AlreadyStarted(mission.status));
+    }
+
+    let workflow_name = req
+        .workflow_name
+        .clone()
+        .unwrap_or_else(|| mission.workflow_name.clone());
+    let flavor_id = match &req.flavor_id {
+        Some(flavor_id) => Some(flavor_id.clone()),
+        None if workflow_name == mission.workflow_name => mission.flavor_id.clone(),
+        None => None,
+    };
+    let prompt = match &req.prompt {
+        Some(prompt) if prompt.trim().is_empty() => None,
+        Some(prompt) => Some(prompt.clone()),
+        None => mission.prompt.clone(),
+    };
+
+    let prompts_root = settings_db::get(conn, "prompts_root")
+        .map_err(|e| Internal(e.to_string()))?
+        .ok_or(EditMissionError::PromptsRootNotSet)?;
+    let service = MissionService::new(conn).map_err(Internal)?;
+    let registry = WorkflowRegistry::open(conn, prompts_root).map_err(Internal)?;
+    let wf = registry
+        .get_workflow(&workflow_name)
+        .ok_or(EditMissionError::WorkflowNotFound)?;
+    let step_orders = compute_step_orders(&wf.steps).map_err(EditMissionError::InvalidWorkflow)?;
+    if let Some(repo) = repos_db::get_by_id(conn, &mission.repo_id).map_err(Internal)? {
+        checks_supported(&repo.forge, &wf).map_err(EditMissionError::InvalidWorkflow)?;
+    }
+    let source_context = match &mission.context_from_mission_id {
+        Some(source_id) => mission_outputs_context(conn, source_id, &mission.context_artifacts)
+            .map_err(Internal)?,
+        None => None,
+    };
+
+    let tx = conn.transaction().map_err(|e| Internal(e.to_string()))?;
+    missions_db::update_pending_mission(
+        &tx,
+        mission_id,
+        &workflow_name,
+        flavor_id.as_deref(),
+        prompt.as_deref(),
+    )
+    .map_err(Internal)?;
+    missions_db::set_workflow_version(&tx, mission_id, wf.workflow.version.as_deref())
+        .map_err(Internal)?;
+    tasks_db::delete_tasks_for_mission(&tx, mission_id).map_err(Internal)?;
+    let updated = Mission {
+        workflow_name,
+        flavor_id,
+        prompt,
+        ..mission
+    };
+    expand_workflow(
+        &tx,
+        &service,
+        &updated,
+        &wf,
+        &step_orders,
+        0,
+        source_context.as_deref(),
+    )
+    .map_err(Internal)?;
+    tx.commit().map_err(|e| Internal(e.to_string()))?;
+
+    missions_db::get_mission(conn, mission_id)
+        .map_err(Internal)?
+        .ok_or(EditMissionError::MissionNotFound)
+}
+
+#[derive(Debug)]
+pub enum ReplayMissionError {
+    MissionNotFound,
+    /// The workflow isn't at the version the replay was pinned to:
+    /// (expected, found)
+    VersionMismatch(String, Option<String>),
+    Create(CreateMissionError),
+    Edit(EditMissionError),
+}
+
+impl fmt::Display for ReplayMissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissionNotFound => write!(f, "mission not found"),
+            Self::VersionMismatch(expected, found) => write!(
+                f,
+                "workflow is at version {}, not {}",
+                found.as_deref().unwrap_or("(none)"),
+                expected
+            ),
+            Self::Create(e) => write!(f, "{}", e),
+            Self::Edit(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Create a fresh mission for the same issue and operator prompt as
+/// `mission_id`, on the workflow as it is now, linked back to the original.
+/// Any mission can be replayed, typically a failed one after its workflow was fixed.
+pub fn replay_mission(
+    conn: &mut Connection,
+    mission_id: &str,
+    req: &ReplayMissionRequest,
+) -> Result<Mission, ReplayMissionError> {
+    let internal = |e: String| ReplayMissionError::Create(CreateMissionError::Internal(e));
+
+    let original = missions_db::get_mission(conn, mission_id)
+        .map_err(internal)?
+        .ok_or(ReplayMissionError::MissionNotFound)?;
+    let workflow_name = req
+        .workflow_name
+        .clone()
+        .unwrap_or_else(|| original.workflow_name.clone());
+    let flavor_id = match &req.flavor_id {
+        Some(flavor_id) => Some(flavor_id.clone()),
+        None if workflow_name == original.workflow_name => original.flavor_id.clone(),
+        None => None,
+    };
+
+    if let Some(expected) = &req.workflow_version {
+        let prompts_root = settings_db::get(conn, "prompts_root")
+            .map_err(|e| internal(e.to_string()))?
+            .ok_or(ReplayMissionError::Create(
+                CreateMissionError::PromptsRootNotSet,
+            ))?;
+        let registry = WorkflowRegistry::open(conn, prompts_root).map_err(internal)?;
+        let wf = registry
+            .get_workflow(&workflow_name)
+            .ok_or(ReplayMissionError::Create(
+                CreateMissionError::WorkflowNotFound,
+            ))?;
+        if wf.workflow.version.as_ref() != Some(expected) {
+            return Err(ReplayMissionError::VersionMismatch(
+                expected.clone(),
+                wf.workflow.version.clone(),
+            ));
+        }
+    }
+
+    let replay = create_mission_with_artifacts(
+        conn,
+        &CreateMissionRequest {
+            repo_id: original.repo_id.clone(),
+            issue_number: original.issue_number,
+            workflow_name,
+            flavor_id,
+            priority: Some(original.priority),
+            context_from_mission_id: original.context_from_mission_id.clone(),
+            exclusive: original.exclusive,
+        },
+        &original.context_artifacts,
+    )
+    .map_err(ReplayMissionError::Create)?;
+    missions_db::set_replay_of(conn, &replay.mission_id, mission_id).map_err(internal)?;
+
+    // The operator's prompt goes into every task, so it needs a re-expansion
+    if original.prompt.is_some() {
+        edit_pending_mission(
+            conn,
+            &replay.mission_id,
+            &UpdateMissionRequest {
+                prompt: original.prompt.clone(),
+                ..Default::default()
+            },
+        )
+        .map_err(ReplayMissionError::Edit)?;
+    }
+
+    missions_db::get_mission(conn, &replay.mission_id)
+        .map_err(internal)?
+        .ok_or(ReplayMissionError::MissionNotFound)
+}
+
+/// Expand the workflow of every live mission that has no tasks, so it gets
+/// scheduled after all. Creation expands in the same transaction as the
+/// insert, but missions from before that, or whose tasks were removed by
+/// hand, would otherwise sit forever. Safe to run repeatedly: a mission is
+/// only expanded while it still has no tasks. Returns the repaired ids.
+pub fn repair_taskless_missions(conn: &Connection) -> Result<Vec<String>, String> {
+    let taskless = missions_db::list_taskless(conn)?;
+    if taskless.is_empty() {
+        return Ok(taskless);
+    }
+    let service = MissionService::new(conn)?;
+
+    let mut repaired = Vec::new();
+    for mission_id in taskless {
+        let Some(mission) = missions_db::get_mission(conn, &mission_id)? else {
+            continue;
+        };
+        let Some(wf) = service.registry.get_workflow(&mission.workflow_name) else {
+            tracing::warn!(
+                "mission {} has no tasks and its workflow {} is gone",
+                mission_id,
+                mission.workflow_name
+            );
+            continue;
+        };
+        let step_orders = compute_step_orders(&wf.steps)?;
+        let source_context = match &mission.context_from_mission_id {
+            Some(source_id) => {
+                mission_outputs_context(conn, source_id, &mission.context_artifacts)?
+            }
+            None => None,
+        };
+
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        if !tasks_db::list_tasks_for_mission(&tx, &mission_id)?.is_empty() {
+            continue;
+        }
+        expand_workflow(
+            &tx,
+            &service,
+            &mission,
+            &wf,
+            &step_orders,
+            0,
+            source_context.as_deref(),
+        )?;
+        tx.commit().map_err(|e| e.to_string())?;
+        repaired.push(mission_id);
+    }
+    Ok(repaired)
+}
+
+/// Insert a task per workflow step, with step orders shifted by `base_order`.
+/// Only a fresh mission (`base_order == 0`) queues its first tier; otherwise
+/// every task starts blocked and the cascade promotes them. `first_context`
+/// goes into the queued tier's prompts; later tiers get theirs on promotion.
+fn expand_workflow(
+    conn: &Connection,
+    service: &MissionService,
+    mission: &Mission,
+    wf: &WorkflowFile,
+    step_orders: &[(usize, usize)],
+    base_order: i64,
+    first_context: Option<&str>,
+) -> Result<(), String> {
+    missions_db::set_max_concurrent_tasks(
+        conn,
+        &mission.mission_id,
+        wf.workflow.max_concurrent_tasks,
+    )?;
+    for (step_idx, order) in step_orders {
+        let step = &wf.steps[*step_idx];
+        let step_order = base_order + *order as i64;
+        let prompt = service.assemble_prompt(
+            conn,
+            AssemblePromptRequest {
+                mission_id: &mission.mission_id,
+                branch: &mission.branch,
+                workflow_name: &wf.workflow.name,
+                step_id: &step.id,
+                flavor_id: mission.flavor_id.as_deref(),
+                repo_id: &mission.repo_id,
+                issue_number: mission.issue_number,
+                // Later tiers get their context when the task is promoted
+                context: if step_order == 0 { first_context } else { None },
+                item: None,
+                mission_prompt: mission.prompt.as_deref(),
+            },
+        )?;
+
+        let step_config = StepConfig {
+            min_score: step.min_score,
+            on_fail: step.on_fail.clone(),
+            read_only: step.read_only.unwrap_or(false),
+            switch_to: step.switch_to.clone().unwrap_or_default(),
+            timeout_secs: step.timeout(),
+            executor: step.executor.clone(),
+            executor_min_version: step.executor_min_version.clone(),
+            for_each: step.for_each.clone(),
+            item: None,
+            approval: step.step_type.as_deref() == Some("approval"),
+            context_max_bytes: step.context_budget(),
+            context_strategy: step.context_strategy.clone(),
+            network: step.network.clone().filter(|n| n != "full"),
+            require_checks: step.require_checks.unwrap_or(false),
+        };
+        let status = if step_order == 0 {
+            step_config.ready_status()
+        } else {
+            "blocked"
+        };
+
+        tasks_db::insert_new_task(
+            conn,
+            &NewTask {
+                mission_id: &mission.mission_id,
+                step_id: &step.id,
+                step_order,
+                assembled_prompt: &prompt,
+                max_retries: step.max_retries.unwrap_or(3) as i64,
+                status,
+                step_config,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Hand the rest of a mission to the workflow its classify step selected.
+///
+/// Applies when the task's latest run reported a `next_workflow` listed in the
+/// step's `switch_to`. The mission's not-yet-started tasks are replaced by the
+/// target workflow's steps, ordered after this task, in a single transaction.
+/// Returns the new workflow name when a switch happened.
+pub fn apply_workflow_switch(conn: &Connection, task: &Task) -> Result<Option<String>, String> {
+    if task.step_config.switch_to.is_empty() {
+        return Ok(None);
+    }
+    let Some(target) = tasks_db::latest_run_for_task(conn, &task.task_id)?
+        .and_then(|r| r.next_workflow)
+        .filter(|w| task.step_config.switch_to.contains(w))
+    else {
+        return Ok(None);
+    };
+
+    let mission = missions_db::get_mission(conn, &task.mission_id)?
+        .ok_or_else(|| format!("mission not found: {}", task.mission_id))?;
+    if mission.workflow_name == target {
+        return Ok(None);
+    }
+
+    let service = MissionService::new(conn)?;
+    let wf = service
+        .registry
+        .get_workflow(&target)
+        .ok_or_else(|| format!("workflow not found: {}", target))?;
+    let step_orders = compute_step_orders(&wf.steps)?;
+    if let Some(repo) = repos_db::get_by_id(conn, &mission.repo_id)? {
+        checks_supported(&repo.forge, &wf)?;
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tasks_db::delete_blocked_tasks_after(&tx, &mission.mission_id, task.step_order)?;
+    missions_db::update_workflow_name(&tx, &mission.mission_id, &target)?;
+    expand_workflow(
+        &tx,
+        &service,
+        &mission,
+        &wf,
+        &step_orders,
+        task.step_order + 1,
+        None,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(Some(target))
+}
+
+/// Cancel a mission: every task not yet finished is skipped and pending debug
+/// runs are failed, in a single transaction. Crabs running one of its tasks
+/// see the `skipped` status on their next check and kill the agent.
+pub fn cancel_mission(
+    conn: &Connection,
+    mission_id: &str,
+    actor: &str,
+) -> Result<MissionCancellation, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut skipped_tasks = 0;
+    let mut interrupted_tasks = 0;
+    for task in tasks_db::list_tasks_for_mission(&tx, mission_id)? {
+        if !matches!(
+            task.status.as_str(),
+            "blocked" | "queued" | "running" | "awaiting_approval" | "awaiting_checks"
+        ) {
+            continue;
+        }
+        tasks_db::transition_task(
+            &tx,
+            &task.task_id,
+            "skipped",
+            actor,
+            Some("mission cancelled"),
+        )?;
+        skipped_tasks += 1;
+        if task.status == "running" {
+            interrupted_tasks += 1;
+        }
+    }
+    let cancelled_runs = tasks_db::cancel_pending_debug_runs(&tx, mission_id)?;
+    missions_db::recalculate_mission_status(&tx, mission_id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(MissionCancellation {
+        mission_id: mission_id.to_string(),
+        skipped_tasks,
+        interrupted_tasks,
+        cancelled_runs,
+    })
+}
+
+/// Recover tasks whose crab went silent: record a failed run for the lost
+/// attempt, then requeue the task, or fail it once its retries are used up.
+/// Returns the ids of the recovered tasks.
+pub fn requeue_stale_tasks(conn: &Connection, stale_secs: i64) -> Result<Vec<String>, String> {
+    let mut recovered = Vec::new();
+    for (task, worker_id) in tasks_db::list_stale_running(conn, stale_secs)? {
+        let reason = format!("no heartbeat for {}s", stale_secs);
+        let summary = format!("Crab stopped reporting ({})", reason);
+        abandon_attempt(conn, &task, worker_id, &summary, "stale", &reason)?;
+        recovered.push(task.task_id);
+    }
+    Ok(recovered)
+}
+
+/// Take back tasks that have been running longer than their step's timeout
+/// plus `grace_secs`, the same way as stale ones. The crab sees the task leave
+/// `running` on its next heartbeat and kills the agent.
+pub fn fail_timed_out_tasks(conn: &Connection, grace_secs: i64) -> Result<Vec<String>, String> {
+    let mut timed_out = Vec::new();
+    for (task, worker_id) in tasks_db::list_timed_out_running(conn, grace_secs)? {
+        let limit = task.step_config.timeout_secs.unwrap_or_default();
+        let reason = format!("exceeded step timeout of {}s", limit);
+        let summary = format!("Run {} and was failed by the control plane", reason);
+        abandon_attempt(conn, &task, worker_id, &summary, "timeout", &reason)?;
+        timed_out.push(task.task_id);
+    }
+    Ok(timed_out)
+}
+
+/// Take back running tasks that have shown no output for `stuck_secs` even
+/// though their crab still heartbeats, the same way as timed-out ones
+pub fn fail_stuck_tasks(conn: &Connection, stuck_secs: i64) -> Result<Vec<String>, String> {
+    let mut stuck = Vec::new();
+    for running in tasks_db::list_running_output(conn)? {
+        if running.silent_secs < stuck_secs {
+            continue;
+        }
+        let reason = format!("no output for {}s", running.silent_secs);
+        let summary = format!(
+            "Run was stuck ({}) and was failed by the control plane",
+            reason
+        );
+        let worker_id = running.worker_id;
+        abandon_attempt(conn, &running.task, worker_id, &summary, "stuck", &reason)?;
+        stuck.push(running.task.task_id);
+    }
+    Ok(stuck)
+}
+
+/// Take a task back from the crab that just claimed it, as if the crab had
+/// died; for fault injection
+pub fn kill_assignment(
+    conn: &Connection,
+    task: &Task,
+    worker_id: Option<String>,
+) -> Result<(), String> {
+    abandon_attempt(
+        conn,
+        task,
+        worker_id,
+        "Run was killed by fault injection",
+        "chaos",
+        "assignment killed by chaos",
+    )
+}
+
+/// Record a failed run for a running attempt the control plane gave up on,
+/// then requeue the task while it has retries left, otherwise fail it
+fn abandon_attempt(
+    conn: &Connection,
+    task: &Task,
+    worker_id: Option<String>,
+    summary: &str,
+    failure_kind: &str,
+    reason: &str,
+) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tasks_db::insert_run(
+        &tx,
+        &task.task_id,
+        &CreateRunRequest {
+            status: "failed".to_string(),
+            summary: Some(summary.to_string()),
+            worker_id,
+            failure_kind: Some(failure_kind.to_string()),
+            ..Default::default()
+        },
+    )?;
+    if task.retry_count < task.max_retries {
+        tasks_db::transition_task(&tx, &task.task_id, "queued", "watchdog", Some(reason))?;
+        tasks_db::bump_retry_count(&tx, &task.task_id)?;
+    } else {
+        tasks_db::transition_task(&tx, &task.task_id, "failed", "watchdog", Some(reason))?;
+    }
+    missions_db::recalculate_mission_status(&tx, &task.mission_id)?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Highest priority among issue triggers routing this issue to the requested workflow
+fn trigger_priority(conn: &Connection, req: &CreateMissionRequest) -> Result<Option<i64>, String> {
+    let Some(issue) = issues_db::get_cached_issue(conn, &req.repo_id, req.issue_number)? else {
+        return Ok(None);
+    };
+
+    let event = TriggerEvent {
+        repo_id: req.repo_id.clone(),
+        event_type: "issue".to_string(),
+        title: issue.title,
+        body: issue.body,
+        labels: issue.labels,
+    };
+
+    Ok(triggers_db::evaluate(conn, &event)?
+        .into_iter()
+        .filter(|t| t.workflow_name == req.workflow_name)
+        .map(|t| t.priority)
+        .max())
+}
+
+/// Re-run prompt assembly for a task's workflow step, injecting `context`
+/// followed by any operator notes on the task into `{{context}}`.
 pub fn reassemble_prompt_with_context(
     conn: &Connection,
     task: &Task,
@@ -124,17 +1014,690 @@ pub fn reassemble_prompt_with_context(
     let mission = missions_db::get_mission(conn, &task.mission_id)?
         .ok_or_else(|| format!("mission not found: {}", task.mission_id))?;
 
+    let mut parts = vec![context.to_string()];
+    for note in tasks_db::list_notes(conn, &task.task_id)? {
+        parts.push(format!(
+            "<operator_note author=\"{}\">\n{}\n</operator_note>",
+            note.author.as_deref().unwrap_or("operator"),
+            note.body
+        ));
+    }
+    let context = parts.join("\n\n");
+    let context = context.trim();
+
     let service = MissionService::new(conn)?;
 
     service.assemble_prompt(
         conn,
         AssemblePromptRequest {
+            mission_id: &mission.mission_id,
+            branch: &mission.branch,
             workflow_name: &mission.workflow_name,
             step_id: &task.step_id,
             flavor_id: mission.flavor_id.as_deref(),
             repo_id: &mission.repo_id,
             issue_number: mission.issue_number,
             context: Some(context),
+            item: task.step_config.item.as_deref(),
+            mission_prompt: mission.prompt.as_deref(),
+        },
+    )
+}
+
+/// Fan a blocked `for_each` task out into one task per item of its source
+/// step's output, as its tier is promoted. The task itself takes the first
+/// item and a sibling at the same step order is added for each other one.
+///
+/// Returns the ids of every task now standing for the step, prompts assembled
+/// with `context`, or `None` when the task isn't a `for_each` step waiting to
+/// expand or its source step reported no items.
+pub fn expand_for_each(
+    conn: &Connection,
+    task: &Task,
+    context: &str,
+) -> Result<Option<Vec<String>>, String> {
+    let Some(source) = &task.step_config.for_each else {
+        return Ok(None);
+    };
+    if task.step_config.item.is_some() {
+        return Ok(None);
+    }
+
+    let mut items = Vec::new();
+    for t in tasks_db::list_tasks_for_mission(conn, &task.mission_id)? {
+        if &t.step_id != source || t.status != "completed" {
+            continue;
+        }
+        let Some(run) = tasks_db::latest_run_for_task(conn, &t.task_id)? else {
+            continue;
+        };
+        if let Some(found) = [run.summary, run.logs]
+            .iter()
+            .flatten()
+            .find_map(|output| for_each_items(output))
+        {
+            items.extend(found);
+        }
+    }
+    if items.is_empty() {
+        return Ok(None);
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut ids = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let step_config = StepConfig {
+            item: Some(item),
+            ..task.step_config.clone()
+        };
+        let task_id = if index == 0 {
+            tasks_db::update_task_step_config(&tx, &task.task_id, &step_config)?;
+            task.task_id.clone()
+        } else {
+            tasks_db::insert_new_task(
+                &tx,
+                &NewTask {
+                    mission_id: &task.mission_id,
+                    step_id: &task.step_id,
+                    step_order: task.step_order,
+                    assembled_prompt: &task.assembled_prompt,
+                    max_retries: task.max_retries,
+                    status: "blocked",
+                    step_config,
+                },
+            )?
+            .task_id
+        };
+        let copy = tasks_db::get_task(&tx, &task_id)?
+            .ok_or_else(|| format!("task not found: {}", task_id))?;
+        let prompt = reassemble_prompt_with_context(&tx, &copy, context)?;
+        tasks_db::update_task_assembled_prompt(&tx, &task_id, &prompt)?;
+        ids.push(task_id);
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(Some(ids))
+}
+
+/// Items in a step's output: the whole output or its last ```json block,
+/// holding either an array or an object with an `items` array. String items
+/// are used as-is, anything else as compact JSON.
+pub fn for_each_items(output: &str) -> Option<Vec<String>> {
+    let fenced = output
+        .rsplit_once("```json")
+        .and_then(|(_, rest)| rest.split_once("```"))
+        .map(|(block, _)| block);
+    let value = [Some(output), fenced]
+        .into_iter()
+        .flatten()
+        .find_map(|text| serde_json::from_str::<serde_json::Value>(text.trim()).ok())?;
+    let array = match value {
+        serde_json::Value::Array(array) => array,
+        serde_json::Value::Object(mut object) => match object.remove("items") {
+            Some(serde_json::Value::Array(array)) => array,
+            _ => return None,
         },
+        _ => return None,
+    };
+    Some(
+        array
+            .into_iter()
+            .map(|item| match item {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// Fan-in / fan-out after `task` completed: once every task at its step order
+/// is done, the next tier's blocked tasks get the tier's combined output as
+/// context and are queued, or held for a human if they are approval steps.
+pub fn promote_next_tier(conn: &Connection, task: &Task) -> Result<(), String> {
+    let mission_id = &task.mission_id;
+    let current_order = task.step_order;
+    if tasks_db::count_incomplete_at_order(conn, mission_id, current_order)? > 0 {
+        return Ok(());
+    }
+
+    let reason = format!("fan-in complete at order {}", current_order);
+    for next_task in tasks_db::get_blocked_tasks_at_order(conn, mission_id, current_order + 1)? {
+        let combined_context = upstream_context(conn, &next_task);
+        // A for_each step becomes one task per item, prompts already assembled
+        let task_ids = match expand_for_each(conn, &next_task, &combined_context) {
+            Ok(Some(ids)) => ids,
+            result => {
+                if let Err(e) = result {
+                    tracing::warn!(
+                        "for_each expansion of task {} failed: {}",
+                        next_task.task_id,
+                        e
+                    );
+                }
+                if let Ok(new_prompt) =
+                    reassemble_prompt_with_context(conn, &next_task, &combined_context)
+                {
+                    tasks_db::update_task_assembled_prompt(conn, &next_task.task_id, &new_prompt)?;
+                }
+                vec![next_task.task_id.clone()]
+            }
+        };
+        let status = next_task.step_config.ready_status();
+        for id in &task_ids {
+            tasks_db::transition_task(conn, id, status, "cascade", Some(&reason))?;
+        }
+    }
+    Ok(())
+}
+
+/// Output of one completed upstream task
+pub struct StepOutput {
+    pub step_id: String,
+    pub logs: String,
+    /// The agent's own summary of its work, if it wrote one
+    pub summary: Option<String>,
+}
+
+fn fan_in_outputs(conn: &Connection, mission_id: &str, step_order: i64) -> Vec<StepOutput> {
+    let completed =
+        tasks_db::get_completed_tasks_at_order(conn, mission_id, step_order).unwrap_or_default();
+
+    completed
+        .into_iter()
+        .map(|task| {
+            let run = tasks_db::latest_run_for_task(conn, &task.task_id).unwrap_or_default();
+            StepOutput {
+                step_id: task.step_id,
+                logs: run
+                    .as_ref()
+                    .and_then(|r| r.logs.clone())
+                    .unwrap_or_default(),
+                summary: run.and_then(|r| r.summary).filter(|s| !s.trim().is_empty()),
+            }
+        })
+        .collect()
+}
+
+/// Collect logs from all completed tasks at a given step_order, wrapped in XML tags.
+pub fn collect_fan_in_context(conn: &Connection, mission_id: &str, step_order: i64) -> String {
+    fit_context(&fan_in_outputs(conn, mission_id, step_order), None, "tail")
+}
+
+/// The previous tier's output as context for `task`, cut to the step's
+/// budget (or the `context_max_bytes` setting) with the step's strategy.
+pub fn upstream_context(conn: &Connection, task: &Task) -> String {
+    if task.step_order == 0 {
+        return String::new();
+    }
+    let budget = task
+        .step_config
+        .context_max_bytes
+        .or_else(|| {
+            settings_db::get(conn, "context_max_bytes")
+                .ok()
+                .flatten()
+                .and_then(|v| v.trim().parse().ok())
+        })
+        .filter(|&bytes| bytes > 0);
+    let strategy = task
+        .step_config
+        .context_strategy
+        .as_deref()
+        .unwrap_or("tail");
+    // Approval tiers produce no output; what they let through passes on
+    let mut order = task.step_order - 1;
+    while order > 0 {
+        let tier = tasks_db::get_completed_tasks_at_order(conn, &task.mission_id, order)
+            .unwrap_or_default();
+        if tier.is_empty() || !tier.iter().all(|t| t.step_config.approval) {
+            break;
+        }
+        order -= 1;
+    }
+    fit_context(
+        &fan_in_outputs(conn, &task.mission_id, order),
+        budget,
+        strategy,
+    )
+}
+
+/// Join step outputs into `<step>` blocks holding at most `budget` bytes of
+/// output between them. Over budget, `summarize` swaps each step's logs for
+/// its summary, and every strategy then splits the budget evenly (short
+/// outputs hand their slack to longer ones) and cuts each output to its
+/// share: `head` keeps the start, `tail` and `summarize` keep the end.
+pub fn fit_context(outputs: &[StepOutput], budget: Option<usize>, strategy: &str) -> String {
+    let mut bodies: Vec<&str> = outputs.iter().map(|o| o.logs.as_str()).collect();
+    let total = |bodies: &[&str]| bodies.iter().map(|b| b.len()).sum::<usize>();
+
+    if let Some(budget) = budget
+        && total(&bodies) > budget
+    {
+        if strategy == "summarize" {
+            bodies = outputs
+                .iter()
+                .map(|o| o.summary.as_deref().unwrap_or(&o.logs))
+                .collect();
+        }
+        if total(&bodies) > budget {
+            let mut by_len: Vec<usize> = (0..bodies.len()).collect();
+            by_len.sort_by_key(|&i| bodies[i].len());
+            let mut shares = vec![0; bodies.len()];
+            let mut remaining = budget;
+            for (k, &i) in by_len.iter().enumerate() {
+                let share = bodies[i].len().min(remaining / (by_len.len() - k));
+                shares[i] = share;
+                remaining -= share;
+            }
+            let cut: Vec<String> = bodies
+                .iter()
+                .zip(shares)
+                .map(|(body, share)| blobs::truncate(body, share, strategy == "head"))
+                .collect();
+            return join_steps(outputs, cut.iter().map(String::as_str));
+        }
+    }
+    join_steps(outputs, bodies.into_iter())
+}
+
+fn join_steps<'a>(outputs: &[StepOutput], bodies: impl Iterator<Item = &'a str>) -> String {
+    outputs
+        .iter()
+        .zip(bodies)
+        .map(|(o, body)| format!("<step id=\"{}\">\n{}\n</step>", o.step_id, body))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Context carried from an earlier mission: every completed step's summary,
+/// the full output of its last completed tier and the artifacts whose name or
+/// kind is in `artifacts`, tagged with where they came from. Artifacts that
+/// aren't text are listed without their content. `None` when the mission
+/// doesn't exist.
+pub fn mission_outputs_context(
+    conn: &Connection,
+    mission_id: &str,
+    artifacts: &[String],
+) -> Result<Option<String>, String> {
+    let Some(source) = missions_db::get_mission(conn, mission_id)? else {
+        return Ok(None);
+    };
+    let completed: Vec<Task> = tasks_db::list_tasks_for_mission(conn, mission_id)?
+        .into_iter()
+        .filter(|t| t.status == "completed")
+        .collect();
+    let final_order = completed.iter().map(|t| t.step_order).max();
+
+    let mut parts = Vec::new();
+    for task in &completed {
+        let Some(run) = tasks_db::latest_run_for_task(conn, &task.task_id)? else {
+            continue;
+        };
+        if let Some(summary) = run.summary.filter(|s| !s.trim().is_empty()) {
+            parts.push(format!(
+                "<summary step=\"{}\">\n{}\n</summary>",
+                task.step_id, summary
+            ));
+        }
+        if Some(task.step_order) == final_order {
+            let logs = run.logs.unwrap_or_default();
+            parts.push(format!(
+                "<output step=\"{}\">\n{}\n</output>",
+                task.step_id, logs
+            ));
+        }
+    }
+
+    if !artifacts.is_empty() {
+        let tasks = tasks_db::list_tasks_for_mission(conn, mission_id)?;
+        for artifact in artifacts_db::list_for_mission(conn, mission_id)? {
+            let selected = artifacts
+                .iter()
+                .any(|a| *a == artifact.name || Some(a) == artifact.kind.as_ref());
+            if !selected {
+                continue;
+            }
+            let Some((_, content)) = artifacts_db::get_content(conn, &artifact.artifact_id)? else {
+                continue;
+            };
+            let step_id = tasks
+                .iter()
+                .find(|t| t.task_id == artifact.task_id)
+                .map_or("", |t| t.step_id.as_str());
+            let tag = format!(
+                "<artifact name=\"{}\" step=\"{}\" sha256=\"{}\"",
+                artifact.name, step_id, artifact.sha256
+            );
+            parts.push(match String::from_utf8(content) {
+                Ok(text) => format!("{}>\n{}\n</artifact>", tag, text),
+                Err(_) => format!("{} content_type=\"{}\" />", tag, artifact.content_type),
+            });
+        }
+    }
+
+    Ok(Some(format!(
+        "<from_mission id=\"{}\" workflow=\"{}\" issue=\"{}\" branch=\"{}\" status=\"{}\">\n{}\n</from_mission>",
+        source.mission_id,
+        source.workflow_name,
+        source.issue_number,
+        source.branch,
+        source.status,
+        parts.join("\n\n")
+    )))
+}
+
+/// Longest tail of a failed attempt's logs carried into the retry prompt
+const FAILURE_CONTEXT_CHARS: usize = 4000;
+
+/// Requeue a task that was just reported failed, if its step has retries left.
+///
+/// The retry prompt gets the same upstream context as the first attempt plus
+/// the failed attempt's summary (or the tail of its logs). Returns whether the
+/// task was requeued; otherwise it stays failed and so does the mission.
+pub fn requeue_failed_task(conn: &Connection, task: &Task) -> Result<bool, String> {
+    if task.status != "failed" || task.retry_count >= task.max_retries {
+        return Ok(false);
+    }
+
+    let attempt = tasks_db::latest_run_for_task(conn, &task.task_id)?
+        .and_then(|r| r.summary.or(r.logs))
+        .unwrap_or_default();
+    let tail_start = attempt
+        .char_indices()
+        .rev()
+        .nth(FAILURE_CONTEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    let mut context = format!(
+        "<previous_attempt number=\"{}\" outcome=\"failed\">\n{}\n</previous_attempt>",
+        task.retry_count + 1,
+        &attempt[tail_start..]
+    );
+    if task.step_order > 0 {
+        let upstream = upstream_context(conn, task);
+        if !upstream.is_empty() {
+            context = format!("{}\n\n{}", upstream, context);
+        }
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    if let Ok(new_prompt) = reassemble_prompt_with_context(&tx, task, &context) {
+        tasks_db::update_task_assembled_prompt(&tx, &task.task_id, &new_prompt)?;
+    }
+    let reason = format!(
+        "attempt {} of {}",
+        task.retry_count + 2,
+        task.max_retries + 1
+    );
+    tasks_db::transition_task(&tx, &task.task_id, "queued", "auto-retry", Some(&reason))?;
+    tasks_db::bump_retry_count(&tx, &task.task_id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Result of checking a completed task against its step's `min_score`
+#[derive(Debug, PartialEq, Eq)]
+pub enum GateOutcome {
+    /// No gate configured, or the latest run met it
+    Passed,
+    /// Score too low; the `on_fail` step was requeued with the review as context
+    Rerouted { fix_task_id: String },
+    /// Score too low and no rework possible; the task was marked failed
+    Failed { reason: String },
+}
+
+/// Enforce the quality gate for a task that was just marked completed.
+///
+/// A run without a score counts as failing the gate. When the step has an
+/// `on_fail` target and retries remain, every task from the fix step up to
+/// this one is reset and the fix step is requeued with the review output.
+pub fn apply_quality_gate(conn: &Connection, task: &Task) -> Result<GateOutcome, String> {
+    let Some(min_score) = task.step_config.min_score else {
+        return Ok(GateOutcome::Passed);
+    };
+
+    let latest_run = tasks_db::latest_run_for_task(conn, &task.task_id)?;
+    let score = latest_run.as_ref().and_then(|r| r.score);
+
+    let reason = match score {
+        Some(s) if s >= min_score => return Ok(GateOutcome::Passed),
+        Some(s) => format!("score {} below min_score {}", s, min_score),
+        None => format!("no score reported (min_score {})", min_score),
+    };
+    let review = latest_run
+        .and_then(|r| r.summary.or(r.logs))
+        .unwrap_or_default();
+    let context = format!(
+        "<review step=\"{}\" outcome=\"{}\">\n{}\n</review>",
+        task.step_id, reason, review
+    );
+    reroute_to_fix_step(conn, task, "quality-gate", &reason, &context)
+}
+
+/// A gate rejected `task`: when the step has an `on_fail` target and
+/// retries remain, every task from the fix step up to this one is reset and
+/// the fix step is requeued with `context` and the review comments left on
+/// the mission's pull request since the last fix; otherwise the task fails.
+fn reroute_to_fix_step(
+    conn: &Connection,
+    task: &Task,
+    actor: &str,
+    reason: &str,
+    context: &str,
+) -> Result<GateOutcome, String> {
+    let fix_task = match &task.step_config.on_fail {
+        Some(fix_step) if task.retry_count < task.max_retries => {
+            tasks_db::list_tasks_for_mission(conn, &task.mission_id)?
+                .into_iter()
+                .find(|t| &t.step_id == fix_step && t.step_order < task.step_order)
+        }
+        _ => None,
+    };
+
+    let Some(fix_task) = fix_task else {
+        tasks_db::transition_task(conn, &task.task_id, "failed", actor, Some(reason))?;
+        return Ok(GateOutcome::Failed {
+            reason: reason.to_string(),
+        });
+    };
+
+    // Human review on the pull request goes along with the gate's own findings
+    let mut parts: Vec<String> = vec![context.to_string()];
+    parts.extend(
+        missions_db::take_pr_review_comments(conn, &task.mission_id)?
+            .iter()
+            .map(pr_review_context),
+    );
+    let context = parts.join("\n\n");
+    if let Ok(new_prompt) = reassemble_prompt_with_context(conn, &fix_task, context.trim()) {
+        tasks_db::update_task_assembled_prompt(conn, &fix_task.task_id, &new_prompt)?;
+    }
+
+    // Count the rejection against the review step, then rewind the pipeline
+    tasks_db::bump_retry_count(conn, &task.task_id)?;
+    for t in tasks_db::list_tasks_for_mission(conn, &task.mission_id)? {
+        if t.step_order > fix_task.step_order && t.step_order <= task.step_order {
+            // A sibling still running cannot be rewound; let it finish
+            if let Err(e) =
+                tasks_db::transition_task(conn, &t.task_id, "blocked", actor, Some(reason))
+            {
+                tracing::warn!("not rewinding task {}: {}", t.task_id, e);
+            }
+        }
+    }
+    tasks_db::transition_task(conn, &fix_task.task_id, "queued", actor, Some(reason))?;
+
+    Ok(GateOutcome::Rerouted {
+        fix_task_id: fix_task.task_id,
+    })
+}
+
+fn pr_review_context(comment: &PrReviewComment) -> String {
+    let mut attrs = format!("author=\"{}\"", comment.author);
+    if let Some(path) = &comment.path {
+        attrs.push_str(&format!(" path=\"{}\"", path));
+    }
+    if let Some(line) = comment.line {
+        attrs.push_str(&format!(" line=\"{}\"", line));
+    }
+    if !comment.url.is_empty() {
+        attrs.push_str(&format!(" url=\"{}\"", comment.url));
+    }
+    format!(
+        "<pr_review {}>\n{}\n</pr_review>",
+        attrs,
+        comment.body.trim()
     )
 }
+
+/// Everything that follows a task completing and passing its gates: a
+/// workflow switch, the risk review and promotion of the next tier. Each
+/// failure is logged and doesn't stop the rest.
+pub fn advance_completed_task(conn: &Connection, task: &Task) {
+    // A classify step may swap the remaining steps for a cheaper workflow
+    if let Err(e) = apply_workflow_switch(conn, task) {
+        tracing::warn!("workflow switch for task {} failed: {}", task.task_id, e);
+    }
+    // A high-risk change waits for a human before the next tier
+    if let Err(e) = risk::insert_review_gate(conn, task) {
+        tracing::warn!("risk review for task {} failed: {}", task.task_id, e);
+    }
+    if let Err(e) = promote_next_tier(conn, task) {
+        tracing::warn!("cascade from task {} failed: {}", task.task_id, e);
+    }
+}
+
+/// Park a task that just completed in `awaiting_checks` if its step requires
+/// CI checks. Returns whether it now waits.
+pub fn hold_for_checks(conn: &Connection, task: &Task) -> Result<bool, String> {
+    if !task.step_config.require_checks {
+        return Ok(false);
+    }
+    tasks_db::transition_task(
+        conn,
+        &task.task_id,
+        "awaiting_checks",
+        "ci-checks",
+        Some("waiting for pull request checks"),
+    )?;
+    Ok(true)
+}
+
+/// Settings key for how long a task waits for its pull request's first
+/// required check to be reported before the gate fails
+pub const CHECKS_GRACE_SECS_SETTING: &str = "checks_grace_secs";
+const DEFAULT_CHECKS_GRACE_SECS: i64 = 900;
+
+pub fn checks_grace_secs(conn: &Connection) -> i64 {
+    settings_db::get(conn, CHECKS_GRACE_SECS_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CHECKS_GRACE_SECS)
+}
+
+/// Result of a round of CI checks for a task in `awaiting_checks`
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChecksOutcome {
+    /// Some checks haven't finished; the task keeps waiting
+    Pending,
+    Gate(GateOutcome),
+}
+
+/// Settle a task waiting on CI with the checks its pull request reports.
+/// Any failed or cancelled check, or a reviewer requesting changes, sends
+/// the mission back to the step's `on_fail` with the failures and review
+/// comments as context, as a failed quality gate would;
+/// all passing completes the task and promotes the next tier. A freshly
+/// opened pull request reports no checks until CI picks it up, so none at
+/// all keeps the task waiting, and fails the gate once that has lasted
+/// `checks_grace_secs`. `checks` is `Err` with the reason when there is no
+/// way to read them, such as a mission without a pull request, which fails
+/// the gate.
+pub fn apply_checks(
+    conn: &Connection,
+    task: &Task,
+    checks: Result<&[CheckRun], &str>,
+) -> Result<ChecksOutcome, String> {
+    let outcome = settle_checks(conn, task, checks)?;
+    if outcome != ChecksOutcome::Pending {
+        missions_db::recalculate_mission_status(conn, &task.mission_id)?;
+    }
+    Ok(outcome)
+}
+
+fn settle_checks(
+    conn: &Connection,
+    task: &Task,
+    checks: Result<&[CheckRun], &str>,
+) -> Result<ChecksOutcome, String> {
+    let checks = match checks {
+        Ok(checks) => checks,
+        Err(reason) => {
+            tasks_db::transition_task(conn, &task.task_id, "failed", "ci-checks", Some(reason))?;
+            return Ok(ChecksOutcome::Gate(GateOutcome::Failed {
+                reason: reason.to_string(),
+            }));
+        }
+    };
+
+    let checks = match checks {
+        [] => {
+            let waited = tasks_db::secs_in_status(conn, &task.task_id, "awaiting_checks")?;
+            let grace = checks_grace_secs(conn);
+            if waited.is_some_and(|w| w >= grace) {
+                return settle_checks(
+                    conn,
+                    task,
+                    Err(&format!("no required checks reported within {}s", grace)),
+                );
+            }
+            return Ok(ChecksOutcome::Pending);
+        }
+        checks => checks,
+    };
+    let failing: Vec<&CheckRun> = checks
+        .iter()
+        .filter(|c| matches!(c.bucket.as_str(), "fail" | "cancel"))
+        .collect();
+    let changes_requested = missions_db::has_untaken_pr_review_comments(conn, &task.mission_id)?;
+    if failing.is_empty() && !changes_requested {
+        if checks.iter().any(|c| c.bucket == "pending") {
+            return Ok(ChecksOutcome::Pending);
+        }
+        tasks_db::transition_task(
+            conn,
+            &task.task_id,
+            "completed",
+            "ci-checks",
+            Some("checks passed"),
+        )?;
+        advance_completed_task(conn, task);
+        return Ok(ChecksOutcome::Gate(GateOutcome::Passed));
+    }
+
+    if failing.is_empty() {
+        let reason = "changes requested on the pull request";
+        return reroute_to_fix_step(conn, task, "ci-checks", reason, "").map(ChecksOutcome::Gate);
+    }
+    let names: Vec<&str> = failing.iter().map(|c| c.name.as_str()).collect();
+    let reason = format!("checks failed: {}", names.join(", "));
+    let details: Vec<String> = failing
+        .iter()
+        .map(|c| {
+            let mut line = format!("- {} ({})", c.name, c.bucket);
+            if !c.description.is_empty() {
+                line.push_str(&format!(": {}", c.description));
+            }
+            if !c.link.is_empty() {
+                line.push_str(&format!(" {}", c.link));
+            }
+            line
+        })
+        .collect();
+    let context = format!(
+        "<checks step=\"{}\" outcome=\"{}\">\n{}\n</checks>",
+        task.step_id,
+        reason,
+        details.join("\n")
+    );
+    reroute_to_fix_step(conn, task, "ci-checks", &reason, &context).map(ChecksOutcome::Gate)
+}
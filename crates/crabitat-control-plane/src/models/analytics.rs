@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// Daily score aggregate for one crab (worker) or one workflow
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScoreTrend {
+    pub group: String,
+    pub day: String,
+    pub runs: i64,
+    pub avg_score: f64,
+    pub min_score: i64,
+    pub max_score: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScoreTrendQuery {
+    /// `workflow` (default) or `worker`
+    pub group_by: Option<String>,
+}
+
+/// One crab's activity on one UTC day, from the `crab_daily_stats` rollup
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrabDailyStats {
+    pub worker_id: String,
+    pub day: String,
+    pub runs: i64,
+    pub tasks_completed: i64,
+    pub tasks_failed: i64,
+    pub busy_ms: i64,
+    /// Share of the day spent running tasks, 0-100
+    pub busy_pct: f64,
+    pub tokens_used: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CrabStatsQuery {
+    /// Number of days back from today, inclusive (default 7)
+    pub window: Option<i64>,
+}
+
+/// Seconds in each `granularity` a repo's activity can be bucketed by
+pub const ACTIVITY_GRANULARITIES: &[(&str, i64)] = &[("hour", 3600), ("day", 86_400)];
+
+/// Longest range one activity query covers
+pub const MAX_ACTIVITY_RANGE_SECS: i64 = 92 * 86_400;
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityQuery {
+    /// `hour` (default) or `day`
+    pub granularity: Option<String>,
+    /// ISO-8601 start of the range; defaults to 7 days before `to`
+    pub from: Option<String>,
+    /// ISO-8601 end of the range; defaults to now
+    pub to: Option<String>,
+}
+
+/// How busy a repo was in one hour or day
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityBucket {
+    /// UTC start of the bucket
+    pub start: String,
+    /// Minutes in which at least one of the repo's runs was going; parallel
+    /// runs don't add up
+    pub busy_minutes: i64,
+    pub runs_started: i64,
+}
+
+/// A repo's run activity over a range, every bucket included, for heatmaps
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoActivity {
+    pub repo_id: String,
+    pub granularity: String,
+    pub from: String,
+    pub to: String,
+    pub buckets: Vec<ActivityBucket>,
+}
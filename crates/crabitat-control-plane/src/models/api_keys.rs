@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Every scope a key can hold.
+///
+/// - `console:read`: any `GET`
+/// - `console:write`: everything the console changes (missions, repos, settings, ...)
+/// - `crab:write`: the crab protocol (claiming tasks, heartbeats, reporting runs)
+/// - `admin`: everything, including `/v1/admin`
+///
+/// Write scopes also grant reads.
+pub const SCOPES: &[&str] = &["admin", "console:read", "console:write", "crab:write"];
+
+/// An API key as listed; the token itself is only shown once, on creation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key_id: String,
+    pub name: String,
+    /// First characters of the token, to tell keys apart
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<String>,
+}
+
+impl ApiKey {
+    /// Whether the key may call a route that needs `scope`
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|held| {
+            held == "admin"
+                || held == scope
+                || (scope == "console:read" && held.ends_with(":write"))
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+/// Response to creating a key; `token` is not stored and can't be fetched again
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub token: String,
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// Largest artifact accepted, after base64 decoding
+pub const MAX_ARTIFACT_BYTES: usize = 8 * 1024 * 1024;
+
+/// A file a crab attached to a run: a diff, test log, generated file, ...
+/// The content itself is served separately.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Artifact {
+    pub artifact_id: String,
+    pub run_id: String,
+    pub task_id: String,
+    pub name: String,
+    /// Free-form label such as `diff` or `test-log`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub content_type: String,
+    pub size: i64,
+    /// Hex-encoded SHA-256 of the stored content
+    pub sha256: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateArtifactRequest {
+    pub name: String,
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// Defaults to `text/plain` for UTF-8 content, else `application/octet-stream`
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Standard base64 with padding
+    pub content_base64: String,
+}
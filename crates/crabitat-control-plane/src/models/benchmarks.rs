@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/// Most issues one benchmark re-runs
+pub const MAX_BENCHMARK_ISSUES: usize = 50;
+
+/// Variant names, as stored in `missions.benchmark_variant`
+pub const BASELINE: &str = "baseline";
+pub const CANDIDATE: &str = "candidate";
+
+/// A workflow as one side of a benchmark runs it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkVariant {
+    pub workflow_name: String,
+    /// Refuse to start unless the workflow is at this version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flavor_id: Option<String>,
+}
+
+/// Body of `POST /v1/benchmarks`
+#[derive(Debug, Deserialize)]
+pub struct CreateBenchmarkRequest {
+    pub repo_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Cached issues of the repo, typically ones already solved
+    pub issue_numbers: Vec<i64>,
+    pub baseline: BenchmarkVariant,
+    pub candidate: BenchmarkVariant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Benchmark {
+    pub benchmark_id: String,
+    pub repo_id: String,
+    pub name: String,
+    pub baseline: BenchmarkVariant,
+    pub candidate: BenchmarkVariant,
+    pub issue_numbers: Vec<i64>,
+    pub created_at: String,
+}
+
+/// How one benchmark mission went
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkOutcome {
+    pub issue_number: i64,
+    /// `baseline` or `candidate`
+    pub variant: String,
+    pub mission_id: String,
+    pub status: String,
+    /// Average review score of the mission's scored runs
+    pub score: Option<f64>,
+    pub total_tokens: i64,
+    pub duration_ms: Option<i64>,
+}
+
+/// One variant's numbers across the benchmark's issues
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VariantStats {
+    pub missions: i64,
+    pub completed: i64,
+    /// Failed or cancelled
+    pub failed: i64,
+    /// Completed share of the finished missions
+    pub success_rate: Option<f64>,
+    pub avg_score: Option<f64>,
+    /// Averages over finished missions
+    pub avg_tokens: Option<f64>,
+    pub avg_duration_ms: Option<f64>,
+}
+
+/// `GET /v1/benchmarks/{benchmark_id}/report`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub benchmark: Benchmark,
+    /// Every mission has finished
+    pub finished: bool,
+    pub baseline: VariantStats,
+    pub candidate: VariantStats,
+    pub outcomes: Vec<BenchmarkOutcome>,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// Content-addressed chunk of prompt text, keyed by its SHA-256 hash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Blob {
+    pub hash: String,
+    pub content: String,
+    pub size: i64,
+    pub created_at: String,
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// A crab's claim on a mission worktree it keeps alive between steps
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BurrowLease {
+    pub mission_id: String,
+    pub worker_id: String,
+    pub path: String,
+    pub acquired_at: String,
+    pub renewed_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcquireBurrowRequest {
+    pub worker_id: String,
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BurrowQuery {
+    pub worker_id: String,
+}
+
+/// Warm-pool counters a crab last reported with its heartbeat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BurrowPoolStats {
+    pub worker_id: String,
+    /// Warm burrows ready to be claimed
+    pub ready: i64,
+    /// New mission burrows served from the pool since the crab started
+    pub hits: i64,
+    /// New mission burrows created cold because the pool was empty
+    pub misses: i64,
+    pub reported_at: String,
+}
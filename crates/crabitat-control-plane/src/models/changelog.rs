@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// One completed mission as it appears in release notes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    pub mission_id: String,
+    pub issue_number: i64,
+    pub issue_title: String,
+    pub workflow_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// The mission's pull request, once one was found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pr_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pr_number: Option<i64>,
+    pub completed_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Changelog {
+    pub repo_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    pub entries: Vec<ChangelogEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangelogQuery {
+    /// ISO-8601 timestamp; only missions completed after it are included
+    pub since: Option<String>,
+    /// `json` (default) or `markdown`
+    pub format: Option<String>,
+}
+
+impl Changelog {
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("## Changes\n");
+        if self.entries.is_empty() {
+            md.push_str("\n_No completed missions._\n");
+            return md;
+        }
+        for entry in &self.entries {
+            md.push_str(&format!(
+                "\n- {} (#{}",
+                entry.issue_title, entry.issue_number
+            ));
+            match (&entry.pr_url, entry.pr_number) {
+                (Some(url), Some(number)) => md.push_str(&format!(", PR [#{}]({})", number, url)),
+                (Some(url), None) => md.push_str(&format!(", PR {}", url)),
+                _ => {}
+            }
+            md.push(')');
+            if let Some(summary) = entry.summary.as_deref().map(str::trim)
+                && !summary.is_empty()
+            {
+                md.push_str(&format!("\n  {}", summary.replace('\n', "\n  ")));
+            }
+        }
+        md.push('\n');
+        md
+    }
+}
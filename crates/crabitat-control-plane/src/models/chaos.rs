@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// Fault injection rules; every fault is off at its default. Chances are
+/// between 0 and 1.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChaosRules {
+    /// Chance a crab-protocol request is dropped with a 503 before its handler runs
+    #[serde(default)]
+    pub drop_crab_requests: f64,
+    /// Delay before every request that writes, in milliseconds
+    #[serde(default)]
+    pub write_delay_ms: u64,
+    /// Chance a GitHub call fails as if GitHub were down; counts against the
+    /// circuit breaker like a real outage
+    #[serde(default)]
+    pub fail_github: f64,
+    /// Chance a claimed task is taken back right after the claim, as if its
+    /// crab had died
+    #[serde(default)]
+    pub kill_assignments: f64,
+    /// Seed for the dice, so a run can be replayed; random when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl ChaosRules {
+    pub const NONE: Self = Self {
+        drop_crab_requests: 0.0,
+        write_delay_ms: 0,
+        fail_github: 0.0,
+        kill_assignments: 0.0,
+        seed: None,
+    };
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, chance) in [
+            ("drop_crab_requests", self.drop_crab_requests),
+            ("fail_github", self.fail_github),
+            ("kill_assignments", self.kill_assignments),
+        ] {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Response of `/v1/admin/chaos`: the rules in force and the faults injected
+/// since they were set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChaosStatus {
+    pub rules: ChaosRules,
+    pub dropped_crab_requests: u64,
+    pub delayed_writes: u64,
+    pub failed_github_calls: u64,
+    pub killed_assignments: u64,
+}
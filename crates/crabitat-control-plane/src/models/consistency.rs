@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// One anomaly the consistency checker found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    /// `mission_status_drift`, `taskless_mission`, `task_of_unknown_crab`,
+    /// `orphaned_task` or `orphaned_run`
+    pub kind: String,
+    /// Id of the mission, task or run concerned
+    pub record_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<String>,
+    pub detail: String,
+    /// Set when the checker repaired it; the rest need a human
+    pub fixed: bool,
+}
+
+/// What one consistency check found (`GET /v1/admin/consistency`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    pub report_id: i64,
+    pub checked_at: String,
+    pub findings: Vec<Finding>,
+}
+
+impl ConsistencyReport {
+    /// Findings still needing a human
+    pub fn open(&self) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(|f| !f.fixed)
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+/// Identity handed to a crab by `POST /v1/crabs/register`. Once registered,
+/// requests naming the worker id must carry the token as `X-Crab-Token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrabRegistration {
+    pub worker_id: String,
+    pub token: String,
+}
+
+/// An agent CLI a crab can run, as reported when it polls for work
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Executor {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl Executor {
+    /// Parse a `name@version,name` inventory as sent in the `executors` query parameter
+    pub fn parse_list(s: &str) -> Vec<Executor> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| match item.split_once('@') {
+                Some((name, version)) => Executor {
+                    name: name.to_string(),
+                    version: Some(version.to_string()).filter(|v| !v.is_empty()),
+                },
+                None => Executor {
+                    name: item.to_string(),
+                    version: None,
+                },
+            })
+            .collect()
+    }
+
+    /// Whether this executor is `name` at `min_version` or newer. An executor
+    /// that didn't report its version never satisfies a minimum.
+    pub fn satisfies(&self, name: &str, min_version: Option<&str>) -> bool {
+        if self.name != name {
+            return false;
+        }
+        match (min_version, self.version.as_deref()) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(min), Some(have)) => version_parts(have) >= version_parts(min),
+        }
+    }
+}
+
+/// Numeric components of a version like `1.0.30` or `v2.1.0-beta`; parsing
+/// stops at the first component that doesn't start with a digit
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+/// Last executor inventory a crab reported, and what it is doing now
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrabExecutors {
+    pub worker_id: String,
+    pub executors: Vec<Executor>,
+    pub seen_at: String,
+    /// Role the crab polled with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// `idle`, `busy`, or `stuck`: busy, but its run has shown no output for
+    /// `stuck_task_secs`
+    #[serde(default = "idle")]
+    pub state: String,
+    /// The running task, when busy or stuck
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// Newest log output of the running task, or its claim if it has none yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_output_at: Option<String>,
+}
+
+fn idle() -> String {
+    "idle".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GuideQuery {
+    /// Role the guide is for; its prompt stack is described
+    pub role: Option<String>,
+}
+
+/// Getting a crab working on a repo, from the control plane's current state
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrabGuide {
+    pub repo_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    /// Address the guide was requested at, for the crab's `--api-url`
+    pub control_plane_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Files of the role's prompt stack, loaded as the crab's system context
+    #[serde(default)]
+    pub role_stack_files: Vec<String>,
+    /// Every role with a prompt stack, and how many active crabs poll as it
+    pub roles: Vec<RoleStaffing>,
+    /// Roles no crab that polled in the last hour has taken
+    pub unfilled_roles: Vec<String>,
+    /// Workflows of the repo's pending and running missions
+    pub workflows: Vec<GuideWorkflow>,
+    /// Command line that starts a crab for this guide
+    pub command: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleStaffing {
+    pub role: String,
+    pub active_crabs: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuideWorkflow {
+    pub name: String,
+    pub live_missions: i64,
+    pub steps: Vec<GuideStep>,
+}
+
+/// What a step asks of the crab that runs it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuideStep {
+    pub step_id: String,
+    pub prompt_file: String,
+    /// Runs against the checkout without a worktree of its own
+    #[serde(default)]
+    pub read_only: bool,
+    /// Agent CLI the crab must report, e.g. `claude>=1.0`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor: Option<String>,
+}
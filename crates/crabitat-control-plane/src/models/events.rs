@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// One change to a mission, task or run, as logged in `events`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsoleEvent {
+    /// Increases with every event; pass the last one seen as `after_seq`
+    pub seq: i64,
+    /// `mission_created`, `mission_updated`, `mission_deleted`,
+    /// `task_created`, `task_updated`, `run_created` or `run_updated`
+    pub kind: String,
+    /// Id of the mission, task or run
+    pub record_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<String>,
+    /// The record's status after the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Only events with a greater `seq`; omitted starts from now when
+    /// following, from the oldest kept event otherwise
+    #[serde(default)]
+    pub after_seq: Option<i64>,
+    /// Keep the response open as a server-sent event stream
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// Most events one listing returns; page with `after_seq`
+pub const MAX_EVENTS_PER_PAGE: i64 = 1000;
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Issue {
     pub repo_id: String,
     pub number: i64,
@@ -9,4 +9,32 @@ pub struct Issue {
     pub labels: Vec<String>,
     pub state: String,
     pub fetched_at: String,
+    /// Served from the cache because the repo's forge couldn't be reached
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+}
+
+/// Most issues on one page of `GET /v1/repos/{repo_id}/issues`
+pub const MAX_ISSUES_PER_PAGE: i64 = 100;
+
+/// Issues a page holds when only `page` is given
+pub const DEFAULT_ISSUES_PER_PAGE: i64 = 50;
+
+/// Response header carrying the `page` cursor of the next page
+pub const NEXT_PAGE_HEADER: &str = "x-next-page";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct IssuesQuery {
+    /// `open`, `closed` or `all` (the default)
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Comma-separated; an issue must carry every one (case-insensitive)
+    #[serde(default)]
+    pub labels: Option<String>,
+    /// Cursor from the previous page's `X-Next-Page` header
+    #[serde(default)]
+    pub page: Option<i64>,
+    /// Without `page` or `per_page`, every matching issue comes back at once
+    #[serde(default)]
+    pub per_page: Option<i64>,
 }
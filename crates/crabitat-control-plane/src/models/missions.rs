@@ -1,5 +1,10 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::models::repos::IssueCompletion;
+use crate::models::tasks::Task;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Mission {
     pub mission_id: String,
@@ -16,6 +21,172 @@ pub struct Mission {
     pub branch: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_worker_id: Option<String>,
+    /// Higher runs first; set explicitly or by the best matching trigger
+    #[serde(default)]
+    pub priority: i64,
+    /// Set when the mission was archived instead of deleted; archived missions are hidden and never scheduled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<String>,
+    /// Cap on this mission's running tasks, taken from its workflow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_tasks: Option<i64>,
+    /// Mission whose outputs were copied into this one's context at creation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_from_mission_id: Option<String>,
+    /// Why a pending mission isn't running yet; filled in by list endpoints, never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking_reason: Option<String>,
+    #[serde(default)]
+    pub rollup: MissionRollup,
+    /// Extra instructions from the operator, added to every step's prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Runs alone: starts only once no other mission in its repo is active,
+    /// and holds back the repo's other missions while active
+    #[serde(default)]
+    pub exclusive: bool,
+    /// Task list parsed from the issue body when the mission was created
+    #[serde(default)]
+    pub acceptance_criteria: Vec<AcceptanceCriterion>,
+    /// `version` of the workflow manifest the tasks were expanded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow_version: Option<String>,
+    /// Mission this one re-ran (`POST /v1/missions/{id}/replay`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_of_mission_id: Option<String>,
+    /// Set once a step's diff matched the repo's risk rules; a `risk-review`
+    /// approval then runs before the mission's next tier
+    #[serde(default)]
+    pub high_risk: bool,
+    /// Which rules matched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub risk_reasons: Vec<String>,
+    /// Pull request a step opened, taken from the first completed run that linked one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr_url: Option<String>,
+    /// Names or kinds of `context_from_mission_id`'s artifacts carried into
+    /// this mission's context
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_artifacts: Vec<String>,
+}
+
+/// One `- [ ]` item of the issue, with the latest verdict a step reported on it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AcceptanceCriterion {
+    /// 1-based position in the issue's task list
+    pub index: i64,
+    pub text: String,
+    /// Already ticked in the issue
+    #[serde(default)]
+    pub done: bool,
+    /// `pass` or `fail`, once a step has checked it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Task whose run reported `result`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+}
+
+/// Totals over a mission's (non-debug) runs, refreshed whenever a run is recorded
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MissionRollup {
+    pub total_tokens: i64,
+    /// Sum of run durations, i.e. agent time rather than wall-clock time
+    pub total_duration_ms: i64,
+    /// Runs recorded, including failed and retried attempts
+    pub attempts: i64,
+    /// When the first task was claimed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_started_at: Option<String>,
+    /// When the latest run finished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_finished_at: Option<String>,
+    /// Time from `first_started_at` to `last_finished_at`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wall_clock_ms: Option<i64>,
+}
+
+/// Filters of `GET /v1/missions`, across every repo unless `repo_id` is set
+#[derive(Debug, Default, Deserialize)]
+pub struct MissionListQuery {
+    /// Comma-separated statuses, e.g. `running,failed`
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub repo_id: Option<String>,
+    /// Case-insensitive text matched against `owner/name#number`, the
+    /// issue title, the branch and the workflow
+    #[serde(default)]
+    pub q: Option<String>,
+    /// Include archived missions
+    #[serde(default)]
+    pub archived: bool,
+    /// Cursor from the previous page's `X-Next-Cursor` header
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Without `cursor` or `limit`, every matching mission comes back at once
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// Most missions on one page of `GET /v1/missions`
+pub const MAX_MISSIONS_PER_PAGE: i64 = 200;
+
+/// Missions a page holds when only `cursor` is given
+pub const DEFAULT_MISSIONS_PER_PAGE: i64 = 50;
+
+/// Fleet-wide numbers (`GET /v1/missions/summary`); archived missions and
+/// deleted repos are left out
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FleetSummary {
+    pub repos: i64,
+    /// Repos with a pending or running mission
+    pub busy_repos: i64,
+    pub missions_by_status: BTreeMap<String, i64>,
+    /// Tasks of live missions by status: queued, running, awaiting_approval, ...
+    pub tasks_by_status: BTreeMap<String, i64>,
+    pub high_risk_missions: i64,
+    pub completed_last_24h: i64,
+    pub failed_last_24h: i64,
+    /// Crabs seen within the last hour
+    pub active_crabs: i64,
+    /// The same mission counts per repo, busiest first
+    pub by_repo: Vec<RepoMissionCounts>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RepoMissionCounts {
+    pub repo_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub missions_by_status: BTreeMap<String, i64>,
+}
+
+/// What a pending mission is waiting on
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QueueDiagnostic {
+    pub mission_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub priority: i64,
+    /// `next_in_queue`, `queued_behind`, `no_capable_crab`,
+    /// `rejected_by_all_crabs`, `awaiting_approval`, `repo_busy`, `stalled`,
+    /// `repo_deleted` or `no_tasks`
+    pub reason: String,
+    pub detail: String,
+    pub queued_tasks: i64,
+    pub blocked_tasks: i64,
+    /// Position of the mission's first queued task in the global queue (0 = next)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<i64>,
+    /// When a crab is expected to pick up the mission's first queued task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_start_at: Option<String>,
+    /// When the mission is expected to finish, judging by its repo's history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_finish_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,4 +204,334 @@ pub struct CreateMissionRequest {
     pub issue_number: i64,
     pub workflow_name: String,
     pub flavor_id: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i64>,
+    /// Earlier mission of the same repo whose step summaries and final
+    /// outputs seed this mission's first steps
+    #[serde(default)]
+    pub context_from_mission_id: Option<String>,
+    /// Run alone in the repo; the workflow can also ask for this
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+/// Body of `POST /v1/missions`
+#[derive(Debug, Deserialize)]
+pub struct CreateMissionBody {
+    #[serde(flatten)]
+    pub mission: CreateMissionRequest,
+    /// Artifacts of `context_from_mission_id` to seed the first steps with
+    /// too, matched by name or kind
+    #[serde(default)]
+    pub context_artifacts: Vec<String>,
+}
+
+/// Re-run a mission's issue and prompt on a (usually fixed) workflow
+#[derive(Debug, Default, Deserialize)]
+pub struct ReplayMissionRequest {
+    /// Defaults to the original mission's workflow
+    #[serde(default)]
+    pub workflow_name: Option<String>,
+    /// Defaults to the original's flavor when the workflow is unchanged
+    #[serde(default)]
+    pub flavor_id: Option<String>,
+    /// Refuse to replay unless the workflow is at this version
+    #[serde(default)]
+    pub workflow_version: Option<String>,
+}
+
+/// A mission next to its replays, for comparing outcomes and rollups
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayComparison {
+    pub original: Mission,
+    pub replays: Vec<Mission>,
+}
+
+/// Edits to a mission that hasn't started; omitted fields keep their value.
+/// Switching workflow without naming a flavor clears the flavor.
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateMissionRequest {
+    #[serde(default)]
+    pub workflow_name: Option<String>,
+    #[serde(default)]
+    pub flavor_id: Option<String>,
+    /// Extra instructions for every step; an empty string removes them
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+/// Rows that deleting a mission removes. Prompt blobs are content-addressed and shared, so they stay.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MissionDeletionReport {
+    pub mission_id: String,
+    pub tasks: i64,
+    pub runs: i64,
+    pub artifacts: i64,
+    /// Streamed log chunks
+    pub run_logs: i64,
+    pub state_history: i64,
+    pub burrow_leases: i64,
+    pub running_tasks: i64,
+}
+
+/// What cancelling a mission changed
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MissionCancellation {
+    pub mission_id: String,
+    /// Tasks moved to `skipped`
+    pub skipped_tasks: i64,
+    /// Of those, tasks a crab was running; the crab kills its agent when it notices
+    pub interrupted_tasks: i64,
+    /// Pending debug runs marked failed
+    pub cancelled_runs: i64,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DeleteMissionQuery {
+    /// Without this, nothing is changed and only the report is returned
+    #[serde(default)]
+    pub confirm: bool,
+    /// `delete` (default) removes all rows; `archive` hides the mission and keeps its history
+    pub mode: Option<String>,
+}
+
+/// A mission whose issue needs claiming or releasing on GitHub
+#[derive(Debug, PartialEq, Eq)]
+pub struct IssueClaimChange {
+    pub mission_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub issue_number: i64,
+    /// True to claim the issue, false to release it
+    pub claim: bool,
+}
+
+/// A mission whose status comment on its issue is out of date
+#[derive(Debug, Clone)]
+pub struct IssueCommentChange {
+    pub mission_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub issue_number: i64,
+    /// `running`, `pr_opened`, `completed`, `failed` or `cancelled`
+    pub state: String,
+    /// The comment to edit; `None` posts a new one
+    pub comment_id: Option<i64>,
+}
+
+/// A completed mission whose issue hasn't been dealt with yet
+#[derive(Debug, Clone)]
+pub struct IssueDoneChange {
+    pub mission_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub issue_number: i64,
+    /// The repo's setting; `None` leaves the issue as it is
+    pub completion: Option<IssueCompletion>,
+}
+
+/// A completed mission of a Jira repo whose ticket wasn't moved along yet
+#[derive(Debug, Clone)]
+pub struct JiraDoneChange {
+    pub mission_id: String,
+    pub repo_id: String,
+    pub issue_number: i64,
+}
+
+/// A running mission with a pull request whose reviews are watched
+#[derive(Debug, Clone)]
+pub struct PrReviewWatch {
+    pub mission_id: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub pr_url: String,
+}
+
+/// Feedback from a review requesting changes on a mission's pull request:
+/// the review's own body or one of its inline comments
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrReviewComment {
+    /// `review` or `inline`
+    pub kind: String,
+    /// GitHub's id for the review or comment
+    pub comment_id: i64,
+    pub author: String,
+    pub body: String,
+    /// File and line an inline comment is on
+    pub path: Option<String>,
+    pub line: Option<i64>,
+    pub url: String,
+    pub submitted_at: Option<String>,
+}
+
+/// A mission's tasks as a dependency graph. Tasks unblock tier by tier, so
+/// every task depends on all tasks of the tier before it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MissionGraph {
+    pub mission_id: String,
+    pub nodes: Vec<GraphNode>,
+    /// `(from, to)` task ids
+    pub edges: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub task_id: String,
+    pub step_id: String,
+    pub step_order: i64,
+    pub status: String,
+}
+
+/// Where a mission's time went, per task and along its critical path
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MissionTimings {
+    pub mission_id: String,
+    /// From the first task's creation to the last task finishing, or now
+    pub wall_ms: i64,
+    pub tasks: Vec<TaskTiming>,
+    /// Task ids, tier by tier, of the dependency chain that took longest
+    pub critical_path: Vec<String>,
+    pub critical_path_ms: i64,
+}
+
+/// A task's time split by phase, in milliseconds, from its status history.
+/// Time still being spent counts up to now.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskTiming {
+    pub task_id: String,
+    pub step_id: String,
+    pub step_order: i64,
+    pub status: String,
+    /// Waiting on upstream tasks
+    pub blocked_ms: i64,
+    /// Ready, waiting for a crab to claim it
+    pub queue_wait_ms: i64,
+    /// Claimed but outside the agent: burrow setup, prompt resolution, cleanup
+    pub setup_ms: i64,
+    /// Inside the agent, as reported by the crab's runs
+    pub model_ms: i64,
+    /// Waiting for a human to approve
+    pub approval_wait_ms: i64,
+    /// Runs recorded, retries included
+    pub runs: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    pub on_critical_path: bool,
+}
+
+impl TaskTiming {
+    /// Time from becoming ready to finishing; what the task adds to the critical path
+    pub fn active_ms(&self) -> i64 {
+        self.queue_wait_ms + self.setup_ms + self.model_ms + self.approval_wait_ms
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    /// `mermaid` (default), `dot` or `json`
+    pub format: Option<String>,
+}
+
+/// Fill and border colours per task status
+fn status_colors(status: &str) -> (&'static str, &'static str) {
+    match status {
+        "completed" => ("#bbf7d0", "#15803d"),
+        "running" => ("#bfdbfe", "#1d4ed8"),
+        "queued" => ("#fef08a", "#a16207"),
+        "failed" => ("#fecaca", "#b91c1c"),
+        "skipped" => ("#f3f4f6", "#9ca3af"),
+        _ => ("#e5e7eb", "#4b5563"),
+    }
+}
+
+impl MissionGraph {
+    pub fn from_tasks(mission_id: &str, tasks: &[Task]) -> Self {
+        let nodes: Vec<GraphNode> = tasks
+            .iter()
+            .map(|t| GraphNode {
+                task_id: t.task_id.clone(),
+                step_id: t.step_id.clone(),
+                step_order: t.step_order,
+                status: t.status.clone(),
+            })
+            .collect();
+
+        let mut edges = Vec::new();
+        for from in &nodes {
+            let next_tier = nodes
+                .iter()
+                .map(|n| n.step_order)
+                .filter(|&order| order > from.step_order)
+                .min();
+            for to in nodes.iter().filter(|n| Some(n.step_order) == next_tier) {
+                edges.push((from.task_id.clone(), to.task_id.clone()));
+            }
+        }
+
+        Self {
+            mission_id: mission_id.to_string(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// Short node ids for the text formats, in node order
+    fn node_key(&self, task_id: &str) -> String {
+        let idx = self.nodes.iter().position(|n| n.task_id == task_id);
+        format!("t{}", idx.unwrap_or_default())
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            out.push_str(&format!(
+                "    t{}[\"{}<br/>{}\"]\n",
+                idx,
+                node.step_id.replace('"', "#quot;"),
+                node.status
+            ));
+        }
+        for (from, to) in &self.edges {
+            out.push_str(&format!(
+                "    {} --> {}\n",
+                self.node_key(from),
+                self.node_key(to)
+            ));
+        }
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let (fill, stroke) = status_colors(&node.status);
+            out.push_str(&format!(
+                "    style t{} fill:{},stroke:{}\n",
+                idx, fill, stroke
+            ));
+        }
+        out
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out =
+            String::from("digraph mission {\n    node [shape=box, style=\"rounded,filled\"];\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let (fill, stroke) = status_colors(&node.status);
+            out.push_str(&format!(
+                "    t{} [label=\"{}\\n{}\", fillcolor=\"{}\", color=\"{}\"];\n",
+                idx,
+                node.step_id.replace('\\', "\\\\").replace('"', "\\\""),
+                node.status,
+                fill,
+                stroke
+            ));
+        }
+        for (from, to) in &self.edges {
+            out.push_str(&format!(
+                "    {} -> {};\n",
+                self.node_key(from),
+                self.node_key(to)
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
 }
@@ -1,9 +1,23 @@
+pub mod analytics;
+pub mod api_keys;
+pub mod artifacts;
+pub mod benchmarks;
+pub mod blobs;
+pub mod burrows;
+pub mod changelog;
+pub mod chaos;
+pub mod consistency;
+pub mod crabs;
+pub mod events;
 pub mod issues;
 pub mod missions;
 pub mod repos;
+pub mod run_logs;
+pub mod scheduler;
 pub mod settings;
 pub mod system;
 pub mod tasks;
+pub mod triggers;
 pub mod workflows;
 
 // Re-export only what is currently used elsewhere in the crate
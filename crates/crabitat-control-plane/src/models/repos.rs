@@ -12,6 +12,12 @@ pub struct Repo {
     pub updated_at: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_at: Option<String>,
+    /// Whether a Slack webhook is set; the URL itself is a secret and never returned
+    pub slack_notifications: bool,
+    /// `github`, `gitlab` or `gitea`; see `PUT /v1/repos/{repo_id}/forge`
+    pub forge: String,
+    /// A disposable copy benchmarks may run against; see `PUT /v1/repos/{repo_id}/sandbox`
+    pub sandbox: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,6 +28,73 @@ pub struct CreateRepoRequest {
     pub repo_url: Option<String>,
 }
 
+/// Body of `PUT /v1/repos/{repo_id}/slack`; `null` turns notifications off
+#[derive(Debug, Deserialize)]
+pub struct SlackSettingsRequest {
+    pub webhook_url: Option<String>,
+}
+
+/// Body of `PUT /v1/repos/{repo_id}/sandbox`
+#[derive(Debug, Deserialize)]
+pub struct SandboxRequest {
+    pub sandbox: bool,
+}
+
+/// Changes that make a mission high-risk (`PUT /v1/repos/{repo_id}/risk-rules`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RiskRules {
+    /// Globs over changed paths; `*` stays within a directory, `**` spans them
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Case-insensitive words searched for in the diff's added lines
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+/// What happens to a mission's issue once the mission completes
+/// (`PUT /v1/repos/{repo_id}/issue-completion`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IssueCompletion {
+    /// Close the issue as completed
+    #[serde(default)]
+    pub close: bool,
+    /// Label to add, e.g. `done-by-crabitat`; it must already exist in the repo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Forges a repo's issues can live on
+pub const FORGES: &[&str] = &["github", "gitlab", "gitea", "jira"];
+
+/// Where a repo's issues live (`PUT /v1/repos/{repo_id}/forge`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForgeConfig {
+    /// One of [`FORGES`]
+    pub kind: String,
+    /// GitLab instance, e.g. `https://gitlab.example.com`, gitlab.com when
+    /// unset; or the Gitea/Forgejo or Jira site, which must be given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Which Jira issues to queue and what to do with them; Jira only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jira: Option<JiraConfig>,
+}
+
+/// A repo's issue queue in Jira
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JiraConfig {
+    /// Project key, e.g. `PLAT`; issue `PLAT-42` is the repo's issue 42
+    pub project: String,
+    /// JQL narrowing the project's unresolved issues, e.g.
+    /// `labels = crabitat`, without `ORDER BY`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jql: Option<String>,
+    /// Transition, by name or target status, applied to the ticket once its
+    /// mission completes; none leaves the ticket as it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done_transition: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateRepoRequest {
     pub local_path: Option<String>,
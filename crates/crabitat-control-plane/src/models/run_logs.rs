@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// Streams a chunk can come from
+pub const RUN_LOG_STREAMS: [&str; 2] = ["stdout", "stderr"];
+
+/// One slice of an agent's stdout or stderr, as shipped by a crab while the
+/// agent runs. `seq` orders chunks within a run across both streams and makes
+/// resending a batch harmless.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunLogChunk {
+    pub seq: i64,
+    pub stream: String,
+    pub content: String,
+    /// Set by the control plane when the chunk is stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// Secrets scrubbed from the chunk; never read from crabs
+    #[serde(skip)]
+    pub redactions: i64,
+}
+
+/// Chunks arrive before the run is recorded, so they carry the task they belong to
+#[derive(Debug, Deserialize)]
+pub struct AppendRunLogsRequest {
+    pub task_id: String,
+    pub chunks: Vec<RunLogChunk>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RunLogsQuery {
+    /// Only chunks with a greater `seq`
+    #[serde(default)]
+    pub after: Option<i64>,
+    /// Keep the response open as a server-sent event stream until the run finishes
+    #[serde(default)]
+    pub follow: bool,
+}
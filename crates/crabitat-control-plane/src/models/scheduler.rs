@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// What the scheduler did with one queued task when a crab polled for work
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchedulerDecision {
+    pub id: i64,
+    /// Shared by every decision made for the same poll
+    pub tick_id: String,
+    /// Crab that polled; absent for anonymous peeks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<String>,
+    pub task_id: String,
+    pub mission_id: String,
+    /// `chosen` or `skipped`
+    pub decision: String,
+    /// Why a task was skipped: `outranked`, `rejected_by_worker`,
+    /// `executor_unmet`, `mission_at_concurrency_cap`, `repo_busy`,
+    /// `mission_archived` or `repo_deleted`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Recently polling crabs able to run the task that haven't rejected it
+    pub eligible_crabs: Vec<String>,
+    pub created_at: String,
+}
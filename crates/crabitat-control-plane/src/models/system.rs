@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::analytics::CrabDailyStats;
+use crate::models::missions::Mission;
+use crate::models::tasks::{Run, Task};
+
 #[derive(Debug, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct EnvironmentPath {
@@ -26,4 +30,75 @@ pub struct SystemStatus {
     pub gh_auth_status: bool,
     pub gh_version: Option<String>,
     pub gh_user: Option<String>,
+    /// Today's utilization per crab
+    #[serde(default)]
+    pub crabs: Vec<CrabDailyStats>,
+    #[serde(default)]
+    pub github: GithubHealth,
+    /// Set when GitHub calls authenticate as this GitHub App rather than gh's login
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_app_id: Option<u64>,
+}
+
+/// State of the circuit breaker around GitHub calls
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GithubHealth {
+    /// `ok`, `degraded` (recent failures, still trying), `down` (circuit
+    /// open: calls fail fast until `retry_after_secs` passes) or
+    /// `rate_limited` (open the same way, waiting out a GitHub rate limit)
+    pub status: String,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Which control plane runs a background job; with several sharing a database
+/// only the holder of a live lease acts, the rest wait for it to lapse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobLease {
+    pub job: String,
+    /// Instance id of the control plane holding the lease
+    pub holder: String,
+    pub acquired_at: String,
+    pub renewed_at: String,
+    pub expires_at: String,
+    /// Whether the control plane answering holds it
+    #[serde(default)]
+    pub held_here: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusDeltaQuery {
+    /// Last `seq` the client saw; only later changes are returned
+    pub since_seq: Option<i64>,
+    /// Only changes after this Unix time in milliseconds
+    pub since_ms: Option<i64>,
+    /// Only records of this repo's missions
+    pub repo_id: Option<String>,
+    /// Comma-separated subset of `missions`, `tasks` and `runs`; the rest
+    /// come back empty. Defaults to all three.
+    pub kinds: Option<String>,
+}
+
+/// What `StatusDeltaQuery::kinds` accepts
+pub const STATUS_KINDS: &[&str] = &["missions", "tasks", "runs"];
+
+/// Missions, tasks and runs changed since the client last looked. Without
+/// `since_seq`/`since_ms` it is a full snapshot; pass the returned `seq` back
+/// to get only what changed after it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusDelta {
+    /// Newest change this response covers
+    pub seq: i64,
+    /// Server time in Unix milliseconds, for clients tracking `since_ms`
+    pub as_of_ms: i64,
+    pub missions: Vec<Mission>,
+    pub tasks: Vec<Task>,
+    pub runs: Vec<Run>,
+    /// Missions deleted since the cursor; not narrowed by `repo_id` since the
+    /// rows are gone, but ids are unique so clients can drop unknown ones
+    #[serde(default)]
+    pub deleted_mission_ids: Vec<String>,
 }
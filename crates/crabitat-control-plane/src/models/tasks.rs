@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::crabs::Executor;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Task {
     pub task_id: String,
@@ -13,6 +15,254 @@ pub struct Task {
     pub created_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<String>,
+    /// Ordered blob hashes whose concatenation equals `assembled_prompt`
+    #[serde(default)]
+    pub prompt_chunks: Vec<String>,
+    #[serde(default)]
+    pub step_config: StepConfig,
+}
+
+/// Workflow step settings captured on the task at expansion time, so later
+/// edits to the workflow TOML don't change the behaviour of in-flight missions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepConfig {
+    /// Minimum review score required for the task to count as completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<i64>,
+    /// Step to send the mission back to when the quality gate fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_fail: Option<String>,
+    /// Analysis-only step: runs in the existing checkout, no worktree or branch
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Workflows a classify step may switch the mission to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub switch_to: Vec<String>,
+    /// Kill the agent after this many seconds instead of the crab's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Executor a crab must report to be handed the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor_min_version: Option<String>,
+    /// Step whose output the task fans out over when its tier is promoted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub for_each: Option<String>,
+    /// The item this copy of a `for_each` step works on, once expanded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+    /// Human approval gate: waits in `awaiting_approval` instead of going to a crab
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub approval: bool,
+    /// Most bytes of upstream output injected at promotion; unset falls back
+    /// to the `context_max_bytes` setting, then no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_max_bytes: Option<usize>,
+    /// Network the agent should use, as a proxy hint: `none`, `restricted` or
+    /// `full` (unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// `tail`, `head` or `summarize`; see `mission_service::fit_context`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_strategy: Option<String>,
+    /// CI gate: once completed, waits in `awaiting_checks` for the mission's
+    /// pull request checks
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_checks: bool,
+}
+
+impl StepConfig {
+    /// Status a task of this step takes when its tier is ready to run
+    pub fn ready_status(&self) -> &'static str {
+        if self.approval {
+            "awaiting_approval"
+        } else {
+            "queued"
+        }
+    }
+
+    /// Why a crab with these executors can't run the task, or `None` if it can
+    pub fn executor_unmet(&self, executors: &[Executor]) -> Option<String> {
+        let name = self.executor.as_deref()?;
+        let min_version = self.executor_min_version.as_deref();
+        if executors.iter().any(|e| e.satisfies(name, min_version)) {
+            return None;
+        }
+        Some(match min_version {
+            Some(min) => format!("needs executor {} >= {}", name, min),
+            None => format!("needs executor {}", name),
+        })
+    }
+}
+
+/// Allowed task status moves. Staying in the same status is always allowed and is a no-op.
+pub const TASK_TRANSITIONS: &[(&str, &str)] = &[
+    ("blocked", "queued"),
+    ("queued", "running"),
+    ("queued", "blocked"),
+    ("running", "completed"),
+    ("running", "failed"),
+    ("running", "queued"),
+    // Quality gate: fail the review, rewind the pipeline, requeue the fix step
+    ("completed", "failed"),
+    ("completed", "blocked"),
+    ("completed", "queued"),
+    ("failed", "queued"),
+    // Approval steps wait for a human instead of a crab
+    ("blocked", "awaiting_approval"),
+    ("awaiting_approval", "completed"),
+    ("awaiting_approval", "failed"),
+    ("awaiting_approval", "blocked"),
+    ("failed", "awaiting_approval"),
+    // CI gate: a completed step waits for its pull request's checks
+    ("completed", "awaiting_checks"),
+    ("awaiting_checks", "completed"),
+    ("awaiting_checks", "failed"),
+    ("awaiting_checks", "blocked"),
+    // Mission cancellation
+    ("blocked", "skipped"),
+    ("queued", "skipped"),
+    ("running", "skipped"),
+    ("awaiting_approval", "skipped"),
+    ("awaiting_checks", "skipped"),
+];
+
+pub fn can_transition(from: &str, to: &str) -> bool {
+    from == to || TASK_TRANSITIONS.contains(&(from, to))
+}
+
+/// A task in `awaiting_checks`, with where its mission's pull request is
+#[derive(Debug)]
+pub struct ChecksWait {
+    pub task_id: String,
+    pub repo_owner: String,
+    pub forge: String,
+    pub pr_url: Option<String>,
+}
+
+/// One CI check on a pull request, as `gh pr checks` reports it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckRun {
+    pub name: String,
+    /// `pass`, `fail`, `pending`, `skipping` or `cancel`
+    pub bucket: String,
+    #[serde(default)]
+    pub link: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A running task and the last sign of progress from its run
+#[derive(Debug)]
+pub struct RunningOutput {
+    pub task: Task,
+    pub worker_id: Option<String>,
+    /// Newest log chunk since the claim, or the claim itself
+    pub last_output_at: String,
+    pub silent_secs: i64,
+}
+
+/// One recorded status change of a task
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskTransition {
+    pub task_id: String,
+    pub from_status: String,
+    pub to_status: String,
+    /// Who made the change: a worker id, or `cascade`, `quality-gate`, `retry`, `api`
+    pub actor: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub created_at: String,
+}
+
+/// A note sent to the agent working on a task
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskMessage {
+    pub message_id: String,
+    pub task_id: String,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Crab that was running the task when the message was posted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<String>,
+    pub created_at: String,
+    /// When a heartbeat handed the message to the crab
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<String>,
+}
+
+/// Operator guidance added to a task's context before it runs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskNote {
+    pub note_id: String,
+    pub task_id: String,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddContextRequest {
+    pub body: String,
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+/// A crab turning down a task it claimed but cannot serve
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskRejection {
+    pub task_id: String,
+    pub worker_id: String,
+    pub reason: String,
+    pub created_at: String,
+}
+
+/// Rejection of a running task by the crab holding it
+#[derive(Debug, Deserialize)]
+pub struct RejectTaskRequest {
+    /// Crab handing the task back; it must hold the task
+    #[serde(default)]
+    pub worker_id: Option<String>,
+    /// Why the crab can't run the task, e.g. a missing tool or a full disk
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RejectApprovalRequest {
+    /// Who turned the step down, recorded as the transition actor
+    #[serde(default)]
+    pub reviewer: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ApproveTaskRequest {
+    /// Who approved, recorded as the transition actor
+    #[serde(default)]
+    pub approver: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendMessageRequest {
+    pub body: String,
+    #[serde(default)]
+    pub sender: Option<String>,
+}
+
+/// Insert parameters for a new task
+pub struct NewTask<'a> {
+    pub mission_id: &'a str,
+    pub step_id: &'a str,
+    pub step_order: i64,
+    pub assembled_prompt: &'a str,
+    pub max_retries: i64,
+    pub status: &'a str,
+    pub step_config: StepConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,23 +284,128 @@ pub struct Run {
     pub task_id: String,
     pub status: String,
     pub logs: Option<String>,
+    /// The summary, or only its excerpt when `summary_hash` is set
     pub summary: Option<String>,
+    /// Blob hash of the full summary, when it was too long to keep inline;
+    /// `GET /v1/runs/{run_id}` returns it in `summary`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary_hash: Option<String>,
     pub duration_ms: Option<i64>,
     pub tokens_used: Option<i64>,
     pub started_at: String,
     pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<String>,
+    /// Debug runs are diagnostic re-executions; they never cascade or count in analytics
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub debug: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_override: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assigned_worker_id: Option<String>,
+    /// How the crab prepared the checkout: `worktree`, `read_only` or `detached`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burrow_mode: Option<String>,
+    /// Where on the crab the checkout was, after resolving its burrow root and template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burrow_path: Option<String>,
+    /// Workflow a classify step selected for the rest of the mission
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_workflow: Option<String>,
+    /// Blob hash of the crab's per-task working log (`GET /v1/blobs/{hash}`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_log_hash: Option<String>,
+    /// Secrets redacted from this run's output; non-zero flags the run for review
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub redactions: i64,
+    /// Why a failed run failed when the crab can tell, e.g. `timeout`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_kind: Option<String>,
+    /// Verdicts the run gave on the mission's acceptance criteria
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub criteria: Vec<CriterionResult>,
+    /// Network policy the crab hinted to the agent through its proxy
+    /// variables, e.g. `none (proxy hint)`; not an enforced restriction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+}
+
+/// A step's verdict on one acceptance criterion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CriterionResult {
+    pub index: i64,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+fn is_zero(n: &i64) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DebugRunRequest {
+    /// Replaces the task's assembled prompt verbatim
+    pub prompt: Option<String>,
+    /// Reassembles the step prompt with this `{{context}}` (ignored if `prompt` is set)
+    pub context: Option<String>,
+    /// Only this crab may pick the debug run up
+    pub worker_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Default)]
 pub struct RetryTaskRequest {
+    /// Replaces the step's `{{context}}` outright
     pub context: Option<String>,
+    /// Human guidance appended to the upstream context the step originally received
+    #[serde(default)]
+    pub guidance: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 pub struct CreateRunRequest {
+    /// Id the crab streamed the run's logs under; generated when absent
+    #[serde(default)]
+    pub run_id: Option<String>,
     pub status: String,
     pub logs: Option<String>,
     pub summary: Option<String>,
     pub duration_ms: Option<i64>,
     pub tokens_used: Option<i64>,
+    /// Numeric quality score reported by review steps
+    #[serde(default)]
+    pub score: Option<i64>,
+    #[serde(default)]
+    pub worker_id: Option<String>,
+    /// Completes this pending debug run instead of recording a new run
+    #[serde(default)]
+    pub debug_run_id: Option<String>,
+    #[serde(default)]
+    pub burrow_mode: Option<String>,
+    #[serde(default)]
+    pub burrow_path: Option<String>,
+    #[serde(default)]
+    pub next_workflow: Option<String>,
+    /// Crab working log (setup, executor invocation, cleanup); stored as a blob
+    #[serde(default)]
+    pub work_log: Option<String>,
+    #[serde(default)]
+    pub failure_kind: Option<String>,
+    /// Per-criterion verdicts from `CRITERION <n>: PASS|FAIL` lines
+    #[serde(default)]
+    pub criteria: Vec<CriterionResult>,
+    /// Paths the step changed, checked against the repo's risk rules
+    #[serde(default)]
+    pub changed_files: Vec<String>,
+    /// The step's diff, possibly truncated; only its added lines are scanned
+    /// for risk keywords and it is not stored
+    #[serde(default)]
+    pub diff: Option<String>,
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Set by the control plane after scanning the output; never read from crabs
+    #[serde(skip)]
+    pub redactions: i64,
 }
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+/// Conditions an event must satisfy; every field that is set has to match
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TriggerMatcher {
+    /// Case-insensitive substring of the issue title or body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    /// Issue label (exact match)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Routes matching repo issues to a workflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trigger {
+    pub trigger_id: String,
+    pub repo_id: String,
+    pub event_type: String,
+    pub matcher: TriggerMatcher,
+    pub workflow_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flavor_id: Option<String>,
+    pub priority: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTriggerRequest {
+    pub repo_id: String,
+    pub event_type: String,
+    #[serde(default)]
+    pub matcher: TriggerMatcher,
+    pub workflow_name: String,
+    pub flavor_id: Option<String>,
+    #[serde(default)]
+    pub priority: i64,
+}
+
+/// Normalized repo event used for trigger evaluation
+#[derive(Debug, Default, Deserialize)]
+pub struct TriggerEvent {
+    pub repo_id: String,
+    pub event_type: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TriggerListQuery {
+    pub repo_id: Option<String>,
+}
+
+/// Every mission works on an issue, so issues are the only events that
+/// can start one
+pub const TRIGGER_EVENT_TYPES: &[&str] = &["issue"];
+
+impl Trigger {
+    pub fn matches(&self, event: &TriggerEvent) -> bool {
+        if self.repo_id != event.repo_id || self.event_type != event.event_type {
+            return false;
+        }
+
+        if let Some(keyword) = &self.matcher.keyword {
+            let needle = keyword.to_lowercase();
+            let in_title = event.title.to_lowercase().contains(&needle);
+            let in_body = event
+                .body
+                .as_deref()
+                .is_some_and(|b| b.to_lowercase().contains(&needle));
+            if !in_title && !in_body {
+                return false;
+            }
+        }
+
+        if let Some(label) = &self.matcher.label
+            && !event.labels.iter().any(|l| l == label)
+        {
+            return false;
+        }
+
+        true
+    }
+}
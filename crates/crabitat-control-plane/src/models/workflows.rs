@@ -1,10 +1,15 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 /// Represents a workflow defined in a TOML file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkflowFile {
     pub workflow: WorkflowInfo,
     pub steps: Vec<WorkflowStepFile>,
+    /// Manifest file the workflow was loaded from; unset for stored workflows
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,15 +17,161 @@ pub struct WorkflowInfo {
     pub name: String,
     pub description: String,
     pub version: Option<String>,
+    /// Most tasks of one mission that may run at once; unset falls back to
+    /// the `max_concurrent_tasks` setting, then no cap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_tasks: Option<i64>,
+    /// Missions on this workflow run alone in their repo (e.g. ones touching
+    /// migrations)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclusive: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WorkflowStepFile {
     pub id: String,
     pub prompt_file: String,
     pub depends_on: Option<Vec<String>>,
     pub on_fail: Option<String>,
     pub max_retries: Option<u32>,
+    /// Built-in step behaviour; `release-notes` injects the repo changelog as context
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub step_type: Option<String>,
+    /// Analysis-only step that needs no writable worktree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// Quality gate: runs scoring below this are routed to `on_fail`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<i64>,
+    /// Cheaper workflows a `classify` step may hand the rest of the mission to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub switch_to: Option<Vec<String>>,
+    /// Agent execution limit for this step, overriding the crab's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Same limit in minutes; `timeout_secs` wins when both are set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_minutes: Option<u64>,
+    /// Agent CLI the step needs, e.g. `claude`; only crabs reporting it get the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor: Option<String>,
+    /// Oldest version of `executor` the step works with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor_min_version: Option<String>,
+    /// Earlier step whose JSON output lists items; the step runs once per item,
+    /// with the item in `{{item}}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub for_each: Option<String>,
+    /// Most bytes of upstream output the step gets as context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_max_bytes: Option<usize>,
+    /// Same budget in tokens, counted as four bytes each; the smaller wins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_max_tokens: Option<usize>,
+    /// How context over budget is cut: `tail` (default), `head` or `summarize`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_strategy: Option<String>,
+    /// Ask the step for a PASS/FAIL verdict on each of the issue's acceptance criteria
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_criteria: Option<bool>,
+    /// What the agent should reach: `none` (only its own model API),
+    /// `restricted` (also the control plane and the repo's git host) or `full`
+    /// (default). Crabs pass this on as a proxy hint only, which tools that
+    /// ignore proxy variables are not held to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Hold the completed step until the mission's pull request passes its
+    /// required CI checks; failing checks are routed to `on_fail`. Checks are
+    /// only read from GitHub, so repos on other forges can't run such a step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_checks: Option<bool>,
+}
+
+/// Ways of fitting upstream output into a context budget
+pub const CONTEXT_STRATEGIES: &[&str] = &["tail", "head", "summarize"];
+
+/// Network policies a step may ask for
+pub const NETWORK_POLICIES: &[&str] = &["none", "restricted", "full"];
+
+/// Bytes per token when a budget is given in tokens
+pub const BYTES_PER_TOKEN: usize = 4;
+
+impl WorkflowStepFile {
+    /// The step's execution limit in seconds, from either timeout field
+    pub fn timeout(&self) -> Option<u64> {
+        self.timeout_secs
+            .or(self.timeout_minutes.map(|minutes| minutes * 60))
+    }
+
+    /// The step's context budget in bytes, from either budget field
+    pub fn context_budget(&self) -> Option<usize> {
+        let tokens = self.context_max_tokens.map(|t| t * BYTES_PER_TOKEN);
+        match (self.context_max_bytes, tokens) {
+            (Some(bytes), Some(tokens)) => Some(bytes.min(tokens)),
+            (bytes, tokens) => bytes.or(tokens),
+        }
+    }
+}
+
+/// One problem found in a workflow manifest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkflowIssue {
+    /// `parse`, `empty`, `duplicate_step`, `unknown_dependency`, `unknown_on_fail`,
+    /// `unknown_for_each`, `invalid_for_each`, `invalid_executor`,
+    /// `invalid_context_strategy`, `invalid_network`, `cycle` or
+    /// `missing_prompt`
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_id: Option<String>,
+    pub message: String,
+}
+
+impl WorkflowIssue {
+    pub fn new(kind: &str, step_id: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            step_id: step_id.map(String::from),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateWorkflowRequest {
+    /// Workflow TOML, as it would be saved under `workflows/`
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowValidation {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub issues: Vec<WorkflowIssue>,
+}
+
+/// Workflow created through the API and kept in SQLite rather than on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredWorkflow {
+    pub name: String,
+    /// Workflow TOML, in the same format as the files under `workflows/`
+    pub content: String,
+    /// Prompt files owned by the workflow, keyed by their path relative to
+    /// prompts_root
+    pub prompts: BTreeMap<String, String>,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+/// Body of `POST /v1/workflows` and `PUT /v1/workflows/{name}`
+#[derive(Debug, Deserialize)]
+pub struct SaveWorkflowRequest {
+    /// Workflow TOML; on update its name must match the path
+    pub content: String,
+    /// Prompt files to store with the workflow; replaces the previous set on update
+    #[serde(default)]
+    pub prompts: BTreeMap<String, String>,
 }
 
 /// DB-backed flavor for a workflow
@@ -42,7 +193,27 @@ pub struct WorkflowDetail {
     pub description: String,
     pub version: Option<String>,
     pub steps: Vec<WorkflowStepFile>,
+    /// One per step, in `steps` order
+    pub previews: Vec<StepPreview>,
     pub flavors: Vec<WorkflowFlavor>,
+    /// Created through the API rather than loaded from prompts_root
+    pub stored: bool,
+    /// Manifest file the workflow was loaded from, in whichever prompts
+    /// directory won; unset for stored workflows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// How a step would be expanded into a task, for showing the DAG before a
+/// mission is launched
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StepPreview {
+    pub step_id: String,
+    /// Tier the task would get; steps sharing an order run in parallel
+    pub step_order: usize,
+    /// The step's prompt file, with `{{mission}}` and `{{context}}` left unrendered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
 }
 
 /// Simplified view for listing all workflows
@@ -52,6 +223,21 @@ pub struct WorkflowSummary {
     pub description: String,
     pub step_count: usize,
     pub flavor_count: usize,
+    /// Created through the API rather than loaded from prompts_root
+    pub stored: bool,
+    /// Manifest file the workflow was loaded from; unset for stored workflows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Role prompt stack assembled from {prompts_root}/roles/{role}/*.md
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromptStack {
+    pub role: String,
+    pub files: Vec<String>,
+    pub content: String,
+    /// SHA-256 of `content`; crabs refetch the stack when this changes
+    pub hash: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,3 +245,30 @@ pub struct CreateFlavorRequest {
     pub name: String,
     pub prompt_paths: Vec<String>,
 }
+
+/// Workflow pack installed from git into {prompts_root}/packs/{name}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowPack {
+    pub name: String,
+    pub git_url: String,
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// Commit the installed snapshot was taken from
+    pub commit_sha: String,
+    /// Names of the workflows the pack provides
+    pub workflows: Vec<String>,
+    pub installed_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstallPackRequest {
+    pub git_url: String,
+    /// Branch, tag or commit; defaults to the remote HEAD
+    #[serde(rename = "ref", default)]
+    pub git_ref: Option<String>,
+    /// Defaults to the repository name from `git_url`
+    #[serde(default)]
+    pub name: Option<String>,
+}
@@ -0,0 +1,132 @@
+//! Slack notifications.
+//!
+//! Each repo may set a Slack incoming webhook (`PUT /v1/repos/{repo_id}/slack`).
+//! A background job tails the `events` log and posts there when one of the
+//! repo's missions starts, completes or fails, or when a task starts waiting
+//! for a human; messages about high-risk missions say why they were flagged. Messages link back to the console at the `public_url`
+//! setting, falling back to `console_url`; with neither set they carry no link.
+
+use rusqlite::Connection;
+use serde_json::json;
+
+use crate::console::CONSOLE_URL_SETTING;
+use crate::db::events as events_db;
+use crate::db::missions as missions_db;
+use crate::db::repos as repos_db;
+use crate::db::settings as settings_db;
+use crate::db::tasks as tasks_db;
+use crate::models::events::ConsoleEvent;
+use crate::models::missions::Mission;
+
+/// Settings key for the address people reach the console at, for links
+pub const PUBLIC_URL_SETTING: &str = "public_url";
+
+/// One message ready to post
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub webhook_url: String,
+    pub text: String,
+}
+
+/// Where links to the console point: the `public_url` setting, falling back
+/// to `console_url`
+pub fn base_url(conn: &Connection) -> Option<String> {
+    [PUBLIC_URL_SETTING, CONSOLE_URL_SETTING]
+        .iter()
+        .find_map(|key| settings_db::get(conn, key).ok().flatten())
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Notifications for up to `limit` events after `after_seq`, with the seq of
+/// the last event read (`after_seq` when there were none)
+pub fn pending(
+    conn: &Connection,
+    after_seq: i64,
+    limit: i64,
+) -> Result<(i64, Vec<Notification>), String> {
+    let events = events_db::list_after(conn, after_seq, limit)?;
+    let last_seq = events.last().map_or(after_seq, |e| e.seq);
+    let base_url = base_url(conn);
+
+    let mut out = Vec::new();
+    for event in &events {
+        let Some(mission_id) = &event.mission_id else {
+            continue;
+        };
+        let Some(mission) = missions_db::get_mission(conn, mission_id)? else {
+            continue;
+        };
+        // Archiving logs a mission update too; archived missions stay quiet
+        if mission.archived_at.is_some() {
+            continue;
+        }
+        let Some(webhook_url) = repos_db::slack_webhook(conn, &mission.repo_id)? else {
+            continue;
+        };
+        let step_id = if event.kind == "task_updated" {
+            tasks_db::get_task(conn, &event.record_id)?.map(|t| t.step_id)
+        } else {
+            None
+        };
+        if let Some(text) = message(event, &mission, step_id.as_deref(), base_url.as_deref()) {
+            out.push(Notification { webhook_url, text });
+        }
+    }
+    Ok((last_seq, out))
+}
+
+/// The Slack text for `event`, if it is one people are told about
+fn message(
+    event: &ConsoleEvent,
+    mission: &Mission,
+    step_id: Option<&str>,
+    base_url: Option<&str>,
+) -> Option<String> {
+    let issue = format!(
+        "{}/{}#{}",
+        mission.repo_owner, mission.repo_name, mission.issue_number
+    );
+    let label = match base_url {
+        Some(base) => format!("<{}/missions/{}|{}>", base, mission.mission_id, issue),
+        None => issue,
+    };
+    let status = event.status.as_deref()?;
+    let text = match (event.kind.as_str(), status) {
+        ("mission_updated", "running") => Some(format!(
+            ":rocket: Mission started: {} ({})",
+            label, mission.workflow_name
+        )),
+        ("mission_updated", "completed") => {
+            Some(format!(":white_check_mark: Mission completed: {}", label))
+        }
+        ("mission_updated", "failed") => Some(format!(":x: Mission failed: {}", label)),
+        ("task_updated", "awaiting_approval") => Some(format!(
+            ":raised_hand: Task `{}` is waiting for approval: {}",
+            step_id.unwrap_or("?"),
+            label
+        )),
+        _ => None,
+    }?;
+    if !mission.high_risk {
+        return Some(text);
+    }
+    Some(format!(
+        "{}\n:warning: High risk: {}",
+        text,
+        mission.risk_reasons.join("; ")
+    ))
+}
+
+/// Post `notification` to its webhook
+pub async fn post(client: &reqwest::Client, notification: &Notification) -> Result<(), String> {
+    let resp = client
+        .post(&notification.webhook_url)
+        .json(&json!({ "text": notification.text }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("Slack answered HTTP {}", resp.status()));
+    }
+    Ok(())
+}
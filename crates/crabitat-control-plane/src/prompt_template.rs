@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::models::missions::AcceptanceCriterion;
+use crate::workflow_registry::WorkflowRegistry;
+
+/// Settings with this prefix are available to prompts as `vars.<rest>`;
+/// other settings (webhook secrets and the like) are never exposed
+pub const TEMPLATE_VAR_PREFIX: &str = "template_var_";
+
+/// Variables available to every prompt template
+#[derive(Debug, Default, Serialize)]
+pub struct PromptVars {
+    /// Issue title and body; the original `{{mission}}` placeholder
+    pub mission: String,
+    /// Upstream output and notes injected at promotion
+    pub context: String,
+    /// Item of a `for_each` expansion, empty otherwise
+    pub item: String,
+    /// Left as a placeholder for the crab, which fills it in late
+    pub worktree_path: &'static str,
+    pub mission_id: String,
+    pub branch: String,
+    pub workflow: String,
+    pub step_id: String,
+    pub issue: IssueVars,
+    pub repo: RepoVars,
+    pub vars: BTreeMap<String, String>,
+    /// Completed steps of the mission so far, by step id
+    pub steps: BTreeMap<String, StepResult>,
+    /// The issue's task list, with any verdicts reported so far
+    pub criteria: Vec<AcceptanceCriterion>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IssueVars {
+    pub number: i64,
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RepoVars {
+    pub owner: String,
+    pub name: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct StepResult {
+    pub status: String,
+    pub summary: String,
+    pub output: String,
+}
+
+/// Every prompt file under the prompts root, compiled as Tera templates so
+/// prompts can use loops, conditionals and `{% include "other.md" %}`.
+///
+/// Files that don't parse as templates (a stray `{{` in a code sample, say)
+/// fall back to plain `{{mission}}`/`{{context}}`/`{{item}}` substitution.
+pub struct PromptTemplates {
+    tera: Tera,
+}
+
+impl PromptTemplates {
+    pub fn load(registry: &WorkflowRegistry) -> Self {
+        let sources: Vec<(String, String)> = registry
+            .list_prompt_files()
+            .into_iter()
+            .filter_map(|path| {
+                let source = registry.read_prompt(&path).ok()?;
+                Some((path, source))
+            })
+            .collect();
+
+        let mut tera = Tera::default();
+        if tera.add_raw_templates(sources.clone()).is_err() {
+            // One bad file fails the batch; add the rest one by one
+            tera = Tera::default();
+            for (path, source) in sources {
+                if let Err(e) = tera.add_raw_template(&path, &source) {
+                    tracing::debug!("prompt {} is not a valid template: {}", path, e);
+                }
+            }
+        }
+        Self { tera }
+    }
+
+    /// Render the prompt file `path`, whose raw content is `source`
+    pub fn render(&self, path: &str, source: &str, vars: &PromptVars) -> String {
+        if self.tera.get_template_names().any(|name| name == path) {
+            match Context::from_serialize(vars).and_then(|ctx| self.tera.render(path, &ctx)) {
+                Ok(rendered) => return rendered,
+                Err(e) => tracing::warn!(
+                    "rendering prompt {} failed, using plain placeholders: {}",
+                    path,
+                    error_chain(&e)
+                ),
+            }
+        }
+        source
+            .replace("{{mission}}", &vars.mission)
+            .replace("{{item}}", &vars.item)
+            .replace("{{context}}", &vars.context)
+    }
+}
+
+/// Tera nests the useful message (e.g. the undefined variable) in its sources
+fn error_chain(e: &tera::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(inner) = source {
+        message.push_str(": ");
+        message.push_str(&inner.to_string());
+        source = inner.source();
+    }
+    message
+}
@@ -0,0 +1,108 @@
+use regex_automata::meta::Regex;
+use rusqlite::Connection;
+
+use crate::db::settings as settings_db;
+
+/// Built-in secret patterns: (rule name, regex)
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    ("github-token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+    ("github-pat", r"\bgithub_pat_[A-Za-z0-9_]{22,}\b"),
+    ("aws-access-key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("slack-token", r"\bxox[aboprs]-[A-Za-z0-9-]{10,}\b"),
+    ("api-key", r"\bsk-[A-Za-z0-9_-]{20,}\b"),
+    (
+        "private-key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    ),
+    (
+        "credential-assignment",
+        r#"(?i)\b(?:password|passwd|secret|api[_-]?key|access[_-]?token)\s*[:=]\s*["']?[^\s"']{8,}"#,
+    ),
+];
+
+/// How much of the previous chunk of a stream is rescanned with the next one,
+/// enough to hold a private key block split across the two
+const CARRY_BYTES: usize = 4096;
+
+/// Settings key holding extra deny patterns, one regex per line
+pub const DENY_PATTERNS_SETTING: &str = "redaction_deny_patterns";
+
+/// Scans agent output for secrets before it is persisted
+pub struct Scanner {
+    rules: Vec<(String, Regex)>,
+}
+
+impl Scanner {
+    /// Scanner with only the built-in rules
+    pub fn builtin() -> Self {
+        let rules = BUILTIN_RULES
+            .iter()
+            .map(|(name, pattern)| {
+                let re = Regex::new(pattern).expect("built-in redaction rule must compile");
+                (name.to_string(), re)
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Built-in rules plus the deny patterns configured in settings.
+    /// Patterns that fail to compile are logged and skipped.
+    pub fn from_settings(conn: &Connection) -> Self {
+        let mut scanner = Self::builtin();
+        if let Ok(Some(patterns)) = settings_db::get(conn, DENY_PATTERNS_SETTING) {
+            for pattern in patterns.lines().map(str::trim).filter(|p| !p.is_empty()) {
+                match Regex::new(pattern) {
+                    Ok(re) => scanner.rules.push(("deny-pattern".to_string(), re)),
+                    Err(e) => tracing::warn!("skipping invalid deny pattern {:?}: {}", pattern, e),
+                }
+            }
+        }
+        scanner
+    }
+
+    /// Replace every match with `[REDACTED:<rule>]`, returning the text and the match count
+    pub fn redact(&self, text: &str) -> (String, usize) {
+        let mut out = text.to_string();
+        let mut count = 0;
+        for (name, re) in &self.rules {
+            let matches: Vec<_> = re.find_iter(&out).map(|m| m.range()).collect();
+            if matches.is_empty() {
+                continue;
+            }
+            count += matches.len();
+            let marker = format!("[REDACTED:{}]", name);
+            for range in matches.into_iter().rev() {
+                out.replace_range(range, &marker);
+            }
+        }
+        (out, count)
+    }
+
+    /// Redact `text` streamed right after `previous`, catching secrets split
+    /// between the two. Returns how much of `previous` to keep, the redacted
+    /// text and the match count; a match reaching back into `previous` moves
+    /// the rest of it into the returned text.
+    pub fn redact_continuation(&self, previous: &str, text: &str) -> (usize, String, usize) {
+        let mut start = previous.len().saturating_sub(CARRY_BYTES);
+        while !previous.is_char_boundary(start) {
+            start += 1;
+        }
+        let tail = &previous[start..];
+        let (redacted, count) = self.redact(&format!("{}{}", tail, text));
+        match redacted.strip_prefix(tail) {
+            Some(rest) => (previous.len(), rest.to_string(), count),
+            None => (start, redacted, count),
+        }
+    }
+
+    /// Redact an optional field in place, returning the match count
+    pub fn redact_field(&self, field: &mut Option<String>) -> usize {
+        match field.as_deref().map(|text| self.redact(text)) {
+            Some((redacted, count)) if count > 0 => {
+                *field = Some(redacted);
+                count
+            }
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Mission risk scoring.
+//!
+//! Each repo may keep risk rules (`PUT /v1/repos/{repo_id}/risk-rules`):
+//! globs over changed paths and keywords. When a step that changes the
+//! checkout reports its diff, the rules are checked against it and a match
+//! flags the mission high-risk. Once that step completes, a `risk-review`
+//! approval task is inserted ahead of the mission's remaining steps, so a
+//! human signs off before anything builds on the change.
+
+use regex_automata::meta::Regex;
+use rusqlite::Connection;
+
+use crate::db::missions as missions_db;
+use crate::db::repos as repos_db;
+use crate::db::tasks as tasks_db;
+use crate::models::repos::RiskRules;
+use crate::models::tasks::{CreateRunRequest, NewTask, StepConfig, Task};
+
+/// Step id of the approval task inserted for high-risk missions
+pub const RISK_REVIEW_STEP: &str = "risk-review";
+
+/// Regex for a path glob: `**` spans directories, `*` and `?` stay within
+/// one, and a glob without a `/` matches the file name at any depth
+pub fn glob_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    if !glob.contains('/') {
+        re.push_str("(?:.*/)?");
+    }
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c if "\\.+()|[]{}^$#&-~".contains(c) => {
+                re.push('\\');
+                re.push(c);
+            }
+            c => re.push(c),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Check rules before they are stored
+pub fn validate(rules: &RiskRules) -> Result<(), String> {
+    for glob in &rules.paths {
+        if glob.trim().is_empty() {
+            return Err("path globs must not be empty".to_string());
+        }
+        Regex::new(&glob_regex(glob.trim()))
+            .map_err(|e| format!("invalid path glob {:?}: {}", glob, e))?;
+    }
+    if rules.keywords.iter().any(|k| k.trim().is_empty()) {
+        return Err("keywords must not be empty".to_string());
+    }
+    Ok(())
+}
+
+/// Why a change to `changed_files` with `diff` is risky under `rules`; empty
+/// when it isn't. Keywords are only looked for in the lines the diff adds.
+pub fn assess(rules: &RiskRules, changed_files: &[String], diff: Option<&str>) -> Vec<String> {
+    let mut reasons = Vec::new();
+    for glob in &rules.paths {
+        let glob = glob.trim();
+        let Ok(re) = Regex::new(&glob_regex(glob)) else {
+            continue;
+        };
+        if let Some(path) = changed_files.iter().find(|p| re.is_match(p.as_str())) {
+            reasons.push(format!("{} matches {}", path, glob));
+        }
+    }
+
+    let added = diff
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| l.starts_with('+') && !l.starts_with("+++"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .to_lowercase();
+    for keyword in &rules.keywords {
+        let keyword = keyword.trim();
+        if added.contains(&keyword.to_lowercase()) {
+            reasons.push(format!("diff adds {:?}", keyword));
+        }
+    }
+    reasons
+}
+
+/// Check the diff reported with a run of `task_id` against its repo's rules,
+/// flagging the mission on a match. Returns the reasons found.
+pub fn record_run(
+    conn: &Connection,
+    task_id: &str,
+    req: &CreateRunRequest,
+) -> Result<Vec<String>, String> {
+    if req.changed_files.is_empty() && req.diff.is_none() {
+        return Ok(Vec::new());
+    }
+    let task = tasks_db::get_task(conn, task_id)?.ok_or("task not found")?;
+    let mission = missions_db::get_mission(conn, &task.mission_id)?
+        .ok_or_else(|| format!("mission not found: {}", task.mission_id))?;
+    let Some(rules) = repos_db::risk_rules(conn, &mission.repo_id)? else {
+        return Ok(Vec::new());
+    };
+    let reasons = assess(&rules, &req.changed_files, req.diff.as_deref());
+    if !reasons.is_empty() {
+        missions_db::flag_high_risk(conn, &mission.mission_id, &reasons)?;
+    }
+    Ok(reasons)
+}
+
+/// After `task` completed: if its mission is high-risk and has steps left,
+/// insert the `risk-review` approval right after `task`'s tier. Each mission
+/// gets at most one. Returns whether it was inserted.
+pub fn insert_review_gate(conn: &Connection, task: &Task) -> Result<bool, String> {
+    let mission = missions_db::get_mission(conn, &task.mission_id)?
+        .ok_or_else(|| format!("mission not found: {}", task.mission_id))?;
+    if !mission.high_risk {
+        return Ok(false);
+    }
+    let tasks = tasks_db::list_tasks_for_mission(conn, &mission.mission_id)?;
+    if tasks.iter().any(|t| t.step_id == RISK_REVIEW_STEP)
+        || !tasks.iter().any(|t| t.step_order > task.step_order)
+    {
+        return Ok(false);
+    }
+
+    let prompt = format!(
+        "High-risk change; approve to let the mission continue.\n\n{}",
+        mission
+            .risk_reasons
+            .iter()
+            .map(|r| format!("- {}", r))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tasks_db::shift_tasks_after(&tx, &mission.mission_id, task.step_order)?;
+    tasks_db::insert_new_task(
+        &tx,
+        &NewTask {
+            mission_id: &mission.mission_id,
+            step_id: RISK_REVIEW_STEP,
+            step_order: task.step_order + 1,
+            assembled_prompt: &prompt,
+            max_retries: 0,
+            status: "blocked",
+            step_config: StepConfig {
+                approval: true,
+                ..Default::default()
+            },
+        },
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(true)
+}
@@ -1,10 +1,16 @@
-use axum::Router;
-use axum::routing::{delete, get, post};
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{delete, get, post, put};
+use axum::{Router, middleware};
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
 use crate::AppState;
+use crate::auth;
+use crate::chaos;
+use crate::console;
 use crate::handlers;
+use crate::metrics;
+use crate::models::artifacts::MAX_ARTIFACT_BYTES;
 
 pub fn create_router(state: AppState) -> Router {
     Router::new()
@@ -13,9 +19,33 @@ pub fn create_router(state: AppState) -> Router {
         .nest("/v1/prompts", prompts_routes())
         .nest("/v1/missions", missions_routes())
         .nest("/v1/tasks", tasks_routes())
+        .nest("/v1/runs", runs_routes())
+        .nest("/v1/blobs", blobs_routes())
+        .nest("/v1/burrows", burrows_routes())
+        .nest("/v1/crabs", crabs_routes())
+        .nest("/v1/analytics", analytics_routes())
+        .nest("/v1/benchmarks", benchmarks_routes())
         .nest("/v1/github", github_routes())
         .nest("/v1/settings", settings_routes())
         .nest("/v1/system", system_routes())
+        .nest("/v1/admin", admin_routes())
+        .route("/v1/metrics", get(handlers::system::get_metrics))
+        .route("/v1/status", get(handlers::system::get_status_delta))
+        .route("/v1/events", get(handlers::events::list_events))
+        .fallback(console::fallback)
+        .route_layer(middleware::from_fn(chaos::inject))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_crab_token,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track,
+        ))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
         .with_state(state)
@@ -34,6 +64,10 @@ fn repos_routes() -> Router<AppState> {
                 .put(handlers::repos::update_repo),
         )
         .route("/{repo_id}/issues", get(handlers::issues::list_repo_issues))
+        .route(
+            "/{repo_id}/issues/{number}",
+            get(handlers::issues::get_repo_issue),
+        )
         .route(
             "/{repo_id}/issues/refresh",
             post(handlers::issues::refresh_repo_issues),
@@ -42,12 +76,52 @@ fn repos_routes() -> Router<AppState> {
             "/{repo_id}/missions",
             get(handlers::missions::list_repo_missions),
         )
+        .route("/{repo_id}/changelog", get(handlers::repos::get_changelog))
+        .route("/{repo_id}/activity", get(handlers::repos::get_activity))
+        .route("/{repo_id}/guide", get(handlers::crabs::get_repo_guide))
+        .route(
+            "/{repo_id}/slack",
+            put(handlers::repos::update_slack_settings),
+        )
+        .route("/{repo_id}/sandbox", put(handlers::repos::update_sandbox))
+        .route(
+            "/{repo_id}/risk-rules",
+            get(handlers::repos::get_risk_rules).put(handlers::repos::update_risk_rules),
+        )
+        .route(
+            "/{repo_id}/issue-completion",
+            get(handlers::repos::get_issue_completion)
+                .put(handlers::repos::update_issue_completion),
+        )
+        .route(
+            "/{repo_id}/forge",
+            get(handlers::repos::get_forge).put(handlers::repos::update_forge),
+        )
 }
 
 fn workflows_routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(handlers::workflows::list_all_workflows))
-        .route("/{name}", get(handlers::workflows::get_workflow))
+        .route(
+            "/",
+            get(handlers::workflows::list_all_workflows).post(handlers::workflows::create_workflow),
+        )
+        .route("/install", post(handlers::workflows::install_pack))
+        .route(
+            "/validate",
+            post(handlers::workflows::validate_workflow_manifest),
+        )
+        .route("/packs", get(handlers::workflows::list_packs))
+        .route("/packs/{name}", delete(handlers::workflows::uninstall_pack))
+        .route(
+            "/packs/{name}/update",
+            post(handlers::workflows::update_pack),
+        )
+        .route(
+            "/{name}",
+            get(handlers::workflows::get_workflow)
+                .put(handlers::workflows::update_workflow)
+                .delete(handlers::workflows::delete_workflow),
+        )
         .route("/{name}/flavors", post(handlers::workflows::create_flavor))
         .route(
             "/{name}/flavors/{flavor_id}",
@@ -59,6 +133,7 @@ fn prompts_routes() -> Router<AppState> {
     Router::new()
         .route("/files", get(handlers::workflows::list_prompt_files))
         .route("/content", post(handlers::workflows::get_prompts_content))
+        .route("/stacks/{role}", get(handlers::workflows::get_prompt_stack))
 }
 
 fn missions_routes() -> Router<AppState> {
@@ -67,37 +142,198 @@ fn missions_routes() -> Router<AppState> {
             "/",
             post(handlers::missions::create_mission).get(handlers::missions::list_missions),
         )
-        .route("/{mission_id}", get(handlers::missions::get_mission))
+        .route("/summary", get(handlers::missions::fleet_summary))
+        .route(
+            "/queue/diagnostics",
+            get(handlers::missions::queue_diagnostics),
+        )
+        .route(
+            "/{mission_id}",
+            get(handlers::missions::get_mission)
+                .patch(handlers::missions::update_mission)
+                .delete(handlers::missions::delete_mission),
+        )
+        .route(
+            "/{mission_id}/cancel",
+            post(handlers::missions::cancel_mission),
+        )
+        .route(
+            "/{mission_id}/replay",
+            post(handlers::missions::replay_mission),
+        )
+        .route(
+            "/{mission_id}/artifacts",
+            get(handlers::artifacts::list_mission_artifacts),
+        )
+        .route(
+            "/{mission_id}/graph",
+            get(handlers::missions::get_mission_graph),
+        )
+        .route(
+            "/{mission_id}/timings",
+            get(handlers::missions::get_mission_timings),
+        )
+        .route(
+            "/{mission_id}/burrow",
+            get(handlers::burrows::get_burrow)
+                .post(handlers::burrows::acquire_burrow)
+                .delete(handlers::burrows::release_burrow),
+        )
 }
 
 fn tasks_routes() -> Router<AppState> {
     Router::new()
         .route("/next", get(handlers::tasks::get_next_task))
+        .route("/claim", post(handlers::tasks::claim_task))
+        .route("/{task_id}", get(handlers::tasks::get_task))
         .route(
             "/{task_id}/status",
             post(handlers::tasks::update_task_status),
         )
+        .route(
+            "/{task_id}/transitions",
+            get(handlers::tasks::list_transitions),
+        )
+        .route("/{task_id}/heartbeat", post(handlers::tasks::heartbeat))
+        .route(
+            "/{task_id}/messages",
+            get(handlers::tasks::list_messages).post(handlers::tasks::send_message),
+        )
+        .route(
+            "/{task_id}/context",
+            get(handlers::tasks::list_context).post(handlers::tasks::add_context),
+        )
         .route("/{task_id}/retry", post(handlers::tasks::retry_task))
+        .route("/{task_id}/approve", post(handlers::tasks::approve_task))
+        .route(
+            "/{task_id}/approval/reject",
+            post(handlers::tasks::reject_approval),
+        )
+        .route("/{task_id}/reject", post(handlers::tasks::reject_task))
+        .route(
+            "/{task_id}/rejections",
+            get(handlers::tasks::list_rejections),
+        )
         .route("/{task_id}/runs", post(handlers::tasks::create_run))
+        .route(
+            "/{task_id}/debug-run",
+            post(handlers::tasks::create_debug_run),
+        )
+}
+
+fn runs_routes() -> Router<AppState> {
+    Router::new()
+        .route("/{run_id}", get(handlers::tasks::get_run))
+        .route(
+            "/{run_id}/artifacts",
+            post(handlers::artifacts::create_artifact)
+                .get(handlers::artifacts::list_run_artifacts)
+                // base64 inflates content by a third; leave room for the JSON around it
+                .layer(DefaultBodyLimit::max(
+                    MAX_ARTIFACT_BYTES / 3 * 4 + 64 * 1024,
+                )),
+        )
+        .route(
+            "/{run_id}/artifacts/{artifact_id}",
+            get(handlers::artifacts::get_artifact_content),
+        )
+        .route(
+            "/{run_id}/logs",
+            post(handlers::run_logs::append_run_logs).get(handlers::run_logs::get_run_logs),
+        )
+}
+
+fn blobs_routes() -> Router<AppState> {
+    Router::new().route("/{hash}", get(handlers::blobs::get_blob))
+}
+
+fn burrows_routes() -> Router<AppState> {
+    Router::new()
+        .route("/stale", get(handlers::burrows::list_stale_burrows))
+        .route("/pools", get(handlers::burrows::list_pools))
+}
+
+fn analytics_routes() -> Router<AppState> {
+    Router::new()
+        .route("/scores", get(handlers::analytics::get_score_trends))
+        .route("/crabs", get(handlers::analytics::get_crab_stats))
+        .route(
+            "/replays/{mission_id}",
+            get(handlers::analytics::get_replay_comparison),
+        )
+}
+
+fn benchmarks_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/",
+            post(handlers::benchmarks::create_benchmark).get(handlers::benchmarks::list_benchmarks),
+        )
+        .route(
+            "/{benchmark_id}/report",
+            get(handlers::benchmarks::get_benchmark_report),
+        )
 }
 
 fn github_routes() -> Router<AppState> {
-    Router::new().route("/repos", get(handlers::github::search_repos))
+    Router::new()
+        .route("/repos", get(handlers::github::search_repos))
+        .route("/webhook", post(handlers::github::receive_webhook))
 }
 
 fn settings_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(handlers::settings::list_settings))
+        .route(
+            "/triggers",
+            get(handlers::triggers::list_triggers).post(handlers::triggers::create_trigger),
+        )
+        .route(
+            "/triggers/evaluate",
+            post(handlers::triggers::evaluate_triggers),
+        )
+        .route(
+            "/triggers/{trigger_id}",
+            delete(handlers::triggers::delete_trigger),
+        )
         .route(
             "/{key}",
             get(handlers::settings::get_setting).post(handlers::settings::update_setting),
         )
 }
 
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/scheduler/decisions",
+            get(handlers::scheduler::list_decisions),
+        )
+        .route(
+            "/keys",
+            post(handlers::api_keys::create_api_key).get(handlers::api_keys::list_api_keys),
+        )
+        .route("/keys/{key_id}", delete(handlers::api_keys::revoke_api_key))
+        .route(
+            "/chaos",
+            get(handlers::chaos::get_chaos).post(handlers::chaos::set_chaos),
+        )
+        .route(
+            "/consistency",
+            get(handlers::consistency::get_consistency)
+                .post(handlers::consistency::run_consistency),
+        )
+}
+
+fn crabs_routes() -> Router<AppState> {
+    Router::new().route("/register", post(handlers::crabs::register_crab))
+}
+
 fn system_routes() -> Router<AppState> {
     Router::new()
         .route("/status", get(handlers::system::get_status))
         .route("/dirs", get(handlers::system::list_dirs))
+        .route("/executors", get(handlers::system::list_executors))
+        .route("/jobs", get(handlers::system::list_job_leases))
         .route(
             "/env-path/{env}/{type}/{name}",
             get(handlers::system::get_environment_path)
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::workflow_registry::{WorkflowRegistry, parse_workflow, validate_workflow};
+
+#[derive(Debug)]
+pub enum PackError {
+    InvalidName(String),
+    InvalidSource(String),
+    Git(String),
+    Invalid(String),
+    Io(String),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid pack name: {}", name),
+            Self::InvalidSource(e) => write!(f, "invalid pack source: {}", e),
+            Self::Git(e) => write!(f, "git: {}", e),
+            Self::Invalid(e) => write!(f, "invalid pack: {}", e),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A pack checked out, validated and swapped into place
+#[derive(Debug)]
+pub struct InstalledPack {
+    pub commit_sha: String,
+    pub workflows: Vec<String>,
+}
+
+/// Pack name from an explicit name or the last segment of the git URL
+pub fn pack_name(git_url: &str, name: Option<&str>) -> Result<String, PackError> {
+    let name = match name {
+        Some(n) => n.to_string(),
+        None => git_url
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or("")
+            .trim_end_matches(".git")
+            .to_string(),
+    };
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(name)
+    } else {
+        Err(PackError::InvalidName(name))
+    }
+}
+
+/// First symlink under `dir`, skipping git's own directory. A link could point
+/// a prompt at any file on the host, so packs may not contain any.
+fn find_symlink(dir: &Path) -> Result<Option<PathBuf>, String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_symlink() {
+            return Ok(Some(path));
+        }
+        if file_type.is_dir()
+            && entry.file_name() != ".git"
+            && let Some(link) = find_symlink(&path)?
+        {
+            return Ok(Some(link));
+        }
+    }
+    Ok(None)
+}
+
+/// Check a checked-out pack: it contains no symlinks, every `workflows/*.toml`
+/// parses, has a valid step DAG and only references prompt files inside the
+/// pack, and no workflow name clashes with one provided outside this pack.
+pub fn validate_pack(
+    pack_dir: &Path,
+    taken_names: &HashSet<String>,
+) -> Result<Vec<String>, String> {
+    if let Some(link) = find_symlink(pack_dir)? {
+        let rel = link.strip_prefix(pack_dir).unwrap_or(&link);
+        return Err(format!("{}: symlinks are not allowed", rel.display()));
+    }
+    let workflows_dir = pack_dir.join("workflows");
+    let entries =
+        fs::read_dir(&workflows_dir).map_err(|_| "no workflows/ directory".to_string())?;
+
+    let mut names = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+            continue;
+        }
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", file, e))?;
+        let wf = parse_workflow(&content).map_err(|e| format!("{}: {}", file, e.message))?;
+
+        for step in &wf.steps {
+            let rel = Path::new(&step.prompt_file);
+            if rel.components().any(|c| !matches!(c, Component::Normal(_))) {
+                return Err(format!(
+                    "{}: step {} prompt_file must be a relative path inside the pack",
+                    file, step.id
+                ));
+            }
+        }
+        if let Some(issue) = validate_workflow(&wf, pack_dir).into_iter().next() {
+            return Err(format!("{}: {}", file, issue.message));
+        }
+
+        if taken_names.contains(&wf.workflow.name) || names.contains(&wf.workflow.name) {
+            return Err(format!(
+                "{}: duplicate workflow name {}",
+                file, wf.workflow.name
+            ));
+        }
+        names.push(wf.workflow.name);
+    }
+
+    if names.is_empty() {
+        return Err("pack contains no workflows".to_string());
+    }
+    names.sort();
+    Ok(names)
+}
+
+async fn git(args: &[&str], dir: Option<&Path>) -> Result<String, PackError> {
+    let mut cmd = tokio::process::Command::new("git");
+    cmd.args(args).env("GIT_TERMINAL_PROMPT", "0");
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| PackError::Git(e.to_string()))?;
+    if !output.status.success() {
+        return Err(PackError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Clone `git_url` at `git_ref`, validate it and install it as
+/// {prompts_root}/packs/{name}, replacing any previous version of the pack.
+///
+/// The checkout is staged in a hidden directory so a failed clone or
+/// validation leaves the installed version untouched.
+pub async fn install(
+    prompts_root: &Path,
+    name: &str,
+    git_url: &str,
+    git_ref: Option<&str>,
+) -> Result<InstalledPack, PackError> {
+    // Both end up on git's command line, where a leading dash is an option
+    if git_url.is_empty() || git_url.starts_with('-') {
+        return Err(PackError::InvalidSource(format!("git URL {}", git_url)));
+    }
+    if let Some(git_ref) = git_ref.filter(|r| r.is_empty() || r.starts_with('-')) {
+        return Err(PackError::InvalidSource(format!("git ref {}", git_ref)));
+    }
+    let packs_dir = prompts_root.join("packs");
+    fs::create_dir_all(&packs_dir).map_err(|e| PackError::Io(e.to_string()))?;
+    let staging = packs_dir.join(format!(".staging-{}", uuid::Uuid::new_v4()));
+
+    let result = stage(&staging, prompts_root, name, git_url, git_ref).await;
+    let installed = match result {
+        Ok(installed) => installed,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+
+    let target = packs_dir.join(name);
+    let retired = packs_dir.join(format!(".retired-{}", uuid::Uuid::new_v4()));
+    if target.exists() {
+        fs::rename(&target, &retired).map_err(|e| PackError::Io(e.to_string()))?;
+    }
+    fs::rename(&staging, &target).map_err(|e| PackError::Io(e.to_string()))?;
+    let _ = fs::remove_dir_all(&retired);
+
+    Ok(installed)
+}
+
+async fn stage(
+    staging: &Path,
+    prompts_root: &Path,
+    name: &str,
+    git_url: &str,
+    git_ref: Option<&str>,
+) -> Result<InstalledPack, PackError> {
+    let staging_str = staging.to_string_lossy();
+    git(&["clone", "--quiet", "--", git_url, &staging_str], None).await?;
+    if let Some(git_ref) = git_ref {
+        git(
+            &["checkout", "--quiet", "--detach", git_ref, "--"],
+            Some(staging),
+        )
+        .await?;
+    }
+    let commit_sha = git(&["rev-parse", "HEAD"], Some(staging)).await?;
+
+    // Names owned by anything other than the pack being replaced
+    let pack_prefix = format!("packs/{}/", name);
+    let taken: HashSet<String> = WorkflowRegistry::new(prompts_root)
+        .list_workflows()
+        .into_iter()
+        .filter(|wf| {
+            !wf.steps
+                .iter()
+                .any(|s| s.prompt_file.starts_with(&pack_prefix))
+        })
+        .map(|wf| wf.workflow.name)
+        .collect();
+    let workflows = validate_pack(staging, &taken).map_err(PackError::Invalid)?;
+
+    // Installed packs are snapshots; the commit is tracked in the database
+    fs::remove_dir_all(staging.join(".git")).map_err(|e| PackError::Io(e.to_string()))?;
+
+    Ok(InstalledPack {
+        commit_sha,
+        workflows,
+    })
+}
+
+/// Remove an installed pack's files
+pub fn uninstall(prompts_root: &Path, name: &str) -> Result<(), PackError> {
+    let dir = prompts_root.join("packs").join(name);
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(|e| PackError::Io(e.to_string()))?;
+    }
+    Ok(())
+}
@@ -1,47 +1,220 @@
-use crate::models::workflows::WorkflowFile;
+use crate::db::blobs::hash_content;
+use crate::db::settings as settings_db;
+use crate::db::workflows as wf_db;
+use crate::models::workflows::{
+    CONTEXT_STRATEGIES, NETWORK_POLICIES, PromptStack, StoredWorkflow, WorkflowFile, WorkflowIssue,
+    WorkflowStepFile,
+};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+
+/// Setting listing prompts directories stacked over prompts_root, separated
+/// like `PATH`; later directories override earlier ones
+pub const PROMPTS_PATHS_SETTING: &str = "prompts_paths";
+
+/// The override directories in the `prompts_paths` setting, in order
+pub fn prompts_paths(conn: &Connection) -> Result<Vec<PathBuf>, String> {
+    let value = settings_db::get(conn, PROMPTS_PATHS_SETTING).map_err(|e| e.to_string())?;
+    Ok(value
+        .map(|v| {
+            std::env::split_paths(&v)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default())
+}
 
 pub struct WorkflowRegistry {
-    prompts_root: PathBuf,
+    /// prompts_root first, then the override directories; later ones win
+    roots: Vec<PathBuf>,
+    stored: Vec<StoredWorkflow>,
 }
 
 impl WorkflowRegistry {
+    /// Registry over the workflow files under prompts_root only
     pub fn new<P: AsRef<Path>>(prompts_root: P) -> Self {
         Self {
-            prompts_root: prompts_root.as_ref().to_path_buf(),
+            roots: vec![prompts_root.as_ref().to_path_buf()],
+            stored: Vec::new(),
+        }
+    }
+
+    /// Registry over the files under prompts_root and the `prompts_paths`
+    /// directories, plus the workflows stored in SQLite
+    pub fn open<P: AsRef<Path>>(conn: &Connection, prompts_root: P) -> Result<Self, String> {
+        Ok(Self::new(prompts_root)
+            .with_overrides(prompts_paths(conn)?)
+            .with_stored(wf_db::list_stored(conn)?))
+    }
+
+    /// Stack directories over the current ones. A workflow or prompt file in
+    /// a later directory replaces the one with the same name or path before it.
+    pub fn with_overrides(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.roots.extend(dirs);
+        self
+    }
+
+    /// Replace the stored workflows, e.g. to check one before it is saved
+    pub fn with_stored(mut self, stored: Vec<StoredWorkflow>) -> Self {
+        self.stored = stored;
+        self
+    }
+
+    /// Whether `name` was created through the API
+    pub fn is_stored(&self, name: &str) -> bool {
+        self.stored.iter().any(|wf| wf.name == name)
+    }
+
+    fn stored_prompt(&self, rel_path: &str) -> Option<&str> {
+        self.stored
+            .iter()
+            .find_map(|wf| wf.prompts.get(rel_path))
+            .map(String::as_str)
+    }
+
+    /// The file a prompt path resolves to: the one in the last directory that
+    /// has it. Paths that could leave the directories resolve to nothing.
+    pub fn prompt_file(&self, rel_path: &str) -> Option<PathBuf> {
+        if !inside_root(rel_path) {
+            return None;
         }
+        self.roots
+            .iter()
+            .rev()
+            .map(|root| root.join(rel_path))
+            .find(|path| path.is_file())
+    }
+
+    /// Whether a prompt exists, either stored or as a file in a prompts directory
+    pub fn has_prompt(&self, rel_path: &str) -> bool {
+        self.stored_prompt(rel_path).is_some() || self.prompt_file(rel_path).is_some()
     }
 
-    /// List all workflows in {prompts_root}/workflows/*.toml
+    /// Validate a workflow, resolving prompt files against stored prompts and
+    /// the prompts directories
+    pub fn validate(&self, wf: &WorkflowFile) -> Vec<WorkflowIssue> {
+        check_workflow(wf, |path| self.has_prompt(path))
+    }
+
+    /// The base prompts directory, where packs are installed
+    pub fn prompts_root(&self) -> &Path {
+        &self.roots[0]
+    }
+
+    /// List all workflows in {dir}/workflows/*.toml, in installed packs at
+    /// {dir}/packs/{pack}/workflows/*.toml for each prompts directory, and
+    /// stored in SQLite. A workflow in an override directory replaces the one
+    /// of the same name before it. Workflows that fail validation are logged
+    /// and left out, so no mission is built from them; a stored workflow
+    /// never shadows a file.
     pub fn list_workflows(&self) -> Vec<WorkflowFile> {
-        let workflows_dir = self.prompts_root.join("workflows");
-        let mut workflows = Vec::new();
+        let mut workflows: Vec<WorkflowFile> = Vec::new();
+        for (path, loaded) in self.load_all() {
+            match loaded {
+                Ok(wf) => match workflows
+                    .iter_mut()
+                    .find(|w| w.workflow.name == wf.workflow.name)
+                {
+                    Some(existing) => {
+                        tracing::debug!(
+                            "workflow {} from {:?} overrides {:?}",
+                            wf.workflow.name,
+                            path,
+                            existing.source
+                        );
+                        *existing = wf;
+                    }
+                    None => workflows.push(wf),
+                },
+                Err(issues) => {
+                    for issue in issues {
+                        tracing::error!("skipping invalid workflow {:?}: {}", path, issue.message);
+                    }
+                }
+            }
+        }
 
-        if let Ok(entries) = fs::read_dir(workflows_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("toml") {
-                    match fs::read_to_string(&path) {
-                        Ok(content) => match toml::from_str::<WorkflowFile>(&content) {
-                            Ok(wf) => workflows.push(wf),
-                            Err(e) => tracing::error!(
-                                "failed to parse workflow TOML at {:?}: {}",
-                                path,
-                                e
-                            ),
-                        },
-                        Err(e) => {
-                            tracing::error!("failed to read workflow file at {:?}: {}", path, e)
-                        }
+        for stored in &self.stored {
+            if workflows.iter().any(|wf| wf.workflow.name == stored.name) {
+                tracing::error!(
+                    "skipping stored workflow {}: a workflow file has the same name",
+                    stored.name
+                );
+                continue;
+            }
+            let issues = match parse_workflow(&stored.content) {
+                Ok(wf) => {
+                    let issues = self.validate(&wf);
+                    if issues.is_empty() {
+                        workflows.push(wf);
+                        continue;
                     }
+                    issues
                 }
+                Err(issue) => vec![issue],
+            };
+            for issue in issues {
+                tracing::error!(
+                    "skipping invalid stored workflow {}: {}",
+                    stored.name,
+                    issue.message
+                );
             }
         }
 
         workflows
     }
 
+    /// Workflow files that fail validation, with what is wrong in each
+    pub fn invalid_workflows(&self) -> Vec<(PathBuf, Vec<WorkflowIssue>)> {
+        self.load_all()
+            .into_iter()
+            .filter_map(|(path, loaded)| loaded.err().map(|issues| (path, issues)))
+            .collect()
+    }
+
+    /// Parse and validate every workflow file, top-level and in packs, from
+    /// each prompts directory in order
+    fn load_all(&self) -> Vec<(PathBuf, Result<WorkflowFile, Vec<WorkflowIssue>>)> {
+        let mut loaded = Vec::new();
+        for root in &self.roots {
+            loaded.extend(read_workflow_dir(&root.join("workflows")));
+
+            for pack in list_pack_dirs(root) {
+                let Some(pack_name) = pack.file_name().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                // Pack prompt files are relative to the pack; rebase them onto its prompts directory
+                for (path, mut wf) in read_workflow_dir(&pack.join("workflows")) {
+                    if let Ok(wf) = &mut wf {
+                        for step in &mut wf.steps {
+                            step.prompt_file = format!("packs/{}/{}", pack_name, step.prompt_file);
+                        }
+                    }
+                    loaded.push((path, wf));
+                }
+            }
+        }
+
+        loaded
+            .into_iter()
+            .map(|(path, wf)| {
+                let wf = wf.and_then(|mut wf| {
+                    let issues = self.validate(&wf);
+                    if issues.is_empty() {
+                        wf.source = Some(path.clone());
+                        Ok(wf)
+                    } else {
+                        Err(issues)
+                    }
+                });
+                (path, wf)
+            })
+            .collect()
+    }
+
     /// Get a workflow by its name (from the TOML [workflow] name field)
     pub fn get_workflow(&self, name: &str) -> Option<WorkflowFile> {
         self.list_workflows()
@@ -49,37 +222,409 @@ impl WorkflowRegistry {
             .find(|w| w.workflow.name == name)
     }
 
-    /// Recursively list all .md files in the prompts root
+    /// Recursively list all .md files in the prompts directories, each path
+    /// once, then the stored prompts
     pub fn list_prompt_files(&self) -> Vec<String> {
         let mut files = Vec::new();
-        self.walk_prompts(&self.prompts_root, &mut files);
+        for root in &self.roots {
+            walk_prompts(root, root, &mut files);
+        }
+        let mut seen = HashSet::new();
+        files.retain(|f| seen.insert(f.clone()));
+        for wf in &self.stored {
+            files.extend(wf.prompts.keys().cloned());
+        }
         files
     }
 
-    fn walk_prompts(&self, dir: &Path, files: &mut Vec<String>) {
-        if let Ok(entries) = fs::read_dir(dir) {
-            for entry in entries.flatten() {
+    /// Read the content of a prompt path; stored prompts are checked before
+    /// files, which resolve through the prompts directories
+    #[allow(dead_code)]
+    pub fn read_prompt(&self, rel_path: &str) -> Result<String, String> {
+        if let Some(content) = self.stored_prompt(rel_path) {
+            return Ok(content.to_string());
+        }
+        if !inside_root(rel_path) {
+            return Err(format!("invalid prompt path: {}", rel_path));
+        }
+        let full_path = self
+            .prompt_file(rel_path)
+            .unwrap_or_else(|| self.prompts_root().join(rel_path));
+        fs::read_to_string(full_path).map_err(|e| e.to_string())
+    }
+
+    /// Assemble the prompt stack for a role from {dir}/roles/{role}/*.md in
+    /// every prompts directory, concatenated in filename order; an override
+    /// directory replaces files of the same name. Returns `None` if the role
+    /// has no stack.
+    pub fn read_stack(&self, role: &str) -> Result<Option<PromptStack>, String> {
+        if role.is_empty()
+            || !role
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("invalid role name: {}", role));
+        }
+
+        let role_rel = Path::new("roles").join(role);
+        let mut found = false;
+        let mut by_name: BTreeMap<std::ffi::OsString, PathBuf> = BTreeMap::new();
+        for root in &self.roots {
+            let role_dir = root.join(&role_rel);
+            if !role_dir.is_dir() {
+                continue;
+            }
+            found = true;
+            for entry in fs::read_dir(&role_dir)
+                .map_err(|e| e.to_string())?
+                .flatten()
+            {
                 let path = entry.path();
-                if path.is_dir() {
-                    // Skip 'workflows' directory as it contains TOMLs, not prompt fragments
-                    if path.file_name().and_then(|s| s.to_str()) == Some("workflows") {
-                        continue;
+                if path.extension().and_then(|s| s.to_str()) == Some("md") {
+                    by_name.insert(entry.file_name(), path);
+                }
+            }
+        }
+        if !found {
+            return Ok(None);
+        }
+
+        let mut files = Vec::new();
+        let mut sections = Vec::new();
+        for (name, path) in &by_name {
+            sections.push(fs::read_to_string(path).map_err(|e| e.to_string())?);
+            if let Some(rel_str) = role_rel.join(name).to_str() {
+                files.push(rel_str.to_string());
+            }
+        }
+
+        let content = sections
+            .iter()
+            .map(|s| s.trim())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(Some(PromptStack {
+            role: role.to_string(),
+            files,
+            hash: hash_content(&content),
+            content,
+        }))
+    }
+
+    /// Roles with a prompt stack (a `roles/{role}/` directory) in any
+    /// prompts directory, sorted
+    pub fn list_roles(&self) -> Vec<String> {
+        let mut roles: Vec<String> = self
+            .roots
+            .iter()
+            .flat_map(|root| fs::read_dir(root.join("roles")).into_iter().flatten())
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(String::from))
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        roles.sort();
+        roles.dedup();
+        roles
+    }
+}
+
+/// Installed pack directories under a prompts directory; dot-prefixed staging
+/// directories are skipped
+fn list_pack_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(root.join("packs"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                && !path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|n| n.starts_with('.'))
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn walk_prompts(root: &Path, dir: &Path, files: &mut Vec<String>) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                // Skip 'workflows' directory as it contains TOMLs, not prompt fragments,
+                // and hidden directories (.git, pack staging)
+                let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                if name == "workflows" || name.starts_with('.') {
+                    continue;
+                }
+                walk_prompts(root, &path, files);
+            } else if path.extension().and_then(|s| s.to_str()) == Some("md")
+                && let Ok(rel_path) = path.strip_prefix(root)
+                && let Some(rel_str) = rel_path.to_str()
+            {
+                files.push(rel_str.to_string());
+            }
+        }
+    }
+}
+
+/// Parse a workflow manifest, reporting a TOML error as a `parse` issue
+pub fn parse_workflow(content: &str) -> Result<WorkflowFile, WorkflowIssue> {
+    toml::from_str(content).map_err(|e| WorkflowIssue::new("parse", None, e.to_string()))
+}
+
+/// Check a parsed workflow against the prompts under `root`: it has steps,
+/// step ids are unique, `depends_on` and `on_fail` name existing steps, the
+/// dependencies form no cycle and every prompt file exists.
+pub fn validate_workflow(wf: &WorkflowFile, root: &Path) -> Vec<WorkflowIssue> {
+    check_workflow(wf, |path| root.join(path).is_file())
+}
+
+/// Whether a path is relative and stays inside the directory it is joined to
+fn inside_root(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Prompt path usable for a stored prompt: relative, inside prompts_root and a .md file
+pub fn valid_prompt_path(path: &str) -> bool {
+    path.ends_with(".md") && inside_root(path)
+}
+
+fn check_workflow(wf: &WorkflowFile, prompt_exists: impl Fn(&str) -> bool) -> Vec<WorkflowIssue> {
+    let mut issues = Vec::new();
+    if wf.steps.is_empty() {
+        issues.push(WorkflowIssue::new("empty", None, "workflow has no steps"));
+    }
+
+    let mut ids = HashSet::new();
+    for step in &wf.steps {
+        if !ids.insert(step.id.as_str()) {
+            issues.push(WorkflowIssue::new(
+                "duplicate_step",
+                Some(&step.id),
+                format!("step id '{}' is used more than once", step.id),
+            ));
+        }
+    }
+
+    for step in &wf.steps {
+        for dep in step.depends_on.iter().flatten() {
+            if !ids.contains(dep.as_str()) {
+                issues.push(WorkflowIssue::new(
+                    "unknown_dependency",
+                    Some(&step.id),
+                    format!("step '{}' depends on unknown step '{}'", step.id, dep),
+                ));
+            }
+        }
+        if let Some(target) = &step.on_fail
+            && !ids.contains(target.as_str())
+        {
+            issues.push(WorkflowIssue::new(
+                "unknown_on_fail",
+                Some(&step.id),
+                format!(
+                    "step '{}' has on_fail to unknown step '{}'",
+                    step.id, target
+                ),
+            ));
+        }
+        if let Some(source) = &step.for_each
+            && !ids.contains(source.as_str())
+        {
+            issues.push(WorkflowIssue::new(
+                "unknown_for_each",
+                Some(&step.id),
+                format!(
+                    "step '{}' has for_each over unknown step '{}'",
+                    step.id, source
+                ),
+            ));
+        }
+        if step.executor_min_version.is_some() && step.executor.is_none() {
+            issues.push(WorkflowIssue::new(
+                "invalid_executor",
+                Some(&step.id),
+                format!(
+                    "step '{}' sets executor_min_version without an executor",
+                    step.id
+                ),
+            ));
+        }
+        if let Some(strategy) = &step.context_strategy
+            && !CONTEXT_STRATEGIES.contains(&strategy.as_str())
+        {
+            issues.push(WorkflowIssue::new(
+                "invalid_context_strategy",
+                Some(&step.id),
+                format!(
+                    "step '{}' has unknown context_strategy '{}' (expected {})",
+                    step.id,
+                    strategy,
+                    CONTEXT_STRATEGIES.join(", ")
+                ),
+            ));
+        }
+        if let Some(network) = &step.network
+            && !NETWORK_POLICIES.contains(&network.as_str())
+        {
+            issues.push(WorkflowIssue::new(
+                "invalid_network",
+                Some(&step.id),
+                format!(
+                    "step '{}' has unknown network policy '{}' (expected {})",
+                    step.id,
+                    network,
+                    NETWORK_POLICIES.join(", ")
+                ),
+            ));
+        }
+        if !prompt_exists(&step.prompt_file) {
+            issues.push(WorkflowIssue::new(
+                "missing_prompt",
+                Some(&step.id),
+                format!(
+                    "step '{}' prompt_file not found: {}",
+                    step.id, step.prompt_file
+                ),
+            ));
+        }
+    }
+
+    // Ordering is only meaningful once every step id resolves
+    if issues
+        .iter()
+        .all(|i| i.kind != "duplicate_step" && i.kind != "unknown_dependency")
+    {
+        match compute_step_orders(&wf.steps) {
+            Ok(orders) => {
+                let order_of = |id: &str| {
+                    orders
+                        .iter()
+                        .find(|(idx, _)| wf.steps[*idx].id == id)
+                        .map(|(_, order)| *order)
+                };
+                // Items must exist by the time the step's tier is promoted
+                for step in &wf.steps {
+                    if let Some(source) = &step.for_each
+                        && let (Some(src), Some(own)) = (order_of(source), order_of(&step.id))
+                        && src >= own
+                    {
+                        issues.push(WorkflowIssue::new(
+                            "invalid_for_each",
+                            Some(&step.id),
+                            format!(
+                                "step '{}' runs before or alongside its for_each step '{}'",
+                                step.id, source
+                            ),
+                        ));
                     }
-                    self.walk_prompts(&path, files);
-                } else if path.extension().and_then(|s| s.to_str()) == Some("md")
-                    && let Ok(rel_path) = path.strip_prefix(&self.prompts_root)
-                    && let Some(rel_str) = rel_path.to_str()
-                {
-                    files.push(rel_str.to_string());
                 }
             }
+            Err(e) => issues.push(WorkflowIssue::new("cycle", None, e)),
         }
     }
 
-    /// Read the content of a prompt file relative to prompts_root
-    #[allow(dead_code)]
-    pub fn read_prompt(&self, rel_path: &str) -> Result<String, String> {
-        let full_path = self.prompts_root.join(rel_path);
-        fs::read_to_string(full_path).map_err(|e| e.to_string())
+    issues
+}
+
+/// Parse every *.toml workflow in a directory; unreadable files are logged and skipped
+fn read_workflow_dir(
+    workflows_dir: &Path,
+) -> Vec<(PathBuf, Result<WorkflowFile, Vec<WorkflowIssue>>)> {
+    let mut workflows = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(workflows_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("toml") {
+                match fs::read_to_string(&path) {
+                    Ok(content) => {
+                        let wf = parse_workflow(&content).map_err(|issue| vec![issue]);
+                        workflows.push((path, wf));
+                    }
+                    Err(e) => {
+                        tracing::error!("failed to read workflow file at {:?}: {}", path, e)
+                    }
+                }
+            }
+        }
+    }
+
+    workflows
+}
+
+/// Topological sort using Kahn's algorithm.
+/// Returns a vec of (step_index, depth) pairs where depth is the DAG level.
+pub fn topological_sort_steps(steps: &[WorkflowStepFile]) -> Result<Vec<(usize, usize)>, String> {
+    let id_to_idx: HashMap<&str, usize> = steps
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.id.as_str(), i))
+        .collect();
+
+    let n = steps.len();
+    let mut in_degree = vec![0usize; n];
+    let mut children: Vec<Vec<usize>> = vec![vec![]; n];
+
+    for (i, step) in steps.iter().enumerate() {
+        if let Some(deps) = &step.depends_on {
+            for dep_id in deps {
+                let &parent = id_to_idx.get(dep_id.as_str()).ok_or_else(|| {
+                    format!("step '{}' depends on unknown step '{}'", step.id, dep_id)
+                })?;
+                children[parent].push(i);
+                in_degree[i] += 1;
+            }
+        }
     }
+
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    let mut depth = vec![0usize; n];
+
+    for (i, &deg) in in_degree.iter().enumerate() {
+        if deg == 0 {
+            queue.push_back(i);
+        }
+    }
+
+    let mut visited = 0usize;
+    let mut result: Vec<(usize, usize)> = Vec::with_capacity(n);
+
+    while let Some(idx) = queue.pop_front() {
+        visited += 1;
+        result.push((idx, depth[idx]));
+
+        for &child in &children[idx] {
+            in_degree[child] -= 1;
+            depth[child] = depth[child].max(depth[idx] + 1);
+            if in_degree[child] == 0 {
+                queue.push_back(child);
+            }
+        }
+    }
+
+    if visited != n {
+        return Err("cycle detected in workflow step dependencies".to_string());
+    }
+
+    Ok(result)
+}
+
+/// Compute step_order values for workflow steps.
+/// If no step has `depends_on`, falls back to sequential enumerate (backward compat).
+/// Otherwise uses topological sort to assign DAG depth as step_order.
+pub fn compute_step_orders(steps: &[WorkflowStepFile]) -> Result<Vec<(usize, usize)>, String> {
+    let has_deps = steps.iter().any(|s| s.depends_on.is_some());
+
+    if !has_deps {
+        return Ok(steps.iter().enumerate().map(|(i, _)| (i, i)).collect());
+    }
+
+    topological_sort_steps(steps)
 }
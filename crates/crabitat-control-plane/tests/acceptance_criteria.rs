@@ -0,0 +1,179 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::acceptance::parse_checklist;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::mission_service::create_mission;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{CreateRunRequest, CriterionResult, Task};
+use rusqlite::{Connection, params};
+
+const ISSUE_BODY: &str = "Login breaks on Safari.
+
+## Acceptance criteria
+- [ ] Login works on Safari
+- [x] Error page stays as is
+* [ ] Add a regression test
+
+```
+- [ ] not a criterion
+```";
+
+/// Temp prompts root with an implement -> review workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("implement.md", "implement {{mission}}"),
+        (
+            "review.md",
+            "{% for c in criteria %}{{ c.index }}={{ c.text }};{% endfor %}",
+        ),
+        (
+            "workflows/checked.toml",
+            r#"
+[workflow]
+name = "checked"
+description = "implement, then review against the issue's task list"
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+
+[[steps]]
+id = "review"
+prompt_file = "review.md"
+depends_on = ["implement"]
+report_criteria = true
+"#,
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> (Connection, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Fix login", ISSUE_BODY],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "checked".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    (conn, mission.mission_id)
+}
+
+fn task(conn: &Connection, mission_id: &str, step_id: &str) -> Task {
+    tasks::list_tasks_for_mission(conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == step_id)
+        .unwrap()
+}
+
+#[test]
+fn test_checklist_parsing_skips_code_blocks() {
+    let criteria = parse_checklist(ISSUE_BODY);
+    let items: Vec<(i64, &str, bool)> = criteria
+        .iter()
+        .map(|c| (c.index, c.text.as_str(), c.done))
+        .collect();
+    assert_eq!(
+        items,
+        vec![
+            (1, "Login works on Safari", false),
+            (2, "Error page stays as is", true),
+            (3, "Add a regression test", false),
+        ]
+    );
+    assert!(parse_checklist("- [] nope\n- [ ]\n- plain item").is_empty());
+}
+
+#[test]
+fn test_criteria_are_stored_and_injected_into_prompts() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+
+    let mission = missions::get_mission(&conn, &mission_id).unwrap().unwrap();
+    assert_eq!(mission.acceptance_criteria.len(), 3);
+
+    let implement = task(&conn, &mission_id, "implement").assembled_prompt;
+    assert!(
+        implement.contains("# Acceptance Criteria\n1. [ ] Login works on Safari\n2. [x] Error page stays as is\n3. [ ] Add a regression test"),
+        "{implement}"
+    );
+    assert!(!implement.contains("CRITERION <n>"), "{implement}");
+
+    let review = task(&conn, &mission_id, "review").assembled_prompt;
+    assert!(
+        review.contains("1=Login works on Safari;2=Error page stays as is;"),
+        "{review}"
+    );
+    assert!(review.contains("`CRITERION <n>: PASS`"), "{review}");
+}
+
+#[test]
+fn test_review_verdicts_land_on_the_mission() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+    let review = task(&conn, &mission_id, "review");
+
+    let run = tasks::insert_run(
+        &conn,
+        &review.task_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            criteria: vec![
+                CriterionResult {
+                    index: 1,
+                    passed: true,
+                    note: None,
+                },
+                CriterionResult {
+                    index: 3,
+                    passed: false,
+                    note: Some("no test added".to_string()),
+                },
+                CriterionResult {
+                    index: 9,
+                    passed: true,
+                    note: None,
+                },
+            ],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let stored = tasks::get_run(&conn, &run.run_id).unwrap().unwrap();
+    assert_eq!(stored.criteria.len(), 3);
+
+    let criteria = missions::get_mission(&conn, &mission_id)
+        .unwrap()
+        .unwrap()
+        .acceptance_criteria;
+    assert_eq!(criteria[0].result.as_deref(), Some("pass"));
+    assert_eq!(
+        criteria[0].task_id.as_deref(),
+        Some(review.task_id.as_str())
+    );
+    assert_eq!(criteria[1].result, None);
+    assert_eq!(criteria[2].result.as_deref(), Some("fail"));
+    assert_eq!(criteria[2].note.as_deref(), Some("no test added"));
+}
@@ -0,0 +1,205 @@
+use axum::http::Method;
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crabitat_control_plane::auth::required_scope;
+use crabitat_control_plane::db::api_keys;
+use crabitat_control_plane::{AppState, db, routes};
+use rusqlite::Connection;
+
+/// Serve the router on a free port; returns the state and base URL
+async fn serve() -> (AppState, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let state = AppState::new(conn);
+    let app = routes::create_router(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (state, base)
+}
+
+fn create_key(state: &AppState, scopes: &[&str]) -> String {
+    let conn = state.db.lock().unwrap();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+    api_keys::insert(&conn, "test", &scopes).unwrap().1
+}
+
+#[test]
+fn test_required_scope() {
+    assert_eq!(required_scope(&Method::GET, "/v1/admin/keys"), "admin");
+    assert_eq!(
+        required_scope(&Method::POST, "/v1/tasks/claim"),
+        "crab:write"
+    );
+    assert_eq!(
+        required_scope(&Method::POST, "/v1/tasks/{task_id}/heartbeat"),
+        "crab:write"
+    );
+    assert_eq!(required_scope(&Method::GET, "/v1/missions"), "console:read");
+    assert_eq!(
+        required_scope(&Method::POST, "/v1/prompts/content"),
+        "console:read"
+    );
+    assert_eq!(
+        required_scope(&Method::POST, "/v1/missions"),
+        "console:write"
+    );
+    assert_eq!(
+        required_scope(&Method::POST, "/v1/tasks/{task_id}/retry"),
+        "console:write"
+    );
+}
+
+#[tokio::test]
+async fn test_api_is_open_until_a_key_exists() {
+    let (_state, base) = serve().await;
+    let resp = Client::new()
+        .get(format!("{base}/v1/settings"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_keys_and_scopes_are_enforced() {
+    let (state, base) = serve().await;
+    let admin = create_key(&state, &["admin"]);
+    let reader = create_key(&state, &["console:read"]);
+    let crab = create_key(&state, &["crab:write"]);
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("{base}/v1/settings"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = client
+        .get(format!("{base}/v1/settings"))
+        .bearer_auth("crab_nope")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Reads work for every scope, through the header or the query string
+    for token in [&admin, &reader, &crab] {
+        let resp = client
+            .get(format!("{base}/v1/settings"))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = client
+        .get(format!("{base}/v1/settings?access_token={reader}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // A reader can't write; a crab can claim but not create missions
+    let resp = client
+        .post(format!("{base}/v1/settings/prompts_root"))
+        .bearer_auth(&reader)
+        .json(&json!({"value": "/tmp"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().contains("console:write"));
+
+    let resp = client
+        .post(format!("{base}/v1/tasks/claim"))
+        .bearer_auth(&crab)
+        .json(&json!({"worker_id": "w1"}))
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_ne!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = client
+        .post(format!("{base}/v1/missions"))
+        .bearer_auth(&crab)
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Only admin keys manage keys
+    let resp = client
+        .get(format!("{base}/v1/admin/keys"))
+        .bearer_auth(&crab)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = client
+        .post(format!("{base}/v1/admin/keys"))
+        .bearer_auth(&admin)
+        .json(&json!({"name": "console", "scopes": ["console:write"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let created: Value = resp.json().await.unwrap();
+    let token = created["token"].as_str().unwrap();
+    assert!(token.starts_with(created["prefix"].as_str().unwrap()));
+
+    let resp = client
+        .post(format!("{base}/v1/admin/keys"))
+        .bearer_auth(&admin)
+        .json(&json!({"name": "bad", "scopes": ["root"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Revoked keys stop working at once
+    let resp = client
+        .delete(format!(
+            "{base}/v1/admin/keys/{}",
+            created["key_id"].as_str().unwrap()
+        ))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = client
+        .get(format!("{base}/v1/settings"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .get(format!("{base}/v1/admin/keys"))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    let keys: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(keys.len(), 4);
+    assert!(keys.iter().all(|k| k.get("token").is_none()));
+}
+
+#[tokio::test]
+async fn test_webhook_checks_its_own_signature() {
+    let (state, base) = serve().await;
+    create_key(&state, &["admin"]);
+    let resp = Client::new()
+        .post(format!("{base}/v1/github/webhook"))
+        .header("X-GitHub-Event", "ping")
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(resp.status(), StatusCode::UNAUTHORIZED);
+}
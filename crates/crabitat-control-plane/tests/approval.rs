@@ -0,0 +1,182 @@
+mod common;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::tasks::{
+    UpdateStatusRequest, approve_task, reject_approval, reject_task, update_task_status,
+};
+use crabitat_control_plane::mission_service::create_mission;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{
+    ApproveTaskRequest, RejectApprovalRequest, RejectTaskRequest, Task,
+};
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with a plan -> sign-off -> implement workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("plan.md", "plan {{context}}"),
+        ("sign-off.md", "sign-off {{context}}"),
+        ("implement.md", "implement {{context}}"),
+        (
+            "workflows/gated.toml",
+            r#"
+[workflow]
+name = "gated"
+description = "plan, human sign-off, implement"
+
+[[steps]]
+id = "plan"
+prompt_file = "plan.md"
+
+[[steps]]
+id = "sign-off"
+prompt_file = "sign-off.md"
+type = "approval"
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+"#,
+        ),
+    ])
+}
+
+/// Mission whose plan step was just reported completed by a crab
+async fn setup(root: &TempDir) -> (AppState, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Risky change", "Body"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "gated".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    let plan = tasks::claim_next_task(&conn, "crab-a").unwrap().unwrap();
+
+    let state = AppState::new(conn);
+    update_task_status(
+        State(state.clone()),
+        Path(plan.task.task_id),
+        Json(UpdateStatusRequest {
+            status: "completed".to_string(),
+            worker_id: Some("crab-a".to_string()),
+            reason: None,
+        }),
+    )
+    .await
+    .unwrap();
+    (state, mission.mission_id)
+}
+
+fn task(state: &AppState, mission_id: &str, step_id: &str) -> Task {
+    let conn = state.db.lock().unwrap();
+    tasks::list_tasks_for_mission(&conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == step_id)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_approval_step_waits_for_a_human() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+
+    let gate = task(&state, &mission_id, "sign-off");
+    assert_eq!(gate.status, "awaiting_approval");
+    assert!(gate.step_config.approval);
+    assert!(
+        tasks::claim_next_task(&state.db.lock().unwrap(), "crab-a")
+            .unwrap()
+            .is_none()
+    );
+
+    let status = approve_task(
+        State(state.clone()),
+        Path(gate.task_id.clone()),
+        Some(Json(ApproveTaskRequest {
+            approver: Some("alice".to_string()),
+            comment: Some("looks safe".to_string()),
+        })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(task(&state, &mission_id, "sign-off").status, "completed");
+    assert_eq!(task(&state, &mission_id, "implement").status, "queued");
+
+    let last = tasks::list_transitions(&state.db.lock().unwrap(), &gate.task_id)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(last.actor, "alice");
+    assert_eq!(last.reason.as_deref(), Some("approved: looks safe"));
+
+    // Only tasks awaiting approval can be approved
+    let (code, _) = approve_task(State(state.clone()), Path(gate.task_id), None)
+        .await
+        .unwrap_err();
+    assert_eq!(code, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_rejected_approval_fails_the_mission() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+    let gate = task(&state, &mission_id, "sign-off");
+
+    // Crabs can't turn a human gate down
+    let (code, _) = reject_task(
+        State(state.clone()),
+        Path(gate.task_id.clone()),
+        Json(RejectTaskRequest {
+            worker_id: Some("crab-a".to_string()),
+            reason: "not my job".to_string(),
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(code, StatusCode::CONFLICT);
+
+    let Json(rejected) = reject_approval(
+        State(state.clone()),
+        Path(gate.task_id.clone()),
+        Json(RejectApprovalRequest {
+            reviewer: Some("alice".to_string()),
+            reason: "plan touches billing".to_string(),
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(rejected["status"], "failed");
+    assert_eq!(task(&state, &mission_id, "implement").status, "blocked");
+
+    let conn = state.db.lock().unwrap();
+    let mission = missions::get_mission(&conn, &mission_id).unwrap().unwrap();
+    assert_eq!(mission.status, "failed");
+}
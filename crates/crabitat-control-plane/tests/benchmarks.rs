@@ -0,0 +1,236 @@
+mod common;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use rusqlite::{Connection, params};
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::benchmarks::{create_benchmark, get_benchmark_report};
+use crabitat_control_plane::models::benchmarks::{
+    BenchmarkVariant, CreateBenchmarkRequest, VariantStats,
+};
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+
+/// Temp prompts root with one-step workflows `ship` (version 1) and
+/// `ship-lean`
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("implement.md", "implement"),
+        (
+            "workflows/ship.toml",
+            "[workflow]\nname = \"ship\"\ndescription = \"d\"\nversion = \"1\"\n\n[[steps]]\nid = \"implement\"\nprompt_file = \"implement.md\"\n",
+        ),
+        (
+            "workflows/ship-lean.toml",
+            "[workflow]\nname = \"ship-lean\"\ndescription = \"d\"\n\n[[steps]]\nid = \"implement\"\nprompt_file = \"implement.md\"\n",
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> (AppState, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+    let repo = repos::insert(&conn, "l1x", "sandbox", None, None).unwrap();
+    repos::set_sandbox(&conn, &repo.repo_id, true).unwrap();
+    for number in [1, 2] {
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, 'T', 'B')",
+            params![repo.repo_id, number],
+        )
+        .unwrap();
+    }
+    (AppState::new(conn), repo.repo_id)
+}
+
+fn variant(workflow_name: &str, workflow_version: Option<&str>) -> BenchmarkVariant {
+    BenchmarkVariant {
+        workflow_name: workflow_name.to_string(),
+        workflow_version: workflow_version.map(String::from),
+        flavor_id: None,
+    }
+}
+
+fn request(repo_id: &str, issue_numbers: Vec<i64>) -> CreateBenchmarkRequest {
+    CreateBenchmarkRequest {
+        repo_id: repo_id.to_string(),
+        name: None,
+        issue_numbers,
+        baseline: variant("ship", Some("1")),
+        candidate: variant("ship-lean", None),
+    }
+}
+
+/// Finish a benchmark mission: its one task ends with `status` after a run
+/// scored `score`, having taken a minute and `tokens`
+fn finish(conn: &Connection, mission_id: &str, status: &str, score: i64, tokens: i64) {
+    let task = &tasks::list_tasks_for_mission(conn, mission_id).unwrap()[0];
+    tasks::insert_run(
+        conn,
+        &task.task_id,
+        &CreateRunRequest {
+            status: status.to_string(),
+            score: Some(score),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE missions SET status = ?1, total_tokens = ?2,
+             first_started_at = '2026-01-01T00:00:00Z', last_finished_at = '2026-01-01T00:01:00Z'
+         WHERE mission_id = ?3",
+        params![status, tokens, mission_id],
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_benchmark_runs_both_variants_and_compares_them() {
+    let root = prompts_root();
+    let (state, repo_id) = setup(&root);
+
+    let (status, Json(benchmark)) =
+        create_benchmark(State(state.clone()), Json(request(&repo_id, vec![1, 2, 1])))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(benchmark.issue_numbers, vec![1, 2]);
+    assert_eq!(benchmark.name, "ship@1 vs ship-lean");
+
+    let Json(report) =
+        get_benchmark_report(State(state.clone()), Path(benchmark.benchmark_id.clone()))
+            .await
+            .unwrap();
+    assert!(!report.finished);
+    let runs: Vec<(i64, &str)> = report
+        .outcomes
+        .iter()
+        .map(|o| (o.issue_number, o.variant.as_str()))
+        .collect();
+    assert_eq!(
+        runs,
+        vec![
+            (1, "baseline"),
+            (1, "candidate"),
+            (2, "baseline"),
+            (2, "candidate")
+        ]
+    );
+
+    {
+        let conn = state.db.lock().unwrap();
+        // Every mission has a branch of its own and leaves the issue alone
+        let mut branches: Vec<String> = report
+            .outcomes
+            .iter()
+            .map(|o| {
+                missions::get_mission(&conn, &o.mission_id)
+                    .unwrap()
+                    .unwrap()
+                    .branch
+            })
+            .collect();
+        branches.sort();
+        branches.dedup();
+        assert_eq!(branches.len(), 4);
+        assert!(branches.iter().all(|b| b.starts_with("benchmark/")));
+        conn.execute("UPDATE missions SET status = 'running'", [])
+            .unwrap();
+        assert!(
+            missions::list_issue_claim_changes(&conn)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            missions::list_issue_comment_changes(&conn)
+                .unwrap()
+                .is_empty()
+        );
+
+        let ids: Vec<&str> = report
+            .outcomes
+            .iter()
+            .map(|o| o.mission_id.as_str())
+            .collect();
+        finish(&conn, ids[0], "completed", 6, 1000);
+        finish(&conn, ids[1], "completed", 9, 400);
+        finish(&conn, ids[2], "failed", 2, 3000);
+        finish(&conn, ids[3], "completed", 7, 600);
+    }
+
+    let Json(report) = get_benchmark_report(State(state.clone()), Path(benchmark.benchmark_id))
+        .await
+        .unwrap();
+    assert!(report.finished);
+    assert_eq!(
+        report.baseline,
+        VariantStats {
+            missions: 2,
+            completed: 1,
+            failed: 1,
+            success_rate: Some(0.5),
+            avg_score: Some(4.0),
+            avg_tokens: Some(2000.0),
+            avg_duration_ms: Some(60000.0),
+        }
+    );
+    assert_eq!(report.candidate.success_rate, Some(1.0));
+    assert_eq!(report.candidate.avg_score, Some(8.0));
+    assert_eq!(report.candidate.avg_tokens, Some(500.0));
+}
+
+#[tokio::test]
+async fn test_benchmark_checks_its_request() {
+    let root = prompts_root();
+    let (state, repo_id) = setup(&root);
+    let rejected = |req: CreateBenchmarkRequest| {
+        let state = state.clone();
+        async move {
+            let (status, Json(body)) = create_benchmark(State(state), Json(req)).await.unwrap_err();
+            (status, body["message"].as_str().unwrap().to_string())
+        }
+    };
+
+    let (status, message) = rejected(request(&repo_id, vec![1, 7])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(message.contains("#7"), "{message}");
+    assert_eq!(
+        rejected(request(&repo_id, vec![])).await.0,
+        StatusCode::BAD_REQUEST
+    );
+
+    let mut same = request(&repo_id, vec![1]);
+    same.candidate = same.baseline.clone();
+    assert_eq!(rejected(same).await.0, StatusCode::BAD_REQUEST);
+
+    let mut stale = request(&repo_id, vec![1]);
+    stale.baseline.workflow_version = Some("2".into());
+    let (status, message) = rejected(stale).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(message, "workflow ship is at version 1, not 2");
+
+    let real = {
+        let conn = state.db.lock().unwrap();
+        repos::insert(&conn, "l1x", "real", None, None)
+            .unwrap()
+            .repo_id
+    };
+    let (status, message) = rejected(request(&real, vec![1])).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(message.contains("sandbox"), "{message}");
+
+    let mut missing = request(&repo_id, vec![1]);
+    missing.candidate.workflow_name = "nope".into();
+    assert_eq!(rejected(missing).await.0, StatusCode::NOT_FOUND);
+
+    // Nothing was created along the way
+    let conn = state.db.lock().unwrap();
+    assert!(missions::list_all(&conn).unwrap().is_empty());
+}
@@ -0,0 +1,111 @@
+use reqwest::{Client, StatusCode};
+use rusqlite::{Connection, params};
+use serde_json::{Value, json};
+
+use crabitat_control_plane::db::{missions, repos, tasks};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::{AppState, db, routes};
+
+/// Serve the router on a free port; returns the state and base URL
+async fn serve() -> (AppState, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let state = AppState::new(conn);
+    let app = routes::create_router(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (state, base)
+}
+
+fn queue_task(conn: &Connection) -> String {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: 1,
+        workflow_name: "test-wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    let mission = missions::insert_mission(conn, &req, "mission/branch").unwrap();
+    tasks::insert_task(conn, &mission.mission_id, "step1", 0, "p1", 3, "queued")
+        .unwrap()
+        .task_id
+}
+
+// Fault rules are process-wide, so every case runs in this one test
+#[tokio::test]
+async fn test_chaos_rules_inject_faults() {
+    let (state, base) = serve().await;
+    let task_id = queue_task(&state.db.lock().unwrap());
+    let client = Client::new();
+    let set_rules = |rules: Value| {
+        client
+            .post(format!("{base}/v1/admin/chaos"))
+            .json(&rules)
+            .send()
+    };
+
+    let resp = set_rules(json!({"fail_github": 1.5})).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Every crab request is dropped; console requests still get through
+    let resp = set_rules(json!({"drop_crab_requests": 1.0, "seed": 7}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = client
+        .post(format!("{base}/v1/tasks/claim?worker_id=w1"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "unavailable");
+    assert_eq!(body["retryable"], true);
+    let resp = client
+        .get(format!("{base}/v1/missions"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // A killed assignment goes back to the queue with a failed run
+    set_rules(json!({"kill_assignments": 1.0})).await.unwrap();
+    let resp = client
+        .post(format!("{base}/v1/tasks/claim?worker_id=w1"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    {
+        let conn = state.db.lock().unwrap();
+        let task = tasks::get_task(&conn, &task_id).unwrap().unwrap();
+        assert_eq!(task.status, "queued");
+        assert_eq!(task.retry_count, 1);
+        let runs = tasks::list_runs_for_task(&conn, &task_id).unwrap();
+        assert_eq!(runs[0].failure_kind.as_deref(), Some("chaos"));
+    }
+
+    let status: Value = client
+        .get(format!("{base}/v1/admin/chaos"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["killed_assignments"], 1);
+    assert_eq!(status["dropped_crab_requests"], 0);
+
+    // An empty rule set turns everything off
+    let status: Value = set_rules(json!({})).await.unwrap().json().await.unwrap();
+    assert_eq!(status["rules"]["kill_assignments"], 0.0);
+}
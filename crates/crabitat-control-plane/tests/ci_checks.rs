@@ -0,0 +1,343 @@
+mod common;
+
+use axum::Json;
+use axum::extract::{Path, State};
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::tasks::{UpdateStatusRequest, update_task_status};
+use crabitat_control_plane::mission_service::{
+    ChecksOutcome, CreateMissionError, GateOutcome, apply_checks, create_mission,
+};
+use crabitat_control_plane::models::missions::{CreateMissionRequest, PrReviewComment};
+use crabitat_control_plane::models::tasks::{CheckRun, Task};
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with an implement -> open-pr -> finalize workflow whose
+/// open-pr step waits on CI
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("implement.md", "implement {{context}}"),
+        ("open-pr.md", "open-pr {{context}}"),
+        ("finalize.md", "finalize {{context}}"),
+        (
+            "workflows/ci.toml",
+            r#"
+[workflow]
+name = "ci"
+description = "implement, open a PR and wait for green CI"
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+
+[[steps]]
+id = "open-pr"
+prompt_file = "open-pr.md"
+require_checks = true
+on_fail = "implement"
+max_retries = 1
+
+[[steps]]
+id = "finalize"
+prompt_file = "finalize.md"
+"#,
+        ),
+    ])
+}
+
+async fn complete(state: &AppState, task_id: &str) {
+    update_task_status(
+        State(state.clone()),
+        Path(task_id.to_string()),
+        Json(UpdateStatusRequest {
+            status: "completed".to_string(),
+            worker_id: Some("crab-a".to_string()),
+            reason: None,
+        }),
+    )
+    .await
+    .unwrap();
+}
+
+/// Mission whose implement and open-pr steps a crab just completed
+async fn setup(root: &TempDir) -> (AppState, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Change", "Body"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "ci".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+
+    let state = AppState::new(conn);
+    for _ in 0..2 {
+        let claimed = {
+            let conn = state.db.lock().unwrap();
+            tasks::claim_next_task(&conn, "crab-a").unwrap().unwrap()
+        };
+        complete(&state, &claimed.task.task_id).await;
+    }
+    (state, mission.mission_id)
+}
+
+fn task(state: &AppState, mission_id: &str, step_id: &str) -> Task {
+    let conn = state.db.lock().unwrap();
+    tasks::list_tasks_for_mission(&conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == step_id)
+        .unwrap()
+}
+
+fn check(name: &str, bucket: &str) -> CheckRun {
+    CheckRun {
+        name: name.to_string(),
+        bucket: bucket.to_string(),
+        link: format!("https://ci.example/{name}"),
+        description: String::new(),
+    }
+}
+
+fn settle(state: &AppState, mission_id: &str, checks: Result<&[CheckRun], &str>) -> ChecksOutcome {
+    let task = task(state, mission_id, "open-pr");
+    let conn = state.db.lock().unwrap();
+    apply_checks(&conn, &task, checks).unwrap()
+}
+
+#[tokio::test]
+async fn test_step_waits_for_checks_and_advances_once_green() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+
+    assert_eq!(
+        task(&state, &mission_id, "open-pr").status,
+        "awaiting_checks"
+    );
+    assert_eq!(task(&state, &mission_id, "finalize").status, "blocked");
+    {
+        // The mission counts as running while CI does
+        let conn = state.db.lock().unwrap();
+        let mission = missions::get_mission(&conn, &mission_id).unwrap().unwrap();
+        assert_eq!(mission.status, "running");
+    }
+
+    let pending = [check("build", "pass"), check("test", "pending")];
+    assert_eq!(
+        settle(&state, &mission_id, Ok(&pending)),
+        ChecksOutcome::Pending
+    );
+    assert_eq!(
+        task(&state, &mission_id, "open-pr").status,
+        "awaiting_checks"
+    );
+
+    let green = [check("build", "pass"), check("docs", "skipping")];
+    assert_eq!(
+        settle(&state, &mission_id, Ok(&green)),
+        ChecksOutcome::Gate(GateOutcome::Passed)
+    );
+    assert_eq!(task(&state, &mission_id, "open-pr").status, "completed");
+    assert_eq!(task(&state, &mission_id, "finalize").status, "queued");
+}
+
+#[tokio::test]
+async fn test_no_checks_keep_waiting_then_fail_the_step() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+
+    // Checks haven't registered on the new pull request yet
+    assert_eq!(settle(&state, &mission_id, Ok(&[])), ChecksOutcome::Pending);
+    assert_eq!(
+        task(&state, &mission_id, "open-pr").status,
+        "awaiting_checks"
+    );
+
+    // Past the grace window, the gate fails rather than passing without CI
+    state
+        .db
+        .lock()
+        .unwrap()
+        .execute(
+            "UPDATE task_transitions SET created_at = '2000-01-01T00:00:00Z'",
+            [],
+        )
+        .unwrap();
+    assert!(matches!(
+        settle(&state, &mission_id, Ok(&[])),
+        ChecksOutcome::Gate(GateOutcome::Failed { reason }) if reason.contains("no required checks")
+    ));
+    assert_eq!(task(&state, &mission_id, "open-pr").status, "failed");
+}
+
+#[tokio::test]
+async fn test_failing_checks_reroute_to_fix_step_with_details() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+    let implement = task(&state, &mission_id, "implement");
+
+    let red = [check("build", "pass"), check("lint", "fail")];
+    assert_eq!(
+        settle(&state, &mission_id, Ok(&red)),
+        ChecksOutcome::Gate(GateOutcome::Rerouted {
+            fix_task_id: implement.task_id.clone()
+        })
+    );
+
+    let implement = task(&state, &mission_id, "implement");
+    assert_eq!(implement.status, "queued");
+    assert!(
+        implement
+            .assembled_prompt
+            .contains("- lint (fail) https://ci.example/lint"),
+        "{}",
+        implement.assembled_prompt
+    );
+    assert!(!implement.assembled_prompt.contains("- build"));
+    let open_pr = task(&state, &mission_id, "open-pr");
+    assert_eq!(open_pr.status, "blocked");
+    assert_eq!(open_pr.retry_count, 1);
+
+    // The second red run is past the step's one retry
+    for step in ["implement", "open-pr"] {
+        let claimed = {
+            let conn = state.db.lock().unwrap();
+            tasks::claim_next_task(&conn, "crab-a").unwrap().unwrap()
+        };
+        assert_eq!(claimed.task.step_id, step);
+        complete(&state, &claimed.task.task_id).await;
+    }
+    assert!(matches!(
+        settle(&state, &mission_id, Ok(&red)),
+        ChecksOutcome::Gate(GateOutcome::Failed { .. })
+    ));
+    assert_eq!(task(&state, &mission_id, "open-pr").status, "failed");
+}
+
+#[tokio::test]
+async fn test_checks_that_cannot_be_read_fail_the_step() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+
+    assert_eq!(
+        settle(&state, &mission_id, Err("no pull request to check")),
+        ChecksOutcome::Gate(GateOutcome::Failed {
+            reason: "no pull request to check".to_string()
+        })
+    );
+    let open_pr = task(&state, &mission_id, "open-pr");
+    let conn = state.db.lock().unwrap();
+    let history = tasks::list_transitions(&conn, &open_pr.task_id).unwrap();
+    let last = history.last().unwrap();
+    assert_eq!(last.to_status, "failed");
+    assert_eq!(last.actor, "ci-checks");
+}
+
+#[test]
+fn test_workflow_requiring_checks_is_refused_off_github() {
+    let root = prompts_root();
+    let mut conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "UPDATE repos SET forge = 'gitlab' WHERE repo_id = ?1",
+        [&repo.repo_id],
+    )
+    .unwrap();
+
+    let err = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "ci".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap_err();
+    match err {
+        CreateMissionError::InvalidWorkflow(e) => {
+            assert!(e.contains("only read from GitHub"), "{}", e)
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+fn review_comment(kind: &str, comment_id: i64, body: &str, path: Option<&str>) -> PrReviewComment {
+    PrReviewComment {
+        kind: kind.to_string(),
+        comment_id,
+        author: "alice".to_string(),
+        body: body.to_string(),
+        path: path.map(String::from),
+        line: path.map(|_| 3),
+        url: format!("https://github.com/l1x/test/pull/7#r{comment_id}"),
+        submitted_at: Some("2026-01-01T00:00:00Z".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_change_requests_reroute_with_the_review_comments() {
+    let root = prompts_root();
+    let (state, mission_id) = setup(&root).await;
+    let comments = [
+        review_comment("review", 11, "Please split this up", None),
+        review_comment("inline", 12, "Off by one here", Some("src/lib.rs")),
+    ];
+    {
+        let conn = state.db.lock().unwrap();
+        assert_eq!(
+            missions::insert_pr_review_comments(&conn, &mission_id, &comments).unwrap(),
+            2
+        );
+    }
+
+    // Green CI doesn't outweigh a reviewer asking for changes
+    let green = [check("build", "pass")];
+    assert!(matches!(
+        settle(&state, &mission_id, Ok(&green)),
+        ChecksOutcome::Gate(GateOutcome::Rerouted { .. })
+    ));
+    let implement = task(&state, &mission_id, "implement");
+    assert_eq!(implement.status, "queued");
+    let prompt = &implement.assembled_prompt;
+    assert!(prompt.contains("Please split this up"), "{prompt}");
+    assert!(
+        prompt.contains("<pr_review author=\"alice\" path=\"src/lib.rs\" line=\"3\""),
+        "{prompt}"
+    );
+    assert!(prompt.contains("Off by one here"), "{prompt}");
+
+    // The comments went to this fix; seeing them again on GitHub changes nothing
+    let conn = state.db.lock().unwrap();
+    assert!(!missions::has_untaken_pr_review_comments(&conn, &mission_id).unwrap());
+    assert_eq!(
+        missions::insert_pr_review_comments(&conn, &mission_id, &comments).unwrap(),
+        0
+    );
+}
@@ -0,0 +1,53 @@
+//! Fixtures shared by the integration tests. Each test binary uses only some
+//! of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Temp directory removed on drop
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(label: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("crabitat-{}-{}", label, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    /// Temp prompts root with a `workflows` directory and `files`, given as
+    /// (path relative to the root, content)
+    pub fn prompts_root(files: &[(&str, &str)]) -> Self {
+        let root = Self::new("prompts");
+        fs::create_dir_all(root.0.join("workflows")).unwrap();
+        for (rel, content) in files {
+            root.write(rel, content);
+        }
+        root
+    }
+
+    /// Write `content` to `rel`, creating its parent directories
+    pub fn write(&self, rel: &str, content: &str) {
+        let path = self.0.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run git in `dir` with a throwaway identity, failing the test on error
+pub fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
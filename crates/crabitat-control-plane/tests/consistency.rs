@@ -0,0 +1,146 @@
+use crabitat_control_plane::consistency::{check, notifications};
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::consistency::latest_report;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{NewTask, StepConfig};
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+/// Running mission on issue `number` of l1x/test with one task in `task_status`
+fn mission_with_task(
+    conn: &Connection,
+    repo_id: &str,
+    number: i64,
+    task_status: &str,
+) -> (String, String) {
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title) VALUES (?1, ?2, 'Issue')",
+        params![repo_id, number],
+    )
+    .unwrap();
+    let mission = missions::insert_mission(
+        conn,
+        &CreateMissionRequest {
+            repo_id: repo_id.to_string(),
+            issue_number: number,
+            workflow_name: "wf".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+        &format!("crabitat/issue-{number}"),
+    )
+    .unwrap();
+    let task = tasks::insert_new_task(
+        conn,
+        &NewTask {
+            mission_id: &mission.mission_id,
+            step_id: "implement",
+            step_order: 0,
+            assembled_prompt: "implement",
+            max_retries: 0,
+            status: task_status,
+            step_config: StepConfig::default(),
+        },
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE missions SET status = 'running' WHERE mission_id = ?1",
+        [&mission.mission_id],
+    )
+    .unwrap();
+    (mission.mission_id, task.task_id)
+}
+
+#[test]
+fn test_check_fixes_drift_and_reports_the_rest() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    repos::set_slack_webhook(&conn, &repo.repo_id, Some("https://hooks.slack.test/abc")).unwrap();
+
+    let (drifted, _) = mission_with_task(&conn, &repo.repo_id, 1, "completed");
+    let (_, ghost_task) = mission_with_task(&conn, &repo.repo_id, 2, "running");
+    conn.execute(
+        "UPDATE tasks SET heartbeat_worker_id = 'ghost' WHERE task_id = ?1",
+        [&ghost_task],
+    )
+    .unwrap();
+    // Left behind from before foreign keys were enforced
+    conn.pragma_update(None, "foreign_keys", "OFF").unwrap();
+    conn.execute(
+        "INSERT INTO runs (run_id, task_id, status) VALUES ('run-1', 'no-such-task', 'failed')",
+        [],
+    )
+    .unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    assert!(latest_report(&conn).unwrap().is_none());
+
+    let report = check(&conn).unwrap();
+    let found: Vec<_> = report
+        .findings
+        .iter()
+        .map(|f| (f.kind.as_str(), f.record_id.as_str(), f.fixed))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("mission_status_drift", drifted.as_str(), true),
+            ("task_of_unknown_crab", ghost_task.as_str(), false),
+            ("orphaned_run", "run-1", false),
+        ]
+    );
+    let mission = missions::get_mission(&conn, &drifted).unwrap().unwrap();
+    assert_eq!(mission.status, "completed");
+    assert_eq!(
+        latest_report(&conn).unwrap().unwrap().report_id,
+        report.report_id
+    );
+
+    // Only open findings tied to a mission reach Slack
+    let sent = notifications(&conn, &report).unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].webhook_url, "https://hooks.slack.test/abc");
+    assert!(sent[0].text.contains(&ghost_task));
+    assert!(sent[0].text.contains("l1x/test#2"));
+    assert!(!sent[0].text.contains("run-1"));
+
+    // A crab that polls is known; the drift stays fixed
+    conn.execute(
+        "INSERT INTO crab_executors (worker_id) VALUES ('ghost')",
+        [],
+    )
+    .unwrap();
+    conn.execute("DELETE FROM runs", []).unwrap();
+    assert!(check(&conn).unwrap().findings.is_empty());
+}
+
+#[test]
+fn test_taskless_mission_without_workflow_stays_open() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    let (mission_id, task_id) = mission_with_task(&conn, &repo.repo_id, 1, "queued");
+    conn.execute("DELETE FROM tasks WHERE task_id = ?1", [&task_id])
+        .unwrap();
+
+    let report = check(&conn).unwrap();
+    assert_eq!(report.findings.len(), 1);
+    let finding = &report.findings[0];
+    assert_eq!(
+        (
+            finding.kind.as_str(),
+            finding.record_id.as_str(),
+            finding.fixed
+        ),
+        ("taskless_mission", mission_id.as_str(), false)
+    );
+    assert!(finding.detail.contains("expanding failed"));
+}
@@ -0,0 +1,48 @@
+use reqwest::{Client, StatusCode, header, redirect};
+
+use crabitat_control_plane::console::CONSOLE_URL_SETTING;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::{AppState, db, routes};
+use rusqlite::Connection;
+
+/// Serve the router on a free port; returns the state and base URL
+async fn serve() -> (AppState, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let state = AppState::new(conn);
+    let app = routes::create_router(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (state, base)
+}
+
+#[tokio::test]
+async fn test_external_console_url_redirects() {
+    let (state, base) = serve().await;
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    settings::set(
+        &state.db.lock().unwrap(),
+        CONSOLE_URL_SETTING,
+        "https://console.example.com/",
+    )
+    .unwrap();
+    let resp = client
+        .get(format!("{base}/missions/abc?tab=runs"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(
+        resp.headers()[header::LOCATION],
+        "https://console.example.com/missions/abc?tab=runs"
+    );
+
+    // Unknown API paths stay plain 404s
+    let resp = client.get(format!("{base}/v1/nope")).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
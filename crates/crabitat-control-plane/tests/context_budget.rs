@@ -0,0 +1,146 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::mission_service::{create_mission, promote_next_tier};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+use rusqlite::{Connection, params};
+
+/// Temp prompts root where one long plan feeds steps with different budgets
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("step.md", "{{context}}"),
+        (
+            "workflows/budget.toml",
+            r#"
+[workflow]
+name = "budget"
+description = "plan, then readers with bounded context"
+
+[[steps]]
+id = "plan"
+prompt_file = "step.md"
+
+[[steps]]
+id = "tail"
+prompt_file = "step.md"
+depends_on = ["plan"]
+context_max_bytes = 10
+
+[[steps]]
+id = "head"
+prompt_file = "step.md"
+depends_on = ["plan"]
+context_max_tokens = 3
+context_strategy = "head"
+
+[[steps]]
+id = "summarize"
+prompt_file = "step.md"
+depends_on = ["plan"]
+context_max_bytes = 100
+context_strategy = "summarize"
+
+[[steps]]
+id = "unbounded"
+prompt_file = "step.md"
+depends_on = ["plan"]
+"#,
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> (Connection, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Long", "mission"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "budget".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    (conn, mission.mission_id)
+}
+
+fn prompt_of(conn: &Connection, mission_id: &str, step_id: &str) -> String {
+    tasks::list_tasks_for_mission(conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == step_id)
+        .unwrap()
+        .assembled_prompt
+}
+
+#[test]
+fn test_promotion_cuts_context_to_each_steps_budget() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+    settings::set(&conn, "context_max_bytes", "1000").unwrap();
+
+    let plan = tasks::list_tasks_for_mission(&conn, &mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == "plan")
+        .unwrap();
+    let logs = format!("START{}END", "x".repeat(1990));
+    tasks::insert_run(
+        &conn,
+        &plan.task_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            logs: Some(logs),
+            summary: Some("short summary".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    tasks::update_task_status(&conn, &plan.task_id, "completed").unwrap();
+    promote_next_tier(&conn, &plan).unwrap();
+
+    let tail = prompt_of(&conn, &mission_id, "tail");
+    assert!(
+        tail.contains("[... 1988 bytes truncated ...]\nxxxxxxxEND\n</step>"),
+        "{tail}"
+    );
+
+    // 3 tokens = 12 bytes
+    let head = prompt_of(&conn, &mission_id, "head");
+    assert!(
+        head.contains("<step id=\"plan\">\nSTARTxxxxxxx\n[... 1986 bytes truncated ...]"),
+        "{head}"
+    );
+
+    let summarized = prompt_of(&conn, &mission_id, "summarize");
+    assert!(
+        summarized.contains("<step id=\"plan\">\nshort summary\n</step>"),
+        "{summarized}"
+    );
+
+    // Steps without a budget of their own fall back to the setting
+    let unbounded = prompt_of(&conn, &mission_id, "unbounded");
+    assert!(
+        unbounded.contains("[... 998 bytes truncated ...]"),
+        "{unbounded}"
+    );
+    assert!(!unbounded.contains("START"), "{unbounded}");
+}
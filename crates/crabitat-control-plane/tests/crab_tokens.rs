@@ -0,0 +1,213 @@
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use crabitat_control_plane::db::{missions, repos, tasks};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::{AppState, db, routes};
+use rusqlite::{Connection, params};
+
+/// Serve the router on a free port; returns the base URL
+async fn serve() -> String {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    serve_db(conn).await
+}
+
+async fn serve_db(conn: Connection) -> String {
+    let app = routes::create_router(AppState::new(conn));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
+}
+
+#[tokio::test]
+async fn test_registered_worker_id_needs_its_token() {
+    let base = serve().await;
+    let client = Client::new();
+
+    let resp = client
+        .post(format!("{base}/v1/crabs/register"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let registration: Value = resp.json().await.unwrap();
+    let worker_id = registration["worker_id"].as_str().unwrap();
+    let token = registration["token"].as_str().unwrap();
+
+    let claim = |token: Option<&str>| {
+        let mut req = client
+            .post(format!("{base}/v1/tasks/claim"))
+            .query(&[("worker_id", worker_id)]);
+        if let Some(token) = token {
+            req = req.header("X-Crab-Token", token);
+        }
+        req.send()
+    };
+    assert_eq!(
+        claim(None).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        claim(Some("crabt_guess")).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_ne!(
+        claim(Some(token)).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Percent-encoding the worker id doesn't make it a stranger
+    let encoded = format!("%{:02X}{}", worker_id.as_bytes()[0], &worker_id[1..]);
+    let resp = client
+        .post(format!("{base}/v1/tasks/claim?worker_id={encoded}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Worker ids in the body are checked too
+    let resp = client
+        .post(format!("{base}/v1/tasks/t1/status"))
+        .json(&json!({"status": "completed", "worker_id": worker_id}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = client
+        .post(format!("{base}/v1/tasks/t1/status"))
+        .header("X-Crab-Token", token)
+        .json(&json!({"status": "completed", "worker_id": worker_id}))
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // A token only works for the worker it was issued to
+    let other: Value = client
+        .post(format!("{base}/v1/crabs/register"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let resp = client
+        .delete(format!("{base}/v1/missions/m1/burrow"))
+        .query(&[("worker_id", other["worker_id"].as_str().unwrap())])
+        .header("X-Crab-Token", token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_unregistered_worker_ids_pass_through() {
+    let base = serve().await;
+    let resp = Client::new()
+        .post(format!("{base}/v1/tasks/claim"))
+        .query(&[("worker_id", "legacy-crab")])
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_task_requests_without_worker_id_need_the_holders_token() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Issue", "Body"],
+    )
+    .unwrap();
+    let mission = missions::insert_mission(
+        &conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "wf".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+        "mission/branch",
+    )
+    .unwrap();
+    let task = tasks::insert_task(&conn, &mission.mission_id, "s1", 0, "p", 0, "queued").unwrap();
+    let base = serve_db(conn).await;
+    let client = Client::new();
+
+    let registration: Value = client
+        .post(format!("{base}/v1/crabs/register"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let worker_id = registration["worker_id"].as_str().unwrap();
+    let token = registration["token"].as_str().unwrap();
+    let resp = client
+        .post(format!("{base}/v1/tasks/claim"))
+        .query(&[("worker_id", worker_id)])
+        .header("X-Crab-Token", token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Leaving the worker out doesn't get round the holder's token
+    let heartbeat = client
+        .post(format!("{base}/v1/tasks/{}/heartbeat", task.task_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(heartbeat.status(), StatusCode::UNAUTHORIZED);
+
+    // Runs of the task stream logs and artifacts as its holder
+    let logs = |token: Option<&str>| {
+        let mut req = client
+            .post(format!("{base}/v1/runs/run-1/logs"))
+            .json(&json!({
+                "task_id": task.task_id,
+                "chunks": [{"seq": 0, "stream": "stdout", "content": "hi\n"}],
+            }));
+        if let Some(token) = token {
+            req = req.header("X-Crab-Token", token);
+        }
+        req.send()
+    };
+    assert_eq!(logs(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(logs(Some(token)).await.unwrap().status(), StatusCode::OK);
+    let artifact = client
+        .post(format!("{base}/v1/runs/run-1/artifacts"))
+        .query(&[("worker_id", "legacy-crab")])
+        .json(&json!({"name": "a.txt", "content_base64": "aGk="}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(artifact.status(), StatusCode::UNAUTHORIZED);
+    let complete = |token: Option<&str>| {
+        let mut req = client
+            .post(format!("{base}/v1/tasks/{}/status", task.task_id))
+            .json(&json!({"status": "completed"}));
+        if let Some(token) = token {
+            req = req.header("X-Crab-Token", token);
+        }
+        req.send()
+    };
+    assert_eq!(
+        complete(None).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_ne!(
+        complete(Some(token)).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
@@ -1,13 +1,12 @@
-use crabitat_control_plane::handlers::missions::{compute_step_orders, topological_sort_steps};
 use crabitat_control_plane::models::workflows::WorkflowStepFile;
+use crabitat_control_plane::workflow_registry::{compute_step_orders, topological_sort_steps};
 
 fn step(id: &str, depends_on: Option<Vec<&str>>) -> WorkflowStepFile {
     WorkflowStepFile {
         id: id.to_string(),
         prompt_file: format!("{}.md", id),
         depends_on: depends_on.map(|deps| deps.into_iter().map(String::from).collect()),
-        on_fail: None,
-        max_retries: None,
+        ..Default::default()
     }
 }
 
@@ -96,3 +95,20 @@ fn test_compute_step_orders_with_deps() {
     assert_eq!(depth_map[&1], 1);
     assert_eq!(depth_map[&2], 2);
 }
+
+#[test]
+fn test_step_timeout_accepts_minutes() {
+    let mut s: WorkflowStepFile = toml::from_str(
+        r#"
+id = "implement"
+prompt_file = "implement.md"
+timeout_minutes = 30
+"#,
+    )
+    .unwrap();
+    assert_eq!(s.timeout(), Some(1800));
+
+    s.timeout_secs = Some(90);
+    assert_eq!(s.timeout(), Some(90));
+    assert_eq!(step("plan", None).timeout(), None);
+}
@@ -0,0 +1,95 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::analytics;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_task(conn: &Connection) -> String {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: 1,
+        workflow_name: "wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    let mission = missions::insert_mission(conn, &req, "mission/branch").unwrap();
+    tasks::insert_task(conn, &mission.mission_id, "implement", 0, "p", 3, "queued")
+        .unwrap()
+        .task_id
+}
+
+fn run(conn: &Connection, task_id: &str, worker: &str, status: &str, ms: i64, tokens: i64) {
+    tasks::insert_run(
+        conn,
+        task_id,
+        &CreateRunRequest {
+            status: status.to_string(),
+            duration_ms: Some(ms),
+            tokens_used: Some(tokens),
+            worker_id: Some(worker.to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_crab_day_rollup() {
+    let conn = test_conn();
+    let task_id = setup_task(&conn);
+    run(&conn, &task_id, "crab-a", "completed", 43_200_000, 100);
+    run(&conn, &task_id, "crab-a", "failed", 1_000, 5);
+    run(&conn, &task_id, "crab-b", "completed", 2_000, 50);
+    tasks::insert_debug_run(&conn, &task_id, "debug", Some("crab-b")).unwrap();
+
+    let today = analytics::day_offset(&conn, 0).unwrap();
+    assert_eq!(analytics::aggregate_crab_day(&conn, &today).unwrap(), 2);
+    // Re-running the rollup replaces rather than double counts
+    analytics::aggregate_crab_day(&conn, &today).unwrap();
+
+    let stats = analytics::crab_stats(&conn, &today).unwrap();
+    assert_eq!(stats.len(), 2);
+
+    let a = &stats[0];
+    assert_eq!(a.worker_id, "crab-a");
+    assert_eq!(a.runs, 2);
+    assert_eq!(a.tasks_completed, 1);
+    assert_eq!(a.tasks_failed, 1);
+    assert_eq!(a.tokens_used, 105);
+    assert!((a.busy_pct - 50.0).abs() < 0.01);
+
+    assert_eq!(stats[1].worker_id, "crab-b");
+    assert_eq!(stats[1].runs, 1);
+}
+
+#[test]
+fn test_crab_stats_window() {
+    let conn = test_conn();
+    conn.execute(
+        "INSERT INTO crab_daily_stats (worker_id, day, runs) VALUES ('crab-a', '2000-01-01', 3)",
+        [],
+    )
+    .unwrap();
+
+    let since = analytics::day_offset(&conn, 6).unwrap();
+    assert!(analytics::crab_stats(&conn, &since).unwrap().is_empty());
+    assert_eq!(analytics::crab_stats(&conn, "2000-01-01").unwrap().len(), 1);
+}
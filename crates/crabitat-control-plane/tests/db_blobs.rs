@@ -0,0 +1,101 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::blobs;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_mission(conn: &Connection) -> String {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: 1,
+        workflow_name: "test-wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+#[test]
+fn split_chunks_round_trips() {
+    let prompt = "# Instructions\nDo it\n\n# Context & Standards\nRust\n\n# Target Issue\n<issue/>";
+    let chunks = blobs::split_chunks(prompt);
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks[1].starts_with("# Context & Standards"));
+    assert_eq!(chunks.concat(), prompt);
+}
+
+#[test]
+fn split_chunks_without_headings_is_single_chunk() {
+    let chunks = blobs::split_chunks("plain prompt\nno headings");
+    assert_eq!(chunks, vec!["plain prompt\nno headings"]);
+    assert!(blobs::split_chunks("").is_empty());
+}
+
+#[test]
+fn put_is_content_addressed() {
+    let conn = test_conn();
+    let h1 = blobs::put(&conn, "same content").unwrap();
+    let h2 = blobs::put(&conn, "same content").unwrap();
+    assert_eq!(h1, h2);
+    assert_eq!(h1.len(), 64);
+
+    let blob = blobs::get(&conn, &h1).unwrap().unwrap();
+    assert_eq!(blob.content, "same content");
+    assert_eq!(blob.size, 12);
+
+    assert!(blobs::get(&conn, "missing").unwrap().is_none());
+}
+
+#[test]
+fn tasks_share_static_prompt_chunks() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn);
+
+    let p1 = "# Instructions\nplan\n\n# Context & Standards\nstyle guide\n";
+    let p2 = "# Instructions\ncode\n\n# Context & Standards\nstyle guide\n";
+    let t1 = tasks::insert_task(&conn, &mission_id, "plan", 0, p1, 3, "queued").unwrap();
+    let t2 = tasks::insert_task(&conn, &mission_id, "code", 1, p2, 3, "blocked").unwrap();
+
+    assert_eq!(t1.prompt_chunks.len(), 2);
+    assert_ne!(t1.prompt_chunks[0], t2.prompt_chunks[0]);
+    assert_eq!(t1.prompt_chunks[1], t2.prompt_chunks[1]);
+
+    let fetched = tasks::get_task(&conn, &t1.task_id).unwrap().unwrap();
+    assert_eq!(fetched.prompt_chunks, t1.prompt_chunks);
+}
+
+#[test]
+fn updating_prompt_refreshes_chunks() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn);
+
+    let t = tasks::insert_task(&conn, &mission_id, "plan", 0, "# A\nold\n", 3, "queued").unwrap();
+    tasks::update_task_assembled_prompt(&conn, &t.task_id, "# A\nnew\n").unwrap();
+
+    let fetched = tasks::get_task(&conn, &t.task_id).unwrap().unwrap();
+    let rebuilt: String = fetched
+        .prompt_chunks
+        .iter()
+        .map(|h| blobs::get(&conn, h).unwrap().unwrap().content)
+        .collect();
+    assert_eq!(rebuilt, "# A\nnew\n");
+}
@@ -0,0 +1,108 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::burrows;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_mission(conn: &Connection) -> String {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: 1,
+        workflow_name: "wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+#[test]
+fn test_acquire_and_renew() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn);
+
+    let lease = burrows::acquire(&conn, &mission_id, "crab-a", "/repo/burrows/a").unwrap();
+    assert_eq!(lease.worker_id, "crab-a");
+    assert!(lease.released_at.is_none());
+
+    let renewed = burrows::acquire(&conn, &mission_id, "crab-a", "/repo/burrows/a").unwrap();
+    assert_eq!(renewed.acquired_at, lease.acquired_at);
+
+    let active = burrows::get_active(&conn, &mission_id).unwrap().unwrap();
+    assert_eq!(active.path, "/repo/burrows/a");
+    assert!(burrows::list_stale(&conn, "crab-a").unwrap().is_empty());
+}
+
+#[test]
+fn test_takeover_makes_old_lease_stale() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn);
+
+    burrows::acquire(&conn, &mission_id, "crab-a", "/a/burrows/x").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    burrows::acquire(&conn, &mission_id, "crab-b", "/b/burrows/x").unwrap();
+
+    let active = burrows::get_active(&conn, &mission_id).unwrap().unwrap();
+    assert_eq!(active.worker_id, "crab-b");
+
+    let stale = burrows::list_stale(&conn, "crab-a").unwrap();
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].path, "/a/burrows/x");
+    assert!(burrows::list_stale(&conn, "crab-b").unwrap().is_empty());
+}
+
+#[test]
+fn test_finished_mission_lease_is_stale() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn);
+    burrows::acquire(&conn, &mission_id, "crab-a", "/a/burrows/x").unwrap();
+
+    conn.execute(
+        "UPDATE missions SET status = 'completed' WHERE mission_id = ?1",
+        [&mission_id],
+    )
+    .unwrap();
+
+    assert_eq!(burrows::list_stale(&conn, "crab-a").unwrap().len(), 1);
+}
+
+#[test]
+fn test_release() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn);
+    burrows::acquire(&conn, &mission_id, "crab-a", "/a/burrows/x").unwrap();
+
+    assert!(burrows::release(&conn, &mission_id, "crab-a").unwrap());
+    assert!(!burrows::release(&conn, &mission_id, "crab-a").unwrap());
+    assert!(burrows::get_active(&conn, &mission_id).unwrap().is_none());
+}
+
+#[test]
+fn test_pool_stats_keep_latest_report() {
+    let conn = test_conn();
+    burrows::record_pool_stats(&conn, "crab-a", 2, 0, 1).unwrap();
+    burrows::record_pool_stats(&conn, "crab-a", 1, 1, 1).unwrap();
+    burrows::record_pool_stats(&conn, "crab-b", 3, 0, 0).unwrap();
+
+    let pools = burrows::list_pool_stats(&conn).unwrap();
+    assert_eq!(pools.len(), 2);
+    let a = pools.iter().find(|p| p.worker_id == "crab-a").unwrap();
+    assert_eq!((a.ready, a.hits, a.misses), (1, 1, 1));
+}
@@ -0,0 +1,106 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::changelog;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+/// Insert a mission for a fresh issue, with its status and completion time forced
+fn mission(conn: &Connection, repo_id: &str, number: i64, status: &str, at: &str) -> String {
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo_id, number, format!("Issue {number}"), "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo_id.to_string(),
+        issue_number: number,
+        workflow_name: "fix".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    let m = missions::insert_mission(conn, &req, "mission/branch").unwrap();
+    conn.execute(
+        "UPDATE missions SET status = ?1, updated_at = ?2 WHERE mission_id = ?3",
+        params![status, at, m.mission_id],
+    )
+    .unwrap();
+    m.mission_id
+}
+
+#[test]
+fn test_changelog_lists_completed_missions_with_final_summary() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    let done = mission(&conn, &repo.repo_id, 1, "completed", "2026-01-02T00:00:00Z");
+    mission(&conn, &repo.repo_id, 2, "running", "2026-01-03T00:00:00Z");
+
+    let plan = tasks::insert_task(&conn, &done, "plan", 0, "p", 3, "completed").unwrap();
+    let pr = tasks::insert_task(&conn, &done, "pr", 1, "p", 3, "completed").unwrap();
+    for (task_id, summary) in [(&plan.task_id, "planned"), (&pr.task_id, "Fixed the crash")] {
+        let req = CreateRunRequest {
+            status: "completed".to_string(),
+            summary: Some(summary.to_string()),
+            ..Default::default()
+        };
+        tasks::insert_run(&conn, task_id, &req).unwrap();
+    }
+
+    missions::set_pr_url(&conn, &done, "https://github.com/l1x/test/pull/7").unwrap();
+
+    let log = changelog::build(&conn, &repo.repo_id, None).unwrap();
+    assert_eq!(log.entries.len(), 1);
+    assert_eq!(log.entries[0].issue_title, "Issue 1");
+    assert_eq!(log.entries[0].summary.as_deref(), Some("Fixed the crash"));
+    assert_eq!(log.entries[0].pr_number, Some(7));
+    let json = serde_json::to_value(&log.entries[0]).unwrap();
+    assert_eq!(json["pr_url"], "https://github.com/l1x/test/pull/7");
+
+    let md = log.to_markdown();
+    assert!(
+        md.contains("- Issue 1 (#1, PR [#7](https://github.com/l1x/test/pull/7))"),
+        "{md}"
+    );
+    assert!(md.contains("  Fixed the crash"));
+}
+
+#[test]
+fn test_changelog_since_and_last_completed() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    mission(&conn, &repo.repo_id, 1, "completed", "2026-01-01T00:00:00Z");
+    mission(&conn, &repo.repo_id, 2, "completed", "2026-02-01T00:00:00Z");
+
+    let log = changelog::build(&conn, &repo.repo_id, Some("2026-01-15T00:00:00Z")).unwrap();
+    assert_eq!(log.entries.len(), 1);
+    assert_eq!(log.entries[0].issue_number, 2);
+
+    assert_eq!(
+        changelog::last_completed_at(&conn, &repo.repo_id, "fix")
+            .unwrap()
+            .as_deref(),
+        Some("2026-02-01T00:00:00Z")
+    );
+    assert!(
+        changelog::last_completed_at(&conn, &repo.repo_id, "release")
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        changelog::build(&conn, &repo.repo_id, Some("2026-03-01T00:00:00Z"))
+            .unwrap()
+            .to_markdown()
+            .contains("No completed missions")
+    );
+}
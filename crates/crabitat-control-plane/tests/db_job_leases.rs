@@ -0,0 +1,36 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::job_leases;
+use rusqlite::Connection;
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+#[test]
+fn test_one_holder_per_job() {
+    let conn = test_conn();
+    assert!(job_leases::try_acquire(&conn, "watchdog", "a", 60).unwrap());
+    // The holder renews; anyone else waits while the lease is live
+    assert!(job_leases::try_acquire(&conn, "watchdog", "a", 60).unwrap());
+    assert!(!job_leases::try_acquire(&conn, "watchdog", "b", 60).unwrap());
+    // Other jobs are leased separately
+    assert!(job_leases::try_acquire(&conn, "crab_stats", "b", 60).unwrap());
+
+    let leases = job_leases::list(&conn).unwrap();
+    assert_eq!(leases.len(), 2);
+    assert_eq!(leases[1].job, "watchdog");
+    assert_eq!(leases[1].holder, "a");
+}
+
+#[test]
+fn test_lapsed_lease_is_taken_over() {
+    let conn = test_conn();
+    assert!(job_leases::try_acquire(&conn, "watchdog", "a", 0).unwrap());
+    assert!(job_leases::try_acquire(&conn, "watchdog", "b", 60).unwrap());
+    assert!(!job_leases::try_acquire(&conn, "watchdog", "a", 60).unwrap());
+
+    let leases = job_leases::list(&conn).unwrap();
+    assert_eq!(leases[0].holder, "b");
+}
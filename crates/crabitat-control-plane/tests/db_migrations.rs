@@ -0,0 +1,135 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::migrations::{self, MigrationKind};
+use rusqlite::Connection;
+
+#[test]
+fn test_fresh_database_plans_every_table_then_nothing() {
+    let conn = Connection::open_in_memory().unwrap();
+    let steps = migrations::plan(&conn).unwrap();
+    assert!(
+        steps
+            .iter()
+            .any(|s| s.kind == MigrationKind::CreateTable && s.table == "missions")
+    );
+    assert!(steps.iter().all(|s| !s.destructive));
+
+    let applied = migrations::apply(&conn, false).unwrap();
+    assert_eq!(applied.len(), steps.len());
+    assert!(migrations::plan(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn test_plan_counts_rows_a_new_column_touches() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    conn.execute_batch(
+        "INSERT INTO settings (key, value) VALUES ('a', '1'), ('b', '2');
+         ALTER TABLE settings DROP COLUMN updated_at;",
+    )
+    .unwrap();
+
+    let steps = migrations::plan(&conn).unwrap();
+    assert_eq!(steps.len(), 1, "{steps:?}");
+    assert_eq!(steps[0].kind, MigrationKind::AddColumn);
+    assert_eq!(steps[0].table, "settings");
+    assert_eq!(steps[0].detail, "updated_at");
+    assert_eq!(steps[0].rows, 2);
+
+    migrations::apply(&conn, false).unwrap();
+    assert!(migrations::plan(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn test_table_rebuild_needs_allow_destructive() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    // A repos table from before the UNIQUE constraint became a partial index
+    conn.execute_batch(
+        "DROP TABLE repos;
+         CREATE TABLE repos (
+            repo_id    TEXT PRIMARY KEY,
+            owner      TEXT NOT NULL,
+            name       TEXT NOT NULL,
+            local_path TEXT,
+            repo_url   TEXT,
+            created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+            updated_at TEXT,
+            deleted_at TEXT,
+            UNIQUE (owner, name)
+         );
+         INSERT INTO repos (repo_id, owner, name) VALUES ('r1', 'l1x', 'crabitat');",
+    )
+    .unwrap();
+
+    let steps = migrations::plan(&conn).unwrap();
+    let rebuild = steps
+        .iter()
+        .find(|s| s.kind == MigrationKind::RebuildTable)
+        .unwrap();
+    assert_eq!(rebuild.table, "repos");
+    assert_eq!(rebuild.rows, 1);
+    assert!(rebuild.destructive);
+
+    let err = migrations::apply(&conn, false).unwrap_err();
+    assert!(err.contains("--allow-destructive"), "{err}");
+    assert!(
+        migrations::plan(&conn)
+            .unwrap()
+            .iter()
+            .any(|s| s.destructive)
+    );
+
+    migrations::apply(&conn, true).unwrap();
+    assert!(migrations::plan(&conn).unwrap().is_empty());
+    let owner: String = conn
+        .query_row("SELECT owner FROM repos WHERE repo_id = 'r1'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(owner, "l1x");
+
+    let mut out = Vec::new();
+    migrations::write_plan(&[], &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "schema is up to date\n");
+}
+
+fn index_exists(conn: &Connection, name: &str) -> bool {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = ?1)",
+        [name],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_versioned_migrations_apply_and_revert() {
+    let conn = Connection::open_in_memory().unwrap();
+    let latest = migrations::MIGRATIONS.last().unwrap().version;
+    let steps = migrations::plan(&conn).unwrap();
+    assert!(
+        steps
+            .iter()
+            .any(|s| s.kind == MigrationKind::Migrate && s.version == Some(latest))
+    );
+
+    migrations::apply(&conn, false).unwrap();
+    assert_eq!(
+        migrations::applied_versions(&conn).unwrap().last(),
+        Some(&latest)
+    );
+    assert!(index_exists(&conn, "tasks_mission_id"));
+
+    let reverted = migrations::revert(&conn, 0).unwrap();
+    assert_eq!(reverted.len(), migrations::MIGRATIONS.len());
+    assert!(reverted.iter().all(|s| s.kind == MigrationKind::Revert));
+    assert!(migrations::applied_versions(&conn).unwrap().is_empty());
+    assert!(!index_exists(&conn, "tasks_mission_id"));
+
+    // Reverted migrations are pending again, and only they are
+    let steps = migrations::plan(&conn).unwrap();
+    assert_eq!(steps.len(), migrations::MIGRATIONS.len());
+    assert!(steps.iter().all(|s| s.kind == MigrationKind::Migrate));
+    migrations::apply(&conn, false).unwrap();
+    assert!(migrations::plan(&conn).unwrap().is_empty());
+}
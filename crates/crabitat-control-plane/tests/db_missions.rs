@@ -1,8 +1,15 @@
 use crabitat_control_plane::db;
+use crabitat_control_plane::db::artifacts;
 use crabitat_control_plane::db::missions;
 use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::run_logs;
 use crabitat_control_plane::db::tasks;
-use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::mission_service::cancel_mission;
+use crabitat_control_plane::models::missions::{
+    CreateMissionRequest, MissionGraph, MissionListQuery,
+};
+use crabitat_control_plane::models::run_logs::RunLogChunk;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
 use rusqlite::{Connection, params};
 
 fn test_conn() -> Connection {
@@ -28,6 +35,9 @@ fn make_mission_req(repo_id: &str) -> CreateMissionRequest {
         issue_number: 1,
         workflow_name: "test-wf".to_string(),
         flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
     }
 }
 
@@ -47,6 +57,9 @@ fn test_mission_status_recalculation() {
         issue_number: 1,
         workflow_name: "test-wf".to_string(),
         flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
     };
     let mission = missions::insert_mission(&conn, &req, "mission/branch").unwrap();
 
@@ -222,3 +235,297 @@ fn test_list_by_repo() {
             .is_empty()
     );
 }
+
+#[test]
+fn test_deletion_report_and_cascade_delete() {
+    let mut conn = test_conn();
+    let repo = setup_repo_and_issue(&conn);
+    let mission = missions::insert_mission(&conn, &make_mission_req(&repo.repo_id), "b").unwrap();
+    missions::insert_state_history_entry(&conn, &mission.mission_id, "pending").unwrap();
+    let t1 =
+        tasks::insert_task(&conn, &mission.mission_id, "step1", 0, "p1", 3, "completed").unwrap();
+    let t2 =
+        tasks::insert_task(&conn, &mission.mission_id, "step2", 1, "p2", 3, "blocked").unwrap();
+    let run = tasks::insert_run(&conn, &t1.task_id, &Default::default()).unwrap();
+    artifacts::insert(&conn, &run.run_id, "report.txt", None, "text/plain", b"ok").unwrap();
+    let chunks: Vec<RunLogChunk> = (0..2)
+        .map(|seq| RunLogChunk {
+            seq,
+            stream: "stdout".to_string(),
+            content: "line\n".to_string(),
+            created_at: None,
+            redactions: 0,
+        })
+        .collect();
+    run_logs::append(&conn, &run.run_id, &t1.task_id, &chunks).unwrap();
+    tasks::transition_task(&conn, &t2.task_id, "queued", "cascade", None).unwrap();
+    tasks::insert_message(&conn, &t2.task_id, "skip the docs", None).unwrap();
+
+    let report = missions::deletion_report(&conn, &mission.mission_id).unwrap();
+    assert_eq!(report.tasks, 2);
+    assert_eq!(report.runs, 1);
+    assert_eq!(report.artifacts, 1);
+    assert_eq!(report.run_logs, 2);
+    assert_eq!(report.state_history, 1);
+    assert_eq!(report.running_tasks, 0);
+
+    missions::delete_mission(&mut conn, &mission.mission_id).unwrap();
+    assert!(
+        missions::get_mission(&conn, &mission.mission_id)
+            .unwrap()
+            .is_none()
+    );
+    for table in ["runs", "artifacts", "run_logs"] {
+        let orphans: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(orphans, 0, "{table}");
+    }
+}
+
+#[test]
+fn test_archived_mission_hidden_and_not_scheduled() {
+    let conn = test_conn();
+    let repo = setup_repo_and_issue(&conn);
+    let mission = missions::insert_mission(&conn, &make_mission_req(&repo.repo_id), "b").unwrap();
+    tasks::insert_task(&conn, &mission.mission_id, "step1", 0, "p1", 3, "queued").unwrap();
+
+    missions::archive_mission(&conn, &mission.mission_id).unwrap();
+
+    assert!(missions::list_all(&conn).unwrap().is_empty());
+    assert!(tasks::get_next_queued_task(&conn, None).unwrap().is_none());
+    let archived = missions::get_mission(&conn, &mission.mission_id)
+        .unwrap()
+        .unwrap();
+    assert!(archived.archived_at.is_some());
+}
+
+#[test]
+fn test_cancel_mission_skips_unfinished_tasks() {
+    let conn = test_conn();
+    let repo = setup_repo_and_issue(&conn);
+    let mission = missions::insert_mission(&conn, &make_mission_req(&repo.repo_id), "b").unwrap();
+    let id = &mission.mission_id;
+
+    let done = tasks::insert_task(&conn, id, "plan", 0, "p", 3, "completed").unwrap();
+    let running = tasks::insert_task(&conn, id, "build", 1, "p", 3, "running").unwrap();
+    let queued = tasks::insert_task(&conn, id, "lint", 1, "p", 3, "queued").unwrap();
+    let blocked = tasks::insert_task(&conn, id, "review", 2, "p", 3, "blocked").unwrap();
+    let debug = tasks::insert_debug_run(&conn, &done.task_id, "p", None).unwrap();
+
+    let report = cancel_mission(&conn, id, "api").unwrap();
+    assert_eq!(report.skipped_tasks, 3);
+    assert_eq!(report.interrupted_tasks, 1);
+    assert_eq!(report.cancelled_runs, 1);
+
+    let status = |task_id: &str| tasks::get_task(&conn, task_id).unwrap().unwrap().status;
+    assert_eq!(status(&done.task_id), "completed");
+    for t in [&running, &queued, &blocked] {
+        assert_eq!(status(&t.task_id), "skipped");
+    }
+    let run = tasks::get_run(&conn, &debug.run_id).unwrap().unwrap();
+    assert_eq!(run.status, "failed");
+    assert_eq!(run.summary.as_deref(), Some("cancelled"));
+
+    let mission = missions::get_mission(&conn, id).unwrap().unwrap();
+    assert_eq!(mission.status, "cancelled");
+
+    // Skipped tasks are terminal: a late report from the crab is rejected
+    assert!(tasks::transition_task(&conn, &running.task_id, "completed", "crab-a", None).is_err());
+}
+
+#[test]
+fn test_run_rollup_on_mission() {
+    let conn = test_conn();
+    let repo = setup_repo_and_issue(&conn);
+    let mission = missions::insert_mission(&conn, &make_mission_req(&repo.repo_id), "b").unwrap();
+    let task = tasks::insert_task(&conn, &mission.mission_id, "plan", 0, "p", 3, "queued").unwrap();
+    tasks::transition_task(&conn, &task.task_id, "running", "crab-1", None).unwrap();
+
+    for (tokens, duration) in [(100, 2_000), (50, 1_000)] {
+        let req = CreateRunRequest {
+            status: "failed".to_string(),
+            tokens_used: Some(tokens),
+            duration_ms: Some(duration),
+            ..Default::default()
+        };
+        tasks::insert_run(&conn, &task.task_id, &req).unwrap();
+    }
+    tasks::insert_debug_run(&conn, &task.task_id, "p", None).unwrap();
+
+    let rollup = missions::get_mission(&conn, &mission.mission_id)
+        .unwrap()
+        .unwrap()
+        .rollup;
+    assert_eq!(rollup.total_tokens, 150);
+    assert_eq!(rollup.total_duration_ms, 3_000);
+    assert_eq!(rollup.attempts, 2);
+    assert!(rollup.first_started_at.is_some());
+    assert!(rollup.wall_clock_ms.is_some_and(|ms| ms >= 0));
+}
+
+#[test]
+fn test_issue_claim_changes_follow_mission_status() {
+    let conn = test_conn();
+    let repo = setup_repo_and_issue(&conn);
+    let mission = missions::insert_mission(&conn, &make_mission_req(&repo.repo_id), "b").unwrap();
+    let id = &mission.mission_id;
+    let task = tasks::insert_task(&conn, id, "plan", 0, "p", 3, "queued").unwrap();
+    assert!(
+        missions::list_issue_claim_changes(&conn)
+            .unwrap()
+            .is_empty()
+    );
+
+    tasks::transition_task(&conn, &task.task_id, "running", "crab-1", None).unwrap();
+    missions::recalculate_mission_status(&conn, id).unwrap();
+    let changes = missions::list_issue_claim_changes(&conn).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].claim);
+    assert_eq!(changes[0].issue_number, 1);
+    missions::set_github_claimed(&conn, id, true).unwrap();
+    assert!(
+        missions::list_issue_claim_changes(&conn)
+            .unwrap()
+            .is_empty()
+    );
+
+    tasks::transition_task(&conn, &task.task_id, "completed", "crab-1", None).unwrap();
+    missions::recalculate_mission_status(&conn, id).unwrap();
+    let changes = missions::list_issue_claim_changes(&conn).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert!(!changes[0].claim);
+    missions::set_github_claimed(&conn, id, false).unwrap();
+    assert!(
+        missions::list_issue_claim_changes(&conn)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_mission_graph_links_consecutive_tiers() {
+    let conn = test_conn();
+    let repo = setup_repo_and_issue(&conn);
+    let mission = missions::insert_mission(&conn, &make_mission_req(&repo.repo_id), "b").unwrap();
+    let id = &mission.mission_id;
+    tasks::insert_task(&conn, id, "plan", 0, "p", 3, "completed").unwrap();
+    tasks::insert_task(&conn, id, "api", 1, "p", 3, "running").unwrap();
+    tasks::insert_task(&conn, id, "ui", 1, "p", 3, "failed").unwrap();
+    tasks::insert_task(&conn, id, "review", 2, "p", 3, "blocked").unwrap();
+
+    let all = tasks::list_tasks_for_mission(&conn, id).unwrap();
+    let graph = MissionGraph::from_tasks(id, &all);
+    assert_eq!(graph.nodes.len(), 4);
+    assert_eq!(graph.edges.len(), 4);
+
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.starts_with("flowchart TD\n"));
+    assert!(mermaid.contains("t0[\"plan<br/>completed\"]"));
+    assert!(mermaid.contains("t0 --> t1"));
+    assert!(mermaid.contains("fill:#fecaca,stroke:#b91c1c"));
+
+    let dot = graph.to_dot();
+    assert!(dot.contains("t1 -> t3;"));
+    assert!(!dot.contains("t0 -> t3;"));
+}
+
+/// Two repos: `l1x/test` with a running and a failed mission, `acme/api` with
+/// a failed one and an archived one
+fn setup_fleet(conn: &Connection) -> (String, String) {
+    let test = setup_repo_and_issue(conn);
+    let api = repos::insert(conn, "acme", "api", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 7, 'Flaky login', '')",
+        params![api.repo_id],
+    )
+    .unwrap();
+    for (repo_id, issue, branch, status, finished) in [
+        (&test.repo_id, 1, "test/running", "running", None),
+        (&test.repo_id, 1, "test/failed", "failed", Some("-1 hour")),
+        (&api.repo_id, 7, "api/failed", "failed", Some("-2 days")),
+        (&api.repo_id, 7, "api/archived", "failed", Some("-1 hour")),
+    ] {
+        let mut req = make_mission_req(repo_id);
+        req.issue_number = issue;
+        let mission = missions::insert_mission(conn, &req, branch).unwrap();
+        conn.execute(
+            "UPDATE missions SET status = ?1,
+                last_finished_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?2)
+             WHERE mission_id = ?3",
+            params![status, finished, mission.mission_id],
+        )
+        .unwrap();
+        if branch == "api/archived" {
+            missions::archive_mission(conn, &mission.mission_id).unwrap();
+        }
+    }
+    (test.repo_id, api.repo_id)
+}
+
+#[test]
+fn test_list_filtered_spans_repos() {
+    let conn = test_conn();
+    let (test_repo, _) = setup_fleet(&conn);
+    let branches = |query: MissionListQuery| {
+        let mut branches: Vec<String> = missions::list_filtered(&conn, &query, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.branch)
+            .collect();
+        branches.sort();
+        branches
+    };
+
+    let failed = MissionListQuery {
+        status: Some("failed".into()),
+        ..Default::default()
+    };
+    assert_eq!(branches(failed), vec!["api/failed", "test/failed"]);
+
+    let everywhere = MissionListQuery {
+        status: Some("failed, running".into()),
+        archived: true,
+        ..Default::default()
+    };
+    assert_eq!(branches(everywhere).len(), 4);
+
+    let one_repo = MissionListQuery {
+        repo_id: Some(test_repo),
+        ..Default::default()
+    };
+    assert_eq!(branches(one_repo), vec!["test/failed", "test/running"]);
+
+    // Search covers the repo slug and the issue title
+    let by_title = MissionListQuery {
+        q: Some("flaky".into()),
+        ..Default::default()
+    };
+    assert_eq!(branches(by_title), vec!["api/failed"]);
+    let by_slug = MissionListQuery {
+        q: Some("L1X/TEST#1".into()),
+        ..Default::default()
+    };
+    assert_eq!(branches(by_slug).len(), 2);
+}
+
+#[test]
+fn test_fleet_summary_counts_every_repo() {
+    let conn = test_conn();
+    let (test_repo, api_repo) = setup_fleet(&conn);
+
+    let summary = missions::fleet_summary(&conn).unwrap();
+    assert_eq!(summary.repos, 2);
+    assert_eq!(summary.busy_repos, 1);
+    assert_eq!(summary.missions_by_status["failed"], 2);
+    assert_eq!(summary.missions_by_status["running"], 1);
+    assert_eq!(summary.failed_last_24h, 1);
+    assert_eq!(summary.completed_last_24h, 0);
+
+    let repos: Vec<&str> = summary.by_repo.iter().map(|r| r.repo_id.as_str()).collect();
+    assert_eq!(repos, vec![test_repo.as_str(), api_repo.as_str()]);
+    assert_eq!(summary.by_repo[1].repo_owner, "acme");
+    assert_eq!(summary.by_repo[1].missions_by_status["failed"], 1);
+}
@@ -0,0 +1,250 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::analytics;
+use crabitat_control_plane::db::crabs;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::queue;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::crabs::Executor;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{NewTask, StepConfig};
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_mission(conn: &Connection, issue: i64, priority: i64) -> String {
+    let repo = repos::insert(conn, "l1x", &format!("repo-{}", issue), None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, issue, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: issue,
+        workflow_name: "wf".to_string(),
+        flavor_id: None,
+        priority: Some(priority),
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+#[test]
+fn test_diagnostics_explain_each_pending_mission() {
+    let conn = test_conn();
+    let urgent = setup_mission(&conn, 1, 10);
+    let normal = setup_mission(&conn, 2, 0);
+    let stalled = setup_mission(&conn, 3, 0);
+    let empty = setup_mission(&conn, 4, 0);
+
+    tasks::insert_task(&conn, &urgent, "plan", 0, "p", 3, "queued").unwrap();
+    tasks::insert_task(&conn, &urgent, "build", 1, "p", 3, "blocked").unwrap();
+    tasks::insert_task(&conn, &normal, "plan", 0, "p", 3, "queued").unwrap();
+    tasks::insert_task(&conn, &stalled, "plan", 0, "p", 3, "completed").unwrap();
+    tasks::insert_task(&conn, &stalled, "build", 1, "p", 3, "blocked").unwrap();
+
+    let diagnostics = queue::diagnostics(&conn).unwrap();
+    let find = |id: &str| diagnostics.iter().find(|d| d.mission_id == id).unwrap();
+
+    let d = find(&urgent);
+    assert_eq!(d.reason, "next_in_queue");
+    assert_eq!(d.queue_position, Some(0));
+    assert_eq!((d.queued_tasks, d.blocked_tasks), (1, 1));
+
+    let d = find(&normal);
+    assert_eq!(d.reason, "queued_behind");
+    assert_eq!(d.queue_position, Some(1));
+
+    let d = find(&stalled);
+    assert_eq!(d.reason, "stalled");
+    assert_eq!(d.queue_position, None);
+
+    assert_eq!(find(&empty).reason, "no_tasks");
+}
+
+#[test]
+fn test_annotate_only_touches_pending_missions() {
+    let conn = test_conn();
+    let pending = setup_mission(&conn, 1, 0);
+    let running = setup_mission(&conn, 2, 0);
+    tasks::insert_task(&conn, &pending, "plan", 0, "p", 3, "queued").unwrap();
+    tasks::insert_task(&conn, &running, "plan", 0, "p", 3, "running").unwrap();
+    missions::recalculate_mission_status(&conn, &running).unwrap();
+
+    let mut all = missions::list_all(&conn).unwrap();
+    queue::annotate(&conn, &mut all).unwrap();
+
+    let pending = all.iter().find(|m| m.mission_id == pending).unwrap();
+    assert!(
+        pending
+            .blocking_reason
+            .as_deref()
+            .unwrap()
+            .contains("first in the queue")
+    );
+    let running = all.iter().find(|m| m.mission_id == running).unwrap();
+    assert_eq!(running.status, "running");
+    assert!(running.blocking_reason.is_none());
+}
+
+#[test]
+fn test_diagnostics_report_missing_executor() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn, 1, 0);
+    tasks::insert_new_task(
+        &conn,
+        &NewTask {
+            mission_id: &mission_id,
+            step_id: "implement",
+            step_order: 0,
+            assembled_prompt: "p",
+            max_retries: 1,
+            status: "queued",
+            step_config: StepConfig {
+                executor: Some("claude".to_string()),
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+    crabs::record_executors(&conn, "crab-1", &Executor::parse_list("gemini@0.4.0")).unwrap();
+
+    let d = queue::diagnostics(&conn).unwrap().remove(0);
+    assert_eq!(d.reason, "no_capable_crab");
+    assert!(d.detail.starts_with("needs executor claude"));
+    assert_eq!(d.queue_position, None);
+
+    crabs::record_executors(&conn, "crab-2", &Executor::parse_list("claude@1.0.30")).unwrap();
+    assert_eq!(
+        queue::diagnostics(&conn).unwrap()[0].reason,
+        "next_in_queue"
+    );
+}
+
+#[test]
+fn test_diagnostics_report_task_rejected_by_every_crab() {
+    let conn = test_conn();
+    let mission_id = setup_mission(&conn, 1, 0);
+    let task = tasks::insert_task(&conn, &mission_id, "implement", 0, "p", 1, "queued").unwrap();
+    crabs::record_executors(&conn, "crab-1", &[]).unwrap();
+    tasks::claim_next_task(&conn, "crab-1").unwrap().unwrap();
+    tasks::reject_task(&conn, &task.task_id, "crab-1", "repo not checked out").unwrap();
+
+    let d = queue::diagnostics(&conn).unwrap().remove(0);
+    assert_eq!(d.reason, "rejected_by_all_crabs");
+    assert!(d.detail.ends_with("repo not checked out"));
+    assert_eq!(d.queue_position, None);
+
+    crabs::record_executors(&conn, "crab-2", &[]).unwrap();
+    assert_eq!(
+        queue::diagnostics(&conn).unwrap()[0].reason,
+        "next_in_queue"
+    );
+}
+
+#[test]
+fn test_diagnostics_report_mission_waiting_for_repo() {
+    let conn = test_conn();
+    let active = setup_mission(&conn, 1, 0);
+    let repo_id = missions::get_mission(&conn, &active)
+        .unwrap()
+        .unwrap()
+        .repo_id;
+    tasks::insert_task(&conn, &active, "plan", 0, "p", 3, "running").unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 2, 'Migrate', 'Body')",
+        [&repo_id],
+    )
+    .unwrap();
+    let exclusive = missions::insert_mission(
+        &conn,
+        &CreateMissionRequest {
+            repo_id,
+            issue_number: 2,
+            workflow_name: "wf".to_string(),
+            flavor_id: None,
+            priority: Some(10),
+            context_from_mission_id: None,
+            exclusive: true,
+        },
+        "mission/issue-2",
+    )
+    .unwrap()
+    .mission_id;
+    tasks::insert_task(&conn, &exclusive, "plan", 0, "p", 3, "queued").unwrap();
+
+    let diagnostics = queue::diagnostics(&conn).unwrap();
+    let d = diagnostics
+        .iter()
+        .find(|d| d.mission_id == exclusive)
+        .unwrap();
+    assert_eq!(d.reason, "repo_busy");
+    assert_eq!(d.queue_position, None);
+    assert!(d.detail.starts_with("exclusive"), "{}", d.detail);
+}
+
+#[test]
+fn test_diagnostics_estimate_start_and_finish_from_history() {
+    let conn = test_conn();
+    let first = setup_mission(&conn, 1, 10);
+    let second = setup_mission(&conn, 2, 0);
+    tasks::insert_task(&conn, &first, "plan", 0, "p", 3, "queued").unwrap();
+    tasks::insert_task(&conn, &second, "plan", 0, "p", 3, "queued").unwrap();
+
+    // Without history there is nothing to estimate from
+    let diagnostics = queue::diagnostics(&conn).unwrap();
+    assert!(diagnostics.iter().all(|d| d.estimated_start_at.is_none()));
+
+    // One finished mission elsewhere: 10-minute tasks, an hour end to end
+    let done = setup_mission(&conn, 3, 0);
+    let task = tasks::insert_task(&conn, &done, "plan", 0, "p", 3, "completed").unwrap();
+    conn.execute(
+        "INSERT INTO runs (run_id, task_id, status, duration_ms, finished_at)
+         VALUES ('r1', ?1, 'completed', 600000, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
+        [&task.task_id],
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE missions SET status = 'completed',
+                first_started_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-60 minutes'),
+                last_finished_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         WHERE mission_id = ?1",
+        [&done],
+    )
+    .unwrap();
+
+    let now: i64 = conn
+        .query_row("SELECT CAST(strftime('%s', 'now') AS INTEGER)", [], |r| {
+            r.get(0)
+        })
+        .unwrap();
+    let diagnostics = queue::diagnostics(&conn).unwrap();
+    let offsets = |id: &str| {
+        let d = diagnostics.iter().find(|d| d.mission_id == id).unwrap();
+        let secs = |ts: &Option<String>| {
+            analytics::unix_secs(&conn, ts.as_deref().unwrap())
+                .unwrap()
+                .unwrap()
+                - now
+        };
+        (secs(&d.estimated_start_at), secs(&d.estimated_finish_at))
+    };
+    let near = |(start, finish): (i64, i64), (want_start, want_finish): (i64, i64)| {
+        (start - want_start).abs() <= 5 && (finish - want_finish).abs() <= 5
+    };
+    assert!(near(offsets(&first), (0, 3600)), "{:?}", offsets(&first));
+    assert!(
+        near(offsets(&second), (600, 4200)),
+        "{:?}",
+        offsets(&second)
+    );
+}
@@ -0,0 +1,137 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::crabs;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::scheduler;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::crabs::Executor;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{NewTask, StepConfig};
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_mission(conn: &Connection, issue: i64, priority: i64) -> String {
+    let repo = repos::insert(conn, "l1x", &format!("repo-{}", issue), None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, issue, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: issue,
+        workflow_name: "wf".to_string(),
+        flavor_id: None,
+        priority: Some(priority),
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+#[test]
+fn test_claim_records_why_each_queued_task_was_passed_over() {
+    let conn = test_conn();
+    let capped = setup_mission(&conn, 1, 10);
+    let gpu = setup_mission(&conn, 2, 5);
+    let rejected = setup_mission(&conn, 3, 3);
+    let normal = setup_mission(&conn, 4, 0);
+    let later = setup_mission(&conn, 5, 0);
+
+    tasks::insert_task(&conn, &capped, "busy", 0, "p", 0, "running").unwrap();
+    let capped_task = tasks::insert_task(&conn, &capped, "wait", 0, "p", 0, "queued").unwrap();
+    missions::set_max_concurrent_tasks(&conn, &capped, Some(1)).unwrap();
+    let gpu_task = tasks::insert_new_task(
+        &conn,
+        &NewTask {
+            mission_id: &gpu,
+            step_id: "train",
+            step_order: 0,
+            assembled_prompt: "p",
+            max_retries: 0,
+            status: "queued",
+            step_config: StepConfig {
+                executor: Some("gpu".to_string()),
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+    let rejected_task = tasks::insert_task(&conn, &rejected, "x", 0, "p", 0, "queued").unwrap();
+    tasks::reject_task(&conn, &rejected_task.task_id, "crab-a", "no checkout").unwrap();
+    let chosen = tasks::insert_task(&conn, &normal, "x", 0, "p", 0, "queued").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let outranked = tasks::insert_task(&conn, &later, "x", 0, "p", 0, "queued").unwrap();
+
+    let claude = vec![Executor {
+        name: "claude".to_string(),
+        version: None,
+    }];
+    crabs::record_executors(&conn, "crab-a", &claude).unwrap();
+    let claimed = tasks::claim_next_task_for(&conn, "crab-a", &claude)
+        .unwrap()
+        .unwrap();
+    assert_eq!(claimed.task.task_id, chosen.task_id);
+
+    let decisions = scheduler::list_decisions(&conn, None, Some("crab-a"), 100).unwrap();
+    assert_eq!(decisions.len(), 5);
+    assert!(decisions.iter().all(|d| d.tick_id == decisions[0].tick_id));
+    let find = |task_id: &str| decisions.iter().find(|d| d.task_id == task_id).unwrap();
+
+    let d = find(&chosen.task_id);
+    assert_eq!(d.decision, "chosen");
+    assert_eq!(d.eligible_crabs, vec!["crab-a".to_string()]);
+    assert_eq!(
+        find(&capped_task.task_id).reason.as_deref(),
+        Some("mission_at_concurrency_cap")
+    );
+    let d = find(&gpu_task.task_id);
+    assert_eq!(d.reason.as_deref(), Some("executor_unmet"));
+    assert_eq!(d.detail.as_deref(), Some("needs executor gpu"));
+    assert!(d.eligible_crabs.is_empty());
+    let d = find(&rejected_task.task_id);
+    assert_eq!(d.reason.as_deref(), Some("rejected_by_worker"));
+    assert!(d.eligible_crabs.is_empty());
+    assert_eq!(
+        find(&outranked.task_id).reason.as_deref(),
+        Some("outranked")
+    );
+
+    // Filtering by task replays that task's history across ticks: another
+    // crab hasn't rejected it, so gets it next
+    tasks::claim_next_task_for(&conn, "crab-b", &claude).unwrap();
+    let history =
+        scheduler::list_decisions(&conn, Some(&rejected_task.task_id), None, 100).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].decision, "chosen");
+    assert_eq!(history[0].worker_id.as_deref(), Some("crab-b"));
+    assert_eq!(history[1].reason.as_deref(), Some("rejected_by_worker"));
+}
+
+#[test]
+fn test_decisions_are_capped() {
+    let conn = test_conn();
+    settings::set(&conn, "scheduler_decisions_cap", "3").unwrap();
+    let mission = setup_mission(&conn, 1, 0);
+    for step in ["a", "b"] {
+        tasks::insert_task(&conn, &mission, step, 0, "p", 0, "queued").unwrap();
+    }
+    for _ in 0..3 {
+        tasks::get_next_queued_task(&conn, None).unwrap();
+    }
+    assert_eq!(
+        scheduler::list_decisions(&conn, None, None, 100)
+            .unwrap()
+            .len(),
+        3
+    );
+}
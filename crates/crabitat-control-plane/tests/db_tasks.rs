@@ -1,9 +1,15 @@
 use crabitat_control_plane::db;
+use crabitat_control_plane::db::blobs;
+use crabitat_control_plane::db::crabs;
 use crabitat_control_plane::db::missions;
 use crabitat_control_plane::db::repos;
 use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::mission_service::{
+    fail_stuck_tasks, fail_timed_out_tasks, requeue_stale_tasks,
+};
+use crabitat_control_plane::models::crabs::Executor;
 use crabitat_control_plane::models::missions::CreateMissionRequest;
-use crabitat_control_plane::models::tasks::CreateRunRequest;
+use crabitat_control_plane::models::tasks::{CreateRunRequest, NewTask, StepConfig};
 use rusqlite::{Connection, params};
 
 fn test_conn() -> Connection {
@@ -25,6 +31,9 @@ fn setup_repo_and_mission(conn: &Connection) -> (String, String) {
         issue_number: 1,
         workflow_name: "test-wf".to_string(),
         flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
     };
     let mission = missions::insert_mission(conn, &req, "mission/branch").unwrap();
     (repo.repo_id, mission.mission_id)
@@ -51,6 +60,54 @@ fn test_task_retry_logic() {
     assert_eq!(updated.task.retry_count, 2);
 }
 
+#[test]
+fn test_transition_task_enforces_state_machine() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let t = tasks::insert_task(&conn, &mission_id, "step1", 0, "p1", 3, "blocked").unwrap();
+
+    // blocked tasks must be queued before they can run
+    let err = tasks::transition_task(&conn, &t.task_id, "running", "crab-a", None).unwrap_err();
+    assert!(matches!(err, tasks::TransitionError::Invalid { .. }));
+
+    tasks::transition_task(&conn, &t.task_id, "queued", "cascade", None).unwrap();
+    tasks::transition_task(&conn, &t.task_id, "running", "crab-a", None).unwrap();
+    // Same-status moves are accepted and not recorded
+    tasks::transition_task(&conn, &t.task_id, "running", "crab-a", None).unwrap();
+    tasks::transition_task(&conn, &t.task_id, "failed", "crab-a", Some("exit 1")).unwrap();
+
+    let err = tasks::transition_task(&conn, &t.task_id, "completed", "crab-a", None).unwrap_err();
+    assert!(matches!(err, tasks::TransitionError::Invalid { .. }));
+    assert_eq!(
+        tasks::get_task(&conn, &t.task_id).unwrap().unwrap().status,
+        "failed"
+    );
+
+    let history = tasks::list_transitions(&conn, &t.task_id).unwrap();
+    let moves: Vec<_> = history
+        .iter()
+        .map(|h| {
+            (
+                h.from_status.as_str(),
+                h.to_status.as_str(),
+                h.actor.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        moves,
+        vec![
+            ("blocked", "queued", "cascade"),
+            ("queued", "running", "crab-a"),
+            ("running", "failed", "crab-a"),
+        ]
+    );
+    assert_eq!(history[2].reason.as_deref(), Some("exit 1"));
+
+    let err = tasks::transition_task(&conn, "missing", "queued", "api", None).unwrap_err();
+    assert!(matches!(err, tasks::TransitionError::NotFound));
+}
+
 #[test]
 fn test_sticky_distribution() {
     let conn = test_conn();
@@ -74,6 +131,9 @@ fn test_sticky_distribution() {
             issue_number: 1,
             workflow_name: "wf1".to_string(),
             flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
         },
         "branch1",
     )
@@ -86,6 +146,9 @@ fn test_sticky_distribution() {
             issue_number: 2,
             workflow_name: "wf2".to_string(),
             flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
         },
         "branch2",
     )
@@ -153,6 +216,9 @@ fn test_next_queued_task_with_null_repo_url() {
         issue_number: 1,
         workflow_name: "wf".to_string(),
         flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
     };
     let mission = missions::insert_mission(&conn, &req, "branch").unwrap();
     tasks::insert_task(
@@ -196,6 +262,9 @@ fn test_next_queued_task_with_both_url_and_local_path() {
         issue_number: 1,
         workflow_name: "wf".to_string(),
         flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
     };
     let mission = missions::insert_mission(&conn, &req, "branch").unwrap();
     tasks::insert_task(
@@ -233,6 +302,9 @@ fn test_next_queued_task_skips_deleted_repos() {
         issue_number: 1,
         workflow_name: "wf".to_string(),
         flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
     };
     let mission = missions::insert_mission(&conn, &req, "branch").unwrap();
     tasks::insert_task(
@@ -273,6 +345,7 @@ fn test_insert_and_list_runs() {
         summary: None,
         duration_ms: Some(1500),
         tokens_used: Some(500),
+        ..Default::default()
     };
     tasks::insert_run(&conn, &task.task_id, &run_req).unwrap();
 
@@ -616,3 +689,681 @@ fn test_linear_backward_compat_single_task_per_order() {
     assert_eq!(blocked.len(), 1);
     assert_eq!(blocked[0].step_id, "step2");
 }
+
+#[test]
+fn test_debug_run_claimed_only_by_assigned_worker() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_task(&conn, &mission_id, "step1", 0, "p1", 3, "completed").unwrap();
+
+    let run = tasks::insert_debug_run(&conn, &task.task_id, "tweaked", Some("crab-a")).unwrap();
+    assert!(run.debug);
+    assert_eq!(run.status, "pending");
+
+    assert!(
+        tasks::claim_debug_run(&conn, Some("crab-b"))
+            .unwrap()
+            .is_none()
+    );
+    assert!(tasks::claim_debug_run(&conn, None).unwrap().is_none());
+
+    let claimed = tasks::claim_debug_run(&conn, Some("crab-a"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(claimed.run_id, run.run_id);
+    assert_eq!(claimed.status, "running");
+    assert_eq!(claimed.prompt_override.as_deref(), Some("tweaked"));
+    assert!(
+        tasks::claim_debug_run(&conn, Some("crab-a"))
+            .unwrap()
+            .is_none()
+    );
+
+    // The task itself is untouched
+    let task = tasks::get_task(&conn, &task.task_id).unwrap().unwrap();
+    assert_eq!(task.status, "completed");
+}
+
+#[test]
+fn test_finished_debug_run_is_not_latest_run() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_task(&conn, &mission_id, "step1", 0, "p1", 3, "completed").unwrap();
+
+    let real = CreateRunRequest {
+        status: "completed".to_string(),
+        logs: Some("real logs".to_string()),
+        ..Default::default()
+    };
+    tasks::insert_run(&conn, &task.task_id, &real).unwrap();
+
+    let debug = tasks::insert_debug_run(&conn, &task.task_id, "tweaked", None).unwrap();
+    tasks::claim_debug_run(&conn, None).unwrap().unwrap();
+    let finished = tasks::finish_debug_run(
+        &conn,
+        &task.task_id,
+        &debug.run_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            logs: Some("debug logs".to_string()),
+            score: Some(3),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(finished.logs.as_deref(), Some("debug logs"));
+    assert!(finished.finished_at.is_some());
+
+    assert_eq!(
+        tasks::list_runs_for_task(&conn, &task.task_id)
+            .unwrap()
+            .len(),
+        2
+    );
+    let latest = tasks::latest_run_for_task(&conn, &task.task_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest.logs.as_deref(), Some("real logs"));
+
+    // Finishing a non-debug run id through the debug path is rejected, as is
+    // finishing a debug run twice or as another task's
+    assert!(
+        tasks::finish_debug_run(&conn, &task.task_id, &latest.run_id, &real)
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        tasks::finish_debug_run(&conn, &task.task_id, &debug.run_id, &real)
+            .unwrap()
+            .is_none()
+    );
+    let other = tasks::insert_task(&conn, &mission_id, "step2", 1, "p2", 3, "completed").unwrap();
+    let pending = tasks::insert_debug_run(&conn, &task.task_id, "again", None).unwrap();
+    tasks::claim_debug_run(&conn, None).unwrap().unwrap();
+    assert!(
+        tasks::finish_debug_run(&conn, &other.task_id, &pending.run_id, &real)
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(
+        tasks::get_run(&conn, &pending.run_id)
+            .unwrap()
+            .unwrap()
+            .status,
+        "running"
+    );
+}
+
+#[test]
+fn test_read_only_step_config_and_burrow_mode() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_new_task(
+        &conn,
+        &NewTask {
+            mission_id: &mission_id,
+            step_id: "summarize",
+            step_order: 0,
+            assembled_prompt: "p",
+            max_retries: 1,
+            status: "queued",
+            step_config: StepConfig {
+                read_only: true,
+                network: Some("none".to_string()),
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    let next = tasks::get_next_queued_task(&conn, None).unwrap().unwrap();
+    assert!(next.task.step_config.read_only);
+    assert_eq!(next.task.step_config.network.as_deref(), Some("none"));
+
+    let req = CreateRunRequest {
+        status: "completed".to_string(),
+        burrow_mode: Some("read_only".to_string()),
+        burrow_path: Some("/mnt/burrows/crabitat".to_string()),
+        network: Some("none".to_string()),
+        ..Default::default()
+    };
+    tasks::insert_run(&conn, &task.task_id, &req).unwrap();
+    let runs = tasks::list_runs_for_task(&conn, &task.task_id).unwrap();
+    assert_eq!(runs[0].burrow_mode.as_deref(), Some("read_only"));
+    assert_eq!(runs[0].network.as_deref(), Some("none"));
+    assert_eq!(
+        runs[0].burrow_path.as_deref(),
+        Some("/mnt/burrows/crabitat")
+    );
+}
+
+#[test]
+fn test_run_work_log_stored_as_blob() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_task(&conn, &mission_id, "implement", 0, "p", 3, "running").unwrap();
+
+    let log = "+0ms [claim] task\n+12ms [setup] failed: no repo_url\n";
+    let run = tasks::insert_run(
+        &conn,
+        &task.task_id,
+        &CreateRunRequest {
+            status: "failed".to_string(),
+            work_log: Some(log.to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let hash = run.work_log_hash.expect("work log hash recorded");
+    assert_eq!(blobs::get(&conn, &hash).unwrap().unwrap().content, log);
+
+    let stored = tasks::get_run(&conn, &run.run_id).unwrap().unwrap();
+    assert_eq!(stored.work_log_hash.as_deref(), Some(hash.as_str()));
+}
+
+#[test]
+fn test_long_summary_keeps_an_excerpt_inline() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_task(&conn, &mission_id, "implement", 0, "p", 3, "running").unwrap();
+    let insert = |summary: String| {
+        tasks::insert_run(
+            &conn,
+            &task.task_id,
+            &CreateRunRequest {
+                status: "completed".to_string(),
+                summary: Some(summary),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    };
+
+    let short = insert("Done.".to_string());
+    assert_eq!(short.summary.as_deref(), Some("Done."));
+    assert!(short.summary_hash.is_none());
+
+    // Multi-byte text so the cut has to land on a char boundary
+    let long = "詳細 ".repeat(1000);
+    let run = insert(long.clone());
+    let stored = tasks::get_run(&conn, &run.run_id).unwrap().unwrap();
+    let excerpt = stored.summary.clone().unwrap();
+    assert!(excerpt.len() < tasks::SUMMARY_EXCERPT_BYTES + 64);
+    assert!(excerpt.ends_with("bytes truncated ...]"));
+    assert!(stored.summary_hash.is_some());
+
+    let mut full = stored;
+    tasks::expand_summary(&conn, &mut full).unwrap();
+    assert_eq!(full.summary.as_deref(), Some(long.as_str()));
+
+    let huge = insert("x".repeat(tasks::MAX_SUMMARY_BYTES * 2));
+    let mut huge = tasks::get_run(&conn, &huge.run_id).unwrap().unwrap();
+    tasks::expand_summary(&conn, &mut huge).unwrap();
+    let kept = huge.summary.unwrap();
+    assert!(kept.starts_with(&"x".repeat(tasks::MAX_SUMMARY_BYTES)));
+    assert!(kept.ends_with(&format!(
+        "[... {} bytes truncated ...]",
+        tasks::MAX_SUMMARY_BYTES
+    )));
+}
+
+#[test]
+fn test_timeout_failure_kind_round_trips() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_new_task(
+        &conn,
+        &NewTask {
+            mission_id: &mission_id,
+            step_id: "implement",
+            step_order: 0,
+            assembled_prompt: "p",
+            max_retries: 1,
+            status: "running",
+            step_config: StepConfig {
+                timeout_secs: Some(900),
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+    let fetched = tasks::get_task(&conn, &task.task_id).unwrap().unwrap();
+    assert_eq!(fetched.step_config.timeout_secs, Some(900));
+
+    let run = tasks::insert_run(
+        &conn,
+        &task.task_id,
+        &CreateRunRequest {
+            status: "failed".to_string(),
+            summary: Some("Agent timed out after 900s and was killed".to_string()),
+            failure_kind: Some("timeout".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let stored = tasks::get_run(&conn, &run.run_id).unwrap().unwrap();
+    assert_eq!(stored.failure_kind.as_deref(), Some("timeout"));
+}
+
+#[test]
+fn test_claim_next_task_hands_each_task_out_once() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let t = tasks::insert_task(&conn, &mission_id, "step1", 0, "p1", 3, "queued").unwrap();
+
+    let claimed = tasks::claim_next_task(&conn, "worker-A").unwrap().unwrap();
+    assert_eq!(claimed.task.task_id, t.task_id);
+    assert_eq!(claimed.task.status, "running");
+
+    // The second crab finds nothing left to claim
+    assert!(tasks::claim_next_task(&conn, "worker-B").unwrap().is_none());
+
+    let history = tasks::list_transitions(&conn, &t.task_id).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].actor, "worker-A");
+    assert_eq!(history[0].to_status, "running");
+}
+
+#[test]
+fn test_watchdog_requeues_silent_running_tasks() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    tasks::insert_task(&conn, &mission_id, "build", 0, "p", 1, "queued").unwrap();
+    tasks::insert_task(&conn, &mission_id, "lint", 0, "p", 1, "queued").unwrap();
+    let silent = tasks::claim_next_task(&conn, "crab-a")
+        .unwrap()
+        .unwrap()
+        .task;
+    let alive = tasks::claim_next_task(&conn, "crab-b")
+        .unwrap()
+        .unwrap()
+        .task;
+
+    // crab-a went quiet ten minutes ago; crab-b just checked in
+    conn.execute(
+        "UPDATE tasks SET heartbeat_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-600 seconds') WHERE task_id = ?1",
+        [&silent.task_id],
+    )
+    .unwrap();
+    tasks::record_heartbeat(&conn, &alive.task_id, "crab-b").unwrap();
+
+    let recovered = requeue_stale_tasks(&conn, 300).unwrap();
+    assert_eq!(recovered, vec![silent.task_id.clone()]);
+
+    let task = tasks::get_task(&conn, &silent.task_id).unwrap().unwrap();
+    assert_eq!(task.status, "queued");
+    assert_eq!(task.retry_count, 1);
+    let run = tasks::latest_run_for_task(&conn, &silent.task_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(run.failure_kind.as_deref(), Some("stale"));
+    assert_eq!(run.worker_id.as_deref(), Some("crab-a"));
+
+    // Out of retries the next time it goes quiet
+    tasks::claim_next_task(&conn, "crab-a").unwrap();
+    conn.execute(
+        "UPDATE tasks SET heartbeat_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-600 seconds') WHERE task_id = ?1",
+        [&silent.task_id],
+    )
+    .unwrap();
+    requeue_stale_tasks(&conn, 300).unwrap();
+    let task = tasks::get_task(&conn, &silent.task_id).unwrap().unwrap();
+    assert_eq!(task.status, "failed");
+}
+
+#[test]
+fn test_crab_without_output_is_stuck() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    tasks::insert_task(&conn, &mission_id, "build", 0, "p", 1, "queued").unwrap();
+    tasks::insert_task(&conn, &mission_id, "lint", 0, "p", 1, "queued").unwrap();
+    for crab in ["crab-a", "crab-b", "crab-c"] {
+        crabs::record_executors(&conn, crab, &[]).unwrap();
+    }
+    let quiet = tasks::claim_next_task(&conn, "crab-a")
+        .unwrap()
+        .unwrap()
+        .task;
+    let chatty = tasks::claim_next_task(&conn, "crab-b")
+        .unwrap()
+        .unwrap()
+        .task;
+
+    // Both were claimed 20 minutes ago and still heartbeat; only crab-b logged since
+    conn.execute(
+        "UPDATE task_transitions SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-1200 seconds')",
+        [],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO run_logs (run_id, task_id, seq, stream, content) VALUES ('r1', ?1, 0, 'stdout', 'working')",
+        [&chatty.task_id],
+    )
+    .unwrap();
+
+    let states: Vec<(String, String, Option<String>)> =
+        crabs::list_executors(&conn, crabs::ACTIVE_CRAB_SECS)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.worker_id, c.state, c.task_id))
+            .collect();
+    assert!(states.contains(&("crab-a".into(), "stuck".into(), Some(quiet.task_id.clone()))));
+    assert!(states.contains(&("crab-b".into(), "busy".into(), Some(chatty.task_id.clone()))));
+    assert!(states.contains(&("crab-c".into(), "idle".into(), None)));
+
+    let stuck = fail_stuck_tasks(&conn, crabs::stuck_task_secs(&conn)).unwrap();
+    assert_eq!(stuck, vec![quiet.task_id.clone()]);
+    let run = tasks::latest_run_for_task(&conn, &quiet.task_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(run.failure_kind.as_deref(), Some("stuck"));
+    assert_eq!(
+        tasks::get_task(&conn, &chatty.task_id)
+            .unwrap()
+            .unwrap()
+            .status,
+        "running"
+    );
+}
+
+#[test]
+fn test_concurrency_cap_lets_other_missions_through() {
+    let conn = test_conn();
+    let (repo_id, wide) = setup_repo_and_mission(&conn);
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 2, 'Other', 'Body')",
+        [&repo_id],
+    )
+    .unwrap();
+    let other = missions::insert_mission(
+        &conn,
+        &CreateMissionRequest {
+            repo_id,
+            issue_number: 2,
+            workflow_name: "test-wf".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+        "branch2",
+    )
+    .unwrap()
+    .mission_id;
+
+    // A wide fan-out is queued first, so it would otherwise take every crab
+    for step in ["a", "b", "c"] {
+        tasks::insert_task(&conn, &wide, step, 0, "p", 1, "queued").unwrap();
+    }
+    tasks::insert_task(&conn, &other, "x", 0, "p", 1, "queued").unwrap();
+    conn.execute(
+        "UPDATE tasks SET created_at = CASE mission_id WHEN ?1 THEN '2026-01-01T00:00:00Z' ELSE '2026-01-01T00:00:01Z' END,
+             updated_at = CASE mission_id WHEN ?1 THEN '2026-01-01T00:00:00Z' ELSE '2026-01-01T00:00:01Z' END",
+        [&wide],
+    )
+    .unwrap();
+    missions::set_max_concurrent_tasks(&conn, &wide, Some(2)).unwrap();
+
+    let claims: Vec<String> = ["crab-a", "crab-b", "crab-c"]
+        .iter()
+        .map(|w| {
+            tasks::claim_next_task(&conn, w)
+                .unwrap()
+                .unwrap()
+                .task
+                .mission_id
+        })
+        .collect();
+    assert_eq!(claims, vec![wide.clone(), wide.clone(), other.clone()]);
+    assert!(tasks::claim_next_task(&conn, "crab-d").unwrap().is_none());
+
+    // The setting caps missions whose workflow doesn't
+    missions::set_max_concurrent_tasks(&conn, &wide, None).unwrap();
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES ('max_concurrent_tasks', '2')",
+        [],
+    )
+    .unwrap();
+    assert!(tasks::claim_next_task(&conn, "crab-d").unwrap().is_none());
+    conn.execute(
+        "DELETE FROM settings WHERE key = 'max_concurrent_tasks'",
+        [],
+    )
+    .unwrap();
+    let claimed = tasks::claim_next_task(&conn, "crab-d").unwrap().unwrap();
+    assert_eq!(claimed.task.mission_id, wide);
+}
+
+fn insert_mission_in_repo(conn: &Connection, repo_id: &str, issue: i64, exclusive: bool) -> String {
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, 'Issue', 'Body')",
+        params![repo_id, issue],
+    )
+    .unwrap();
+    missions::insert_mission(
+        conn,
+        &CreateMissionRequest {
+            repo_id: repo_id.to_string(),
+            issue_number: issue,
+            workflow_name: "test-wf".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive,
+        },
+        &format!("mission/issue-{}", issue),
+    )
+    .unwrap()
+    .mission_id
+}
+
+#[test]
+fn test_repo_caps_active_missions_and_honours_exclusive() {
+    let conn = test_conn();
+    let (repo_id, first) = setup_repo_and_mission(&conn);
+    let second = insert_mission_in_repo(&conn, &repo_id, 2, false);
+    let migration = insert_mission_in_repo(&conn, &repo_id, 3, true);
+    for mission_id in [&first, &second] {
+        tasks::insert_task(&conn, mission_id, "a", 0, "p", 1, "queued").unwrap();
+        tasks::insert_task(&conn, mission_id, "b", 1, "p", 1, "blocked").unwrap();
+    }
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES ('max_active_missions_per_repo', '2')",
+        [],
+    )
+    .unwrap();
+
+    // Two ordinary missions run side by side; the exclusive one waits for both
+    let claim = |worker: &str| {
+        tasks::claim_next_task(&conn, worker)
+            .unwrap()
+            .map(|t| t.task)
+    };
+    let a = claim("crab-a").unwrap();
+    let b = claim("crab-b").unwrap();
+    assert_ne!(a.mission_id, b.mission_id);
+    tasks::insert_task(&conn, &migration, "a", 0, "p", 1, "queued").unwrap();
+    assert!(claim("crab-c").is_none());
+
+    // Finishing one tier doesn't free the slot; the mission is still active
+    tasks::update_task_status(&conn, &a.task_id, "completed").unwrap();
+    assert!(claim("crab-c").is_none());
+
+    // Nor does finishing one whole mission, while the other is active
+    let finish = |mission_id: &str| {
+        for task in tasks::list_tasks_for_mission(&conn, mission_id).unwrap() {
+            tasks::update_task_status(&conn, &task.task_id, "completed").unwrap();
+        }
+        missions::recalculate_mission_status(&conn, mission_id).unwrap();
+    };
+    finish(&first);
+    assert!(claim("crab-c").is_none());
+
+    finish(&second);
+    let c = claim("crab-c").unwrap();
+    assert_eq!(c.mission_id, migration);
+
+    // While it is active, a new ordinary mission in the repo waits
+    let late = insert_mission_in_repo(&conn, &repo_id, 4, false);
+    tasks::insert_task(&conn, &late, "a", 0, "p", 1, "queued").unwrap();
+    assert!(claim("crab-d").is_none());
+}
+
+#[test]
+fn test_watchdog_takes_back_tasks_past_their_timeout() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    for (step_id, timeout_secs) in [("slow", Some(60)), ("unbounded", None)] {
+        tasks::insert_new_task(
+            &conn,
+            &NewTask {
+                mission_id: &mission_id,
+                step_id,
+                step_order: 0,
+                assembled_prompt: "p",
+                max_retries: 1,
+                status: "queued",
+                step_config: StepConfig {
+                    timeout_secs,
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+    }
+    let first = tasks::claim_next_task(&conn, "crab-a")
+        .unwrap()
+        .unwrap()
+        .task;
+    let second = tasks::claim_next_task(&conn, "crab-b")
+        .unwrap()
+        .unwrap()
+        .task;
+    let slow = if first.step_id == "slow" {
+        &first
+    } else {
+        &second
+    };
+
+    // Claimed ten minutes ago: well past 60s plus the grace, still heartbeating
+    conn.execute(
+        "UPDATE task_transitions SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-600 seconds')",
+        [],
+    )
+    .unwrap();
+    tasks::record_heartbeat(&conn, &slow.task_id, "crab-a").unwrap();
+
+    assert!(fail_timed_out_tasks(&conn, 600).unwrap().is_empty());
+    let timed_out = fail_timed_out_tasks(&conn, 300).unwrap();
+    assert_eq!(timed_out, vec![slow.task_id.clone()]);
+
+    let task = tasks::get_task(&conn, &slow.task_id).unwrap().unwrap();
+    assert_eq!(task.status, "queued");
+    assert_eq!(task.retry_count, 1);
+    let run = tasks::latest_run_for_task(&conn, &slow.task_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(run.failure_kind.as_deref(), Some("timeout"));
+    let history = tasks::list_transitions(&conn, &slow.task_id).unwrap();
+    assert_eq!(history.last().unwrap().actor, "watchdog");
+
+    // A fresh claim restarts the clock
+    tasks::claim_next_task(&conn, "crab-c").unwrap().unwrap();
+    assert!(fail_timed_out_tasks(&conn, 300).unwrap().is_empty());
+}
+
+#[test]
+fn test_claim_skips_tasks_needing_missing_executor() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let needs_claude = tasks::insert_new_task(
+        &conn,
+        &NewTask {
+            mission_id: &mission_id,
+            step_id: "implement",
+            step_order: 0,
+            assembled_prompt: "p",
+            max_retries: 1,
+            status: "queued",
+            step_config: StepConfig {
+                executor: Some("claude".to_string()),
+                executor_min_version: Some("1.0.30".to_string()),
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+    let plain = tasks::insert_task(&conn, &mission_id, "summarize", 1, "p", 1, "queued").unwrap();
+
+    // A gemini crab and an outdated claude crab both get passed over
+    for inventory in ["gemini@0.4.0", "claude@1.0.9"] {
+        let executors = Executor::parse_list(inventory);
+        let next = tasks::next_queued_task_for(&conn, None, &executors, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.task.task_id, plain.task_id);
+    }
+
+    // Once gemini has taken the plain task, only the claude crab gets the other
+    let gemini = Executor::parse_list("gemini@0.4.0");
+    tasks::claim_next_task_for(&conn, "crab-1", &gemini)
+        .unwrap()
+        .unwrap();
+    assert!(
+        tasks::claim_next_task_for(&conn, "crab-1", &gemini)
+            .unwrap()
+            .is_none()
+    );
+    let executors = Executor::parse_list("gemini, claude@1.0.30 ");
+    let claimed = tasks::claim_next_task_for(&conn, "crab-2", &executors)
+        .unwrap()
+        .unwrap();
+    assert_eq!(claimed.task.task_id, needs_claude.task_id);
+}
+
+#[test]
+fn test_rejected_task_goes_to_another_crab() {
+    let conn = test_conn();
+    let (_, mission_id) = setup_repo_and_mission(&conn);
+    let task = tasks::insert_task(&conn, &mission_id, "implement", 0, "p", 1, "queued").unwrap();
+    tasks::claim_next_task(&conn, "crab-1").unwrap().unwrap();
+
+    let rejection = tasks::reject_task(&conn, &task.task_id, "crab-1", "disk full").unwrap();
+    assert_eq!(rejection.reason, "disk full");
+    assert_eq!(
+        tasks::get_task(&conn, &task.task_id)
+            .unwrap()
+            .unwrap()
+            .status,
+        "queued"
+    );
+    let last = tasks::list_transitions(&conn, &task.task_id)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(last.actor, "crab-1");
+    assert_eq!(last.reason.as_deref(), Some("rejected: disk full"));
+
+    // The rejecting crab is passed over; anyone else still gets the task
+    assert!(tasks::claim_next_task(&conn, "crab-1").unwrap().is_none());
+    let next = tasks::claim_next_task(&conn, "crab-2").unwrap().unwrap();
+    assert_eq!(next.task.task_id, task.task_id);
+    assert_eq!(
+        tasks::active_rejections(&conn, &task.task_id)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_executor_version_comparison() {
+    let claude = |v: &str| Executor::parse_list(&format!("claude@{}", v)).remove(0);
+    assert!(claude("1.0.30").satisfies("claude", Some("1.0.9")));
+    assert!(claude("v2.1.0-beta").satisfies("claude", Some("2.1")));
+    assert!(!claude("1.0").satisfies("claude", Some("1.0.1")));
+    assert!(!claude("1.0").satisfies("codex", None));
+    assert!(!Executor::parse_list("claude")[0].satisfies("claude", Some("1.0")));
+}
@@ -0,0 +1,158 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::db::timings;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use rusqlite::{Connection, params};
+
+fn setup() -> (Connection, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 1, 'Issue', 'Body')",
+        [&repo.repo_id],
+    )
+    .unwrap();
+    let mission = missions::insert_mission(
+        &conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "wf".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+        "mission/issue-1",
+    )
+    .unwrap();
+    (conn, mission.mission_id)
+}
+
+/// A task created at 12:00:00 that moved through `history` ("status@HH:MM:SS")
+/// and whose runs spent `model_ms` in the agent
+fn task(
+    conn: &Connection,
+    mission_id: &str,
+    step: &str,
+    order: i64,
+    history: &[&str],
+    model_ms: i64,
+) -> String {
+    let (first, _) = history[0].split_once('@').unwrap();
+    let task = tasks::insert_task(conn, mission_id, step, order, "p", 0, first).unwrap();
+    conn.execute(
+        "UPDATE tasks SET created_at = '2020-01-01T12:00:00Z' WHERE task_id = ?1",
+        [&task.task_id],
+    )
+    .unwrap();
+    for pair in history.windows(2) {
+        let (from, _) = pair[0].split_once('@').unwrap();
+        let (to, at) = pair[1].split_once('@').unwrap();
+        conn.execute(
+            "INSERT INTO task_transitions (task_id, from_status, to_status, actor, created_at)
+             VALUES (?1, ?2, ?3, 'test', ?4)",
+            params![task.task_id, from, to, format!("2020-01-01T{at}.000Z")],
+        )
+        .unwrap();
+    }
+    let (last, _) = history.last().unwrap().split_once('@').unwrap();
+    conn.execute(
+        "UPDATE tasks SET status = ?1 WHERE task_id = ?2",
+        params![last, task.task_id],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO runs (run_id, task_id, status, duration_ms) VALUES (?1, ?2, 'completed', ?3)",
+        params![format!("run-{step}"), task.task_id, model_ms],
+    )
+    .unwrap();
+    task.task_id
+}
+
+#[test]
+fn test_phases_and_critical_path() {
+    let (conn, mission_id) = setup();
+    let plan = task(
+        &conn,
+        &mission_id,
+        "plan",
+        0,
+        &["queued@12:00:00", "running@12:00:10", "completed@12:01:10"],
+        50_000,
+    );
+    let build = task(
+        &conn,
+        &mission_id,
+        "build",
+        1,
+        &[
+            "blocked@12:00:00",
+            "queued@12:01:10",
+            "running@12:01:15",
+            "completed@12:02:15",
+        ],
+        30_000,
+    );
+    let docs = task(
+        &conn,
+        &mission_id,
+        "docs",
+        1,
+        &[
+            "blocked@12:00:00",
+            "queued@12:01:10",
+            "running@12:01:12",
+            "completed@12:01:22",
+        ],
+        10_000,
+    );
+
+    let timings = timings::mission_timings(&conn, &mission_id).unwrap();
+    let find = |id: &str| timings.tasks.iter().find(|t| t.task_id == id).unwrap();
+
+    let plan_t = find(&plan);
+    assert_eq!(plan_t.queue_wait_ms, 10_000);
+    assert_eq!(plan_t.model_ms, 50_000);
+    assert_eq!(plan_t.setup_ms, 10_000);
+    assert_eq!(plan_t.runs, 1);
+    assert_eq!(
+        plan_t.started_at.as_deref(),
+        Some("2020-01-01T12:00:10.000Z")
+    );
+    assert_eq!(
+        plan_t.finished_at.as_deref(),
+        Some("2020-01-01T12:01:10.000Z")
+    );
+
+    let build_t = find(&build);
+    assert_eq!(build_t.blocked_ms, 70_000);
+    assert_eq!(build_t.queue_wait_ms, 5_000);
+    assert_eq!(build_t.setup_ms, 30_000);
+
+    assert_eq!(timings.critical_path, vec![plan.clone(), build.clone()]);
+    assert_eq!(timings.critical_path_ms, 135_000);
+    assert!(!find(&docs).on_critical_path);
+    assert_eq!(timings.wall_ms, 135_000);
+}
+
+#[test]
+fn test_unfinished_task_counts_up_to_now() {
+    let (conn, mission_id) = setup();
+    let id = task(
+        &conn,
+        &mission_id,
+        "plan",
+        0,
+        &["queued@12:00:00", "running@12:00:10"],
+        0,
+    );
+    let timings = timings::mission_timings(&conn, &mission_id).unwrap();
+    let t = timings.tasks.iter().find(|t| t.task_id == id).unwrap();
+    // Still running since 2020, so far longer than a day
+    assert!(t.setup_ms > 86_400_000);
+    assert!(t.finished_at.is_none());
+}
@@ -0,0 +1,205 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::db::triggers;
+use crabitat_control_plane::handlers::triggers::create_trigger;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::triggers::{
+    CreateTriggerRequest, TriggerEvent, TriggerMatcher,
+};
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn trigger_req(
+    repo_id: &str,
+    event_type: &str,
+    matcher: TriggerMatcher,
+    workflow: &str,
+    priority: i64,
+) -> CreateTriggerRequest {
+    CreateTriggerRequest {
+        repo_id: repo_id.to_string(),
+        event_type: event_type.to_string(),
+        matcher,
+        workflow_name: workflow.to_string(),
+        flavor_id: None,
+        priority,
+    }
+}
+
+fn issue_event(repo_id: &str, title: &str, labels: &[&str]) -> TriggerEvent {
+    TriggerEvent {
+        repo_id: repo_id.to_string(),
+        event_type: "issue".to_string(),
+        title: title.to_string(),
+        labels: labels.iter().map(|l| l.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_insert_list_delete_trigger() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+
+    let matcher = TriggerMatcher {
+        keyword: Some("security".into()),
+        ..Default::default()
+    };
+    let t = triggers::insert(
+        &conn,
+        &trigger_req(&repo.repo_id, "issue", matcher, "security", 10),
+    )
+    .unwrap();
+    assert_eq!(t.matcher.keyword.as_deref(), Some("security"));
+    assert_eq!(triggers::list(&conn, Some(&repo.repo_id)).unwrap().len(), 1);
+    assert_eq!(triggers::list(&conn, None).unwrap().len(), 1);
+
+    assert!(triggers::delete(&conn, &t.trigger_id).unwrap());
+    assert!(!triggers::delete(&conn, &t.trigger_id).unwrap());
+    assert!(triggers::list(&conn, None).unwrap().is_empty());
+}
+
+#[test]
+fn test_evaluate_issue_keyword_and_label() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    let keyword = TriggerMatcher {
+        keyword: Some("Security".into()),
+        ..Default::default()
+    };
+    let label = TriggerMatcher {
+        label: Some("bug".into()),
+        ..Default::default()
+    };
+    triggers::insert(
+        &conn,
+        &trigger_req(&repo.repo_id, "issue", keyword, "security", 10),
+    )
+    .unwrap();
+    triggers::insert(
+        &conn,
+        &trigger_req(&repo.repo_id, "issue", label, "bugfix", 1),
+    )
+    .unwrap();
+
+    let both = triggers::evaluate(
+        &conn,
+        &issue_event(&repo.repo_id, "Fix security hole", &["bug"]),
+    )
+    .unwrap();
+    assert_eq!(both.len(), 2);
+    assert_eq!(both[0].workflow_name, "security");
+
+    let label_only = triggers::evaluate(
+        &conn,
+        &issue_event(&repo.repo_id, "Crash on start", &["bug"]),
+    )
+    .unwrap();
+    assert_eq!(label_only.len(), 1);
+    assert_eq!(label_only[0].workflow_name, "bugfix");
+
+    let none = triggers::evaluate(&conn, &issue_event(&repo.repo_id, "Docs typo", &[])).unwrap();
+    assert!(none.is_empty());
+}
+
+#[test]
+fn test_evaluate_repo_scope() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    let other = repos::insert(&conn, "l1x", "other", None, None).unwrap();
+    triggers::insert(
+        &conn,
+        &trigger_req(
+            &repo.repo_id,
+            "issue",
+            TriggerMatcher::default(),
+            "bugfix",
+            0,
+        ),
+    )
+    .unwrap();
+
+    assert_eq!(
+        triggers::evaluate(&conn, &issue_event(&repo.repo_id, "Crash", &[]))
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(
+        triggers::evaluate(&conn, &issue_event(&other.repo_id, "Crash", &[]))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_push_triggers_are_rejected() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+    let state = AppState::new(conn);
+
+    // Missions work on issues; a push has none to start one for
+    let (status, _) = create_trigger(
+        State(state.clone()),
+        Json(trigger_req(
+            &repo.repo_id,
+            "push",
+            TriggerMatcher::default(),
+            "changelog",
+            0,
+        )),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let conn = state.db.lock().unwrap();
+    assert!(triggers::list(&conn, None).unwrap().is_empty());
+}
+
+#[test]
+fn test_high_priority_mission_is_scheduled_first() {
+    let conn = test_conn();
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    for number in [1, 2] {
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+            params![repo.repo_id, number, "Issue", "Body"],
+        )
+        .unwrap();
+    }
+
+    let mission = |issue_number: i64, priority: Option<i64>| {
+        let req = CreateMissionRequest {
+            repo_id: repo.repo_id.clone(),
+            issue_number,
+            workflow_name: "wf".to_string(),
+            flavor_id: None,
+            priority,
+            context_from_mission_id: None,
+            exclusive: false,
+        };
+        missions::insert_mission(&conn, &req, "mission/branch").unwrap()
+    };
+    let normal = mission(1, None);
+    let urgent = mission(2, Some(10));
+    assert_eq!(normal.priority, 0);
+
+    tasks::insert_task(&conn, &normal.mission_id, "s", 0, "p", 3, "queued").unwrap();
+    tasks::insert_task(&conn, &urgent.mission_id, "s", 0, "p", 3, "queued").unwrap();
+
+    let next = tasks::get_next_queued_task(&conn, None).unwrap().unwrap();
+    assert_eq!(next.task.mission_id, urgent.mission_id);
+}
@@ -1,4 +1,5 @@
 use crabitat_control_plane::db;
+use crabitat_control_plane::db::workflow_packs;
 use crabitat_control_plane::db::workflows;
 use rusqlite::Connection;
 
@@ -163,3 +164,47 @@ fn active_duplicate_flavor_rejected() {
     let err = result.err().unwrap();
     assert!(err.contains("already exists"), "got: {err}");
 }
+
+#[test]
+fn upsert_and_delete_workflow_pack() {
+    let conn = test_conn();
+    let url = "https://github.com/l1x/chores.git";
+    let v1 = workflow_packs::upsert(&conn, "chores", url, None, "aaa", &["chore".into()]).unwrap();
+    assert_eq!(v1.workflows, vec!["chore"]);
+    assert!(v1.updated_at.is_none());
+
+    let v2 =
+        workflow_packs::upsert(&conn, "chores", url, Some("v2"), "bbb", &["chore".into()]).unwrap();
+    assert_eq!(v2.commit_sha, "bbb");
+    assert_eq!(v2.git_ref.as_deref(), Some("v2"));
+    assert!(v2.updated_at.is_some());
+    assert_eq!(workflow_packs::list(&conn).unwrap().len(), 1);
+
+    assert!(workflow_packs::delete(&conn, "chores").unwrap());
+    assert!(workflow_packs::get(&conn, "chores").unwrap().is_none());
+}
+
+#[test]
+fn save_and_delete_stored_workflow() {
+    let conn = test_conn();
+    let prompts = [("api/plan.md".to_string(), "plan".to_string())]
+        .into_iter()
+        .collect();
+    let wf = workflows::save_stored(&conn, "api", "toml v1", &prompts).unwrap();
+    assert_eq!(wf.prompts["api/plan.md"], "plan");
+    assert_eq!(
+        workflows::prompt_owner(&conn, "api/plan.md").unwrap(),
+        Some("api".to_string())
+    );
+
+    // Saving again replaces the content and the prompt set
+    let wf = workflows::save_stored(&conn, "api", "toml v2", &Default::default()).unwrap();
+    assert_eq!(wf.content, "toml v2");
+    assert!(wf.prompts.is_empty());
+    assert!(wf.updated_at.is_some());
+    assert_eq!(workflows::list_stored(&conn).unwrap().len(), 1);
+
+    assert!(workflows::delete_stored(&conn, "api").unwrap());
+    assert!(!workflows::delete_stored(&conn, "api").unwrap());
+    assert!(workflows::get_stored(&conn, "api").unwrap().is_none());
+}
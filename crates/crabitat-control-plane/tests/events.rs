@@ -0,0 +1,107 @@
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use crabitat_control_plane::db::{events, missions, repos, tasks};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::{AppState, db, routes};
+use rusqlite::{Connection, params};
+
+/// Serve the router on a free port; returns the state and base URL
+async fn serve() -> (AppState, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let state = AppState::new(conn);
+    let app = routes::create_router(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (state, base)
+}
+
+fn create_mission(conn: &Connection) -> String {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: 1,
+        workflow_name: "test-wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+#[tokio::test]
+async fn test_events_replay_after_seq() {
+    let (state, base) = serve().await;
+    let (mission_id, task_id) = {
+        let conn = state.db.lock().unwrap();
+        let mission_id = create_mission(&conn);
+        let task = tasks::insert_task(&conn, &mission_id, "step1", 0, "p1", 3, "queued").unwrap();
+        (mission_id, task.task_id)
+    };
+    let client = Client::new();
+
+    let all: Vec<Value> = client
+        .get(format!("{base}/v1/events"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let kinds: Vec<&str> = all.iter().map(|e| e["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["mission_created", "task_created"]);
+    assert_eq!(all[1]["mission_id"], mission_id.as_str());
+    let seen = all[1]["seq"].as_i64().unwrap();
+
+    // Only status changes are events; the reconnecting client gets just those
+    tasks::update_task_status(&state.db.lock().unwrap(), &task_id, "running").unwrap();
+    let missed: Vec<Value> = client
+        .get(format!("{base}/v1/events?after_seq={seen}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(missed.len(), 1);
+    assert_eq!(missed[0]["kind"], "task_updated");
+    assert_eq!(missed[0]["status"], "running");
+
+    // Following resumes from Last-Event-ID
+    let mut resp = client
+        .get(format!("{base}/v1/events?follow=true"))
+        .header("Last-Event-ID", seen.to_string())
+        .send()
+        .await
+        .unwrap();
+    let first = String::from_utf8(resp.chunk().await.unwrap().unwrap().to_vec()).unwrap();
+    assert!(first.contains("event: task_updated"), "{first}");
+}
+
+#[tokio::test]
+async fn test_pruned_cursor_is_refused() {
+    let (state, base) = serve().await;
+    {
+        let conn = state.db.lock().unwrap();
+        create_mission(&conn);
+        conn.execute("UPDATE events SET created_at = '2000-01-01T00:00:00Z'", [])
+            .unwrap();
+        assert_eq!(events::prune(&conn, 7).unwrap(), 1);
+    }
+    let resp = Client::new()
+        .get(format!("{base}/v1/events?after_seq=0"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
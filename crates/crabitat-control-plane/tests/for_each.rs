@@ -0,0 +1,157 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::mission_service::{create_mission, expand_for_each, for_each_items};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with a plan -> implement each -> integrate workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("plan.md", "plan {{context}}"),
+        ("implement.md", "implement {{item}}"),
+        ("integrate.md", "integrate {{context}}"),
+        (
+            "workflows/split.toml",
+            r#"
+[workflow]
+name = "split"
+description = "plan, implement each subtask, integrate"
+
+[[steps]]
+id = "plan"
+prompt_file = "plan.md"
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+for_each = "plan"
+
+[[steps]]
+id = "integrate"
+prompt_file = "integrate.md"
+"#,
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> (Connection, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Split me", "three files"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "split".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    (conn, mission.mission_id)
+}
+
+fn complete_plan(conn: &Connection, mission_id: &str, logs: &str) {
+    let plan = tasks::list_tasks_for_mission(conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == "plan")
+        .unwrap();
+    tasks::insert_run(
+        conn,
+        &plan.task_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            logs: Some(logs.to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    tasks::update_task_status(conn, &plan.task_id, "completed").unwrap();
+}
+
+fn implement_tasks(conn: &Connection, mission_id: &str) -> Vec<(i64, Option<String>, String)> {
+    tasks::list_tasks_for_mission(conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .filter(|t| t.step_id == "implement")
+        .map(|t| (t.step_order, t.step_config.item, t.assembled_prompt))
+        .collect()
+}
+
+#[test]
+fn test_for_each_expands_one_task_per_item() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+    complete_plan(
+        &conn,
+        &mission_id,
+        "Plan ready.\n```json\n[\"src/a.rs\", {\"file\": \"src/b.rs\"}]\n```\n",
+    );
+
+    let template = tasks::get_blocked_tasks_at_order(&conn, &mission_id, 1)
+        .unwrap()
+        .remove(0);
+    assert_eq!(template.step_config.for_each.as_deref(), Some("plan"));
+    let ids = expand_for_each(&conn, &template, "ctx").unwrap().unwrap();
+    assert_eq!(ids.len(), 2);
+    assert_eq!(ids[0], template.task_id);
+
+    let expanded = implement_tasks(&conn, &mission_id);
+    assert_eq!(expanded.len(), 2);
+    assert!(expanded.iter().all(|(order, _, _)| *order == 1));
+    let items: Vec<_> = expanded.iter().map(|(_, item, _)| item.clone()).collect();
+    assert!(items.contains(&Some("src/a.rs".to_string())));
+    assert!(items.contains(&Some(r#"{"file":"src/b.rs"}"#.to_string())));
+    assert!(expanded.iter().all(|(_, item, prompt)| {
+        prompt.contains(&format!("implement {}", item.as_ref().unwrap()))
+    }));
+
+    // Expanded copies don't fan out again
+    let first = tasks::get_task(&conn, &ids[0]).unwrap().unwrap();
+    assert!(expand_for_each(&conn, &first, "ctx").unwrap().is_none());
+}
+
+#[test]
+fn test_for_each_without_items_runs_once() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+    complete_plan(&conn, &mission_id, "nothing structured here");
+
+    let template = tasks::get_blocked_tasks_at_order(&conn, &mission_id, 1)
+        .unwrap()
+        .remove(0);
+    assert!(expand_for_each(&conn, &template, "ctx").unwrap().is_none());
+    assert_eq!(implement_tasks(&conn, &mission_id).len(), 1);
+}
+
+#[test]
+fn test_for_each_items_accepts_arrays_and_items_objects() {
+    assert_eq!(
+        for_each_items(r#"["a", "b"]"#),
+        Some(vec!["a".to_string(), "b".to_string()])
+    );
+    assert_eq!(
+        for_each_items("```json\n{\"items\": [1, 2]}\n```"),
+        Some(vec!["1".to_string(), "2".to_string()])
+    );
+    assert_eq!(for_each_items(r#"{"files": ["a"]}"#), None);
+    assert_eq!(for_each_items("no json"), None);
+}
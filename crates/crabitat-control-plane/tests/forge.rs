@@ -0,0 +1,333 @@
+use axum::Json;
+use axum::Router;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::issues as issues_db;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::forge::{ForgeProvider, GitLab, Gitea, bind_credential};
+use crabitat_control_plane::handlers::issues::{get_repo_issue, list_repo_issues};
+use crabitat_control_plane::handlers::repos::update_forge;
+use crabitat_control_plane::models::issues::IssuesQuery;
+use crabitat_control_plane::models::repos::ForgeConfig;
+use rusqlite::Connection;
+
+/// A fake GitLab API for project `group/sub/app`: two pages of public open
+/// issues, and merge requests 1 (merged) and 2 (open) behind a token
+async fn fake_gitlab() -> String {
+    let app = Router::new()
+        .route(
+            "/api/v4/projects/{project}/issues",
+            get(
+                |Path(project): Path<String>, Query(query): Query<HashMap<String, String>>| async move {
+                    assert_eq!(project, "group/sub/app");
+                    assert_eq!(query["state"], "opened");
+                    let page = query["page"].as_str();
+                    let (next, issues) = match page {
+                        "1" => (
+                            "2",
+                            json!([{"iid": 3, "title": "Third", "description": "Body",
+                                    "labels": ["bug"], "state": "opened"}]),
+                        ),
+                        _ => (
+                            "",
+                            json!([{"iid": 1, "title": "First", "description": null,
+                                    "labels": [], "state": "opened"}]),
+                        ),
+                    };
+                    (
+                        [("x-next-page", next)],
+                        Json(issues),
+                    )
+                        .into_response()
+                },
+            ),
+        )
+        .route(
+            "/api/v4/projects/{project}/merge_requests/{iid}",
+            get(|Path((_, iid)): Path<(String, i64)>, headers: HeaderMap| async move {
+                if headers.get("private-token").is_none_or(|t| t != "glpat-test") {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+                match iid {
+                    1 => Ok(Json(json!({"iid": 1, "state": "merged"}))),
+                    2 => Ok(Json(json!({"iid": 2, "state": "opened"}))),
+                    _ => Err(StatusCode::NOT_FOUND),
+                }
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
+}
+
+#[tokio::test]
+async fn test_gitlab_lists_issues_across_pages_and_reads_merge_status() {
+    let base = fake_gitlab().await;
+    let gitlab = GitLab::new(&format!("{base}/"), Some("glpat-test".to_string())).unwrap();
+
+    let issues = gitlab.fetch_issues("group/sub", "app").await.unwrap();
+    let summary: Vec<_> = issues
+        .iter()
+        .map(|i| {
+            (
+                i.number,
+                i.title.as_str(),
+                i.state.as_str(),
+                i.labels.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (3, "Third", "OPEN", vec!["bug".to_string()]),
+            (1, "First", "OPEN", vec![]),
+        ]
+    );
+
+    assert!(gitlab.is_merged("group/sub", "app", 1).await.unwrap());
+    assert!(!gitlab.is_merged("group/sub", "app", 2).await.unwrap());
+    let err = gitlab.is_merged("group/sub", "app", 9).await.unwrap_err();
+    assert!(err.contains("404"), "{err}");
+
+    let anonymous = GitLab::new(&base, None).unwrap();
+    let err = anonymous
+        .is_merged("group/sub", "app", 1)
+        .await
+        .unwrap_err();
+    assert!(err.contains("401"), "{err}");
+}
+
+#[test]
+fn test_credentials_only_go_to_their_instance() {
+    let bind = |base_url: &str| {
+        bind_credential(
+            "GITLAB_TOKEN",
+            Some("glpat-test"),
+            Some("https://gitlab.example.com/"),
+            base_url,
+        )
+    };
+    assert_eq!(bind("https://gitlab.example.com"), Ok(Some("glpat-test")));
+    let err = bind("http://attacker.example").unwrap_err();
+    assert!(
+        err.contains("only sent to https://gitlab.example.com"),
+        "{err}"
+    );
+    // An instance the operator didn't name gets nothing
+    assert!(bind_credential("GITEA_TOKEN", Some("t"), None, "https://x").is_err());
+    // Without a credential any instance will do
+    assert_eq!(
+        bind_credential::<&str>("GITEA_TOKEN", None, None, "https://x"),
+        Ok(None)
+    );
+}
+
+#[tokio::test]
+async fn test_gitlab_repo_fills_the_issue_queue() {
+    let base = fake_gitlab().await;
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let repo = repos::insert(&conn, "group/sub", "app", None, None).unwrap();
+    assert_eq!(repo.forge, "github");
+    let state = AppState::new(conn);
+
+    let config = |kind: &str, base_url: Option<&str>| {
+        Json(ForgeConfig {
+            kind: kind.to_string(),
+            base_url: base_url.map(String::from),
+            jira: None,
+        })
+    };
+    for (kind, base_url) in [
+        ("bitbucket", None),
+        ("github", Some("https://x")),
+        ("gitlab", Some("gitlab.local")),
+    ] {
+        let (status, _) = update_forge(
+            State(state.clone()),
+            Path(repo.repo_id.clone()),
+            config(kind, base_url),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{kind} {base_url:?}");
+    }
+    update_forge(
+        State(state.clone()),
+        Path(repo.repo_id.clone()),
+        config("gitlab", Some(&base)),
+    )
+    .await
+    .unwrap();
+
+    list_repo_issues(
+        State(state.clone()),
+        Path(repo.repo_id.clone()),
+        Query(IssuesQuery::default()),
+    )
+    .await
+    .unwrap();
+    let conn = state.db.lock().unwrap();
+    let mut cached: Vec<i64> = issues_db::list_by_repo(&conn, &repo.repo_id)
+        .unwrap()
+        .iter()
+        .map(|i| i.number)
+        .collect();
+    cached.sort();
+    assert_eq!(cached, vec![1, 3]);
+    assert_eq!(
+        repos::get_by_id(&conn, &repo.repo_id)
+            .unwrap()
+            .unwrap()
+            .forge,
+        "gitlab"
+    );
+}
+
+/// A fake Gitea API for repo `team/app`: a full page of open issues with
+/// a pull request among them, then one more; closed issue 7; and pull
+/// requests 1 (merged) and 2 (open) behind a token
+async fn fake_gitea() -> String {
+    let issue = |number: i64, state: &str, updated_at: &str| {
+        json!({"number": number, "title": format!("Issue {number}"), "body": "",
+               "labels": [{"name": "bug"}], "state": state, "updated_at": updated_at,
+               "pull_request": null})
+    };
+    let app = Router::new()
+        .route(
+            "/api/v1/repos/{owner}/{repo}/issues",
+            get(
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    assert_eq!(query["state"], "open");
+                    assert_eq!(query["type"], "issues");
+                    let limit: i64 = query["limit"].parse().unwrap();
+                    let issues: Vec<_> = match query["page"].as_str() {
+                        "1" => (0..limit)
+                            .map(|i| {
+                                let mut issue =
+                                    issue(100 + i, "open", &format!("2026-01-01T00:00:{:02}Z", i));
+                                if i == 0 {
+                                    issue["pull_request"] = json!({"merged": false});
+                                }
+                                issue
+                            })
+                            .collect(),
+                        _ => vec![issue(3, "open", "2026-02-01T00:00:00Z")],
+                    };
+                    Json(issues)
+                },
+            ),
+        )
+        .route(
+            "/api/v1/repos/{owner}/{repo}/issues/{number}",
+            get(
+                move |Path((_, _, number)): Path<(String, String, i64)>| async move {
+                    match number {
+                        7 => Ok(Json(issue(7, "closed", "2025-12-01T00:00:00Z"))),
+                        _ => Err(StatusCode::NOT_FOUND),
+                    }
+                },
+            ),
+        )
+        .route(
+            "/api/v1/repos/{owner}/{repo}/pulls/{number}",
+            get(
+                |Path((_, _, number)): Path<(String, String, i64)>, headers: HeaderMap| async move {
+                    if headers
+                        .get("authorization")
+                        .is_none_or(|t| t != "token gitea-test")
+                    {
+                        return Err(StatusCode::UNAUTHORIZED);
+                    }
+                    Ok(Json(json!({"number": number, "merged": number == 1})))
+                },
+            ),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
+}
+
+#[tokio::test]
+async fn test_gitea_lists_issues_fetches_one_and_reads_pull_status() {
+    let base = fake_gitea().await;
+    let gitea = Gitea::new(&base, Some("gitea-test".to_string())).unwrap();
+
+    let issues = gitea.fetch_issues("team", "app").await.unwrap();
+    // The pull request is left out; the latest activity comes first
+    assert_eq!(issues.len(), 50);
+    assert_eq!(issues[0].number, 3);
+    assert_eq!(issues[1].number, 149);
+    assert!(issues.iter().all(|i| i.number != 100));
+    assert_eq!(issues[0].state, "OPEN");
+    assert_eq!(issues[0].labels, vec!["bug".to_string()]);
+    assert!(issues[0].body.is_none());
+
+    let closed = gitea.fetch_issue("team", "app", 7).await.unwrap().unwrap();
+    assert_eq!((closed.number, closed.state.as_str()), (7, "CLOSED"));
+    assert!(gitea.fetch_issue("team", "app", 8).await.unwrap().is_none());
+
+    assert!(gitea.is_merged("team", "app", 1).await.unwrap());
+    assert!(!gitea.is_merged("team", "app", 2).await.unwrap());
+    let anonymous = Gitea::new(&base, None).unwrap();
+    let err = anonymous.is_merged("team", "app", 1).await.unwrap_err();
+    assert!(err.contains("401"), "{err}");
+}
+
+#[tokio::test]
+async fn test_gitea_repo_caches_a_closed_issue() {
+    let base = fake_gitea().await;
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let repo = repos::insert(&conn, "team", "app", None, None).unwrap();
+    let state = AppState::new(conn);
+
+    let gitea = |base_url: Option<&str>| {
+        Json(ForgeConfig {
+            kind: "gitea".to_string(),
+            base_url: base_url.map(String::from),
+            jira: None,
+        })
+    };
+    let (status, _) = update_forge(
+        State(state.clone()),
+        Path(repo.repo_id.clone()),
+        gitea(None),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    update_forge(
+        State(state.clone()),
+        Path(repo.repo_id.clone()),
+        gitea(Some(&base)),
+    )
+    .await
+    .unwrap();
+
+    let Json(issue) = get_repo_issue(State(state.clone()), Path((repo.repo_id.clone(), 7)))
+        .await
+        .unwrap();
+    assert_eq!(issue.state, "CLOSED");
+    assert!(!issue.stale);
+    let (status, _) = get_repo_issue(State(state.clone()), Path((repo.repo_id.clone(), 8)))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let conn = state.db.lock().unwrap();
+    let cached = issues_db::get_cached_issue(&conn, &repo.repo_id, 7)
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.title, "Issue 7");
+}
@@ -9,3 +9,122 @@ async fn test_check_status_exists() {
         assert!(status.gh_version.is_some());
     }
 }
+
+#[test]
+fn test_verify_signature() {
+    // HMAC-SHA256("secret", "payload")
+    let sig = "sha256=b82fcb791acec57859b989b430a826488ce2e479fdf92326bd0a2e8375a42ba4";
+    assert!(github::verify_signature("secret", b"payload", sig));
+    assert!(!github::verify_signature("other", b"payload", sig));
+    assert!(!github::verify_signature("secret", b"payload", "sha1=abc"));
+    assert!(!github::verify_signature("secret", b"payload", "sha256=zz"));
+}
+
+#[test]
+fn test_circuit_opens_after_repeated_failures_and_retries_later() {
+    use std::time::{Duration, Instant};
+
+    let mut breaker = github::CircuitBreaker::new();
+    let now = Instant::now();
+    for _ in 1..github::FAILURE_THRESHOLD {
+        breaker.record_failure(now, "gh failed: HTTP 502");
+    }
+    assert!(breaker.allow(now).is_ok());
+    assert_eq!(breaker.health(now).status, "degraded");
+
+    breaker.record_failure(now, "gh failed: HTTP 502");
+    assert!(breaker.allow(now).is_err());
+    let health = breaker.health(now);
+    assert_eq!(health.status, "down");
+    assert_eq!(health.retry_after_secs, Some(60));
+    assert_eq!(health.last_error.as_deref(), Some("gh failed: HTTP 502"));
+
+    // After the cooldown one call goes through; a failure reopens at once
+    let later = now + github::OPEN_DURATION + Duration::from_secs(1);
+    assert!(breaker.allow(later).is_ok());
+    breaker.record_failure(later, "gh failed: timeout");
+    assert!(breaker.allow(later).is_err());
+
+    breaker.record_success();
+    assert!(breaker.allow(later).is_ok());
+    assert_eq!(breaker.health(later).status, "ok");
+}
+
+#[test]
+fn test_is_outage_ignores_request_errors() {
+    assert!(github::is_outage("HTTP 503: Service Unavailable"));
+    assert!(github::is_outage(
+        "error connecting to api.github.com\ncheck your internet connection"
+    ));
+    assert!(!github::is_outage(
+        "GraphQL: Could not resolve to an issue or pull request with the number of 7."
+    ));
+}
+
+#[test]
+fn test_rate_limit_headers_set_the_wait() {
+    use std::time::Duration;
+
+    let exhausted = "HTTP/2.0 403 Forbidden\r\nX-Ratelimit-Remaining: 0\r\nX-Ratelimit-Reset: 1000120\r\n\r\n{\"message\":\"API rate limit exceeded\"}";
+    let limit = github::RateLimit::parse(exhausted);
+    assert_eq!(limit.remaining, Some(0));
+    assert_eq!(limit.wait(1_000_000), Some(Duration::from_secs(120)));
+
+    let secondary = "HTTP/2.0 403 Forbidden\r\nRetry-After: 30\r\n\r\n{}";
+    assert_eq!(
+        github::RateLimit::parse(secondary).wait(0),
+        Some(Duration::from_secs(30))
+    );
+
+    let plenty = "HTTP/2.0 200 OK\nX-Ratelimit-Remaining: 4999\n\n[]";
+    assert_eq!(github::RateLimit::parse(plenty).wait(0), None);
+
+    // Plain gh output carries no headers
+    assert_eq!(
+        github::RateLimit::parse("[{\"number\": 1}]"),
+        github::RateLimit::default()
+    );
+    assert!(github::is_rate_limited(
+        "gh: You have exceeded a secondary rate limit (HTTP 403)"
+    ));
+    assert!(!github::is_rate_limited("gh: Not Found (HTTP 404)"));
+}
+
+#[test]
+fn test_rate_limits_open_the_circuit_with_backoff() {
+    use std::time::{Duration, Instant};
+
+    let mut breaker = github::CircuitBreaker::new();
+    let now = Instant::now();
+
+    // A known reset is waited out exactly
+    breaker.record_rate_limit(now, Some(Duration::from_secs(120)), "rate limit");
+    let health = breaker.health(now);
+    assert_eq!(health.status, "rate_limited");
+    assert_eq!(health.retry_after_secs, Some(120));
+
+    // Without one, each hit in a row waits twice as long
+    breaker.record_rate_limit(now, None, "secondary rate limit");
+    assert_eq!(
+        breaker.health(now).retry_after_secs,
+        Some(2 * github::OPEN_DURATION.as_secs())
+    );
+    breaker.record_rate_limit(now, None, "secondary rate limit");
+    assert_eq!(
+        breaker.health(now).retry_after_secs,
+        Some(4 * github::OPEN_DURATION.as_secs())
+    );
+
+    breaker.record_success();
+    assert_eq!(breaker.health(now).status, "ok");
+}
+
+#[test]
+fn test_link_header_tells_whether_more_pages_follow() {
+    let middle = "HTTP/2.0 200 OK\nLink: <https://api.github.com/repositories/1/issues?page=3>; rel=\"next\", <https://api.github.com/repositories/1/issues?page=9>; rel=\"last\"";
+    assert!(github::has_next_page(middle));
+
+    let last = "HTTP/2.0 200 OK\nLink: <https://api.github.com/repositories/1/issues?page=1>; rel=\"first\", <https://api.github.com/repositories/1/issues?page=8>; rel=\"prev\"";
+    assert!(!github::has_next_page(last));
+    assert!(!github::has_next_page("HTTP/2.0 200 OK\nEtag: \"abc\""));
+}
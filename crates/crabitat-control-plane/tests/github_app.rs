@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axum::Json;
+use axum::Router;
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde_json::{Value, json};
+
+use crabitat_control_plane::github_app::AppAuth;
+
+/// Throwaway key generated for these tests only
+const TEST_KEY: &str = include_str!("fixtures/github_app_key.pem");
+
+/// A fake GitHub API: the app is installed on user `l1x` only, and every
+/// token exchange is counted
+async fn fake_github() -> (String, Arc<AtomicUsize>) {
+    let exchanges = Arc::new(AtomicUsize::new(0));
+    let counter = exchanges.clone();
+    let app = Router::new()
+        .route(
+            "/orgs/{owner}/installation",
+            get(|| async { StatusCode::NOT_FOUND }),
+        )
+        .route(
+            "/users/{owner}/installation",
+            get(|Path(owner): Path<String>| async move {
+                if owner == "l1x" {
+                    Ok(Json(json!({"id": 42})))
+                } else {
+                    Err(StatusCode::NOT_FOUND)
+                }
+            }),
+        )
+        .route(
+            "/app/installations/{id}/access_tokens",
+            post(move |Path(id): Path<u64>, headers: HeaderMap| async move {
+                let auth = headers["authorization"].to_str().unwrap().to_string();
+                let jwt = auth.strip_prefix("Bearer ").unwrap();
+                let claims: Value = serde_json::from_slice(
+                    &URL_SAFE_NO_PAD
+                        .decode(jwt.split('.').nth(1).unwrap())
+                        .unwrap(),
+                )
+                .unwrap();
+                assert_eq!(claims["iss"], "7");
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                Json(
+                    json!({"token": format!("ghs_{id}_{n}"), "expires_at": "2099-01-01T00:00:00Z"}),
+                )
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (base, exchanges)
+}
+
+#[test]
+fn test_jwt_is_rs256_for_the_app() {
+    let app = AppAuth::new(7, TEST_KEY, None, "http://unused").unwrap();
+    let jwt = app.jwt().unwrap();
+    let parts: Vec<&str> = jwt.split('.').collect();
+    assert_eq!(parts.len(), 3);
+    let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+    assert_eq!(header["alg"], "RS256");
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+    assert_eq!(claims["iss"], "7");
+    assert!(claims["exp"].as_u64().unwrap() - claims["iat"].as_u64().unwrap() <= 600);
+    assert_eq!(URL_SAFE_NO_PAD.decode(parts[2]).unwrap().len(), 256);
+
+    assert!(AppAuth::new(7, "not a key", None, "http://unused").is_err());
+}
+
+#[tokio::test]
+async fn test_installation_tokens_are_looked_up_and_cached() {
+    let (base, exchanges) = fake_github().await;
+    let app = AppAuth::new(7, TEST_KEY, None, &base).unwrap();
+
+    assert_eq!(app.token("l1x").await.unwrap(), "ghs_42_0");
+    assert_eq!(app.token("l1x").await.unwrap(), "ghs_42_0");
+    assert_eq!(exchanges.load(Ordering::SeqCst), 1);
+
+    let err = app.token("someone-else").await.unwrap_err();
+    assert!(err.contains("not installed"), "{err}");
+
+    // A fixed installation skips the lookup
+    let app = AppAuth::new(7, TEST_KEY, Some(99), &base).unwrap();
+    assert_eq!(app.token("anyone").await.unwrap(), "ghs_99_1");
+}
@@ -1,18 +1,20 @@
+use axum::body::to_bytes;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 
 use crabitat_control_plane::AppState;
 use crabitat_control_plane::db;
+use crabitat_control_plane::db::issues as issues_db;
 use crabitat_control_plane::db::repos;
-use crabitat_control_plane::handlers::issues::lookup_repo;
+use crabitat_control_plane::handlers::issues::{list_repo_issues, lookup_repo};
+use crabitat_control_plane::models::Issue;
+use crabitat_control_plane::models::issues::IssuesQuery;
 use rusqlite::Connection;
-use std::sync::{Arc, Mutex};
 
 fn setup() -> AppState {
     let conn = Connection::open_in_memory().unwrap();
     db::migrate(&conn);
-    AppState {
-        db: Arc::new(Mutex::new(conn)),
-    }
+    AppState::new(conn)
 }
 
 #[test]
@@ -46,3 +48,110 @@ fn test_lookup_repo_soft_deleted_returns_404() {
     let (status, _) = res.unwrap_err();
     assert_eq!(status, StatusCode::NOT_FOUND);
 }
+
+/// Repo with issues 1..=5 cached; even ones are closed, multiples of three are bugs
+fn setup_with_issues() -> (AppState, String) {
+    let state = setup();
+    let repo_id = {
+        let conn = state.db.lock().unwrap();
+        let repo = repos::insert(&conn, "owner", "name", None, None).unwrap();
+        let issues: Vec<Issue> = (1..=5)
+            .map(|n| Issue {
+                repo_id: repo.repo_id.clone(),
+                number: n,
+                title: format!("Issue {n}"),
+                body: None,
+                labels: if n % 3 == 0 {
+                    vec!["Bug".to_string(), "ui".to_string()]
+                } else {
+                    vec!["ui".to_string()]
+                },
+                state: if n % 2 == 0 { "CLOSED" } else { "OPEN" }.to_string(),
+                fetched_at: String::new(),
+                stale: false,
+            })
+            .collect();
+        issues_db::upsert_issues(&conn, &repo.repo_id, &issues).unwrap();
+        repo.repo_id
+    };
+    (state, repo_id)
+}
+
+/// Issue numbers on one page, and the next page's cursor
+async fn page(state: &AppState, repo_id: &str, query: IssuesQuery) -> (Vec<i64>, Option<String>) {
+    let response = list_repo_issues(
+        State(state.clone()),
+        Path(repo_id.to_string()),
+        Query(query),
+    )
+    .await
+    .unwrap();
+    let next = response
+        .headers()
+        .get("x-next-page")
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let issues: Vec<Issue> = serde_json::from_slice(&body).unwrap();
+    (issues.iter().map(|i| i.number).collect(), next)
+}
+
+#[tokio::test]
+async fn test_list_issues_filters_by_state_and_labels() {
+    let (state, repo_id) = setup_with_issues();
+
+    let (all, next) = page(&state, &repo_id, IssuesQuery::default()).await;
+    assert_eq!(all, vec![5, 4, 3, 2, 1]);
+    assert_eq!(next, None);
+
+    let open = IssuesQuery {
+        state: Some("open".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(page(&state, &repo_id, open).await.0, vec![5, 3, 1]);
+
+    // Every label must match, whatever its case
+    let bugs = IssuesQuery {
+        labels: Some("bug, UI".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(page(&state, &repo_id, bugs).await.0, vec![3]);
+
+    let bad = list_repo_issues(
+        State(state.clone()),
+        Path(repo_id.clone()),
+        Query(IssuesQuery {
+            state: Some("merged".to_string()),
+            ..Default::default()
+        }),
+    )
+    .await;
+    assert_eq!(bad.unwrap_err().0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_list_issues_pages_with_a_cursor() {
+    let (state, repo_id) = setup_with_issues();
+
+    let first = IssuesQuery {
+        per_page: Some(2),
+        ..Default::default()
+    };
+    let (issues, next) = page(&state, &repo_id, first).await;
+    assert_eq!(issues, vec![5, 4]);
+    assert_eq!(next.as_deref(), Some("4"));
+
+    let second = IssuesQuery {
+        per_page: Some(2),
+        page: Some(4),
+        ..Default::default()
+    };
+    let (issues, next) = page(&state, &repo_id, second).await;
+    assert_eq!(issues, vec![3, 2]);
+
+    let last = IssuesQuery {
+        per_page: Some(2),
+        page: next.unwrap().parse().ok(),
+        ..Default::default()
+    };
+    assert_eq!(page(&state, &repo_id, last).await, (vec![1], None));
+}
@@ -1,21 +1,32 @@
+mod common;
+
 use axum::Json;
-use axum::extract::State;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 
+use common::TempDir;
 use crabitat_control_plane::AppState;
+use crabitat_control_plane::cursor::{Cursor, NEXT_CURSOR_HEADER};
 use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions as missions_db;
 use crabitat_control_plane::db::repos as repos_db;
-use crabitat_control_plane::handlers::missions::create_mission;
-use crabitat_control_plane::models::missions::CreateMissionRequest;
-use rusqlite::Connection;
-use std::sync::{Arc, Mutex};
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::analytics::get_replay_comparison;
+use crabitat_control_plane::handlers::missions::{
+    create_mission, list_missions, replay_mission, update_mission,
+};
+use crabitat_control_plane::mission_service::repair_taskless_missions;
+use crabitat_control_plane::models::missions::{
+    CreateMissionBody, CreateMissionRequest, MissionListQuery, ReplayMissionRequest,
+    UpdateMissionRequest,
+};
+use rusqlite::{Connection, params};
 
 fn setup() -> AppState {
     let conn = Connection::open_in_memory().unwrap();
     db::migrate(&conn);
-    AppState {
-        db: Arc::new(Mutex::new(conn)),
-    }
+    AppState::new(conn)
 }
 
 #[tokio::test]
@@ -28,15 +39,369 @@ async fn test_create_mission_soft_deleted_repo_returns_404() {
         repo.repo_id
     };
 
-    let req = CreateMissionRequest {
-        repo_id,
-        issue_number: 1,
-        workflow_name: "test-wf".into(),
-        flavor_id: None,
+    let req = CreateMissionBody {
+        mission: CreateMissionRequest {
+            repo_id,
+            issue_number: 1,
+            workflow_name: "test-wf".into(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+        context_artifacts: Vec::new(),
     };
 
     let result = create_mission(State(state), Json(req)).await;
     assert!(result.is_err());
-    let (status, _) = result.unwrap_err();
+    let (status, Json(body)) = result.unwrap_err();
     assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "repo_not_found");
+    assert_eq!(body["retryable"], false);
+    assert_eq!(body["message"], body["error"]);
+}
+
+/// Temp prompts root with a two-step and a one-step workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("plan.md", "plan"),
+        ("implement.md", "implement"),
+        ("fix.md", "fix"),
+        (
+            "workflows/full.toml",
+            "[workflow]\nname = \"full\"\ndescription = \"d\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n\n[[steps]]\nid = \"implement\"\nprompt_file = \"implement.md\"\n",
+        ),
+        (
+            "workflows/chore.toml",
+            "[workflow]\nname = \"chore\"\ndescription = \"d\"\nversion = \"2\"\n\n[[steps]]\nid = \"fix\"\nprompt_file = \"fix.md\"\n",
+        ),
+    ])
+}
+
+#[tokio::test]
+async fn test_pending_mission_can_be_edited_until_it_starts() {
+    let root = prompts_root();
+    let state = setup();
+    let repo_id = {
+        let conn = state.db.lock().unwrap();
+        settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+        let repo = repos_db::insert(&conn, "owner", "name", None, None).unwrap();
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+            params![repo.repo_id, 1, "Typo", "Body"],
+        )
+        .unwrap();
+        repo.repo_id
+    };
+    let (_, Json(mission)) = create_mission(
+        State(state.clone()),
+        Json(CreateMissionBody {
+            mission: CreateMissionRequest {
+                repo_id,
+                issue_number: 1,
+                workflow_name: "full".into(),
+                flavor_id: None,
+                priority: None,
+                context_from_mission_id: None,
+                exclusive: false,
+            },
+            context_artifacts: Vec::new(),
+        }),
+    )
+    .await
+    .unwrap();
+
+    let Json(edited) = update_mission(
+        State(state.clone()),
+        Path(mission.mission_id.clone()),
+        Json(UpdateMissionRequest {
+            workflow_name: Some("chore".into()),
+            prompt: Some("Also update the changelog.".into()),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(edited.workflow_name, "chore");
+    assert_eq!(edited.prompt.as_deref(), Some("Also update the changelog."));
+
+    let expanded =
+        tasks::list_tasks_for_mission(&state.db.lock().unwrap(), &mission.mission_id).unwrap();
+    assert_eq!(expanded.len(), 1);
+    assert_eq!(expanded[0].step_id, "fix");
+    assert_eq!(expanded[0].status, "queued");
+    assert!(
+        expanded[0]
+            .assembled_prompt
+            .ends_with("# Additional Instructions\nAlso update the changelog.")
+    );
+
+    // Once a crab picks up work the mission is locked
+    tasks::claim_next_task(&state.db.lock().unwrap(), "crab-1")
+        .unwrap()
+        .unwrap();
+    let (status, Json(body)) = update_mission(
+        State(state.clone()),
+        Path(mission.mission_id.clone()),
+        Json(UpdateMissionRequest::default()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "invalid_state");
+}
+
+#[tokio::test]
+async fn test_replay_pins_workflow_version_and_links_back() {
+    let root = prompts_root();
+    let state = setup();
+    let repo_id = {
+        let conn = state.db.lock().unwrap();
+        settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+        let repo = repos_db::insert(&conn, "owner", "name", None, None).unwrap();
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+            params![repo.repo_id, 1, "Typo", "Body"],
+        )
+        .unwrap();
+        repo.repo_id
+    };
+    let (_, Json(original)) = create_mission(
+        State(state.clone()),
+        Json(CreateMissionBody {
+            mission: CreateMissionRequest {
+                repo_id,
+                issue_number: 1,
+                workflow_name: "full".into(),
+                flavor_id: None,
+                priority: Some(3),
+                context_from_mission_id: None,
+                exclusive: false,
+            },
+            context_artifacts: Vec::new(),
+        }),
+    )
+    .await
+    .unwrap();
+    let _ = update_mission(
+        State(state.clone()),
+        Path(original.mission_id.clone()),
+        Json(UpdateMissionRequest {
+            prompt: Some("Keep it small.".into()),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap();
+
+    // Pinned to a version the workflow isn't at
+    let (status, Json(body)) = replay_mission(
+        State(state.clone()),
+        Path(original.mission_id.clone()),
+        Some(Json(ReplayMissionRequest {
+            workflow_name: Some("chore".into()),
+            workflow_version: Some("1".into()),
+            ..Default::default()
+        })),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["message"].as_str().unwrap().contains("version 2"));
+
+    let (status, Json(replay)) = replay_mission(
+        State(state.clone()),
+        Path(original.mission_id.clone()),
+        Some(Json(ReplayMissionRequest {
+            workflow_name: Some("chore".into()),
+            workflow_version: Some("2".into()),
+            ..Default::default()
+        })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_ne!(replay.mission_id, original.mission_id);
+    assert_eq!(
+        replay.replay_of_mission_id.as_deref(),
+        Some(original.mission_id.as_str())
+    );
+    assert_eq!(replay.workflow_version.as_deref(), Some("2"));
+    assert_eq!(replay.issue_number, 1);
+    assert_eq!(replay.priority, 3);
+    assert_eq!(replay.prompt.as_deref(), Some("Keep it small."));
+    let expanded =
+        tasks::list_tasks_for_mission(&state.db.lock().unwrap(), &replay.mission_id).unwrap();
+    assert_eq!(expanded.len(), 1);
+    assert!(expanded[0].assembled_prompt.ends_with("Keep it small."));
+
+    let Json(comparison) =
+        get_replay_comparison(State(state.clone()), Path(original.mission_id.clone()))
+            .await
+            .unwrap();
+    assert_eq!(comparison.original.mission_id, original.mission_id);
+    assert_eq!(comparison.replays.len(), 1);
+    assert_eq!(comparison.replays[0].mission_id, replay.mission_id);
+}
+
+#[tokio::test]
+async fn test_taskless_mission_is_expanded_once() {
+    let root = prompts_root();
+    let state = setup();
+    let repo_id = {
+        let conn = state.db.lock().unwrap();
+        settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+        let repo = repos_db::insert(&conn, "owner", "name", None, None).unwrap();
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+            params![repo.repo_id, 1, "Typo", "Body"],
+        )
+        .unwrap();
+        repo.repo_id
+    };
+    let (_, Json(mission)) = create_mission(
+        State(state.clone()),
+        Json(CreateMissionBody {
+            mission: CreateMissionRequest {
+                repo_id,
+                issue_number: 1,
+                workflow_name: "full".into(),
+                flavor_id: None,
+                priority: None,
+                context_from_mission_id: None,
+                exclusive: false,
+            },
+            context_artifacts: Vec::new(),
+        }),
+    )
+    .await
+    .unwrap();
+
+    let conn = state.db.lock().unwrap();
+    conn.execute(
+        "DELETE FROM tasks WHERE mission_id = ?1",
+        [&mission.mission_id],
+    )
+    .unwrap();
+    assert_eq!(
+        repair_taskless_missions(&conn).unwrap(),
+        [mission.mission_id.as_str()]
+    );
+    let expanded = tasks::list_tasks_for_mission(&conn, &mission.mission_id).unwrap();
+    let steps: Vec<_> = expanded
+        .iter()
+        .map(|t| (t.step_id.as_str(), t.status.as_str()))
+        .collect();
+    assert_eq!(steps, [("plan", "queued"), ("implement", "blocked")]);
+
+    // Nothing left to repair, so nothing is expanded twice
+    assert!(repair_taskless_missions(&conn).unwrap().is_empty());
+    assert_eq!(
+        tasks::list_tasks_for_mission(&conn, &mission.mission_id)
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_mission_list_pages_by_cursor() {
+    let state = setup();
+    let insert = |issue_number: i64| {
+        let conn = state.db.lock().unwrap();
+        let repo_id = match repos_db::list(&conn).unwrap().first() {
+            Some(repo) => repo.repo_id.clone(),
+            None => {
+                repos_db::insert(&conn, "l1x", "test", None, None)
+                    .unwrap()
+                    .repo_id
+            }
+        };
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, 'T', 'B')",
+            params![repo_id, issue_number],
+        )
+        .unwrap();
+        let req = CreateMissionRequest {
+            repo_id,
+            issue_number,
+            workflow_name: "wf".into(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        };
+        let mission = missions_db::insert_mission(&conn, &req, "b").unwrap();
+        // Two missions share each second, so the id breaks the tie
+        conn.execute(
+            "UPDATE missions SET created_at = ?1 WHERE mission_id = ?2",
+            params![
+                format!("2026-01-01T00:00:0{}Z", issue_number / 2),
+                mission.mission_id
+            ],
+        )
+        .unwrap();
+    };
+    for n in 1..=5 {
+        insert(n);
+    }
+
+    let page = |cursor: Option<String>, limit: Option<i64>| {
+        let state = state.clone();
+        async move {
+            let query = MissionListQuery {
+                cursor,
+                limit,
+                ..Default::default()
+            };
+            let response = list_missions(State(state), Query(query)).await.unwrap();
+            let next = response
+                .headers()
+                .get(NEXT_CURSOR_HEADER)
+                .map(|v| v.to_str().unwrap().to_string());
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let numbers: Vec<i64> = serde_json::from_slice::<Vec<serde_json::Value>>(&body)
+                .unwrap()
+                .iter()
+                .map(|m| m["issue_number"].as_i64().unwrap())
+                .collect();
+            (numbers, next)
+        }
+    };
+
+    let (all, next) = page(None, None).await;
+    assert_eq!(all.len(), 5);
+    assert!(next.is_none());
+
+    let (first, next) = page(None, Some(2)).await;
+    assert_eq!(first, all[..2]);
+    // A mission created mid-way doesn't shift the pages that follow
+    insert(6);
+    let (second, next) = page(next, Some(2)).await;
+    assert_eq!(second, all[2..4]);
+    let (last, next) = page(next, Some(2)).await;
+    assert_eq!(last, all[4..]);
+    assert!(next.is_none());
+
+    let bad = MissionListQuery {
+        cursor: Some("not a cursor".into()),
+        ..Default::default()
+    };
+    let (status, _) = list_missions(State(state.clone()), Query(bad))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let too_many = MissionListQuery {
+        limit: Some(1000),
+        ..Default::default()
+    };
+    let (status, _) = list_missions(State(state.clone()), Query(too_many))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let cursor = Cursor::new("2026-01-01T00:00:00Z", "m-1");
+    assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
 }
@@ -1,20 +1,26 @@
 use axum::Json;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
 
 use crabitat_control_plane::AppState;
 use crabitat_control_plane::db;
+use crabitat_control_plane::db::crabs;
 use crabitat_control_plane::db::repos;
-use crabitat_control_plane::handlers::repos::{delete_repo, get_repo, list_repos};
-use rusqlite::Connection;
-use std::sync::{Arc, Mutex};
+use crabitat_control_plane::db::{missions, tasks};
+use crabitat_control_plane::handlers::crabs::get_repo_guide;
+use crabitat_control_plane::handlers::repos::{
+    delete_repo, get_activity, get_issue_completion, get_repo, list_repos, update_issue_completion,
+};
+use crabitat_control_plane::models::analytics::ActivityQuery;
+use crabitat_control_plane::models::crabs::GuideQuery;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::repos::IssueCompletion;
+use rusqlite::{Connection, params};
 
 fn setup() -> AppState {
     let conn = Connection::open_in_memory().unwrap();
     db::migrate(&conn);
-    AppState {
-        db: Arc::new(Mutex::new(conn)),
-    }
+    AppState::new(conn)
 }
 
 #[tokio::test]
@@ -65,3 +71,234 @@ async fn test_list_repos_excludes_deleted() {
     assert_eq!(all.len(), 1);
     assert_eq!(all[0].name, "active");
 }
+
+#[tokio::test]
+async fn test_activity_counts_busy_minutes_once() {
+    let state = setup();
+    let repo_id = {
+        let conn = state.db.lock().unwrap();
+        let repo = repos::insert(&conn, "owner", "name", None, None).unwrap();
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 1, 't', 'b')",
+            [&repo.repo_id],
+        )
+        .unwrap();
+        let mission = missions::insert_mission(
+            &conn,
+            &CreateMissionRequest {
+                repo_id: repo.repo_id.clone(),
+                issue_number: 1,
+                workflow_name: "wf".into(),
+                flavor_id: None,
+                priority: None,
+                context_from_mission_id: None,
+                exclusive: false,
+            },
+            "mission/issue-1",
+        )
+        .unwrap();
+        let task =
+            tasks::insert_task(&conn, &mission.mission_id, "step", 0, "p", 0, "queued").unwrap();
+        // Two overlapping runs, and a debug run that doesn't count
+        for (run_id, started, finished, debug) in [
+            ("a", "2026-01-01T10:10:00Z", "2026-01-01T10:40:00Z", 0),
+            ("b", "2026-01-01T10:30:00Z", "2026-01-01T11:15:00Z", 0),
+            ("c", "2026-01-01T11:30:00Z", "2026-01-01T11:50:00Z", 1),
+        ] {
+            conn.execute(
+                "INSERT INTO runs (run_id, task_id, status, started_at, finished_at, debug)
+                 VALUES (?1, ?2, 'completed', ?3, ?4, ?5)",
+                params![run_id, task.task_id, started, finished, debug],
+            )
+            .unwrap();
+        }
+        repo.repo_id
+    };
+
+    let Json(activity) = get_activity(
+        State(state.clone()),
+        Path(repo_id.clone()),
+        Query(ActivityQuery {
+            granularity: None,
+            from: Some("2026-01-01T10:20:00Z".into()),
+            to: Some("2026-01-01T12:00:00Z".into()),
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(activity.from, "2026-01-01T10:00:00Z");
+    let cells: Vec<_> = activity
+        .buckets
+        .iter()
+        .map(|b| (b.start.as_str(), b.busy_minutes, b.runs_started))
+        .collect();
+    assert_eq!(
+        cells,
+        [
+            ("2026-01-01T10:00:00Z", 50, 2),
+            ("2026-01-01T11:00:00Z", 15, 0)
+        ]
+    );
+
+    let (status, _) = get_activity(
+        State(state),
+        Path(repo_id),
+        Query(ActivityQuery {
+            granularity: Some("week".into()),
+            from: None,
+            to: None,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_guide_reflects_roles_and_live_workflows() {
+    let root = std::env::temp_dir().join(format!("crabitat-guide-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("workflows")).unwrap();
+    std::fs::write(root.join("review.md"), "review").unwrap();
+    for role in ["coder", "reviewer"] {
+        std::fs::create_dir_all(root.join("roles").join(role)).unwrap();
+        std::fs::write(root.join("roles").join(role).join("00-base.md"), role).unwrap();
+    }
+    std::fs::write(
+        root.join("workflows/review.toml"),
+        "[workflow]\nname = \"review\"\ndescription = \"d\"\n\n[[steps]]\nid = \"review\"\nprompt_file = \"review.md\"\nread_only = true\nexecutor = \"claude\"\nexecutor_min_version = \"1.0\"\n",
+    )
+    .unwrap();
+
+    let state = setup();
+    let repo_id = {
+        let conn = state.db.lock().unwrap();
+        db::settings::set(&conn, "prompts_root", root.to_str().unwrap()).unwrap();
+        let repo = repos::insert(&conn, "l1x", "crabitat", None, None).unwrap();
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 1, 'T', 'B')",
+            params![repo.repo_id],
+        )
+        .unwrap();
+        let req = CreateMissionRequest {
+            repo_id: repo.repo_id.clone(),
+            issue_number: 1,
+            workflow_name: "review".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        };
+        missions::insert_mission(&conn, &req, "mission/1").unwrap();
+        crabs::record_executors(&conn, "crab-1", &[]).unwrap();
+        crabs::record_role(&conn, "crab-1", Some("coder")).unwrap();
+        repo.repo_id
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert("host", "cp.example:3001".parse().unwrap());
+    let Json(guide) = get_repo_guide(
+        State(state.clone()),
+        Path(repo_id.clone()),
+        Query(GuideQuery {
+            role: Some("reviewer".to_string()),
+        }),
+        headers.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(guide.control_plane_url, "http://cp.example:3001");
+    assert_eq!(guide.role_stack_files, ["roles/reviewer/00-base.md"]);
+    assert_eq!(guide.unfilled_roles, ["reviewer"]);
+    assert_eq!(guide.workflows.len(), 1);
+    let step = &guide.workflows[0].steps[0];
+    assert_eq!(step.executor.as_deref(), Some("claude>=1.0"));
+    assert!(step.read_only);
+    assert!(guide.command.ends_with("--role reviewer"));
+
+    let result = get_repo_guide(
+        State(state),
+        Path(repo_id),
+        Query(GuideQuery {
+            role: Some("tester".to_string()),
+        }),
+        headers,
+    )
+    .await;
+    assert_eq!(result.unwrap_err().0, StatusCode::BAD_REQUEST);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test]
+async fn test_issue_completion_applies_to_newly_completed_missions() {
+    let state = setup();
+    let (repo_id, mission_id) = {
+        let conn = state.db.lock().unwrap();
+        let repo = repos::insert(&conn, "l1x", "test", None, None).unwrap();
+        conn.execute(
+            "INSERT INTO github_issues_cache (repo_id, number, title) VALUES (?1, 7, 'Issue')",
+            [&repo.repo_id],
+        )
+        .unwrap();
+        let mission = missions::insert_mission(
+            &conn,
+            &CreateMissionRequest {
+                repo_id: repo.repo_id.clone(),
+                issue_number: 7,
+                workflow_name: "wf".to_string(),
+                flavor_id: None,
+                priority: None,
+                context_from_mission_id: None,
+                exclusive: false,
+            },
+            "crabitat/issue-7",
+        )
+        .unwrap();
+        (repo.repo_id, mission.mission_id)
+    };
+
+    let completion = IssueCompletion {
+        close: true,
+        label: Some(" done-by-crabitat ".to_string()),
+    };
+    let status = update_issue_completion(
+        State(state.clone()),
+        Path(repo_id.clone()),
+        Json(completion),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let Json(stored) = get_issue_completion(State(state.clone()), Path(repo_id.clone()))
+        .await
+        .unwrap();
+    assert!(stored.close);
+    assert_eq!(stored.label.as_deref(), Some("done-by-crabitat"));
+
+    let empty_label = IssueCompletion {
+        close: false,
+        label: Some("  ".to_string()),
+    };
+    let (status, _) = update_issue_completion(
+        State(state.clone()),
+        Path(repo_id.clone()),
+        Json(empty_label),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let conn = state.db.lock().unwrap();
+    assert!(missions::list_issue_done_changes(&conn).unwrap().is_empty());
+    conn.execute(
+        "UPDATE missions SET status = 'completed' WHERE mission_id = ?1",
+        [&mission_id],
+    )
+    .unwrap();
+    let changes = missions::list_issue_done_changes(&conn).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].issue_number, 7);
+    assert_eq!(changes[0].completion.as_ref(), Some(&stored));
+
+    missions::set_issue_done(&conn, &mission_id).unwrap();
+    assert!(missions::list_issue_done_changes(&conn).unwrap().is_empty());
+}
@@ -0,0 +1,624 @@
+mod common;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::artifacts;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::tasks::{
+    HeartbeatQuery, TaskQuery, UpdateStatusRequest, add_context, claim_task, get_next_task,
+    heartbeat, reject_task, retry_task, send_message, update_task_status,
+};
+use crabitat_control_plane::mission_service::{
+    CreateMissionError, create_mission, create_mission_with_artifacts, promote_next_tier,
+};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{
+    AddContextRequest, CreateRunRequest, RejectTaskRequest, RetryTaskRequest, SendMessageRequest,
+    Task,
+};
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with a plan -> implement workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("plan.md", "plan {{context}}"),
+        ("implement.md", "implement {{context}}"),
+        (
+            "workflows/build.toml",
+            r#"
+[workflow]
+name = "build"
+description = "plan then implement"
+
+[[steps]]
+id = "plan"
+prompt_file = "plan.md"
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+depends_on = ["plan"]
+"#,
+        ),
+    ])
+}
+
+/// Mission with a completed plan step and a failed implement step
+fn setup(root: &TempDir) -> (AppState, Task) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Add retries", "Body"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "build".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+
+    let mission_tasks = tasks::list_tasks_for_mission(&conn, &mission.mission_id).unwrap();
+    let plan = mission_tasks.iter().find(|t| t.step_id == "plan").unwrap();
+    let implement = mission_tasks
+        .iter()
+        .find(|t| t.step_id == "implement")
+        .unwrap();
+    tasks::insert_run(
+        &conn,
+        &plan.task_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            logs: Some("PLAN OUTPUT".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    tasks::update_task_status(&conn, &plan.task_id, "completed").unwrap();
+    tasks::update_task_status(&conn, &implement.task_id, "failed").unwrap();
+
+    let implement = tasks::get_task(&conn, &implement.task_id).unwrap().unwrap();
+    (AppState::new(conn), implement)
+}
+
+#[tokio::test]
+async fn test_retry_with_guidance_keeps_upstream_context() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+
+    let status = retry_task(
+        State(state.clone()),
+        Path(implement.task_id.clone()),
+        Some(Json(RetryTaskRequest {
+            guidance: Some("reuse the existing backoff helper".to_string()),
+            ..Default::default()
+        })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let conn = state.db.lock().unwrap();
+    let retried = tasks::get_task(&conn, &implement.task_id).unwrap().unwrap();
+    assert_eq!(retried.status, "queued");
+    assert_eq!(retried.retry_count, 1);
+    assert!(retried.assembled_prompt.contains("PLAN OUTPUT"));
+    assert!(
+        retried
+            .assembled_prompt
+            .contains("<guidance>\nreuse the existing backoff helper\n</guidance>")
+    );
+}
+
+#[tokio::test]
+async fn test_retry_rejects_task_that_has_not_failed() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    {
+        let conn = state.db.lock().unwrap();
+        tasks::update_task_status(&conn, &implement.task_id, "running").unwrap();
+    }
+
+    let (status, _) = retry_task(State(state), Path(implement.task_id), None)
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_reject_is_limited_to_the_holder() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    {
+        let conn = state.db.lock().unwrap();
+        tasks::update_task_status(&conn, &implement.task_id, "running").unwrap();
+        conn.execute(
+            "UPDATE tasks SET heartbeat_worker_id = 'crab-a' WHERE task_id = ?1",
+            [&implement.task_id],
+        )
+        .unwrap();
+    }
+    let reject = |worker_id: &str| {
+        Json(RejectTaskRequest {
+            worker_id: Some(worker_id.to_string()),
+            reason: "no docker".to_string(),
+        })
+    };
+
+    let (status, _) = reject_task(
+        State(state.clone()),
+        Path(implement.task_id.clone()),
+        reject("crab-b"),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let Json(rejection) = reject_task(State(state), Path(implement.task_id), reject("crab-a"))
+        .await
+        .unwrap();
+    assert_eq!(rejection["worker_id"], "crab-a");
+}
+
+/// The implement step running again, with a failed attempt recorded by the crab
+fn fail_attempt(state: &AppState, task_id: &str) {
+    let conn = state.db.lock().unwrap();
+    tasks::update_task_status(&conn, task_id, "running").unwrap();
+    tasks::insert_run(
+        &conn,
+        task_id,
+        &CreateRunRequest {
+            status: "failed".to_string(),
+            summary: Some("cargo test: 2 failures".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+fn report_failed() -> Json<UpdateStatusRequest> {
+    Json(UpdateStatusRequest {
+        status: "failed".to_string(),
+        worker_id: Some("crab-a".to_string()),
+        reason: None,
+    })
+}
+
+#[tokio::test]
+async fn test_failed_task_is_requeued_with_previous_attempt() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    fail_attempt(&state, &implement.task_id);
+
+    update_task_status(
+        State(state.clone()),
+        Path(implement.task_id.clone()),
+        report_failed(),
+    )
+    .await
+    .unwrap();
+
+    let conn = state.db.lock().unwrap();
+    let retried = tasks::get_task(&conn, &implement.task_id).unwrap().unwrap();
+    assert_eq!(retried.status, "queued");
+    assert_eq!(retried.retry_count, 1);
+    assert!(retried.assembled_prompt.contains("PLAN OUTPUT"));
+    assert!(retried.assembled_prompt.contains("cargo test: 2 failures"));
+    let mission = missions::get_mission(&conn, &retried.mission_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(mission.status, "pending");
+}
+
+#[tokio::test]
+async fn test_failed_task_without_retries_fails_mission() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    fail_attempt(&state, &implement.task_id);
+    {
+        let conn = state.db.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET retry_count = max_retries WHERE task_id = ?1",
+            [&implement.task_id],
+        )
+        .unwrap();
+    }
+
+    update_task_status(
+        State(state.clone()),
+        Path(implement.task_id.clone()),
+        report_failed(),
+    )
+    .await
+    .unwrap();
+
+    let conn = state.db.lock().unwrap();
+    let task = tasks::get_task(&conn, &implement.task_id).unwrap().unwrap();
+    assert_eq!(task.status, "failed");
+    let mission = missions::get_mission(&conn, &task.mission_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(mission.status, "failed");
+}
+
+#[test]
+fn test_context_from_mission_seeds_first_steps() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    let mut conn = state.db.lock().unwrap();
+    let repo_id = missions::get_mission(&conn, &implement.mission_id)
+        .unwrap()
+        .unwrap()
+        .repo_id;
+    let plan_run = tasks::list_tasks_for_mission(&conn, &implement.mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == "plan")
+        .and_then(|t| tasks::latest_run_for_task(&conn, &t.task_id).unwrap())
+        .unwrap();
+    for (name, kind, content) in [
+        ("design.md", Some("design"), &b"DESIGN DOC"[..]),
+        ("test.log", Some("test-log"), &b"TEST LOG"[..]),
+        ("mock.png", Some("design"), &[0xff, 0xd8][..]),
+    ] {
+        artifacts::insert(&conn, &plan_run.run_id, name, kind, "text/plain", content).unwrap();
+    }
+    let mut req = CreateMissionRequest {
+        repo_id,
+        issue_number: 1,
+        workflow_name: "build".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: Some(implement.mission_id.clone()),
+        exclusive: false,
+    };
+
+    let follow_up =
+        create_mission_with_artifacts(&mut conn, &req, &["design".to_string()]).unwrap();
+    assert_eq!(
+        follow_up.context_from_mission_id.as_deref(),
+        Some(implement.mission_id.as_str())
+    );
+    let stored = missions::get_mission(&conn, &follow_up.mission_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        stored.context_from_mission_id,
+        follow_up.context_from_mission_id
+    );
+
+    let follow_tasks = tasks::list_tasks_for_mission(&conn, &follow_up.mission_id).unwrap();
+    let plan = follow_tasks.iter().find(|t| t.step_id == "plan").unwrap();
+    assert!(
+        plan.assembled_prompt
+            .contains(&format!("<from_mission id=\"{}\"", implement.mission_id))
+    );
+    assert!(plan.assembled_prompt.contains("PLAN OUTPUT"));
+    // Only the selected artifacts come along; binary ones without their content
+    assert!(plan.assembled_prompt.contains("DESIGN DOC"));
+    assert!(
+        plan.assembled_prompt
+            .contains("<artifact name=\"mock.png\" step=\"plan\"")
+    );
+    assert!(!plan.assembled_prompt.contains("TEST LOG"));
+    let next = follow_tasks
+        .iter()
+        .find(|t| t.step_id == "implement")
+        .unwrap();
+    assert!(!next.assembled_prompt.contains("from_mission"));
+
+    req.context_from_mission_id = Some("missing".to_string());
+    assert!(matches!(
+        create_mission(&mut conn, &req),
+        Err(CreateMissionError::SourceMissionNotFound)
+    ));
+
+    // Another repo's mission can't seed this one
+    let other = repos::insert(&conn, "l1x", "other", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 1, 'Other', 'Body')",
+        [&other.repo_id],
+    )
+    .unwrap();
+    req.repo_id = other.repo_id;
+    req.context_from_mission_id = Some(implement.mission_id.clone());
+    assert!(matches!(
+        create_mission(&mut conn, &req),
+        Err(CreateMissionError::SourceMissionOtherRepo)
+    ));
+}
+
+#[tokio::test]
+async fn test_messages_reach_running_task_once() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    let send = |body: &str| {
+        send_message(
+            State(state.clone()),
+            Path(implement.task_id.clone()),
+            Json(SendMessageRequest {
+                body: body.to_string(),
+                sender: Some("alice".to_string()),
+            }),
+        )
+    };
+
+    // Only a running task has an agent to talk to
+    let (status, _) = send("focus on the tests").await.unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    {
+        let conn = state.db.lock().unwrap();
+        tasks::update_task_status(&conn, &implement.task_id, "running").unwrap();
+        tasks::record_heartbeat(&conn, &implement.task_id, "crab-1").unwrap();
+    }
+    let (status, _) = send("  ").await.unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, Json(message)) = send("focus on the tests").await.unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(message["worker_id"], "crab-1");
+
+    let beat = || {
+        heartbeat(
+            State(state.clone()),
+            Path(implement.task_id.clone()),
+            Query(HeartbeatQuery {
+                worker_id: "crab-1".to_string(),
+                pool_ready: None,
+                pool_hits: None,
+                pool_misses: None,
+            }),
+        )
+    };
+    let Json(first) = beat().await.unwrap();
+    assert_eq!(first["status"], "running");
+    assert_eq!(first["messages"][0]["body"], "focus on the tests");
+    let Json(second) = beat().await.unwrap();
+    assert_eq!(second["messages"].as_array().unwrap().len(), 0);
+
+    let conn = state.db.lock().unwrap();
+    let timeline = tasks::list_messages(&conn, &implement.task_id).unwrap();
+    assert_eq!(timeline.len(), 1);
+    assert_eq!(timeline[0].sender.as_deref(), Some("alice"));
+    assert!(timeline[0].delivered_at.is_some());
+}
+
+#[tokio::test]
+async fn test_only_the_holder_reports_on_a_running_task() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    {
+        // The watchdog requeued crab-1's task and crab-2 claimed it
+        let conn = state.db.lock().unwrap();
+        tasks::update_task_status(&conn, &implement.task_id, "running").unwrap();
+        tasks::record_heartbeat(&conn, &implement.task_id, "crab-2").unwrap();
+    }
+    let beat = |worker_id: &str| {
+        heartbeat(
+            State(state.clone()),
+            Path(implement.task_id.clone()),
+            Query(HeartbeatQuery {
+                worker_id: worker_id.to_string(),
+                pool_ready: None,
+                pool_hits: None,
+                pool_misses: None,
+            }),
+        )
+    };
+    let report = |worker_id: &str| {
+        update_task_status(
+            State(state.clone()),
+            Path(implement.task_id.clone()),
+            Json(UpdateStatusRequest {
+                status: "completed".to_string(),
+                worker_id: Some(worker_id.to_string()),
+                reason: None,
+            }),
+        )
+    };
+
+    let (status, _) = beat("crab-1").await.unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = report("crab-1").await.unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    {
+        let conn = state.db.lock().unwrap();
+        assert_eq!(
+            tasks::holder(&conn, &implement.task_id).unwrap().as_deref(),
+            Some("crab-2")
+        );
+    }
+
+    let Json(beat) = beat("crab-2").await.unwrap();
+    assert_eq!(beat["status"], "running");
+    assert_eq!(report("crab-2").await.unwrap(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_next_only_peeks_at_debug_runs() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    let run = {
+        let conn = state.db.lock().unwrap();
+        tasks::insert_debug_run(&conn, &implement.task_id, "tweaked", None).unwrap()
+    };
+    let query = || {
+        Query(TaskQuery {
+            worker_id: Some("crab-a".to_string()),
+            delta: false,
+            role: None,
+            executors: None,
+        })
+    };
+
+    // Peeking twice shows the same run and leaves it for the claim
+    for _ in 0..2 {
+        let Json(next) = get_next_task(State(state.clone()), query()).await.unwrap();
+        assert_eq!(next["debug_run_id"], run.run_id);
+    }
+    {
+        let conn = state.db.lock().unwrap();
+        assert_eq!(
+            tasks::get_run(&conn, &run.run_id).unwrap().unwrap().status,
+            "pending"
+        );
+    }
+
+    let Json(claimed) = claim_task(State(state.clone()), query()).await.unwrap();
+    assert_eq!(claimed["debug_run_id"], run.run_id);
+    let conn = state.db.lock().unwrap();
+    let run = tasks::get_run(&conn, &run.run_id).unwrap().unwrap();
+    assert_eq!(run.status, "running");
+    assert_eq!(run.worker_id.as_deref(), Some("crab-a"));
+}
+
+#[tokio::test]
+async fn test_next_peeks_on_a_read_only_connection() {
+    let root = prompts_root();
+    let dir = TempDir::new("peek");
+    let path = dir.0.join("crabitat.db");
+    let path = path.to_str().unwrap();
+    let mut conn = db::init(path);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 1, 'Issue', 'Body')",
+        [&repo.repo_id],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "build".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    let state = AppState::with_read_pool(conn, path).unwrap();
+
+    let Json(next) = get_next_task(
+        State(state.clone()),
+        Query(TaskQuery {
+            worker_id: Some("crab-a".to_string()),
+            delta: false,
+            role: None,
+            executors: None,
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(next["task"]["step_id"], "plan");
+
+    // Neither stickiness nor the scheduler's reasoning was recorded
+    let conn = state.db.lock().unwrap();
+    let mission = missions::get_mission(&conn, &mission.mission_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(mission.last_worker_id, None);
+    let decisions: i64 = conn
+        .query_row("SELECT COUNT(*) FROM scheduler_decisions", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(decisions, 0);
+}
+
+#[tokio::test]
+async fn test_operator_note_joins_context_on_promotion() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+    let note = |body: &str| {
+        add_context(
+            State(state.clone()),
+            Path(implement.task_id.clone()),
+            Json(AddContextRequest {
+                body: body.to_string(),
+                author: Some("alice".to_string()),
+            }),
+        )
+    };
+
+    // Notes only go on tasks that haven't been queued yet
+    let (status, _) = note("keep the public API").await.unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    tasks::update_task_status(&state.db.lock().unwrap(), &implement.task_id, "blocked").unwrap();
+    let (status, Json(added)) = note("keep the public API").await.unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(added["author"], "alice");
+
+    let conn = state.db.lock().unwrap();
+    let plan = tasks::list_tasks_for_mission(&conn, &implement.mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == "plan")
+        .unwrap();
+    promote_next_tier(&conn, &plan).unwrap();
+    let promoted = tasks::get_task(&conn, &implement.task_id).unwrap().unwrap();
+    assert_eq!(promoted.status, "queued");
+    let prompt = &promoted.assembled_prompt;
+    let upstream = prompt.find("PLAN OUTPUT").unwrap();
+    let guidance = prompt
+        .find("<operator_note author=\"alice\">\nkeep the public API\n</operator_note>")
+        .unwrap();
+    assert!(upstream < guidance);
+}
+
+#[tokio::test]
+async fn test_transition_errors_carry_codes() {
+    let root = prompts_root();
+    let (state, implement) = setup(&root);
+
+    // failed -> completed is not an allowed move
+    let (status, Json(body)) = update_task_status(
+        State(state.clone()),
+        Path(implement.task_id.clone()),
+        Json(UpdateStatusRequest {
+            status: "completed".to_string(),
+            worker_id: None,
+            reason: None,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "invalid_state");
+
+    let (status, Json(body)) = update_task_status(
+        State(state),
+        Path("missing".to_string()),
+        Json(UpdateStatusRequest {
+            status: "queued".to_string(),
+            worker_id: None,
+            reason: None,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "task_not_found");
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use std::collections::BTreeMap;
+use std::fs;
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::handlers::workflows::{
+    create_workflow, delete_workflow, get_workflow, list_all_workflows, update_workflow,
+};
+use crabitat_control_plane::models::workflows::SaveWorkflowRequest;
+use rusqlite::Connection;
+
+/// Temp prompts root with one workflow file on disk
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("plan.md", "plan"),
+        (
+            "workflows/disk.toml",
+            "[workflow]\nname = \"disk\"\ndescription = \"d\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n",
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> AppState {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+    AppState::new(conn)
+}
+
+fn request(name: &str, prompt_file: &str, prompts: &[(&str, &str)]) -> Json<SaveWorkflowRequest> {
+    Json(SaveWorkflowRequest {
+        content: format!(
+            "[workflow]\nname = \"{name}\"\ndescription = \"d\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n\n[[steps]]\nid = \"review\"\nprompt_file = \"{prompt_file}\"\ndepends_on = [\"plan\"]\n"
+        ),
+        prompts: prompts
+            .iter()
+            .map(|(path, body)| (path.to_string(), body.to_string()))
+            .collect::<BTreeMap<_, _>>(),
+    })
+}
+
+#[tokio::test]
+async fn test_stored_workflow_lifecycle() {
+    let root = prompts_root();
+    let state = setup(&root);
+
+    let (status, Json(created)) = create_workflow(
+        State(state.clone()),
+        request("api", "api/review.md", &[("api/review.md", "review it")]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(created.stored);
+    assert_eq!(created.steps.len(), 2);
+    let previews: Vec<(&str, usize, Option<&str>)> = created
+        .previews
+        .iter()
+        .map(|p| (p.step_id.as_str(), p.step_order, p.prompt.as_deref()))
+        .collect();
+    assert_eq!(
+        previews,
+        vec![("plan", 0, Some("plan")), ("review", 1, Some("review it"))]
+    );
+
+    let Json(listed) = list_all_workflows(State(state.clone())).await.unwrap();
+    let mut names: Vec<(String, bool)> = listed.into_iter().map(|w| (w.name, w.stored)).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![("api".to_string(), true), ("disk".to_string(), false)]
+    );
+
+    // Same name again is a conflict, as is a name taken by a file
+    let (status, _) = create_workflow(State(state.clone()), request("api", "plan.md", &[]))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = create_workflow(State(state.clone()), request("disk", "plan.md", &[]))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Updating drops prompts that are no longer listed
+    let Json(updated) = update_workflow(
+        State(state.clone()),
+        Path("api".to_string()),
+        request("api", "plan.md", &[]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(updated.steps[1].prompt_file, "plan.md");
+
+    assert_eq!(
+        delete_workflow(State(state.clone()), Path("api".to_string()))
+            .await
+            .unwrap(),
+        StatusCode::NO_CONTENT
+    );
+    let (status, _) = get_workflow(State(state), Path("api".to_string()))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_save_workflow_rejects_bad_input() {
+    let root = prompts_root();
+    let state = setup(&root);
+
+    // Prompt that is neither stored nor on disk
+    let (status, Json(body)) =
+        create_workflow(State(state.clone()), request("api", "missing.md", &[]))
+            .await
+            .unwrap_err();
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["details"]["issues"][0]["kind"], "missing_prompt");
+
+    let (status, _) = create_workflow(
+        State(state.clone()),
+        request("api", "plan.md", &[("../escape.md", "x")]),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Steps can't point outside the prompts directories either
+    let secret = root.0.with_extension("secret.md");
+    fs::write(&secret, "secret").unwrap();
+    for prompt_file in ["../escape.md", secret.to_str().unwrap(), "plan"] {
+        let (status, _) = create_workflow(State(state.clone()), request("api", prompt_file, &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{prompt_file}");
+    }
+    let _ = fs::remove_file(&secret);
+
+    // Disk workflows are edited on disk
+    let (status, _) = update_workflow(
+        State(state.clone()),
+        Path("disk".to_string()),
+        request("disk", "plan.md", &[]),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = delete_workflow(State(state), Path("nope".to_string()))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
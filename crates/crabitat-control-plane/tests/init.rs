@@ -0,0 +1,87 @@
+mod common;
+
+use common::{TempDir, git};
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::workflow_packs;
+use crabitat_control_plane::init::{self, InitArgs};
+use std::fs;
+use std::path::Path;
+
+/// Git repo holding a one-workflow pack
+fn starter_pack() -> TempDir {
+    let pack = TempDir::new("starter");
+    fs::create_dir_all(pack.0.join("workflows")).unwrap();
+    fs::write(pack.0.join("fix.md"), "Fix it: {{mission}}").unwrap();
+    fs::write(
+        pack.0.join("workflows/chore.toml"),
+        "[workflow]\nname = \"chore\"\ndescription = \"d\"\n\n[[steps]]\nid = \"fix\"\nprompt_file = \"fix.md\"\n",
+    )
+    .unwrap();
+    git(&pack.0, &["init", "--quiet"]);
+    git(&pack.0, &["add", "-A"]);
+    git(&pack.0, &["commit", "--quiet", "-m", "starter"]);
+    pack
+}
+
+#[tokio::test]
+async fn test_init_sets_up_deployment_and_is_rerunnable() {
+    let dir = TempDir::new("init");
+    let pack = starter_pack();
+    let args = InitArgs {
+        config: dir.0.join("crabitat.env"),
+        db: Some(dir.0.join("crabitat.db").to_string_lossy().to_string()),
+        listen: Some("127.0.0.1:4001".to_string()),
+        prompts_root: Some(dir.0.join("prompts")),
+        repo: Some("l1x/crabitat".to_string()),
+        local_path: Some("/src/crabitat".to_string()),
+        pack: Some(pack.0.to_string_lossy().to_string()),
+        yes: true,
+        ..Default::default()
+    };
+
+    let mut out = Vec::new();
+    init::run(&args, &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("repo: registered l1x/crabitat"), "{}", out);
+    assert!(out.contains("crabitat-crab --api-url http://127.0.0.1:4001"));
+
+    let env = fs::read_to_string(&args.config).unwrap();
+    assert!(env.contains("LISTEN_ADDR=127.0.0.1:4001"));
+
+    {
+        let conn = db::init(args.db.as_deref().unwrap());
+        let root = settings::get(&conn, "prompts_root").unwrap().unwrap();
+        assert!(Path::new(&root).join("workflows").is_dir());
+        let repo = repos::get_by_owner_name(&conn, "l1x", "crabitat")
+            .unwrap()
+            .unwrap();
+        assert_eq!(repo.local_path.as_deref(), Some("/src/crabitat"));
+        let packs = workflow_packs::list(&conn).unwrap();
+        assert_eq!(packs.len(), 1);
+        assert_eq!(packs[0].workflows, vec!["chore"]);
+    }
+
+    // A second run keeps the config and the repo
+    let mut out = Vec::new();
+    init::run(&args, &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("config: keeping existing"), "{}", out);
+    assert!(out.contains("already registered"), "{}", out);
+}
+
+#[tokio::test]
+async fn test_init_rejects_malformed_repo() {
+    let dir = TempDir::new("init");
+    let args = InitArgs {
+        config: dir.0.join("crabitat.env"),
+        db: Some(dir.0.join("crabitat.db").to_string_lossy().to_string()),
+        prompts_root: Some(dir.0.join("prompts")),
+        repo: Some("crabitat".to_string()),
+        yes: true,
+        ..Default::default()
+    };
+    let err = init::run(&args, &mut Vec::new()).await.unwrap_err();
+    assert!(err.contains("owner/name"), "{}", err);
+}
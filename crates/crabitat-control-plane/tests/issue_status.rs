@@ -0,0 +1,160 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::issue_status::{find_pr_url, pending, record_run};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{CreateRunRequest, NewTask, StepConfig};
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+/// Running mission on l1x/test#1 with one running `implement` task
+fn running_mission(conn: &Connection) -> (String, String) {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let mission = missions::insert_mission(
+        conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "ship".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+        "crabitat/issue-1",
+    )
+    .unwrap();
+    let task = tasks::insert_new_task(
+        conn,
+        &NewTask {
+            mission_id: &mission.mission_id,
+            step_id: "implement",
+            step_order: 0,
+            assembled_prompt: "implement",
+            max_retries: 0,
+            status: "running",
+            step_config: StepConfig::default(),
+        },
+    )
+    .unwrap();
+    set_status(conn, &mission.mission_id, "running");
+    (mission.mission_id, task.task_id)
+}
+
+fn set_status(conn: &Connection, mission_id: &str, status: &str) {
+    conn.execute(
+        "UPDATE missions SET status = ?1, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+         WHERE mission_id = ?2",
+        params![status, mission_id],
+    )
+    .unwrap();
+}
+
+fn pending_states(conn: &Connection) -> Vec<(String, Option<i64>)> {
+    missions::list_issue_comment_changes(conn)
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.state, c.comment_id))
+        .collect()
+}
+
+#[test]
+fn test_find_pr_url() {
+    assert_eq!(
+        find_pr_url(
+            "l1x",
+            "test",
+            "Opened (https://github.com/L1x/Test/pull/42).\nDone"
+        )
+        .as_deref(),
+        Some("https://github.com/L1x/Test/pull/42")
+    );
+    // Enterprise hosts count; other repos and non-links don't
+    assert_eq!(
+        find_pr_url("l1x", "test", "see https://git.corp/l1x/test/pull/7/files").as_deref(),
+        Some("https://git.corp/l1x/test/pull/7")
+    );
+    assert!(find_pr_url("l1x", "test", "https://github.com/other/test/pull/1").is_none());
+    assert!(find_pr_url("l1x", "test", "/l1x/test/pull/3 and l1x/test/pull/4").is_none());
+    assert!(find_pr_url("l1x", "test", "https://github.com/l1x/test/pull/new").is_none());
+}
+
+#[test]
+fn test_status_comment_follows_mission_state() {
+    let conn = test_conn();
+    settings::set(&conn, "public_url", "https://crabs.example/").unwrap();
+    let (mission_id, task_id) = running_mission(&conn);
+    assert_eq!(pending_states(&conn), vec![("running".to_string(), None)]);
+
+    let comments = pending(&conn).unwrap();
+    let body = &comments[0].1;
+    assert!(body.contains("Mission running"));
+    assert!(body.contains("| `implement` | running |"));
+    assert!(body.contains(&format!("(https://crabs.example/missions/{})", mission_id)));
+
+    missions::set_issue_comment(&conn, &mission_id, 900, "running").unwrap();
+    assert!(pending_states(&conn).is_empty());
+
+    // A failed run's link doesn't count; a completed one's does, once
+    let run = |status: &str, logs: &str| CreateRunRequest {
+        status: status.to_string(),
+        logs: Some(logs.to_string()),
+        ..Default::default()
+    };
+    let failed = run("failed", "https://github.com/l1x/test/pull/5");
+    assert_eq!(record_run(&conn, &task_id, &failed).unwrap(), None);
+    let opened = run("completed", "Created https://github.com/l1x/test/pull/6");
+    assert_eq!(
+        record_run(&conn, &task_id, &opened).unwrap().as_deref(),
+        Some("https://github.com/l1x/test/pull/6")
+    );
+    let again = run("completed", "https://github.com/l1x/test/pull/8");
+    assert_eq!(record_run(&conn, &task_id, &again).unwrap(), None);
+
+    assert_eq!(
+        pending_states(&conn),
+        vec![("pr_opened".to_string(), Some(900))]
+    );
+    assert!(
+        pending(&conn).unwrap()[0]
+            .1
+            .contains("Pull request: https://github.com/l1x/test/pull/6")
+    );
+    missions::set_issue_comment(&conn, &mission_id, 900, "pr_opened").unwrap();
+
+    set_status(&conn, &mission_id, "completed");
+    assert_eq!(
+        pending_states(&conn),
+        vec![("completed".to_string(), Some(900))]
+    );
+}
+
+#[test]
+fn test_old_finished_missions_get_no_comment() {
+    let conn = test_conn();
+    let (mission_id, _) = running_mission(&conn);
+    conn.execute(
+        "UPDATE missions SET status = 'failed',
+             updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-2 days')
+         WHERE mission_id = ?1",
+        [&mission_id],
+    )
+    .unwrap();
+    assert!(pending_states(&conn).is_empty());
+
+    // Pending missions have nothing to show yet
+    set_status(&conn, &mission_id, "pending");
+    assert!(pending_states(&conn).is_empty());
+}
@@ -0,0 +1,241 @@
+mod common;
+
+use axum::Json;
+use axum::Router;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::issues as issues_db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::triggers;
+use crabitat_control_plane::handlers::issues::get_repo_issue;
+use crabitat_control_plane::handlers::repos::update_forge;
+use crabitat_control_plane::jira;
+use crabitat_control_plane::models::repos::{ForgeConfig, JiraConfig};
+use crabitat_control_plane::models::triggers::{CreateTriggerRequest, TriggerMatcher};
+use rusqlite::Connection;
+
+/// Transitions the fake Jira was asked for: (ticket, transition id)
+type Transitions = Arc<Mutex<Vec<(String, String)>>>;
+
+fn ticket(key: &str, labels: &[&str], category: &str) -> Value {
+    json!({"key": key, "fields": {
+        "summary": format!("Ticket {key}"), "description": "Body", "labels": labels,
+        "status": {"name": "Whatever", "statusCategory": {"key": category}}}})
+}
+
+/// A fake Jira site with project `PLAT`: two pages of unresolved tickets,
+/// one of them moved in from another project; done ticket PLAT-7; and a
+/// `Close` transition leading to `Done`
+async fn fake_jira() -> (String, Transitions) {
+    let transitions = Transitions::default();
+    let recorded = transitions.clone();
+    let app = Router::new()
+        .route(
+            "/rest/api/2/search/jql",
+            get(|Query(query): Query<HashMap<String, String>>| async move {
+                let jql = &query["jql"];
+                assert!(jql.starts_with("project = \"PLAT\" AND statusCategory != Done"));
+                assert!(
+                    jql.contains("AND (labels = crabitat OR priority = High)"),
+                    "{jql}"
+                );
+                assert!(jql.ends_with("ORDER BY updated DESC"), "{jql}");
+                match query.get("nextPageToken").map(String::as_str) {
+                    None => Json(json!({
+                        "issues": [ticket("PLAT-3", &["crabitat"], "new"),
+                                   ticket("OLD-9", &["crabitat"], "new")],
+                        "nextPageToken": "p2"})),
+                    Some("p2") => Json(json!({
+                        "issues": [ticket("PLAT-1", &[], "indeterminate")], "isLast": true})),
+                    Some(other) => panic!("unexpected page token {other}"),
+                }
+            }),
+        )
+        .route(
+            "/rest/api/2/issue/{key}",
+            get(|Path(key): Path<String>| async move {
+                match key.as_str() {
+                    "PLAT-7" => Ok(Json(ticket("PLAT-7", &[], "done"))),
+                    _ => Err(StatusCode::NOT_FOUND),
+                }
+            }),
+        )
+        .route(
+            "/rest/api/2/issue/{key}/transitions",
+            get(|| async {
+                Json(json!({"transitions": [
+                    {"id": "21", "name": "Start", "to": {"name": "In Progress"}},
+                    {"id": "31", "name": "Close", "to": {"name": "Done"}}]}))
+            })
+            .post(
+                |State(recorded): State<Transitions>,
+                 Path(key): Path<String>,
+                 Json(body): Json<Value>| async move {
+                    let id = body["transition"]["id"].as_str().unwrap().to_string();
+                    recorded.lock().unwrap().push((key, id));
+                    StatusCode::NO_CONTENT
+                },
+            ),
+        )
+        .with_state(recorded);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (base, transitions)
+}
+
+/// Temp prompts root with a one-step workflow `ship`
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("implement.md", "implement"),
+        (
+            "workflows/ship.toml",
+            "[workflow]\nname = \"ship\"\ndescription = \"d\"\n\n[[steps]]\nid = \"implement\"\nprompt_file = \"implement.md\"\n",
+        ),
+    ])
+}
+
+fn jira_config(project: &str, jql: Option<&str>) -> JiraConfig {
+    JiraConfig {
+        project: project.to_string(),
+        jql: jql.map(String::from),
+        done_transition: Some("done".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_jira_repo_queues_tickets_and_completes_them() {
+    let (base, transitions) = fake_jira().await;
+    let root = prompts_root();
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+    let repo = repos::insert(&conn, "l1x", "app", None, None).unwrap();
+    triggers::insert(
+        &conn,
+        &CreateTriggerRequest {
+            repo_id: repo.repo_id.clone(),
+            event_type: "issue".to_string(),
+            matcher: TriggerMatcher {
+                label: Some("crabitat".to_string()),
+                ..Default::default()
+            },
+            workflow_name: "ship".to_string(),
+            flavor_id: None,
+            priority: 5,
+        },
+    )
+    .unwrap();
+    let state = AppState::new(conn);
+
+    let config = |kind: &str, base_url: Option<&str>, jira: Option<JiraConfig>| {
+        Json(ForgeConfig {
+            kind: kind.to_string(),
+            base_url: base_url.map(String::from),
+            jira,
+        })
+    };
+    for (kind, base_url, jira) in [
+        ("jira", None, Some(jira_config("PLAT", None))),
+        ("jira", Some(base.as_str()), None),
+        (
+            "jira",
+            Some(base.as_str()),
+            Some(jira_config("PL AT", None)),
+        ),
+        (
+            "jira",
+            Some(base.as_str()),
+            Some(jira_config("PLAT", Some("labels = x ORDER BY rank"))),
+        ),
+        ("github", None, Some(jira_config("PLAT", None))),
+    ] {
+        let (status, _) = update_forge(
+            State(state.clone()),
+            Path(repo.repo_id.clone()),
+            config(kind, base_url, jira.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "{kind} {base_url:?} {jira:?}"
+        );
+    }
+    update_forge(
+        State(state.clone()),
+        Path(repo.repo_id.clone()),
+        config(
+            "jira",
+            Some(&base),
+            Some(jira_config(
+                " PLAT ",
+                Some(" labels = crabitat OR priority = High "),
+            )),
+        ),
+    )
+    .await
+    .unwrap();
+
+    // Both pages are cached; only the labelled ticket matches the trigger
+    let report = jira::sync(&state).await.unwrap();
+    assert_eq!(report.issues, 2);
+    assert_eq!(report.missions.len(), 1);
+    {
+        let conn = state.db.lock().unwrap();
+        let mut cached: Vec<i64> = issues_db::list_by_repo(&conn, &repo.repo_id)
+            .unwrap()
+            .iter()
+            .map(|i| i.number)
+            .collect();
+        cached.sort();
+        assert_eq!(cached, vec![1, 3]);
+        let mission = missions::get_mission(&conn, &report.missions[0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(mission.issue_number, 3);
+        assert_eq!(mission.priority, 5);
+    }
+
+    // A ticket already queued isn't queued twice
+    assert!(jira::sync(&state).await.unwrap().missions.is_empty());
+    assert!(transitions.lock().unwrap().is_empty());
+
+    state
+        .db
+        .lock()
+        .unwrap()
+        .execute(
+            "UPDATE missions SET status = 'completed' WHERE mission_id = ?1",
+            [&report.missions[0]],
+        )
+        .unwrap();
+    assert_eq!(jira::sync(&state).await.unwrap().completed, 1);
+    assert_eq!(
+        *transitions.lock().unwrap(),
+        vec![("PLAT-3".to_string(), "31".to_string())]
+    );
+    assert_eq!(jira::sync(&state).await.unwrap().completed, 0);
+
+    // Done tickets are fetched one by one, like closed issues elsewhere
+    let Json(issue) = get_repo_issue(State(state.clone()), Path((repo.repo_id.clone(), 7)))
+        .await
+        .unwrap();
+    assert_eq!(issue.state, "CLOSED");
+    assert_eq!(issue.title, "Ticket PLAT-7");
+    let (status, _) = get_repo_issue(State(state.clone()), Path((repo.repo_id.clone(), 8)))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,49 @@
+use axum::http::StatusCode;
+use crabitat_control_plane::metrics::Metrics;
+use std::time::Duration;
+
+#[test]
+fn test_histogram_buckets_and_errors() {
+    let metrics = Metrics::default();
+    metrics.record("GET", "/v1/repos", StatusCode::OK, Duration::from_millis(3));
+    metrics.record(
+        "GET",
+        "/v1/repos",
+        StatusCode::OK,
+        Duration::from_millis(40),
+    );
+    metrics.record(
+        "GET",
+        "/v1/repos",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Duration::from_millis(7000),
+    );
+    metrics.record(
+        "POST",
+        "/v1/missions",
+        StatusCode::NOT_FOUND,
+        Duration::from_millis(1),
+    );
+
+    let out = metrics.render();
+    let repos = "method=\"GET\",route=\"/v1/repos\"";
+    assert!(out.contains(&format!(
+        "crabitat_http_request_duration_ms_bucket{{{repos},le=\"5\"}} 1"
+    )));
+    assert!(out.contains(&format!(
+        "crabitat_http_request_duration_ms_bucket{{{repos},le=\"50\"}} 2"
+    )));
+    assert!(out.contains(&format!(
+        "crabitat_http_request_duration_ms_bucket{{{repos},le=\"+Inf\"}} 3"
+    )));
+    assert!(out.contains(&format!(
+        "crabitat_http_request_duration_ms_count{{{repos}}} 3"
+    )));
+    assert!(out.contains(&format!(
+        "crabitat_http_errors_total{{{repos},class=\"5xx\"}} 1"
+    )));
+    assert!(out.contains(
+        "crabitat_http_errors_total{method=\"POST\",route=\"/v1/missions\",class=\"4xx\"} 1"
+    ));
+    assert!(out.contains("crabitat_db_slow_queries_total"));
+}
@@ -0,0 +1,87 @@
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::events;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::notify;
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_mission(conn: &Connection, repo_name: &str) -> (String, String) {
+    let repo = repos::insert(conn, "l1x", repo_name, None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, 7, 'Issue', 'Body')",
+        params![repo.repo_id],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id.clone(),
+        issue_number: 7,
+        workflow_name: "wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    let mission = missions::insert_mission(conn, &req, "mission/branch").unwrap();
+    (repo.repo_id, mission.mission_id)
+}
+
+#[test]
+fn test_milestones_notify_repos_with_a_webhook() {
+    let conn = test_conn();
+    let (repo_id, mission_id) = setup_mission(&conn, "loud");
+    let (_, quiet_mission) = setup_mission(&conn, "quiet");
+    repos::set_slack_webhook(&conn, &repo_id, Some("https://hooks.slack.test/abc")).unwrap();
+    settings::set(&conn, "console_url", "https://console.test/").unwrap();
+    assert!(
+        repos::get_by_id(&conn, &repo_id)
+            .unwrap()
+            .unwrap()
+            .slack_notifications
+    );
+
+    let cursor = events::latest_seq(&conn).unwrap();
+    let task = tasks::insert_task(&conn, &mission_id, "review", 0, "p", 3, "queued").unwrap();
+    tasks::insert_task(&conn, &quiet_mission, "review", 0, "p", 3, "running").unwrap();
+    missions::recalculate_mission_status(&conn, &quiet_mission).unwrap();
+    for status in ["running", "awaiting_approval", "completed"] {
+        tasks::update_task_status(&conn, &task.task_id, status).unwrap();
+        missions::recalculate_mission_status(&conn, &mission_id).unwrap();
+    }
+
+    let (last_seq, notifications) = notify::pending(&conn, cursor, 100).unwrap();
+    assert_eq!(last_seq, events::latest_seq(&conn).unwrap());
+    let texts: Vec<&str> = notifications.iter().map(|n| n.text.as_str()).collect();
+    let link = format!("<https://console.test/missions/{mission_id}|l1x/loud#7>");
+    assert_eq!(
+        texts,
+        [
+            format!(":rocket: Mission started: {link} (wf)"),
+            format!(":raised_hand: Task `review` is waiting for approval: {link}"),
+            format!(":white_check_mark: Mission completed: {link}"),
+        ]
+    );
+    assert!(
+        notifications
+            .iter()
+            .all(|n| n.webhook_url == "https://hooks.slack.test/abc")
+    );
+
+    // Nothing new, nothing to send; clearing the webhook silences the repo
+    let (_, notifications) = notify::pending(&conn, last_seq, 100).unwrap();
+    assert!(notifications.is_empty());
+    repos::set_slack_webhook(&conn, &repo_id, None).unwrap();
+    tasks::update_task_status(&conn, &task.task_id, "failed").unwrap();
+    missions::recalculate_mission_status(&conn, &mission_id).unwrap();
+    let (_, notifications) = notify::pending(&conn, last_seq, 100).unwrap();
+    assert!(notifications.is_empty());
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::mission_service::{create_mission, promote_next_tier};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{CreateRunRequest, Task};
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with a plan -> review workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        (
+            "partials/rules.md",
+            "Team: {{ vars.team }}. Work in {{ worktree_path }}.",
+        ),
+        // Not a valid template, so it falls back to plain placeholders
+        ("plan.md", "plan {{mission}} {% broken"),
+        (
+            "review.md",
+            r#"{% include "partials/rules.md" %}
+Issue #{{ issue.number }} in {{ repo.owner }}/{{ repo.name }} on {{ branch }}.
+{% for label in issue.labels %}[{{ label }}]{% endfor %}
+{% if steps.plan %}Plan said: {{ steps.plan.summary }}{% else %}No plan yet.{% endif %}"#,
+        ),
+        (
+            "workflows/reviewed.toml",
+            r#"
+[workflow]
+name = "reviewed"
+description = "plan, then review"
+
+[[steps]]
+id = "plan"
+prompt_file = "plan.md"
+
+[[steps]]
+id = "review"
+prompt_file = "review.md"
+depends_on = ["plan"]
+"#,
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> (Connection, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+    settings::set(&conn, "template_var_team", "Platform").unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body, labels) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![repo.repo_id, 1, "Fix login", "it breaks", r#"["bug","ui"]"#],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "reviewed".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    (conn, mission.mission_id)
+}
+
+fn task(conn: &Connection, mission_id: &str, step_id: &str) -> Task {
+    tasks::list_tasks_for_mission(conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == step_id)
+        .unwrap()
+}
+
+#[test]
+fn test_prompt_renders_includes_loops_and_vars() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+
+    let review = task(&conn, &mission_id, "review").assembled_prompt;
+    assert!(review.contains("Team: Platform."), "{review}");
+    assert!(review.contains("Work in {{worktree_path}}."), "{review}");
+    assert!(review.contains("Issue #1 in l1x/test"), "{review}");
+    assert!(review.contains("[bug][ui]"), "{review}");
+    assert!(review.contains("No plan yet."), "{review}");
+}
+
+#[test]
+fn test_prompt_sees_prior_step_results() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+
+    let plan = task(&conn, &mission_id, "plan");
+    tasks::insert_run(
+        &conn,
+        &plan.task_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            summary: Some("touch two files".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    tasks::update_task_status(&conn, &plan.task_id, "completed").unwrap();
+    promote_next_tier(&conn, &plan).unwrap();
+
+    let review = task(&conn, &mission_id, "review").assembled_prompt;
+    assert!(review.contains("Plan said: touch two files"), "{review}");
+}
+
+#[test]
+fn test_invalid_template_falls_back_to_placeholders() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+
+    let plan = task(&conn, &mission_id, "plan").assembled_prompt;
+    assert!(
+        plan.contains("plan Fix login\n\nit breaks {% broken"),
+        "{plan}"
+    );
+}
@@ -0,0 +1,262 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::analytics;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::tasks::{UpdateStatusRequest, update_task_status};
+use crabitat_control_plane::mission_service::{GateOutcome, apply_quality_gate};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::{CreateRunRequest, NewTask, StepConfig, Task};
+use rusqlite::{Connection, params};
+
+fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    conn
+}
+
+fn setup_mission(conn: &Connection) -> String {
+    let repo = repos::insert(conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo.repo_id,
+        issue_number: 1,
+        workflow_name: "review-wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+/// implement (order 0, completed) -> review (order 1, gated)
+fn setup_pipeline(
+    conn: &Connection,
+    min_score: Option<i64>,
+    on_fail: Option<&str>,
+) -> (Task, Task) {
+    let mission_id = setup_mission(conn);
+    let implement =
+        tasks::insert_task(conn, &mission_id, "implement", 0, "impl", 3, "completed").unwrap();
+    let review = tasks::insert_new_task(
+        conn,
+        &NewTask {
+            mission_id: &mission_id,
+            step_id: "review",
+            step_order: 1,
+            assembled_prompt: "review",
+            max_retries: 2,
+            status: "completed",
+            step_config: StepConfig {
+                min_score,
+                on_fail: on_fail.map(String::from),
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+    (implement, review)
+}
+
+fn record_score(conn: &Connection, task_id: &str, score: Option<i64>, worker: &str) {
+    let req = CreateRunRequest {
+        status: "completed".to_string(),
+        summary: Some("needs more tests".to_string()),
+        score,
+        worker_id: Some(worker.to_string()),
+        ..Default::default()
+    };
+    tasks::insert_run(conn, task_id, &req).unwrap();
+}
+
+#[test]
+fn test_step_config_round_trips() {
+    let conn = test_conn();
+    let (_, review) = setup_pipeline(&conn, Some(8), Some("implement"));
+
+    let fetched = tasks::get_task(&conn, &review.task_id).unwrap().unwrap();
+    assert_eq!(fetched.step_config.min_score, Some(8));
+    assert_eq!(fetched.step_config.on_fail.as_deref(), Some("implement"));
+}
+
+#[test]
+fn test_gate_passes_without_min_score() {
+    let conn = test_conn();
+    let (_, review) = setup_pipeline(&conn, None, Some("implement"));
+    record_score(&conn, &review.task_id, Some(2), "crab-a");
+
+    assert_eq!(
+        apply_quality_gate(&conn, &review).unwrap(),
+        GateOutcome::Passed
+    );
+}
+
+#[test]
+fn test_gate_passes_at_threshold() {
+    let conn = test_conn();
+    let (_, review) = setup_pipeline(&conn, Some(8), Some("implement"));
+    record_score(&conn, &review.task_id, Some(8), "crab-a");
+
+    assert_eq!(
+        apply_quality_gate(&conn, &review).unwrap(),
+        GateOutcome::Passed
+    );
+}
+
+#[test]
+fn test_low_score_reroutes_to_fix_step() {
+    let conn = test_conn();
+    let (implement, review) = setup_pipeline(&conn, Some(8), Some("implement"));
+    record_score(&conn, &review.task_id, Some(5), "crab-a");
+
+    let outcome = apply_quality_gate(&conn, &review).unwrap();
+    assert_eq!(
+        outcome,
+        GateOutcome::Rerouted {
+            fix_task_id: implement.task_id.clone()
+        }
+    );
+
+    let implement = tasks::get_task(&conn, &implement.task_id).unwrap().unwrap();
+    assert_eq!(implement.status, "queued");
+    let review = tasks::get_task(&conn, &review.task_id).unwrap().unwrap();
+    assert_eq!(review.status, "blocked");
+    assert_eq!(review.retry_count, 1);
+}
+
+#[test]
+fn test_missing_score_fails_gate_without_on_fail() {
+    let conn = test_conn();
+    let (_, review) = setup_pipeline(&conn, Some(8), None);
+    record_score(&conn, &review.task_id, None, "crab-a");
+
+    let outcome = apply_quality_gate(&conn, &review).unwrap();
+    assert!(matches!(outcome, GateOutcome::Failed { .. }));
+    let review = tasks::get_task(&conn, &review.task_id).unwrap().unwrap();
+    assert_eq!(review.status, "failed");
+}
+
+#[test]
+fn test_gate_fails_once_retries_exhausted() {
+    let conn = test_conn();
+    let (_, review) = setup_pipeline(&conn, Some(8), Some("implement"));
+    tasks::increment_task_retry(&conn, &review.task_id).unwrap();
+    tasks::increment_task_retry(&conn, &review.task_id).unwrap();
+    tasks::update_task_status(&conn, &review.task_id, "completed").unwrap();
+    record_score(&conn, &review.task_id, Some(3), "crab-a");
+
+    let review = tasks::get_task(&conn, &review.task_id).unwrap().unwrap();
+    let outcome = apply_quality_gate(&conn, &review).unwrap();
+    assert!(matches!(outcome, GateOutcome::Failed { .. }));
+}
+
+#[test]
+fn test_score_trends_by_worker_and_workflow() {
+    let conn = test_conn();
+    let (implement, review) = setup_pipeline(&conn, Some(8), Some("implement"));
+    record_score(&conn, &review.task_id, Some(6), "crab-a");
+    record_score(&conn, &review.task_id, Some(9), "crab-a");
+    record_score(&conn, &implement.task_id, Some(7), "crab-b");
+    record_score(&conn, &implement.task_id, None, "crab-b");
+
+    let by_worker = analytics::score_trends(&conn, "worker").unwrap();
+    assert_eq!(by_worker.len(), 2);
+    assert_eq!(by_worker[0].group, "crab-a");
+    assert_eq!(by_worker[0].runs, 2);
+    assert_eq!(by_worker[0].avg_score, 7.5);
+    assert_eq!(by_worker[0].min_score, 6);
+    assert_eq!(by_worker[0].max_score, 9);
+    assert_eq!(by_worker[1].group, "crab-b");
+    assert_eq!(by_worker[1].runs, 1);
+
+    // Debug runs are excluded from trends
+    tasks::insert_debug_run(&conn, &review.task_id, "tweaked", None).unwrap();
+    tasks::claim_debug_run(&conn, None).unwrap();
+    let debug_run = tasks::list_runs_for_task(&conn, &review.task_id).unwrap();
+    let debug_id = debug_run.iter().find(|r| r.debug).unwrap().run_id.clone();
+    let finished = CreateRunRequest {
+        status: "completed".to_string(),
+        score: Some(1),
+        ..Default::default()
+    };
+    tasks::finish_debug_run(&conn, &review.task_id, &debug_id, &finished).unwrap();
+
+    let by_workflow = analytics::score_trends(&conn, "workflow").unwrap();
+    assert_eq!(by_workflow.len(), 1);
+    assert_eq!(by_workflow[0].group, "review-wf");
+    assert_eq!(by_workflow[0].runs, 3);
+
+    assert!(analytics::score_trends(&conn, "repo").is_err());
+}
+
+#[test]
+fn test_reroute_records_transitions() {
+    let conn = test_conn();
+    let (implement, review) = setup_pipeline(&conn, Some(8), Some("implement"));
+    record_score(&conn, &review.task_id, Some(5), "crab-a");
+    apply_quality_gate(&conn, &review).unwrap();
+
+    let history = tasks::list_transitions(&conn, &implement.task_id).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].from_status, "completed");
+    assert_eq!(history[0].to_status, "queued");
+    assert_eq!(history[0].actor, "quality-gate");
+    assert!(
+        history[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .contains("below min_score")
+    );
+}
+
+#[tokio::test]
+async fn test_gate_error_holds_the_task() {
+    let conn = test_conn();
+    let (implement, review) = setup_pipeline(&conn, Some(7), None);
+    tasks::update_task_status(&conn, &review.task_id, "running").unwrap();
+    let ship = tasks::insert_task(
+        &conn,
+        &implement.mission_id,
+        "ship",
+        2,
+        "ship",
+        0,
+        "blocked",
+    )
+    .unwrap();
+    // The gate can't read the run it should score
+    conn.execute_batch("ALTER TABLE runs RENAME TO runs_gone")
+        .unwrap();
+    let state = AppState::new(conn);
+
+    let status = update_task_status(
+        State(state.clone()),
+        Path(review.task_id.clone()),
+        Json(UpdateStatusRequest {
+            status: "completed".to_string(),
+            worker_id: None,
+            reason: None,
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let conn = state.db.lock().unwrap();
+    let ship = tasks::get_task(&conn, &ship.task_id).unwrap().unwrap();
+    assert_eq!(ship.status, "blocked");
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db::{self, ReadConn, settings};
+
+#[test]
+fn test_reads_dont_wait_on_an_open_write() {
+    let dir = TempDir::new("pool");
+    let path = dir.0.join("crabitat.db");
+    let path = path.to_str().unwrap();
+    let state = AppState::with_read_pool(db::init(path), path).unwrap();
+    settings::set(&state.db.lock().unwrap(), "prompts_root", "/before").unwrap();
+
+    // Hold the writer mid-transaction; reads still go through and see the
+    // last committed value
+    let writer = state.db.lock().unwrap();
+    writer.execute_batch("BEGIN IMMEDIATE").unwrap();
+    settings::set(&writer, "prompts_root", "/after").unwrap();
+
+    let reader = state.read();
+    assert!(matches!(reader, ReadConn::Pooled(_)));
+    assert_eq!(
+        settings::get(&reader, "prompts_root").unwrap().as_deref(),
+        Some("/before")
+    );
+    // Pooled connections can't write
+    assert!(settings::set(&reader, "prompts_root", "/reader").is_err());
+    drop(reader);
+
+    writer.execute_batch("COMMIT").unwrap();
+    drop(writer);
+    assert_eq!(
+        settings::get(&state.read(), "prompts_root")
+            .unwrap()
+            .as_deref(),
+        Some("/after")
+    );
+}
+
+#[test]
+fn test_reads_share_the_writer_without_a_pool() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let state = AppState::new(conn);
+    assert!(matches!(state.read(), ReadConn::Shared(_)));
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use axum::Json;
+use axum::extract::{Path, State};
+
+use common::TempDir;
+use crabitat_control_plane::AppState;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::handlers::tasks::{
+    UpdateStatusRequest, approve_task, create_run, update_task_status,
+};
+use crabitat_control_plane::mission_service::create_mission;
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::repos::RiskRules;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+use crabitat_control_plane::risk::{RISK_REVIEW_STEP, assess, validate};
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with an implement -> pr workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("implement.md", "implement {{context}}"),
+        ("pr.md", "pr {{context}}"),
+        (
+            "workflows/ship.toml",
+            r#"
+[workflow]
+name = "ship"
+description = "implement, open a PR"
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+
+[[steps]]
+id = "pr"
+prompt_file = "pr.md"
+"#,
+        ),
+    ])
+}
+
+fn rules() -> RiskRules {
+    RiskRules {
+        paths: vec![
+            "**/migrations/**".into(),
+            "*.yml".into(),
+            "src/auth/*".into(),
+        ],
+        keywords: vec!["unsafe".into()],
+    }
+}
+
+/// Mission whose implement step a crab finished, reporting `changed_files` and `diff`
+async fn run_implement(root: &TempDir, changed_files: &[&str], diff: &str) -> (AppState, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    repos::set_risk_rules(&conn, &repo.repo_id, &rules()).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Change", "Body"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "ship".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    let implement = tasks::claim_next_task(&conn, "crab-a").unwrap().unwrap();
+    let task_id = implement.task.task_id;
+
+    let state = AppState::new(conn);
+    let _ = create_run(
+        State(state.clone()),
+        Path(task_id.clone()),
+        Json(CreateRunRequest {
+            status: "completed".to_string(),
+            logs: Some("IMPLEMENTED".to_string()),
+            worker_id: Some("crab-a".to_string()),
+            burrow_mode: Some("worktree".to_string()),
+            changed_files: changed_files.iter().map(|f| f.to_string()).collect(),
+            diff: Some(diff.to_string()),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap();
+    update_task_status(
+        State(state.clone()),
+        Path(task_id),
+        Json(UpdateStatusRequest {
+            status: "completed".to_string(),
+            worker_id: Some("crab-a".to_string()),
+            reason: None,
+        }),
+    )
+    .await
+    .unwrap();
+    (state, mission.mission_id)
+}
+
+#[test]
+fn test_assess_matches_path_globs_and_added_keywords() {
+    let files = |fs: &[&str]| fs.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+
+    let reasons = assess(
+        &rules(),
+        &files(&["crates/db/migrations/0001_init.sql", ".github/ci.yml"]),
+        None,
+    );
+    assert_eq!(
+        reasons,
+        vec![
+            "crates/db/migrations/0001_init.sql matches **/migrations/**",
+            ".github/ci.yml matches *.yml",
+        ]
+    );
+
+    // `*` stays within one directory
+    assert!(assess(&rules(), &files(&["src/auth/session/token.rs"]), None).is_empty());
+    assert_eq!(
+        assess(&rules(), &files(&["src/auth/token.rs"]), None).len(),
+        1
+    );
+
+    // Keywords count on added lines only, case-insensitively
+    let diff = "--- a/src/lib.rs\n+++ b/src/lib.rs\n-unsafe { old() }\n+safe()\n";
+    assert!(assess(&rules(), &[], Some(diff)).is_empty());
+    let diff = "+++ b/src/lib.rs\n+    UNSAFE { ptr.read() }\n";
+    assert_eq!(
+        assess(&rules(), &[], Some(diff)),
+        vec!["diff adds \"unsafe\""]
+    );
+
+    assert!(
+        validate(&RiskRules {
+            paths: vec![" ".into()],
+            keywords: vec![],
+        })
+        .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_high_risk_change_waits_for_review_before_pr() {
+    let root = prompts_root();
+    let (state, mission_id) = run_implement(&root, &["db/migrations/0002.sql"], "").await;
+
+    let mission = missions::get_mission(&state.db.lock().unwrap(), &mission_id)
+        .unwrap()
+        .unwrap();
+    assert!(mission.high_risk);
+    assert_eq!(
+        mission.risk_reasons,
+        vec!["db/migrations/0002.sql matches **/migrations/**"]
+    );
+
+    let all = tasks::list_tasks_for_mission(&state.db.lock().unwrap(), &mission_id).unwrap();
+    let review = all.iter().find(|t| t.step_id == RISK_REVIEW_STEP).unwrap();
+    let pr = all.iter().find(|t| t.step_id == "pr").unwrap();
+    assert_eq!(
+        (review.step_order, review.status.as_str()),
+        (1, "awaiting_approval")
+    );
+    assert!(review.assembled_prompt.contains("**/migrations/**"));
+    assert_eq!((pr.step_order, pr.status.as_str()), (2, "blocked"));
+    assert!(
+        tasks::claim_next_task(&state.db.lock().unwrap(), "crab-a")
+            .unwrap()
+            .is_none()
+    );
+
+    approve_task(State(state.clone()), Path(review.task_id.clone()), None)
+        .await
+        .unwrap();
+
+    // The PR step still sees what implement did, not the empty review tier
+    let pr = tasks::get_task(&state.db.lock().unwrap(), &pr.task_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(pr.status, "queued");
+    assert!(pr.assembled_prompt.contains("IMPLEMENTED"));
+}
+
+#[tokio::test]
+async fn test_low_risk_change_goes_straight_on() {
+    let root = prompts_root();
+    let (state, mission_id) =
+        run_implement(&root, &["src/lib.rs"], "+++ b/src/lib.rs\n+fn safe() {}\n").await;
+
+    let conn = state.db.lock().unwrap();
+    let mission = missions::get_mission(&conn, &mission_id).unwrap().unwrap();
+    assert!(!mission.high_risk);
+    let all = tasks::list_tasks_for_mission(&conn, &mission_id).unwrap();
+    assert!(all.iter().all(|t| t.step_id != RISK_REVIEW_STEP));
+    let pr = all.iter().find(|t| t.step_id == "pr").unwrap();
+    assert_eq!(pr.status, "queued");
+}
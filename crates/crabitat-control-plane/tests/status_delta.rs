@@ -0,0 +1,123 @@
+use reqwest::Client;
+use serde_json::Value;
+
+use crabitat_control_plane::db::{missions, repos, tasks};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::{AppState, db, routes};
+use rusqlite::{Connection, params};
+
+/// Serve the router on a free port; returns the state and base URL
+async fn serve() -> (AppState, String) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::migrate(&conn);
+    let state = AppState::new(conn);
+    let app = routes::create_router(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (state, base)
+}
+
+fn create_mission(conn: &Connection, repo_id: &str, issue_number: i64) -> String {
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo_id, issue_number, "Test Issue", "Body"],
+    )
+    .unwrap();
+    let req = CreateMissionRequest {
+        repo_id: repo_id.to_string(),
+        issue_number,
+        workflow_name: "test-wf".to_string(),
+        flavor_id: None,
+        priority: None,
+        context_from_mission_id: None,
+        exclusive: false,
+    };
+    missions::insert_mission(conn, &req, "mission/branch")
+        .unwrap()
+        .mission_id
+}
+
+async fn status(base: &str, query: &str) -> Value {
+    Client::new()
+        .get(format!("{base}/v1/status{query}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_status_returns_only_changes_since_seq() {
+    let (state, base) = serve().await;
+    let (first, second) = {
+        let conn = state.db.lock().unwrap();
+        let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+        let first = create_mission(&conn, &repo.repo_id, 1);
+        let second = create_mission(&conn, &repo.repo_id, 2);
+        tasks::insert_task(&conn, &first, "step1", 0, "p1", 3, "queued").unwrap();
+        (first, second)
+    };
+
+    // No cursor: everything
+    let snapshot = status(&base, "").await;
+    assert_eq!(snapshot["missions"].as_array().unwrap().len(), 2);
+    assert_eq!(snapshot["tasks"].as_array().unwrap().len(), 1);
+    let seq = snapshot["seq"].as_i64().unwrap();
+    assert!(seq > 0);
+
+    // Nothing changed: nothing sent, same sequence number
+    let idle = status(&base, &format!("?since_seq={seq}")).await;
+    assert!(idle["missions"].as_array().unwrap().is_empty());
+    assert!(idle["tasks"].as_array().unwrap().is_empty());
+    assert_eq!(idle["seq"].as_i64().unwrap(), seq);
+
+    {
+        let mut conn = state.db.lock().unwrap();
+        missions::archive_mission(&conn, &first).unwrap();
+        missions::delete_mission(&mut conn, &second).unwrap();
+    }
+    let delta = status(&base, &format!("?since_seq={seq}")).await;
+    let changed = delta["missions"].as_array().unwrap();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0]["mission_id"], first.as_str());
+    assert!(delta["tasks"].as_array().unwrap().is_empty());
+    assert_eq!(delta["deleted_mission_ids"][0], second.as_str());
+    assert!(delta["seq"].as_i64().unwrap() > seq);
+}
+
+#[tokio::test]
+async fn test_status_narrows_to_repo_and_kinds() {
+    let (state, base) = serve().await;
+    let (repo_a, mission_a) = {
+        let conn = state.db.lock().unwrap();
+        let repo_a = repos::insert(&conn, "l1x", "a", None, Some("url")).unwrap();
+        let repo_b = repos::insert(&conn, "l1x", "b", None, Some("url")).unwrap();
+        let mission_a = create_mission(&conn, &repo_a.repo_id, 1);
+        let mission_b = create_mission(&conn, &repo_b.repo_id, 1);
+        tasks::insert_task(&conn, &mission_a, "step1", 0, "p1", 3, "queued").unwrap();
+        tasks::insert_task(&conn, &mission_b, "step1", 0, "p1", 3, "queued").unwrap();
+        (repo_a.repo_id, mission_a)
+    };
+
+    let scoped = status(&base, &format!("?repo_id={repo_a}")).await;
+    let missions = scoped["missions"].as_array().unwrap();
+    assert_eq!(missions.len(), 1);
+    assert_eq!(missions[0]["mission_id"], mission_a.as_str());
+    let scoped_tasks = scoped["tasks"].as_array().unwrap();
+    assert_eq!(scoped_tasks.len(), 1);
+    assert_eq!(scoped_tasks[0]["mission_id"], mission_a.as_str());
+
+    let tasks_only = status(&base, &format!("?repo_id={repo_a}&kinds=tasks")).await;
+    assert!(tasks_only["missions"].as_array().unwrap().is_empty());
+    assert_eq!(tasks_only["tasks"].as_array().unwrap().len(), 1);
+
+    let resp = Client::new()
+        .get(format!("{base}/v1/status?kinds=crabs"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
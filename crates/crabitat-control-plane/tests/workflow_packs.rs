@@ -0,0 +1,126 @@
+mod common;
+
+use common::{TempDir, git};
+use crabitat_control_plane::workflow_packs::{self, PackError};
+use crabitat_control_plane::workflow_registry::WorkflowRegistry;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+fn write_pack(dir: &Path, workflow: &str, prompt_file: &str) {
+    fs::create_dir_all(dir.join("workflows")).unwrap();
+    fs::create_dir_all(dir.join("prompts")).unwrap();
+    fs::write(dir.join("prompts/fix.md"), "Fix it: {{mission}}").unwrap();
+    fs::write(
+        dir.join(format!("workflows/{workflow}.toml")),
+        format!(
+            "[workflow]\nname = \"{workflow}\"\ndescription = \"d\"\n\n[[steps]]\nid = \"fix\"\nprompt_file = \"{prompt_file}\"\n"
+        ),
+    )
+    .unwrap();
+}
+
+fn commit_all(dir: &Path, message: &str) {
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "--quiet", "-m", message]);
+}
+
+#[test]
+fn test_pack_name_from_url() {
+    assert_eq!(
+        workflow_packs::pack_name("https://github.com/l1x/chore-pack.git", None).unwrap(),
+        "chore-pack"
+    );
+    assert_eq!(
+        workflow_packs::pack_name("git@github.com:l1x/chore-pack", None).unwrap(),
+        "chore-pack"
+    );
+    assert!(matches!(
+        workflow_packs::pack_name("x", Some("../evil")),
+        Err(PackError::InvalidName(_))
+    ));
+}
+
+#[test]
+fn test_validate_rejects_escaping_prompt_and_taken_name() {
+    let pack = TempDir::new("pack");
+    write_pack(&pack.0, "chore", "../outside.md");
+    let err = workflow_packs::validate_pack(&pack.0, &HashSet::new()).unwrap_err();
+    assert!(err.contains("inside the pack"), "{}", err);
+
+    write_pack(&pack.0, "chore", "prompts/fix.md");
+    let taken = HashSet::from(["chore".to_string()]);
+    let err = workflow_packs::validate_pack(&pack.0, &taken).unwrap_err();
+    assert!(err.contains("duplicate workflow name"), "{}", err);
+
+    assert_eq!(
+        workflow_packs::validate_pack(&pack.0, &HashSet::new()).unwrap(),
+        vec!["chore"]
+    );
+
+    // A prompt may not link out of the pack
+    fs::remove_file(pack.0.join("prompts/fix.md")).unwrap();
+    std::os::unix::fs::symlink("/etc/hostname", pack.0.join("prompts/fix.md")).unwrap();
+    let err = workflow_packs::validate_pack(&pack.0, &HashSet::new()).unwrap_err();
+    assert!(err.contains("symlinks are not allowed"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_install_rejects_options_as_source() {
+    let prompts_root = TempDir::new("prompts");
+    for (url, git_ref) in [
+        ("--upload-pack=touch /tmp/pwned", None),
+        ("https://example.com/pack.git", Some("--output=/tmp/pwned")),
+    ] {
+        let err = workflow_packs::install(&prompts_root.0, "evil", url, git_ref)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PackError::InvalidSource(_)), "{err}");
+    }
+}
+
+#[tokio::test]
+async fn test_install_update_and_uninstall() {
+    let source = TempDir::new("pack-src");
+    let prompts_root = TempDir::new("prompts");
+    git(&source.0, &["init", "--quiet"]);
+    write_pack(&source.0, "chore", "prompts/fix.md");
+    commit_all(&source.0, "v1");
+
+    let url = source.0.to_string_lossy().to_string();
+    let v1 = workflow_packs::install(&prompts_root.0, "chores", &url, None)
+        .await
+        .unwrap();
+    assert_eq!(v1.workflows, vec!["chore"]);
+
+    // Pack workflows resolve their prompts relative to the pack
+    let registry = WorkflowRegistry::new(&prompts_root.0);
+    let wf = registry.get_workflow("chore").unwrap();
+    assert_eq!(wf.steps[0].prompt_file, "packs/chores/prompts/fix.md");
+    assert!(registry.read_prompt(&wf.steps[0].prompt_file).is_ok());
+
+    // Reinstalling the same pack may keep its own workflow names
+    write_pack(&source.0, "typo", "prompts/fix.md");
+    commit_all(&source.0, "v2");
+    let v2 = workflow_packs::install(&prompts_root.0, "chores", &url, None)
+        .await
+        .unwrap();
+    assert_ne!(v1.commit_sha, v2.commit_sha);
+    assert_eq!(v2.workflows, vec!["chore", "typo"]);
+
+    // Pinning to the first commit rolls back
+    let pinned = workflow_packs::install(&prompts_root.0, "chores", &url, Some(&v1.commit_sha))
+        .await
+        .unwrap();
+    assert_eq!(pinned.workflows, vec!["chore"]);
+
+    // A second pack cannot claim an installed workflow name
+    let err = workflow_packs::install(&prompts_root.0, "other", &url, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PackError::Invalid(_)));
+    assert!(!prompts_root.0.join("packs/other").exists());
+
+    workflow_packs::uninstall(&prompts_root.0, "chores").unwrap();
+    assert!(registry.get_workflow("chore").is_none());
+}
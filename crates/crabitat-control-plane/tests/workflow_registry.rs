@@ -0,0 +1,190 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::workflow_registry::{
+    WorkflowRegistry, parse_workflow, validate_workflow,
+};
+use std::fs;
+
+#[test]
+fn test_read_stack_concatenates_in_filename_order() {
+    let root = TempDir::new("prompts");
+    root.write("roles/reviewer/10-skills.md", "Check tests.\n");
+    root.write("roles/reviewer/00-role.md", "You are a reviewer.\n");
+    root.write("roles/reviewer/notes.txt", "ignored");
+
+    let registry = WorkflowRegistry::new(&root.0);
+    let stack = registry.read_stack("reviewer").unwrap().unwrap();
+
+    assert_eq!(stack.content, "You are a reviewer.\n\nCheck tests.");
+    assert_eq!(
+        stack.files,
+        vec!["roles/reviewer/00-role.md", "roles/reviewer/10-skills.md"]
+    );
+}
+
+#[test]
+fn test_read_stack_hash_changes_with_content() {
+    let root = TempDir::new("prompts");
+    root.write("roles/reviewer/00-role.md", "v1");
+    let registry = WorkflowRegistry::new(&root.0);
+    let before = registry.read_stack("reviewer").unwrap().unwrap().hash;
+
+    root.write("roles/reviewer/00-role.md", "v2");
+    let after = registry.read_stack("reviewer").unwrap().unwrap().hash;
+
+    assert_ne!(before, after);
+}
+
+#[test]
+fn test_read_stack_unknown_and_invalid_roles() {
+    let root = TempDir::new("prompts");
+    let registry = WorkflowRegistry::new(&root.0);
+
+    assert!(registry.read_stack("coder").unwrap().is_none());
+    assert!(registry.read_stack("../workflows").is_err());
+}
+
+fn issue_kinds(root: &TempDir, manifest: &str) -> Vec<String> {
+    let wf = parse_workflow(manifest).unwrap();
+    validate_workflow(&wf, &root.0)
+        .into_iter()
+        .map(|i| i.kind)
+        .collect()
+}
+
+#[test]
+fn test_validate_workflow_reports_each_problem() {
+    let root = TempDir::new("prompts");
+    root.write("plan.md", "plan");
+    let header = "[workflow]\nname = \"wf\"\ndescription = \"d\"\n";
+
+    let valid = format!(
+        "{header}[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n\n[[steps]]\nid = \"again\"\nprompt_file = \"plan.md\"\ndepends_on = [\"plan\"]\non_fail = \"plan\"\n"
+    );
+    assert!(issue_kinds(&root, &valid).is_empty());
+
+    let broken = format!(
+        "{header}[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"missing.md\"\ndepends_on = [\"review\"]\non_fail = \"fix\"\n"
+    );
+    assert_eq!(
+        issue_kinds(&root, &broken),
+        vec![
+            "duplicate_step",
+            "unknown_dependency",
+            "unknown_on_fail",
+            "missing_prompt"
+        ]
+    );
+
+    let cyclic = format!(
+        "{header}[[steps]]\nid = \"a\"\nprompt_file = \"plan.md\"\ndepends_on = [\"b\"]\n\n[[steps]]\nid = \"b\"\nprompt_file = \"plan.md\"\ndepends_on = [\"a\"]\n"
+    );
+    assert_eq!(issue_kinds(&root, &cyclic), vec!["cycle"]);
+
+    let fan_out = format!(
+        "{header}[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\nfor_each = \"each\"\n\n[[steps]]\nid = \"each\"\nprompt_file = \"plan.md\"\nfor_each = \"missing\"\n"
+    );
+    assert_eq!(
+        issue_kinds(&root, &fan_out),
+        vec!["unknown_for_each", "invalid_for_each"]
+    );
+
+    let offline = format!(
+        "{header}[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\nnetwork = \"none\"\n\n[[steps]]\nid = \"fetch\"\nprompt_file = \"plan.md\"\nnetwork = \"lan\"\n"
+    );
+    assert_eq!(issue_kinds(&root, &offline), vec!["invalid_network"]);
+
+    assert_eq!(parse_workflow("[workflow]").unwrap_err().kind, "parse");
+}
+
+#[test]
+fn test_invalid_workflows_are_not_listed() {
+    let root = TempDir::new("prompts");
+    fs::create_dir_all(root.0.join("workflows")).unwrap();
+    root.write("plan.md", "plan");
+    root.write(
+        "workflows/good.toml",
+        "[workflow]\nname = \"good\"\ndescription = \"d\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n",
+    );
+    root.write(
+        "workflows/bad.toml",
+        "[workflow]\nname = \"bad\"\ndescription = \"d\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"gone.md\"\n",
+    );
+
+    let registry = WorkflowRegistry::new(&root.0);
+    let names: Vec<String> = registry
+        .list_workflows()
+        .into_iter()
+        .map(|wf| wf.workflow.name)
+        .collect();
+    assert_eq!(names, vec!["good"]);
+    assert!(registry.get_workflow("bad").is_none());
+
+    let invalid = registry.invalid_workflows();
+    assert_eq!(invalid.len(), 1);
+    assert!(invalid[0].0.ends_with("bad.toml"));
+    assert_eq!(invalid[0].1[0].kind, "missing_prompt");
+}
+
+#[test]
+fn test_override_directories_win() {
+    let org = TempDir::new("prompts");
+    let team = TempDir::new("prompts");
+    for root in [&org, &team] {
+        fs::create_dir_all(root.0.join("workflows")).unwrap();
+    }
+    org.write("plan.md", "org plan");
+    org.write("review.md", "org review");
+    org.write("roles/reviewer/00-role.md", "org role");
+    org.write("roles/reviewer/10-skills.md", "org skills");
+    org.write(
+        "workflows/shared.toml",
+        "[workflow]\nname = \"shared\"\ndescription = \"org\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n",
+    );
+    org.write(
+        "workflows/org-only.toml",
+        "[workflow]\nname = \"org-only\"\ndescription = \"org\"\n\n[[steps]]\nid = \"plan\"\nprompt_file = \"plan.md\"\n",
+    );
+    // The team manifest can use prompts it only finds in the org directory
+    team.write("plan.md", "team plan");
+    team.write("roles/reviewer/10-skills.md", "team skills");
+    team.write(
+        "workflows/shared.toml",
+        "[workflow]\nname = \"shared\"\ndescription = \"team\"\n\n[[steps]]\nid = \"review\"\nprompt_file = \"review.md\"\n",
+    );
+
+    let registry = WorkflowRegistry::new(&org.0).with_overrides([team.0.clone()]);
+    let mut workflows = registry.list_workflows();
+    workflows.sort_by(|a, b| a.workflow.name.cmp(&b.workflow.name));
+    assert_eq!(workflows.len(), 2);
+    assert_eq!(workflows[0].workflow.name, "org-only");
+    assert!(workflows[0].source.as_ref().unwrap().starts_with(&org.0));
+    assert_eq!(workflows[1].workflow.description, "team");
+    assert_eq!(
+        workflows[1].source.as_deref(),
+        Some(team.0.join("workflows/shared.toml").as_path())
+    );
+
+    assert_eq!(registry.read_prompt("plan.md").unwrap(), "team plan");
+    assert_eq!(registry.read_prompt("review.md").unwrap(), "org review");
+    // Nothing outside the prompts directories resolves
+    for escape in ["../team/plan.md", "/etc/passwd"] {
+        assert!(registry.prompt_file(escape).is_none(), "{escape}");
+        assert!(registry.read_prompt(escape).is_err(), "{escape}");
+    }
+    let mut files = registry.list_prompt_files();
+    files.sort();
+    assert_eq!(
+        files,
+        vec![
+            "plan.md",
+            "review.md",
+            "roles/reviewer/00-role.md",
+            "roles/reviewer/10-skills.md"
+        ]
+    );
+
+    let stack = registry.read_stack("reviewer").unwrap().unwrap();
+    assert_eq!(stack.content, "org role\n\nteam skills");
+}
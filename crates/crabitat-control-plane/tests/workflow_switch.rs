@@ -0,0 +1,148 @@
+mod common;
+
+use common::TempDir;
+use crabitat_control_plane::db;
+use crabitat_control_plane::db::missions;
+use crabitat_control_plane::db::repos;
+use crabitat_control_plane::db::settings;
+use crabitat_control_plane::db::tasks;
+use crabitat_control_plane::mission_service::{apply_workflow_switch, create_mission};
+use crabitat_control_plane::models::missions::CreateMissionRequest;
+use crabitat_control_plane::models::tasks::CreateRunRequest;
+use rusqlite::{Connection, params};
+
+/// Temp prompts root with a full and a chore workflow
+fn prompts_root() -> TempDir {
+    TempDir::prompts_root(&[
+        ("plan.md", "plan {{context}}"),
+        ("implement.md", "implement {{context}}"),
+        ("review.md", "review {{context}}"),
+        ("fix.md", "fix {{context}}"),
+        (
+            "workflows/full.toml",
+            r#"
+[workflow]
+name = "full"
+description = "plan, implement, review"
+
+[[steps]]
+id = "plan"
+prompt_file = "plan.md"
+type = "classify"
+switch_to = ["chore"]
+
+[[steps]]
+id = "implement"
+prompt_file = "implement.md"
+
+[[steps]]
+id = "review"
+prompt_file = "review.md"
+"#,
+        ),
+        (
+            "workflows/chore.toml",
+            r#"
+[workflow]
+name = "chore"
+description = "single step"
+
+[[steps]]
+id = "fix"
+prompt_file = "fix.md"
+"#,
+        ),
+    ])
+}
+
+fn setup(root: &TempDir) -> (Connection, String) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    db::migrate(&conn);
+    settings::set(&conn, "prompts_root", root.0.to_str().unwrap()).unwrap();
+
+    let repo = repos::insert(&conn, "l1x", "test", None, Some("url")).unwrap();
+    conn.execute(
+        "INSERT INTO github_issues_cache (repo_id, number, title, body) VALUES (?1, ?2, ?3, ?4)",
+        params![repo.repo_id, 1, "Fix typo", "teh -> the"],
+    )
+    .unwrap();
+    let mission = create_mission(
+        &mut conn,
+        &CreateMissionRequest {
+            repo_id: repo.repo_id,
+            issue_number: 1,
+            workflow_name: "full".to_string(),
+            flavor_id: None,
+            priority: None,
+            context_from_mission_id: None,
+            exclusive: false,
+        },
+    )
+    .unwrap();
+    (conn, mission.mission_id)
+}
+
+fn complete_plan(conn: &Connection, mission_id: &str, next_workflow: Option<&str>) -> String {
+    let plan = tasks::list_tasks_for_mission(conn, mission_id)
+        .unwrap()
+        .into_iter()
+        .find(|t| t.step_id == "plan")
+        .unwrap();
+    tasks::insert_run(
+        conn,
+        &plan.task_id,
+        &CreateRunRequest {
+            status: "completed".to_string(),
+            next_workflow: next_workflow.map(String::from),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    tasks::update_task_status(conn, &plan.task_id, "completed").unwrap();
+    plan.task_id
+}
+
+#[test]
+fn test_switch_replaces_remaining_steps() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+    let plan_id = complete_plan(&conn, &mission_id, Some("chore"));
+
+    let plan = tasks::get_task(&conn, &plan_id).unwrap().unwrap();
+    assert_eq!(plan.step_config.switch_to, vec!["chore"]);
+    let switched = apply_workflow_switch(&conn, &plan).unwrap();
+    assert_eq!(switched.as_deref(), Some("chore"));
+
+    let remaining: Vec<(String, i64, String)> = tasks::list_tasks_for_mission(&conn, &mission_id)
+        .unwrap()
+        .into_iter()
+        .map(|t| (t.step_id, t.step_order, t.status))
+        .collect();
+    assert_eq!(
+        remaining,
+        vec![
+            ("plan".to_string(), 0, "completed".to_string()),
+            ("fix".to_string(), 1, "blocked".to_string()),
+        ]
+    );
+
+    let mission = missions::get_mission(&conn, &mission_id).unwrap().unwrap();
+    assert_eq!(mission.workflow_name, "chore");
+}
+
+#[test]
+fn test_switch_ignores_unlisted_or_missing_workflow() {
+    let root = prompts_root();
+    let (conn, mission_id) = setup(&root);
+    let plan_id = complete_plan(&conn, &mission_id, Some("release"));
+
+    let plan = tasks::get_task(&conn, &plan_id).unwrap().unwrap();
+    assert_eq!(apply_workflow_switch(&conn, &plan).unwrap(), None);
+    assert_eq!(
+        tasks::list_tasks_for_mission(&conn, &mission_id)
+            .unwrap()
+            .len(),
+        3
+    );
+}
@@ -1,18 +1,37 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Command;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
-#[derive(Parser, Debug)]
-#[command(author, version, about = "The Crabitat Worker", long_about = None)]
+#[derive(Parser, Debug, Clone)]
+#[command(
+    author,
+    version,
+    about = "The Crabitat Worker",
+    long_about = None,
+    after_help = EXIT_CODES_HELP
+)]
 struct Args {
     /// URL of the control-plane
     #[arg(short = 'u', long, default_value = "http://localhost:3001")]
     api_url: String,
 
+    /// API key with the `crab:write` scope; needed once the control-plane has keys
+    #[arg(long, env = "CRABITAT_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
     /// Polling interval in seconds
     #[arg(short = 'i', long, default_value_t = 10)]
     interval: u64,
@@ -25,6 +44,16 @@ struct Args {
     #[arg(long, default_value = "burrows")]
     burrows_root: String,
 
+    /// Directory to create task burrows under; repeat to give several, and the one
+    /// with the most free space is used. Defaults to `burrows/` inside the repo
+    #[arg(long = "burrow-root")]
+    burrow_roots: Vec<PathBuf>,
+
+    /// Where a burrow goes: {root}, {repo}, {mission}, {task} and {burrow}
+    /// (the branch, or `debug-<run id>`) are filled in
+    #[arg(long, default_value = "{root}/{burrow}")]
+    burrow_template: String,
+
     /// Environment profile ('local', 'remote')
     #[arg(short = 'e', long, default_value = "local")]
     env: String,
@@ -36,20 +65,197 @@ struct Args {
     /// SSH Key name/path (Mock for AWS Secrets Manager integration)
     #[arg(long)]
     ssh_key: Option<String>,
+
+    /// Role whose prompt stack (roles/{role}/*.md) is loaded as system context
+    #[arg(long)]
+    role: Option<String>,
+
+    /// Seconds an agent may run before it is killed; steps may override with `timeout_secs`
+    #[arg(long, default_value_t = 3600)]
+    exec_timeout: u64,
+
+    /// Pre-created worktrees kept per repo for new mission burrows (0 disables the pool)
+    #[arg(long, default_value_t = 0)]
+    warm_burrows: usize,
+
+    /// Command run in each warm burrow after it is created or refreshed, e.g. "npm ci"
+    #[arg(long)]
+    warm_setup: Option<String>,
+
+    /// Seconds before an idle warm burrow is reset to the base branch and set up again
+    #[arg(long, default_value_t = 1800)]
+    warm_refresh: u64,
+
+    /// Host left out of the proxy hint for `none` or `restricted` network
+    /// steps, besides the agent's model API; repeat for several
+    #[arg(long = "network-allow")]
+    network_allow: Vec<String>,
+
+    /// Claim and run at most one task, then exit; finding no task is not an error
+    #[arg(long)]
+    once: bool,
+
+    /// Print errors, and what `--once` ran, to stdout as JSON; logs go to stderr
+    #[arg(long)]
+    json: bool,
+
+    /// Log errors only
+    #[arg(short = 'q', long)]
+    quiet: bool,
+
+    #[command(subcommand)]
+    command: Option<CrabCommand>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum CrabCommand {
+    /// Print how to run a crab on a repo, from the control plane's live
+    /// state: roles still without crabs and what the repo's workflows ask of
+    /// `--role`
+    Guide {
+        /// Repo to run on
+        repo_id: String,
+    },
+}
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success; with --once, also when there was no task to claim
+  1  any other failure
+  2  invalid command line
+  3  network: the control plane could not be reached
+  4  auth: the API key or crab token was rejected
+  5  not found: the control plane has no such resource
+  6  validation: the request or the configuration was rejected as invalid";
+
+/// What kind of failure ended the crab, for scripts wrapping it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FailureClass {
+    Other,
+    Network,
+    Auth,
+    NotFound,
+    Validation,
+}
+
+impl FailureClass {
+    /// Classify by the first HTTP error in the chain; see `EXIT_CODES_HELP`
+    fn of(err: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(err);
+        while let Some(e) = source {
+            if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+                return match e.status().map(|s| s.as_u16()) {
+                    Some(401 | 403) => Self::Auth,
+                    Some(404) => Self::NotFound,
+                    Some(400 | 409 | 422) => Self::Validation,
+                    Some(_) => Self::Other,
+                    None if e.is_connect() || e.is_timeout() || e.is_request() => Self::Network,
+                    None => Self::Other,
+                };
+            }
+            source = e.source();
+        }
+        Self::Other
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Other => "other",
+            Self::Network => "network",
+            Self::Auth => "auth",
+            Self::NotFound => "not_found",
+            Self::Validation => "validation",
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Network => 3,
+            Self::Auth => 4,
+            Self::NotFound => 5,
+            Self::Validation => 6,
+        }
+    }
+}
+
+/// Report a fatal error the way `--json` asks for and exit with its class's code
+fn fail(args: &Args, class: FailureClass, message: impl Display) -> ! {
+    if args.json {
+        let error = serde_json::json!({
+            "error": {
+                "class": class.name(),
+                "exit_code": class.exit_code(),
+                "message": message.to_string(),
+            }
+        });
+        println!("{}", error);
+    } else {
+        error!("{}", message);
+    }
+    std::process::exit(class.exit_code());
 }
 
 #[derive(Debug, Deserialize)]
 struct TaskResponse {
     task: Task,
     git: GitInfo,
+    #[serde(default)]
+    stack_hash: Option<String>,
+    /// Set when this is a debug re-execution rather than a mission task
+    #[serde(default)]
+    debug_run_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Task {
     task_id: String,
+    mission_id: String,
+    step_id: String,
+    #[serde(default)]
     assembled_prompt: String,
+    #[serde(default)]
+    prompt_chunks: Vec<String>,
     retry_count: i64,
     max_retries: i64,
+    #[serde(default)]
+    step_config: StepConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StepConfig {
+    #[serde(default)]
+    read_only: bool,
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+    /// `none` or `restricted`, passed on as a proxy hint; unset means full network
+    #[serde(default)]
+    network: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Blob {
+    content: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PromptStack {
+    hash: String,
+    content: String,
+}
+
+/// Worker id and token from `POST /v1/crabs/register`
+#[derive(Debug, Deserialize)]
+struct CrabRegistration {
+    worker_id: String,
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BurrowLease {
+    mission_id: String,
+    worker_id: String,
+    path: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,26 +268,233 @@ struct GitInfo {
 #[derive(Serialize)]
 struct UpdateStatusRequest {
     status: String,
+    worker_id: String,
+}
+
+#[derive(Serialize)]
+struct RejectTaskRequest {
+    worker_id: String,
+    reason: String,
 }
 
 #[derive(Serialize)]
 struct CreateRunRequest {
+    run_id: Option<String>,
     status: String,
     logs: Option<String>,
     summary: Option<String>,
     duration_ms: Option<i64>,
     tokens_used: Option<i64>,
+    score: Option<i64>,
+    worker_id: Option<String>,
+    debug_run_id: Option<String>,
+    burrow_mode: Option<String>,
+    burrow_path: Option<String>,
+    next_workflow: Option<String>,
+    work_log: Option<String>,
+    failure_kind: Option<String>,
+    criteria: Vec<CriterionResult>,
+    changed_files: Vec<String>,
+    diff: Option<String>,
+    network: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CriterionResult {
+    index: i64,
+    passed: bool,
+    note: Option<String>,
+}
+
+/// Per-task working log kept at `{burrows_root}/logs/{task_id}.log`.
+///
+/// Records what the crab did around the agent (repo setup, burrow preparation,
+/// the executor invocation, cleanup) and is uploaded with every run.
+#[derive(Clone)]
+struct WorkLog {
+    path: PathBuf,
+    started: Instant,
+}
+
+impl WorkLog {
+    fn open(burrows_root: &str, task_id: &str) -> Self {
+        let dir = PathBuf::from(burrows_root).join("logs");
+        let path = dir.join(format!("{}.log", task_id));
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, "")) {
+            warn!("Failed to create work log {:?}: {}", path, e);
+        }
+        Self {
+            path,
+            started: Instant::now(),
+        }
+    }
+
+    /// Append one `+<ms>ms [phase] message` line
+    fn entry(&self, phase: &str, message: impl Display) {
+        let line = format!(
+            "+{}ms [{}] {}\n",
+            self.started.elapsed().as_millis(),
+            phase,
+            message
+        );
+        let written = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(line.as_bytes()));
+        if let Err(e) = written {
+            debug!("Failed to write work log {:?}: {}", self.path, e);
+        }
+    }
+
+    fn contents(&self) -> Option<String> {
+        std::fs::read_to_string(&self.path).ok()
+    }
+}
+
+/// Hosts `agent` needs for its model API, left out of any proxy hint
+fn agent_api_hosts(agent: &str) -> &'static [&'static str] {
+    match agent {
+        "claude" => &["api.anthropic.com"],
+        "gemini" | "gemini-cli" => &[
+            "generativelanguage.googleapis.com",
+            "cloudcode-pa.googleapis.com",
+            "oauth2.googleapis.com",
+        ],
+        "codex" => &["api.openai.com", "chatgpt.com"],
+        _ => &[],
+    }
+}
+
+/// Host of an http(s) URL or an scp-style git remote (`git@host:owner/repo`)
+fn url_host(url: &str) -> Option<String> {
+    if let Ok(parsed) = reqwest::Url::parse(url)
+        && let Some(host) = parsed.host_str()
+    {
+        return Some(host.to_string());
+    }
+    let (_, rest) = url.split_once('@')?;
+    let host = rest.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+/// Pass a step's network policy to the agent command as a proxy hint. There
+/// is no sandbox here, so this only points every proxy variable at a closed
+/// port and exempts the allowed hosts: tools honouring the proxy variables
+/// are cut off, others still reach the network. Returns the exempted hosts,
+/// or `None` for full network.
+fn apply_proxy_hint(
+    cmd: &mut tokio::process::Command,
+    policy: Option<&str>,
+    args: &Args,
+    repo_url: Option<&str>,
+) -> Option<Vec<String>> {
+    let policy = policy.filter(|p| *p == "none" || *p == "restricted")?;
+    let mut allowed: Vec<String> = agent_api_hosts(&args.agent)
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+    allowed.extend(args.network_allow.iter().cloned());
+    if policy == "restricted" {
+        allowed.extend(["localhost".to_string(), "127.0.0.1".to_string()]);
+        allowed.extend(url_host(&args.api_url));
+        allowed.extend(repo_url.and_then(url_host));
+    }
+    allowed.sort();
+    allowed.dedup();
+
+    let blackhole = "http://127.0.0.1:9";
+    for var in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"] {
+        cmd.env(var, blackhole);
+        cmd.env(var.to_lowercase(), blackhole);
+    }
+    let no_proxy = allowed.join(",");
+    cmd.env("NO_PROXY", &no_proxy);
+    cmd.env("no_proxy", &no_proxy);
+    Some(allowed)
+}
+
+/// The step's network policy as reported on its run: a proxy hint, never an
+/// enforced restriction
+fn network_report(policy: Option<&str>) -> Option<String> {
+    policy
+        .filter(|p| *p == "none" || *p == "restricted")
+        .map(|p| format!("{} (proxy hint)", p))
+}
+
+/// The agent command line with the prompt argument elided
+fn describe_invocation(cmd: &Command, prompt: &str) -> String {
+    let mut parts = vec![cmd.get_program().to_string_lossy().to_string()];
+    for arg in cmd.get_args() {
+        if arg == prompt {
+            parts.push(format!("<prompt: {} bytes>", prompt.len()));
+        } else {
+            parts.push(arg.to_string_lossy().to_string());
+        }
+    }
+    parts.join(" ")
+}
+
+/// HTTP client that sends the API key and crab token, when there are any, on every request
+fn build_client(
+    api_key: Option<&str>,
+    crab_token: Option<&str>,
+) -> Result<reqwest::Client, String> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let sensitive = |value: String| {
+        reqwest::header::HeaderValue::from_str(&value)
+            .map(|mut value| {
+                value.set_sensitive(true);
+                value
+            })
+            .map_err(|e| e.to_string())
+    };
+    if let Some(key) = api_key {
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            sensitive(format!("Bearer {}", key))?,
+        );
+    }
+    if let Some(token) = crab_token {
+        headers.insert("x-crab-token", sensitive(token.to_string())?);
+    }
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|e| e.to_string())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "crabitat_crab=info".into()),
-        )
-        .init();
     let args = Args::parse();
+    let default_filter = if args.quiet {
+        "crabitat_crab=error"
+    } else {
+        "crabitat_crab=info"
+    };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| default_filter.into()),
+    );
+    // Keep stdout for the JSON a script parses
+    if args.json {
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
+
+    if let Some(CrabCommand::Guide { repo_id }) = &args.command {
+        let client = build_client(args.api_key.as_deref(), None).unwrap_or_else(|e| {
+            fail(
+                &args,
+                FailureClass::Validation,
+                format!("Invalid API key: {}", e),
+            )
+        });
+        if let Err(e) = print_guide(&args, &client, repo_id).await {
+            fail(&args, FailureClass::of(e.as_ref()), e);
+        }
+        return Ok(());
+    }
 
     info!(
         "Crab worker started. API: {}, agent: {}, env: {}, interval: {}s",
@@ -94,16 +507,81 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // In a real AWS scenario, we would fetch from Secrets Manager here
     }
 
-    let client = reqwest::Client::new();
-    let worker_id = uuid::Uuid::new_v4().to_string();
+    let client = match build_client(args.api_key.as_deref(), None) {
+        Ok(client) => client,
+        Err(e) => fail(
+            &args,
+            FailureClass::Validation,
+            format!("Invalid API key: {}", e),
+        ),
+    };
+
+    // Registered crabs get a token only they can use their worker id with
+    let (client, worker_id) = match register(&args, &client).await {
+        Ok(registration) => (
+            build_client(args.api_key.as_deref(), Some(&registration.token)).unwrap_or_else(|e| {
+                fail(
+                    &args,
+                    FailureClass::Validation,
+                    format!("Invalid crab token: {}", e),
+                )
+            }),
+            registration.worker_id,
+        ),
+        Err(e) => {
+            warn!("Crab registration failed, continuing unregistered: {}", e);
+            (client, uuid::Uuid::new_v4().to_string())
+        }
+    };
 
     info!("Worker ID: {}", worker_id);
 
+    let mut stack = match &args.role {
+        Some(role) => match sync_stack(&args, &client, role).await {
+            Ok(stack) => Some(stack),
+            Err(e) => {
+                warn!("Failed to sync prompt stack for role {}: {}", role, e);
+                load_cached_stack(&args, role)
+            }
+        },
+        None => None,
+    };
+
+    let pool = Arc::new(WarmPool::new(&args));
+    let executors = detect_executor(&args, &client).await;
+    info!("Executor inventory: {}", executors);
+
+    if args.once {
+        if let Err(e) = cleanup_stale_burrows(&args, &client, &worker_id).await {
+            debug!("Burrow cleanup skipped: {}", e);
+        }
+        match poll_and_execute(&args, &client, &worker_id, &executors, &mut stack, &pool).await {
+            // No task: exit 0 and print nothing
+            Ok(false) => {}
+            Ok(true) if args.json => println!("{}", serde_json::json!({ "executed": true })),
+            Ok(true) => {}
+            Err(e) => fail(&args, FailureClass::of(e.as_ref()), e),
+        }
+        return Ok(());
+    }
+
     loop {
-        match poll_and_execute(&args, &client, &worker_id).await {
+        if let Err(e) = cleanup_stale_burrows(&args, &client, &worker_id).await {
+            debug!("Burrow cleanup skipped: {}", e);
+        }
+        match poll_and_execute(&args, &client, &worker_id, &executors, &mut stack, &pool).await {
             Ok(executed) => {
                 if !executed {
                     debug!("No tasks found, sleeping...");
+                    if pool.enabled() {
+                        // Fetching and the setup command can take minutes
+                        let (pool, args) = (pool.clone(), args.clone());
+                        if let Err(e) =
+                            tokio::task::spawn_blocking(move || pool.replenish(&args)).await
+                        {
+                            warn!("Warm pool replenish failed: {}", e);
+                        }
+                    }
                 }
             }
             Err(e) => {
@@ -114,6 +592,100 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Fetch the repo's guide and print it, as JSON with `--json`
+async fn print_guide(
+    args: &Args,
+    client: &reqwest::Client,
+    repo_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut request = client.get(format!("{}/v1/repos/{}/guide", args.api_url, repo_id));
+    if let Some(role) = &args.role {
+        request = request.query(&[("role", role)]);
+    }
+    let guide: serde_json::Value = request.send().await?.error_for_status()?.json().await?;
+    if args.json {
+        println!("{}", guide);
+        return Ok(());
+    }
+
+    let text = |v: &serde_json::Value| v.as_str().unwrap_or_default().to_string();
+    let list = |v: &serde_json::Value| v.as_array().cloned().unwrap_or_default();
+    println!(
+        "Crabitat guide for {}/{}\n",
+        text(&guide["repo_owner"]),
+        text(&guide["repo_name"])
+    );
+    println!("Control plane: {}", text(&guide["control_plane_url"]));
+    if let Some(role) = guide["role"].as_str() {
+        println!("Role: {}", role);
+        for file in list(&guide["role_stack_files"]) {
+            println!("  system context: {}", text(&file));
+        }
+    }
+
+    println!("\nRoles:");
+    let roles = list(&guide["roles"]);
+    if roles.is_empty() {
+        println!("  (none defined under roles/ in the prompts directory)");
+    }
+    for role in roles {
+        let crabs = role["active_crabs"].as_u64().unwrap_or(0);
+        let note = if crabs == 0 {
+            "unfilled".to_string()
+        } else {
+            format!("{} active crab(s)", crabs)
+        };
+        println!("  {:<20} {}", text(&role["role"]), note);
+    }
+
+    println!("\nLive workflows:");
+    let workflows = list(&guide["workflows"]);
+    if workflows.is_empty() {
+        println!("  (no pending or running missions)");
+    }
+    for workflow in workflows {
+        println!(
+            "  {} ({} mission(s))",
+            text(&workflow["name"]),
+            workflow["live_missions"].as_i64().unwrap_or(0)
+        );
+        for step in list(&workflow["steps"]) {
+            let mut needs = Vec::new();
+            if let Some(executor) = step["executor"].as_str() {
+                needs.push(format!("needs {}", executor));
+            }
+            if step["read_only"].as_bool() == Some(true) {
+                needs.push("read-only".to_string());
+            }
+            println!(
+                "    - {} ({}){}",
+                text(&step["step_id"]),
+                text(&step["prompt_file"]),
+                if needs.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", needs.join(", "))
+                }
+            );
+        }
+    }
+
+    println!("\nStart a crab with:\n  {}", text(&guide["command"]));
+    Ok(())
+}
+
+async fn register(
+    args: &Args,
+    client: &reqwest::Client,
+) -> Result<CrabRegistration, Box<dyn std::error::Error>> {
+    let res = client
+        .post(format!("{}/v1/crabs/register", args.api_url))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(res.json().await?)
+}
+
 async fn get_env_path(
     client: &reqwest::Client,
     api_url: &str,
@@ -137,6 +709,522 @@ async fn get_env_path(
     None
 }
 
+/// The agent as a `name@version` inventory entry for the control plane, so it
+/// only hands out steps this crab can run. The version is the first token of
+/// `{agent} --version` that starts with a digit; without one only the name is sent.
+async fn detect_executor(args: &Args, client: &reqwest::Client) -> String {
+    let agent_path = get_env_path(client, &args.api_url, &args.env, "agent", &args.agent)
+        .await
+        .unwrap_or_else(|| args.agent.clone());
+    let version = tokio::process::Command::new(&agent_path)
+        .arg("--version")
+        .output()
+        .await
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| {
+            String::from_utf8_lossy(&out.stdout)
+                .split_whitespace()
+                .map(|token| token.trim_start_matches('v'))
+                .find(|token| token.starts_with(|c: char| c.is_ascii_digit()))
+                .map(String::from)
+        });
+    match version {
+        Some(version) => format!("{}@{}", args.agent, version),
+        None => {
+            warn!("Could not determine the version of agent {}", args.agent);
+            args.agent.clone()
+        }
+    }
+}
+
+/// Whether `content` is the chunk named by `hash`, the hex sha256 of its text
+fn chunk_matches(hash: &str, content: &str) -> bool {
+    let digest = Sha256::digest(content.as_bytes());
+    digest
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>()
+        == hash
+}
+
+/// Rebuild the assembled prompt from its content-addressed chunks, fetching
+/// only the chunks missing from the local blob cache. Chunk names must be
+/// hex sha256 hashes, and every chunk, cached or fetched, must hash to its
+/// name: a bad name would escape the cache directory, and a bad chunk would
+/// put text nobody assembled into the agent's prompt.
+async fn resolve_prompt(
+    args: &Args,
+    client: &reqwest::Client,
+    task: &Task,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if task.prompt_chunks.is_empty() {
+        return Ok(task.assembled_prompt.clone());
+    }
+
+    let cache_dir = PathBuf::from(&args.burrows_root)
+        .join("cache")
+        .join("blobs");
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let mut prompt = String::new();
+    let mut fetched = 0;
+    for hash in &task.prompt_chunks {
+        if hash.len() != 64 || !hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(format!("Invalid prompt chunk hash {:?}", hash).into());
+        }
+        let cache_path = cache_dir.join(hash);
+        if let Ok(content) = std::fs::read_to_string(&cache_path) {
+            if chunk_matches(hash, &content) {
+                prompt.push_str(&content);
+                continue;
+            }
+            warn!("Cached prompt chunk {} is corrupt; fetching it again", hash);
+        }
+
+        let res = client
+            .get(format!("{}/v1/blobs/{}", args.api_url, hash))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("Failed to fetch prompt chunk {}: {}", hash, res.status()).into());
+        }
+        let blob: Blob = res.json().await?;
+        if !chunk_matches(hash, &blob.content) {
+            return Err(format!("Prompt chunk {} does not match its hash", hash).into());
+        }
+        std::fs::write(&cache_path, &blob.content)?;
+        prompt.push_str(&blob.content);
+        fetched += 1;
+    }
+
+    debug!(
+        "Resolved prompt from {} chunks ({} fetched, {} cached)",
+        task.prompt_chunks.len(),
+        fetched,
+        task.prompt_chunks.len() - fetched
+    );
+    Ok(prompt)
+}
+
+fn stack_cache_path(args: &Args, role: &str) -> PathBuf {
+    PathBuf::from(&args.burrows_root)
+        .join("cache")
+        .join("stacks")
+        .join(format!("{}.json", role))
+}
+
+fn load_cached_stack(args: &Args, role: &str) -> Option<PromptStack> {
+    let raw = std::fs::read_to_string(stack_cache_path(args, role)).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+/// Fetch the role's prompt stack from the control-plane and cache it locally
+async fn sync_stack(
+    args: &Args,
+    client: &reqwest::Client,
+    role: &str,
+) -> Result<PromptStack, Box<dyn std::error::Error>> {
+    let res = client
+        .get(format!("{}/v1/prompts/stacks/{}", args.api_url, role))
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(format!("Failed to fetch prompt stack {}: {}", role, res.status()).into());
+    }
+    let stack: PromptStack = res.json().await?;
+
+    let cache_path = stack_cache_path(args, role);
+    if let Some(parent) = cache_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&cache_path, serde_json::to_string(&stack)?)?;
+
+    info!("Loaded prompt stack for role {} ({})", role, stack.hash);
+    Ok(stack)
+}
+
+/// Write the prompt stack into the burrow as `.crabitat/system.md`, kept out of git
+fn write_system_context(worktree_path: &Path, stack: &PromptStack) -> std::io::Result<()> {
+    let dir = worktree_path.join(".crabitat");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(".gitignore"), "*\n")?;
+    std::fs::write(dir.join("system.md"), &stack.content)
+}
+
+/// What the agent wrote before it exited or was killed
+struct AgentOutput {
+    exit: AgentExit,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug)]
+enum AgentExit {
+    Exited(ExitStatus),
+    /// Killed after outliving its timeout
+    TimedOut,
+    /// Killed because the control plane took the task back; holds the status
+    /// it reported: `skipped` for a mission cancellation, otherwise the
+    /// watchdog failed or requeued it (e.g. the step's timeout passed)
+    Cancelled(String),
+}
+
+/// How often streamed agent output is sent to the control plane
+const LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Captured output on its way to the log shipper, tagged with its stream
+type LogTee = UnboundedSender<(&'static str, String)>;
+
+#[derive(Serialize)]
+struct RunLogChunk {
+    seq: i64,
+    stream: &'static str,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct AppendRunLogsRequest<'a> {
+    task_id: &'a str,
+    chunks: &'a [RunLogChunk],
+}
+
+/// Ship agent output to `/v1/runs/{run_id}/logs` in batches until every sender
+/// is dropped. A failed post keeps its chunks for the next flush; the run
+/// report carries the complete output either way.
+fn spawn_log_shipper(
+    client: &reqwest::Client,
+    api_url: &str,
+    run_id: &str,
+    task_id: &str,
+) -> (LogTee, JoinHandle<()>) {
+    let (tx, mut rx) = unbounded_channel::<(&'static str, String)>();
+    let client = client.clone();
+    let url = format!("{}/v1/runs/{}/logs", api_url, run_id);
+    let task_id = task_id.to_string();
+    let shipper = tokio::spawn(async move {
+        let mut pending: Vec<RunLogChunk> = Vec::new();
+        let mut seq = 0;
+        let mut tick = tokio::time::interval(LOG_FLUSH_INTERVAL);
+        let mut open = true;
+        while open {
+            tokio::select! {
+                received = rx.recv() => match received {
+                    Some((stream, content)) => {
+                        pending.push(RunLogChunk { seq, stream, content });
+                        seq += 1;
+                        continue;
+                    }
+                    None => open = false,
+                },
+                _ = tick.tick() => {}
+            }
+            if pending.is_empty() {
+                continue;
+            }
+            let sent = client
+                .post(&url)
+                .json(&AppendRunLogsRequest {
+                    task_id: &task_id,
+                    chunks: &pending,
+                })
+                .send()
+                .await;
+            match sent {
+                Ok(res) if res.status().is_success() => pending.clear(),
+                Ok(res) => debug!("Shipping logs to {} returned {}", url, res.status()),
+                Err(e) => debug!("Shipping logs to {} failed: {}", url, e),
+            }
+        }
+    });
+    (tx, shipper)
+}
+
+/// Take the longest complete UTF-8 prefix of `bytes`, leaving a character
+/// split across reads for the next one
+fn take_utf8(bytes: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => bytes.len(),
+    };
+    let rest = bytes.split_off(complete);
+    let text = String::from_utf8_lossy(bytes).into_owned();
+    *bytes = rest;
+    text
+}
+
+/// A pipe drained into a shared buffer, so partial output survives a kill.
+/// With a tee, each read is also forwarded for streaming.
+struct Capture {
+    buf: Arc<Mutex<Vec<u8>>>,
+    reader: JoinHandle<()>,
+}
+
+impl Capture {
+    fn start<R: AsyncRead + Unpin + Send + 'static>(
+        pipe: Option<R>,
+        stream: &'static str,
+        tee: Option<LogTee>,
+    ) -> Self {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let sink = buf.clone();
+        let reader = tokio::spawn(async move {
+            let Some(mut pipe) = pipe else { return };
+            let mut chunk = [0u8; 8192];
+            let mut unsent = Vec::new();
+            while let Ok(n) = pipe.read(&mut chunk).await {
+                if n == 0 {
+                    break;
+                }
+                sink.lock().unwrap().extend_from_slice(&chunk[..n]);
+                if let Some(tee) = &tee {
+                    unsent.extend_from_slice(&chunk[..n]);
+                    let text = take_utf8(&mut unsent);
+                    if !text.is_empty() {
+                        let _ = tee.send((stream, text));
+                    }
+                }
+            }
+            if let Some(tee) = &tee
+                && !unsent.is_empty()
+            {
+                let _ = tee.send((stream, String::from_utf8_lossy(&unsent).into_owned()));
+            }
+        });
+        Self { buf, reader }
+    }
+
+    /// Give the reader a moment to drain; a stray grandchild holding the pipe can't stall us
+    async fn finish(self) -> String {
+        let _ = tokio::time::timeout(Duration::from_secs(5), self.reader).await;
+        String::from_utf8_lossy(&self.buf.lock().unwrap()).to_string()
+    }
+}
+
+/// Run the agent in its own process group and kill the whole group if it
+/// outlives `limit` or `cancelled` resolves, keeping whatever it printed up
+/// to that point. Output is also forwarded to `tee` as it arrives.
+async fn run_agent(
+    mut cmd: tokio::process::Command,
+    limit: Duration,
+    cancelled: impl std::future::Future<Output = String>,
+    tee: Option<LogTee>,
+) -> std::io::Result<AgentOutput> {
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    let mut child = cmd.spawn()?;
+    let stdout = Capture::start(child.stdout.take(), "stdout", tee.clone());
+    let stderr = Capture::start(child.stderr.take(), "stderr", tee);
+
+    let exit = tokio::select! {
+        status = child.wait() => AgentExit::Exited(status?),
+        _ = sleep(limit) => AgentExit::TimedOut,
+        status = cancelled => AgentExit::Cancelled(status),
+    };
+    if !matches!(exit, AgentExit::Exited(_)) {
+        if let Some(pid) = child.id() {
+            // The group id is the agent's pid since it leads its own group
+            let killed = Command::new("kill")
+                .args(["-KILL", "--", &format!("-{}", pid)])
+                .status();
+            if !killed.is_ok_and(|s| s.success()) {
+                let _ = child.start_kill();
+            }
+        }
+        let _ = child.wait().await;
+    }
+
+    Ok(AgentOutput {
+        exit,
+        stdout: stdout.finish().await,
+        stderr: stderr.finish().await,
+    })
+}
+
+/// Send heartbeats from the claim on so the watchdog leaves the task alone
+/// while the crab clones, prepares the burrow and runs the agent.
+/// Resolves with the task's status once the control plane has taken it back:
+/// it left `running`, its retry count moved past `retry_count` because the
+/// watchdog requeued it, or another crab holds it now.
+///
+/// Messages posted for the task arrive with the heartbeat response. Every
+/// supported agent runs one-shot (`-p <prompt>`) with no session to write
+/// into, so they are recorded in the work log rather than forwarded.
+async fn heartbeat_until_cancelled(
+    client: reqwest::Client,
+    api_url: String,
+    task_id: String,
+    worker_id: String,
+    retry_count: i64,
+    work_log: WorkLog,
+    pool: Arc<WarmPool>,
+) -> String {
+    #[derive(Deserialize)]
+    struct TaskStatus {
+        status: String,
+        retry_count: i64,
+        #[serde(default)]
+        messages: Vec<TaskMessage>,
+    }
+
+    #[derive(Deserialize)]
+    struct TaskMessage {
+        body: String,
+        sender: Option<String>,
+    }
+
+    loop {
+        sleep(Duration::from_secs(15)).await;
+        let mut request = client
+            .post(format!("{}/v1/tasks/{}/heartbeat", api_url, task_id))
+            .query(&[("worker_id", &worker_id)]);
+        if pool.enabled() {
+            let (ready, hits, misses) = pool.stats();
+            request = request.query(&[
+                ("pool_ready", ready as u64),
+                ("pool_hits", hits),
+                ("pool_misses", misses),
+            ]);
+        }
+        let status = match request.send().await {
+            Ok(res) if res.status() == reqwest::StatusCode::CONFLICT => {
+                return "reassigned".to_string();
+            }
+            Ok(res) => res.json::<TaskStatus>().await.ok(),
+            Err(e) => {
+                debug!("Heartbeat for task {} failed: {}", task_id, e);
+                None
+            }
+        };
+        let Some(status) = status else { continue };
+        for message in &status.messages {
+            let sender = message.sender.as_deref().unwrap_or("operator");
+            info!(
+                "Message for task {} from {}: {}",
+                task_id, sender, message.body
+            );
+            work_log.entry(
+                "message",
+                format!(
+                    "from {} (not forwarded, agent is non-interactive): {}",
+                    sender, message.body
+                ),
+            );
+        }
+        if status.status != "running" || status.retry_count != retry_count {
+            return status.status;
+        }
+    }
+}
+
+/// Heartbeats running in the background for a claimed task; they stop when
+/// this is dropped, however the crab is done with the task
+struct Heartbeats(JoinHandle<String>);
+
+impl Heartbeats {
+    /// The task's status once the control plane has taken it back
+    async fn taken_back(&mut self) -> String {
+        (&mut self.0)
+            .await
+            .unwrap_or_else(|e| format!("unknown ({})", e))
+    }
+}
+
+impl Drop for Heartbeats {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Extract the last `SCORE: N` line an agent printed (used by review steps)
+fn parse_score(stdout: &str) -> Option<i64> {
+    stdout.lines().rev().find_map(|line| {
+        line.trim()
+            .strip_prefix("SCORE:")
+            .and_then(|rest| rest.trim().parse().ok())
+    })
+}
+
+/// Workflow a classify step picked, from a trailing `WORKFLOW: <name>` line
+fn parse_next_workflow(stdout: &str) -> Option<String> {
+    stdout.lines().rev().find_map(|line| {
+        line.trim()
+            .strip_prefix("WORKFLOW:")
+            .map(|rest| rest.trim().to_string())
+            .filter(|name| !name.is_empty())
+    })
+}
+
+/// Verdicts on the issue's acceptance criteria, from `CRITERION <n>: PASS` or
+/// `CRITERION <n>: FAIL - <reason>` lines; the last line for a criterion wins
+fn parse_criteria(stdout: &str) -> Vec<CriterionResult> {
+    let mut results: Vec<CriterionResult> = Vec::new();
+    for line in stdout.lines() {
+        let Some((index, verdict)) = line
+            .trim()
+            .strip_prefix("CRITERION")
+            .and_then(|rest| rest.split_once(':'))
+        else {
+            continue;
+        };
+        let Ok(index) = index.trim().parse::<i64>() else {
+            continue;
+        };
+        let verdict = verdict.trim();
+        let (passed, rest) = if let Some(rest) = verdict.strip_prefix("PASS") {
+            (true, rest)
+        } else if let Some(rest) = verdict.strip_prefix("FAIL") {
+            (false, rest)
+        } else {
+            continue;
+        };
+        let note = rest
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '-' || c == ':')
+            .trim();
+        results.retain(|r| r.index != index);
+        results.push(CriterionResult {
+            index,
+            passed,
+            note: (!note.is_empty()).then(|| note.to_string()),
+        });
+    }
+    results
+}
+
+/// Most bytes of a step's diff sent with its run, for risk scoring
+const MAX_REPORTED_DIFF_BYTES: usize = 256 * 1024;
+
+/// Paths changed in `worktree` since `base`, and the diff, cut to
+/// `MAX_REPORTED_DIFF_BYTES`; uncommitted changes count too
+fn step_diff(args: &Args, worktree: &Path, base: &str) -> (Vec<String>, Option<String>) {
+    let git = |extra: &[&str]| {
+        new_git_command(args)
+            .arg("diff")
+            .args(extra)
+            .arg(base)
+            .current_dir(worktree)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+    };
+    let changed_files = git(&["--name-only"])
+        .map(|names| names.lines().map(String::from).collect())
+        .unwrap_or_default();
+    let diff = git(&[]).map(|mut diff| {
+        if diff.len() > MAX_REPORTED_DIFF_BYTES {
+            let mut end = MAX_REPORTED_DIFF_BYTES;
+            while !diff.is_char_boundary(end) {
+                end -= 1;
+            }
+            diff.truncate(end);
+        }
+        diff
+    });
+    (changed_files, diff)
+}
+
 fn new_git_command(args: &Args) -> Command {
     let mut cmd = Command::new("git");
     if args.yolo {
@@ -145,90 +1233,379 @@ fn new_git_command(args: &Args) -> Command {
     cmd
 }
 
-async fn poll_and_execute(
+/// The configured burrow root with the most free space, or `burrows/` in the repo
+fn pick_burrow_root(args: &Args, repo_root: &Path) -> PathBuf {
+    args.burrow_roots
+        .iter()
+        .filter(|root| std::fs::create_dir_all(root).is_ok())
+        .max_by_key(|root| fs4::available_space(root).unwrap_or(0))
+        .cloned()
+        .unwrap_or_else(|| repo_root.join("burrows"))
+}
+
+/// Resolve `--burrow-template` for a task
+fn burrow_path_for(
+    args: &Args,
+    repo_root: &Path,
+    task: &Task,
+    branch: &str,
+    throwaway: Option<&str>,
+) -> PathBuf {
+    let burrow = match throwaway {
+        Some(name) => name.to_string(),
+        None => branch.replace("/", "-"),
+    };
+    let repo = repo_root
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let root = pick_burrow_root(args, repo_root);
+    PathBuf::from(
+        args.burrow_template
+            .replace("{root}", &root.to_string_lossy())
+            .replace("{repo}", &repo)
+            .replace("{mission}", &task.mission_id)
+            .replace("{task}", &task.task_id)
+            .replace("{burrow}", &burrow),
+    )
+}
+
+/// The repo a worktree belongs to, wherever the worktree lives
+fn repo_root_of(args: &Args, worktree_path: &Path) -> Option<PathBuf> {
+    let output = new_git_command(args)
+        .args(["rev-parse", "--path-format=absolute", "--git-common-dir"])
+        .current_dir(worktree_path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let git_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    git_dir.parent().map(Path::to_path_buf)
+}
+
+/// The mission burrow this worker holds the control-plane lease on, if any
+async fn leased_burrow(
     args: &Args,
     client: &reqwest::Client,
+    mission_id: &str,
     worker_id: &str,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    // 1. Fetch next task
+) -> Option<PathBuf> {
     let res = client
-        .get(format!("{}/v1/tasks/next", args.api_url))
+        .get(format!(
+            "{}/v1/missions/{}/burrow",
+            args.api_url, mission_id
+        ))
+        .send()
+        .await
+        .ok()?;
+    let lease = res.json::<BurrowLease>().await.ok()?;
+    (lease.worker_id == worker_id).then(|| PathBuf::from(lease.path))
+}
+
+/// Tear down burrows whose mission finished or moved to another crab, then release the lease
+async fn cleanup_stale_burrows(
+    args: &Args,
+    client: &reqwest::Client,
+    worker_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let leases: Vec<BurrowLease> = client
+        .get(format!("{}/v1/burrows/stale", args.api_url))
         .query(&[("worker_id", worker_id)])
         .send()
+        .await?
+        .json()
         .await?;
 
-    if res.status() == reqwest::StatusCode::NOT_FOUND {
-        return Ok(false);
+    for lease in leases {
+        let path = PathBuf::from(&lease.path);
+        info!(
+            "Cleaning up burrow {:?} for mission {}",
+            path, lease.mission_id
+        );
+        // Default burrows live at {repo_root}/burrows/{name}
+        let repo_root = repo_root_of(args, &path)
+            .or_else(|| path.parent().and_then(Path::parent).map(Path::to_path_buf));
+        if let Some(repo_root) = repo_root {
+            let _ = new_git_command(args)
+                .args(["worktree", "remove", "--force", &lease.path])
+                .current_dir(repo_root)
+                .status();
+        }
+        client
+            .delete(format!(
+                "{}/v1/missions/{}/burrow",
+                args.api_url, lease.mission_id
+            ))
+            .query(&[("worker_id", worker_id)])
+            .send()
+            .await?;
     }
+    Ok(())
+}
 
-    let task_data: TaskResponse = res.json().await?;
-    let task_id = &task_data.task.task_id;
+/// Worktrees pre-created at the base branch, with dependencies installed by
+/// `--warm-setup`, that new mission burrows take over instead of starting cold.
+///
+/// Warm burrows live at `{repo_root}/burrows/.warm-{id}` with a `.stamp` file
+/// beside each recording when it was last refreshed. The pool is topped up
+/// and refreshed while the crab is idle. A warm burrow can't be moved to a
+/// `--burrow-root` on another filesystem; those burrows start cold.
+struct WarmPool {
+    size: usize,
+    setup: Option<String>,
+    refresh: Duration,
+    /// Repos this crab has worked in; only those get a pool
+    repos: Mutex<BTreeSet<PathBuf>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
 
-    info!(
-        "Found task {} for repo {}",
-        task_id,
-        task_data.git.repo_url.as_deref().unwrap_or("(local)")
-    );
+impl WarmPool {
+    fn new(args: &Args) -> Self {
+        Self {
+            size: args.warm_burrows,
+            setup: args.warm_setup.clone(),
+            refresh: Duration::from_secs(args.warm_refresh),
+            repos: Mutex::new(BTreeSet::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
 
-    // 2. Mark as running
-    client
-        .post(format!("{}/v1/tasks/{}/status", args.api_url, task_id))
-        .json(&UpdateStatusRequest {
-            status: "running".into(),
-        })
-        .send()
-        .await?;
+    fn enabled(&self) -> bool {
+        self.size > 0
+    }
 
-    // 3. Resolve Paths via API
-    let agent_path = get_env_path(client, &args.api_url, &args.env, "agent", &args.agent)
-        .await
-        .unwrap_or_else(|| args.agent.clone());
+    fn remember(&self, repo_root: &Path) {
+        if self.enabled() {
+            self.repos.lock().unwrap().insert(repo_root.to_path_buf());
+        }
+    }
 
-    // 4. Setup Environment (Clone or CD)
-    let repo_root = if let Some(lp) = &task_data.git.local_path {
-        PathBuf::from(lp)
-    } else {
-        // Deterministic cache path based on repo URL
-        let repo_url = task_data
-            .git
-            .repo_url
-            .as_ref()
-            .ok_or("No repo_url or local_path provided")?;
-        let repo_name = repo_url.split('/').next_back().unwrap().replace(".git", "");
-
-        match get_env_path(client, &args.api_url, &args.env, "repo", &repo_name).await {
-            Some(p) => PathBuf::from(p),
-            None => {
-                let cache_path = PathBuf::from(&args.burrows_root)
-                    .join("cache")
-                    .join(&repo_name);
-
-                if !cache_path.exists() {
-                    info!("Cloning repo {} to {:?}", repo_url, cache_path);
-                    std::fs::create_dir_all(cache_path.parent().unwrap())?;
-                    let status = new_git_command(args)
-                        .args(["clone", repo_url.as_str(), cache_path.to_str().unwrap()])
-                        .status()?;
-                    if !status.success() {
-                        return Err("Failed to clone repository".into());
-                    }
+    /// Warm burrows ready to be claimed in `repo_root`
+    fn ready(repo_root: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(repo_root.join("burrows")) else {
+            return Vec::new();
+        };
+        let mut ready: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with(".warm-"))
+                    && p.extension().is_none()
+                    && p.with_extension("stamp").exists()
+            })
+            .collect();
+        ready.sort();
+        ready
+    }
+
+    /// Counters for the control plane: `(ready, hits, misses)`
+    fn stats(&self) -> (usize, u64, u64) {
+        let ready = self
+            .repos
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| Self::ready(r).len())
+            .sum();
+        (
+            ready,
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Move a warm burrow to `worktree_path` and check out `branch` in it.
+    /// Returns false, leaving nothing behind, when the pool can't serve the request.
+    fn take(
+        &self,
+        args: &Args,
+        repo_root: &Path,
+        worktree_path: &Path,
+        branch: &str,
+        branch_exists: bool,
+    ) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        let Some(warm) = Self::ready(repo_root).into_iter().next() else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return false;
+        };
+        let _ = std::fs::remove_file(warm.with_extension("stamp"));
+
+        let moved = new_git_command(args)
+            .args(["worktree", "move"])
+            .arg(&warm)
+            .arg(worktree_path)
+            .current_dir(repo_root)
+            .status()
+            .is_ok_and(|s| s.success());
+        if !moved {
+            warn!("Failed to move warm burrow {:?}, dropping it", warm);
+            Self::remove(args, repo_root, &warm);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let mut checkout = new_git_command(args);
+        checkout.arg("checkout");
+        if !branch_exists {
+            checkout.arg("-b");
+        }
+        let checked_out = checkout
+            .arg(branch)
+            .current_dir(worktree_path)
+            .status()
+            .is_ok_and(|s| s.success());
+        if !checked_out {
+            warn!("Failed to check out {} in warm burrow, recreating", branch);
+            Self::remove(args, repo_root, worktree_path);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        info!("Claimed warm burrow {:?} as {:?}", warm, worktree_path);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    fn remove(args: &Args, repo_root: &Path, path: &Path) {
+        let _ = new_git_command(args)
+            .args(["worktree", "remove", "--force"])
+            .arg(path)
+            .current_dir(repo_root)
+            .status();
+        let _ = std::fs::remove_file(path.with_extension("stamp"));
+    }
+
+    /// Refresh warm burrows older than the refresh interval and create missing
+    /// ones. Blocks on git and the setup command; run it off the async runtime.
+    fn replenish(&self, args: &Args) {
+        let repos: Vec<PathBuf> = self.repos.lock().unwrap().iter().cloned().collect();
+        for repo_root in repos {
+            let ready = Self::ready(&repo_root);
+            let stale: Vec<&PathBuf> = ready
+                .iter()
+                .filter(|p| {
+                    std::fs::metadata(p.with_extension("stamp"))
+                        .and_then(|m| m.modified())
+                        .map(|t| t.elapsed().unwrap_or_default() >= self.refresh)
+                        .unwrap_or(true)
+                })
+                .collect();
+            if stale.is_empty() && ready.len() >= self.size {
+                continue;
+            }
+
+            let _ = new_git_command(args)
+                .args(["fetch", "origin"])
+                .current_dir(&repo_root)
+                .status();
+            let base = ["origin/HEAD", "HEAD"]
+                .into_iter()
+                .find(|r| {
+                    new_git_command(args)
+                        .args(["rev-parse", "--verify", "--quiet", r])
+                        .current_dir(&repo_root)
+                        .status()
+                        .is_ok_and(|s| s.success())
+                })
+                .unwrap_or("HEAD");
+
+            for warm in stale {
+                // Keep ignored files so installed dependencies survive the reset
+                let reset = new_git_command(args)
+                    .args(["checkout", "--force", "--detach", base])
+                    .current_dir(warm)
+                    .status()
+                    .is_ok_and(|s| s.success())
+                    && new_git_command(args)
+                        .args(["clean", "-fd"])
+                        .current_dir(warm)
+                        .status()
+                        .is_ok_and(|s| s.success());
+                if reset && self.set_up(warm) {
+                    debug!("Refreshed warm burrow {:?}", warm);
+                } else {
+                    warn!("Failed to refresh warm burrow {:?}, dropping it", warm);
+                    Self::remove(args, &repo_root, warm);
+                }
+            }
+
+            for _ in Self::ready(&repo_root).len()..self.size {
+                let warm = repo_root
+                    .join("burrows")
+                    .join(format!(".warm-{}", uuid::Uuid::new_v4()));
+                let added = new_git_command(args)
+                    .args(["worktree", "add", "--detach"])
+                    .arg(&warm)
+                    .arg(base)
+                    .current_dir(&repo_root)
+                    .status()
+                    .is_ok_and(|s| s.success());
+                if added && self.set_up(&warm) {
+                    info!("Created warm burrow {:?}", warm);
+                } else {
+                    warn!("Failed to create warm burrow in {:?}", repo_root);
+                    Self::remove(args, &repo_root, &warm);
+                    break;
                 }
-                cache_path
             }
         }
-    };
+    }
 
-    // 5. Update repo state
-    info!("Fetching latest state from origin...");
-    let _ = new_git_command(args)
-        .arg("fetch")
-        .arg("origin")
-        .current_dir(&repo_root)
-        .status();
+    /// Run the setup command and stamp the burrow as ready
+    fn set_up(&self, warm: &Path) -> bool {
+        if let Some(setup) = &self.setup {
+            let ok = Command::new("sh")
+                .args(["-c", setup])
+                .current_dir(warm)
+                .status()
+                .is_ok_and(|s| s.success());
+            if !ok {
+                return false;
+            }
+        }
+        std::fs::write(warm.with_extension("stamp"), "").is_ok()
+    }
+}
 
-    // 6. Create Worktree
-    let worktree_name = task_data.git.branch.replace("/", "-");
-    let worktree_path = repo_root.join("burrows").join(worktree_name);
+/// Create the git worktree a task runs in. Debug runs get their own detached
+/// checkout so nothing they do lands on the mission branch; new mission
+/// burrows come from the warm pool when it has one ready.
+fn create_worktree(
+    args: &Args,
+    pool: &WarmPool,
+    repo_root: &Path,
+    worktree_path: PathBuf,
+    branch: &str,
+    detached: bool,
+    reuse: bool,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if reuse && worktree_path.exists() {
+        // Keep ignored files (dependency installs, build caches); drop everything else
+        info!("Reusing leased worktree {:?}", worktree_path);
+        let reset = new_git_command(args)
+            .args(["reset", "--hard"])
+            .current_dir(&worktree_path)
+            .status()?;
+        let clean = new_git_command(args)
+            .args(["clean", "-fd"])
+            .current_dir(&worktree_path)
+            .status()?;
+        if reset.success() && clean.success() {
+            let _ = new_git_command(args)
+                .args(["pull", "--ff-only", "origin", branch])
+                .current_dir(&worktree_path)
+                .status();
+            return Ok(worktree_path);
+        }
+        warn!("Failed to reset worktree {:?}, recreating", worktree_path);
+    }
 
     if worktree_path.exists() {
         info!("Cleaning up existing worktree {:?}", worktree_path);
@@ -239,39 +1616,72 @@ async fn poll_and_execute(
                 "--force",
                 worktree_path.to_str().unwrap(),
             ])
-            .current_dir(&repo_root)
+            .current_dir(repo_root)
             .status();
     }
 
+    if let Some(parent) = worktree_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
     // Check if the branch already exists locally or remotely
     let branch_exists = new_git_command(args)
         .args(["show-ref", "--verify", "--quiet"])
-        .arg(format!("refs/heads/{}", task_data.git.branch))
-        .current_dir(&repo_root)
+        .arg(format!("refs/heads/{}", branch))
+        .current_dir(repo_root)
         .status()
         .map(|s| s.success())
         .unwrap_or(false)
         || new_git_command(args)
             .args(["show-ref", "--verify", "--quiet"])
-            .arg(format!("refs/remotes/origin/{}", task_data.git.branch))
-            .current_dir(&repo_root)
+            .arg(format!("refs/remotes/origin/{}", branch))
+            .current_dir(repo_root)
             .status()
             .map(|s| s.success())
             .unwrap_or(false);
 
-    if branch_exists {
+    if detached {
+        // Detached checkout of the mission branch so nothing is committed back to it
+        let base = [branch.to_string(), format!("origin/{}", branch)]
+            .into_iter()
+            .find(|r| {
+                new_git_command(args)
+                    .args(["rev-parse", "--verify", "--quiet", r])
+                    .current_dir(repo_root)
+                    .status()
+                    .map(|s| s.success())
+                    .unwrap_or(false)
+            })
+            .unwrap_or_else(|| "HEAD".into());
+
         info!(
-            "Branch {} exists, creating worktree and checking it out at {:?}",
-            task_data.git.branch, worktree_path
+            "Creating detached worktree at {:?} from {}",
+            worktree_path, base
         );
         let status = new_git_command(args)
             .args([
                 "worktree",
                 "add",
+                "--detach",
                 worktree_path.to_str().unwrap(),
-                &task_data.git.branch,
+                &base,
             ])
-            .current_dir(&repo_root)
+            .current_dir(repo_root)
+            .status()?;
+
+        if !status.success() {
+            return Err("Failed to create detached worktree".into());
+        }
+    } else if pool.take(args, repo_root, &worktree_path, branch, branch_exists) {
+        // Warm burrow moved into place with the branch checked out
+    } else if branch_exists {
+        info!(
+            "Branch {} exists, creating worktree and checking it out at {:?}",
+            branch, worktree_path
+        );
+        let status = new_git_command(args)
+            .args(["worktree", "add", worktree_path.to_str().unwrap(), branch])
+            .current_dir(repo_root)
             .status()?;
 
         if !status.success() {
@@ -280,7 +1690,7 @@ async fn poll_and_execute(
     } else {
         info!(
             "Creating new branch {} and worktree at {:?}",
-            task_data.git.branch, worktree_path
+            branch, worktree_path
         );
         let status = new_git_command(args)
             .args([
@@ -288,9 +1698,9 @@ async fn poll_and_execute(
                 "add",
                 worktree_path.to_str().unwrap(),
                 "-b",
-                &task_data.git.branch,
+                branch,
             ])
-            .current_dir(&repo_root)
+            .current_dir(repo_root)
             .status()?;
 
         if !status.success() {
@@ -298,17 +1708,304 @@ async fn poll_and_execute(
         }
     }
 
-    // 7. Final Prompt Resolution
-    let final_prompt = task_data
-        .task
-        .assembled_prompt
-        .replace("{{worktree_path}}", worktree_path.to_str().unwrap());
+    Ok(worktree_path)
+}
+
+async fn poll_and_execute(
+    args: &Args,
+    client: &reqwest::Client,
+    worker_id: &str,
+    executors: &str,
+    stack: &mut Option<PromptStack>,
+    pool: &Arc<WarmPool>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    // 1. Claim next task (the control plane marks it running for this worker)
+    let mut query = vec![
+        ("worker_id", worker_id),
+        ("delta", "true"),
+        ("executors", executors),
+    ];
+    if let Some(role) = &args.role {
+        query.push(("role", role));
+    }
+    let res = client
+        .post(format!("{}/v1/tasks/claim", args.api_url))
+        .query(&query)
+        .send()
+        .await?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+
+    let task_data: TaskResponse = res.error_for_status()?.json().await?;
+    let task_id = &task_data.task.task_id;
+
+    // The control-plane reports the current stack hash; resync if it moved on
+    if let (Some(role), Some(hash)) = (&args.role, &task_data.stack_hash)
+        && stack.as_ref().map(|s| &s.hash) != Some(hash)
+    {
+        info!("Prompt stack for role {} changed, resyncing", role);
+        match sync_stack(args, client, role).await {
+            Ok(fresh) => *stack = Some(fresh),
+            Err(e) => warn!("Failed to resync prompt stack: {}", e),
+        }
+    }
+
+    info!(
+        "Found task {} for repo {}",
+        task_id,
+        task_data.git.repo_url.as_deref().unwrap_or("(local)")
+    );
+
+    let work_log = WorkLog::open(&args.burrows_root, task_id);
+    work_log.entry(
+        "claim",
+        format!(
+            "task {} step {} on worker {}",
+            task_id, task_data.task.step_id, worker_id
+        ),
+    );
+
+    // 2. Debug runs leave the task's status alone
+    let debug_run_id = task_data.debug_run_id.as_deref();
+
+    // A checkout this crab doesn't have is another crab's job, not a failure
+    if debug_run_id.is_none()
+        && let Some(lp) = &task_data.git.local_path
+        && !Path::new(lp).exists()
+    {
+        let reason = format!("local path {} does not exist on this crab", lp);
+        warn!("Rejecting task {}: {}", task_id, reason);
+        work_log.entry("claim", format!("rejected: {}", reason));
+        client
+            .post(format!("{}/v1/tasks/{}/reject", args.api_url, task_id))
+            .json(&RejectTaskRequest {
+                worker_id: worker_id.to_string(),
+                reason,
+            })
+            .send()
+            .await?;
+        return Ok(true);
+    }
+    if let Some(run_id) = debug_run_id {
+        info!("Executing debug run {}", run_id);
+    }
+
+    // Heartbeat from the claim on: a slow clone mustn't look like a dead crab.
+    // Debug runs don't belong to the mission flow: no watchdog, no cancellation.
+    let mut heartbeats = debug_run_id.is_none().then(|| {
+        Heartbeats(tokio::spawn(heartbeat_until_cancelled(
+            client.clone(),
+            args.api_url.clone(),
+            task_id.clone(),
+            worker_id.to_string(),
+            task_data.task.retry_count,
+            work_log.clone(),
+            pool.clone(),
+        )))
+    });
+
+    // Read-only steps and debug runs get a throwaway detached worktree, so
+    // nothing they do reaches the repo checkout or the mission branch
+    let read_only = task_data.task.step_config.read_only;
+    let burrow_mode = if read_only {
+        "read_only"
+    } else if debug_run_id.is_some() {
+        "detached"
+    } else {
+        "worktree"
+    };
+
+    // Steps 3-7 can fail on environment problems; those still produce a run with the work log
+    let setup: Result<(String, PathBuf, PathBuf, String), Box<dyn std::error::Error>> = async {
+        // 3. Resolve Paths via API
+        let agent_path = get_env_path(client, &args.api_url, &args.env, "agent", &args.agent)
+            .await
+            .unwrap_or_else(|| args.agent.clone());
+        work_log.entry(
+            "setup",
+            format!("agent {} resolved to {}", args.agent, agent_path),
+        );
+
+        // 4. Setup Environment (Clone or CD)
+        let repo_root = if let Some(lp) = &task_data.git.local_path {
+            PathBuf::from(lp)
+        } else {
+            // Deterministic cache path based on repo URL
+            let repo_url = task_data
+                .git
+                .repo_url
+                .as_ref()
+                .ok_or("No repo_url or local_path provided")?;
+            let repo_name = repo_url.split('/').next_back().unwrap().replace(".git", "");
+
+            match get_env_path(client, &args.api_url, &args.env, "repo", &repo_name).await {
+                Some(p) => PathBuf::from(p),
+                None => {
+                    let cache_path = PathBuf::from(&args.burrows_root)
+                        .join("cache")
+                        .join(&repo_name);
+
+                    if !cache_path.exists() {
+                        info!("Cloning repo {} to {:?}", repo_url, cache_path);
+                        work_log.entry("repo", format!("cloning {} to {:?}", repo_url, cache_path));
+                        std::fs::create_dir_all(cache_path.parent().unwrap())?;
+                        let status = new_git_command(args)
+                            .args(["clone", repo_url.as_str(), cache_path.to_str().unwrap()])
+                            .status()?;
+                        if !status.success() {
+                            return Err("Failed to clone repository".into());
+                        }
+                    }
+                    cache_path
+                }
+            }
+        };
+        work_log.entry("repo", format!("repo root {:?}", repo_root));
+        pool.remember(&repo_root);
+
+        // 5. Update repo state
+        info!("Fetching latest state from origin...");
+        let fetch = new_git_command(args)
+            .arg("fetch")
+            .arg("origin")
+            .current_dir(&repo_root)
+            .status();
+        work_log.entry(
+            "repo",
+            format!("git fetch origin: {:?}", fetch.map(|s| s.code())),
+        );
+
+        // 6. Prepare Burrow
+        let throwaway = match debug_run_id {
+            Some(run_id) => Some(format!("debug-{}", run_id)),
+            None if read_only => Some(format!("read-{}", task_id)),
+            None => None,
+        };
+        let worktree_path = if let Some(name) = throwaway {
+            let path = burrow_path_for(
+                args,
+                &repo_root,
+                &task_data.task,
+                &task_data.git.branch,
+                Some(&name),
+            );
+            create_worktree(
+                args,
+                pool,
+                &repo_root,
+                path,
+                &task_data.git.branch,
+                true,
+                false,
+            )?
+        } else {
+            // Mission burrows survive between steps on the same crab while we hold the lease
+            let mission_id = &task_data.task.mission_id;
+            let leased = leased_burrow(args, client, mission_id, worker_id).await;
+            work_log.entry("burrow", format!("lease held on {:?}", leased));
+            let reuse = leased.is_some();
+            let path = leased.unwrap_or_else(|| {
+                burrow_path_for(
+                    args,
+                    &repo_root,
+                    &task_data.task,
+                    &task_data.git.branch,
+                    None,
+                )
+            });
+            let path = create_worktree(
+                args,
+                pool,
+                &repo_root,
+                path,
+                &task_data.git.branch,
+                false,
+                reuse,
+            )?;
+            client
+                .post(format!(
+                    "{}/v1/missions/{}/burrow",
+                    args.api_url, mission_id
+                ))
+                .json(&serde_json::json!({
+                    "worker_id": worker_id,
+                    "path": path.to_string_lossy(),
+                }))
+                .send()
+                .await?;
+            path
+        };
+        work_log.entry("burrow", format!("{} at {:?}", burrow_mode, worktree_path));
+
+        // 7. Final Prompt Resolution
+        let mut final_prompt = resolve_prompt(args, client, &task_data.task)
+            .await?
+            .replace("{{worktree_path}}", worktree_path.to_str().unwrap());
+
+        if let Some(stack) = stack.as_ref() {
+            if !read_only {
+                write_system_context(&worktree_path, stack)?;
+            }
+            work_log.entry("setup", format!("prompt stack {} loaded", stack.hash));
+            final_prompt = format!("{}\n\n{}", stack.content, final_prompt);
+        }
+
+        Ok((agent_path, repo_root, worktree_path, final_prompt))
+    }
+    .await;
+
+    let (agent_path, repo_root, worktree_path, final_prompt) = match setup {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            error!("Setup for task {} failed: {}", task_id, e);
+            work_log.entry("setup", format!("failed: {}", e));
+            client
+                .post(format!("{}/v1/tasks/{}/runs", args.api_url, task_id))
+                .json(&CreateRunRequest {
+                    run_id: None,
+                    status: "failed".into(),
+                    logs: Some(format!("Setup failed: {}", e)),
+                    summary: None,
+                    duration_ms: None,
+                    tokens_used: None,
+                    score: None,
+                    worker_id: Some(worker_id.to_string()),
+                    debug_run_id: debug_run_id.map(String::from),
+                    burrow_mode: Some(burrow_mode.into()),
+                    burrow_path: None,
+                    next_workflow: None,
+                    work_log: work_log.contents(),
+                    failure_kind: None,
+                    criteria: Vec::new(),
+                    changed_files: Vec::new(),
+                    diff: None,
+                    network: network_report(task_data.task.step_config.network.as_deref()),
+                })
+                .send()
+                .await?;
+            return Err(e);
+        }
+    };
 
     // 8. Execute Agent
     info!("Spawning agent: {} in {:?}", agent_path, worktree_path);
+    // Where the step started, so its diff can be reported for risk scoring
+    let start_head = (debug_run_id.is_none() && !read_only)
+        .then(|| {
+            new_git_command(args)
+                .args(["rev-parse", "HEAD"])
+                .current_dir(&worktree_path)
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        })
+        .flatten();
     let start_time = Instant::now();
 
-    let mut child = Command::new(&agent_path);
+    let mut child = tokio::process::Command::new(&agent_path);
 
     // Full tool use: ensure the agent inherits the parent shell's PATH and environment
     child.env("PATH", std::env::var("PATH").unwrap_or_default());
@@ -337,85 +2034,214 @@ async fn poll_and_execute(
         child.arg(&final_prompt);
     }
 
-    let output = child.current_dir(&worktree_path).output();
+    let network = task_data.task.step_config.network.as_deref();
+    if let Some(allowed) =
+        apply_proxy_hint(&mut child, network, args, task_data.git.repo_url.as_deref())
+    {
+        work_log.entry(
+            "proxy hint",
+            format!(
+                "{}: proxy variables point at a closed port except for {}; not enforced",
+                network.unwrap_or_default(),
+                allowed.join(", ")
+            ),
+        );
+    }
+
+    let timeout_secs = task_data
+        .task
+        .step_config
+        .timeout_secs
+        .unwrap_or(args.exec_timeout);
+    work_log.entry(
+        "exec",
+        format!(
+            "{} (timeout {}s)",
+            describe_invocation(child.as_std(), &final_prompt),
+            timeout_secs
+        ),
+    );
+    child.current_dir(&worktree_path);
+    let cancelled = async {
+        match heartbeats.as_mut() {
+            Some(heartbeats) => heartbeats.taken_back().await,
+            None => std::future::pending().await,
+        }
+    };
+    // Debug runs already have their id; others pick one now so output can stream under it
+    let run_id = debug_run_id
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let (tee, log_shipper) = spawn_log_shipper(client, &args.api_url, &run_id, task_id);
+    let output = run_agent(
+        child,
+        Duration::from_secs(timeout_secs),
+        cancelled,
+        Some(tee),
+    )
+    .await;
+    drop(heartbeats);
+    // Land the last batch before the run is recorded, which ends any followers
+    let _ = tokio::time::timeout(Duration::from_secs(10), log_shipper).await;
 
     let duration = start_time.elapsed();
+    work_log.entry(
+        "exec",
+        format!(
+            "finished in {}ms: {:?}",
+            duration.as_millis(),
+            output.as_ref().map(|o| &o.exit)
+        ),
+    );
 
     // 9. Handle Result
-    let (success, logs) = match output {
+    let mut summary = None;
+    let mut failure_kind = None;
+    let mut criteria = Vec::new();
+    let mut changed_files = Vec::new();
+    let mut diff = None;
+    let (success, logs, score, next_workflow) = match output {
         Ok(out) => {
-            let stdout = String::from_utf8_lossy(&out.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-            let combined_logs = format!("STDOUT:\n{}\n\nSTDERR:\n{}", stdout, stderr);
-
-            if out.status.success() {
-                info!(
-                    "Task {} completed successfully. Pushing changes...",
-                    task_id
-                );
-                let _ = new_git_command(args)
-                    .args(["push", "origin", &task_data.git.branch])
-                    .current_dir(&worktree_path)
-                    .status();
-                (true, combined_logs)
-            } else {
-                warn!(
-                    "Task {} failed with exit code: {:?}",
-                    task_id,
-                    out.status.code()
-                );
-                (false, combined_logs)
+            let score = parse_score(&out.stdout);
+            criteria = parse_criteria(&out.stdout);
+            let next_workflow = parse_next_workflow(&out.stdout);
+            let combined_logs = format!("STDOUT:\n{}\n\nSTDERR:\n{}", out.stdout, out.stderr);
+
+            match out.exit {
+                AgentExit::Cancelled(status) => {
+                    warn!("Task {} was taken back ({}), agent killed", task_id, status);
+                    summary = Some(if status == "skipped" {
+                        "Mission cancelled; the agent was killed and logs hold its output up to that point."
+                            .to_string()
+                    } else {
+                        format!(
+                            "Control plane took the task back (now {}); the agent was killed and logs hold its output up to that point.",
+                            status
+                        )
+                    });
+                    failure_kind = Some("cancelled".to_string());
+                    (false, combined_logs, score, next_workflow)
+                }
+                AgentExit::TimedOut => {
+                    warn!("Task {} timed out after {}s", task_id, timeout_secs);
+                    summary = Some(format!(
+                        "Agent timed out after {}s and was killed; logs hold its output up to that point.",
+                        timeout_secs
+                    ));
+                    failure_kind = Some("timeout".to_string());
+                    (false, combined_logs, score, next_workflow)
+                }
+                AgentExit::Exited(status) if status.success() => {
+                    if debug_run_id.is_none() && !read_only {
+                        if let Some(base) = &start_head {
+                            (changed_files, diff) = step_diff(args, &worktree_path, base);
+                        }
+                        info!(
+                            "Task {} completed successfully. Pushing changes...",
+                            task_id
+                        );
+                        let push = new_git_command(args)
+                            .args(["push", "origin", &task_data.git.branch])
+                            .current_dir(&worktree_path)
+                            .status();
+                        work_log.entry(
+                            "push",
+                            format!(
+                                "git push origin {}: {:?}",
+                                task_data.git.branch,
+                                push.map(|s| s.code())
+                            ),
+                        );
+                    }
+                    (true, combined_logs, score, next_workflow)
+                }
+                AgentExit::Exited(status) => {
+                    warn!(
+                        "Task {} failed with exit code: {:?}",
+                        task_id,
+                        status.code()
+                    );
+                    (false, combined_logs, score, next_workflow)
+                }
             }
         }
         Err(e) => {
             error!("Failed to spawn agent: {}", e);
-            (false, format!("Failed to spawn agent: {}", e))
+            (false, format!("Failed to spawn agent: {}", e), None, None)
         }
     };
 
+    // Debug runs and read-only steps drop their throwaway worktree
+    if burrow_mode != "worktree" {
+        let removed = new_git_command(args)
+            .args([
+                "worktree",
+                "remove",
+                "--force",
+                worktree_path.to_str().unwrap(),
+            ])
+            .current_dir(&repo_root)
+            .status();
+        work_log.entry(
+            "cleanup",
+            format!(
+                "removed worktree {:?}: {:?}",
+                worktree_path,
+                removed.map(|s| s.code())
+            ),
+        );
+    }
+
     // 10. Record Run
+    let cancelled = failure_kind.as_deref() == Some("cancelled");
     let final_status = if success { "completed" } else { "failed" };
     client
         .post(format!("{}/v1/tasks/{}/runs", args.api_url, task_id))
         .json(&CreateRunRequest {
+            run_id: Some(run_id),
             status: final_status.into(),
             logs: Some(logs),
-            summary: None,
+            summary,
             duration_ms: Some(duration.as_millis() as i64),
             tokens_used: None,
+            score,
+            worker_id: Some(worker_id.to_string()),
+            debug_run_id: debug_run_id.map(String::from),
+            burrow_mode: Some(burrow_mode.into()),
+            burrow_path: Some(worktree_path.to_string_lossy().to_string()),
+            next_workflow,
+            work_log: work_log.contents(),
+            failure_kind,
+            criteria,
+            changed_files,
+            diff,
+            network: network_report(task_data.task.step_config.network.as_deref()),
         })
         .send()
         .await?;
 
-    // 11. Report Result or Retry
-    if success {
-        client
-            .post(format!("{}/v1/tasks/{}/status", args.api_url, task_id))
-            .json(&UpdateStatusRequest {
-                status: "completed".into(),
-            })
-            .send()
-            .await?;
-    } else if task_data.task.retry_count < task_data.task.max_retries {
+    // Debug runs never report task status, and the control plane already moved a cancelled task on
+    if debug_run_id.is_some() || cancelled {
+        return Ok(true);
+    }
+
+    // 11. Report Result; the control plane requeues failures while retries remain
+    if !success && task_data.task.retry_count < task_data.task.max_retries {
         info!(
-            "Retrying task {} ({} of {})",
+            "Task {} failed, control plane will retry ({} of {})",
             task_id,
             task_data.task.retry_count + 1,
             task_data.task.max_retries
         );
-        client
-            .post(format!("{}/v1/tasks/{}/retry", args.api_url, task_id))
-            .send()
-            .await?;
-    } else {
-        client
-            .post(format!("{}/v1/tasks/{}/status", args.api_url, task_id))
-            .json(&UpdateStatusRequest {
-                status: "failed".into(),
-            })
-            .send()
-            .await?;
     }
+    client
+        .post(format!("{}/v1/tasks/{}/status", args.api_url, task_id))
+        .json(&UpdateStatusRequest {
+            status: final_status.into(),
+            worker_id: worker_id.to_string(),
+        })
+        .send()
+        .await?;
 
     Ok(true)
 }